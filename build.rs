@@ -0,0 +1,95 @@
+// 构建脚本：记录 /__version 返回的构建信息（git 提交、构建时间与启用的 feature）；
+// embed feature 从 SONICWAVE_EMBED_DIR（绝对路径或相对 Cargo.toml 所在目录）读取要内嵌的目录，
+// 未设置时内嵌一个空目录，保证 --all-features 也能编译；
+// 同时由 src/cli.rs 的命令定义生成 man 手册，写入 SONICWAVE_MAN_DIR（未设置时为 OUT_DIR/man）
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// 构建脚本只用到命令定义，字段由主程序读取
+#[allow(dead_code)]
+#[path = "src/cli.rs"]
+mod cli;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=SONICWAVE_EMBED_DIR");
+    build_info();
+    man_pages();
+    if env::var_os("CARGO_FEATURE_EMBED").is_none() {
+        return;
+    }
+    let dir = match env::var("SONICWAVE_EMBED_DIR") {
+        Ok(dir) if !dir.is_empty() => {
+            let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+            manifest_dir.join(dir)
+        }
+        _ => {
+            let dir = PathBuf::from(env::var("OUT_DIR").unwrap()).join("embed-empty");
+            std::fs::create_dir_all(&dir).expect("failed to create empty embed directory");
+            println!("cargo:warning=SONICWAVE_EMBED_DIR is not set, embedding an empty site");
+            dir
+        }
+    };
+    // 目录中的文件增删改后重新编译
+    println!("cargo:rerun-if-changed={}", dir.display());
+    println!("cargo:rustc-env=SONICWAVE_EMBED_DIR={}", dir.display());
+}
+
+fn build_info() {
+    // 没有 .git 的构建环境（如 Docker）可以通过 SONICWAVE_GIT_HASH 传入
+    println!("cargo:rerun-if-env-changed=SONICWAVE_GIT_HASH");
+    let git_hash = match env::var("SONICWAVE_GIT_HASH") {
+        Ok(hash) if !hash.is_empty() => hash,
+        _ => Command::new("git")
+            .args(["rev-parse", "--short=12", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|hash| hash.trim().to_string())
+            .unwrap_or_else(|| "unknown".to_string()),
+    };
+    println!("cargo:rustc-env=SONICWAVE_GIT_HASH={}", git_hash);
+    // 切换分支或提交后重新运行
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    if let Ok(head) = std::fs::read_to_string(".git/HEAD") {
+        if let Some(reference) = head.trim().strip_prefix("ref: ") {
+            println!("cargo:rerun-if-changed=.git/{}", reference);
+        }
+    }
+
+    // 可重现构建时使用 SOURCE_DATE_EPOCH
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    let epoch = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+    println!("cargo:rustc-env=SONICWAVE_BUILD_EPOCH={}", epoch);
+
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| key.strip_prefix("CARGO_FEATURE_").map(str::to_string))
+        .map(|name| name.to_lowercase().replace('_', "-"))
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=SONICWAVE_FEATURES={}", features.join(","));
+}
+
+// sonic-wave.1 与每个子命令的 sonic-wave-<子命令>.1；#[cfg(unix)] / #[cfg(windows)] 的选项按构建主机的平台取舍
+fn man_pages() {
+    use clap::CommandFactory;
+
+    println!("cargo:rerun-if-changed=src/cli.rs");
+    println!("cargo:rerun-if-env-changed=SONICWAVE_MAN_DIR");
+    let dir = match env::var("SONICWAVE_MAN_DIR") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var("OUT_DIR").unwrap()).join("man"),
+    };
+    std::fs::create_dir_all(&dir).expect("failed to create man page directory");
+    clap_mangen::generate_to(cli::Cli::command(), &dir).expect("failed to write man pages");
+}
@@ -0,0 +1,157 @@
+// 访问日志中间件；[access_log_rules] 可以不记录健康检查等路径、对成功的请求抽样，错误与慢请求总是记录
+use crate::chaos;
+use crate::forwarded::ClientInfo;
+use crate::geoip::Country;
+use crate::glob::PathPattern;
+use crate::request_id::RequestId;
+use crate::server::ClientAddr;
+use crate::tls::TlsInfo;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::Response;
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct AccessLogRulesConfig {
+    // 不记录的路径（模式规则与 deny 相同），如健康检查与指标端点
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // 其余请求中成功（状态码低于 error_status）的记录比例，0.0 ~ 1.0
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    // 状态码不低于该值的请求总是记录，不受 exclude 与抽样影响
+    #[serde(default = "default_error_status")]
+    pub error_status: u16,
+    // 耗时超过该毫秒数的请求总是记录；0 表示不按耗时判断
+    #[serde(default = "default_slow_ms")]
+    pub slow_ms: u64,
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+fn default_error_status() -> u16 {
+    400
+}
+
+fn default_slow_ms() -> u64 {
+    1000
+}
+
+pub struct Rules {
+    exclude: Vec<PathPattern>,
+    sample_rate: f64,
+    error_status: u16,
+    slow: Option<Duration>,
+}
+
+impl Rules {
+    // 未配置 [access_log_rules] 时记录所有请求
+    pub fn new(config: Option<&AccessLogRulesConfig>) -> Result<Self, String> {
+        let Some(config) = config else {
+            return Ok(Rules {
+                exclude: Vec::new(),
+                sample_rate: 1.0,
+                error_status: 0,
+                slow: None,
+            });
+        };
+        if !(0.0..=1.0).contains(&config.sample_rate) {
+            return Err("[access_log_rules] sample_rate must be between 0.0 and 1.0".to_string());
+        }
+        if StatusCode::from_u16(config.error_status).is_err() {
+            return Err(format!(
+                "[access_log_rules] invalid error_status {}",
+                config.error_status
+            ));
+        }
+        let exclude = config
+            .exclude
+            .iter()
+            .map(|pattern| PathPattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Rules {
+            exclude,
+            sample_rate: config.sample_rate,
+            error_status: config.error_status,
+            slow: (config.slow_ms > 0).then(|| Duration::from_millis(config.slow_ms)),
+        })
+    }
+
+    fn logged(&self, path: &str, status: StatusCode, elapsed: Duration) -> bool {
+        if status.as_u16() >= self.error_status || self.slow.is_some_and(|slow| elapsed >= slow) {
+            return true;
+        }
+        if !self.exclude.is_empty() {
+            let decoded = percent_decode_str(path).decode_utf8_lossy();
+            if self.exclude.iter().any(|pattern| pattern.matches(&decoded)) {
+                return false;
+            }
+        }
+        self.sample_rate >= 1.0 || chaos::random() < self.sample_rate
+    }
+}
+
+pub async fn log_request(State(rules): State<Arc<Rules>>, req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    // 经可信代理还原的客户端 IP 优先，其次是连接对端地址
+    let info = req.extensions().get::<ClientInfo>().cloned();
+    let client = info
+        .as_ref()
+        .and_then(|info| info.ip)
+        .map(|ip| ip.to_string())
+        .or_else(|| {
+            req.extensions()
+                .get::<ClientAddr>()
+                .map(|addr| addr.0.to_string())
+        })
+        .unwrap_or_else(|| "-".to_string());
+    let scheme = info.map(|info| info.scheme).unwrap_or("http");
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    let version = req.version();
+    // 启用 request_id 时在行尾附加请求 ID，便于按 ID 检索
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| format!(" {}", id.0))
+        .unwrap_or_default();
+    // 出示了客户端证书时在客户端地址后附加证书主题
+    let subject = req
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(|tls| tls.client_subject.as_ref())
+        .map(|subject| format!(" \"{}\"", subject))
+        .unwrap_or_default();
+
+    let response = next.run(req).await;
+    let elapsed = started.elapsed();
+    if !rules.logged(uri.path(), response.status(), elapsed) {
+        return response;
+    }
+
+    // 启用 [geoip] 时在客户端地址后附加国家代码
+    let client = match response.extensions().get::<Country>() {
+        Some(country) => format!("{} {}{}", client, country.0, subject),
+        None => format!("{}{}", client, subject),
+    };
+    info!(
+        "{} {} \"{} {} {:?}\" {} {:.1}ms{}",
+        client,
+        scheme,
+        method,
+        uri,
+        version,
+        response.status().as_u16(),
+        elapsed.as_secs_f64() * 1000.0,
+        request_id
+    );
+    response
+}
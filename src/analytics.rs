@@ -0,0 +1,535 @@
+// 下载统计（[analytics]）：在内存中按路径统计成功响应的次数、发送的字节数与近似的独立客户端数，
+// 通过 Bearer token 保护的 JSON 端点与状态页查看，不需要在页面中嵌入第三方统计脚本。
+// 独立客户端用 HyperLogLog 估算，只保存加盐哈希，不保存客户端 IP；可定期保存到文件，重启后继续累计
+use crate::forwarded::ClientInfo;
+use crate::glob::PathPattern;
+use crate::listener::PeerAddr;
+use crate::server::ClientAddr;
+use crate::upload;
+use axum::body::{Body, Bytes};
+use axum::extract::{Query, Request, State};
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+// 每个路径的 HyperLogLog 寄存器数，误差约 13%
+const PATH_REGISTERS: usize = 64;
+// 全站的寄存器数，误差约 3%
+const TOTAL_REGISTERS: usize = 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct AnalyticsConfig {
+    // JSON 端点路径
+    #[serde(default = "default_path")]
+    pub path: String,
+    // JSON 端点的 Bearer token，未设置时只在状态页显示
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub token: Option<String>,
+    // 只统计匹配的路径（模式规则与 deny 相同），如 "/downloads/**"；为空时统计所有路径
+    #[serde(default)]
+    pub include: Vec<String>,
+    // 最多记录的不同路径数，超出时所有计数减半并丢弃归零的路径
+    #[serde(default = "default_max_paths")]
+    pub max_paths: usize,
+    // 保存统计的 JSON 文件，启动时读取；未设置时只保存在内存中
+    #[serde(default)]
+    pub state_file: Option<String>,
+    #[serde(default = "default_save_interval")]
+    pub save_interval_secs: u64,
+}
+
+fn default_path() -> String {
+    "/__analytics".to_string()
+}
+
+fn default_max_paths() -> usize {
+    10_000
+}
+
+fn default_save_interval() -> u64 {
+    60
+}
+
+// HyperLogLog：寄存器保存哈希前缀之后的最长前导零个数
+#[derive(Clone)]
+struct Sketch(Vec<u8>);
+
+impl Sketch {
+    fn new(registers: usize) -> Self {
+        Sketch(vec![0; registers])
+    }
+
+    fn insert(&mut self, hash: u64) {
+        let bits = self.0.len().trailing_zeros();
+        let index = (hash >> (64 - bits)) as usize;
+        let rank = ((hash << bits) | (1 << (bits - 1))).leading_zeros() as u8 + 1;
+        if rank > self.0[index] {
+            self.0[index] = rank;
+        }
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = self.0.len() as f64;
+        let alpha = match self.0.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self.0.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.0.iter().filter(|&&r| r == 0).count();
+        // 基数较小时改用线性计数
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+
+    fn decode(encoded: &str, registers: usize) -> Option<Self> {
+        let bytes = STANDARD.decode(encoded).ok()?;
+        (bytes.len() == registers).then_some(Sketch(bytes))
+    }
+}
+
+#[derive(Clone)]
+struct Counter {
+    hits: u64,
+    bytes: u64,
+    clients: Sketch,
+}
+
+impl Counter {
+    fn new(registers: usize) -> Self {
+        Counter {
+            hits: 0,
+            bytes: 0,
+            clients: Sketch::new(registers),
+        }
+    }
+}
+
+struct Counters {
+    since: SystemTime,
+    total: Counter,
+    paths: HashMap<String, Counter>,
+}
+
+// state_file 的格式；寄存器为 base64
+#[derive(Serialize, Deserialize)]
+struct SavedCounter {
+    hits: u64,
+    bytes: u64,
+    clients: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Saved {
+    salt: String,
+    since: u64,
+    total: SavedCounter,
+    paths: HashMap<String, SavedCounter>,
+}
+
+impl SavedCounter {
+    fn from(counter: &Counter) -> Self {
+        SavedCounter {
+            hits: counter.hits,
+            bytes: counter.bytes,
+            clients: STANDARD.encode(&counter.clients.0),
+        }
+    }
+
+    fn restore(&self, registers: usize) -> Option<Counter> {
+        Some(Counter {
+            hits: self.hits,
+            bytes: self.bytes,
+            clients: Sketch::decode(&self.clients, registers)?,
+        })
+    }
+}
+
+// 一条统计结果：JSON 端点与状态页共用
+pub struct Entry {
+    pub path: String,
+    pub hits: u64,
+    pub bytes: u64,
+    pub clients: u64,
+}
+
+impl Entry {
+    fn of(path: &str, counter: &Counter) -> Self {
+        Entry {
+            path: path.to_string(),
+            hits: counter.hits,
+            bytes: counter.bytes,
+            clients: counter.clients.estimate(),
+        }
+    }
+
+    fn json(&self) -> serde_json::Value {
+        json!({
+            "path": self.path,
+            "hits": self.hits,
+            "bytes": self.bytes,
+            "clients": self.clients,
+        })
+    }
+}
+
+pub struct Analytics {
+    path: String,
+    token: Option<String>,
+    include: Vec<PathPattern>,
+    // 统计端点、状态页与就绪检查不计入
+    exempt: Vec<String>,
+    max_paths: usize,
+    state_file: Option<PathBuf>,
+    // 客户端 IP 加盐后再哈希，盐与统计一起保存
+    salt: Vec<u8>,
+    counters: Mutex<Counters>,
+}
+
+impl Analytics {
+    pub fn new(config: &AnalyticsConfig, exempt: &[&str]) -> Result<Self, String> {
+        if !config.path.starts_with('/') {
+            return Err("[analytics] path must start with /".to_string());
+        }
+        if config.token.as_deref() == Some("") {
+            return Err("[analytics] token must not be empty".to_string());
+        }
+        if config.max_paths == 0 {
+            return Err("[analytics] max_paths must be at least 1".to_string());
+        }
+        // 多个工作进程写同一个文件会互相覆盖
+        if config.state_file.is_some() && crate::supervisor::worker_id().is_some() {
+            return Err("[analytics] state_file is not supported in supervisor mode".to_string());
+        }
+        let include = config
+            .include
+            .iter()
+            .map(|pattern| PathPattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let state_file = config.state_file.as_ref().map(PathBuf::from);
+        let restored = match &state_file {
+            Some(path) if path.exists() => {
+                let content = std::fs::read_to_string(path)
+                    .map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+                let saved = serde_json::from_str::<Saved>(&content)
+                    .map_err(|e| format!("invalid analytics state {}: {}", path.display(), e))?;
+                Some(
+                    restore(saved)
+                        .ok_or_else(|| format!("invalid analytics state {}", path.display()))?,
+                )
+            }
+            _ => None,
+        };
+        let (salt, counters) = match restored {
+            Some(restored) => restored,
+            None => (
+                crate::login::random_bytes(16)?,
+                Counters {
+                    since: SystemTime::now(),
+                    total: Counter::new(TOTAL_REGISTERS),
+                    paths: HashMap::new(),
+                },
+            ),
+        };
+        Ok(Analytics {
+            path: config.path.clone(),
+            token: config.token.clone(),
+            include,
+            exempt: std::iter::once(config.path.as_str())
+                .chain(exempt.iter().copied())
+                .map(str::to_string)
+                .collect(),
+            max_paths: config.max_paths,
+            state_file,
+            salt,
+            counters: Mutex::new(counters),
+        })
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.token.as_ref().map(|_| self.path.as_str())
+    }
+
+    // 定期写入 state_file；先写临时文件再改名，中途退出不会留下损坏的文件
+    pub fn spawn_saver(self: &Arc<Self>, interval: Duration) {
+        if self.state_file.is_none() {
+            return;
+        }
+        let analytics = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval.max(Duration::from_secs(1)));
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                let analytics = analytics.clone();
+                let result = tokio::task::spawn_blocking(move || analytics.save()).await;
+                if let Ok(Err(e)) = result {
+                    warn!("Failed to save analytics: {}", e);
+                }
+            }
+        });
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.state_file else {
+            return Ok(());
+        };
+        let saved = {
+            let counters = self.counters.lock().unwrap();
+            Saved {
+                salt: STANDARD.encode(&self.salt),
+                since: counters
+                    .since
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0),
+                total: SavedCounter::from(&counters.total),
+                paths: counters
+                    .paths
+                    .iter()
+                    .map(|(path, counter)| (path.clone(), SavedCounter::from(counter)))
+                    .collect(),
+            }
+        };
+        let data = serde_json::to_vec(&saved).map_err(|e| e.to_string())?;
+        let temp = path.with_extension("tmp");
+        std::fs::write(&temp, data)
+            .and_then(|_| std::fs::rename(&temp, path))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn counted(&self, path: &str) -> bool {
+        if self.exempt.iter().any(|exempt| exempt == path) {
+            return false;
+        }
+        if self.include.is_empty() {
+            return true;
+        }
+        let decoded = percent_decode_str(path).decode_utf8_lossy();
+        self.include.iter().any(|pattern| pattern.matches(&decoded))
+    }
+
+    fn client_hash(&self, ip: IpAddr) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.salt);
+        hasher.update(ip.to_string().as_bytes());
+        let digest = hasher.finalize();
+        u64::from_be_bytes(digest[..8].try_into().expect("digest is 32 bytes"))
+    }
+
+    fn hit(&self, path: &str, client: Option<u64>) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.total.hits += 1;
+        if let Some(hash) = client {
+            counters.total.clients.insert(hash);
+        }
+        if !counters.paths.contains_key(path) && counters.paths.len() >= self.max_paths {
+            counters.paths.retain(|_, counter| {
+                counter.hits /= 2;
+                counter.bytes /= 2;
+                counter.hits > 0
+            });
+        }
+        let counter = counters
+            .paths
+            .entry(path.to_string())
+            .or_insert_with(|| Counter::new(PATH_REGISTERS));
+        counter.hits += 1;
+        if let Some(hash) = client {
+            counter.clients.insert(hash);
+        }
+    }
+
+    fn sent(&self, path: &str, bytes: u64) {
+        let mut counters = self.counters.lock().unwrap();
+        counters.total.bytes += bytes;
+        if let Some(counter) = counters.paths.get_mut(path) {
+            counter.bytes += bytes;
+        }
+    }
+
+    // 全站合计、按 sort 排序的前 limit 条路径、统计起始时间与记录的路径数
+    pub fn top(&self, sort: Sort, limit: usize) -> (Entry, Vec<Entry>, SystemTime, usize) {
+        let counters = self.counters.lock().unwrap();
+        let mut entries: Vec<Entry> = counters
+            .paths
+            .iter()
+            .map(|(path, counter)| Entry::of(path, counter))
+            .collect();
+        let key = |entry: &Entry| match sort {
+            Sort::Hits => entry.hits,
+            Sort::Bytes => entry.bytes,
+            Sort::Clients => entry.clients,
+        };
+        entries.sort_by(|a, b| key(b).cmp(&key(a)).then_with(|| a.path.cmp(&b.path)));
+        entries.truncate(limit);
+        (
+            Entry::of("", &counters.total),
+            entries,
+            counters.since,
+            counters.paths.len(),
+        )
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        upload::bearer_authorized(headers, self.token.as_deref())
+    }
+}
+
+fn restore(saved: Saved) -> Option<(Vec<u8>, Counters)> {
+    let salt = STANDARD.decode(&saved.salt).ok()?;
+    let mut paths = HashMap::new();
+    for (path, counter) in &saved.paths {
+        paths.insert(path.clone(), counter.restore(PATH_REGISTERS)?);
+    }
+    Some((
+        salt,
+        Counters {
+            since: UNIX_EPOCH + Duration::from_secs(saved.since),
+            total: saved.total.restore(TOTAL_REGISTERS)?,
+            paths,
+        },
+    ))
+}
+
+#[derive(Clone, Copy, Deserialize, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    #[default]
+    Hits,
+    Bytes,
+    Clients,
+}
+
+// 只统计 GET 的成功响应与 304；字节数在响应体发送完毕（或连接中断）时累加
+pub async fn track(State(analytics): State<Arc<Analytics>>, req: Request, next: Next) -> Response {
+    let path = req.uri().path().to_string();
+    if req.method() != Method::GET || !analytics.counted(&path) {
+        return next.run(req).await;
+    }
+    let ip = req
+        .extensions()
+        .get::<ClientInfo>()
+        .and_then(|info| info.ip)
+        .or_else(|| match req.extensions().get::<ClientAddr>() {
+            Some(ClientAddr(PeerAddr::Tcp(addr))) => Some(addr.ip()),
+            _ => None,
+        });
+    let response = next.run(req).await;
+    let status = response.status();
+    if !status.is_success() && status != StatusCode::NOT_MODIFIED {
+        return response;
+    }
+    analytics.hit(&path, ip.map(|ip| analytics.client_hash(ip)));
+    response.map(|body| {
+        Body::new(Counted {
+            inner: body,
+            analytics,
+            path,
+            bytes: 0,
+        })
+    })
+}
+
+struct Counted {
+    inner: Body,
+    analytics: Arc<Analytics>,
+    path: String,
+    bytes: u64,
+}
+
+impl Drop for Counted {
+    fn drop(&mut self) {
+        if self.bytes > 0 {
+            self.analytics.sent(&self.path, self.bytes);
+        }
+    }
+}
+
+impl HttpBody for Counted {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                self.bytes += data.len() as u64;
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReportQuery {
+    #[serde(default)]
+    sort: Sort,
+    #[serde(default = "default_limit")]
+    limit: usize,
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+// GET ?sort=hits|bytes|clients&limit=100
+pub async fn report(
+    State(analytics): State<Arc<Analytics>>,
+    Query(query): Query<ReportQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if !analytics.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    let (total, paths, since, tracked) = analytics.top(query.sort, query.limit);
+    let mut total = total.json();
+    if let Some(total) = total.as_object_mut() {
+        total.remove("path");
+    }
+    let mut response = Json(json!({
+        "since": crate::error_pages::rfc3339(since),
+        "total": total,
+        "tracked_paths": tracked,
+        "paths": paths.iter().map(Entry::json).collect::<Vec<_>>(),
+    }))
+    .into_response();
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
@@ -0,0 +1,592 @@
+// 按配置组装站点：就绪检查、反向代理、挂载点、主目录（虚拟主机、写入模式）与播客等路由，
+// 再套上全局中间件；命令行服务与嵌入其他应用的 Builder 共用
+use crate::analytics::{self, Analytics};
+use crate::audio_meta::AudioMeta;
+use crate::basic_auth::{self, BasicAuth};
+use crate::cache::FileCache;
+use crate::canary::{self, Canary};
+use crate::config::Config;
+use crate::early_hints::EarlyHints;
+use crate::favicon::{self, Favicon};
+use crate::fingerprint::Fingerprint;
+use crate::git_site::{self, GitSite};
+use crate::hls::Hls;
+use crate::i18n::I18n;
+use crate::images::Images;
+use crate::listener::PeerAddr;
+use crate::live_reload::{self, Change, ChangeHub};
+use crate::maintenance::{self, Maintenance};
+use crate::markdown::Markdown;
+use crate::mirror::{self, Mirror};
+use crate::path_match::PathMatch;
+use crate::podcast::{self, Podcast};
+use crate::releases::{self, Releases};
+use crate::reports::{self, Reports};
+use crate::runtime_env::RuntimeEnv;
+use crate::s3::{Backend, S3Store};
+use crate::search::{self, Search};
+use crate::server::ClientAddr;
+use crate::service_worker::{self, ServiceWorker};
+use crate::shutdown::{self, Readiness};
+use crate::site::{self, SiteOptions};
+use crate::sitemap::{self, Robots, Sitemap};
+use crate::sri::{self, Sri};
+use crate::ssi::Ssi;
+use crate::status::{self, Status};
+use crate::templates::Templates;
+use crate::tenants::{self, Tenants};
+use crate::upload::{self, Uploader};
+use crate::uring::{IoBackend, UringReader};
+use crate::waveform::Waveform;
+use crate::{archive, embed, manifest, metrics, middleware, mime, proxy, version, vhost};
+use axum::body::Body;
+use axum::http::{header, Request};
+use axum::routing::{get, post, MethodRouter};
+use axum::Router;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+// changes 为 --watch 模式下的文件变化通知，存在时添加 live reload 路由并在变化时清空缓存
+pub fn build(
+    config: &Config,
+    readiness: Readiness,
+    changes: Option<Arc<ChangeHub>>,
+) -> Result<Router, String> {
+    let static_dir = config.site_dir();
+    // 顶层 Basic 认证保护整个站点：站点目录之外的反向代理、指标、版本与生成的端点同样需要认证；
+    // 就绪检查与使用自己 token 的管理端点除外（[auth.oidc] 由全局中间件处理）
+    let auth = match &config.auth {
+        Some(auth) if auth.oidc.is_none() => BasicAuth::new(auth)?.map(Arc::new),
+        _ => None,
+    };
+    let layer = |auth: &Arc<BasicAuth>| {
+        axum::middleware::from_fn_with_state(auth.clone(), basic_auth::check)
+    };
+    let protect = |route: MethodRouter| match &auth {
+        Some(auth) => route.layer(layer(auth)),
+        None => route,
+    };
+    let proxy_routes = match &auth {
+        Some(auth) => proxy::routes(&config.proxy)?.layer(layer(auth)),
+        None => proxy::routes(&config.proxy)?,
+    };
+    for rule in &config.proxy {
+        info!("Proxy: {} -> {}", rule.prefix, rule.upstream.join(", "));
+    }
+
+    // 构建路由：就绪检查、反向代理、挂载点，其余请求由主目录处理
+    let mut app = Router::new()
+        .route(
+            &config.shutdown.readiness_path,
+            get(shutdown::readiness_handler).with_state(readiness),
+        )
+        .merge(proxy_routes);
+    if config.metrics_endpoint {
+        app = app.route(metrics::METRICS_PATH, protect(get(metrics::export)));
+    }
+    if config.version_endpoint {
+        app = app.route(version::VERSION_PATH, protect(get(version::handler)));
+    }
+    let analytics = match &config.analytics {
+        Some(analytics_config) => {
+            let mut exempt = vec![config.shutdown.readiness_path.as_str()];
+            exempt.extend(config.status.as_ref().map(|status| status.path.as_str()));
+            let analytics = Arc::new(Analytics::new(analytics_config, &exempt)?);
+            analytics.spawn_saver(Duration::from_secs(analytics_config.save_interval_secs));
+            Some(analytics)
+        }
+        None => None,
+    };
+    if let Some(analytics) = &analytics {
+        if let Some(path) = analytics.path() {
+            info!("Analytics endpoint: {}", path);
+            app = app.route(path, get(analytics::report).with_state(analytics.clone()));
+        }
+    }
+    let status = match &config.status {
+        Some(config) => Some(Arc::new(Status::new(config, analytics.clone())?)),
+        None => None,
+    };
+    if let Some(status) = &status {
+        info!("Status page: {}", status.path());
+        app = app.route(status.path(), get(status::page).with_state(status.clone()));
+    }
+    // 切换端点只在设置了 token 时提供
+    let maintenance = match &config.maintenance {
+        Some(maintenance) => Some(Arc::new(Maintenance::new(
+            maintenance,
+            &config.shutdown.readiness_path,
+        )?)),
+        None => None,
+    };
+    if let Some(maintenance) = &maintenance {
+        if maintenance.enabled() {
+            warn!("Maintenance mode enabled, serving 503 to all requests");
+        }
+        if let Some(path) = maintenance.path() {
+            info!("Maintenance endpoint: {}", path);
+            app = app.route(
+                path,
+                get(maintenance::status)
+                    .post(maintenance::toggle)
+                    .with_state(maintenance.clone()),
+            );
+        }
+    }
+    if let Some(reports) = &config.reports {
+        let reports = Arc::new(Reports::new(reports)?);
+        info!("Report collector: {}", reports.path());
+        app = app.route(
+            reports.path(),
+            post(reports::collect).with_state(reports.clone()),
+        );
+    }
+    let mut defaults = SiteOptions::new(
+        config.cache_control.clone(),
+        config.html_cache_control.clone(),
+    );
+    defaults.cache_rules = config.cache_rules.clone();
+    defaults.allow_dotfiles = config.allow_dotfiles;
+    defaults.deny = config.deny.clone();
+    defaults.follow_symlinks = config.follow_symlinks;
+    defaults.download = config.download.clone();
+    defaults.mime = Arc::new(mime::MimeTable::new(
+        &config.mime,
+        &config.charset,
+        &config.charset_by_extension,
+    )?);
+    defaults.index_files = config.index_files.clone();
+    defaults.error_pages = config.error_pages.clone();
+    defaults.clean_urls = config.clean_urls;
+    defaults.clean_urls_redirect = config.clean_urls_redirect;
+    defaults.trailing_slash = config.trailing_slash;
+    defaults.mmap_min_size = config.mmap_min_size;
+    defaults.preindex = config.preindex;
+    defaults.negative_cache_secs = config.negative_cache_secs;
+    defaults.precompressed = config.precompressed;
+    defaults.image_variants = config.image_variants;
+    defaults.webdav = config.webdav;
+    defaults.directory_listing = config.directory_listing;
+    defaults.zip_download = config.zip_download.clone().map(Arc::new);
+    defaults.dir_overrides = config.dir_overrides.clone().map(Arc::new);
+    defaults.path_match = config.path_matching.as_ref().and_then(PathMatch::new);
+    defaults.security_headers = config.security_headers.clone();
+    // [permissions_policy] 生成的头作为顶层值，[[mount]] / [[vhost]] 仍可覆盖
+    if let Some(policy) = &config.permissions_policy {
+        if config.security_headers.permissions_policy.is_some() {
+            return Err(
+                "set either [permissions_policy] or [security_headers] permissions_policy, not both"
+                    .to_string(),
+            );
+        }
+        defaults.security_headers.permissions_policy = Some(policy.header()?);
+    }
+    // [auth.oidc] 由全局中间件处理，站点不再使用 Basic 认证
+    defaults.auth = match &config.auth {
+        Some(auth) if auth.oidc.is_some() => {
+            if !auth.password.is_empty() {
+                return Err("set either [auth] password or [auth.oidc], not both".to_string());
+            }
+            None
+        }
+        auth => auth.clone(),
+    };
+    defaults.playlist = config.playlist.clone().map(Arc::new);
+    if let Some(markdown) = &config.markdown {
+        defaults.markdown = Some(Arc::new(Markdown::new(markdown)?));
+    }
+    defaults.ssi = config.ssi.as_ref().map(|ssi| Arc::new(Ssi::new(ssi)));
+    if let Some(runtime_env) = &config.runtime_env {
+        defaults.runtime_env = Some(Arc::new(RuntimeEnv::new(runtime_env)?));
+    }
+    if let Some(templates) = &config.templates {
+        defaults.templates = Some(Arc::new(Templates::new(templates)?));
+    }
+    if let Some(i18n) = &config.i18n {
+        defaults.i18n = Some(Arc::new(I18n::new(i18n)?));
+    }
+    if let Some(images) = &config.images {
+        defaults.images = Some(Arc::new(Images::new(images)?));
+    }
+    defaults.audio_meta = config
+        .audio_meta
+        .as_ref()
+        .map(|audio_meta| Arc::new(AudioMeta::new(audio_meta)));
+    if let Some(waveform) = &config.waveform {
+        defaults.waveform = Some(Arc::new(Waveform::new(waveform)?));
+    }
+    if let Some(hls) = &config.hls {
+        defaults.hls = Some(Arc::new(Hls::new(hls)?));
+    }
+    if let Some(fingerprint) = &config.fingerprint {
+        defaults.fingerprint = Some(Arc::new(Fingerprint::new(fingerprint)?));
+    }
+    if let Some(hints) = &config.early_hints {
+        defaults.early_hints = Some(Arc::new(EarlyHints::new(hints)?));
+    }
+    let sri = config.sri.as_ref().map(Sri::new).transpose()?.map(Arc::new);
+    defaults.sri = sri.clone().filter(|sri| sri.injects());
+    if config.io_backend == IoBackend::Uring {
+        match UringReader::new() {
+            Ok(reader) => {
+                info!("File I/O backend: io_uring");
+                defaults.uring = Some(Arc::new(reader));
+            }
+            Err(e) => warn!("io_uring unavailable ({}), using standard file I/O", e),
+        }
+    }
+    if let Some(cache) = &config.cache {
+        info!(
+            "File cache: {} bytes, {} bytes per file, ttl {}s",
+            cache.max_bytes, cache.max_file_size, cache.ttl_secs
+        );
+        defaults.cache = Some(Arc::new(FileCache::new(cache.clone())));
+    }
+    // --watch：文件变化时通知浏览器刷新，并使缓存失效
+    if let Some(changes) = &changes {
+        app = app
+            .route(
+                live_reload::EVENTS_PATH,
+                get(live_reload::events).with_state(changes.clone()),
+            )
+            .route(live_reload::SCRIPT_PATH, get(live_reload::script));
+        if let Some(cache) = defaults.cache.clone() {
+            let mut rx = changes.subscribe();
+            tokio::spawn(async move {
+                while !matches!(
+                    rx.recv().await,
+                    Ok(Change::Shutdown) | Err(RecvError::Closed)
+                ) {
+                    cache.clear();
+                }
+            });
+        }
+        defaults.changes = Some(changes.clone());
+    }
+    for mount in &config.mount {
+        app = defaults
+            .with_overrides(&mount.site)
+            .and_then(|options| site::router(&mount.dir, options))
+            .and_then(|site| site::mount(app.clone(), &mount.prefix, site))
+            .map_err(|e| format!("Invalid mount {}: {}", mount.prefix, e))?;
+        info!("Mount: {} -> {}", mount.prefix, mount.dir);
+    }
+
+    if config.releases.is_some() && (config.embedded || config.backend != Backend::Fs) {
+        return Err("[releases] requires static_dir to be a directory on disk".to_string());
+    }
+    // 主目录；配置了 [[vhost]] 时按 Host 头分发，未匹配的主机使用默认站点
+    let root = if config.backend == Backend::S3 {
+        match &config.s3 {
+            Some(s3) => S3Store::new(s3).and_then(|store| {
+                info!("Serving objects from {}", store.describe());
+                site::s3_router(Arc::new(store), defaults.clone())
+            }),
+            None => Err("backend = \"s3\" requires an [s3] table".to_string()),
+        }
+    } else if config.backend == Backend::Git {
+        match &config.git {
+            Some(git) => {
+                let site = Arc::new(GitSite::new(git, defaults.clone())?);
+                if let Some(path) = site.deploy_path() {
+                    info!("Deploy endpoint: {}", path);
+                    app = app.route(
+                        path,
+                        get(git_site::status)
+                            .post(git_site::deploy)
+                            .with_state(site.clone()),
+                    );
+                }
+                Ok(site.router())
+            }
+            None => Err("backend = \"git\" requires a [git] table".to_string()),
+        }
+    } else if config.embedded {
+        match embed::load() {
+            Ok(files) => {
+                info!(
+                    "Serving {} embedded files ({} bytes)",
+                    files.len(),
+                    files.total_bytes()
+                );
+                site::memory_router(Arc::new(files), defaults.clone())
+            }
+            Err(e) => Err(format!("cannot serve embedded assets: {}", e)),
+        }
+    } else if let Some(releases) = &config.releases {
+        if archive::is_archive(&static_dir) {
+            return Err("[releases] requires static_dir to be a directory on disk".to_string());
+        }
+        let releases = Arc::new(Releases::new(releases, &static_dir, defaults.clone())?);
+        // 写入模式与镜像按 static_dir 写入，只有符号链接才会随切换指向新版本
+        if !releases.symlinked() && (config.upload.is_some() || config.mirror.is_some()) {
+            return Err(
+                "[releases] with [upload] or [mirror] requires static_dir to be a symlink"
+                    .to_string(),
+            );
+        }
+        info!("Release: {}", releases.describe());
+        if let Some(path) = releases.path() {
+            info!("Release endpoint: {}", path);
+            app = app.route(
+                path,
+                get(releases::status)
+                    .post(releases::deploy)
+                    .with_state(releases.clone()),
+            );
+        }
+        Ok(releases.router())
+    } else {
+        site::router(&static_dir, defaults.clone())
+    }?;
+    // 写入模式、镜像、播客 feed 与资源清单只支持磁盘上的主目录
+    let on_disk =
+        !config.embedded && config.backend == Backend::Fs && !archive::is_archive(&static_dir);
+    // 只镜像默认站点，包在虚拟主机分发之内
+    let root = match &config.mirror {
+        Some(_) if !on_disk => {
+            return Err("[mirror] requires static_dir to be a directory on disk".to_string());
+        }
+        Some(_) if config.preindex || config.negative_cache_secs.is_some() => {
+            return Err(
+                "[mirror] cannot be combined with preindex or negative_cache_secs: mirrored files would not be found"
+                    .to_string(),
+            );
+        }
+        Some(mirror) => {
+            let mirror = Arc::new(Mirror::new(mirror, Path::new(&static_dir))?);
+            info!("Mirror: {} -> {}", mirror.origin(), static_dir);
+            if let Some(path) = mirror.purge_path() {
+                app = app.route(path, post(mirror::purge).with_state(mirror.clone()));
+            }
+            root.layer(axum::middleware::from_fn_with_state(mirror, mirror::handle))
+        }
+        None => root,
+    };
+    // 只分流默认站点，包在镜像之外，分到金丝雀目录的请求不会从源站取回文件
+    let root = match &config.canary {
+        Some(canary) => {
+            let site = site::router(&canary.dir, defaults.clone())?;
+            let canary = Arc::new(Canary::new(canary, site)?);
+            info!("Canary: {}", canary.describe());
+            if let Some(path) = canary.path() {
+                info!("Canary endpoint: {}", path);
+                app = app.route(
+                    path,
+                    get(canary::status)
+                        .post(canary::set)
+                        .with_state(canary.clone()),
+                );
+            }
+            root.layer(axum::middleware::from_fn_with_state(canary, canary::split))
+        }
+        None => root,
+    };
+    let root = if config.vhost.is_empty() {
+        root
+    } else {
+        vhost::router(&config.vhost, &defaults, root)?
+    };
+    // 包在虚拟主机外面，子域名模式下租户的主机名先于 [[vhost]] 匹配
+    let root = match &config.tenants {
+        Some(tenants) => {
+            let tenants = Arc::new(Tenants::new(
+                tenants,
+                defaults.clone(),
+                config.allow_dotfiles,
+                &config.deny,
+                on_disk.then(|| Path::new(&static_dir)),
+                &config.mount,
+            )?);
+            info!("Tenants: {}", tenants.describe());
+            if let Some(path) = tenants.path() {
+                info!("Tenant endpoint: {}", path);
+                app = app.route(
+                    path,
+                    get(tenants::list)
+                        .post(tenants::provision)
+                        .delete(tenants::remove)
+                        .with_state(tenants.clone()),
+                );
+            }
+            root.layer(axum::middleware::from_fn_with_state(
+                tenants,
+                tenants::serve,
+            ))
+        }
+        None => root,
+    };
+    // 包在虚拟主机外面，按 Host 选中的站点中不存在 /favicon.ico 时兜底
+    let root = match &config.favicon {
+        Some(favicon) => {
+            let favicon = Favicon::new(favicon, root.clone())?;
+            root.layer(axum::middleware::from_fn_with_state(
+                Arc::new(favicon),
+                favicon::fallback,
+            ))
+        }
+        None => root,
+    };
+    let root = match &config.upload {
+        Some(_) if !on_disk => {
+            return Err("[upload] requires static_dir to be a directory on disk".to_string());
+        }
+        Some(upload) => {
+            let uploader = Uploader::new(
+                upload,
+                Path::new(&static_dir),
+                config.allow_dotfiles,
+                &config.deny,
+            )?;
+            info!(
+                "Uploads enabled under {:?} ({} bytes per file)",
+                uploader.prefixes(),
+                upload.max_size
+            );
+            root.layer(axum::middleware::from_fn_with_state(
+                Arc::new(uploader),
+                upload::handle,
+            ))
+        }
+        None => root,
+    };
+    // 以下端点的内容由站点文件生成，同样受顶层 Basic 认证保护
+    // 播客 feed 由音频文件的元数据生成
+    for feed in &config.podcast {
+        if !on_disk {
+            return Err("[[podcast]] requires static_dir to be a directory on disk".to_string());
+        }
+        let podcast = Podcast::new(feed, Path::new(&static_dir))?;
+        info!("Podcast feed: {} -> {}", feed.path, feed.dir);
+        app = app.route(
+            &feed.path,
+            protect(get(podcast::feed).with_state(Arc::new(podcast))),
+        );
+    }
+    if let Some(robots) = &config.robots {
+        let robots = Robots::new(robots, config.sitemap.as_ref())?;
+        app = app.route(
+            sitemap::ROBOTS_PATH,
+            get(sitemap::robots).with_state(Arc::new(robots)),
+        );
+    }
+    // sitemap 由目录中的 HTML 文件生成
+    if let Some(config_sitemap) = &config.sitemap {
+        if !on_disk {
+            return Err("[sitemap] requires static_dir to be a directory on disk".to_string());
+        }
+        let sitemap = Sitemap::new(config_sitemap, config, Path::new(&static_dir))?;
+        info!("Sitemap: {}", sitemap.path());
+        app = app.route(
+            &config_sitemap.path,
+            protect(get(sitemap::sitemap).with_state(Arc::new(sitemap))),
+        );
+    }
+    let manifest_path = config
+        .fingerprint
+        .as_ref()
+        .and_then(|f| f.manifest.as_ref());
+    if let (Some(path), Some(fingerprint)) = (manifest_path, &defaults.fingerprint) {
+        if !on_disk {
+            return Err(
+                "[fingerprint] manifest requires static_dir to be a directory on disk".to_string(),
+            );
+        }
+        if !fingerprint.has_hash_group() {
+            return Err(
+                "[fingerprint] manifest requires a capture group for the hash in pattern"
+                    .to_string(),
+            );
+        }
+        info!("Asset manifest: {}", path);
+        let route = manifest::ManifestRoute {
+            dir: PathBuf::from(&static_dir),
+            fingerprint: fingerprint.clone(),
+        };
+        app = app.route(
+            path,
+            protect(get(manifest::serve).with_state(Arc::new(route))),
+        );
+    }
+    if let (Some(path), Some(sri)) = (config.sri.as_ref().and_then(|s| s.manifest.as_ref()), sri) {
+        if !on_disk {
+            return Err("[sri] manifest requires static_dir to be a directory on disk".to_string());
+        }
+        info!("SRI manifest: {}", path);
+        let route = sri::SriRoute {
+            dir: PathBuf::from(&static_dir),
+            sri,
+        };
+        app = app.route(path, protect(get(sri::serve).with_state(Arc::new(route))));
+    }
+    if let Some(worker) = &config.service_worker {
+        if !on_disk {
+            return Err(
+                "[service_worker] requires static_dir to be a directory on disk".to_string(),
+            );
+        }
+        let worker = Arc::new(ServiceWorker::new(
+            worker,
+            Path::new(&static_dir),
+            &config.deny,
+        )?);
+        info!("Precache manifest: {}", worker.manifest_path());
+        app = app.route(
+            worker.manifest_path(),
+            protect(get(service_worker::manifest).with_state(worker.clone())),
+        );
+        if let Some(path) = worker.script_path() {
+            info!("Service worker: {}", path);
+            app = app.route(
+                path,
+                protect(get(service_worker::script).with_state(worker.clone())),
+            );
+        }
+    }
+    if let Some(search) = &config.search {
+        if !on_disk {
+            return Err("[search] requires static_dir to be a directory on disk".to_string());
+        }
+        let search = Arc::new(Search::new(
+            search,
+            Path::new(&static_dir),
+            &config.deny,
+            &config.index_files,
+        )?);
+        if let Some(changes) = &changes {
+            search.watch(changes);
+        }
+        info!("Search endpoint: {}", search.path());
+        // 搜索结果包含页面内容
+        app = app.route(
+            search.path(),
+            protect(get(search::handler).with_state(search.clone())),
+        );
+    }
+    middleware::apply(
+        app.fallback_service(root),
+        config,
+        changes.is_some(),
+        status,
+        maintenance,
+        analytics,
+    )
+}
+
+// 进程内直接交给 Router 处理的请求（bench、preload），模拟来自本机的连接
+pub fn local_request(path: &str) -> Result<Request<Body>, String> {
+    let mut req = Request::get(path)
+        .header(header::HOST, "localhost")
+        .body(Body::empty())
+        .map_err(|e| format!("invalid path `{}`: {}", path, e))?;
+    req.extensions_mut()
+        .insert(ClientAddr(PeerAddr::Tcp(SocketAddr::from((
+            Ipv4Addr::LOCALHOST,
+            0,
+        )))));
+    Ok(req)
+}
@@ -0,0 +1,170 @@
+// 直接从归档文件提供站点（static_dir = "site.zip" / "site.tar.zst"）：启动时建立条目索引，
+// 未压缩的条目（zip 中 stored 的条目、.tar）直接从内存映射中切片返回，其余条目解压到内存
+use crate::memfs::{MemFile, MemoryFs};
+use axum::body::Bytes;
+use axum::http::HeaderValue;
+use memmap2::Mmap;
+use std::io::{self, Cursor, Read};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+#[derive(Clone, Copy)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+fn format(path: &str) -> Option<Format> {
+    let lower = path.to_ascii_lowercase();
+    if lower.ends_with(".zip") {
+        Some(Format::Zip)
+    } else if lower.ends_with(".tar") {
+        Some(Format::Tar)
+    } else if lower.ends_with(".tar.gz") || lower.ends_with(".tgz") {
+        Some(Format::TarGz)
+    } else if lower.ends_with(".tar.zst") || lower.ends_with(".tzst") {
+        Some(Format::TarZst)
+    } else {
+        None
+    }
+}
+
+pub fn is_archive(path: &str) -> bool {
+    format(path).is_some()
+}
+
+pub fn load(path: &str) -> io::Result<MemoryFs> {
+    let format = format(path).ok_or_else(|| io::Error::other("unsupported archive format"))?;
+    let started = Instant::now();
+    let file = std::fs::File::open(path)?;
+    // SAFETY: 与 mmap_min_size 相同，归档应整体替换（rename）而不是原地改写
+    let mmap = Bytes::from_owner(unsafe { Mmap::map(&file)? });
+    let mut files = MemoryFs::new(path);
+    match format {
+        Format::Zip => load_zip(mmap, &mut files)?,
+        Format::Tar => load_tar(mmap, &mut files)?,
+        Format::TarGz => {
+            let mut data = Vec::new();
+            flate2::read::GzDecoder::new(&mmap[..]).read_to_end(&mut data)?;
+            load_tar(Bytes::from(data), &mut files)?
+        }
+        Format::TarZst => load_tar(Bytes::from(zstd::decode_all(&mmap[..])?), &mut files)?,
+    }
+    info!(
+        "Loaded {} entries ({} bytes) from {} in {:?}",
+        files.len(),
+        files.total_bytes(),
+        path,
+        started.elapsed()
+    );
+    Ok(files)
+}
+
+fn load_zip(data: Bytes, files: &mut MemoryFs) -> io::Result<()> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(&data[..])).map_err(io::Error::other)?;
+    for i in 0..archive.len() {
+        let entry = archive.by_index_raw(i).map_err(io::Error::other)?;
+        if !entry.is_file() {
+            continue;
+        }
+        let Some(name) = entry.enclosed_name() else {
+            warn!("Skipping unsafe archive entry {}", entry.name());
+            continue;
+        };
+        if entry.encrypted() {
+            warn!("Skipping encrypted archive entry {}", entry.name());
+            continue;
+        }
+        let modified = entry.last_modified().and_then(dos_time);
+        let etag =
+            HeaderValue::from_str(&format!("\"{:08x}-{:x}\"", entry.crc32(), entry.size())).ok();
+        let contents = if entry.compression() == zip::CompressionMethod::Stored {
+            let start = entry.data_start() as usize;
+            let end = start.saturating_add(entry.size() as usize);
+            if end > data.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("entry {} extends past the end of the archive", entry.name()),
+                ));
+            }
+            data.slice(start..end)
+        } else {
+            drop(entry);
+            let mut entry = archive.by_index(i).map_err(io::Error::other)?;
+            let mut buf = Vec::with_capacity(entry.size() as usize);
+            entry.read_to_end(&mut buf)?;
+            Bytes::from(buf)
+        };
+        files.insert(
+            &name.to_string_lossy().replace('\\', "/"),
+            MemFile {
+                data: contents,
+                modified,
+                etag,
+            },
+        );
+    }
+    Ok(())
+}
+
+fn load_tar(data: Bytes, files: &mut MemoryFs) -> io::Result<()> {
+    let mut archive = tar::Archive::new(&data[..]);
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?;
+        if path.components().any(|c| {
+            !matches!(
+                c,
+                std::path::Component::Normal(_) | std::path::Component::CurDir
+            )
+        }) {
+            warn!("Skipping unsafe archive entry {}", path.display());
+            continue;
+        }
+        let name = path.to_string_lossy().into_owned();
+        let start = entry.raw_file_position() as usize;
+        let modified = entry
+            .header()
+            .mtime()
+            .ok()
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        files.insert(
+            &name,
+            MemFile {
+                data: data.slice(start..start + entry.size() as usize),
+                modified,
+                etag: None,
+            },
+        );
+    }
+    Ok(())
+}
+
+// zip 中的时间是不带时区的本地时间，按 UTC 处理
+fn dos_time(time: zip::DateTime) -> Option<SystemTime> {
+    let (year, month, day) = (
+        i64::from(time.year()),
+        i64::from(time.month()),
+        i64::from(time.day()),
+    );
+    // 公历日期 -> 自 1970-01-01 起的天数
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    let secs = days * 86400
+        + i64::from(time.hour()) * 3600
+        + i64::from(time.minute()) * 60
+        + i64::from(time.second());
+    u64::try_from(secs)
+        .ok()
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+}
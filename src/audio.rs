@@ -0,0 +1,304 @@
+// 音频文件：按扩展名识别类型，从文件头读取时长（不解码音频数据）
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const TYPES: [(&str, &str); 8] = [
+    ("mp3", "audio/mpeg"),
+    ("m4a", "audio/mp4"),
+    ("mp4", "audio/mp4"),
+    ("aac", "audio/aac"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/ogg"),
+    ("flac", "audio/flac"),
+    ("wav", "audio/wav"),
+];
+
+// 按扩展名返回音频的 Content-Type，不是音频文件时返回 None
+pub fn content_type(name: &str) -> Option<&'static str> {
+    let ext = name.rsplit_once('.')?.1.to_ascii_lowercase();
+    TYPES.iter().find(|(e, _)| *e == ext).map(|(_, mime)| *mime)
+}
+
+// 音频时长（秒）；不支持的格式返回 None
+pub fn duration(path: &Path, len: u64) -> io::Result<Option<u64>> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    let mut file = File::open(path)?;
+    let secs = match ext.as_str() {
+        "mp3" => mp3_duration(&mut file, len)?,
+        "m4a" | "mp4" => mp4_duration(&mut file, len)?,
+        "wav" => wav_duration(&mut file)?,
+        "flac" => flac_duration(&mut file)?,
+        "ogg" | "opus" => ogg_duration(&mut file, len)?,
+        _ => None,
+    };
+    Ok(secs.map(|secs| secs.round() as u64))
+}
+
+fn read_at(file: &mut File, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut read = 0;
+    while read < buf.len() {
+        match file.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+fn be32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+fn le32(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+// 文件开头 ID3v2 标签的总长度，没有标签时返回 0
+pub fn id3_len(head: &[u8]) -> u64 {
+    if head.len() < 10 || &head[..3] != b"ID3" {
+        return 0;
+    }
+    let size = head[6..10]
+        .iter()
+        .fold(0u64, |acc, b| (acc << 7) | (*b & 0x7f) as u64);
+    10 + size + if head[5] & 0x10 != 0 { 10 } else { 0 }
+}
+
+// 一个 MPEG 音频（Layer III）或 ADTS（AAC）帧
+pub struct Frame {
+    // 帧的总字节数（含帧头）
+    pub len: usize,
+    pub samples: u32,
+    pub sample_rate: u32,
+    kbps: u32,
+    // Layer III 帧头之后 side information 的长度，Xing / Info 头位于其后
+    side_info: usize,
+}
+
+// 解析 MPEG 音频 Layer III 帧头（至少 4 字节）
+pub fn mp3_frame(header: &[u8]) -> Option<Frame> {
+    if header.len() < 4 || header[0] != 0xff || header[1] & 0xe0 != 0xe0 {
+        return None;
+    }
+    let version = (header[1] >> 3) & 0x03; // 3 = MPEG1, 2 = MPEG2, 0 = MPEG2.5
+    let layer = (header[1] >> 1) & 0x03; // 1 = Layer III
+    let bitrate_index = (header[2] >> 4) as usize;
+    let rate_index = ((header[2] >> 2) & 0x03) as usize;
+    if version == 1 || layer != 1 || bitrate_index == 0 || bitrate_index == 15 || rate_index == 3 {
+        return None;
+    }
+    const MPEG1_RATES: [u32; 15] = [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ];
+    const MPEG2_RATES: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+    let mpeg1 = version == 3;
+    let sample_rate = [44100, 48000, 32000][rate_index]
+        / match version {
+            3 => 1,
+            2 => 2,
+            _ => 4,
+        };
+    let kbps = if mpeg1 {
+        MPEG1_RATES[bitrate_index]
+    } else {
+        MPEG2_RATES[bitrate_index]
+    };
+    let samples = if mpeg1 { 1152 } else { 576 };
+    let padding = ((header[2] >> 1) & 0x01) as u32;
+    let len = (samples / 8 * kbps * 1000 / sample_rate + padding) as usize;
+    let mono = header[3] >> 6 == 3;
+    let side_info = match (mpeg1, mono) {
+        (true, false) => 32,
+        (true, true) => 17,
+        (false, false) => 17,
+        (false, true) => 9,
+    };
+    Some(Frame {
+        len,
+        samples,
+        sample_rate,
+        kbps,
+        side_info,
+    })
+}
+
+// 帧中 Xing / Info / VBRI 头记录的总帧数；这样的帧不含音频
+fn vbr_frames(frame: &[u8], info: &Frame) -> Option<Option<u32>> {
+    let xing = 4 + info.side_info;
+    if frame.len() >= xing + 12 && matches!(&frame[xing..xing + 4], b"Xing" | b"Info") {
+        let flags = be32(&frame[xing + 4..]);
+        return Some((flags & 1 != 0).then(|| be32(&frame[xing + 8..])));
+    }
+    let vbri = 4 + 32;
+    if frame.len() >= vbri + 18 && &frame[vbri..vbri + 4] == b"VBRI" {
+        return Some(Some(be32(&frame[vbri + 14..])));
+    }
+    None
+}
+
+// 第一帧是否为 Xing / Info / VBRI 头
+pub fn is_vbr_header(frame: &[u8], info: &Frame) -> bool {
+    vbr_frames(frame, info).is_some()
+}
+
+// 解析 ADTS 帧头（至少 7 字节）
+pub fn adts_frame(header: &[u8]) -> Option<Frame> {
+    if header.len() < 7 || header[0] != 0xff || header[1] & 0xf6 != 0xf0 {
+        return None;
+    }
+    const RATES: [u32; 13] = [
+        96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350,
+    ];
+    let sample_rate = *RATES.get(((header[2] >> 2) & 0x0f) as usize)?;
+    let len = (((header[3] & 0x03) as usize) << 11)
+        | ((header[4] as usize) << 3)
+        | ((header[5] as usize) >> 5);
+    let header_len = if header[1] & 0x01 != 0 { 7 } else { 9 };
+    if len < header_len {
+        return None;
+    }
+    Some(Frame {
+        len,
+        samples: ((header[6] & 0x03) as u32 + 1) * 1024,
+        sample_rate,
+        kbps: 0,
+        side_info: 0,
+    })
+}
+
+// MP3：跳过 ID3v2 标签，优先使用 Xing / Info / VBRI 头中的帧数，否则按首帧码率估算（CBR）
+fn mp3_duration(file: &mut File, len: u64) -> io::Result<Option<f64>> {
+    let mut head = [0u8; 10];
+    read_at(file, 0, &mut head)?;
+    let start = id3_len(&head);
+    let mut buf = vec![0u8; 64 * 1024];
+    let n = read_at(file, start, &mut buf)?;
+    let buf = &buf[..n];
+    let Some((pos, frame)) = (0..n).find_map(|i| Some((i, mp3_frame(&buf[i..])?))) else {
+        return Ok(None);
+    };
+    let per_frame = frame.samples as f64 / frame.sample_rate as f64;
+    if let Some(Some(frames)) = vbr_frames(&buf[pos..], &frame) {
+        return Ok(Some(frames as f64 * per_frame));
+    }
+    let audio = len.saturating_sub(start + pos as u64) as f64;
+    Ok(Some(audio * 8.0 / (frame.kbps as f64 * 1000.0)))
+}
+
+// MP4 / M4A：moov/mvhd 中的 duration / timescale
+fn mp4_duration(file: &mut File, len: u64) -> io::Result<Option<f64>> {
+    let Some((moov, moov_end)) = find_box(file, 0, len, b"moov")? else {
+        return Ok(None);
+    };
+    let Some((mvhd, _)) = find_box(file, moov, moov_end, b"mvhd")? else {
+        return Ok(None);
+    };
+    let mut buf = [0u8; 32];
+    read_at(file, mvhd, &mut buf)?;
+    let (timescale, duration) = if buf[0] == 1 {
+        (
+            be32(&buf[20..]) as f64,
+            u64::from_be_bytes(buf[24..32].try_into().unwrap()) as f64,
+        )
+    } else {
+        (be32(&buf[12..]) as f64, be32(&buf[16..]) as f64)
+    };
+    Ok((timescale > 0.0).then(|| duration / timescale))
+}
+
+// 在 [start, end) 中查找指定类型的 box，返回内容的起止位置
+fn find_box(
+    file: &mut File,
+    mut offset: u64,
+    end: u64,
+    kind: &[u8; 4],
+) -> io::Result<Option<(u64, u64)>> {
+    let mut header = [0u8; 16];
+    while offset + 8 <= end {
+        if read_at(file, offset, &mut header)? < 8 {
+            return Ok(None);
+        }
+        let (size, header_len) = match be32(&header) as u64 {
+            1 => (u64::from_be_bytes(header[8..16].try_into().unwrap()), 16),
+            0 => (end - offset, 8),
+            size => (size, 8),
+        };
+        if size < header_len {
+            return Ok(None);
+        }
+        if &header[4..8] == kind {
+            return Ok(Some((offset + header_len, (offset + size).min(end))));
+        }
+        offset += size;
+    }
+    Ok(None)
+}
+
+// WAV：data 块大小 / fmt 块中的 byte rate
+fn wav_duration(file: &mut File) -> io::Result<Option<f64>> {
+    let mut header = [0u8; 12];
+    read_at(file, 0, &mut header)?;
+    if &header[..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        return Ok(None);
+    }
+    let mut offset = 12u64;
+    let mut byte_rate = None;
+    let mut chunk = [0u8; 20];
+    loop {
+        if read_at(file, offset, &mut chunk)? < 8 {
+            return Ok(None);
+        }
+        let size = le32(&chunk[4..]) as u64;
+        match &chunk[..4] {
+            b"fmt " => byte_rate = Some(le32(&chunk[16..]) as f64).filter(|r| *r > 0.0),
+            b"data" => return Ok(byte_rate.map(|rate| size as f64 / rate)),
+            _ => {}
+        }
+        offset += 8 + size + (size & 1);
+    }
+}
+
+// FLAC：STREAMINFO 中的总采样数 / 采样率
+fn flac_duration(file: &mut File) -> io::Result<Option<f64>> {
+    let mut buf = [0u8; 26];
+    if read_at(file, 0, &mut buf)? < 26 || &buf[..4] != b"fLaC" || buf[4] & 0x7f != 0 {
+        return Ok(None);
+    }
+    let info = &buf[8..];
+    let sample_rate = ((info[10] as u32) << 12) | ((info[11] as u32) << 4) | (info[12] as u32 >> 4);
+    let total = (((info[13] & 0x0f) as u64) << 32) | be32(&info[14..]) as u64;
+    Ok((sample_rate > 0 && total > 0).then(|| total as f64 / sample_rate as f64))
+}
+
+// Ogg（Vorbis / Opus）：最后一页的 granule position / 采样率
+fn ogg_duration(file: &mut File, len: u64) -> io::Result<Option<f64>> {
+    let mut first = [0u8; 128];
+    let n = read_at(file, 0, &mut first)?;
+    if n < 64 || &first[..4] != b"OggS" {
+        return Ok(None);
+    }
+    let packet = 27 + first[26] as usize;
+    let (rate, pre_skip) = match first.get(packet..packet + 20) {
+        Some(p) if &p[..7] == b"\x01vorbis" => (le32(&p[12..]) as f64, 0.0),
+        Some(p) if &p[..8] == b"OpusHead" => (48000.0, u16::from_le_bytes([p[10], p[11]]) as f64),
+        _ => return Ok(None),
+    };
+    let tail_len = len.min(64 * 1024);
+    let mut tail = vec![0u8; tail_len as usize];
+    let n = read_at(file, len - tail_len, &mut tail)?;
+    let tail = &tail[..n];
+    let Some(page) = (0..n.saturating_sub(14))
+        .rev()
+        .find(|&i| &tail[i..i + 4] == b"OggS")
+    else {
+        return Ok(None);
+    };
+    let granule = u64::from_le_bytes(tail[page + 6..page + 14].try_into().unwrap()) as f64;
+    Ok((rate > 0.0).then(|| (granule - pre_skip).max(0.0) / rate))
+}
@@ -0,0 +1,318 @@
+// 音频元数据（[audio_meta]）：GET /music/a.mp3?meta=json 返回标签、时长、码率与内嵌封面的地址，
+// 封面由 ?meta=artwork&index=0 返回；解析结果按文件缓存在内存中，文件变化后重新解析
+use crate::index::etag_matches;
+use crate::site::Resolver;
+use axum::body::Body;
+use axum::extract::{OriginalUri, Request};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use lofty::file::{AudioFile, TaggedFileExt};
+use lofty::tag::{Accessor, ItemValue, Tag};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct AudioMetaConfig {
+    // 内存中缓存的文件数上限，超出时清空
+    #[serde(default = "default_cache_entries")]
+    pub cache_entries: usize,
+}
+
+fn default_cache_entries() -> usize {
+    1024
+}
+
+enum Query {
+    Json,
+    Artwork(usize),
+}
+
+impl Query {
+    fn parse(query: Option<&str>) -> Option<Self> {
+        let mut meta = None;
+        let mut index = 0;
+        for pair in query?.split('&') {
+            match pair.split_once('=') {
+                Some(("meta", value)) => meta = Some(value),
+                Some(("index", value)) => index = value.parse().ok()?,
+                _ => {}
+            }
+        }
+        match meta? {
+            "json" => Some(Query::Json),
+            "artwork" => Some(Query::Artwork(index)),
+            _ => None,
+        }
+    }
+}
+
+struct Picture {
+    mime: Option<String>,
+    kind: String,
+    description: Option<String>,
+    size: usize,
+}
+
+// 缓存的解析结果；封面只记录信息，数据在请求封面时重新读取
+struct Parsed {
+    len: u64,
+    modified: Option<SystemTime>,
+    info: Value,
+    pictures: Vec<Picture>,
+}
+
+pub struct AudioMeta {
+    cache_entries: usize,
+    cache: Mutex<HashMap<PathBuf, Arc<Parsed>>>,
+}
+
+impl AudioMeta {
+    pub fn new(config: &AudioMetaConfig) -> Self {
+        AudioMeta {
+            cache_entries: config.cache_entries,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn parsed(&self, path: &Path) -> Result<Arc<Parsed>, String> {
+        let meta = tokio::fs::metadata(path).await.map_err(|e| e.to_string())?;
+        let modified = meta.modified().ok();
+        let cached = self.cache.lock().unwrap().get(path).cloned();
+        if let Some(parsed) = cached {
+            if parsed.len == meta.len() && parsed.modified == modified {
+                return Ok(parsed);
+            }
+        }
+        let file = path.to_path_buf();
+        let (info, pictures) = tokio::task::spawn_blocking(move || parse(&file))
+            .await
+            .map_err(|e| e.to_string())??;
+        let parsed = Arc::new(Parsed {
+            len: meta.len(),
+            modified,
+            info,
+            pictures,
+        });
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= self.cache_entries {
+            cache.clear();
+        }
+        cache.insert(path.to_path_buf(), parsed.clone());
+        Ok(parsed)
+    }
+}
+
+fn read(path: &Path) -> Result<lofty::file::TaggedFile, String> {
+    lofty::read_from_path(path).map_err(|e| e.to_string())
+}
+
+// 主标签优先，没有时使用文件中的第一个标签
+fn main_tag(file: &lofty::file::TaggedFile) -> Option<&Tag> {
+    file.primary_tag().or_else(|| file.first_tag())
+}
+
+fn parse(path: &Path) -> Result<(Value, Vec<Picture>), String> {
+    let file = read(path)?;
+    let properties = file.properties();
+    let mut info = json!({
+        "file_type": format!("{:?}", file.file_type()),
+        "duration": properties.duration().as_millis() as f64 / 1000.0,
+        "bitrate": properties.overall_bitrate(),
+        "audio_bitrate": properties.audio_bitrate(),
+        "sample_rate": properties.sample_rate(),
+        "channels": properties.channels(),
+        "bit_depth": properties.bit_depth(),
+    });
+    let mut pictures = Vec::new();
+    if let Some(tag) = main_tag(&file) {
+        info["tags"] = json!({
+            "title": tag.title(),
+            "artist": tag.artist(),
+            "album": tag.album(),
+            "genre": tag.genre(),
+            "track": tag.track(),
+            "track_total": tag.track_total(),
+            "disc": tag.disk(),
+            "disc_total": tag.disk_total(),
+            "year": tag.year(),
+            "comment": tag.comment(),
+        });
+        for picture in tag.pictures() {
+            pictures.push(Picture {
+                mime: picture.mime_type().map(|mime| mime.as_str().to_string()),
+                kind: format!("{:?}", picture.pic_type()),
+                description: picture.description().map(str::to_string),
+                size: picture.data().len(),
+            });
+        }
+    }
+    // 各标签中的原始字段（ID3v2 帧、Vorbis comment 等），同名字段有多个值时返回数组
+    let mut raw = Map::new();
+    for tag in file.tags() {
+        let mut items: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for item in tag.items() {
+            let Some(key) = item.key().map_key(tag.tag_type(), true) else {
+                continue;
+            };
+            let value = match item.value() {
+                ItemValue::Text(text) | ItemValue::Locator(text) => text.clone(),
+                ItemValue::Binary(_) => continue,
+            };
+            items.entry(key.to_string()).or_default().push(value);
+        }
+        let items: Map<String, Value> = items
+            .into_iter()
+            .map(|(key, mut values)| {
+                let value = if values.len() == 1 {
+                    Value::String(values.remove(0))
+                } else {
+                    json!(values)
+                };
+                (key, value)
+            })
+            .collect();
+        raw.insert(format!("{:?}", tag.tag_type()), Value::Object(items));
+    }
+    info["raw"] = Value::Object(raw);
+    Ok((info, pictures))
+}
+
+// 封面的 MIME 类型与数据
+type Artwork = (Option<String>, Vec<u8>);
+
+fn artwork(path: &Path, index: usize) -> Result<Option<Artwork>, String> {
+    let file = read(path)?;
+    Ok(main_tag(&file)
+        .and_then(|tag| tag.pictures().get(index))
+        .map(|picture| {
+            (
+                picture.mime_type().map(|mime| mime.as_str().to_string()),
+                picture.data().to_vec(),
+            )
+        }))
+}
+
+// 在 resolve 之后执行；只处理磁盘上的音频文件，其余请求按原样交给内层服务
+pub async fn respond(
+    audio_meta: &AudioMeta,
+    resolver: &Resolver,
+    cache_control: &str,
+    req: Request,
+    next: Next,
+) -> Response {
+    let is_head = req.method() == Method::HEAD;
+    if !(is_head || req.method() == Method::GET) {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    if crate::audio::content_type(&path).is_none() {
+        return next.run(req).await;
+    }
+    let Some(query) = Query::parse(req.uri().query()) else {
+        return next.run(req).await;
+    };
+    let Some(fs_path) = resolver.include_path(&path).await else {
+        return next.run(req).await;
+    };
+    let parsed = match audio_meta.parsed(&fs_path).await {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            warn!("Failed to read audio metadata of {}: {}", path, e);
+            return (StatusCode::UNPROCESSABLE_ENTITY, "unsupported audio file").into_response();
+        }
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(parsed.len.to_le_bytes());
+    if let Some(modified) = parsed.modified {
+        hasher.update(format!("{:?}", modified).as_bytes());
+    }
+    let digest: String = hasher.finalize()[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    let (etag, content_type, body) = match query {
+        Query::Json => {
+            // 封面地址使用完整的请求路径，挂载点下同样包含前缀
+            let url = req
+                .extensions()
+                .get::<OriginalUri>()
+                .map_or(path.as_str(), |original| original.path());
+            let mut info = parsed.info.clone();
+            info["path"] = json!(url);
+            info["artwork"] = parsed
+                .pictures
+                .iter()
+                .enumerate()
+                .map(|(index, picture)| {
+                    json!({
+                        "url": format!("{}?meta=artwork&index={}", url, index),
+                        "mime": picture.mime,
+                        "type": picture.kind,
+                        "description": picture.description,
+                        "size": picture.size,
+                    })
+                })
+                .collect();
+            (
+                format!("W/\"meta-{}\"", digest),
+                HeaderValue::from_static("application/json"),
+                info.to_string().into_bytes(),
+            )
+        }
+        Query::Artwork(index) => {
+            if index >= parsed.pictures.len() {
+                return StatusCode::NOT_FOUND.into_response();
+            }
+            let file = fs_path.clone();
+            let picture = tokio::task::spawn_blocking(move || artwork(&file, index)).await;
+            let Ok(Ok(Some((mime, data)))) = picture else {
+                return StatusCode::NOT_FOUND.into_response();
+            };
+            let content_type = mime
+                .and_then(|mime| HeaderValue::from_str(&mime).ok())
+                .unwrap_or(HeaderValue::from_static("application/octet-stream"));
+            (
+                format!("W/\"art-{}-{}\"", digest, index),
+                content_type,
+                data,
+            )
+        }
+    };
+
+    let etag = HeaderValue::from_str(&etag).unwrap();
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|tags| tags.to_str().ok())
+        .is_some_and(|tags| etag_matches(tags, &etag));
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let len = body.len();
+        let mut response = Response::new(if is_head {
+            Body::empty()
+        } else {
+            Body::from(body)
+        });
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, content_type);
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+        response
+    };
+    let headers = response.headers_mut();
+    headers.insert(header::ETAG, etag);
+    if let Ok(value) = HeaderValue::from_str(cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+    response
+}
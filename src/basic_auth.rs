@@ -0,0 +1,137 @@
+// HTTP Basic 认证：顶层 / [[mount]] / [[vhost]] 的 auth 与目录覆盖文件中的 [auth] 共用；
+// 需要认证的响应改为 private 缓存，避免共享缓存把内容返回给未认证的请求
+use crate::oidc::OidcConfig;
+use crate::upload::constant_time_eq;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BasicAuthConfig {
+    // 设为 false 时关闭从上一级继承的认证，其余字段可省略
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // 未设置时接受任意用户名
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default, serialize_with = "crate::config::redact")]
+    pub password: String,
+    #[serde(default = "default_realm")]
+    pub realm: String,
+    // OpenID Connect 单点登录（[auth.oidc]），只用于顶层 [auth]，此时不设置 password
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_realm() -> String {
+    "Restricted".to_string()
+}
+
+pub struct BasicAuth {
+    username: Option<String>,
+    password: String,
+    challenge: HeaderValue,
+}
+
+impl BasicAuth {
+    // enabled = false 时返回 None
+    pub fn new(config: &BasicAuthConfig) -> Result<Option<Self>, String> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        // 顶层的 [auth.oidc] 在构建站点之前已经取出
+        if config.oidc.is_some() {
+            return Err("[auth.oidc] is only supported in the top-level [auth]".to_string());
+        }
+        if config.password.is_empty() {
+            return Err("auth password must not be empty".to_string());
+        }
+        let challenge = format!("Basic realm=\"{}\", charset=\"UTF-8\"", config.realm);
+        Ok(Some(BasicAuth {
+            username: config.username.clone(),
+            password: config.password.clone(),
+            challenge: HeaderValue::from_str(&challenge)
+                .map_err(|_| format!("invalid auth realm `{}`", config.realm))?,
+        }))
+    }
+
+    pub fn authorized(&self, headers: &HeaderMap) -> bool {
+        matches(headers, self.username.as_deref(), &self.password)
+    }
+
+    pub fn unauthorized(&self) -> Response {
+        let mut response = StatusCode::UNAUTHORIZED.into_response();
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, self.challenge.clone());
+        response
+    }
+}
+
+// 校验 Authorization 中的 Basic 凭据；状态页、上传等使用自己账号的端点共用。
+// username 为 None 时接受任意用户名
+pub fn matches(headers: &HeaderMap, username: Option<&str>, password: &str) -> bool {
+    let Some(encoded) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Basic "))
+    else {
+        return false;
+    };
+    let Ok(decoded) = base64::engine::general_purpose::STANDARD.decode(encoded.trim()) else {
+        return false;
+    };
+    let decoded = String::from_utf8_lossy(&decoded);
+    let Some((user, given)) = decoded.split_once(':') else {
+        return false;
+    };
+    let user_ok =
+        username.is_none_or(|expected| constant_time_eq(user.as_bytes(), expected.as_bytes()));
+    // 两项都比较，避免通过耗时判断哪一项错误
+    let password_ok = constant_time_eq(given.as_bytes(), password.as_bytes());
+    user_ok && password_ok
+}
+
+// 需要认证的内容不能进入共享缓存
+pub fn private(headers: &mut HeaderMap) {
+    let Some(value) = headers.get(header::CACHE_CONTROL) else {
+        return;
+    };
+    let Ok(text) = value.to_str() else {
+        return;
+    };
+    let directives: Vec<&str> = text.split(',').map(str::trim).collect();
+    if directives
+        .iter()
+        .any(|d| d.eq_ignore_ascii_case("private") || d.eq_ignore_ascii_case("no-store"))
+    {
+        return;
+    }
+    let rest = directives
+        .iter()
+        .filter(|d| !d.is_empty() && !d.eq_ignore_ascii_case("public"));
+    let joined: Vec<&str> = std::iter::once("private").chain(rest.copied()).collect();
+    if let Ok(value) = HeaderValue::from_str(&joined.join(", ")) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+}
+
+pub async fn check(State(auth): State<Arc<BasicAuth>>, req: Request, next: Next) -> Response {
+    if !auth.authorized(req.headers()) {
+        return auth.unauthorized();
+    }
+    let mut response = next.run(req).await;
+    private(response.headers_mut());
+    response
+}
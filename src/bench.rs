@@ -0,0 +1,263 @@
+// bench 子命令：以固定并发反复请求，报告每秒请求数与耗时分位数。目标为 URL 时压测运行中的实例；
+// 为目录时在进程内按 config.toml 组装站点（static_dir 换成该目录），不经过网络，
+// 用于衡量中间件与文件服务本身的开销
+use crate::app;
+use crate::cli::BenchArgs;
+use crate::config;
+use crate::s3::Backend;
+use crate::shutdown::Readiness;
+use axum::body::Body;
+use axum::http::{Request, StatusCode, Uri};
+use axum::Router;
+use futures_util::StreamExt;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+use tracing::subscriber::NoSubscriber;
+
+enum Target {
+    Http {
+        client: Box<Client<HttpsConnector<HttpConnector>, Body>>,
+        uri: Uri,
+    },
+    App {
+        app: Router,
+        paths: Vec<String>,
+    },
+}
+
+impl Target {
+    fn new(args: &BenchArgs) -> Result<Self, String> {
+        if args.target.starts_with("http://") || args.target.starts_with("https://") {
+            let uri: Uri = args
+                .target
+                .parse()
+                .map_err(|e| format!("invalid URL `{}`: {}", args.target, e))?;
+            let mut connector = HttpConnector::new();
+            connector.enforce_http(false);
+            connector.set_nodelay(true);
+            let https = hyper_rustls::HttpsConnectorBuilder::new()
+                .with_webpki_roots()
+                .https_or_http()
+                .enable_http1()
+                .wrap_connector(connector);
+            let client = Client::builder(TokioExecutor::new())
+                .pool_max_idle_per_host(args.concurrency)
+                .build(https);
+            return Ok(Target::Http {
+                client: Box::new(client),
+                uri,
+            });
+        }
+        let mut config =
+            tracing::subscriber::with_default(NoSubscriber::default(), config::load_config);
+        config.static_dir = Some(args.target.clone());
+        config.embedded = false;
+        config.backend = Backend::Fs;
+        // 每个请求一行的访问日志会淹没结果，耗时也主要花在写终端上
+        config.access_log = false;
+        let app = app::build(&config, Readiness::new(), None)?;
+        let paths = if args.path.is_empty() {
+            vec!["/".to_string()]
+        } else {
+            args.path.clone()
+        };
+        for path in &paths {
+            if !path.starts_with('/') {
+                return Err(format!("path `{}` must start with '/'", path));
+            }
+        }
+        Ok(Target::App { app, paths })
+    }
+
+    fn describe(&self, args: &BenchArgs) -> String {
+        match self {
+            Target::Http { uri, .. } => uri.to_string(),
+            Target::App { paths, .. } => {
+                format!("{} (in-process) {}", args.target, paths.join(" "))
+            }
+        }
+    }
+
+    // 返回状态码与响应体字节数
+    async fn send(&self, n: u64) -> Result<(StatusCode, u64), String> {
+        let response = match self {
+            Target::Http { client, uri } => {
+                let req = Request::get(uri.clone())
+                    .body(Body::empty())
+                    .map_err(|e| e.to_string())?;
+                client
+                    .request(req)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .map(Body::new)
+            }
+            Target::App { app, paths } => {
+                let req = app::local_request(&paths[n as usize % paths.len()])?;
+                match app.clone().oneshot(req).await {
+                    Ok(response) => response,
+                    Err(e) => match e {},
+                }
+            }
+        };
+        let status = response.status();
+        let mut stream = response.into_body().into_data_stream();
+        let mut bytes = 0;
+        while let Some(chunk) = stream.next().await {
+            bytes += chunk.map_err(|e| e.to_string())?.len() as u64;
+        }
+        Ok((status, bytes))
+    }
+}
+
+// 一个并发任务的结果
+#[derive(Default)]
+struct Samples {
+    // 每个成功请求的耗时（微秒）
+    latencies: Vec<u64>,
+    statuses: BTreeMap<u16, u64>,
+    bytes: u64,
+    errors: u64,
+    last_error: Option<String>,
+}
+
+pub async fn run(args: BenchArgs) {
+    if args.concurrency == 0 {
+        eprintln!("--concurrency must be greater than 0");
+        std::process::exit(2);
+    }
+    let target = match Target::new(&args) {
+        Ok(target) => Arc::new(target),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let duration = Duration::from_secs(args.duration);
+    match args.requests {
+        Some(requests) => println!(
+            "Benchmarking {} with {} concurrent requests, {} in total",
+            target.describe(&args),
+            args.concurrency,
+            requests
+        ),
+        None => println!(
+            "Benchmarking {} with {} concurrent requests for {} s",
+            target.describe(&args),
+            args.concurrency,
+            args.duration
+        ),
+    }
+
+    // 先发一次请求，目标不可用时直接报错
+    if let Err(e) = target.send(0).await {
+        eprintln!("Request failed: {}", e);
+        std::process::exit(1);
+    }
+
+    let issued = Arc::new(AtomicU64::new(0));
+    let started = Instant::now();
+    let mut workers = Vec::new();
+    for _ in 0..args.concurrency {
+        let target = target.clone();
+        let issued = issued.clone();
+        let limit = args.requests;
+        workers.push(tokio::spawn(async move {
+            let mut samples = Samples::default();
+            loop {
+                let n = issued.fetch_add(1, Ordering::Relaxed);
+                let done = match limit {
+                    Some(limit) => n >= limit,
+                    None => started.elapsed() >= duration,
+                };
+                if done {
+                    break;
+                }
+                let begin = Instant::now();
+                match target.send(n).await {
+                    Ok((status, bytes)) => {
+                        samples.latencies.push(begin.elapsed().as_micros() as u64);
+                        *samples.statuses.entry(status.as_u16()).or_default() += 1;
+                        samples.bytes += bytes;
+                    }
+                    Err(e) => {
+                        samples.errors += 1;
+                        samples.last_error = Some(e);
+                    }
+                }
+            }
+            samples
+        }));
+    }
+    let mut total = Samples::default();
+    for worker in workers {
+        let Ok(mut samples) = worker.await else {
+            continue;
+        };
+        total.latencies.append(&mut samples.latencies);
+        for (status, count) in samples.statuses {
+            *total.statuses.entry(status).or_default() += count;
+        }
+        total.bytes += samples.bytes;
+        total.errors += samples.errors;
+        total.last_error = samples.last_error.or(total.last_error);
+    }
+    report(&total, started.elapsed());
+    if total.errors > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn report(samples: &Samples, elapsed: Duration) {
+    let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+    let completed = samples.latencies.len() as u64;
+    println!();
+    println!(
+        "Requests:    {} in {:.2} s, {} errors",
+        completed, secs, samples.errors
+    );
+    println!("Requests/s:  {:.1}", completed as f64 / secs);
+    println!(
+        "Transfer:    {:.2} MiB/s ({} bytes)",
+        samples.bytes as f64 / secs / (1024.0 * 1024.0),
+        samples.bytes
+    );
+    let statuses: Vec<String> = samples
+        .statuses
+        .iter()
+        .map(|(status, count)| format!("{} x {}", status, count))
+        .collect();
+    println!("Status:      {}", statuses.join(", "));
+    if let Some(e) = &samples.last_error {
+        println!("Last error:  {}", e);
+    }
+    if samples.latencies.is_empty() {
+        return;
+    }
+    let mut latencies = samples.latencies.clone();
+    latencies.sort_unstable();
+    let mean = latencies.iter().sum::<u64>() as f64 / latencies.len() as f64;
+    // 最近秩法：不小于 q 比例样本的最小值
+    let quantile = |q: f64| {
+        let rank = ((q * latencies.len() as f64).ceil() as usize).clamp(1, latencies.len());
+        latencies[rank - 1] as f64
+    };
+    println!("Latency:");
+    for (name, micros) in [
+        ("min", latencies[0] as f64),
+        ("mean", mean),
+        ("p50", quantile(0.50)),
+        ("p90", quantile(0.90)),
+        ("p99", quantile(0.99)),
+        ("p99.9", quantile(0.999)),
+        ("max", latencies[latencies.len() - 1] as f64),
+    ] {
+        println!("  {:<6} {:>10.3} ms", name, micros / 1000.0);
+    }
+}
@@ -0,0 +1,83 @@
+// 站点构建钩子：在启动时（或文件变化时）运行站点自身的构建命令
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::info;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct BuildConfig {
+    // 构建命令，通过系统 shell 执行，例如 "npm run build"
+    pub command: String,
+    // 命令的工作目录（默认当前目录）
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+    // 触发重新构建的源文件 glob（相对 working_dir），--watch 模式下匹配的文件变化时重新构建，成功后才通知浏览器刷新
+    #[serde(default)]
+    pub watch: Vec<String>,
+    // 合并连续变化的等待时间（毫秒）
+    #[serde(default = "default_debounce_ms")]
+    pub debounce_ms: u64,
+    // 普通服务模式下是否在启动时先构建一次
+    #[serde(default = "default_run_on_start")]
+    pub run_on_start: bool,
+}
+
+fn default_run_on_start() -> bool {
+    true
+}
+
+pub fn default_debounce_ms() -> u64 {
+    300
+}
+
+fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    };
+
+    #[cfg(not(windows))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+
+    cmd.kill_on_drop(true);
+    cmd
+}
+
+impl BuildConfig {
+    // 运行一次构建命令，输出直接继承到当前终端
+    pub async fn run(&self) -> Result<(), String> {
+        info!("Running build command: {}", self.command);
+        let elapsed = run_command(&self.command, self.working_dir.as_deref()).await?;
+        info!("Build finished in {:.2?}", elapsed);
+        Ok(())
+    }
+}
+
+// 通过系统 shell 运行命令并等待结束，返回耗时；非零退出码视为失败
+pub async fn run_command(command: &str, working_dir: Option<&Path>) -> Result<Duration, String> {
+    let mut cmd = shell_command(command);
+    if let Some(dir) = working_dir {
+        cmd.current_dir(dir);
+    }
+
+    let started = std::time::Instant::now();
+    let status: ExitStatus = cmd
+        .status()
+        .await
+        .map_err(|e| format!("failed to spawn `{}`: {}", command, e))?;
+
+    if status.success() {
+        Ok(started.elapsed())
+    } else {
+        Err(format!("`{}` exited with {}", command, status))
+    }
+}
@@ -0,0 +1,361 @@
+// 热点文件内存缓存：缓存小文件的完整响应（按 Accept-Encoding 区分预压缩版本），LRU 淘汰，按 mtime 失效
+use crate::metrics::{CacheLookup, METRICS};
+use crate::server_timing::Timings;
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::{header, HeaderMap, Method, Response, StatusCode};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+use tower::Service;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CacheConfig {
+    // 缓存总大小上限（字节）
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: u64,
+    // 单个文件大小上限（字节），更大的文件直接读磁盘
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    // 命中后在该时间内不检查 mtime 直接返回（秒）；0 表示每次都检查
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+fn default_max_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_max_file_size() -> u64 {
+    1024 * 1024
+}
+
+fn default_ttl_secs() -> u64 {
+    5
+}
+
+// (文件路径, Accept-Encoding)
+type Key = (PathBuf, String);
+
+struct Entry {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    modified: SystemTime,
+    validated: Instant,
+    // LRU 序号，越大越新
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<Key, Entry>,
+    lru: BTreeMap<u64, Key>,
+    bytes: u64,
+    tick: u64,
+}
+
+impl Inner {
+    fn touch(&mut self, key: &Key) {
+        self.tick += 1;
+        let tick = self.tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.lru.remove(&entry.tick);
+            entry.tick = tick;
+            self.lru.insert(tick, key.clone());
+        }
+    }
+
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.tick);
+            self.bytes -= entry.body.len() as u64;
+        }
+    }
+}
+
+pub struct FileCache {
+    config: CacheConfig,
+    inner: Mutex<Inner>,
+}
+
+impl FileCache {
+    pub fn new(config: CacheConfig) -> Self {
+        FileCache {
+            config,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    // 命中且未过期时返回缓存的响应；超过 ttl 的条目按 mtime 重新校验
+    async fn get(&self, key: &Key) -> Option<(StatusCode, HeaderMap, Bytes)> {
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        let modified = {
+            let mut inner = self.inner.lock().unwrap();
+            let entry = inner.entries.get(key)?;
+            if entry.validated.elapsed() < ttl {
+                let hit = (entry.status, entry.headers.clone(), entry.body.clone());
+                inner.touch(key);
+                return Some(hit);
+            }
+            entry.modified
+        };
+
+        let current = tokio::fs::metadata(&key.0)
+            .await
+            .and_then(|m| m.modified())
+            .ok();
+        let mut inner = self.inner.lock().unwrap();
+        if current != Some(modified) {
+            inner.remove(key);
+            return None;
+        }
+        let entry = inner.entries.get_mut(key)?;
+        entry.validated = Instant::now();
+        let hit = (entry.status, entry.headers.clone(), entry.body.clone());
+        inner.touch(key);
+        Some(hit)
+    }
+
+    // 清空所有条目（文件变化时调用）
+    pub fn clear(&self) {
+        *self.inner.lock().unwrap() = Inner::default();
+    }
+
+    fn insert(
+        &self,
+        key: Key,
+        status: StatusCode,
+        headers: HeaderMap,
+        body: Bytes,
+        modified: SystemTime,
+    ) {
+        let size = body.len() as u64;
+        if size > self.config.max_file_size || size > self.config.max_bytes {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        inner.remove(&key);
+        while inner.bytes + size > self.config.max_bytes {
+            let Some((_, oldest)) = inner.lru.pop_first() else {
+                break;
+            };
+            if let Some(entry) = inner.entries.remove(&oldest) {
+                inner.bytes -= entry.body.len() as u64;
+            }
+        }
+        inner.tick += 1;
+        let tick = inner.tick;
+        inner.lru.insert(tick, key.clone());
+        inner.bytes += size;
+        inner.entries.insert(
+            key,
+            Entry {
+                status,
+                headers,
+                body,
+                modified,
+                validated: Instant::now(),
+                tick,
+            },
+        );
+    }
+}
+
+// 包在 ServeDir 外面：GET/HEAD 且无 Range 的请求优先走缓存；未启用缓存时直接透传
+#[derive(Clone)]
+pub struct CacheService<S> {
+    inner: S,
+    cache: Option<Arc<FileCache>>,
+    // 站点目录，用于定位 URL 对应的文件
+    dir: PathBuf,
+}
+
+impl<S> CacheService<S> {
+    pub fn new(inner: S, cache: Option<Arc<FileCache>>, dir: &str) -> Self {
+        CacheService {
+            inner,
+            cache,
+            dir: PathBuf::from(dir),
+        }
+    }
+}
+
+impl<S> Service<Request<Body>> for CacheService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let timings = Timings::from_extensions(req.extensions());
+        let Some(cache) = self.cache.clone() else {
+            return Box::pin(async move {
+                Timings::measure(timings.as_ref(), "disk", inner.call(req)).await
+            });
+        };
+        let cacheable = (req.method() == Method::GET || req.method() == Method::HEAD)
+            && !req.headers().contains_key(header::RANGE)
+            && !req.headers().contains_key(header::IF_RANGE)
+            && !req.headers().contains_key(header::IF_NONE_MATCH);
+        let key = crate::site::fs_path(&self.dir, req.uri().path()).map(|path| {
+            let encoding = req
+                .headers()
+                .get(header::ACCEPT_ENCODING)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("")
+                .to_string();
+            (path, encoding)
+        });
+
+        Box::pin(async move {
+            let timings = timings.as_ref();
+            let Some(key) = key.filter(|_| cacheable) else {
+                return Timings::measure(timings, "disk", inner.call(req)).await;
+            };
+            let is_head = req.method() == Method::HEAD;
+
+            let lookup = Instant::now();
+            let cached = cache.get(&key).await;
+            if let Some(timings) = timings {
+                let desc = if cached.is_some() { "hit" } else { "miss" };
+                timings.record("cache", lookup.elapsed(), Some(desc));
+            }
+            if let Some((status, headers, body)) = cached {
+                METRICS.record_cache(true);
+                CacheLookup::mark(req.extensions(), true);
+                // Last-Modified 与 If-Modified-Since 完全一致时直接返回 304
+                let not_modified = req
+                    .headers()
+                    .get(header::IF_MODIFIED_SINCE)
+                    .is_some_and(|since| headers.get(header::LAST_MODIFIED) == Some(since));
+                let mut response = Response::new(if is_head || not_modified {
+                    Body::empty()
+                } else {
+                    Body::from(body)
+                });
+                *response.headers_mut() = headers;
+                if not_modified {
+                    *response.status_mut() = StatusCode::NOT_MODIFIED;
+                    response.headers_mut().remove(header::CONTENT_LENGTH);
+                } else {
+                    *response.status_mut() = status;
+                }
+                return Ok(response);
+            }
+            METRICS.record_cache(false);
+            CacheLookup::mark(req.extensions(), false);
+
+            let conditional = req.headers().contains_key(header::IF_MODIFIED_SINCE);
+            // 读取文件的耗时；要缓存的响应包含完整读出正文的时间
+            let read = Instant::now();
+            let record_read = || {
+                if let Some(timings) = timings {
+                    timings.record("disk", read.elapsed(), None);
+                }
+            };
+            let response = inner.call(req).await?;
+            let size = response
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok());
+            let fits = size.is_some_and(|size| size <= cache.config.max_file_size);
+            if is_head || conditional || response.status() != StatusCode::OK || !fits {
+                record_read();
+                return Ok(response);
+            }
+            // 只缓存真实存在的文件（不缓存 fallback 等替代响应）
+            let modified = match tokio::fs::metadata(&key.0).await {
+                Ok(meta) if meta.is_file() => meta.modified().ok(),
+                _ => None,
+            };
+            let Some(modified) = modified else {
+                record_read();
+                return Ok(response);
+            };
+
+            let (parts, body) = response.into_parts();
+            let limit = cache.config.max_file_size as usize;
+            let body = match axum::body::to_bytes(body, limit).await {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::warn!("Failed to read {} for cache: {}", key.0.display(), e);
+                    let mut response = Response::from_parts(parts, Body::empty());
+                    *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                    response.headers_mut().remove(header::CONTENT_LENGTH);
+                    return Ok(response);
+                }
+            };
+            record_read();
+            cache.insert(
+                key,
+                parts.status,
+                parts.headers.clone(),
+                body.clone(),
+                modified,
+            );
+            Ok(Response::from_parts(parts, Body::from(body)))
+        })
+    }
+}
+
+// 404 结果的短期缓存：扫描器反复请求的不存在路径不再访问文件系统
+pub struct NegativeCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, Instant>>,
+}
+
+// 条目数上限，防止随机路径扫描占满内存
+const NEGATIVE_CACHE_MAX_ENTRIES: usize = 10_000;
+
+impl NegativeCache {
+    pub fn new(ttl: Duration) -> Self {
+        NegativeCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(path) {
+            Some(inserted) if inserted.elapsed() < self.ttl => true,
+            Some(_) => {
+                entries.remove(path);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn insert(&self, path: String) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= NEGATIVE_CACHE_MAX_ENTRIES {
+            let ttl = self.ttl;
+            entries.retain(|_, inserted| inserted.elapsed() < ttl);
+            if entries.len() >= NEGATIVE_CACHE_MAX_ENTRIES {
+                entries.clear();
+            }
+        }
+        entries.insert(path, Instant::now());
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
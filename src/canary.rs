@@ -0,0 +1,240 @@
+// 金丝雀分流（[canary]）：默认站点按比例在主目录与金丝雀目录之间分流，新版前端可以先给一小部分访问者试用。
+// 每个访问者第一次访问时分到 0–99 中的一个桶并写入 Cookie，桶号小于 percent 的访问者看到金丝雀版本；
+// 调高比例时已分到金丝雀的访问者保持不变，调回 0 即全部回到主目录。可以通过 Bearer token 保护的端点调整比例，
+// 状态只保存在当前进程，supervisor 模式下每个工作进程各自调整
+use crate::chaos;
+use crate::cookies;
+use crate::login;
+use crate::upload;
+use crate::vary;
+use axum::extract::{Query, Request, State};
+use axum::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use axum::Router;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::Arc;
+use tower::ServiceExt;
+use tracing::info;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CanaryConfig {
+    // 金丝雀版本的目录（或归档文件），使用与主目录相同的站点设置
+    pub dir: String,
+    // 分到金丝雀的访问者比例（0–100）
+    #[serde(default)]
+    pub percent: u8,
+    // 保存桶号的 Cookie
+    #[serde(default = "default_cookie")]
+    pub cookie: String,
+    #[serde(default = "default_cookie_max_age")]
+    pub cookie_max_age_secs: u64,
+    // 指定版本的请求头，值为 canary 或 stable 时不看比例与 Cookie，便于测试；未设置时不启用
+    #[serde(default)]
+    pub header: Option<String>,
+    // 调整端点的 Bearer token，未设置时不启用调整端点
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub token: Option<String>,
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_cookie() -> String {
+    "sw_canary".to_string()
+}
+
+fn default_cookie_max_age() -> u64 {
+    30 * 24 * 3600
+}
+
+fn default_path() -> String {
+    "/__canary".to_string()
+}
+
+pub struct Canary {
+    dir: String,
+    site: Router,
+    percent: AtomicU8,
+    cookie: String,
+    cookie_max_age: u64,
+    header: Option<HeaderName>,
+    token: Option<String>,
+    path: String,
+    // 启动以来主目录与金丝雀目录各自处理的请求数
+    stable_requests: AtomicU64,
+    canary_requests: AtomicU64,
+}
+
+impl Canary {
+    pub fn new(config: &CanaryConfig, site: Router) -> Result<Self, String> {
+        if !crate::archive::is_archive(&config.dir) && !std::path::Path::new(&config.dir).is_dir() {
+            return Err(format!("[canary] dir {} is not a directory", config.dir));
+        }
+        if config.percent > 100 {
+            return Err("[canary] percent must be between 0 and 100".to_string());
+        }
+        let valid_cookie = !config.cookie.is_empty()
+            && config
+                .cookie
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b));
+        if !valid_cookie {
+            return Err(format!("[canary] invalid cookie name `{}`", config.cookie));
+        }
+        let header = config
+            .header
+            .as_deref()
+            .map(|name| {
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| format!("[canary] invalid header name `{}`", name))
+            })
+            .transpose()?;
+        if !config.path.starts_with('/') {
+            return Err("[canary] path must start with /".to_string());
+        }
+        if config.token.as_deref() == Some("") {
+            return Err("[canary] token must not be empty".to_string());
+        }
+        Ok(Canary {
+            dir: config.dir.clone(),
+            site,
+            percent: AtomicU8::new(config.percent),
+            cookie: config.cookie.clone(),
+            cookie_max_age: config.cookie_max_age_secs,
+            header,
+            token: config.token.clone(),
+            path: config.path.clone(),
+            stable_requests: AtomicU64::new(0),
+            canary_requests: AtomicU64::new(0),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} ({}% of visitors)", self.dir, self.percent())
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.token.as_ref().map(|_| self.path.as_str())
+    }
+
+    fn percent(&self) -> u8 {
+        self.percent.load(Ordering::Relaxed)
+    }
+
+    // 请求头指定的版本
+    fn forced(&self, headers: &HeaderMap) -> Option<bool> {
+        let value = headers.get(self.header.as_ref()?)?.to_str().ok()?;
+        match value.trim().to_ascii_lowercase().as_str() {
+            "canary" => Some(true),
+            "stable" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn bucket(&self, headers: &HeaderMap) -> Option<u8> {
+        cookies::get(headers, &self.cookie)
+            .and_then(|value| value.parse::<u8>().ok())
+            .filter(|bucket| *bucket < 100)
+    }
+
+    fn set_cookie(&self, bucket: u8, https: bool) -> HeaderValue {
+        let secure = if https { "; Secure" } else { "" };
+        let cookie = format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{}",
+            self.cookie, bucket, self.cookie_max_age, secure
+        );
+        HeaderValue::from_str(&cookie).expect("cookie values are ASCII")
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        upload::bearer_authorized(headers, self.token.as_deref())
+    }
+
+    fn status(&self) -> serde_json::Value {
+        json!({
+            "dir": self.dir,
+            "percent": self.percent(),
+            "requests": {
+                "stable": self.stable_requests.load(Ordering::Relaxed),
+                "canary": self.canary_requests.load(Ordering::Relaxed),
+            },
+        })
+    }
+}
+
+// 包在主目录外面：分到金丝雀的请求交给金丝雀站点，其余请求照常处理
+pub async fn split(State(canary): State<Arc<Canary>>, req: Request, next: Next) -> Response {
+    let percent = canary.percent();
+    let forced = canary.forced(req.headers());
+    let (bucket, assigned) = match canary.bucket(req.headers()) {
+        Some(bucket) => (bucket, false),
+        None => (((chaos::random() * 100.0) as u8).min(99), true),
+    };
+    let use_canary = forced.unwrap_or(bucket < percent);
+    // 比例为 0 或 100 时所有桶的结果相同，不必写入 Cookie；请求头指定版本的测试请求也不写入
+    let set_cookie = (assigned && forced.is_none() && percent > 0 && percent < 100)
+        .then(|| canary.set_cookie(bucket, login::is_https(&req)));
+    let mut response = if use_canary {
+        canary.canary_requests.fetch_add(1, Ordering::Relaxed);
+        match canary.site.clone().oneshot(req).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        }
+    } else {
+        canary.stable_requests.fetch_add(1, Ordering::Relaxed);
+        next.run(req).await
+    };
+    let headers = response.headers_mut();
+    if let Some(cookie) = set_cookie {
+        headers.append(header::SET_COOKIE, cookie);
+    }
+    // 同一路径按 Cookie 返回不同版本，共享缓存需要分开保存
+    vary::add(headers, "Cookie");
+    if let Some(name) = &canary.header {
+        vary::add(headers, name.as_str());
+    }
+    response
+}
+
+// GET 返回当前比例与各自处理的请求数；POST ?percent=20 调整比例
+pub async fn status(State(canary): State<Arc<Canary>>, headers: HeaderMap) -> Response {
+    if !canary.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    login::no_store(Json(canary.status()).into_response())
+}
+
+pub async fn set(
+    State(canary): State<Arc<Canary>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if !canary.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    let Some(percent) = query
+        .get("percent")
+        .and_then(|v| v.trim().parse::<u8>().ok())
+        .filter(|percent| *percent <= 100)
+    else {
+        return login::no_store(
+            (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": "expected ?percent=0 to ?percent=100" })),
+            )
+                .into_response(),
+        );
+    };
+    let previous = canary.percent.swap(percent, Ordering::Relaxed);
+    if previous != percent {
+        info!("Canary traffic changed from {}% to {}%", previous, percent);
+    }
+    let mut status = canary.status();
+    status["changed"] = (previous != percent).into();
+    login::no_store(Json(status).into_response())
+}
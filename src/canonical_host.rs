@@ -0,0 +1,125 @@
+// 规范主机（[canonical_host]）：把 www.example.com 等别名永久重定向到唯一的主机名，
+// 可同时强制 HTTPS。主机名与协议按可信代理还原后的值判断（X-Forwarded-Host / -Proto、Forwarded）
+use crate::forwarded::ClientInfo;
+use crate::glob::PathPattern;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CanonicalHostConfig {
+    // 规范的主机名，可带端口，如 "example.com"
+    pub host: String,
+    // 需要重定向的别名；为空时所有其他主机名都重定向（包括用 IP 访问的请求）
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    // 同时把 HTTP 请求重定向到 HTTPS
+    #[serde(default)]
+    pub https: bool,
+    // 不重定向的路径 glob，如负载均衡器按 IP 访问的健康检查
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+pub struct CanonicalHost {
+    host: String,
+    // 不含端口、小写，用于比较
+    hostname: String,
+    aliases: Vec<String>,
+    https: bool,
+    exclude: Vec<PathPattern>,
+}
+
+// "Example.com:8443" -> "example.com"，"[::1]:80" -> "[::1]"
+fn hostname(host: &str) -> String {
+    let host = host.trim().to_ascii_lowercase();
+    if host.starts_with('[') {
+        return match host.find(']') {
+            Some(end) => host[..=end].to_string(),
+            None => host,
+        };
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if port.bytes().all(|b| b.is_ascii_digit()) => name.to_string(),
+        _ => host,
+    }
+}
+
+impl CanonicalHost {
+    pub fn new(config: &CanonicalHostConfig) -> Result<Self, String> {
+        let host = config.host.trim();
+        if host.is_empty() || host.contains(['/', ' ']) {
+            return Err(format!("invalid canonical_host `{}`", config.host));
+        }
+        HeaderValue::from_str(host)
+            .map_err(|e| format!("invalid canonical_host `{}`: {}", config.host, e))?;
+        let mut exclude = Vec::new();
+        for pattern in &config.exclude {
+            exclude.push(PathPattern::new(pattern)?);
+        }
+        Ok(CanonicalHost {
+            host: host.to_string(),
+            hostname: hostname(host),
+            aliases: config.aliases.iter().map(|alias| hostname(alias)).collect(),
+            https: config.https,
+            exclude,
+        })
+    }
+
+    // 需要重定向时返回新的 Location
+    fn location(&self, req: &Request) -> Option<String> {
+        let client = req.extensions().get::<ClientInfo>();
+        let scheme = client.map_or("http", |client| client.scheme);
+        // HTTP/2 没有 Host 头，使用 :authority
+        let requested = client
+            .and_then(|client| client.host.clone())
+            .or_else(|| {
+                req.headers()
+                    .get(header::HOST)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string)
+            })
+            .or_else(|| req.uri().authority().map(|a| a.to_string()))?;
+        let requested = hostname(&requested);
+        let wrong_host = requested != self.hostname
+            && (self.aliases.is_empty() || self.aliases.contains(&requested));
+        let wrong_scheme = self.https && scheme != "https";
+        if !wrong_host && !wrong_scheme {
+            return None;
+        }
+        let path = req.uri().path();
+        if self.exclude.iter().any(|pattern| pattern.matches(path)) {
+            return None;
+        }
+        let scheme = if self.https { "https" } else { scheme };
+        let path_and_query = req.uri().path_and_query().map_or("/", |pq| pq.as_str());
+        Some(format!("{}://{}{}", scheme, self.host, path_and_query))
+    }
+}
+
+pub async fn redirect(
+    State(canonical): State<Arc<CanonicalHost>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(location) = canonical.location(&req) else {
+        return next.run(req).await;
+    };
+    // 其他方法用 308，保留方法与请求体
+    let status = if req.method() == Method::GET || req.method() == Method::HEAD {
+        StatusCode::MOVED_PERMANENTLY
+    } else {
+        StatusCode::PERMANENT_REDIRECT
+    };
+    debug!("Canonical host redirect {} -> {}", req.uri(), location);
+    match HeaderValue::from_str(&location) {
+        Ok(value) => (status, [(header::LOCATION, value)]).into_response(),
+        Err(_) => next.run(req).await,
+    }
+}
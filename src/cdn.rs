@@ -0,0 +1,117 @@
+// CDN / 共享缓存策略（[cdn]）：在面向浏览器的 Cache-Control 之外追加 s-maxage、stale-while-revalidate、
+// stale-if-error，并可单独发送 Surrogate-Control（Fastly 等）与 CDN-Cache-Control（Cloudflare 等）
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::Response;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CdnConfig {
+    // 共享缓存的有效期（秒），追加为 s-maxage
+    #[serde(default)]
+    pub s_maxage: Option<u64>,
+    // 过期后仍可返回旧内容并在后台更新的时间（秒）
+    #[serde(default)]
+    pub stale_while_revalidate: Option<u64>,
+    // 源站出错时仍可返回旧内容的时间（秒）
+    #[serde(default)]
+    pub stale_if_error: Option<u64>,
+    // Surrogate-Control 的值，如 "max-age=86400"；由 CDN 使用并在返回浏览器前去掉
+    #[serde(default)]
+    pub surrogate_control: Option<String>,
+    // CDN-Cache-Control 的值（RFC 9213），如 "max-age=86400"
+    #[serde(default)]
+    pub cdn_cache_control: Option<String>,
+}
+
+pub struct Cdn {
+    // 追加到 Cache-Control 的指令
+    directives: Vec<(&'static str, u64)>,
+    surrogate_control: Option<HeaderValue>,
+    cdn_cache_control: Option<HeaderValue>,
+}
+
+fn header_value(value: &Option<String>, what: &str) -> Result<Option<HeaderValue>, String> {
+    value
+        .as_deref()
+        .map(|value| HeaderValue::from_str(value).map_err(|e| format!("invalid {}: {}", what, e)))
+        .transpose()
+}
+
+impl Cdn {
+    pub fn new(config: &CdnConfig) -> Result<Self, String> {
+        let directives = [
+            ("s-maxage", config.s_maxage),
+            ("stale-while-revalidate", config.stale_while_revalidate),
+            ("stale-if-error", config.stale_if_error),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .collect();
+        Ok(Cdn {
+            directives,
+            surrogate_control: header_value(&config.surrogate_control, "surrogate_control")?,
+            cdn_cache_control: header_value(&config.cdn_cache_control, "cdn_cache_control")?,
+        })
+    }
+
+    // private / no-store 的响应不能进入共享缓存，保持原样；已有的同名指令不重复添加
+    fn extend_cache_control(&self, headers: &mut HeaderMap) {
+        let Some(current) = headers
+            .get(header::CACHE_CONTROL)
+            .and_then(|value| value.to_str().ok())
+        else {
+            return;
+        };
+        let names: Vec<String> = current
+            .split(',')
+            .map(|directive| {
+                let name = directive.split('=').next().unwrap_or_default();
+                name.trim().to_ascii_lowercase()
+            })
+            .collect();
+        if names
+            .iter()
+            .any(|name| name == "private" || name == "no-store")
+        {
+            return;
+        }
+        let mut value = current.to_string();
+        for (name, secs) in &self.directives {
+            if !names.iter().any(|existing| existing == name) {
+                value.push_str(&format!(", {}={}", name, secs));
+            }
+        }
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(header::CACHE_CONTROL, value);
+        }
+    }
+}
+
+// 只处理成功与 304 响应；上游或站点已设置的 Surrogate-Control / CDN-Cache-Control 优先
+pub async fn apply(State(cdn): State<Arc<Cdn>>, req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    let status = response.status();
+    if !(status.is_success() || status == StatusCode::NOT_MODIFIED) {
+        return response;
+    }
+    let headers = response.headers_mut();
+    cdn.extend_cache_control(headers);
+    if let Some(value) = &cdn.surrogate_control {
+        let name = HeaderName::from_static("surrogate-control");
+        if !headers.contains_key(&name) {
+            headers.insert(name, value.clone());
+        }
+    }
+    if let Some(value) = &cdn.cdn_cache_control {
+        let name = HeaderName::from_static("cdn-cache-control");
+        if !headers.contains_key(&name) {
+            headers.insert(name, value.clone());
+        }
+    }
+    response
+}
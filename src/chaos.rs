@@ -0,0 +1,220 @@
+// 故障注入（[chaos]）：仅在 --watch 开发模式下生效，对匹配的路径注入延迟、随机 500、
+// 截断的响应体或限制带宽，便于前端测试加载状态与重试逻辑。注入的响应带有 X-Chaos 头
+use crate::glob::PathPattern;
+use crate::throttle;
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderName, HeaderValue};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Sleep;
+use tracing::debug;
+
+const X_CHAOS: HeaderName = HeaderName::from_static("x-chaos");
+const FLUSH_DELAY: Duration = Duration::from_millis(100);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct ChaosConfig {
+    // 按顺序匹配，第一条匹配的规则生效
+    #[serde(default)]
+    pub rules: Vec<ChaosRuleConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ChaosRuleConfig {
+    // 路径模式，如 "/api/**"、"*.json"
+    pub pattern: String,
+    // 处理请求前固定等待的毫秒数
+    #[serde(default)]
+    pub latency_ms: u64,
+    // 在 latency_ms 之上再随机等待 0 ~ jitter_ms 毫秒
+    #[serde(default)]
+    pub jitter_ms: u64,
+    // 直接返回错误的请求比例（0 ~ 1）
+    #[serde(default)]
+    pub error_rate: f64,
+    // 错误响应的状态码
+    #[serde(default = "default_error_status")]
+    pub error_status: u16,
+    // 响应体在随机位置中断的请求比例（0 ~ 1），客户端看到连接提前断开
+    #[serde(default)]
+    pub truncate_rate: f64,
+    // 响应体的发送速率上限（字节/秒）
+    #[serde(default)]
+    pub bandwidth: Option<u64>,
+}
+
+fn default_error_status() -> u16 {
+    500
+}
+
+struct Rule {
+    pattern: PathPattern,
+    latency: Duration,
+    jitter: Duration,
+    error_rate: f64,
+    error_status: StatusCode,
+    truncate_rate: f64,
+    bandwidth: Option<u64>,
+}
+
+pub struct Chaos {
+    rules: Vec<Rule>,
+}
+
+impl Chaos {
+    pub fn new(config: &ChaosConfig) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for rule in &config.rules {
+            let rate = |name: &str, value: f64| {
+                if (0.0..=1.0).contains(&value) {
+                    Ok(value)
+                } else {
+                    Err(format!(
+                        "[chaos] {} for `{}` must be between 0 and 1",
+                        name, rule.pattern
+                    ))
+                }
+            };
+            if rule.bandwidth == Some(0) {
+                return Err(format!(
+                    "[chaos] bandwidth for `{}` must be greater than 0",
+                    rule.pattern
+                ));
+            }
+            rules.push(Rule {
+                pattern: PathPattern::new(&rule.pattern)?,
+                latency: Duration::from_millis(rule.latency_ms),
+                jitter: Duration::from_millis(rule.jitter_ms),
+                error_rate: rate("error_rate", rule.error_rate)?,
+                error_status: StatusCode::from_u16(rule.error_status)
+                    .map_err(|e| format!("invalid error_status for `{}`: {}", rule.pattern, e))?,
+                truncate_rate: rate("truncate_rate", rule.truncate_rate)?,
+                bandwidth: rule.bandwidth,
+            });
+        }
+        Ok(Chaos { rules })
+    }
+
+    pub fn rules(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+pub async fn inject(State(chaos): State<Arc<Chaos>>, req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    let Some(rule) = chaos.rules.iter().find(|rule| rule.pattern.matches(path)) else {
+        return next.run(req).await;
+    };
+    let delay = rule.latency + rule.jitter.mul_f64(random());
+    if !delay.is_zero() {
+        tokio::time::sleep(delay).await;
+    }
+    if rule.error_rate > 0.0 && random() < rule.error_rate {
+        debug!("Chaos: {} for {}", rule.error_status, path);
+        let mut response = (rule.error_status, "Injected failure\n").into_response();
+        response
+            .headers_mut()
+            .insert(X_CHAOS, HeaderValue::from_static("error"));
+        return response;
+    }
+    let truncate = rule.truncate_rate > 0.0 && random() < rule.truncate_rate;
+    let bandwidth = rule.bandwidth;
+    let mut response = next.run(req).await;
+    if truncate {
+        // 在声明的长度内随机截断；长度未知时最多发送 64 KiB
+        let limit = response
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok()?.parse().ok())
+            .or_else(|| response.body().size_hint().exact())
+            .unwrap_or(64 * 1024);
+        let limit = (limit as f64 * random()) as u64;
+        response
+            .headers_mut()
+            .insert(X_CHAOS, HeaderValue::from_static("truncate"));
+        response = response.map(|body| {
+            Body::new(Truncated {
+                inner: body,
+                remaining: limit,
+                sleep: None,
+            })
+        });
+    }
+    match bandwidth {
+        Some(rate) => response.map(|body| throttle::limit(body, rate)),
+        None => response,
+    }
+}
+
+// 发送 remaining 字节后以错误结束，连接被中断而不是正常结束
+struct Truncated {
+    inner: Body,
+    remaining: u64,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl HttpBody for Truncated {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let this = &mut *self;
+        if this.remaining == 0 {
+            // 稍等片刻再中断，让已发送的响应头与数据先写到客户端
+            let sleep = this
+                .sleep
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep(FLUSH_DELAY)));
+            ready!(sleep.as_mut().poll(cx));
+            return Poll::Ready(Some(Err(axum::Error::new("response truncated by [chaos]"))));
+        }
+        match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+            Some(Ok(frame)) => match frame.into_data() {
+                Ok(mut data) => {
+                    if data.len() as u64 > this.remaining {
+                        data.truncate(this.remaining as usize);
+                    }
+                    this.remaining -= data.len() as u64;
+                    Poll::Ready(Some(Ok(Frame::data(data))))
+                }
+                Err(frame) => Poll::Ready(Some(Ok(frame))),
+            },
+            other => Poll::Ready(other),
+        }
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// [0, 1) 的伪随机数（SplitMix64），只用于故障注入、采样等与安全无关的场合
+pub fn random() -> f64 {
+    static STATE: LazyLock<AtomicU64> = LazyLock::new(|| {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        AtomicU64::new(nanos ^ u64::from(std::process::id()))
+    });
+    let mut z = STATE
+        .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+        .wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,97 @@
+// check 子命令：解析配置文件，检查站点目录可读、目录覆盖文件与 TLS 证书，并像启动时一样编译所有规则，不绑定端口；
+// 有错误时逐条输出并以非零状态退出，可在 CI 中作为部署前的检查
+use crate::cli::CheckArgs;
+use crate::config::Config;
+use crate::listener::ListenSpec;
+use crate::live_reload::OnChange;
+use crate::s3::Backend;
+use crate::shutdown::Readiness;
+use crate::tls::Tls;
+use crate::{app, archive, dir_overrides, runtime};
+use std::fs;
+use std::path::Path;
+
+pub fn run(args: CheckArgs) {
+    let path = args.config.display().to_string();
+    // 解析错误带有行列号与出错的片段
+    let config = match Config::from_file(&args.config) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let mut errors = Vec::new();
+    for (what, dir) in site_dirs(&config) {
+        if let Err(e) = readable(&dir) {
+            errors.push(format!("{} `{}`: {}", what, dir, e));
+            continue;
+        }
+        // 覆盖文件在请求时才读取，这里逐个解析
+        if let Some(dir_overrides) = &config.dir_overrides {
+            if !archive::is_archive(&dir) {
+                errors.extend(dir_overrides::check(dir_overrides, Path::new(&dir)));
+            }
+        }
+    }
+    for entry in &config.listen {
+        if let Err(e) = ListenSpec::parse_all(entry) {
+            errors.push(format!("listen: {}", e));
+        }
+    }
+    if let Some(build) = &config.build {
+        if let Err(e) = OnChange::new(build) {
+            errors.push(format!("[build] watch: {}", e));
+        }
+    }
+    if let Err(e) = runtime::validate(&config.runtime) {
+        errors.push(e);
+    }
+    // 证书与私钥在启动时加载，这里同样加载并检查是否配对
+    if config.listen.iter().any(|entry| entry.options.tls) {
+        if let Err(e) = Tls::new(config.tls.as_ref(), &config.vhost) {
+            errors.push(format!("tls: {}", e));
+        }
+    }
+    // 目录不可读时组装站点必然失败，不再重复报告
+    if errors.is_empty() {
+        if let Err(e) = app::build(&config, Readiness::new(), None) {
+            errors.push(e);
+        }
+    }
+
+    if errors.is_empty() {
+        println!("{}: OK", path);
+        return;
+    }
+    for error in &errors {
+        eprintln!("{}: {}", path, error);
+    }
+    eprintln!("{}: {} error(s)", path, errors.len());
+    std::process::exit(1);
+}
+
+// 磁盘上的主目录、挂载点与虚拟主机目录；内嵌资源与对象存储不在其中
+fn site_dirs(config: &Config) -> Vec<(String, String)> {
+    let mut dirs = Vec::new();
+    if !config.embedded && config.backend == Backend::Fs {
+        dirs.push(("static_dir".to_string(), config.site_dir()));
+    }
+    for mount in &config.mount {
+        dirs.push((format!("[[mount]] {}", mount.prefix), mount.dir.clone()));
+    }
+    for vhost in &config.vhost {
+        dirs.push((format!("[[vhost]] {}", vhost.host), vhost.dir.clone()));
+    }
+    dirs
+}
+
+// 归档文件按站点提供，只需能打开
+fn readable(dir: &str) -> std::io::Result<()> {
+    if archive::is_archive(dir) {
+        fs::File::open(dir).map(|_| ())
+    } else {
+        fs::read_dir(dir).map(|_| ())
+    }
+}
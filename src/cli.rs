@@ -0,0 +1,228 @@
+// 命令行参数定义
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "sonic-wave", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Watch the site directories and live-reload connected browsers on change
+    #[arg(long, global = true)]
+    pub watch: bool,
+    /// Development mode: no-store caching, permissive CORS, directory listings, live reload and debug logs
+    #[arg(long, global = true)]
+    pub dev: bool,
+    /// Overlay the [profile.NAME] table of config.toml on the base configuration (or set SONICWAVE_PROFILE)
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+    /// Open the default browser at PATH (default "/") once the server is listening
+    #[arg(long, global = true, value_name = "PATH", num_args = 0..=1, default_missing_value = "/")]
+    pub open: Option<String>,
+    /// Detach from the terminal and keep running in the background
+    #[cfg(unix)]
+    #[arg(long, global = true)]
+    pub daemon: bool,
+    /// Write the process ID to PATH, refusing to start while it names a running process
+    #[cfg(unix)]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub pid_file: Option<PathBuf>,
+    /// With --daemon, append stdout and stderr to PATH instead of discarding them
+    #[cfg(unix)]
+    #[arg(long, global = true, value_name = "PATH", requires = "daemon")]
+    pub log_file: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the static file server (default)
+    Serve(ServeArgs),
+    /// Run and monitor several worker processes sharing the port via SO_REUSEPORT
+    Supervise(SuperviseArgs),
+    /// Write .br/.gz/.zst sidecars for compressible files under a directory
+    Precompress(PrecompressArgs),
+    /// Write a JSON manifest mapping original names to fingerprinted files
+    Manifest(ManifestArgs),
+    /// Write a JSON manifest of sha384 Subresource Integrity hashes for JS and CSS files
+    Sri(SriArgs),
+    /// Hash a password read from stdin for [login] users
+    HashPassword(HashPasswordArgs),
+    /// Validate a configuration file without starting the server
+    Check(CheckArgs),
+    /// Load-test a running instance (URL) or a site directory served in-process
+    Bench(BenchArgs),
+    /// Print the effective configuration after defaults, config.toml and environment overrides
+    PrintConfig(PrintConfigArgs),
+    /// Print the JSON Schema of config.toml for editor completion and CI validation
+    Schema,
+    /// Print a shell completion script
+    Completions(CompletionsArgs),
+    /// Install, run or uninstall the Windows service
+    #[cfg(windows)]
+    Service(ServiceArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Print the effective configuration and exit instead of serving
+    #[arg(long)]
+    pub dry_run: bool,
+    #[command(flatten)]
+    pub print: PrintConfigArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct SuperviseArgs {
+    /// Number of worker processes (defaults to the number of CPU cores)
+    #[arg(short, long)]
+    pub workers: Option<usize>,
+    /// Seconds between aggregated stats reports
+    #[arg(long, default_value_t = 60)]
+    pub stats_interval: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct PrecompressArgs {
+    /// Directory to walk
+    pub dir: PathBuf,
+    /// Comma-separated formats to write: br, gz, zst
+    #[arg(long, default_value = "br,gz,zst")]
+    pub formats: String,
+    /// Skip files smaller than this many bytes
+    #[arg(long, default_value_t = 256)]
+    pub min_size: u64,
+    /// Number of parallel jobs (defaults to the number of CPU cores)
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
+    /// Rewrite sidecars even when they are newer than the source file
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ManifestArgs {
+    /// Directory to walk
+    pub dir: PathBuf,
+    /// Output file, or "-" for stdout (defaults to DIR/asset-manifest.json)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Filename regex whose first capture group is the hash
+    #[arg(long)]
+    pub pattern: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct SriArgs {
+    /// Directory to walk
+    pub dir: PathBuf,
+    /// Output file, or "-" for stdout (defaults to DIR/sri.json)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Comma-separated file extensions to hash
+    #[arg(long, default_value = "js,mjs,css")]
+    pub extensions: String,
+}
+
+#[derive(Args, Debug)]
+pub struct HashPasswordArgs {
+    /// PBKDF2-HMAC-SHA256 iterations
+    #[arg(long, default_value_t = 600_000)]
+    pub iterations: u32,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    /// Configuration file to validate
+    #[arg(default_value = "config.toml")]
+    pub config: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct BenchArgs {
+    /// URL of a running server, or a directory to serve in-process with config.toml
+    pub target: String,
+    /// Number of requests in flight at once
+    #[arg(short, long, default_value_t = 16)]
+    pub concurrency: usize,
+    /// Stop after this many requests instead of after --duration
+    #[arg(short = 'n', long)]
+    pub requests: Option<u64>,
+    /// Seconds to run for
+    #[arg(short, long, default_value_t = 10)]
+    pub duration: u64,
+    /// Path to request when TARGET is a directory; repeat to cycle through several (default "/")
+    #[arg(long, value_name = "PATH")]
+    pub path: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct PrintConfigArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ConfigFormat::Toml)]
+    pub format: ConfigFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct CompletionsArgs {
+    /// Shell to generate the script for
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+#[cfg(windows)]
+#[derive(Args, Debug)]
+pub struct ServiceArgs {
+    #[command(subcommand)]
+    pub action: ServiceAction,
+}
+
+#[cfg(windows)]
+#[derive(Subcommand, Debug)]
+pub enum ServiceAction {
+    /// Register an automatically started service serving DIR (defaults to the current directory)
+    Install(ServiceInstallArgs),
+    /// Run under the service control manager (the command line of the installed service)
+    Run(ServiceRunArgs),
+    /// Stop and remove the service
+    Uninstall(ServiceNameArgs),
+}
+
+#[cfg(windows)]
+#[derive(Args, Debug)]
+pub struct ServiceNameArgs {
+    /// Service name, so that several sites can be installed side by side
+    #[arg(long, default_value = "SonicWave")]
+    pub name: String,
+}
+
+#[cfg(windows)]
+#[derive(Args, Debug)]
+pub struct ServiceInstallArgs {
+    #[command(flatten)]
+    pub service: ServiceNameArgs,
+    /// Working directory of the service, containing config.toml
+    pub dir: Option<PathBuf>,
+}
+
+#[cfg(windows)]
+#[derive(Args, Debug)]
+pub struct ServiceRunArgs {
+    #[command(flatten)]
+    pub service: ServiceNameArgs,
+    /// Working directory of the service, containing config.toml
+    #[arg(long)]
+    pub dir: PathBuf,
+}
@@ -0,0 +1,234 @@
+// 客户端证书访问控制（[tls] client_rules）：按路径前缀只允许主题匹配的客户端证书访问，
+// 证书由握手时的 client_ca 验证，这里只比对主题；非 TLS 连接与未出示证书的请求一律拒绝
+use crate::site;
+use crate::tls::TlsInfo;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ClientRuleConfig {
+    // URL 路径前缀，如 "/mirror/"
+    pub prefix: String,
+    // 允许的证书主题（RFC 4514，如 "CN=*.build.example.com,O=Example"），* 匹配任意字符；
+    // 为空时任何有效证书都可以访问
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+struct Rule {
+    prefix: String,
+    allow: Vec<Regex>,
+}
+
+impl Rule {
+    fn permits(&self, subject: Option<&str>) -> bool {
+        let Some(subject) = subject else {
+            return false;
+        };
+        self.allow.is_empty() || self.allow.iter().any(|re| re.is_match(subject))
+    }
+}
+
+pub struct ClientRules {
+    rules: Vec<Rule>,
+}
+
+impl ClientRules {
+    pub fn new(config: &[ClientRuleConfig]) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for rule in config {
+            if !rule.prefix.starts_with('/') {
+                return Err(format!(
+                    "client rule prefix `{}` must start with '/'",
+                    rule.prefix
+                ));
+            }
+            let allow = rule
+                .allow
+                .iter()
+                .map(|pattern| {
+                    let source = pattern
+                        .split('*')
+                        .map(regex::escape)
+                        .collect::<Vec<_>>()
+                        .join(".*");
+                    Regex::new(&format!("^{}$", source))
+                        .map_err(|e| format!("invalid client subject pattern `{}`: {}", pattern, e))
+                })
+                .collect::<Result<_, _>>()?;
+            rules.push(Rule {
+                prefix: rule.prefix.clone(),
+                allow,
+            });
+        }
+        Ok(ClientRules { rules })
+    }
+
+    pub fn rules(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+pub async fn check(State(rules): State<Arc<ClientRules>>, req: Request, next: Next) -> Response {
+    let path = site::normalize_path(req.uri().path());
+    let Some(rule) = rules
+        .rules
+        .iter()
+        .find(|rule| path.starts_with(rule.prefix.as_str()))
+    else {
+        return next.run(req).await;
+    };
+    let subject = req
+        .extensions()
+        .get::<TlsInfo>()
+        .and_then(|tls| tls.client_subject.as_deref());
+    if !rule.permits(subject) {
+        debug!(
+            "Blocked {} for client certificate {}",
+            path,
+            subject.unwrap_or("(none)")
+        );
+        return (StatusCode::FORBIDDEN, "client certificate required").into_response();
+    }
+    next.run(req).await
+}
+
+// 证书主题按 RFC 4514 格式化：RDN 逆序、逗号分隔；解析失败时返回 None
+pub fn subject(cert: &CertificateDer<'_>) -> Option<String> {
+    let (_, certificate, _) = der(cert)?;
+    let (_, mut tbs, _) = der(certificate)?;
+    // version [0] 可省略，之后依次是 serialNumber、signature、issuer、validity、subject
+    let (tag, _, rest) = der(tbs)?;
+    if tag == 0xa0 {
+        tbs = rest;
+    }
+    for _ in 0..4 {
+        tbs = der(tbs)?.2;
+    }
+    let (tag, mut name, _) = der(tbs)?;
+    if tag != 0x30 {
+        return None;
+    }
+    let mut rdns = Vec::new();
+    while !name.is_empty() {
+        let (_, mut set, rest) = der(name)?;
+        name = rest;
+        let mut attributes = Vec::new();
+        while !set.is_empty() {
+            let (_, attribute, rest) = der(set)?;
+            set = rest;
+            let (_, oid, value) = der(attribute)?;
+            let (value_tag, content, rest) = der(value)?;
+            let encoded = &value[..value.len() - rest.len()];
+            attributes.push(format!(
+                "{}={}",
+                attribute_name(oid),
+                attribute_value(value_tag, content, encoded)
+            ));
+        }
+        rdns.push(attributes.join("+"));
+    }
+    rdns.reverse();
+    Some(rdns.join(","))
+}
+
+// DER 的一个 TLV：(标签, 内容, 剩余部分)
+fn der(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&first, mut input) = input.split_first()?;
+    let len = if first < 0x80 {
+        first as usize
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || input.len() < count {
+            return None;
+        }
+        let (bytes, rest) = input.split_at(count);
+        input = rest;
+        bytes.iter().fold(0usize, |len, b| (len << 8) | *b as usize)
+    };
+    if input.len() < len {
+        return None;
+    }
+    let (content, rest) = input.split_at(len);
+    Some((tag, content, rest))
+}
+
+fn attribute_name(oid: &[u8]) -> String {
+    match oid {
+        [0x55, 0x04, 0x03] => "CN".to_string(),
+        [0x55, 0x04, 0x06] => "C".to_string(),
+        [0x55, 0x04, 0x07] => "L".to_string(),
+        [0x55, 0x04, 0x08] => "ST".to_string(),
+        [0x55, 0x04, 0x09] => "STREET".to_string(),
+        [0x55, 0x04, 0x0a] => "O".to_string(),
+        [0x55, 0x04, 0x0b] => "OU".to_string(),
+        [0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x01] => "UID".to_string(),
+        [0x09, 0x92, 0x26, 0x89, 0x93, 0xf2, 0x2c, 0x64, 0x01, 0x19] => "DC".to_string(),
+        [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x01] => "emailAddress".to_string(),
+        _ => dotted_oid(oid),
+    }
+}
+
+fn dotted_oid(oid: &[u8]) -> String {
+    let mut arcs = Vec::new();
+    let mut arc = 0u64;
+    for &b in oid {
+        arc = (arc << 7) | (b & 0x7f) as u64;
+        if b & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (arc / 40).min(2);
+                arcs.push(first);
+                arcs.push(arc - first * 40);
+            } else {
+                arcs.push(arc);
+            }
+            arc = 0;
+        }
+    }
+    arcs.iter()
+        .map(u64::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// 字符串按 RFC 4514 转义，其他类型输出为 # 加完整 DER 编码的十六进制
+fn attribute_value(tag: u8, value: &[u8], encoded: &[u8]) -> String {
+    let text = match tag {
+        // UTF8String、PrintableString、IA5String
+        0x0c | 0x13 | 0x16 => String::from_utf8_lossy(value).into_owned(),
+        // BMPString
+        0x1e => {
+            let units: Vec<u16> = value
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return format!("#{}", hex(encoded)),
+    };
+    let mut escaped = String::with_capacity(text.len());
+    let last = text.chars().count().saturating_sub(1);
+    for (i, c) in text.chars().enumerate() {
+        let special = matches!(c, ',' | '+' | '"' | '\\' | '<' | '>' | ';')
+            || (i == 0 && matches!(c, '#' | ' '))
+            || (i == last && c == ' ');
+        if special {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
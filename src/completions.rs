@@ -0,0 +1,16 @@
+// completions 子命令：由 clap_complete 按命令定义生成 bash / zsh / fish 补全脚本，
+// 子命令与选项增减后无需手动维护脚本
+use crate::cli::{Cli, CompletionsArgs, Shell};
+use clap::CommandFactory;
+use clap_complete::shells;
+
+pub fn run(args: CompletionsArgs) {
+    let mut cli = Cli::command();
+    let bin = cli.get_name().to_string();
+    let mut out = std::io::stdout();
+    match args.shell {
+        Shell::Bash => clap_complete::generate(shells::Bash, &mut cli, bin, &mut out),
+        Shell::Zsh => clap_complete::generate(shells::Zsh, &mut cli, bin, &mut out),
+        Shell::Fish => clap_complete::generate(shells::Fish, &mut cli, bin, &mut out),
+    }
+}
@@ -0,0 +1,466 @@
+// 实时压缩（[compression]）：没有预压缩旁路文件的响应按 Accept-Encoding 用 br / zstd / gzip 压缩。
+// 各算法的级别、最小与最大长度、按 MIME 的允许 / 排除列表可配置；默认不再压缩音视频、图片、
+// woff2 与压缩包等已压缩的格式。只压缩长度已知的完整 200 响应，流式响应（如 SSE）原样发送；
+// 设置 cache_dir 时带 ETag 的响应的压缩结果保存在磁盘上，重复请求不必再压缩
+use crate::compression_cache::DiskCache;
+use crate::glob;
+use crate::server_timing::Timings;
+use crate::vary;
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::response::Parts;
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CompressionConfig {
+    // 可用的算法，客户端的 q 值相同时按此顺序优先
+    #[serde(default = "default_algorithms")]
+    pub algorithms: Vec<String>,
+    #[serde(default)]
+    pub levels: CompressionLevels,
+    // 小于该长度（字节）的响应不压缩
+    #[serde(default = "default_min_size")]
+    pub min_size: u64,
+    // 大于该长度的响应不压缩，避免把大文件读入内存
+    #[serde(default = "default_max_size")]
+    pub max_size: u64,
+    // 压缩的 Content-Type，支持 "text/*"、"application/*+json" 这样的通配符
+    #[serde(default = "default_mime_types")]
+    pub mime_types: Vec<String>,
+    // 不压缩的 Content-Type，优先于 mime_types
+    #[serde(default)]
+    pub exclude_mime_types: Vec<String>,
+    // 不压缩音视频、位图、woff / woff2、压缩包等本身已压缩的格式，即使 mime_types 包含它们
+    #[serde(default = "default_true")]
+    pub skip_compressed_media: bool,
+    // 压缩结果的磁盘缓存目录，未设置时不缓存；只缓存带 ETag 或 Last-Modified 的响应
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+    // 磁盘缓存的总大小上限（字节），超出时删除最久未使用的条目
+    #[serde(default = "default_cache_max_bytes")]
+    pub cache_max_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CompressionLevels {
+    // 1 ~ 9
+    #[serde(default = "default_gzip_level")]
+    pub gzip: u32,
+    // 0 ~ 11，高级别很慢，实时压缩用中等级别，离线压缩见 precompress 子命令
+    #[serde(default = "default_br_level")]
+    pub br: u32,
+    // 1 ~ 22
+    #[serde(default = "default_zstd_level")]
+    pub zstd: i32,
+}
+
+impl Default for CompressionLevels {
+    fn default() -> Self {
+        CompressionLevels {
+            gzip: default_gzip_level(),
+            br: default_br_level(),
+            zstd: default_zstd_level(),
+        }
+    }
+}
+
+fn default_algorithms() -> Vec<String> {
+    vec!["br".to_string(), "zstd".to_string(), "gzip".to_string()]
+}
+
+fn default_gzip_level() -> u32 {
+    6
+}
+
+fn default_br_level() -> u32 {
+    4
+}
+
+fn default_zstd_level() -> i32 {
+    3
+}
+
+fn default_min_size() -> u64 {
+    1024
+}
+
+fn default_max_size() -> u64 {
+    8 * 1024 * 1024
+}
+
+fn default_cache_max_bytes() -> u64 {
+    256 * 1024 * 1024
+}
+
+fn default_mime_types() -> Vec<String> {
+    [
+        "text/*",
+        "application/javascript",
+        "application/json",
+        "application/*+json",
+        "application/xml",
+        "application/*+xml",
+        "application/wasm",
+        "image/svg+xml",
+        "image/x-icon",
+        "font/ttf",
+        "font/otf",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// 本身已压缩的格式，压缩只浪费 CPU
+fn compressed_media(mime: &str) -> bool {
+    let svg_or_icon = mime == "image/svg+xml" || mime == "image/x-icon";
+    ((mime.starts_with("image/") && !svg_or_icon)
+        || mime.starts_with("audio/")
+        || mime.starts_with("video/"))
+        || matches!(
+            mime,
+            "font/woff"
+                | "font/woff2"
+                | "application/zip"
+                | "application/gzip"
+                | "application/x-bzip2"
+                | "application/x-xz"
+                | "application/zstd"
+                | "application/x-7z-compressed"
+                | "application/vnd.rar"
+                | "application/ogg"
+                | "application/pdf"
+        )
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Brotli,
+    Zstd,
+    Gzip,
+}
+
+impl Algorithm {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "br" | "brotli" => Some(Algorithm::Brotli),
+            "zstd" | "zst" => Some(Algorithm::Zstd),
+            "gzip" | "gz" => Some(Algorithm::Gzip),
+            _ => None,
+        }
+    }
+
+    fn token(self) -> &'static str {
+        match self {
+            Algorithm::Brotli => "br",
+            Algorithm::Zstd => "zstd",
+            Algorithm::Gzip => "gzip",
+        }
+    }
+
+    fn level(self, levels: &CompressionLevels) -> String {
+        match self {
+            Algorithm::Brotli => levels.br.to_string(),
+            Algorithm::Zstd => levels.zstd.to_string(),
+            Algorithm::Gzip => levels.gzip.to_string(),
+        }
+    }
+}
+
+pub struct Compression {
+    algorithms: Vec<Algorithm>,
+    levels: CompressionLevels,
+    min_size: u64,
+    max_size: u64,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+    skip_compressed_media: bool,
+    cache: Option<Arc<DiskCache>>,
+}
+
+impl Compression {
+    pub fn new(config: &CompressionConfig) -> Result<Self, String> {
+        let mut algorithms = Vec::new();
+        for name in &config.algorithms {
+            let algorithm = Algorithm::parse(name)
+                .ok_or_else(|| format!("unknown [compression] algorithm `{}`", name))?;
+            if !algorithms.contains(&algorithm) {
+                algorithms.push(algorithm);
+            }
+        }
+        let levels = &config.levels;
+        if !(1..=9).contains(&levels.gzip) {
+            return Err("[compression] gzip level must be between 1 and 9".to_string());
+        }
+        if levels.br > 11 {
+            return Err("[compression] br level must be between 0 and 11".to_string());
+        }
+        if !(1..=22).contains(&levels.zstd) {
+            return Err("[compression] zstd level must be between 1 and 22".to_string());
+        }
+        let compile = |patterns: &[String]| -> Result<Vec<Regex>, String> {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    Regex::new(&glob::to_regex(&pattern.trim().to_ascii_lowercase()))
+                        .map_err(|e| format!("invalid MIME pattern `{}`: {}", pattern, e))
+                })
+                .collect()
+        };
+        let cache = match &config.cache_dir {
+            Some(dir) => {
+                let cache = DiskCache::new(dir, config.cache_max_bytes)?;
+                info!("Compression cache: {}", cache.describe());
+                Some(Arc::new(cache))
+            }
+            None => None,
+        };
+        Ok(Compression {
+            algorithms,
+            levels: levels.clone(),
+            min_size: config.min_size,
+            max_size: config.max_size,
+            include: compile(&config.mime_types)?,
+            exclude: compile(&config.exclude_mime_types)?,
+            skip_compressed_media: config.skip_compressed_media,
+            cache,
+        })
+    }
+
+    fn compressible(&self, mime: &str) -> bool {
+        if self.skip_compressed_media && compressed_media(mime) {
+            return false;
+        }
+        self.include.iter().any(|re| re.is_match(mime))
+            && !self.exclude.iter().any(|re| re.is_match(mime))
+    }
+
+    // q 值最高的可用算法，相同时按配置的顺序；q=0 表示拒绝，"*" 匹配未列出的算法
+    fn choose(&self, accept: &str) -> Option<Algorithm> {
+        let mut listed = Vec::new();
+        let mut wildcard = None;
+        for item in accept.split(',') {
+            let mut params = item.split(';');
+            let name = params.next().unwrap_or("").trim().to_ascii_lowercase();
+            let q = params
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            if name == "*" {
+                wildcard = Some(q);
+            } else if let Some(algorithm) = Algorithm::parse(&name) {
+                listed.push((algorithm, q));
+            }
+        }
+        let mut best: Option<(Algorithm, f32)> = None;
+        for &algorithm in &self.algorithms {
+            let q = listed
+                .iter()
+                .find(|(a, _)| *a == algorithm)
+                .map(|(_, q)| *q)
+                .or(wildcard)
+                .unwrap_or(0.0);
+            if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+                best = Some((algorithm, q));
+            }
+        }
+        best.map(|(algorithm, _)| algorithm)
+    }
+
+    fn compress(&self, algorithm: Algorithm, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        match algorithm {
+            Algorithm::Brotli => {
+                let mut out = Vec::new();
+                {
+                    let mut writer =
+                        brotli::CompressorWriter::new(&mut out, 64 * 1024, self.levels.br, 22);
+                    writer.write_all(data)?;
+                }
+                Ok(out)
+            }
+            Algorithm::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    Vec::new(),
+                    flate2::Compression::new(self.levels.gzip),
+                );
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Algorithm::Zstd => zstd::encode_all(data, self.levels.zstd),
+        }
+    }
+}
+
+pub async fn compress(
+    State(compression): State<Arc<Compression>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let accept = req
+        .headers()
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    // HEAD 没有正文可以压缩，按未压缩的版本返回
+    let is_head = req.method() == Method::HEAD;
+    let timings = Timings::from_extensions(req.extensions());
+    // 磁盘缓存的键包含主机，不同虚拟主机的同名文件互不影响
+    let location = compression.cache.as_ref().map(|_| {
+        let host = req
+            .uri()
+            .host()
+            .or_else(|| req.headers().get(header::HOST)?.to_str().ok())
+            .unwrap_or("")
+            .to_string();
+        (host, req.uri().path().to_string())
+    });
+    let mut response = next.run(req).await;
+    let headers = response.headers();
+    let mime = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_ascii_lowercase()
+        });
+    let Some(mime) = mime.filter(|mime| compression.compressible(mime)) else {
+        return response;
+    };
+    if headers.contains_key(header::CONTENT_ENCODING)
+        || headers.contains_key(header::CONTENT_RANGE)
+        || headers
+            .get(header::CACHE_CONTROL)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.to_ascii_lowercase().contains("no-transform"))
+    {
+        return response;
+    }
+    // 同一 URL 可能返回压缩与未压缩两种版本
+    vary::add(response.headers_mut(), "Accept-Encoding");
+    let length = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok());
+    let Some(length) = length else {
+        return response;
+    };
+    if response.status() != StatusCode::OK
+        || is_head
+        || length < compression.min_size
+        || length > compression.max_size
+    {
+        return response;
+    }
+    let Some(algorithm) = accept
+        .as_deref()
+        .and_then(|accept| compression.choose(accept))
+    else {
+        return response;
+    };
+
+    let (parts, body) = response.into_parts();
+    // 以 ETag、Last-Modified 与长度作为内容的版本，两个验证器都没有的响应不缓存
+    let validator = [header::ETAG, header::LAST_MODIFIED]
+        .iter()
+        .filter_map(|name| parts.headers.get(name)?.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(";");
+    let cached = match (&compression.cache, &location) {
+        (Some(cache), Some((host, path))) if !validator.is_empty() => {
+            let level = algorithm.level(&compression.levels);
+            let length = length.to_string();
+            let key = DiskCache::key(
+                &[host, path, &validator, &length, &level],
+                algorithm.token(),
+            );
+            Some((cache.clone(), key))
+        }
+        _ => None,
+    };
+    if let Some((cache, key)) = &cached {
+        let started = Instant::now();
+        if let Some(data) = cache.get(key).await {
+            if let Some(timings) = &timings {
+                let desc = format!("{}, cached", algorithm.token());
+                timings.record("compress", started.elapsed(), Some(&desc));
+            }
+            return encoded(parts, algorithm, data);
+        }
+    }
+    let data = match axum::body::to_bytes(body, compression.max_size as usize).await {
+        Ok(data) => data,
+        Err(e) => {
+            warn!(
+                "Failed to read response body for compression ({}): {}",
+                mime, e
+            );
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let started = Instant::now();
+    let compressed = {
+        let compression = compression.clone();
+        let data = data.clone();
+        tokio::task::spawn_blocking(move || compression.compress(algorithm, &data)).await
+    };
+    let compressed = match compressed {
+        Ok(Ok(compressed)) if compressed.len() < data.len() => compressed,
+        Ok(Err(e)) => {
+            warn!(
+                "Failed to compress {} with {}: {}",
+                mime,
+                algorithm.token(),
+                e
+            );
+            return Response::from_parts(parts, Body::from(data));
+        }
+        // 压缩后没有变小
+        _ => return Response::from_parts(parts, Body::from(data)),
+    };
+    if let Some(timings) = &timings {
+        timings.record("compress", started.elapsed(), Some(algorithm.token()));
+    }
+    let compressed = Bytes::from(compressed);
+    if let Some((cache, key)) = cached {
+        let data = compressed.clone();
+        tokio::spawn(async move { cache.put(key, data).await });
+    }
+    encoded(parts, algorithm, compressed)
+}
+
+fn encoded(mut parts: Parts, algorithm: Algorithm, compressed: Bytes) -> Response {
+    let headers = &mut parts.headers;
+    headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(algorithm.token()),
+    );
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(compressed.len()));
+    // 压缩后的字节与原文不同：不支持对其 Range，ETag 降为弱校验
+    headers.remove(header::ACCEPT_RANGES);
+    if let Some(etag) = headers.get(header::ETAG).and_then(|v| v.to_str().ok()) {
+        if !etag.starts_with("W/") {
+            if let Ok(weak) = HeaderValue::from_str(&format!("W/{}", etag)) {
+                headers.insert(header::ETAG, weak);
+            }
+        }
+    }
+    Response::from_parts(parts, Body::from(compressed))
+}
@@ -0,0 +1,231 @@
+// 实时压缩结果的磁盘缓存（[compression] cache_dir）：按 (主机, 路径, 编码与级别, ETag / Last-Modified) 保存压缩后的正文，
+// 同一文件的重复请求不必再压缩。总大小超过上限时按 LRU 删除；文件变化后验证器改变，旧条目不再命中，随 LRU 淘汰。
+// 启动时载入目录中已有的条目，按修改时间排定先后
+use crate::metrics::METRICS;
+use axum::body::Bytes;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::warn;
+
+// 临时文件的序号，同一条目的并发写入互不覆盖
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+// DiskCache::key 生成的文件名
+fn entry_name(name: &str) -> bool {
+    name.split_once('.').is_some_and(|(hash, encoding)| {
+        hash.len() == 32
+            && hash.bytes().all(|b| b.is_ascii_hexdigit())
+            && matches!(encoding, "br" | "zstd" | "gzip")
+    })
+}
+
+struct Entry {
+    size: u64,
+    // LRU 序号，越大越新
+    tick: u64,
+}
+
+#[derive(Default)]
+struct Index {
+    entries: HashMap<String, Entry>,
+    lru: BTreeMap<u64, String>,
+    bytes: u64,
+    tick: u64,
+}
+
+impl Index {
+    fn insert(&mut self, name: String, size: u64) {
+        self.remove(&name);
+        self.tick += 1;
+        self.lru.insert(self.tick, name.clone());
+        self.entries.insert(
+            name,
+            Entry {
+                size,
+                tick: self.tick,
+            },
+        );
+        self.bytes += size;
+    }
+
+    fn remove(&mut self, name: &str) {
+        if let Some(entry) = self.entries.remove(name) {
+            self.lru.remove(&entry.tick);
+            self.bytes -= entry.size;
+        }
+    }
+
+    fn touch(&mut self, name: &str) -> bool {
+        self.tick += 1;
+        let tick = self.tick;
+        let Some(entry) = self.entries.get_mut(name) else {
+            return false;
+        };
+        self.lru.remove(&entry.tick);
+        entry.tick = tick;
+        self.lru.insert(tick, name.to_string());
+        true
+    }
+
+    // 超出上限时从最久未使用的条目开始删除，返回被删除的文件名
+    fn evict(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.bytes > max_bytes {
+            let Some((_, name)) = self.lru.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&name) {
+                self.bytes -= entry.size;
+            }
+            evicted.push(name);
+        }
+        evicted
+    }
+}
+
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<Index>,
+}
+
+impl DiskCache {
+    pub fn new(dir: &Path, max_bytes: u64) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            format!(
+                "failed to create compression cache {}: {}",
+                dir.display(),
+                e
+            )
+        })?;
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("failed to read compression cache {}: {}", dir.display(), e))?;
+        let mut found = Vec::new();
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if !meta.is_file() {
+                continue;
+            }
+            // 上次退出时没有写完的临时文件；目录中的其他文件不作为条目，也不会被删除
+            if name.contains(".tmp") && entry_name(name.split(".tmp").next().unwrap_or("")) {
+                let _ = std::fs::remove_file(entry.path());
+                continue;
+            }
+            if !entry_name(&name) {
+                continue;
+            }
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            found.push((modified, name, meta.len()));
+        }
+        found.sort();
+        let mut index = Index::default();
+        for (_, name, size) in found {
+            index.insert(name, size);
+        }
+        let cache = DiskCache {
+            dir: dir.to_path_buf(),
+            max_bytes,
+            index: Mutex::new(index),
+        };
+        let evicted = cache.index.lock().unwrap().evict(max_bytes);
+        for name in evicted {
+            let _ = std::fs::remove_file(cache.dir.join(name));
+        }
+        cache.report();
+        Ok(cache)
+    }
+
+    pub fn describe(&self) -> String {
+        let index = self.index.lock().unwrap();
+        format!(
+            "{} ({} entries, {} of {} bytes)",
+            self.dir.display(),
+            index.entries.len(),
+            index.bytes,
+            self.max_bytes
+        )
+    }
+
+    // 条目的文件名：各部分的哈希加编码名
+    pub fn key(parts: &[&str], encoding: &str) -> String {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        let hash: String = hasher.finalize()[..16]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        format!("{}.{}", hash, encoding)
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Bytes> {
+        if !self.index.lock().unwrap().touch(key) {
+            METRICS.record_compression_cache(false);
+            return None;
+        }
+        match tokio::fs::read(self.dir.join(key)).await {
+            Ok(data) => {
+                METRICS.record_compression_cache(true);
+                Some(Bytes::from(data))
+            }
+            // 被外部删除
+            Err(_) => {
+                self.index.lock().unwrap().remove(key);
+                self.report();
+                METRICS.record_compression_cache(false);
+                None
+            }
+        }
+    }
+
+    // 先写临时文件再改名，并发请求不会读到不完整的条目
+    pub async fn put(&self, key: String, data: Bytes) {
+        let size = data.len() as u64;
+        if size > self.max_bytes {
+            return;
+        }
+        let path = self.dir.join(&key);
+        let temp = self.dir.join(format!(
+            "{}.tmp{}-{}",
+            key,
+            std::process::id(),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = match tokio::fs::write(&temp, &data).await {
+            Ok(()) => tokio::fs::rename(&temp, &path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            warn!(
+                "Failed to write compression cache {}: {}",
+                path.display(),
+                e
+            );
+            let _ = tokio::fs::remove_file(&temp).await;
+            return;
+        }
+        let evicted = {
+            let mut index = self.index.lock().unwrap();
+            index.insert(key, size);
+            index.evict(self.max_bytes)
+        };
+        METRICS.record_compression_evictions(evicted.len() as u64);
+        for name in evicted {
+            let _ = tokio::fs::remove_file(self.dir.join(name)).await;
+        }
+        self.report();
+    }
+
+    fn report(&self) {
+        METRICS.set_compression_cache_bytes(self.index.lock().unwrap().bytes);
+    }
+}
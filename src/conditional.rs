@@ -0,0 +1,154 @@
+// 条件请求（RFC 9110 13.2.2）：站点内的文件服务各自处理 If-None-Match / If-Modified-Since，
+// If-Match、If-Unmodified-Since 与 If-Range 在这里按响应的 ETag / Last-Modified 统一判断；
+// 写入模式的 PUT / DELETE 按目标文件当前的状态判断
+use crate::index;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn parse_date(value: &HeaderValue) -> Option<SystemTime> {
+    httpdate::parse_http_date(value.to_str().ok()?).ok()
+}
+
+// HTTP 日期只精确到秒
+fn secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs()
+}
+
+// If-Unmodified-Since：表示没有修改时间时不成立；日期无效时忽略该头
+fn unmodified_since(since: &HeaderValue, modified: Option<SystemTime>) -> bool {
+    let Some(since) = parse_date(since) else {
+        return true;
+    };
+    modified.is_some_and(|modified| secs(modified) <= secs(since))
+}
+
+// If-Range 与响应的 ETag（强比较）或 Last-Modified 一致时才返回部分内容
+fn if_range_matches(if_range: &HeaderValue, headers: &HeaderMap) -> bool {
+    let Ok(if_range) = if_range.to_str() else {
+        return false;
+    };
+    let if_range = if_range.trim();
+    if if_range.starts_with('"') {
+        return headers
+            .get(header::ETAG)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|etag| etag == if_range);
+    }
+    let modified = headers.get(header::LAST_MODIFIED).and_then(parse_date);
+    httpdate::parse_http_date(if_range)
+        .is_ok_and(|date| modified.is_some_and(|modified| secs(modified) == secs(date)))
+}
+
+// If-Match 成立时（或未设置）再看 If-Unmodified-Since。站点的 ETag 由大小与 mtime 生成，都是弱 ETag，
+// If-Match 按弱比较，否则永远无法命中
+fn preconditions_hold(
+    if_match: Option<&HeaderValue>,
+    if_unmodified_since: Option<&HeaderValue>,
+    etag: Option<&HeaderValue>,
+    modified: Option<SystemTime>,
+) -> bool {
+    match (if_match, if_unmodified_since) {
+        (Some(tags), _) => tags
+            .to_str()
+            .is_ok_and(|tags| etag.is_some_and(|etag| index::etag_matches(tags, etag))),
+        (None, Some(since)) => unmodified_since(since, modified),
+        (None, None) => true,
+    }
+}
+
+fn precondition_failed() -> Response {
+    StatusCode::PRECONDITION_FAILED.into_response()
+}
+
+// 站点内的 GET / HEAD：If-None-Match 存在时去掉 If-Modified-Since，内层只按 ETag 判断 304；
+// 前提条件不满足时返回 412；If-Range 与部分内容的验证器不一致时不带 Range 重新请求，返回完整的新内容，
+// 避免断点续传把旧文件的前半段与新文件的后半段拼在一起
+pub async fn evaluate(mut req: Request, next: Next) -> Response {
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return next.run(req).await;
+    }
+    let headers = req.headers_mut();
+    let if_match = headers.remove(header::IF_MATCH);
+    let if_unmodified_since = headers.remove(header::IF_UNMODIFIED_SINCE);
+    let if_range = headers.remove(header::IF_RANGE);
+    if headers.contains_key(header::IF_NONE_MATCH) {
+        headers.remove(header::IF_MODIFIED_SINCE);
+    }
+    let retry = if_range
+        .as_ref()
+        .filter(|_| req.headers().contains_key(header::RANGE))
+        .map(|_| {
+            let mut retry = Request::new(Body::empty());
+            *retry.method_mut() = req.method().clone();
+            *retry.uri_mut() = req.uri().clone();
+            *retry.version_mut() = req.version();
+            *retry.headers_mut() = req.headers().clone();
+            *retry.extensions_mut() = req.extensions().clone();
+            retry.headers_mut().remove(header::RANGE);
+            retry
+        });
+
+    let response = next.clone().run(req).await;
+    let status = response.status();
+    // 其他状态（404 等）不受前提条件影响；304 时 If-Match 不成立同样返回 412
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        let modified = response
+            .headers()
+            .get(header::LAST_MODIFIED)
+            .and_then(parse_date);
+        let etag = response.headers().get(header::ETAG);
+        // 没有 ETag 的表示同样满足 If-Match: *
+        let any = if_match
+            .as_ref()
+            .is_some_and(|tags| tags.as_bytes() == b"*");
+        if !any
+            && !preconditions_hold(
+                if_match.as_ref(),
+                if_unmodified_since.as_ref(),
+                etag,
+                modified,
+            )
+        {
+            return precondition_failed();
+        }
+    }
+    // 文件变短后旧的范围可能无法满足，416 同样按完整内容返回
+    let partial =
+        status == StatusCode::PARTIAL_CONTENT || status == StatusCode::RANGE_NOT_SATISFIABLE;
+    match (if_range, retry) {
+        (Some(if_range), Some(retry))
+            if partial && !if_range_matches(&if_range, response.headers()) =>
+        {
+            next.run(retry).await
+        }
+        _ => response,
+    }
+}
+
+// 写入模式：current 为目标文件当前的元数据（不存在时为 None），ETag 与站点对该文件返回的一致，前提条件不满足时返回 412。
+// If-Match / If-Unmodified-Since 防止覆盖别人刚写入的版本，If-None-Match: * 只在文件不存在时创建
+pub fn check_write(headers: &HeaderMap, current: Option<&std::fs::Metadata>) -> Option<Response> {
+    let modified = current.and_then(|meta| meta.modified().ok());
+    let etag = current.map(|meta| index::file_entry(meta.len(), modified, None).etag);
+    let if_match = headers.get(header::IF_MATCH);
+    let if_unmodified_since = headers.get(header::IF_UNMODIFIED_SINCE);
+    let held = match if_match {
+        Some(tags) if tags.as_bytes() == b"*" => current.is_some(),
+        _ => preconditions_hold(if_match, if_unmodified_since, etag.as_ref(), modified),
+    };
+    let none_matched = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|tags| {
+            etag.as_ref()
+                .is_some_and(|etag| index::etag_matches(tags, etag))
+        });
+    (!held || none_matched).then(precondition_failed)
+}
@@ -0,0 +1,930 @@
+// 配置：config.toml 的结构与默认值，以及环境变量覆盖
+use crate::access_log::AccessLogRulesConfig;
+use crate::analytics::AnalyticsConfig;
+use crate::audio_meta::AudioMetaConfig;
+use crate::basic_auth::BasicAuthConfig;
+use crate::build::{self, BuildConfig};
+use crate::cache::CacheConfig;
+use crate::canary::CanaryConfig;
+use crate::canonical_host::CanonicalHostConfig;
+use crate::cdn::CdnConfig;
+use crate::chaos::ChaosConfig;
+use crate::cli::ConfigFormat;
+use crate::compression::CompressionConfig;
+use crate::cors::{CorsConfig, CorsPolicy};
+use crate::debug_artifacts::DebugArtifactsConfig;
+use crate::dir_overrides::DirOverridesConfig;
+use crate::early_hints::EarlyHintsConfig;
+use crate::favicon::FaviconConfig;
+use crate::fingerprint::FingerprintConfig;
+use crate::forward_auth::ForwardAuthConfig;
+use crate::geoip::GeoipConfig;
+use crate::git_site::{self, GitConfig};
+use crate::glob;
+use crate::hls::HlsConfig;
+use crate::hotlink::HotlinkConfig;
+use crate::i18n::I18nConfig;
+use crate::images::ImagesConfig;
+use crate::keep_alive::KeepAliveConfig;
+use crate::link_headers::LinkHeaderRule;
+use crate::listener::{self, ListenEntry, ListenerOptions, UnixSocketConfig};
+use crate::log_sink::LoggingConfig;
+use crate::login::LoginConfig;
+use crate::maintenance::MaintenanceConfig;
+use crate::markdown::MarkdownConfig;
+use crate::mdns::MdnsConfig;
+use crate::middleware::PipelineEntry;
+use crate::mirror::MirrorConfig;
+use crate::path_match::PathMatchConfig;
+use crate::path_validation::PathValidationConfig;
+use crate::playlist::PlaylistConfig;
+use crate::plugins::PluginConfig;
+use crate::podcast::PodcastConfig;
+use crate::prerender::PrerenderConfig;
+use crate::proxy::ProxyRule;
+use crate::releases::ReleasesConfig;
+use crate::reports::ReportsConfig;
+use crate::rewrite::{RedirectConfig, RewriteConfig};
+use crate::runtime::RuntimeConfig;
+use crate::runtime_env::RuntimeEnvConfig;
+use crate::s3::{self, Backend, S3Config};
+use crate::search::SearchConfig;
+use crate::security_headers::{PermissionsPolicyConfig, SecurityHeadersConfig};
+use crate::sentry::SentryConfig;
+use crate::server_header::ServerHeaderConfig;
+use crate::server_timing::ServerTimingConfig;
+use crate::service_worker::ServiceWorkerConfig;
+use crate::shadow::ShadowConfig;
+use crate::shutdown::ShutdownConfig;
+use crate::site::{self, CacheRule, FollowSymlinks, MountConfig, TrailingSlash};
+use crate::sitemap::{RobotsConfig, SitemapConfig};
+use crate::slow_client::SlowClientConfig;
+use crate::sri::SriConfig;
+use crate::ssi::SsiConfig;
+use crate::status::StatusConfig;
+use crate::templates::TemplatesConfig;
+use crate::tenants::TenantsConfig;
+use crate::throttle::ThrottleConfig;
+use crate::tls::TlsConfig;
+use crate::upload::UploadConfig;
+use crate::uring::IoBackend;
+use crate::user_agent::UserAgentRule;
+use crate::vhost::VhostConfig;
+use crate::waveform::WaveformConfig;
+use crate::zip_download::ZipDownloadConfig;
+use crate::{embed, mime};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::subscriber::NoSubscriber;
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct Config {
+    pub port: Option<u16>,
+    pub static_dir: Option<String>,
+    // --dev 开发模式，只能由命令行开启
+    #[serde(skip)]
+    pub dev: bool,
+    // 主站点使用编译时内嵌的资源（需要 embed feature，以该 feature 编译时默认开启），忽略 static_dir
+    #[serde(default = "default_embedded")]
+    pub embedded: bool,
+    // 主站点的存储后端："fs"（默认，static_dir）、"s3"（[s3] 中的 bucket）或 "git"（[git] 中的 bare 仓库），
+    // 后两者优先于 embedded
+    #[serde(default)]
+    pub backend: Backend,
+    #[serde(default = "default_cache_control")]
+    pub cache_control: String,
+    #[serde(default = "default_html_cache_control")]
+    pub html_cache_control: String,
+    // 按路径 glob 指定 Cache-Control（[[cache_rules]]），按顺序匹配，优先于上面两项
+    #[serde(default)]
+    pub cache_rules: Vec<CacheRule>,
+    // 带内容哈希的文件名使用长期 immutable 缓存（[fingerprint]），其余文件使用 html_cache_control
+    #[serde(default)]
+    pub fingerprint: Option<FingerprintConfig>,
+    // CDN / 共享缓存策略（[cdn]），未配置时只发送面向浏览器的 Cache-Control
+    #[serde(default)]
+    pub cdn: Option<CdnConfig>,
+    // 允许访问以 "." 开头的文件与目录（.well-known 始终允许）
+    #[serde(default)]
+    pub allow_dotfiles: bool,
+    // 拒绝访问的路径 glob，返回 404
+    #[serde(default = "site::default_deny")]
+    pub deny: Vec<String>,
+    // 符号链接策略：never / same-root（默认）/ always
+    #[serde(default)]
+    pub follow_symlinks: FollowSymlinks,
+    // 目录索引文件名，按顺序查找；空列表表示不解析索引文件
+    #[serde(default = "site::default_index_files")]
+    pub index_files: Vec<String>,
+    // 错误页（相对站点目录），如 { 404 = "404.html", "5xx" = "50x.html" }
+    #[serde(default)]
+    pub error_pages: BTreeMap<String, String>,
+    // /about 对应 about.html；clean_urls_redirect 时 /about.html 重定向到 /about
+    #[serde(default)]
+    pub clean_urls: bool,
+    #[serde(default)]
+    pub clean_urls_redirect: bool,
+    // 末尾斜杠策略：redirect-add（默认）/ redirect-strip / serve-both
+    #[serde(default)]
+    pub trailing_slash: TrailingSlash,
+    // 按扩展名覆盖 Content-Type，如 { "glb" = "model/gltf-binary" }
+    #[serde(default)]
+    pub mime: BTreeMap<String, String>,
+    // 文本类型默认追加的字符集（空字符串表示不追加），以及按扩展名的覆盖
+    #[serde(default = "mime::default_charset")]
+    pub charset: String,
+    #[serde(default)]
+    pub charset_by_extension: BTreeMap<String, String>,
+    // 以附件形式下载（Content-Disposition: attachment）的路径 glob
+    #[serde(default)]
+    pub download: Vec<String>,
+    // 不小于该大小（字节）的文件以内存映射方式发送，未设置时不启用
+    #[serde(default)]
+    pub mmap_min_size: Option<u64>,
+    // 文件读取后端：std（默认）/ uring（需要 io-uring feature，不可用时回退到 std）
+    #[serde(default)]
+    pub io_backend: IoBackend,
+    // 启动时遍历站点目录建立索引，路径解析与 404 判断不再访问文件系统
+    #[serde(default)]
+    pub preindex: bool,
+    // 启动时预先请求的路径或 glob（如 "/index.html"、"/assets/**.wasm"），填充内存缓存与预压缩版本
+    #[serde(default)]
+    pub preload: Vec<String>,
+    // 404 结果的缓存时间（秒），未设置时不缓存
+    #[serde(default)]
+    pub negative_cache_secs: Option<u64>,
+    // 存在 .br / .gz / .zst 旁路文件时按 Accept-Encoding 直接返回（见 precompress 子命令）
+    #[serde(default)]
+    pub precompressed: bool,
+    // 没有旁路文件时的实时压缩（[compression]），未配置时不压缩
+    #[serde(default)]
+    pub compression: Option<CompressionConfig>,
+    // 存在 .avif / .webp 旁路文件时按 Accept 返回（photo.jpg → photo.jpg.avif）
+    #[serde(default)]
+    pub image_variants: bool,
+    // 只读 WebDAV（OPTIONS / PROPFIND），可在 Finder、资源管理器中挂载站点目录
+    #[serde(default)]
+    pub webdav: bool,
+    // 没有索引文件的目录返回文件列表；--dev 时开启
+    #[serde(default)]
+    pub directory_listing: bool,
+    // 监听地址列表，支持 TCP 与 "unix:/path"；为空时监听 0.0.0.0:port
+    #[serde(default, deserialize_with = "listener::one_or_many")]
+    #[schemars(with = "listener::OneOrMany<ListenEntry>")]
+    pub listen: Vec<ListenEntry>,
+    // Unix socket 文件权限与属主
+    #[serde(default)]
+    pub unix_socket: UnixSocketConfig,
+    // 记录每个请求的访问日志
+    #[serde(default)]
+    pub access_log: bool,
+    // 访问日志的过滤与抽样（[access_log_rules]），未配置时记录所有请求
+    #[serde(default)]
+    pub access_log_rules: Option<AccessLogRulesConfig>,
+    // 日志输出：标准输出、syslog 或 journald（[logging]）
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    // 为每个请求分配 X-Request-ID 并传递 W3C traceparent，日志中带上请求 ID
+    #[serde(default)]
+    pub request_id: bool,
+    // 全局中间件的顺序（由外到内）与逐项设置，未设置时使用默认顺序
+    #[serde(default)]
+    pub pipeline: Option<Vec<PipelineEntry>>,
+    // 在 /__version 以 JSON 返回版本与构建信息
+    #[serde(default)]
+    pub version_endpoint: bool,
+    // 在 /__metrics 以 Prometheus 文本格式导出请求统计与响应耗时直方图
+    #[serde(default)]
+    pub metrics_endpoint: bool,
+    // Sentry 错误上报（[sentry]，需要以 sentry feature 编译）
+    #[serde(default)]
+    pub sentry: Option<SentryConfig>,
+    // Server 响应头（[server_header]），未配置时不发送，反向代理的响应保留上游的 Server 头
+    #[serde(default)]
+    pub server_header: Option<ServerHeaderConfig>,
+    // 在响应中发送 Server-Timing 头（[server_timing]），未配置时不发送
+    #[serde(default)]
+    pub server_timing: Option<ServerTimingConfig>,
+    // 绑定 0.0.0.0 时在启动信息中显示局域网地址的二维码（仅在终端中）
+    #[serde(default = "default_qr_code")]
+    pub qr_code: bool,
+    // 可信反向代理（CIDR 或 IP，"unix" 表示 Unix socket 对端），
+    // 仅信任这些来源的 Forwarded / X-Forwarded-* 头
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+    // 以 SO_REUSEPORT 绑定，允许新进程与旧进程同时监听同一端口
+    #[serde(default)]
+    pub reuse_port: bool,
+    // 绑定端口后切换到的账号（仅 Unix，需要以 root 启动）；未设置 group 时使用该用户的主组
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    // 用 Landlock 把进程限制在站点目录与缓存目录之内（仅 Linux）
+    #[serde(default)]
+    pub sandbox: bool,
+    // 沙箱中额外允许读取的路径
+    #[serde(default)]
+    pub sandbox_paths: Vec<String>,
+    // include 读取的片段所在的目录，由加载配置时填入
+    #[serde(skip)]
+    pub include_dirs: Vec<PathBuf>,
+    // 额外的挂载点（[[mount]]），将 URL 前缀映射到其他目录
+    #[serde(default)]
+    pub mount: Vec<MountConfig>,
+    // 基于 Host 头的虚拟主机（[[vhost]]）
+    #[serde(default)]
+    pub vhost: Vec<VhostConfig>,
+    // HTTPS 证书（[tls]），供 tls = true 的监听器使用
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    // 重定向与内部重写规则（[[redirect]] / [[rewrite]]），在路由之前生效
+    #[serde(default)]
+    pub redirect: Vec<RedirectConfig>,
+    #[serde(default)]
+    pub rewrite: Vec<RewriteConfig>,
+    // 规范主机（[canonical_host]），别名与 HTTP 请求 301 重定向到唯一的主机名与协议
+    #[serde(default)]
+    pub canonical_host: Option<CanonicalHostConfig>,
+    // 请求路径校验（[path_validation]），可疑的路径在到达文件服务之前返回 400
+    #[serde(default)]
+    pub path_validation: Option<PathValidationConfig>,
+    // 安全响应头（[security_headers]），默认只发送 COOP/COEP；[[mount]] / [[vhost]] 可逐项覆盖
+    #[serde(default)]
+    pub security_headers: SecurityHeadersConfig,
+    // 逐项配置的 Permissions-Policy（[permissions_policy]），与 security_headers.permissions_policy 二选一
+    #[serde(default)]
+    pub permissions_policy: Option<PermissionsPolicyConfig>,
+    // 整个站点的 Basic 认证（[auth]），[[mount]] / [[vhost]] 可替代或关闭
+    #[serde(default)]
+    pub auth: Option<BasicAuthConfig>,
+    // 登录页与 cookie 会话认证（[login]），保护配置的路径前缀
+    #[serde(default)]
+    pub login: Option<LoginConfig>,
+    // 转发认证（[forward_auth]），受保护的请求先由外部认证服务放行
+    #[serde(default)]
+    pub forward_auth: Option<ForwardAuthConfig>,
+    // 跨域资源共享（[cors]），未配置时不添加 Access-Control-* 响应头
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+    // 防盗链（[hotlink]），按 Referer 拒绝第三方站点嵌入媒体文件
+    #[serde(default)]
+    pub hotlink: Option<HotlinkConfig>,
+    // 调试文件拦截（[debug_artifacts]），source map 等只对带 token 的请求返回
+    #[serde(default)]
+    pub debug_artifacts: Option<DebugArtifactsConfig>,
+    // HTML 响应的 Link 头规则（[[link_headers]]）
+    #[serde(default)]
+    pub link_headers: Vec<LinkHeaderRule>,
+    // WASM 插件（[[plugin]]，需要以 wasm feature 编译），按路径模式修改请求头与响应头或直接返回响应
+    #[serde(default)]
+    pub plugin: Vec<PluginConfig>,
+    // Lua 脚本（script = "rules.lua"，需要以 lua feature 编译），在路由之前改写路径、请求头与响应头或直接返回响应
+    #[serde(default)]
+    pub script: Option<PathBuf>,
+    // User-Agent 过滤规则（[[user_agent]]），按顺序匹配，第一条匹配的规则生效
+    #[serde(default)]
+    pub user_agent: Vec<UserAgentRule>,
+    // 为爬虫返回预渲染的页面快照（[prerender]），普通访问者仍得到 SPA 入口页面
+    #[serde(default)]
+    pub prerender: Option<PrerenderConfig>,
+    // 按国家的访问控制（[geoip]），需要 MaxMind GeoLite2 数据库
+    #[serde(default)]
+    pub geoip: Option<GeoipConfig>,
+    // 下载限速（[throttle]），按全局、每个连接与路径规则限制响应体的发送速率
+    #[serde(default)]
+    pub throttle: Option<ThrottleConfig>,
+    // 开发用的故障注入（[chaos]），仅在 --watch 下生效
+    #[serde(default)]
+    pub chaos: Option<ChaosConfig>,
+    // 反向代理规则（[[proxy]]），按前缀转发到上游
+    #[serde(default)]
+    pub proxy: Vec<ProxyRule>,
+    // 热点文件内存缓存（[cache]），未配置时不启用
+    #[serde(default)]
+    pub cache: Option<CacheConfig>,
+    // 通过 mDNS 在局域网中发布服务（[mdns]），未配置时不启用
+    #[serde(default)]
+    pub mdns: Option<MdnsConfig>,
+    // Markdown 渲染（[markdown]），未配置时 .md 按原样返回
+    #[serde(default)]
+    pub markdown: Option<MarkdownConfig>,
+    // 服务端包含（[ssi]），未配置时不处理 include 指令
+    #[serde(default)]
+    pub ssi: Option<SsiConfig>,
+    // 运行时环境注入（[runtime_env]），未配置时 HTML 按原样返回
+    #[serde(default)]
+    pub runtime_env: Option<RuntimeEnvConfig>,
+    // 模板渲染（[templates]），未配置时 .hbs / .tera 按原样返回
+    #[serde(default)]
+    pub templates: Option<TemplatesConfig>,
+    // 多语言内容协商（[i18n]），未配置时不按 Accept-Language 选择内容
+    #[serde(default)]
+    pub i18n: Option<I18nConfig>,
+    // 图片按需缩放（[images]），未配置时忽略 ?w= 等参数
+    #[serde(default)]
+    pub images: Option<ImagesConfig>,
+    // 音频元数据（[audio_meta]），未配置时忽略 ?meta= 参数
+    #[serde(default)]
+    pub audio_meta: Option<AudioMetaConfig>,
+    // 波形峰值（[waveform]），未配置时忽略 ?waveform= 参数
+    #[serde(default)]
+    pub waveform: Option<WaveformConfig>,
+    // 音频 HLS 分段（[hls]），未配置时忽略 ?hls 参数
+    #[serde(default)]
+    pub hls: Option<HlsConfig>,
+    // 103 Early Hints（[early_hints]），未配置时不扫描 HTML
+    #[serde(default)]
+    pub early_hints: Option<EarlyHintsConfig>,
+    // Subresource Integrity（[sri]）：JS / CSS 的哈希清单与 HTML 中的 integrity 属性
+    #[serde(default)]
+    pub sri: Option<SriConfig>,
+    // 预缓存清单与生成的 service worker（[service_worker]），未配置时不提供
+    #[serde(default)]
+    pub service_worker: Option<ServiceWorkerConfig>,
+    // 站内搜索（[search]），为主目录中的页面建立全文索引
+    #[serde(default)]
+    pub search: Option<SearchConfig>,
+    // 播客订阅（[[podcast]]），把音频目录生成为 RSS feed
+    #[serde(default)]
+    pub podcast: Vec<PodcastConfig>,
+    // 按规则生成 /robots.txt（[robots]）
+    #[serde(default)]
+    pub robots: Option<RobotsConfig>,
+    // /favicon.ico 不存在时的兜底（[favicon]）
+    #[serde(default)]
+    pub favicon: Option<FaviconConfig>,
+    // 由目录中的 HTML 文件生成 sitemap.xml（[sitemap]）
+    #[serde(default)]
+    pub sitemap: Option<SitemapConfig>,
+    // 目录打包下载（[zip_download]），未配置时不启用
+    #[serde(default)]
+    pub zip_download: Option<ZipDownloadConfig>,
+    // 目录内的覆盖文件（[dir_overrides]），未配置时不读取
+    #[serde(default)]
+    pub dir_overrides: Option<DirOverridesConfig>,
+    // 精确路径不存在时不区分大小写 / 按 Unicode 规范等价查找（[path_matching]）
+    #[serde(default)]
+    pub path_matching: Option<PathMatchConfig>,
+    // 目录播放列表（[playlist]），未配置时不生成
+    #[serde(default)]
+    pub playlist: Option<PlaylistConfig>,
+    // 写入模式（[upload]），未配置时站点只读
+    #[serde(default)]
+    pub upload: Option<UploadConfig>,
+    // 拉取式镜像（[mirror]），主目录中缺少的文件从源站取回
+    #[serde(default)]
+    pub mirror: Option<MirrorConfig>,
+    // 金丝雀分流（[canary]），按比例把访问者分到另一个目录
+    #[serde(default)]
+    pub canary: Option<CanaryConfig>,
+    // 蓝绿部署（[releases]），通过端点把主目录切换到发布目录中的另一个版本
+    #[serde(default)]
+    pub releases: Option<ReleasesConfig>,
+    // 多租户模式（[tenants]），第一段路径或子域名对应一个租户目录
+    #[serde(default)]
+    pub tenants: Option<TenantsConfig>,
+    // backend = "s3" 时使用的对象存储（[s3]）
+    #[serde(default)]
+    pub s3: Option<S3Config>,
+    // backend = "git" 时使用的仓库（[git]）
+    #[serde(default)]
+    pub git: Option<GitConfig>,
+    // 可选的站点构建钩子
+    #[serde(default)]
+    pub build: Option<BuildConfig>,
+    // [build] 的旧写法：--watch 模式下文件变化时运行的命令与触发命令的 glob，加载时并入 [build]
+    #[serde(default, skip_serializing)]
+    pub on_change: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub on_change_watch: Vec<String>,
+    #[serde(default, skip_serializing)]
+    pub on_change_debounce_ms: Option<u64>,
+    // 优雅关闭与就绪检查
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    // 维护模式（[maintenance]）：开启后所有请求返回 503 维护页
+    #[serde(default)]
+    pub maintenance: Option<MaintenanceConfig>,
+    // 慢速客户端防护：请求头读取超时、响应写入超时与最低传输速率
+    #[serde(default)]
+    pub slow_clients: SlowClientConfig,
+    // 持久连接的空闲超时、最大请求数与最长存活时间
+    #[serde(default)]
+    pub keep_alive: KeepAliveConfig,
+    // tokio 运行时的线程设置，修改后需要重启
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    // 需要认证的状态页（[status]），未配置时不提供
+    #[serde(default)]
+    pub status: Option<StatusConfig>,
+    // 下载统计（[analytics]），按路径统计次数、字节数与独立客户端数
+    #[serde(default)]
+    pub analytics: Option<AnalyticsConfig>,
+    // 流量镜像（[shadow]），按比例把请求异步复制到另一个地址
+    #[serde(default)]
+    pub shadow: Option<ShadowConfig>,
+    // 浏览器报告（CSP、NEL、Reporting API）收集端点（[reports]），未配置时不提供
+    #[serde(default)]
+    pub reports: Option<ReportsConfig>,
+}
+
+fn default_cache_control() -> String {
+    "public, max-age=31536000, immutable".to_string()
+}
+
+fn default_html_cache_control() -> String {
+    "no-cache, must-revalidate".to_string()
+}
+
+fn default_qr_code() -> bool {
+    true
+}
+
+fn default_embedded() -> bool {
+    cfg!(feature = "embed")
+}
+
+impl Config {
+    // 读取指定的配置文件，不应用 PORT 等环境变量覆盖（--profile 选择的 profile 同样生效）；嵌入其他应用时使用
+    pub fn from_file(path: &Path) -> Result<Config, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        parse(&content, base_dir, active_profile().as_deref())
+            .map_err(|e| format!("failed to parse {}: {}", path.display(), e))
+    }
+
+    // --dev：所有响应 Cache-Control: no-store（由中间件覆盖）、允许任意来源跨域、记录访问日志、开启目录列表；
+    // COOP / COEP 照常发送，确保开发时与生产环境一样处于跨源隔离状态
+    pub fn apply_dev(&mut self) {
+        self.dev = true;
+        self.cache_control = "no-store".to_string();
+        self.html_cache_control = "no-store".to_string();
+        self.cdn = None;
+        self.access_log = true;
+        self.directory_listing = true;
+        self.cors = Some(CorsConfig {
+            policy: CorsPolicy {
+                origins: Some(vec!["*".to_string()]),
+                methods: Some(
+                    ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"]
+                        .iter()
+                        .map(|method| method.to_string())
+                        .collect(),
+                ),
+                credentials: Some(true),
+                ..CorsPolicy::default()
+            },
+            paths: Vec::new(),
+        });
+    }
+
+    // 主站点的目录；对象存储与内嵌资源使用各自的虚拟根目录
+    pub fn site_dir(&self) -> String {
+        if self.backend == Backend::S3 {
+            s3::ROOT.to_string()
+        } else if self.backend == Backend::Git {
+            git_site::ROOT.to_string()
+        } else if self.embedded {
+            embed::ROOT.to_string()
+        } else {
+            self.static_dir.clone().unwrap_or_else(|| ".".to_string())
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            port: Some(8089),
+            static_dir: Some(".".to_string()),
+            dev: false,
+            embedded: default_embedded(),
+            backend: Backend::default(),
+            cache_control: default_cache_control(),
+            html_cache_control: default_html_cache_control(),
+            cache_rules: Vec::new(),
+            fingerprint: None,
+            cdn: None,
+            allow_dotfiles: false,
+            deny: site::default_deny(),
+            follow_symlinks: FollowSymlinks::default(),
+            index_files: site::default_index_files(),
+            error_pages: BTreeMap::new(),
+            clean_urls: false,
+            clean_urls_redirect: false,
+            trailing_slash: TrailingSlash::default(),
+            mime: BTreeMap::new(),
+            charset: mime::default_charset(),
+            charset_by_extension: BTreeMap::new(),
+            download: Vec::new(),
+            mmap_min_size: None,
+            io_backend: IoBackend::default(),
+            preindex: false,
+            preload: Vec::new(),
+            negative_cache_secs: None,
+            precompressed: false,
+            compression: None,
+            image_variants: false,
+            webdav: false,
+            directory_listing: false,
+            listen: Vec::new(),
+            unix_socket: UnixSocketConfig::default(),
+            access_log: false,
+            access_log_rules: None,
+            logging: LoggingConfig::default(),
+            request_id: false,
+            pipeline: None,
+            version_endpoint: false,
+            metrics_endpoint: false,
+            sentry: None,
+            server_header: None,
+            server_timing: None,
+            qr_code: default_qr_code(),
+            trusted_proxies: Vec::new(),
+            reuse_port: false,
+            user: None,
+            group: None,
+            sandbox: false,
+            sandbox_paths: Vec::new(),
+            include_dirs: Vec::new(),
+            mount: Vec::new(),
+            vhost: Vec::new(),
+            tls: None,
+            redirect: Vec::new(),
+            rewrite: Vec::new(),
+            canonical_host: None,
+            path_validation: None,
+            security_headers: SecurityHeadersConfig::default(),
+            permissions_policy: None,
+            auth: None,
+            login: None,
+            forward_auth: None,
+            cors: None,
+            hotlink: None,
+            debug_artifacts: None,
+            link_headers: Vec::new(),
+            plugin: Vec::new(),
+            script: None,
+            user_agent: Vec::new(),
+            prerender: None,
+            geoip: None,
+            throttle: None,
+            chaos: None,
+            proxy: Vec::new(),
+            cache: None,
+            mdns: None,
+            markdown: None,
+            ssi: None,
+            runtime_env: None,
+            templates: None,
+            i18n: None,
+            images: None,
+            audio_meta: None,
+            waveform: None,
+            hls: None,
+            early_hints: None,
+            sri: None,
+            service_worker: None,
+            search: None,
+            podcast: Vec::new(),
+            robots: None,
+            favicon: None,
+            sitemap: None,
+            zip_download: None,
+            dir_overrides: None,
+            path_matching: None,
+            playlist: None,
+            upload: None,
+            mirror: None,
+            canary: None,
+            releases: None,
+            tenants: None,
+            s3: None,
+            git: None,
+            build: None,
+            on_change: None,
+            on_change_watch: Vec::new(),
+            on_change_debounce_ms: None,
+            shutdown: ShutdownConfig::default(),
+            maintenance: None,
+            slow_clients: SlowClientConfig::default(),
+            keep_alive: KeepAliveConfig::default(),
+            runtime: RuntimeConfig::default(),
+            status: None,
+            analytics: None,
+            shadow: None,
+            reports: None,
+        }
+    }
+}
+
+// print-config 输出中代替密码与密钥
+pub const REDACTED: &str = "<redacted>";
+
+pub fn redact<S: Serializer>(_: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(REDACTED)
+}
+
+pub fn redact_option<S: Serializer>(
+    value: &Option<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(_) => serializer.serialize_some(REDACTED),
+        None => serializer.serialize_none(),
+    }
+}
+
+// print-config 与 serve --dry-run：输出合并默认值、config.toml 与环境变量之后实际生效的配置；
+// 带 --dev 时与 serve 一样应用开发模式的覆盖
+pub fn print(format: ConfigFormat, dev: bool) {
+    // 覆盖来源的日志会混进标准输出，打印时不记录
+    let mut config = tracing::subscriber::with_default(NoSubscriber::default(), load_config);
+    if dev {
+        config.apply_dev();
+    }
+    let rendered = match format {
+        ConfigFormat::Toml => toml::to_string_pretty(&config).map_err(|e| e.to_string()),
+        ConfigFormat::Json => serde_json::to_string_pretty(&config).map_err(|e| e.to_string()),
+    };
+    match rendered {
+        Ok(text) => println!("{}", text.trim_end()),
+        Err(e) => {
+            eprintln!("Failed to render configuration: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// schema 子命令：输出 config.toml 的 JSON Schema，供编辑器补全（Even Better TOML / taplo 等）与 CI 校验配置文件
+pub fn print_schema() {
+    let mut schema = schemars::schema_for!(Config);
+    schema.insert("title".to_string(), "sonic-wave config.toml".into());
+    // include 与 [profile.<name>] 在反序列化之前处理，不是 Config 的字段
+    if let Some(properties) = schema
+        .get_mut("properties")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        properties.insert(
+            "include".to_string(),
+            serde_json::json!({
+                "anyOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } }
+                ]
+            }),
+        );
+        properties.insert(
+            "profile".to_string(),
+            serde_json::json!({
+                "type": "object",
+                "additionalProperties": { "type": "object" }
+            }),
+        );
+    }
+    match serde_json::to_string_pretty(&schema) {
+        Ok(text) => println!("{}", text),
+        Err(e) => {
+            eprintln!("Failed to render the configuration schema: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// --profile（由 main.rs 写入该环境变量，supervisor 的工作进程随之继承）或 SONICWAVE_PROFILE 选择的 profile
+pub const PROFILE_ENV: &str = "SONICWAVE_PROFILE";
+
+fn active_profile() -> Option<String> {
+    std::env::var(PROFILE_ENV)
+        .ok()
+        .filter(|name| !name.is_empty())
+}
+
+// [profile.<name>] 覆盖在基础配置之上：表逐项合并，其他值（包括数组）整体替换；未选择 profile 时忽略这些表
+fn parse(content: &str, base_dir: &Path, profile: Option<&str>) -> Result<Config, String> {
+    let mut table: toml::Table = toml::from_str(content).map_err(|e| e.to_string())?;
+    let include_dirs = include(&mut table, base_dir)?;
+    let mut config = select(content, table, profile, !include_dirs.is_empty())?;
+    config.include_dirs = include_dirs;
+    on_change_into_build(&mut config)?;
+    Ok(config)
+}
+
+// on_change = "npm run build" 等同于 [build] command 加 watch = on_change_watch，只是启动时不构建
+fn on_change_into_build(config: &mut Config) -> Result<(), String> {
+    let Some(command) = config.on_change.take() else {
+        if !config.on_change_watch.is_empty() || config.on_change_debounce_ms.is_some() {
+            return Err(
+                "on_change_watch / on_change_debounce_ms require on_change; use [build] watch / debounce_ms"
+                    .to_string(),
+            );
+        }
+        return Ok(());
+    };
+    if config.build.is_some() {
+        return Err(
+            "on_change cannot be combined with [build]; move the command to [build] command / watch"
+                .to_string(),
+        );
+    }
+    if config.on_change_watch.is_empty() {
+        return Err(
+            "on_change requires on_change_watch (the source globs that trigger it); see [build] watch"
+                .to_string(),
+        );
+    }
+    config.build = Some(BuildConfig {
+        command,
+        working_dir: None,
+        watch: std::mem::take(&mut config.on_change_watch),
+        debounce_ms: config
+            .on_change_debounce_ms
+            .take()
+            .unwrap_or_else(build::default_debounce_ms),
+        run_on_start: false,
+    });
+    Ok(())
+}
+
+fn select(
+    content: &str,
+    mut table: toml::Table,
+    profile: Option<&str>,
+    included: bool,
+) -> Result<Config, String> {
+    let profiles = table.remove("profile");
+    let Some(name) = profile else {
+        // 没有 include 与 profile 时直接解析原文，错误信息带行号
+        if !included {
+            return toml::from_str(content).map_err(|e| e.to_string());
+        }
+        return toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.to_string());
+    };
+    let overlay = profiles
+        .as_ref()
+        .and_then(|profiles| profiles.get(name))
+        .and_then(toml::Value::as_table)
+        .ok_or_else(|| {
+            format!(
+                "profile `{}` is not defined, expected a [profile.{}] table",
+                name, name
+            )
+        })?;
+    merge(&mut table, overlay);
+    toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| format!("{} (with profile `{}`)", e, name))
+}
+
+// include = "conf.d/*.toml"（或数组）：按文件名顺序读取片段并合并进来，相对路径相对主配置文件所在的目录，
+// 通配符只能出现在文件名中。表逐项合并，数组（[[vhost]]、[[redirect]]、deny 等）追加在后面，其他值由后读的文件覆盖；
+// 返回片段所在的目录（沙箱需要允许读取）
+fn include(table: &mut toml::Table, base_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let patterns = match table.remove("include") {
+        None => return Ok(Vec::new()),
+        Some(toml::Value::String(pattern)) => vec![pattern],
+        Some(toml::Value::Array(items)) => items
+            .into_iter()
+            .map(|item| match item {
+                toml::Value::String(pattern) => Ok(pattern),
+                _ => Err("include must be a string or an array of strings".to_string()),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("include must be a string or an array of strings".to_string()),
+    };
+    let mut dirs = Vec::new();
+    for pattern in patterns {
+        let path = base_dir.join(&pattern);
+        let dir = path.parent().unwrap_or(base_dir).to_path_buf();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| format!("invalid include `{}`", pattern))?;
+        if dir.to_string_lossy().contains(['*', '?']) {
+            return Err(format!(
+                "include `{}`: wildcards are only supported in the file name",
+                pattern
+            ));
+        }
+        let files = if name.contains(['*', '?']) {
+            let regex = regex::Regex::new(&glob::to_regex(&name))
+                .map_err(|e| format!("invalid include `{}`: {}", pattern, e))?;
+            let entries = fs::read_dir(&dir)
+                .map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+            let mut files: Vec<PathBuf> = entries
+                .flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|t| !t.is_dir()))
+                .filter(|entry| regex.is_match(&entry.file_name().to_string_lossy()))
+                .map(|entry| entry.path())
+                .collect();
+            files.sort();
+            files
+        } else {
+            vec![path]
+        };
+        for file in files {
+            let content = fs::read_to_string(&file)
+                .map_err(|e| format!("failed to read {}: {}", file.display(), e))?;
+            let fragment: toml::Table =
+                toml::from_str(&content).map_err(|e| format!("in {}: {}", file.display(), e))?;
+            if fragment.contains_key("include") {
+                return Err(format!(
+                    "in {}: include is only supported in the main config file",
+                    file.display()
+                ));
+            }
+            append(table, fragment);
+        }
+        dirs.push(dir);
+    }
+    Ok(dirs)
+}
+
+fn append(base: &mut toml::Table, fragment: toml::Table) {
+    for (key, value) in fragment {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(fragment)) => {
+                append(base, fragment)
+            }
+            (Some(toml::Value::Array(base)), toml::Value::Array(items)) => base.extend(items),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn merge(base: &mut toml::Table, overlay: &toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+pub fn load_config() -> Config {
+    // 优先级: 环境变量 > profile > 配置文件 > 默认值
+    let profile = active_profile();
+    if let Some(profile) = &profile {
+        info!("Using config profile `{}`", profile);
+    }
+    let mut config = if let Ok(content) = fs::read_to_string("config.toml") {
+        match parse(&content, Path::new("."), profile.as_deref()) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                warn!("Failed to parse config.toml: {}, using defaults", e);
+                Config::default()
+            }
+        }
+    } else {
+        info!("No config.toml found, using defaults");
+        Config::default()
+    };
+
+    // 环境变量覆盖
+    if let Ok(port_str) = std::env::var("PORT") {
+        if let Ok(port) = port_str.parse::<u16>() {
+            info!("Port overridden by env: {}", port);
+            config.port = Some(port);
+        }
+    }
+
+    if let Ok(listen) = std::env::var("LISTEN") {
+        info!("Listen addresses overridden by env: {}", listen);
+        config.listen = listen
+            .split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|address| ListenEntry {
+                address: address.to_string(),
+                options: ListenerOptions::default(),
+                unix_socket: UnixSocketConfig::default(),
+            })
+            .collect();
+    }
+
+    if let Ok(dir) = std::env::var("STATIC_DIR") {
+        info!("Static dir overridden by env: {}", dir);
+        config.static_dir = Some(dir);
+    }
+
+    if let Ok(dsn) = std::env::var("SENTRY_DSN") {
+        if !dsn.is_empty() {
+            info!("Sentry DSN overridden by env");
+            config.sentry.get_or_insert_with(SentryConfig::default).dsn = Some(dsn);
+        }
+    }
+
+    config
+}
@@ -0,0 +1,14 @@
+// 读取请求 Cookie 头中的值；登录会话、OIDC、灰度分桶、语言选择与调试产物共用
+use axum::http::header::{self, HeaderMap};
+
+// 多个 Cookie 头与 "a=1; b=2" 两种写法都支持，同名时取第一个
+pub fn get<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
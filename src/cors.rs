@@ -0,0 +1,227 @@
+// 跨域资源共享（[cors]）：为允许的来源添加 Access-Control-* 响应头并直接应答预检请求，
+// 可按路径前缀覆盖部分设置（[[cors.paths]]）
+use crate::vary;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderName, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct CorsPolicy {
+    // 允许的来源，如 "https://cdn.example.com"；"*" 表示任意来源，"https://*.example.com" 匹配子域名
+    #[serde(default)]
+    pub origins: Option<Vec<String>>,
+    // 预检允许的方法，默认 GET / HEAD / OPTIONS
+    #[serde(default)]
+    pub methods: Option<Vec<String>>,
+    // 预检允许的请求头；未设置时按 Access-Control-Request-Headers 原样允许
+    #[serde(default)]
+    pub headers: Option<Vec<String>>,
+    // 允许脚本读取的响应头（Access-Control-Expose-Headers）
+    #[serde(default)]
+    pub expose_headers: Option<Vec<String>>,
+    // 允许携带 Cookie 等凭据；开启后 "*" 按请求的 Origin 原样返回
+    #[serde(default)]
+    pub credentials: Option<bool>,
+    // 预检结果的缓存时间（秒）
+    #[serde(default)]
+    pub max_age: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CorsConfig {
+    #[serde(flatten)]
+    pub policy: CorsPolicy,
+    // 按路径前缀覆盖，未设置的字段沿用顶层设置；最长前缀优先
+    #[serde(default)]
+    pub paths: Vec<PathPolicy>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct PathPolicy {
+    pub prefix: String,
+    #[serde(flatten)]
+    pub policy: CorsPolicy,
+}
+
+fn default_methods() -> Vec<String> {
+    ["GET", "HEAD", "OPTIONS"]
+        .iter()
+        .map(|method| method.to_string())
+        .collect()
+}
+
+struct Policy {
+    origins: Vec<String>,
+    methods: HeaderValue,
+    headers: Option<HeaderValue>,
+    expose_headers: Option<HeaderValue>,
+    credentials: bool,
+    max_age: Option<u64>,
+}
+
+fn join(values: &[String], what: &str) -> Result<HeaderValue, String> {
+    HeaderValue::from_str(&values.join(", ")).map_err(|e| format!("invalid cors {}: {}", what, e))
+}
+
+impl Policy {
+    // 路径设置覆盖顶层设置
+    fn new(base: &CorsPolicy, overrides: &CorsPolicy) -> Result<Self, String> {
+        let pick = |field: fn(&CorsPolicy) -> &Option<Vec<String>>| {
+            field(overrides).clone().or_else(|| field(base).clone())
+        };
+        let methods = pick(|policy| &policy.methods).unwrap_or_else(default_methods);
+        let headers = pick(|policy| &policy.headers);
+        let expose_headers = pick(|policy| &policy.expose_headers);
+        Ok(Policy {
+            origins: pick(|policy| &policy.origins).unwrap_or_default(),
+            methods: join(&methods, "methods")?,
+            headers: headers
+                .map(|headers| join(&headers, "headers"))
+                .transpose()?,
+            expose_headers: expose_headers
+                .filter(|headers| !headers.is_empty())
+                .map(|headers| join(&headers, "expose_headers"))
+                .transpose()?,
+            credentials: overrides.credentials.or(base.credentials).unwrap_or(false),
+            max_age: overrides.max_age.or(base.max_age),
+        })
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.origins.iter().any(|allowed| {
+            if allowed == "*" {
+                return true;
+            }
+            match allowed.split_once('*') {
+                // 通配符只匹配主机名中的一段或多段子域名
+                Some((before, after)) => origin
+                    .strip_prefix(before)
+                    .and_then(|rest| rest.strip_suffix(after))
+                    .is_some_and(|middle| !middle.is_empty() && !middle.contains(['/', ':', '@'])),
+                None => allowed.eq_ignore_ascii_case(origin),
+            }
+        })
+    }
+
+    fn allow_origin(&self, origin: &HeaderValue) -> HeaderValue {
+        if !self.credentials && self.origins.iter().any(|allowed| allowed == "*") {
+            HeaderValue::from_static("*")
+        } else {
+            origin.clone()
+        }
+    }
+}
+
+pub struct Cors {
+    default: Policy,
+    paths: Vec<(String, Policy)>,
+}
+
+impl Cors {
+    pub fn new(config: &CorsConfig) -> Result<Self, String> {
+        let mut paths = Vec::new();
+        for path in &config.paths {
+            if !path.prefix.starts_with('/') {
+                return Err(format!(
+                    "cors path prefix `{}` must start with '/'",
+                    path.prefix
+                ));
+            }
+            paths.push((
+                path.prefix.clone(),
+                Policy::new(&config.policy, &path.policy)?,
+            ));
+        }
+        paths.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        Ok(Cors {
+            default: Policy::new(&config.policy, &CorsPolicy::default())?,
+            paths,
+        })
+    }
+
+    fn policy(&self, path: &str) -> &Policy {
+        self.paths
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .map_or(&self.default, |(_, policy)| policy)
+    }
+}
+
+pub async fn handle(State(cors): State<Arc<Cors>>, req: Request, next: Next) -> Response {
+    let policy = cors.policy(req.uri().path());
+    let origin = req
+        .headers()
+        .get(header::ORIGIN)
+        .filter(|origin| origin.to_str().is_ok_and(|origin| policy.allows(origin)))
+        .cloned();
+    let Some(origin) = origin else {
+        let mut response = next.run(req).await;
+        // 响应随 Origin 不同，共享缓存不能混用
+        if !policy.origins.is_empty() {
+            vary::add(response.headers_mut(), "Origin");
+        }
+        return response;
+    };
+
+    let preflight = req.method() == Method::OPTIONS
+        && req
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    if preflight {
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        let headers = response.headers_mut();
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_ORIGIN,
+            policy.allow_origin(&origin),
+        );
+        headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, policy.methods.clone());
+        let requested = req
+            .headers()
+            .get(header::ACCESS_CONTROL_REQUEST_HEADERS)
+            .cloned();
+        if let Some(allowed) = policy.headers.clone().or(requested) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, allowed);
+        }
+        if policy.credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+        if let Some(max_age) = policy.max_age {
+            headers.insert(header::ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age));
+        }
+        vary::add(headers, "Origin");
+        vary::add(headers, "Access-Control-Request-Method");
+        vary::add(headers, "Access-Control-Request-Headers");
+        return response;
+    }
+
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_ORIGIN,
+        policy.allow_origin(&origin),
+    );
+    if policy.credentials {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+    }
+    if let Some(expose) = &policy.expose_headers {
+        headers.insert(header::ACCESS_CONTROL_EXPOSE_HEADERS, expose.clone());
+    }
+    // 跨域页面开启 COEP 时，子资源需要允许跨域嵌入
+    headers.insert(
+        HeaderName::from_static("cross-origin-resource-policy"),
+        HeaderValue::from_static("cross-origin"),
+    );
+    vary::add(headers, "Origin");
+    response
+}
@@ -0,0 +1,138 @@
+// 后台运行（仅 Unix）：--daemon 两次 fork 脱离终端与会话，标准输入接 /dev/null，标准输出与错误写入 --log-file；
+// --pid-file 记录进程号，启动时拒绝与仍在运行的实例冲突，残留的 PID 文件直接覆盖
+use crate::config::Config;
+use crate::{privileges, upgrade};
+use nix::errno::Errno;
+use nix::sys::signal::kill;
+use nix::unistd::{self, ForkResult, Pid};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use tracing::warn;
+
+static SUCCESSOR_PID_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// PID 文件，进程正常退出时删除
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        // 热重启后文件里已是新进程的 PID，不能删除
+        if read_pid(&self.path).ok().flatten() == Some(std::process::id() as i32) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+fn read_pid(path: &Path) -> io::Result<Option<i32>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content.trim().parse().ok()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// 文件中的进程仍在运行时拒绝启动；热重启的新进程接替的正是文件中的旧进程
+fn check_pid_file(path: &Path) -> Result<(), String> {
+    let pid = read_pid(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let Some(pid) = pid.filter(|pid| *pid > 0) else {
+        return Ok(());
+    };
+    if upgrade::parent_pid() == Some(pid) {
+        return Ok(());
+    }
+    // EPERM 说明进程存在，只是属于其他用户
+    match kill(Pid::from_raw(pid), None) {
+        Ok(()) | Err(Errno::EPERM) => Err(format!(
+            "already running with pid {} (from {})",
+            pid,
+            path.display()
+        )),
+        Err(_) => {
+            warn!("Replacing stale pid file {} (pid {})", path.display(), pid);
+            Ok(())
+        }
+    }
+}
+
+// 必须在启动 tokio 运行时之前调用：fork 只复制调用线程
+pub fn start(
+    daemon: bool,
+    pid_file: Option<&Path>,
+    log_file: Option<&Path>,
+    config: &Config,
+) -> Result<Option<PidFile>, String> {
+    if let Some(path) = pid_file {
+        check_pid_file(path)?;
+    }
+    // 热重启的新进程由已在后台的旧进程拉起，沿用它的会话与输出
+    if daemon && upgrade::parent_pid().is_none() {
+        detach(log_file)?;
+    }
+    let Some(path) = pid_file else {
+        return Ok(None);
+    };
+    let pid_file = PidFile {
+        path: path.to_path_buf(),
+    };
+    // 热重启的新进程就绪后才接管（见 take_over），启动失败时文件仍指向继续服务的旧进程
+    if upgrade::parent_pid().is_some() {
+        let _ = SUCCESSOR_PID_FILE.set(pid_file.path.clone());
+        return Ok(Some(pid_file));
+    }
+    write_pid(path).map_err(|e| format!("cannot write {}: {}", path.display(), e))?;
+    // 降权后热重启的新进程需要重写 PID 文件
+    privileges::hand_over(path, config.user.as_deref(), config.group.as_deref())
+        .map_err(|e| format!("cannot hand {} over to user: {}", path.display(), e))?;
+    Ok(Some(pid_file))
+}
+
+fn write_pid(path: &Path) -> io::Result<()> {
+    fs::write(path, format!("{}\n", std::process::id()))
+}
+
+// 热重启的新进程开始接受连接时写入自己的 PID，随后旧进程退出
+pub fn take_over() {
+    if let Some(path) = SUCCESSOR_PID_FILE.get() {
+        if let Err(e) = write_pid(path) {
+            warn!("Failed to update pid file {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn detach(log_file: Option<&Path>) -> Result<(), String> {
+    // fork 之前打开，出错时仍能输出到终端
+    let null = File::open("/dev/null").map_err(|e| format!("cannot open /dev/null: {}", e))?;
+    let log = match log_file {
+        Some(path) => OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("cannot open {}: {}", path.display(), e))?,
+        None => OpenOptions::new()
+            .write(true)
+            .open("/dev/null")
+            .map_err(|e| format!("cannot open /dev/null: {}", e))?,
+    };
+
+    // SAFETY: 此时只有主线程，子进程中不存在其他线程持有的锁
+    match unsafe { unistd::fork() }.map_err(|e| format!("fork failed: {}", e))? {
+        ForkResult::Parent { .. } => std::process::exit(0),
+        ForkResult::Child => {}
+    }
+    unistd::setsid().map_err(|e| format!("setsid failed: {}", e))?;
+    // 第二次 fork 后不再是会话首进程，之后打开终端设备也不会获得控制终端
+    // SAFETY: 同上
+    match unsafe { unistd::fork() }.map_err(|e| format!("fork failed: {}", e))? {
+        ForkResult::Parent { .. } => std::process::exit(0),
+        ForkResult::Child => {}
+    }
+
+    unistd::dup2_stdin(&null).map_err(|e| format!("cannot redirect stdin: {}", e))?;
+    unistd::dup2_stdout(&log).map_err(|e| format!("cannot redirect stdout: {}", e))?;
+    unistd::dup2_stderr(&log).map_err(|e| format!("cannot redirect stderr: {}", e))?;
+    Ok(())
+}
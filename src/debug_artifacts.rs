@@ -0,0 +1,114 @@
+// 调试文件拦截（[debug_artifacts]）：source map 等调试文件可以随站点一起部署，
+// 但只有带正确 token（请求头或 cookie）的请求才能取到，其余请求返回 404。--dev 模式下不拦截
+use crate::cookies;
+use crate::glob::PathPattern;
+use crate::upload::constant_time_eq;
+use crate::vary;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct DebugArtifactsConfig {
+    // 拦截的路径 glob
+    #[serde(default = "default_patterns")]
+    pub patterns: Vec<String>,
+    // 放行的 token，未设置时一律拦截
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub token: Option<String>,
+    // 携带 token 的请求头
+    #[serde(default = "default_header")]
+    pub header: String,
+    // 携带 token 的 cookie，浏览器开发者工具加载 source map 时会带上
+    #[serde(default = "default_cookie")]
+    pub cookie: String,
+}
+
+fn default_patterns() -> Vec<String> {
+    vec!["*.map".to_string()]
+}
+
+fn default_header() -> String {
+    "x-debug-token".to_string()
+}
+
+fn default_cookie() -> String {
+    "debug_token".to_string()
+}
+
+pub struct DebugArtifacts {
+    patterns: Vec<PathPattern>,
+    token: Option<String>,
+    header: HeaderName,
+    cookie: String,
+}
+
+impl DebugArtifacts {
+    pub fn new(config: &DebugArtifactsConfig) -> Result<Self, String> {
+        let mut patterns = Vec::new();
+        for pattern in &config.patterns {
+            patterns.push(PathPattern::new(pattern)?);
+        }
+        let header = HeaderName::from_bytes(config.header.as_bytes()).map_err(|e| {
+            format!(
+                "invalid [debug_artifacts] header `{}`: {}",
+                config.header, e
+            )
+        })?;
+        if config.token.as_deref() == Some("") {
+            return Err("[debug_artifacts] token must not be empty".to_string());
+        }
+        Ok(DebugArtifacts {
+            patterns,
+            token: config.token.clone(),
+            header,
+            cookie: config.cookie.clone(),
+        })
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(token) = &self.token else {
+            return false;
+        };
+        let from_header = headers
+            .get(&self.header)
+            .is_some_and(|v| constant_time_eq(v.as_bytes(), token.as_bytes()));
+        let from_cookie = cookies::get(headers, &self.cookie)
+            .is_some_and(|value| constant_time_eq(value.as_bytes(), token.as_bytes()));
+        from_header || from_cookie
+    }
+}
+
+pub async fn check(
+    State(artifacts): State<Arc<DebugArtifacts>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let decoded = percent_decode_str(req.uri().path()).decode_utf8_lossy();
+    if !artifacts.patterns.iter().any(|p| p.matches(&decoded)) {
+        return next.run(req).await;
+    }
+    let vary = format!("{}, Cookie", artifacts.header);
+    if !artifacts.authorized(req.headers()) {
+        debug!("Blocked debug artifact {}", req.uri().path());
+        let mut response = StatusCode::NOT_FOUND.into_response();
+        vary::add(response.headers_mut(), &vary);
+        return response;
+    }
+    // 放行的响应不进入共享缓存，避免 CDN 把它返回给没有 token 的请求
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-store"),
+    );
+    vary::add(headers, &vary);
+    response
+}
@@ -0,0 +1,208 @@
+// 上传内容的摘要校验：Content-MD5（RFC 1864）与 Repr-Digest / Content-Digest（RFC 9530，sha-256 / sha-512 / md5），
+// 写入模式的 PUT 收完请求体后与之比较，不一致时不写入。不认识的算法按 RFC 9530 忽略
+use base64::Engine;
+use sha2::{Digest, Sha256, Sha512};
+
+// RFC 1321；MD5 只用于检查传输损坏，不作为安全校验
+struct Md5 {
+    state: [u32; 4],
+    buffer: Vec<u8>,
+    length: u64,
+}
+
+const MD5_SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+const MD5_K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+impl Md5 {
+    fn new() -> Self {
+        Md5 {
+            state: [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476],
+            buffer: Vec::with_capacity(64),
+            length: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        if !self.buffer.is_empty() {
+            let take = (64 - self.buffer.len()).min(data.len());
+            self.buffer.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.buffer.len() < 64 {
+                return;
+            }
+            let block: [u8; 64] = self.buffer[..].try_into().unwrap();
+            self.block(&block);
+            self.buffer.clear();
+        }
+        let mut chunks = data.chunks_exact(64);
+        for block in &mut chunks {
+            self.block(block.try_into().unwrap());
+        }
+        self.buffer.extend_from_slice(chunks.remainder());
+    }
+
+    fn finish(mut self) -> [u8; 16] {
+        let bits = self.length.wrapping_mul(8);
+        let mut padding = vec![0x80u8];
+        padding.resize((55usize.wrapping_sub(self.buffer.len()) % 64) + 1, 0);
+        padding.extend_from_slice(&bits.to_le_bytes());
+        self.update(&padding);
+        let mut out = [0u8; 16];
+        for (chunk, word) in out.chunks_exact_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        out
+    }
+
+    fn block(&mut self, block: &[u8; 64]) {
+        let mut m = [0u32; 16];
+        for (word, bytes) in m.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_le_bytes(bytes.try_into().unwrap());
+        }
+        let [mut a, mut b, mut c, mut d] = self.state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a
+                .wrapping_add(f)
+                .wrapping_add(MD5_K[i])
+                .wrapping_add(m[g])
+                .rotate_left(MD5_SHIFTS[(i / 16) * 4 + i % 4]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+        for (state, value) in self.state.iter_mut().zip([a, b, c, d]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+}
+
+enum Hasher {
+    Md5(Md5),
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Md5(h) => h.finish().to_vec(),
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+            Hasher::Sha512(h) => h.finalize().to_vec(),
+        }
+    }
+}
+
+struct Check {
+    // 用于日志，如 "Content-MD5"、"Repr-Digest sha-256"
+    source: String,
+    hasher: Hasher,
+    expected: Vec<u8>,
+}
+
+// 请求头中声明的摘要，边接收请求体边计算；没有声明时不做任何计算
+#[derive(Default)]
+pub struct Verifier {
+    checks: Vec<Check>,
+}
+
+impl Verifier {
+    // 摘要头格式错误时返回错误
+    pub fn from_headers(headers: &axum::http::HeaderMap) -> Result<Self, String> {
+        let mut checks = Vec::new();
+        if let Some(value) = headers.get("content-md5") {
+            let expected = value
+                .to_str()
+                .ok()
+                .and_then(|v| {
+                    base64::engine::general_purpose::STANDARD
+                        .decode(v.trim())
+                        .ok()
+                })
+                .filter(|digest| digest.len() == 16)
+                .ok_or("invalid Content-MD5")?;
+            checks.push(Check {
+                source: "Content-MD5".to_string(),
+                hasher: Hasher::Md5(Md5::new()),
+                expected,
+            });
+        }
+        for name in ["Repr-Digest", "Content-Digest"] {
+            for value in headers.get_all(name) {
+                let value = value.to_str().map_err(|_| format!("invalid {}", name))?;
+                // 字典：sha-256=:<Base64>:, sha-512=:<Base64>:
+                for member in value.split(',').filter(|member| !member.trim().is_empty()) {
+                    let (algorithm, digest) = member
+                        .split(';')
+                        .next()
+                        .and_then(|member| member.split_once('='))
+                        .ok_or_else(|| format!("invalid {}", name))?;
+                    let algorithm = algorithm.trim().to_ascii_lowercase();
+                    let hasher = match algorithm.as_str() {
+                        "sha-256" => Hasher::Sha256(Sha256::new()),
+                        "sha-512" => Hasher::Sha512(Sha512::new()),
+                        "md5" => Hasher::Md5(Md5::new()),
+                        _ => continue,
+                    };
+                    let expected = digest
+                        .trim()
+                        .strip_prefix(':')
+                        .and_then(|digest| digest.strip_suffix(':'))
+                        .and_then(|digest| {
+                            base64::engine::general_purpose::STANDARD
+                                .decode(digest)
+                                .ok()
+                        })
+                        .ok_or_else(|| format!("invalid {} {}", name, algorithm))?;
+                    checks.push(Check {
+                        source: format!("{} {}", name, algorithm),
+                        hasher,
+                        expected,
+                    });
+                }
+            }
+        }
+        Ok(Verifier { checks })
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for check in &mut self.checks {
+            check.hasher.update(data);
+        }
+    }
+
+    // 不一致时返回出错的摘要头
+    pub fn verify(self) -> Result<(), String> {
+        for check in self.checks {
+            if check.hasher.finish() != check.expected {
+                return Err(check.source);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,351 @@
+// 目录覆盖文件（默认 .sonicwave.toml）：类似 .htaccess，为所在目录及其子目录覆盖响应头、缓存策略、
+// 索引文件与 Basic 认证；只接受 allow 中列出的选项，文件本身不会被返回
+use crate::basic_auth::{self, BasicAuth, BasicAuthConfig};
+use crate::glob::PathPattern;
+use crate::layers;
+use crate::site::{self, CacheRule};
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderName, HeaderValue};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct DirOverridesConfig {
+    // 覆盖文件名
+    #[serde(default = "default_file_name")]
+    pub file_name: String,
+    // 覆盖文件可以设置的选项，出现其他选项时该目录返回 500
+    #[serde(default = "default_allow")]
+    pub allow: Vec<DirOption>,
+}
+
+// headers：响应头；cache：cache_control、html_cache_control 与 cache_rules；
+// index：index_files；auth：Basic 认证
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum DirOption {
+    Headers,
+    Cache,
+    Index,
+    Auth,
+}
+
+impl DirOption {
+    fn name(self) -> &'static str {
+        match self {
+            DirOption::Headers => "headers",
+            DirOption::Cache => "cache",
+            DirOption::Index => "index",
+            DirOption::Auth => "auth",
+        }
+    }
+}
+
+fn default_file_name() -> String {
+    ".sonicwave.toml".to_string()
+}
+
+fn default_allow() -> Vec<DirOption> {
+    vec![
+        DirOption::Headers,
+        DirOption::Cache,
+        DirOption::Index,
+        DirOption::Auth,
+    ]
+}
+
+// 覆盖文件的内容；未知的键视为错误，拼写错误的 auth 不会被悄悄忽略
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DirFile {
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    cache_control: Option<String>,
+    #[serde(default)]
+    html_cache_control: Option<String>,
+    #[serde(default)]
+    cache_rules: Vec<CacheRule>,
+    #[serde(default)]
+    index_files: Option<Vec<String>>,
+    #[serde(default)]
+    auth: Option<BasicAuthConfig>,
+}
+
+// 编译后的覆盖文件
+struct Overrides {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    cache_control: Option<HeaderValue>,
+    html_cache_control: Option<HeaderValue>,
+    cache_rules: Vec<(PathPattern, HeaderValue)>,
+    index_files: Option<Arc<Vec<String>>>,
+    // Some(None) 表示 enabled = false，关闭上层目录的认证
+    auth: Option<Option<BasicAuth>>,
+}
+
+// 目录覆盖的索引文件，resolve 用它代替站点的 index_files
+#[derive(Clone)]
+pub struct IndexFiles(pub Arc<Vec<String>>);
+
+fn cache_value(value: &str) -> Result<HeaderValue, String> {
+    HeaderValue::from_str(value).map_err(|_| format!("invalid cache_control `{}`", value))
+}
+
+// url_dir 为覆盖文件所在目录的 URL 路径（以 "/" 结尾），含 "/" 的规则相对该目录
+fn parse(content: &str, url_dir: &str, allow: &[DirOption]) -> Result<Overrides, String> {
+    let file: DirFile = toml::from_str(content).map_err(|e| e.to_string())?;
+    let used = [
+        (DirOption::Headers, !file.headers.is_empty()),
+        (
+            DirOption::Cache,
+            file.cache_control.is_some()
+                || file.html_cache_control.is_some()
+                || !file.cache_rules.is_empty(),
+        ),
+        (DirOption::Index, file.index_files.is_some()),
+        (DirOption::Auth, file.auth.is_some()),
+    ];
+    for (option, set) in used {
+        if set && !allow.contains(&option) {
+            return Err(format!(
+                "`{}` is not allowed in per-directory files",
+                option.name()
+            ));
+        }
+    }
+    let cache_rules = file
+        .cache_rules
+        .iter()
+        .map(|rule| {
+            let pattern = if rule.pattern.contains('/') {
+                format!("{}{}", url_dir, rule.pattern.trim_start_matches('/'))
+            } else {
+                rule.pattern.clone()
+            };
+            let value = cache_value(&rule.cache_control)
+                .map_err(|e| format!("{} for `{}`", e, rule.pattern))?;
+            Ok((PathPattern::new(&pattern)?, value))
+        })
+        .collect::<Result<_, String>>()?;
+    let auth = file.auth.as_ref().map(BasicAuth::new).transpose()?;
+    Ok(Overrides {
+        headers: site::parse_headers(&file.headers)?,
+        cache_control: file.cache_control.as_deref().map(cache_value).transpose()?,
+        html_cache_control: file
+            .html_cache_control
+            .as_deref()
+            .map(cache_value)
+            .transpose()?,
+        cache_rules,
+        index_files: file.index_files.map(Arc::new),
+        auth,
+    })
+}
+
+struct Cached {
+    modified: Option<SystemTime>,
+    len: u64,
+    parsed: Result<Arc<Overrides>, String>,
+}
+
+// 一个磁盘站点的覆盖文件
+pub struct DirOverrides {
+    root: PathBuf,
+    file_name: String,
+    allow: Vec<DirOption>,
+    // 按文件路径缓存解析结果，修改时间或大小变化时重新读取
+    cache: Mutex<HashMap<PathBuf, Cached>>,
+}
+
+impl DirOverrides {
+    pub fn new(config: &DirOverridesConfig, root: &Path) -> Self {
+        DirOverrides {
+            root: root.to_path_buf(),
+            file_name: config.file_name.clone(),
+            allow: config.allow.clone(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn load(&self, dir: &Path, url_dir: &str) -> Result<Option<Arc<Overrides>>, String> {
+        let path = dir.join(&self.file_name);
+        let meta = match tokio::fs::metadata(&path).await {
+            Ok(meta) if meta.is_file() => meta,
+            // 不存在，或路径的上一级是文件（NotADirectory）
+            _ => {
+                self.cache.lock().unwrap().remove(&path);
+                return Ok(None);
+            }
+        };
+        let modified = meta.modified().ok();
+        if let Some(cached) = self.cache.lock().unwrap().get(&path) {
+            if cached.modified == modified && cached.len == meta.len() {
+                return cached.parsed.clone().map(Some);
+            }
+        }
+        let parsed = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => parse(&content, url_dir, &self.allow).map(Arc::new),
+            Err(e) => Err(e.to_string()),
+        }
+        .map_err(|e| format!("{}: {}", path.display(), e));
+        self.cache.lock().unwrap().insert(
+            path,
+            Cached {
+                modified,
+                len: meta.len(),
+                parsed: parsed.clone(),
+            },
+        );
+        parsed.map(Some)
+    }
+
+    // 从站点根目录到请求路径依次查找，浅层在前
+    async fn chain(&self, decoded: &str) -> Result<Vec<Arc<Overrides>>, String> {
+        let mut chain = Vec::new();
+        let mut dir = self.root.clone();
+        let mut url_dir = String::from("/");
+        if let Some(overrides) = self.load(&dir, &url_dir).await? {
+            chain.push(overrides);
+        }
+        for segment in decoded.split('/') {
+            if segment.is_empty() || segment == "." {
+                continue;
+            }
+            if segment == ".." || segment.contains('\\') || segment.contains('\0') {
+                break;
+            }
+            dir.push(segment);
+            url_dir.push_str(segment);
+            url_dir.push('/');
+            if let Some(overrides) = self.load(&dir, &url_dir).await? {
+                chain.push(overrides);
+            }
+        }
+        Ok(chain)
+    }
+}
+
+// 深层的规则先匹配；未匹配时按 HTML / 静态资源取最深一层设置的值
+fn cache_control<'a>(
+    chain: &'a [Arc<Overrides>],
+    path: &str,
+    decoded: &str,
+) -> Option<&'a HeaderValue> {
+    let deepest = || chain.iter().rev();
+    if let Some((_, value)) = deepest()
+        .flat_map(|overrides| &overrides.cache_rules)
+        .find(|(pattern, _)| pattern.matches(decoded))
+    {
+        return Some(value);
+    }
+    if layers::is_html(path) {
+        deepest().find_map(|overrides| overrides.html_cache_control.as_ref())
+    } else {
+        deepest().find_map(|overrides| overrides.cache_control.as_ref())
+    }
+}
+
+pub async fn apply(
+    State(dirs): State<Arc<DirOverrides>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path().to_string();
+    let Ok(decoded) = percent_decode_str(&path).decode_utf8() else {
+        return next.run(req).await;
+    };
+    let decoded = decoded.into_owned();
+    // 与 allow_dotfiles 无关，覆盖文件一律不返回
+    if decoded.split('/').any(|segment| segment == dirs.file_name) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let chain = match dirs.chain(&decoded).await {
+        Ok(chain) => chain,
+        Err(e) => {
+            warn!("Invalid per-directory file {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    if chain.is_empty() {
+        return next.run(req).await;
+    }
+    // 最深一层的设置生效
+    let auth = chain
+        .iter()
+        .rev()
+        .find_map(|overrides| overrides.auth.as_ref())
+        .and_then(Option::as_ref);
+    if let Some(auth) = auth {
+        if !auth.authorized(req.headers()) {
+            return auth.unauthorized();
+        }
+    }
+    if let Some(index_files) = chain
+        .iter()
+        .rev()
+        .find_map(|overrides| overrides.index_files.clone())
+    {
+        req.extensions_mut().insert(IndexFiles(index_files));
+    }
+
+    let mut response = next.run(req).await;
+    let status = response.status();
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        if let Some(value) = cache_control(&chain, &path, &decoded) {
+            response
+                .headers_mut()
+                .insert(header::CACHE_CONTROL, value.clone());
+        }
+    }
+    if auth.is_some() {
+        basic_auth::private(response.headers_mut());
+    }
+    for overrides in &chain {
+        for (name, value) in &overrides.headers {
+            response.headers_mut().insert(name.clone(), value.clone());
+        }
+    }
+    response
+}
+
+// check 子命令：检查站点目录下的所有覆盖文件，返回每个文件的错误
+pub fn check(config: &DirOverridesConfig, root: &Path) -> Vec<String> {
+    let mut errors = Vec::new();
+    visit(config, root, "/", &mut errors);
+    errors
+}
+
+fn visit(config: &DirOverridesConfig, dir: &Path, url_dir: &str, errors: &mut Vec<String>) {
+    let path = dir.join(&config.file_name);
+    if path.is_file() {
+        let result = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| parse(&content, url_dir, &config.allow).map(|_| ()));
+        if let Err(e) = result {
+            errors.push(format!("{}: {}", path.display(), e));
+        }
+    }
+    let Ok(items) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for item in items.flatten() {
+        if item.file_type().is_ok_and(|t| t.is_dir()) {
+            let name = item.file_name().to_string_lossy().into_owned();
+            visit(
+                config,
+                &item.path(),
+                &format!("{}{}/", url_dir, name),
+                errors,
+            );
+        }
+    }
+}
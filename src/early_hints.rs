@@ -0,0 +1,386 @@
+// 103 Early Hints（[early_hints]）：首次请求 HTML 页面时扫描 <head> 中的样式表、脚本、字体等关键资源，
+// 结果按文件缓存；之后的请求在最终响应之前先发送带 Link 预加载头的 103，最终响应同样带 Link 头
+use crate::glob::PathPattern;
+use crate::site::Resolver;
+use axum::extract::{OriginalUri, Request};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode, Version};
+use axum::middleware::Next;
+use axum::response::Response;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::poll_fn;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct EarlyHintsConfig {
+    // 发送 Early Hints 的页面（glob，如 "/app/**"）；为空时所有 HTML 页面都发送
+    #[serde(default)]
+    pub paths: Vec<String>,
+    // 每个页面最多预加载的资源数
+    #[serde(default = "default_max_links")]
+    pub max_links: usize,
+    // 内存中缓存的页面数上限，超出时清空
+    #[serde(default = "default_cache_entries")]
+    pub cache_entries: usize,
+}
+
+fn default_max_links() -> usize {
+    8
+}
+
+fn default_cache_entries() -> usize {
+    1024
+}
+
+// 只扫描文件开头这么多字节，<head> 通常远小于此
+const SCAN_LIMIT: usize = 64 * 1024;
+
+struct Scanned {
+    len: u64,
+    modified: Option<SystemTime>,
+    links: Vec<HeaderValue>,
+}
+
+pub struct EarlyHints {
+    paths: Vec<PathPattern>,
+    max_links: usize,
+    cache_entries: usize,
+    cache: Mutex<HashMap<PathBuf, Arc<Scanned>>>,
+}
+
+impl EarlyHints {
+    pub fn new(config: &EarlyHintsConfig) -> Result<Self, String> {
+        let paths = config
+            .paths
+            .iter()
+            .map(|pattern| PathPattern::new(pattern))
+            .collect::<Result<_, _>>()?;
+        Ok(EarlyHints {
+            paths,
+            max_links: config.max_links,
+            cache_entries: config.cache_entries,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        if self.paths.is_empty() {
+            return true;
+        }
+        let decoded = percent_encoding::percent_decode_str(path).decode_utf8_lossy();
+        self.paths.iter().any(|pattern| pattern.matches(&decoded))
+    }
+
+    // 文件大小与修改时间不变时使用缓存的扫描结果
+    async fn links(&self, path: &Path) -> io::Result<Arc<Scanned>> {
+        let meta = tokio::fs::metadata(path).await?;
+        let modified = meta.modified().ok();
+        let cached = self.cache.lock().unwrap().get(path).cloned();
+        if let Some(scanned) = cached {
+            if scanned.len == meta.len() && scanned.modified == modified {
+                return Ok(scanned);
+            }
+        }
+        let mut head = tokio::fs::read(path).await?;
+        head.truncate(SCAN_LIMIT);
+        let links = scan(&String::from_utf8_lossy(&head), self.max_links)
+            .into_iter()
+            .filter_map(|link| HeaderValue::from_str(&link).ok())
+            .collect();
+        let scanned = Arc::new(Scanned {
+            len: meta.len(),
+            modified,
+            links,
+        });
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= self.cache_entries {
+            cache.clear();
+        }
+        cache.insert(path.to_path_buf(), scanned.clone());
+        Ok(scanned)
+    }
+}
+
+// 解析一个标签的属性，属性名转为小写；没有值的属性（async、crossorigin）值为空
+pub fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attrs = Vec::new();
+    let mut rest = tag;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == '/');
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace() || c == '=' || c == '/')
+            .unwrap_or(rest.len());
+        if end == 0 {
+            break;
+        }
+        let name = rest[..end].to_ascii_lowercase();
+        rest = rest[end..].trim_start();
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (parsed, remaining) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let inner = &after[1..];
+                    match inner.find(quote) {
+                        Some(close) => (&inner[..close], &inner[close + 1..]),
+                        None => (inner, ""),
+                    }
+                }
+                _ => {
+                    let end = after
+                        .find(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = parsed.to_string();
+            rest = remaining;
+        }
+        attrs.push((name, value));
+    }
+    attrs
+}
+
+// 生成 Link 头的值；只处理 <head> 中的 <link> 与 <script src>
+fn scan(html: &str, max_links: usize) -> Vec<String> {
+    let lower = html.to_ascii_lowercase();
+    let end = ["</head", "<body"]
+        .iter()
+        .filter_map(|marker| lower.find(marker))
+        .min()
+        .unwrap_or(html.len());
+    let mut links: Vec<String> = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = lower[pos..end].find('<').map(|offset| pos + offset) {
+        let Some(close) = lower[start..end].find('>').map(|offset| start + offset) else {
+            break;
+        };
+        pos = close + 1;
+        let tag = &html[start + 1..close];
+        let name_end = tag
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        if name != "link" && name != "script" {
+            continue;
+        }
+        let attrs = attributes(&tag[name_end..]);
+        let get = |key: &str| {
+            attrs
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str())
+        };
+        let crossorigin = match get("crossorigin") {
+            Some("use-credentials") => "; crossorigin=use-credentials",
+            Some(_) => "; crossorigin",
+            None => "",
+        };
+        let (url, params) = if name == "script" {
+            let Some(src) = get("src") else {
+                continue;
+            };
+            if get("type") == Some("module") {
+                (src, format!("rel=modulepreload{}", crossorigin))
+            } else {
+                (src, format!("rel=preload; as=script{}", crossorigin))
+            }
+        } else {
+            let Some(href) = get("href") else {
+                continue;
+            };
+            let rels: Vec<String> = get("rel")
+                .unwrap_or_default()
+                .split_ascii_whitespace()
+                .map(str::to_ascii_lowercase)
+                .collect();
+            let has = |rel: &str| rels.iter().any(|r| r == rel);
+            if has("stylesheet") && !has("alternate") {
+                (href, format!("rel=preload; as=style{}", crossorigin))
+            } else if has("preload") {
+                let Some(kind) = get("as") else {
+                    continue;
+                };
+                let mut params = format!("rel=preload; as={}", kind);
+                if let Some(mime) = get("type") {
+                    params.push_str(&format!("; type=\"{}\"", mime));
+                }
+                // 字体总是以 CORS 方式获取，缺少 crossorigin 时预加载的结果不会被使用
+                if kind == "font" && crossorigin.is_empty() {
+                    params.push_str("; crossorigin");
+                } else {
+                    params.push_str(crossorigin);
+                }
+                (href, params)
+            } else if has("modulepreload") {
+                (href, format!("rel=modulepreload{}", crossorigin))
+            } else if has("preconnect") {
+                (href, format!("rel=preconnect{}", crossorigin))
+            } else {
+                continue;
+            }
+        };
+        let url = url.trim();
+        if url.is_empty()
+            || url.starts_with("data:")
+            || url.contains(['<', '>', '"'])
+            || url.chars().any(|c| c.is_control())
+        {
+            continue;
+        }
+        let link = format!("<{}>; {}", url, params);
+        if !links.contains(&link) {
+            links.push(link);
+        }
+        if links.len() >= max_links {
+            break;
+        }
+    }
+    links
+}
+
+// 在 resolve 之后执行，看到的是改写后的索引文件路径；只处理磁盘上的 HTML 文件
+pub async fn respond(
+    hints: &EarlyHints,
+    resolver: &Resolver,
+    req: Request,
+    next: Next,
+) -> Response {
+    let is_head = req.method() == Method::HEAD;
+    if !(is_head || req.method() == Method::GET) {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    if !(path.ends_with(".html") || path.ends_with(".htm")) {
+        return next.run(req).await;
+    }
+    let page = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or(path.as_str(), |original| original.path());
+    if !hints.matches(page) {
+        return next.run(req).await;
+    }
+    let Some(fs_path) = resolver.include_path(&path).await else {
+        return next.run(req).await;
+    };
+    let scanned = match hints.links(&fs_path).await {
+        Ok(scanned) if !scanned.links.is_empty() => scanned,
+        Ok(_) => return next.run(req).await,
+        Err(e) => {
+            debug!("Failed to scan {} for early hints: {}", path, e);
+            return next.run(req).await;
+        }
+    };
+    // HTTP/2 连接无法发送 1xx，只在最终响应中带 Link 头
+    if !is_head && req.version() == Version::HTTP_11 {
+        if let Some(sender) = req.extensions().get::<Informational>() {
+            let mut head = String::from("HTTP/1.1 103 Early Hints\r\n");
+            for link in &scanned.links {
+                head.push_str("Link: ");
+                head.push_str(link.to_str().unwrap_or_default());
+                head.push_str("\r\n");
+            }
+            head.push_str("\r\n");
+            if let Err(e) = sender.send(head.as_bytes()).await {
+                debug!("Failed to send early hints for {}: {}", path, e);
+            }
+        }
+    }
+    let mut response = next.run(req).await;
+    if response.status() == StatusCode::OK {
+        for link in &scanned.links {
+            response.headers_mut().append(header::LINK, link.clone());
+        }
+    }
+    response
+}
+
+// 连接的写入端：HTTP/1.1 请求处理中可以在最终响应之前写入 1xx 响应。
+// hyper 在上一个响应完全写出之后才读取下一个请求头，此时写入不会与其他响应交错
+pub struct HintIo<S> {
+    inner: Arc<Mutex<S>>,
+}
+
+type SharedWriter = Arc<Mutex<dyn AsyncWrite + Send + Unpin>>;
+
+// 作为请求扩展传给处理函数
+#[derive(Clone)]
+pub struct Informational(SharedWriter);
+
+pub fn wrap<S>(stream: S) -> (HintIo<S>, Informational)
+where
+    S: AsyncWrite + Send + Unpin + 'static,
+{
+    let inner = Arc::new(Mutex::new(stream));
+    let writer: SharedWriter = inner.clone();
+    (HintIo { inner }, Informational(writer))
+}
+
+impl Informational {
+    async fn send(&self, data: &[u8]) -> io::Result<()> {
+        let mut written = 0;
+        poll_fn(|cx| {
+            let mut writer = self.0.lock().unwrap();
+            while written < data.len() {
+                match Pin::new(&mut *writer).poll_write(cx, &data[written..]) {
+                    Poll::Ready(Ok(0)) => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                    Poll::Ready(Ok(n)) => written += n,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            Pin::new(&mut *writer).poll_flush(cx)
+        })
+        .await
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for HintIo<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.inner.lock().unwrap()).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for HintIo<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.inner.lock().unwrap()).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut *self.inner.lock().unwrap()).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.lock().unwrap().is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.inner.lock().unwrap()).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.inner.lock().unwrap()).poll_shutdown(cx)
+    }
+}
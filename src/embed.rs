@@ -0,0 +1,61 @@
+// 内嵌资源（cargo feature "embed"）：编译时将 SONICWAVE_EMBED_DIR 指定的目录打包进可执行文件，
+// 服务时直接从内存返回，应用与服务器一起作为单个文件发布
+use crate::memfs::MemoryFs;
+use std::io;
+
+pub const ROOT: &str = "<embedded>";
+
+#[cfg(feature = "embed")]
+#[derive(rust_embed::Embed)]
+#[folder = "$SONICWAVE_EMBED_DIR"]
+struct Assets;
+
+#[cfg(feature = "embed")]
+pub fn load() -> io::Result<MemoryFs> {
+    use crate::memfs::MemFile;
+    use axum::body::Bytes;
+    use axum::http::HeaderValue;
+    use std::borrow::Cow;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let mut files = MemoryFs::new(ROOT);
+    for name in <Assets as rust_embed::Embed>::iter() {
+        let Some(asset) = <Assets as rust_embed::Embed>::get(&name) else {
+            continue;
+        };
+        let data = match asset.data {
+            Cow::Borrowed(data) => Bytes::from_static(data),
+            Cow::Owned(data) => Bytes::from(data),
+        };
+        let hash = asset.metadata.sha256_hash();
+        let etag = HeaderValue::from_str(&format!(
+            "\"{}\"",
+            hash[..16]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        ))
+        .ok();
+        let modified = asset
+            .metadata
+            .last_modified()
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+        files.insert(
+            &name,
+            MemFile {
+                data,
+                modified,
+                etag,
+            },
+        );
+    }
+    Ok(files)
+}
+
+#[cfg(not(feature = "embed"))]
+pub fn load() -> io::Result<MemoryFs> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "built without the embed feature",
+    ))
+}
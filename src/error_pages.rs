@@ -0,0 +1,277 @@
+// 错误响应：按状态码返回站点目录中的错误页（可使用模板变量），或按 Accept 返回内置 HTML / JSON 正文
+use crate::memfs::MemoryFs;
+use crate::s3::S3Store;
+use crate::vary;
+use axum::body::{Body, HttpBody};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+// 状态码 -> 页面文件；键可以是 "404" 这样的具体状态码或 "4xx" / "5xx"
+pub struct ErrorPages {
+    exact: BTreeMap<u16, PathBuf>,
+    class: BTreeMap<u16, PathBuf>,
+    // 内存中的站点从这里读取页面，而不是磁盘
+    files: Option<Arc<MemoryFs>>,
+    // 对象存储中的站点从存储读取页面
+    store: Option<Arc<S3Store>>,
+}
+
+impl ErrorPages {
+    pub fn new(dir: &Path, pages: &BTreeMap<String, String>) -> Result<Self, String> {
+        let mut exact = BTreeMap::new();
+        let mut class = BTreeMap::new();
+        for (key, page) in pages {
+            let path = dir.join(page.trim_start_matches('/'));
+            let key = key.trim().to_ascii_lowercase();
+            if let Some(digit) = key.strip_suffix("xx") {
+                match digit.parse::<u16>() {
+                    Ok(d @ 4..=5) => {
+                        class.insert(d, path);
+                    }
+                    _ => return Err(format!("invalid error page status `{}`", key)),
+                }
+            } else {
+                match key.parse::<u16>() {
+                    Ok(code @ 400..=599) => {
+                        exact.insert(code, path);
+                    }
+                    _ => return Err(format!("invalid error page status `{}`", key)),
+                }
+            }
+        }
+        Ok(ErrorPages {
+            exact,
+            class,
+            files: None,
+            store: None,
+        })
+    }
+
+    pub fn with_files(mut self, files: Arc<MemoryFs>) -> Self {
+        self.files = Some(files);
+        self
+    }
+
+    pub fn with_store(mut self, store: Arc<S3Store>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    async fn read(&self, page: &Path) -> std::io::Result<String> {
+        if let Some(store) = &self.store {
+            let data = store.read(page).await?;
+            return Ok(String::from_utf8_lossy(&data).into_owned());
+        }
+        match &self.files {
+            Some(files) => files
+                .get(page)
+                .map(|file| String::from_utf8_lossy(&file.data).into_owned())
+                .ok_or_else(|| std::io::ErrorKind::NotFound.into()),
+            None => tokio::fs::read_to_string(page).await,
+        }
+    }
+
+    fn page(&self, status: StatusCode) -> Option<&Path> {
+        let code = status.as_u16();
+        self.exact
+            .get(&code)
+            .or_else(|| self.class.get(&(code / 100)))
+            .map(PathBuf::as_path)
+    }
+}
+
+// 渲染错误响应时可用的变量：{{status}} {{reason}} {{path}} {{request_id}} {{timestamp}}
+struct ErrorContext {
+    status: StatusCode,
+    path: String,
+    request_id: String,
+    timestamp: String,
+}
+
+impl ErrorContext {
+    fn render(&self, template: &str, escape: fn(&str) -> String) -> String {
+        template
+            .replace("{{status}}", self.status.as_str())
+            .replace(
+                "{{reason}}",
+                &escape(self.status.canonical_reason().unwrap_or("Error")),
+            )
+            .replace("{{path}}", &escape(&self.path))
+            .replace("{{request_id}}", &escape(&self.request_id))
+            .replace("{{timestamp}}", &self.timestamp)
+    }
+
+    fn json(&self) -> String {
+        serde_json::json!({
+            "status": self.status.as_u16(),
+            "error": self.status.canonical_reason().unwrap_or("Error"),
+            "path": self.path,
+            "request_id": self.request_id,
+            "timestamp": self.timestamp,
+        })
+        .to_string()
+    }
+}
+
+const DEFAULT_TEMPLATE: &str = "<!DOCTYPE html>
+<html><head><meta charset=\"utf-8\"><title>{{status}} {{reason}}</title></head>
+<body><h1>{{status}} {{reason}}</h1><p>{{path}}</p><hr><small>request id {{request_id}} &middot; {{timestamp}}</small></body></html>
+";
+
+// 为错误响应生成正文：JSON 客户端得到 JSON，其他客户端得到错误页（自定义页面或内置模板）
+pub async fn replace(State(pages): State<Arc<ErrorPages>>, req: Request, next: Next) -> Response {
+    let is_head = req.method() == Method::HEAD;
+    let json = wants_json(req.headers());
+    let path = req.uri().path().to_string();
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-")
+        .to_string();
+
+    let response = next.run(req).await;
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+    let page = pages.page(status);
+    let empty = response.body().size_hint().exact() == Some(0);
+    if page.is_none() && !empty {
+        return response;
+    }
+
+    let ctx = ErrorContext {
+        status,
+        path,
+        request_id,
+        timestamp: rfc3339(SystemTime::now()),
+    };
+    let (content, mime) = match page {
+        _ if json => (ctx.json(), "application/json"),
+        Some(page) => match pages.read(page).await {
+            Ok(template) => match mime_for(page) {
+                "application/json" => (ctx.render(&template, json_escape), "application/json"),
+                mime => (ctx.render(&template, html_escape), mime),
+            },
+            Err(e) => {
+                warn!("Failed to read error page {}: {}", page.display(), e);
+                (
+                    ctx.render(DEFAULT_TEMPLATE, html_escape),
+                    "text/html; charset=utf-8",
+                )
+            }
+        },
+        None => (
+            ctx.render(DEFAULT_TEMPLATE, html_escape),
+            "text/html; charset=utf-8",
+        ),
+    };
+
+    let (mut parts, _) = response.into_parts();
+    for name in [
+        header::CONTENT_ENCODING,
+        header::ACCEPT_RANGES,
+        header::LAST_MODIFIED,
+        header::ETAG,
+    ] {
+        parts.headers.remove(name);
+    }
+    // 416 的 Content-Range（bytes */长度）告诉客户端实际大小，需要保留
+    if status != StatusCode::RANGE_NOT_SATISFIABLE {
+        parts.headers.remove(header::CONTENT_RANGE);
+    }
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(mime));
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(content.len()));
+    // 错误格式取决于 Accept
+    vary::add(&mut parts.headers, "Accept");
+    let body = if is_head {
+        Body::empty()
+    } else {
+        Body::from(content)
+    };
+    Response::from_parts(parts, body)
+}
+
+// Accept 中 JSON 的权重高于 HTML 时返回 JSON
+fn wants_json(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let mut json_q = 0.0f32;
+    let mut html_q = 0.0f32;
+    for range in accept.split(',') {
+        let mut params = range.split(';');
+        let media = params.next().unwrap_or("").trim().to_ascii_lowercase();
+        let q = params
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if media == "application/json" || media.ends_with("+json") {
+            json_q = json_q.max(q);
+        } else if media == "text/html" || media == "application/xhtml+xml" {
+            html_q = html_q.max(q);
+        }
+    }
+    json_q > html_q
+}
+
+pub fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+fn json_escape(s: &str) -> String {
+    let quoted = serde_json::to_string(s).unwrap_or_default();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+// "2026-10-14T07:00:32Z"
+pub fn rfc3339(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // 公历日期换算（Howard Hinnant 的 civil_from_days）
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn mime_for(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        _ => "text/html; charset=utf-8",
+    }
+}
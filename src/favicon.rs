@@ -0,0 +1,166 @@
+// favicon 兜底（[favicon]）：/favicon.ico 不存在时返回站点内配置的图标（PNG / SVG 等），
+// 仍不存在时返回内置图标或 204，并带较长的缓存时间，浏览器的探测请求不再产生 404
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, LazyLock};
+use tower::ServiceExt;
+use tracing::debug;
+
+pub const FAVICON_PATH: &str = "/favicon.ico";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum FaviconFallback {
+    #[default]
+    Builtin,
+    Empty,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct FaviconConfig {
+    // 站点内的图标路径，如 "/images/icon.svg"；/favicon.ico 不存在时返回该文件
+    #[serde(default)]
+    pub file: Option<String>,
+    // file 未设置或同样不存在时：builtin 返回内置图标，empty 返回 204
+    #[serde(default)]
+    pub fallback: FaviconFallback,
+    // 兜底响应的缓存时间（秒）
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+}
+
+fn default_max_age() -> u64 {
+    7 * 24 * 3600
+}
+
+pub struct Favicon {
+    file: Option<Uri>,
+    fallback: FaviconFallback,
+    cache_control: HeaderValue,
+    // 不带本中间件的站点，用于请求 file
+    site: Router,
+}
+
+impl Favicon {
+    pub fn new(config: &FaviconConfig, site: Router) -> Result<Self, String> {
+        let file = match &config.file {
+            Some(file) if !file.starts_with('/') => {
+                return Err(format!("[favicon] file `{}` must start with '/'", file));
+            }
+            Some(file) => Some(
+                file.parse()
+                    .map_err(|e| format!("invalid [favicon] file `{}`: {}", file, e))?,
+            ),
+            None => None,
+        };
+        Ok(Favicon {
+            file,
+            fallback: config.fallback,
+            cache_control: HeaderValue::from_str(&format!("public, max-age={}", config.max_age))
+                .map_err(|e| e.to_string())?,
+            site,
+        })
+    }
+}
+
+pub async fn fallback(State(favicon): State<Arc<Favicon>>, req: Request, next: Next) -> Response {
+    if req.uri().path() != FAVICON_PATH
+        || !(req.method() == Method::GET || req.method() == Method::HEAD)
+    {
+        return next.run(req).await;
+    }
+    let (parts, body) = req.into_parts();
+    let response = next.run(Request::from_parts(parts.clone(), body)).await;
+    if response.status() != StatusCode::NOT_FOUND {
+        return response;
+    }
+    if let Some(file) = &favicon.file {
+        let mut req = Request::from_parts(parts.clone(), Body::empty());
+        *req.uri_mut() = file.clone();
+        let mut response = match favicon.site.clone().oneshot(req).await {
+            Ok(response) => response,
+            Err(e) => match e {},
+        };
+        if response.status() != StatusCode::NOT_FOUND {
+            if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+                response
+                    .headers_mut()
+                    .insert(header::CACHE_CONTROL, favicon.cache_control.clone());
+            }
+            return response;
+        }
+        debug!("[favicon] file {} not found", file);
+    }
+    match favicon.fallback {
+        FaviconFallback::Empty => (
+            StatusCode::NO_CONTENT,
+            [(header::CACHE_CONTROL, favicon.cache_control.clone())],
+        )
+            .into_response(),
+        FaviconFallback::Builtin => {
+            let body = if parts.method == Method::HEAD {
+                Body::empty()
+            } else {
+                Body::from(BUILTIN.clone())
+            };
+            let mut response = Response::new(body);
+            let headers = response.headers_mut();
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("image/x-icon"),
+            );
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(BUILTIN.len()));
+            headers.insert(header::CACHE_CONTROL, favicon.cache_control.clone());
+            response
+        }
+    }
+}
+
+// 内置图标：16x16、32 位 BMP 格式的 ICO，透明背景上的圆点与三道声波
+static BUILTIN: LazyLock<Bytes> = LazyLock::new(|| {
+    const SIZE: u32 = 16;
+    let pixels = SIZE * SIZE * 4;
+    // AND 掩码每行按 4 字节对齐，透明度由 alpha 通道决定，全部为 0
+    let mask = SIZE * 4;
+    let image = 40 + pixels + mask;
+    let mut ico = Vec::with_capacity((6 + 16 + image) as usize);
+    // ICONDIR
+    ico.extend_from_slice(&[0, 0, 1, 0, 1, 0]);
+    // ICONDIRENTRY：宽、高、调色板、保留、平面数、位深、数据长度、偏移
+    ico.extend_from_slice(&[SIZE as u8, SIZE as u8, 0, 0, 1, 0, 32, 0]);
+    ico.extend_from_slice(&image.to_le_bytes());
+    ico.extend_from_slice(&22u32.to_le_bytes());
+    // BITMAPINFOHEADER，高度包含 AND 掩码，为图像高度的两倍
+    ico.extend_from_slice(&40u32.to_le_bytes());
+    ico.extend_from_slice(&SIZE.to_le_bytes());
+    ico.extend_from_slice(&(SIZE * 2).to_le_bytes());
+    ico.extend_from_slice(&[1, 0, 32, 0]);
+    ico.extend_from_slice(&0u32.to_le_bytes());
+    ico.extend_from_slice(&(pixels + mask).to_le_bytes());
+    ico.extend_from_slice(&[0; 16]);
+    // 像素自下而上逐行存储，BGRA
+    for y in (0..SIZE).rev() {
+        for x in 0..SIZE {
+            let (dx, dy) = (x as f64 - 2.5, y as f64 - 7.5);
+            let r = (dx * dx + dy * dy).sqrt();
+            let on_wave = dx >= 0.0
+                && (dy / r.max(1.0)).abs() < 0.75
+                && [4.5, 8.5, 12.5].iter().any(|ring| (r - ring).abs() < 1.0);
+            let on_wave = on_wave || r < 1.6;
+            if on_wave {
+                ico.extend_from_slice(&[0xe0, 0x8a, 0x1e, 0xff]);
+            } else {
+                ico.extend_from_slice(&[0, 0, 0, 0]);
+            }
+        }
+    }
+    ico.extend(std::iter::repeat_n(0, mask as usize));
+    Bytes::from(ico)
+});
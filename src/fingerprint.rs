@@ -0,0 +1,82 @@
+// 带内容哈希的文件名（[fingerprint]）：如 app.3f9c2b.js、index-5d41402a.css，内容变化时文件名随之变化，
+// 可以使用长期 immutable 缓存；其余文件使用需要验证的 html_cache_control
+use axum::http::header::HeaderValue;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct FingerprintConfig {
+    // 匹配文件名（路径最后一段）的正则；第一个捕获组为哈希，生成资源清单时据此还原原始文件名
+    #[serde(default = "default_pattern")]
+    pub pattern: String,
+    // 带哈希的文件使用的缓存策略
+    #[serde(default = "default_cache_control")]
+    pub cache_control: String,
+    // 资源清单的 URL 路径，如 "/asset-manifest.json"；每次请求时按主目录生成
+    #[serde(default)]
+    pub manifest: Option<String>,
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        FingerprintConfig {
+            pattern: default_pattern(),
+            cache_control: default_cache_control(),
+            manifest: None,
+        }
+    }
+}
+
+// 扩展名前以 "." 或 "-" 分隔的至少 6 位十六进制哈希，允许 .min.js、.css.map 等多段扩展名
+fn default_pattern() -> String {
+    r"[.-]([0-9a-f]{6,})\.[^/]+$".to_string()
+}
+
+fn default_cache_control() -> String {
+    "public, max-age=31536000, immutable".to_string()
+}
+
+pub struct Fingerprint {
+    pattern: Regex,
+    pub cache_control: HeaderValue,
+}
+
+impl Fingerprint {
+    pub fn new(config: &FingerprintConfig) -> Result<Self, String> {
+        let pattern = Regex::new(&config.pattern)
+            .map_err(|e| format!("invalid fingerprint pattern `{}`: {}", config.pattern, e))?;
+        let cache_control = HeaderValue::from_str(&config.cache_control).map_err(|_| {
+            format!(
+                "invalid fingerprint cache_control `{}`",
+                config.cache_control
+            )
+        })?;
+        Ok(Fingerprint {
+            pattern,
+            cache_control,
+        })
+    }
+
+    // path 为已解码的请求路径，只检查最后一段
+    pub fn matches(&self, path: &str) -> bool {
+        let name = path.rsplit('/').next().unwrap_or_default();
+        !name.is_empty() && self.pattern.is_match(name)
+    }
+
+    // 生成资源清单需要用捕获组标出哈希
+    pub fn has_hash_group(&self) -> bool {
+        self.pattern.captures_len() > 1
+    }
+
+    // 去掉哈希及其前面的分隔符得到原始文件名："app.3f9c2b.js" -> "app.js"；
+    // 不匹配或模式中没有捕获组时返回 None
+    pub fn logical_name(&self, name: &str) -> Option<String> {
+        let hash = self.pattern.captures(name)?.get(1)?;
+        let mut start = hash.start();
+        if name[..start].ends_with(['.', '-', '_', '~']) {
+            start -= 1;
+        }
+        Some(format!("{}{}", &name[..start], &name[hash.end()..]))
+    }
+}
@@ -0,0 +1,236 @@
+// 转发认证（[forward_auth]），与 Traefik ForwardAuth、nginx auth_request 相同：受保护的请求先带着原始请求头
+// 向外部认证服务发一个 GET 子请求，2xx 放行，其他状态（401、302 跳转到登录页等）连同响应头与响应体原样返回给客户端。
+// 认证服务响应中的指定头（如 X-Auth-User）复制到请求上，反向代理的上游可以直接使用
+use crate::basic_auth;
+use crate::forwarded::{self, ClientInfo};
+use crate::glob::PathPattern;
+use crate::proxy::strip_hop_by_hop;
+use crate::site;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use axum::http::{StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+// 拒绝时返回给客户端的认证服务响应体大小上限
+const MAX_DENY_BODY: usize = 64 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ForwardAuthConfig {
+    // 认证服务地址，如 "http://127.0.0.1:9091/api/verify"
+    pub url: String,
+    // 需要认证的路径前缀
+    #[serde(default = "default_prefixes")]
+    pub prefixes: Vec<String>,
+    // 前缀下无需认证的路径（模式规则与 deny 相同）
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // 转发给认证服务的请求头；为空时转发全部（逐跳头除外）
+    #[serde(default)]
+    pub request_headers: Vec<String>,
+    // 放行时从认证服务响应复制到请求上的头；客户端发来的同名头总是先删除，不能伪造
+    #[serde(default)]
+    pub response_headers: Vec<String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_prefixes() -> Vec<String> {
+    vec!["/".to_string()]
+}
+
+fn default_timeout() -> u64 {
+    5
+}
+
+type HttpClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+pub struct ForwardAuth {
+    url: Uri,
+    prefixes: Vec<String>,
+    exclude: Vec<PathPattern>,
+    readiness_path: String,
+    request_headers: Vec<HeaderName>,
+    response_headers: Vec<HeaderName>,
+    timeout: Duration,
+    client: HttpClient,
+}
+
+impl ForwardAuth {
+    pub fn new(config: &ForwardAuthConfig, readiness_path: &str) -> Result<Self, String> {
+        let url = config
+            .url
+            .parse::<Uri>()
+            .map_err(|e| format!("[forward_auth] invalid url `{}`: {}", config.url, e))?;
+        if !matches!(url.scheme_str(), Some("http" | "https")) || url.host().is_none() {
+            return Err(format!(
+                "[forward_auth] url `{}` must be an http:// or https:// URL",
+                config.url
+            ));
+        }
+        let names = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| {
+                    HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|_| format!("[forward_auth] invalid header name `{}`", name))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let exclude = config
+            .exclude
+            .iter()
+            .map(|pattern| PathPattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(connector);
+        Ok(ForwardAuth {
+            url,
+            prefixes: config.prefixes.clone(),
+            exclude,
+            readiness_path: readiness_path.to_string(),
+            request_headers: names(&config.request_headers)?,
+            response_headers: names(&config.response_headers)?,
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+            client: Client::builder(TokioExecutor::new()).build(https),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} on {:?}", self.url, self.prefixes)
+    }
+
+    fn protected(&self, path: &str) -> bool {
+        let path = site::normalize_path(path);
+        if path == self.readiness_path
+            || !self
+                .prefixes
+                .iter()
+                .any(|prefix| path.starts_with(prefix.as_str()))
+        {
+            return false;
+        }
+        !self.exclude.iter().any(|pattern| pattern.matches(&path))
+    }
+
+    // 子请求：原始请求头加上描述原始请求的 X-Forwarded-* 与 X-Original-*，不带请求体
+    fn subrequest(&self, req: &Request) -> Result<Request, axum::http::Error> {
+        let mut headers = if self.request_headers.is_empty() {
+            let mut headers = req.headers().clone();
+            strip_hop_by_hop(&mut headers);
+            headers
+        } else {
+            let mut headers = HeaderMap::new();
+            for name in &self.request_headers {
+                for value in req.headers().get_all(name) {
+                    headers.append(name.clone(), value.clone());
+                }
+            }
+            headers
+        };
+        // X-Forwarded-For 只填真实客户端 IP，客户端自己发来的值不转发
+        for name in [
+            header::HOST,
+            header::CONTENT_LENGTH,
+            header::CONTENT_TYPE,
+            HeaderName::from_static("x-forwarded-for"),
+        ] {
+            headers.remove(name);
+        }
+        let info = req.extensions().get::<ClientInfo>();
+        let origin = forwarded::origin(req.headers(), info);
+        let uri = req
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/");
+        let host = origin.split_once("://").map_or("", |(_, host)| host);
+        let client = info.and_then(|info| info.ip).map(|ip| ip.to_string());
+        let values = [
+            ("x-forwarded-method", Some(req.method().as_str())),
+            (
+                "x-forwarded-proto",
+                Some(info.map_or("http", |info| info.scheme)),
+            ),
+            ("x-forwarded-host", Some(host)),
+            ("x-forwarded-uri", Some(uri)),
+            ("x-forwarded-for", client.as_deref()),
+            ("x-original-method", Some(req.method().as_str())),
+            ("x-original-url", Some(&format!("{}{}", origin, uri))),
+        ];
+        for (name, value) in values {
+            if let Some(value) = value.and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+
+        let mut request = Request::builder()
+            .uri(self.url.clone())
+            .body(Body::empty())?;
+        *request.headers_mut() = headers;
+        Ok(request)
+    }
+}
+
+pub async fn check(State(auth): State<Arc<ForwardAuth>>, mut req: Request, next: Next) -> Response {
+    if !auth.protected(req.uri().path()) {
+        return next.run(req).await;
+    }
+    let subrequest = match auth.subrequest(&req) {
+        Ok(subrequest) => subrequest,
+        Err(e) => {
+            warn!("Failed to build forward auth request: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let response = match tokio::time::timeout(auth.timeout, auth.client.request(subrequest)).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => {
+            warn!("Forward auth request to {} failed: {}", auth.url, e);
+            return StatusCode::BAD_GATEWAY.into_response();
+        }
+        Err(_) => {
+            warn!("Forward auth request to {} timed out", auth.url);
+            return StatusCode::GATEWAY_TIMEOUT.into_response();
+        }
+    };
+
+    if response.status().is_success() {
+        let headers = req.headers_mut();
+        for name in &auth.response_headers {
+            headers.remove(name);
+            for value in response.headers().get_all(name) {
+                headers.append(name.clone(), value.clone());
+            }
+        }
+        let mut response = next.run(req).await;
+        basic_auth::private(response.headers_mut());
+        return response;
+    }
+
+    // 拒绝：认证服务的响应原样返回，其中可能有跳转到登录页的 Location 或 WWW-Authenticate
+    let (mut parts, body) = response.into_parts();
+    parts.version = Default::default();
+    strip_hop_by_hop(&mut parts.headers);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let body = axum::body::to_bytes(Body::new(body), MAX_DENY_BODY)
+        .await
+        .unwrap_or_default();
+    Response::from_parts(parts, Body::from(body))
+}
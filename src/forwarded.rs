@@ -0,0 +1,231 @@
+// 可信代理处理：对端属于 trusted_proxies 时，从 Forwarded / X-Forwarded-* 还原客户端 IP 与协议；
+// 否则移除这些头，防止客户端伪造
+use crate::listener::PeerAddr;
+use crate::server::ClientAddr;
+use crate::tls::TlsInfo;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, HeaderName};
+use axum::middleware::Next;
+use axum::response::Response;
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+const FORWARDED_HEADERS: [&str; 5] = [
+    "forwarded",
+    "x-forwarded-for",
+    "x-forwarded-proto",
+    "x-forwarded-host",
+    "x-real-ip",
+];
+
+// 解析后的客户端信息，供日志、访问控制与重定向使用
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    // 真实客户端 IP（Unix socket 且无转发头时为空）
+    pub ip: Option<IpAddr>,
+    // 客户端看到的协议："http" 或 "https"
+    pub scheme: &'static str,
+    // 连接来自可信代理，请求中的 X-Request-ID 等头可以沿用
+    pub trusted: bool,
+    // 可信代理传入的原始 Host（Forwarded host= 或 X-Forwarded-Host）
+    pub host: Option<String>,
+}
+
+// 客户端看到的站点地址，如 "https://example.com"，用于生成播放列表、feed 中的绝对 URL
+pub fn origin(headers: &HeaderMap, client: Option<&ClientInfo>) -> String {
+    let host = headers
+        .get(axum::http::header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("localhost");
+    let scheme = client.map_or("http", |client| client.scheme);
+    format!("{}://{}", scheme, host)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    nets: Vec<IpNet>,
+    // 通过 "unix" 信任 Unix socket 上的对端（本机反向代理）
+    unix: bool,
+}
+
+impl TrustedProxies {
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        let mut trusted = TrustedProxies::default();
+        for entry in entries {
+            let entry = entry.trim();
+            if entry.eq_ignore_ascii_case("unix") {
+                trusted.unix = true;
+                continue;
+            }
+            let net = entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("invalid trusted proxy `{}`", entry))?;
+            trusted.nets.push(net);
+        }
+        Ok(trusted)
+    }
+
+    fn contains_ip(&self, ip: &IpAddr) -> bool {
+        let ip = canonical(*ip);
+        self.nets.iter().any(|net| net.contains(&ip))
+    }
+
+    fn trusts(&self, peer: &PeerAddr) -> bool {
+        match peer {
+            PeerAddr::Tcp(addr) => self.contains_ip(&addr.ip()),
+            #[cfg(unix)]
+            PeerAddr::Unix => self.unix,
+        }
+    }
+}
+
+// IPv4-mapped IPv6 地址按 IPv4 匹配
+fn canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(v6)),
+        v4 => v4,
+    }
+}
+
+pub async fn resolve_client(
+    State(trusted): State<Arc<TrustedProxies>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let peer = req.extensions().get::<ClientAddr>().map(|c| c.0);
+    let peer_ip = match peer {
+        Some(PeerAddr::Tcp(addr)) => Some(canonical(addr.ip())),
+        _ => None,
+    };
+
+    // 直接接受的 TLS 连接
+    let scheme = if req.extensions().get::<TlsInfo>().is_some() {
+        "https"
+    } else {
+        "http"
+    };
+    let info = match peer {
+        Some(peer) if trusted.trusts(&peer) => {
+            from_headers(req.headers(), &trusted, peer_ip, scheme)
+        }
+        _ => {
+            // 不可信来源：丢弃转发头
+            for name in FORWARDED_HEADERS {
+                req.headers_mut().remove(HeaderName::from_static(name));
+            }
+            ClientInfo {
+                ip: peer_ip,
+                scheme,
+                trusted: false,
+                host: None,
+            }
+        }
+    };
+
+    req.extensions_mut().insert(info);
+    next.run(req).await
+}
+
+fn from_headers(
+    headers: &HeaderMap,
+    trusted: &TrustedProxies,
+    peer_ip: Option<IpAddr>,
+    // 没有转发协议头时使用连接本身的协议
+    scheme: &'static str,
+) -> ClientInfo {
+    // RFC 7239 Forwarded 优先于 X-Forwarded-*
+    let (hops, proto, host) = match parse_forwarded(headers) {
+        Some(parsed) => parsed,
+        None => (
+            header_list(headers, "x-forwarded-for")
+                .into_iter()
+                .filter_map(|v| parse_ip(&v))
+                .collect(),
+            header_list(headers, "x-forwarded-proto").pop(),
+            header_list(headers, "x-forwarded-host").into_iter().next(),
+        ),
+    };
+
+    // 从右往左跳过可信代理，第一个不可信的地址即为客户端
+    let mut ip = peer_ip;
+    for hop in hops.iter().rev() {
+        ip = Some(canonical(*hop));
+        if !trusted.contains_ip(hop) {
+            break;
+        }
+    }
+
+    let scheme = match proto.as_deref().map(str::to_ascii_lowercase) {
+        Some(p) if p == "https" || p == "wss" => "https",
+        Some(_) => "http",
+        None => scheme,
+    };
+
+    ClientInfo {
+        ip,
+        scheme,
+        trusted: true,
+        host,
+    }
+}
+
+// 多个同名头与逗号分隔值展开为列表
+fn header_list(headers: &HeaderMap, name: &str) -> Vec<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+// Forwarded: for=192.0.2.43;proto=https;host=example.com, for="[2001:db8::1]:4711"
+// 返回各跳地址、最后的 proto 与最早（离客户端最近）的 host
+fn parse_forwarded(headers: &HeaderMap) -> Option<(Vec<IpAddr>, Option<String>, Option<String>)> {
+    let elements = header_list(headers, "forwarded");
+    if elements.is_empty() {
+        return None;
+    }
+
+    let mut hops = Vec::new();
+    let mut proto = None;
+    let mut host = None;
+    for element in elements {
+        for pair in element.split(';') {
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().trim_matches('"');
+            match key.trim().to_ascii_lowercase().as_str() {
+                "for" => {
+                    if let Some(ip) = parse_ip(value) {
+                        hops.push(ip);
+                    }
+                }
+                "proto" => proto = Some(value.to_string()),
+                "host" if host.is_none() => host = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+    Some((hops, proto, host))
+}
+
+// 接受 "1.2.3.4"、"1.2.3.4:80"、"[::1]"、"[::1]:80"、"::1"
+fn parse_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim();
+    if let Ok(ip) = value.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Some(rest) = value.strip_prefix('[') {
+        return rest.split(']').next()?.parse().ok();
+    }
+    value.rsplit_once(':')?.0.parse().ok()
+}
@@ -0,0 +1,152 @@
+// 按国家的访问控制（[geoip]）：用 MaxMind GeoLite2 数据库查询客户端 IP 所在国家，
+// 按路径前缀允许或拒绝，国家代码同时写入访问日志
+use crate::forwarded::ClientInfo;
+use crate::site;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct GeoipConfig {
+    // GeoLite2-Country 或 GeoLite2-City 数据库（.mmdb）
+    pub database: PathBuf,
+    // 按顺序匹配，第一条前缀匹配的规则生效；没有匹配的规则时放行
+    #[serde(default)]
+    pub rules: Vec<GeoRuleConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct GeoRuleConfig {
+    // URL 路径前缀，如 "/downloads/"
+    pub prefix: String,
+    // 只允许这些国家（ISO 3166-1 代码），与 deny 二选一
+    #[serde(default)]
+    pub allow: Vec<String>,
+    // 拒绝这些国家
+    #[serde(default)]
+    pub deny: Vec<String>,
+    // 查不到国家的地址（内网、数据库缺失）是否放行；默认 deny 规则放行、allow 规则拒绝
+    #[serde(default)]
+    pub allow_unknown: Option<bool>,
+}
+
+// 客户端所在国家，附加在响应上供访问日志使用
+#[derive(Debug, Clone)]
+pub struct Country(pub String);
+
+struct Rule {
+    prefix: String,
+    allow: Vec<String>,
+    deny: Vec<String>,
+    allow_unknown: bool,
+}
+
+impl Rule {
+    fn permits(&self, country: Option<&str>) -> bool {
+        let Some(country) = country else {
+            return self.allow_unknown;
+        };
+        if !self.allow.is_empty() {
+            return self.allow.iter().any(|code| code == country);
+        }
+        !self.deny.iter().any(|code| code == country)
+    }
+}
+
+pub struct Geoip {
+    reader: Reader<Vec<u8>>,
+    rules: Vec<Rule>,
+}
+
+impl Geoip {
+    pub fn new(config: &GeoipConfig) -> Result<Self, String> {
+        let reader = Reader::open_readfile(&config.database).map_err(|e| {
+            format!(
+                "failed to open GeoIP database {}: {}",
+                config.database.display(),
+                e
+            )
+        })?;
+        let codes = |list: &[String]| -> Vec<String> {
+            list.iter().map(|code| code.to_ascii_uppercase()).collect()
+        };
+        let mut rules = Vec::new();
+        for rule in &config.rules {
+            if !rule.prefix.starts_with('/') {
+                return Err(format!(
+                    "geoip rule prefix `{}` must start with '/'",
+                    rule.prefix
+                ));
+            }
+            if rule.allow.is_empty() == rule.deny.is_empty() {
+                return Err(format!(
+                    "geoip rule for `{}` must set exactly one of `allow` and `deny`",
+                    rule.prefix
+                ));
+            }
+            rules.push(Rule {
+                prefix: rule.prefix.clone(),
+                allow: codes(&rule.allow),
+                deny: codes(&rule.deny),
+                allow_unknown: rule.allow_unknown.unwrap_or(rule.allow.is_empty()),
+            });
+        }
+        Ok(Geoip { reader, rules })
+    }
+
+    pub fn rules(&self) -> usize {
+        self.rules.len()
+    }
+
+    fn country(&self, ip: IpAddr) -> Option<String> {
+        match self.reader.lookup::<geoip2::Country>(ip) {
+            Ok(record) => record
+                .country
+                .or(record.registered_country)
+                .and_then(|country| country.iso_code)
+                .map(str::to_string),
+            Err(MaxMindDBError::AddressNotFoundError(_)) => None,
+            Err(e) => {
+                debug!("GeoIP lookup of {} failed: {}", ip, e);
+                None
+            }
+        }
+    }
+}
+
+// 在 resolve_client 之后执行，使用经可信代理还原的客户端 IP
+pub async fn check(State(geoip): State<Arc<Geoip>>, req: Request, next: Next) -> Response {
+    let country = req
+        .extensions()
+        .get::<ClientInfo>()
+        .and_then(|info| info.ip)
+        .and_then(|ip| geoip.country(ip));
+    let path = site::normalize_path(req.uri().path());
+    let rule = geoip
+        .rules
+        .iter()
+        .find(|rule| path.starts_with(rule.prefix.as_str()));
+    let mut response = match rule {
+        Some(rule) if !rule.permits(country.as_deref()) => {
+            debug!(
+                "Blocked {} from {}",
+                path,
+                country.as_deref().unwrap_or("unknown country")
+            );
+            (StatusCode::FORBIDDEN, "not available in your region").into_response()
+        }
+        _ => next.run(req).await,
+    };
+    if let Some(country) = country {
+        response.extensions_mut().insert(Country(country));
+    }
+    response
+}
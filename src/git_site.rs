@@ -0,0 +1,329 @@
+// git 后端（backend = "git"）：站点内容来自 bare 仓库中 [git] ref 指向的提交，文件树读入内存后提供，
+// 不需要工作目录；部署端点把 ref 推进到新的提交并原子替换整个站点，回滚只需再部署之前的提交。
+// 通过 gix 直接读取仓库对象，不依赖 git 可执行文件，ETag 直接使用 blob id
+use crate::login;
+use crate::memfs::{MemFile, MemoryFs};
+use crate::site::{self, SiteOptions};
+use crate::upload;
+use axum::body::Bytes;
+use axum::extract::{Query, Request, State};
+use axum::http::header::{HeaderMap, HeaderValue};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tower::ServiceExt;
+use tracing::{info, warn};
+
+pub const ROOT: &str = "<git>";
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct GitConfig {
+    // bare 仓库路径
+    pub repo: String,
+    // 提供的分支、标签或提交，部署端点推进的也是它
+    #[serde(rename = "ref", default = "default_ref")]
+    pub reference: String,
+    // 仓库中作为站点根目录的子目录，如 "dist"
+    #[serde(default)]
+    pub subdir: String,
+    #[serde(default = "default_deploy_path")]
+    pub deploy_path: String,
+    // 部署端点的 Bearer token，未设置时不启用部署端点
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub token: Option<String>,
+}
+
+fn default_ref() -> String {
+    "HEAD".to_string()
+}
+
+fn default_deploy_path() -> String {
+    "/__deploy".to_string()
+}
+
+// 当前提供的提交
+struct Deployed {
+    commit: String,
+    previous: Option<String>,
+    files: usize,
+    at: SystemTime,
+    router: Router,
+}
+
+pub struct GitSite {
+    config: GitConfig,
+    options: SiteOptions,
+    current: RwLock<Deployed>,
+    // 部署依次执行，推进 ref 时以当前提交为旧值
+    deploying: tokio::sync::Mutex<()>,
+}
+
+impl GitSite {
+    pub fn new(config: &GitConfig, options: SiteOptions) -> Result<Self, String> {
+        if config.reference.is_empty() || config.reference.starts_with('-') {
+            return Err(format!("invalid [git] ref `{}`", config.reference));
+        }
+        if config.token.as_deref() == Some("") {
+            return Err("[git] token must not be empty".to_string());
+        }
+        let commit = resolve(config, &config.reference)?;
+        let files = load(config, &commit)?;
+        info!(
+            "Serving {} {} ({} files, {} bytes) from {}",
+            config.reference,
+            short(&commit),
+            files.len(),
+            files.total_bytes(),
+            config.repo
+        );
+        let current = Deployed {
+            commit,
+            previous: None,
+            files: files.len(),
+            at: SystemTime::now(),
+            router: site::memory_router(Arc::new(files), options.clone())?,
+        };
+        Ok(GitSite {
+            config: config.clone(),
+            options,
+            current: RwLock::new(current),
+            deploying: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn deploy_path(&self) -> Option<&str> {
+        self.config
+            .token
+            .as_ref()
+            .map(|_| self.config.deploy_path.as_str())
+    }
+
+    // 每个请求交给当前提交的站点，部署时整体替换
+    pub fn router(self: &Arc<Self>) -> Router {
+        let site = self.clone();
+        Router::new().fallback_service(tower::service_fn(move |req: Request| {
+            let router = site.current.read().unwrap().router.clone();
+            router.oneshot(req)
+        }))
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        upload::bearer_authorized(headers, self.config.token.as_deref())
+    }
+
+    fn status(&self) -> serde_json::Value {
+        let current = self.current.read().unwrap();
+        json!({
+            "ref": self.config.reference,
+            "commit": current.commit,
+            "previous": current.previous,
+            "files": current.files,
+            "deployed_at": crate::error_pages::rfc3339(current.at),
+        })
+    }
+
+    // to 为空时重新读取 ref（如 git push 之后），否则先读入 to 指向的提交再把 ref 推进过去
+    fn deploy(&self, to: Option<&str>) -> Result<bool, (StatusCode, String)> {
+        let bad_request = |e| (StatusCode::BAD_REQUEST, e);
+        let internal = |e| (StatusCode::INTERNAL_SERVER_ERROR, e);
+        let old = self.current.read().unwrap().commit.clone();
+        let commit = match to {
+            Some(to) => resolve(&self.config, to).map_err(bad_request)?,
+            None => resolve(&self.config, &self.config.reference).map_err(internal)?,
+        };
+        if commit == old {
+            return Ok(false);
+        }
+        let files = load(&self.config, &commit).map_err(internal)?;
+        let count = files.len();
+        let router =
+            site::memory_router(Arc::new(files), self.options.clone()).map_err(internal)?;
+        if to.is_some() {
+            update_ref(&self.config, &commit, &old).map_err(|e| (StatusCode::CONFLICT, e))?;
+        }
+        *self.current.write().unwrap() = Deployed {
+            commit: commit.clone(),
+            previous: Some(old.clone()),
+            files: count,
+            at: SystemTime::now(),
+            router,
+        };
+        info!(
+            "Deployed {} {} -> {} ({} files)",
+            self.config.reference,
+            short(&old),
+            short(&commit),
+            count
+        );
+        Ok(true)
+    }
+}
+
+// GET 返回当前提交；POST 部署，?to= 指定新的提交（分支、标签、提交 id 或 ref@{1} 等）
+pub async fn status(State(site): State<Arc<GitSite>>, headers: HeaderMap) -> Response {
+    if !site.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    login::no_store(Json(site.status()).into_response())
+}
+
+pub async fn deploy(
+    State(site): State<Arc<GitSite>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if !site.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    let _guard = site.deploying.lock().await;
+    let worker = site.clone();
+    let to = query.get("to").filter(|to| !to.is_empty()).cloned();
+    let result = tokio::task::spawn_blocking(move || worker.deploy(to.as_deref())).await;
+    let response = match result {
+        Ok(Ok(changed)) => {
+            let mut status = site.status();
+            status["changed"] = changed.into();
+            Json(status).into_response()
+        }
+        Ok(Err((status, e))) => {
+            warn!("Deploy of {} failed: {}", site.config.reference, e);
+            (status, Json(json!({ "error": e }))).into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    };
+    login::no_store(response)
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(12)]
+}
+
+fn open(config: &GitConfig) -> Result<gix::Repository, String> {
+    open_with(config, gix::open::Options::default())
+}
+
+fn open_with(config: &GitConfig, options: gix::open::Options) -> Result<gix::Repository, String> {
+    gix::open_opts(&config.repo, options).map_err(|e| format!("cannot open {}: {}", config.repo, e))
+}
+
+fn object_id(hex: &str) -> Result<gix::ObjectId, String> {
+    gix::ObjectId::from_hex(hex.as_bytes())
+        .map_err(|e| format!("invalid object id `{}`: {}", hex, e))
+}
+
+// 解析为提交 id，支持分支、标签、提交 id 与 ref@{1} 等写法；标签剥离到其指向的提交
+fn resolve(config: &GitConfig, rev: &str) -> Result<String, String> {
+    let repo = open(config)?;
+    let not_found = || format!("`{}` is not a commit in {}", rev, config.repo);
+    let id = repo.rev_parse_single(rev).map_err(|_| not_found())?;
+    let commit = id
+        .object()
+        .map_err(|e| e.to_string())?
+        .peel_to_commit()
+        .map_err(|_| not_found())?;
+    Ok(commit.id.to_string())
+}
+
+// 以 old 为旧值推进 ref（HEAD 等符号引用推进其指向的分支），期间 ref 被其他人改动则失败；
+// 提交 id 形式的 ref 不能推进
+fn update_ref(config: &GitConfig, commit: &str, old: &str) -> Result<(), String> {
+    use gix::refs::transaction::{Change, LogChange, PreviousValue, RefEdit, RefLog};
+    use gix::refs::Target;
+
+    // bare 仓库默认不记录 reflog，部署始终记录，便于按 ref@{1} 回滚
+    let options = gix::open::Options::default().config_overrides(["core.logAllRefUpdates=always"]);
+    let mut repo = open_with(config, options)?;
+    let name = repo
+        .find_reference(config.reference.as_str())
+        .map_err(|_| {
+            format!(
+                "ref `{}` is not a branch and cannot be advanced",
+                config.reference
+            )
+        })?
+        .name()
+        .to_owned();
+    // reflog 需要提交者，仓库与全局配置都没有时记为 sonic-wave
+    repo.committer_or_set_fallback("sonic-wave", "sonic-wave@localhost")
+        .map_err(|e| e.to_string())?;
+    let edit = RefEdit {
+        change: Change::Update {
+            log: LogChange {
+                mode: RefLog::AndReference,
+                force_create_reflog: true,
+                message: "sonic-wave deploy".into(),
+            },
+            expected: PreviousValue::MustExistAndMatch(Target::Object(object_id(old)?)),
+            new: Target::Object(object_id(commit)?),
+        },
+        name,
+        deref: true,
+    };
+    repo.edit_reference(edit)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+// 读入提交中的文件树；符号链接与子模块跳过
+fn load(config: &GitConfig, commit: &str) -> Result<MemoryFs, String> {
+    let repo = open(config)?;
+    let commit = repo
+        .find_commit(object_id(commit)?)
+        .map_err(|e| e.to_string())?;
+    let modified = commit
+        .time()
+        .ok()
+        .and_then(|time| u64::try_from(time.seconds).ok())
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+
+    let mut tree = commit.tree().map_err(|e| e.to_string())?;
+    let subdir = config.subdir.trim_matches('/');
+    if !subdir.is_empty() {
+        let not_dir = || {
+            format!(
+                "`{}` is not a directory in {}",
+                subdir,
+                short(&commit.id.to_string())
+            )
+        };
+        tree = tree
+            .peel_to_entry_by_path(subdir)
+            .map_err(|e| e.to_string())?
+            .filter(|entry| entry.mode().is_tree())
+            .ok_or_else(not_dir)?
+            .object()
+            .map_err(|e| e.to_string())?
+            .into_tree();
+    }
+    let entries = tree
+        .traverse()
+        .breadthfirst
+        .files()
+        .map_err(|e| e.to_string())?;
+    let mut files = MemoryFs::new(ROOT);
+    for entry in entries.iter().filter(|entry| entry.mode.is_blob()) {
+        let path = String::from_utf8_lossy(&entry.filepath);
+        let blob = repo
+            .find_blob(entry.oid)
+            .map_err(|e| format!("failed to read {}: {}", path, e))?;
+        files.insert(
+            &path,
+            MemFile {
+                data: Bytes::from(blob.detach().data),
+                modified,
+                etag: HeaderValue::from_str(&format!("\"{}\"", entry.oid)).ok(),
+            },
+        );
+    }
+    Ok(files)
+}
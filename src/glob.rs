@@ -0,0 +1,58 @@
+// 路径 glob：* 匹配单段、** 匹配任意层级、? 匹配单个字符，编译为正则
+use regex::Regex;
+
+// "/blog/*/post-?.html" -> "^/blog/([^/]*)/post-([^/])\.html$"，每个通配符是一个捕获组
+pub fn to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str("(.*)");
+            }
+            '*' => regex.push_str("([^/]*)"),
+            '?' => regex.push_str("([^/])"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+// 用于过滤 URL 路径的模式：不含 "/" 的模式匹配任意一段（"*.pem"、".env"），
+// 含 "/" 的模式从站点根开始匹配，并包含其下所有路径（"/private/**"、"/.git/"）
+#[derive(Debug, Clone)]
+pub struct PathPattern {
+    regex: Regex,
+    any_segment: bool,
+}
+
+impl PathPattern {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let trimmed = pattern.trim().trim_end_matches('/');
+        if trimmed.is_empty() {
+            return Err(format!("invalid path pattern `{}`", pattern));
+        }
+        let any_segment = !trimmed.contains('/');
+        let source = if any_segment {
+            to_regex(trimmed)
+        } else {
+            let anchored = format!("/{}", trimmed.trim_start_matches('/'));
+            // 目录模式同时匹配目录下的所有内容
+            format!("{}(/.*)?$", to_regex(&anchored).trim_end_matches('$'))
+        };
+        let regex = Regex::new(&source)
+            .map_err(|e| format!("invalid path pattern `{}`: {}", pattern, e))?;
+        Ok(PathPattern { regex, any_segment })
+    }
+
+    // path 为已解码、以 "/" 开头的请求路径
+    pub fn matches(&self, path: &str) -> bool {
+        if self.any_segment {
+            path.split('/').any(|segment| self.regex.is_match(segment))
+        } else {
+            self.regex.is_match(path)
+        }
+    }
+}
@@ -0,0 +1,383 @@
+// HLS 分段（[hls]）：GET /rec.mp3?hls 返回 VOD 播放列表，/rec.mp3?hls=3 返回第 3 段；
+// MP3 与 ADTS AAC 按帧边界直接切分（不转码），每段以 ID3 时间戳开头（packed audio），切分结果缓存在磁盘上
+use crate::audio::{self, Frame};
+use crate::index::etag_matches;
+use crate::site::Resolver;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct HlsConfig {
+    // 分段的缓存目录
+    #[serde(default = "default_cache_dir")]
+    pub cache_dir: PathBuf,
+    // 目标分段时长（秒），实际时长按帧边界取整
+    #[serde(default = "default_segment_duration")]
+    pub segment_duration: f64,
+    // 小于该大小（字节）的文件不分段，返回 404
+    #[serde(default)]
+    pub min_size: u64,
+}
+
+fn default_cache_dir() -> PathBuf {
+    std::env::temp_dir().join("sonic-wave-hls")
+}
+
+fn default_segment_duration() -> f64 {
+    10.0
+}
+
+// 切分结果中记录各段时长的文件，最后写入，存在即表示切分完成
+const DURATIONS: &str = "durations";
+
+#[derive(Clone, Copy)]
+enum Codec {
+    Mp3,
+    Aac,
+}
+
+impl Codec {
+    fn from_path(path: &str) -> Option<Self> {
+        let ext = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        match ext.as_str() {
+            "mp3" => Some(Codec::Mp3),
+            "aac" => Some(Codec::Aac),
+            _ => None,
+        }
+    }
+
+    fn frame(self, header: &[u8]) -> Option<Frame> {
+        match self {
+            Codec::Mp3 => audio::mp3_frame(header),
+            Codec::Aac => audio::adts_frame(header),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Codec::Mp3 => "mp3",
+            Codec::Aac => "aac",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Codec::Mp3 => "audio/mpeg",
+            Codec::Aac => "audio/aac",
+        }
+    }
+}
+
+enum Query {
+    Playlist,
+    Segment(usize),
+}
+
+impl Query {
+    fn parse(query: Option<&str>) -> Option<Self> {
+        query?
+            .split('&')
+            .find_map(|pair| match pair.split_once('=') {
+                None if pair == "hls" => Some(Query::Playlist),
+                Some(("hls", "" | "playlist")) => Some(Query::Playlist),
+                Some(("hls", n)) => n.parse().ok().map(Query::Segment),
+                _ => None,
+            })
+    }
+}
+
+pub struct Hls {
+    config: HlsConfig,
+}
+
+impl Hls {
+    pub fn new(config: &HlsConfig) -> Result<Self, String> {
+        std::fs::create_dir_all(&config.cache_dir).map_err(|e| {
+            format!(
+                "failed to create HLS cache {}: {}",
+                config.cache_dir.display(),
+                e
+            )
+        })?;
+        if config.segment_duration.is_nan() || config.segment_duration < 1.0 {
+            return Err("hls segment_duration must be at least 1 second".to_string());
+        }
+        Ok(Hls {
+            config: config.clone(),
+        })
+    }
+}
+
+// packed audio 每段开头的 ID3 PRIV 帧，给出该段第一个采样的 90 kHz 时间戳
+fn timestamp_tag(pts: u64) -> Vec<u8> {
+    let owner = b"com.apple.streaming.transportStreamTimestamp\0";
+    let frame_len = owner.len() + 8;
+    let syncsafe = |n: usize| {
+        [
+            ((n >> 21) & 0x7f) as u8,
+            ((n >> 14) & 0x7f) as u8,
+            ((n >> 7) & 0x7f) as u8,
+            (n & 0x7f) as u8,
+        ]
+    };
+    let mut tag = Vec::with_capacity(20 + frame_len);
+    tag.extend_from_slice(b"ID3\x04\x00\x00");
+    tag.extend_from_slice(&syncsafe(10 + frame_len));
+    tag.extend_from_slice(b"PRIV");
+    tag.extend_from_slice(&syncsafe(frame_len));
+    tag.extend_from_slice(&[0, 0]);
+    tag.extend_from_slice(owner);
+    tag.extend_from_slice(&(pts & 0x1_ffff_ffff).to_be_bytes());
+    tag
+}
+
+// 顺序读取源文件，按帧边界切分到 dir 中的 0.mp3、1.mp3 ……，最后写入各段时长
+fn segment(source: &Path, dir: &Path, codec: Codec, target: f64) -> io::Result<()> {
+    let mut file = File::open(source)?;
+    // 跳过开头的 ID3v2 标签
+    let mut head = [0u8; 10];
+    let n = file.read(&mut head)?;
+    file.seek(SeekFrom::Start(audio::id3_len(&head[..n])))?;
+    let mut data = Vec::new();
+    let mut pos = 0;
+    let mut eof = false;
+    let mut first = true;
+    let mut durations: Vec<f64> = Vec::new();
+    let mut out: Option<BufWriter<File>> = None;
+    // 已写出的采样数与当前段的采样数
+    let mut total_samples = 0u64;
+    let mut segment_samples = 0u64;
+    let mut sample_rate = 0u32;
+    let mut chunk = vec![0u8; 1024 * 1024];
+    loop {
+        // 帧最长不超过 8 KiB，剩余不足 16 KiB 时补充数据，保证能看到完整的帧
+        if !eof && data.len() - pos < 16 * 1024 {
+            data.drain(..pos);
+            pos = 0;
+            let n = file.read(&mut chunk)?;
+            if n == 0 {
+                eof = true;
+            }
+            data.extend_from_slice(&chunk[..n]);
+            continue;
+        }
+        let Some(frame) = codec.frame(&data[pos..]) else {
+            if pos >= data.len() {
+                break;
+            }
+            // 帧头无效（ID3v1 标签、损坏的数据等），逐字节重新同步
+            pos += 1;
+            continue;
+        };
+        if frame.len == 0 || pos + frame.len > data.len() {
+            if eof {
+                break;
+            }
+            pos += 1;
+            continue;
+        }
+        let bytes = &data[pos..pos + frame.len];
+        if first {
+            first = false;
+            // Xing / Info 头帧不含音频，不写入分段
+            if matches!(codec, Codec::Mp3) && audio::is_vbr_header(bytes, &frame) {
+                pos += frame.len;
+                continue;
+            }
+        }
+        sample_rate = frame.sample_rate;
+        if segment_samples as f64 >= target * sample_rate as f64 {
+            if let Some(mut writer) = out.take() {
+                writer.flush()?;
+            }
+            durations.push(segment_samples as f64 / sample_rate as f64);
+            segment_samples = 0;
+        }
+        let writer = match &mut out {
+            Some(writer) => writer,
+            None => {
+                let path = dir.join(format!("{}.{}", durations.len(), codec.extension()));
+                let mut writer = BufWriter::new(File::create(path)?);
+                let pts = total_samples * 90_000 / sample_rate.max(1) as u64;
+                writer.write_all(&timestamp_tag(pts))?;
+                out.insert(writer)
+            }
+        };
+        writer.write_all(bytes)?;
+        total_samples += frame.samples as u64;
+        segment_samples += frame.samples as u64;
+        pos += frame.len;
+    }
+    if let Some(mut writer) = out.take() {
+        writer.flush()?;
+        durations.push(segment_samples as f64 / sample_rate.max(1) as f64);
+    }
+    if durations.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no audio frames found",
+        ));
+    }
+    let list: Vec<String> = durations.iter().map(|d| format!("{:.3}", d)).collect();
+    std::fs::write(dir.join(DURATIONS), list.join("\n"))
+}
+
+// 切分到临时目录后改名，并发请求不会看到不完整的结果
+fn prepare(source: &Path, dir: &Path, codec: Codec, target: f64) -> io::Result<()> {
+    if dir.join(DURATIONS).is_file() {
+        return Ok(());
+    }
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let temp = dir.with_extension(format!(
+        "tmp{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&temp)?;
+    let result = segment(source, &temp, codec, target).and_then(|()| {
+        match std::fs::rename(&temp, dir) {
+            // 其他请求已经完成切分
+            Err(_) if dir.join(DURATIONS).is_file() => Ok(()),
+            result => result,
+        }
+    });
+    let _ = std::fs::remove_dir_all(&temp);
+    result
+}
+
+fn playlist(name: &str, durations: &str) -> String {
+    let durations: Vec<f64> = durations
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .collect();
+    let target = durations.iter().fold(0.0f64, |max, d| max.max(*d)).ceil();
+    let mut body = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n",
+        target
+    );
+    for (i, duration) in durations.iter().enumerate() {
+        body.push_str(&format!("#EXTINF:{:.3},\n{}?hls={}\n", duration, name, i));
+    }
+    body.push_str("#EXT-X-ENDLIST\n");
+    body
+}
+
+// 在 resolve 之后执行；只处理磁盘上的 .mp3 / .aac 文件，缓存键包含文件的大小与修改时间
+pub async fn respond(
+    hls: &Hls,
+    resolver: &Resolver,
+    cache_control: &str,
+    req: Request,
+    next: Next,
+) -> Response {
+    let is_head = req.method() == Method::HEAD;
+    if !(is_head || req.method() == Method::GET) {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    let Some(codec) = Codec::from_path(&path) else {
+        return next.run(req).await;
+    };
+    let Some(query) = Query::parse(req.uri().query()) else {
+        return next.run(req).await;
+    };
+    let Some(fs_path) = resolver.include_path(&path).await else {
+        return next.run(req).await;
+    };
+    let Ok(meta) = tokio::fs::metadata(&fs_path).await else {
+        return next.run(req).await;
+    };
+    if meta.len() < hls.config.min_size {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(fs_path.to_string_lossy().as_bytes());
+    hasher.update(meta.len().to_le_bytes());
+    hasher.update(format!("{:?}", meta.modified().ok()).as_bytes());
+    hasher.update(hls.config.segment_duration.to_le_bytes());
+    let key: String = hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let dir = hls.config.cache_dir.join(&key);
+    let prepared = {
+        let (source, dir) = (fs_path.clone(), dir.clone());
+        let target = hls.config.segment_duration;
+        tokio::task::spawn_blocking(move || prepare(&source, &dir, codec, target)).await
+    };
+    match prepared {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            warn!("Failed to segment {}: {}", path, e);
+            return (StatusCode::UNPROCESSABLE_ENTITY, "unsupported audio file").into_response();
+        }
+        Err(e) => {
+            warn!("Failed to segment {}: {}", path, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    }
+
+    let (etag, content_type, body) = match query {
+        Query::Playlist => {
+            let Ok(durations) = tokio::fs::read_to_string(dir.join(DURATIONS)).await else {
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            };
+            let name = path.rsplit('/').next().unwrap_or_default();
+            (
+                format!("W/\"hls-{}\"", &key[..16]),
+                "application/vnd.apple.mpegurl",
+                playlist(name, &durations).into_bytes(),
+            )
+        }
+        Query::Segment(n) => {
+            let segment = dir.join(format!("{}.{}", n, codec.extension()));
+            let Ok(data) = tokio::fs::read(&segment).await else {
+                return StatusCode::NOT_FOUND.into_response();
+            };
+            (
+                format!("W/\"hls-{}-{}\"", &key[..16], n),
+                codec.content_type(),
+                data,
+            )
+        }
+    };
+
+    let etag = HeaderValue::from_str(&etag).unwrap();
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|tags| tags.to_str().ok())
+        .is_some_and(|tags| etag_matches(tags, &etag));
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let len = body.len();
+        let mut response = Response::new(if is_head {
+            Body::empty()
+        } else {
+            Body::from(body)
+        });
+        let headers = response.headers_mut();
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+        response
+    };
+    let headers = response.headers_mut();
+    headers.insert(header::ETAG, etag);
+    if let Ok(value) = HeaderValue::from_str(cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+    response
+}
@@ -0,0 +1,77 @@
+// WASM 插件（[[plugin]]）与 Lua 脚本（script）共用：传给钩子的请求头、钩子返回的头部修改与直接返回的响应
+use axum::body::Body;
+use axum::http::header::{HeaderMap, HeaderName, HeaderValue};
+use axum::http::StatusCode;
+use axum::response::Response;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// 头名称（小写）到值的映射，同名的多个值用 ", " 连接，非 ASCII 的值跳过
+pub fn header_map(headers: &HeaderMap) -> BTreeMap<&str, String> {
+    let mut map: BTreeMap<&str, String> = BTreeMap::new();
+    for (name, value) in headers {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        map.entry(name.as_str())
+            .and_modify(|joined| {
+                joined.push_str(", ");
+                joined.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    map
+}
+
+fn parse_header(name: &str, value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let parsed_name =
+        HeaderName::try_from(name).map_err(|_| format!("invalid header name `{}`", name))?;
+    let parsed_value =
+        HeaderValue::from_str(value).map_err(|_| format!("invalid value for header `{}`", name))?;
+    Ok((parsed_name, parsed_value))
+}
+
+// 先删除 remove 中的头，再用 set 中的值替换同名的头
+pub fn edit(
+    headers: &mut HeaderMap,
+    set: &BTreeMap<String, String>,
+    remove: &[String],
+) -> Result<(), String> {
+    for name in remove {
+        if let Ok(name) = HeaderName::try_from(name.as_str()) {
+            headers.remove(name);
+        }
+    }
+    for (name, value) in set {
+        let (name, value) = parse_header(name, value)?;
+        headers.insert(name, value);
+    }
+    Ok(())
+}
+
+// 钩子直接返回的响应：{ status, headers, body }
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Reply {
+    #[serde(default = "default_status")]
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: String,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+impl Reply {
+    pub fn build(self) -> Result<Response, String> {
+        let status = StatusCode::from_u16(self.status)
+            .map_err(|_| format!("invalid status {}", self.status))?;
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = status;
+        edit(response.headers_mut(), &self.headers, &[])?;
+        Ok(response)
+    }
+}
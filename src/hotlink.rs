@@ -0,0 +1,174 @@
+// 防盗链（[hotlink]）：受保护扩展名的请求带有非允许来源的 Referer 时返回 403 或占位文件，
+// 与请求 Host 相同的来源始终允许
+use crate::vary;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct HotlinkConfig {
+    // 受保护的扩展名（不区分大小写）
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    // 允许的来源主机，"*.example.com" 匹配所有子域名（不含 example.com 本身）
+    #[serde(default)]
+    pub allowed: Vec<String>,
+    // 没有 Referer 的请求（直接访问、隐私设置屏蔽）是否放行
+    #[serde(default = "default_allow_empty")]
+    pub allow_empty: bool,
+    // 拒绝时返回的占位文件（相对当前目录），未设置时返回 403
+    #[serde(default)]
+    pub placeholder: Option<PathBuf>,
+}
+
+fn default_extensions() -> Vec<String> {
+    [
+        "jpg", "jpeg", "png", "gif", "webp", "avif", "svg", "mp3", "m4a", "aac", "ogg", "opus",
+        "flac", "wav", "mp4", "webm", "mov",
+    ]
+    .iter()
+    .map(|ext| ext.to_string())
+    .collect()
+}
+
+fn default_allow_empty() -> bool {
+    true
+}
+
+pub struct Hotlink {
+    extensions: Vec<String>,
+    allowed: Vec<String>,
+    allow_empty: bool,
+    placeholder: Option<(HeaderValue, Vec<u8>)>,
+}
+
+impl Hotlink {
+    pub fn new(config: &HotlinkConfig) -> Result<Self, String> {
+        let placeholder = match &config.placeholder {
+            Some(path) => {
+                let data = std::fs::read(path).map_err(|e| {
+                    format!(
+                        "failed to read hotlink placeholder {}: {}",
+                        path.display(),
+                        e
+                    )
+                })?;
+                let mime = mime_guess::from_path(path).first_or_octet_stream();
+                let content_type = HeaderValue::from_str(mime.as_ref())
+                    .map_err(|e| format!("invalid hotlink placeholder type: {}", e))?;
+                Some((content_type, data))
+            }
+            None => None,
+        };
+        Ok(Hotlink {
+            extensions: config
+                .extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+            allowed: config
+                .allowed
+                .iter()
+                .map(|host| host.to_ascii_lowercase())
+                .collect(),
+            allow_empty: config.allow_empty,
+            placeholder,
+        })
+    }
+
+    fn protects(&self, path: &str) -> bool {
+        let file = path.rsplit('/').next().unwrap_or(path);
+        file.rsplit_once('.').is_some_and(|(_, ext)| {
+            self.extensions
+                .iter()
+                .any(|protected| protected.eq_ignore_ascii_case(ext))
+        })
+    }
+
+    fn allows(&self, referer: &str, own_host: Option<&str>) -> bool {
+        let Some(host) = referer_host(referer) else {
+            return false;
+        };
+        if own_host.is_some_and(|own| strip_port(own).eq_ignore_ascii_case(&host)) {
+            return true;
+        }
+        self.allowed
+            .iter()
+            .any(|allowed| match allowed.strip_prefix("*.") {
+                Some(domain) => host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.ends_with('.')),
+                None => *allowed == host,
+            })
+    }
+}
+
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return host.split_once(']').map_or(host, |(addr, _)| &addr[1..]);
+    }
+    host.rsplit_once(':').map_or(host, |(name, _)| name)
+}
+
+// Referer 中的主机名（小写、不含端口与用户信息）；不是 http(s) 地址时返回 None
+fn referer_host(referer: &str) -> Option<String> {
+    let (scheme, rest) = referer.split_once("://")?;
+    if !(scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")) {
+        return None;
+    }
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = strip_port(authority);
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+pub async fn check(State(hotlink): State<Arc<Hotlink>>, req: Request, next: Next) -> Response {
+    if !hotlink.protects(req.uri().path()) {
+        return next.run(req).await;
+    }
+    let referer = req
+        .headers()
+        .get(header::REFERER)
+        .map(|value| value.to_str().unwrap_or_default());
+    let own_host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| req.uri().host());
+    let allowed = match referer {
+        None | Some("") => hotlink.allow_empty,
+        Some(referer) => hotlink.allows(referer, own_host),
+    };
+    let mut response = if allowed {
+        next.run(req).await
+    } else {
+        debug!(
+            "Blocked hotlink to {} from {}",
+            req.uri().path(),
+            referer.unwrap_or("<none>")
+        );
+        match &hotlink.placeholder {
+            Some((content_type, data)) => {
+                let mut response = Response::new(Body::from(data.clone()));
+                let headers = response.headers_mut();
+                headers.insert(header::CONTENT_TYPE, content_type.clone());
+                headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+                response
+            }
+            None => (StatusCode::FORBIDDEN, "hotlinking is not allowed").into_response(),
+        }
+    };
+    // 同一地址的响应随 Referer 不同，共享缓存不能混用
+    vary::add(response.headers_mut(), "Referer");
+    response
+}
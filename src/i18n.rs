@@ -0,0 +1,248 @@
+// 多语言内容协商（[i18n]）：按 Accept-Language（可由 cookie / 查询参数覆盖）选择
+// 同级语言目录（/en/、/zh/）或带语言后缀的文件（index.zh.html）
+use crate::cookies;
+use crate::site::Resolver;
+use crate::vary;
+use axum::extract::{OriginalUri, Request};
+use axum::http::header::{self, HeaderMap};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct I18nConfig {
+    // 站点提供的语言，如 ["en", "zh"]
+    pub locales: Vec<String>,
+    // 没有匹配时使用的语言，默认为第一个
+    #[serde(default)]
+    pub default: Option<String>,
+    // 覆盖 Accept-Language 的 cookie 与查询参数名，空字符串表示不使用
+    #[serde(default = "default_param")]
+    pub cookie: String,
+    #[serde(default = "default_param")]
+    pub query: String,
+    // 不在语言目录下的请求重定向到首选语言的同级目录（/guide/ → /zh/guide/）
+    #[serde(default = "default_true")]
+    pub trees: bool,
+    // 优先返回带语言后缀的文件（index.html → index.zh.html）
+    #[serde(default = "default_true")]
+    pub suffixes: bool,
+}
+
+fn default_param() -> String {
+    "lang".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+pub struct I18n {
+    locales: Vec<String>,
+    default: String,
+    cookie: String,
+    query: String,
+    trees: bool,
+    suffixes: bool,
+}
+
+impl I18n {
+    pub fn new(config: &I18nConfig) -> Result<Self, String> {
+        let locales: Vec<String> = config
+            .locales
+            .iter()
+            .map(|locale| locale.trim().to_string())
+            .collect();
+        if let Some(locale) = locales.iter().find(|locale| {
+            locale.is_empty()
+                || !locale
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        }) {
+            return Err(format!("invalid i18n locale `{}`", locale));
+        }
+        let default = match &config.default {
+            Some(default) => locales
+                .iter()
+                .find(|locale| locale.eq_ignore_ascii_case(default))
+                .cloned()
+                .ok_or_else(|| format!("i18n default `{}` is not in locales", default))?,
+            None => locales
+                .first()
+                .cloned()
+                .ok_or("i18n locales must not be empty")?,
+        };
+        Ok(I18n {
+            locales,
+            default,
+            cookie: config.cookie.clone(),
+            query: config.query.clone(),
+            trees: config.trees,
+            suffixes: config.suffixes,
+        })
+    }
+
+    fn find(&self, tag: &str) -> Option<&str> {
+        let tag = tag.trim();
+        if let Some(locale) = self.locales.iter().find(|l| l.eq_ignore_ascii_case(tag)) {
+            return Some(locale);
+        }
+        // zh-CN 匹配 zh，zh 匹配 zh-Hans
+        let primary = |s: &str| s.split(['-', '_']).next().unwrap_or(s).to_ascii_lowercase();
+        self.locales
+            .iter()
+            .find(|l| primary(l) == primary(tag))
+            .map(String::as_str)
+    }
+
+    // 按偏好排序的语言列表，默认语言总在末尾
+    fn preferred(&self, headers: &HeaderMap, query: Option<&str>) -> Vec<&str> {
+        let mut ranked: Vec<&str> = Vec::new();
+        if !self.query.is_empty() {
+            let value = query.unwrap_or_default().split('&').find_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                (name == self.query).then_some(value)
+            });
+            ranked
+                .extend(value.and_then(|v| self.find(&percent_decode_str(v).decode_utf8_lossy())));
+        }
+        if !self.cookie.is_empty() {
+            ranked.extend(cookies::get(headers, &self.cookie).and_then(|v| self.find(v)));
+        }
+        let mut accepted: Vec<(&str, f32)> = headers
+            .get_all(header::ACCEPT_LANGUAGE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|item| {
+                let mut parts = item.split(';');
+                let tag = parts.next()?.trim();
+                let q = parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+                (q > 0.0 && !tag.is_empty() && tag != "*").then_some((tag, q))
+            })
+            .collect();
+        // 稳定排序，q 相同时保持请求中的顺序
+        accepted.sort_by(|a, b| b.1.total_cmp(&a.1));
+        for (tag, _) in accepted {
+            ranked.extend(self.find(tag));
+        }
+        ranked.push(&self.default);
+        let mut seen = Vec::new();
+        ranked.retain(|locale| {
+            let new = !seen.contains(locale);
+            seen.push(*locale);
+            new
+        });
+        ranked
+    }
+
+    fn vary(&self) -> &'static str {
+        if self.cookie.is_empty() {
+            "Accept-Language"
+        } else {
+            "Accept-Language, Cookie"
+        }
+    }
+
+    fn locale_prefix(&self, path: &str) -> Option<&str> {
+        let first = path.trim_start_matches('/').split('/').next()?;
+        self.locales
+            .iter()
+            .find(|locale| locale.eq_ignore_ascii_case(first))
+            .map(String::as_str)
+    }
+}
+
+fn negotiable(req: &Request) -> bool {
+    req.method() == Method::GET || req.method() == Method::HEAD
+}
+
+// 在 resolve 之前执行：不在语言目录下、且首选语言目录中存在对应路径时重定向
+pub async fn redirect_tree(
+    i18n: &I18n,
+    resolver: &Resolver,
+    original: &OriginalUri,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path().to_string();
+    if !i18n.trees || !negotiable(&req) || i18n.locale_prefix(&path).is_some() {
+        return next.run(req).await;
+    }
+    let preferred = i18n.preferred(req.headers(), req.uri().query());
+    for locale in preferred {
+        let target = format!("/{}{}", locale, path);
+        if resolver.exists(&target).await {
+            // 挂载点下的原始路径以站点内路径结尾
+            let base = original
+                .path()
+                .strip_suffix(path.as_str())
+                .unwrap_or_default();
+            let location = match original.query() {
+                Some(query) => format!("{}{}?{}", base, target, query),
+                None => format!("{}{}", base, target),
+            };
+            let mut response = (StatusCode::FOUND, [(header::LOCATION, location)]).into_response();
+            vary::add(response.headers_mut(), i18n.vary());
+            return response;
+        }
+    }
+    next.run(req).await
+}
+
+// 在 resolve 之后执行：index.html 改写为首选语言的 index.zh.html；默认语言也可以使用不带后缀的文件
+pub async fn negotiate_file(
+    i18n: &I18n,
+    resolver: &Resolver,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    if !i18n.suffixes || !negotiable(&req) {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    let (dir, name) = path.split_at(path.rfind('/').map_or(0, |i| i + 1));
+    let Some((stem, ext)) = name.rsplit_once('.').filter(|(stem, _)| !stem.is_empty()) else {
+        return next.run(req).await;
+    };
+    // 已经带语言后缀的文件按原样返回
+    if stem.rsplit_once('.').is_some_and(|(_, suffix)| {
+        i18n.find(suffix)
+            .is_some_and(|l| l.eq_ignore_ascii_case(suffix))
+    }) {
+        return next.run(req).await;
+    }
+    let mut variants = Vec::new();
+    for locale in &i18n.locales {
+        let variant = format!("{}{}.{}.{}", dir, stem, locale, ext);
+        if resolver.include_path(&variant).await.is_some() {
+            variants.push((locale.as_str(), variant));
+        }
+    }
+    if variants.is_empty() {
+        return next.run(req).await;
+    }
+    for locale in i18n.preferred(req.headers(), req.uri().query()) {
+        if let Some((_, variant)) = variants.iter().find(|(l, _)| *l == locale) {
+            let uri = match req.uri().query() {
+                Some(query) => format!("{}?{}", variant, query),
+                None => variant.clone(),
+            };
+            if let Ok(uri) = uri.parse() {
+                *req.uri_mut() = uri;
+            }
+            break;
+        }
+        if locale == i18n.default {
+            break;
+        }
+    }
+    let mut response = next.run(req).await;
+    vary::add(response.headers_mut(), i18n.vary());
+    response
+}
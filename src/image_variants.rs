@@ -0,0 +1,80 @@
+// 图片格式协商（image_variants）：存在 photo.jpg.avif / photo.jpg.webp 旁路文件时，
+// 按 Accept 返回浏览器支持的更小格式，页面中的地址无需改动
+use crate::images;
+use crate::site::Resolver;
+use crate::vary;
+use axum::extract::Request;
+use axum::http::header::{self, HeaderMap};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::Response;
+
+// 按同等 q 值时的优先顺序排列
+const VARIANTS: [(&str, &str); 2] = [("avif", "image/avif"), ("webp", "image/webp")];
+
+fn is_image(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    [".jpg", ".jpeg", ".png", ".gif"]
+        .iter()
+        .any(|ext| path.ends_with(ext))
+}
+
+// Accept 中明确列出的类型的 q 值；image/* 与 */* 不表示支持新格式
+fn accept_q(headers: &HeaderMap, mime: &str) -> f32 {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            if !parts.next()?.trim().eq_ignore_ascii_case(mime) {
+                return None;
+            }
+            Some(
+                parts
+                    .find_map(|p| p.trim().strip_prefix("q="))
+                    .map_or(1.0, |q| q.trim().parse().unwrap_or(0.0)),
+            )
+        })
+        .fold(0.0, f32::max)
+}
+
+// 在 resolve 之后执行；带缩放参数的请求交给 [images] 处理原图
+pub async fn respond(resolver: &Resolver, mut req: Request, next: Next) -> Response {
+    if !(req.method() == Method::GET || req.method() == Method::HEAD)
+        || !is_image(req.uri().path())
+        || images::requested(req.uri().query())
+    {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    let mut best: Option<(f32, String)> = None;
+    let mut has_variants = false;
+    for (ext, mime) in VARIANTS {
+        let variant = format!("{}.{}", path, ext);
+        if resolver.include_path(&variant).await.is_none() {
+            continue;
+        }
+        has_variants = true;
+        let q = accept_q(req.headers(), mime);
+        if q > 0.0 && best.as_ref().is_none_or(|(best, _)| q > *best) {
+            best = Some((q, variant));
+        }
+    }
+    if let Some((_, variant)) = best {
+        let uri = match req.uri().query() {
+            Some(query) => format!("{}?{}", variant, query),
+            None => variant,
+        };
+        if let Ok(uri) = uri.parse() {
+            *req.uri_mut() = uri;
+        }
+    }
+    let mut response = next.run(req).await;
+    // 同一 URL 按 Accept 返回不同内容，缓存需要区分
+    if has_variants {
+        vary::add(response.headers_mut(), "Accept");
+    }
+    response
+}
@@ -0,0 +1,332 @@
+// 图片缩放（[images]）：/photo.jpg?w=320&h=240&q=75 按需缩放并重新编码，结果缓存在磁盘上
+use crate::index::etag_matches;
+use axum::body::Body;
+use axum::extract::{OriginalUri, Request};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use hmac::{Hmac, Mac};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::path::PathBuf;
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ImagesConfig {
+    // 缩放结果的缓存目录
+    #[serde(default = "default_cache_dir")]
+    pub cache_dir: PathBuf,
+    // 请求的宽高上限，超出返回 400
+    #[serde(default = "default_max_dimension")]
+    pub max_width: u32,
+    #[serde(default = "default_max_dimension")]
+    pub max_height: u32,
+    // 未指定 q 时的 JPEG 质量
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+    // 超过该大小的原图不处理
+    #[serde(default = "default_max_source_size")]
+    pub max_source_size: usize,
+    // 设置后要求 &s=<签名>，防止任意尺寸请求耗尽 CPU 与磁盘
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub secret: Option<String>,
+}
+
+fn default_cache_dir() -> PathBuf {
+    std::env::temp_dir().join("sonic-wave-images")
+}
+
+fn default_max_dimension() -> u32 {
+    4096
+}
+
+fn default_quality() -> u8 {
+    80
+}
+
+fn default_max_source_size() -> usize {
+    32 * 1024 * 1024
+}
+
+pub struct Images {
+    config: ImagesConfig,
+}
+
+// 查询参数中的缩放选项
+#[derive(Default, PartialEq, Eq)]
+struct Variant {
+    width: Option<u32>,
+    height: Option<u32>,
+    quality: Option<u8>,
+    signature: Option<String>,
+}
+
+impl Variant {
+    fn parse(query: Option<&str>) -> Result<Option<Self>, &'static str> {
+        let mut variant = Variant::default();
+        for pair in query.unwrap_or_default().split('&') {
+            let Some((name, value)) = pair.split_once('=') else {
+                continue;
+            };
+            match name {
+                "w" => variant.width = Some(value.parse().map_err(|_| "invalid w")?),
+                "h" => variant.height = Some(value.parse().map_err(|_| "invalid h")?),
+                "q" => variant.quality = Some(value.parse().map_err(|_| "invalid q")?),
+                "s" => variant.signature = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        if variant.width.is_none() && variant.height.is_none() && variant.quality.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(variant))
+    }
+
+    // 签名内容：路径加上按 w、h、q 顺序排列的参数，如 "/photos/a.jpg?w=320&q=75"
+    fn canonical(&self, path: &str) -> String {
+        let params: Vec<String> = [
+            self.width.map(|w| format!("w={}", w)),
+            self.height.map(|h| format!("h={}", h)),
+            self.quality.map(|q| format!("q={}", q)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        format!("{}?{}", path, params.join("&"))
+    }
+}
+
+// 请求中带有缩放参数
+pub fn requested(query: Option<&str>) -> bool {
+    !matches!(Variant::parse(query), Ok(None))
+}
+
+fn output_format(path: &str) -> Option<ImageFormat> {
+    let ext = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+        "png" => Some(ImageFormat::Png),
+        "webp" => Some(ImageFormat::WebP),
+        // 动图只保留第一帧，以 PNG 返回
+        "gif" => Some(ImageFormat::Png),
+        _ => None,
+    }
+}
+
+impl Images {
+    pub fn new(config: &ImagesConfig) -> Result<Self, String> {
+        std::fs::create_dir_all(&config.cache_dir).map_err(|e| {
+            format!(
+                "failed to create image cache {}: {}",
+                config.cache_dir.display(),
+                e
+            )
+        })?;
+        if !(1..=100).contains(&config.quality) {
+            return Err("images quality must be between 1 and 100".to_string());
+        }
+        Ok(Images {
+            config: config.clone(),
+        })
+    }
+
+    fn check(&self, variant: &Variant, path: &str) -> Result<(), (StatusCode, &'static str)> {
+        if variant
+            .width
+            .is_some_and(|w| w == 0 || w > self.config.max_width)
+            || variant
+                .height
+                .is_some_and(|h| h == 0 || h > self.config.max_height)
+        {
+            return Err((StatusCode::BAD_REQUEST, "requested size is out of range"));
+        }
+        if variant.quality.is_some_and(|q| !(1..=100).contains(&q)) {
+            return Err((StatusCode::BAD_REQUEST, "q must be between 1 and 100"));
+        }
+        let Some(secret) = &self.config.secret else {
+            return Ok(());
+        };
+        let signature = variant
+            .signature
+            .as_deref()
+            .and_then(decode_hex)
+            .ok_or((StatusCode::FORBIDDEN, "missing or malformed signature"))?;
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(variant.canonical(path).as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| (StatusCode::FORBIDDEN, "invalid signature"))
+    }
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode(
+    source: &[u8],
+    variant: &Variant,
+    format: ImageFormat,
+    default_quality: u8,
+) -> Result<Vec<u8>, image::ImageError> {
+    let image = image::load_from_memory(source)?;
+    // 只缩小不放大；只给出一边时按比例计算另一边
+    let width = variant.width.unwrap_or(u32::MAX).min(image.width());
+    let height = variant.height.unwrap_or(u32::MAX).min(image.height());
+    let image = if width < image.width() || height < image.height() {
+        image.resize(width, height, FilterType::Lanczos3)
+    } else {
+        image
+    };
+    let mut output = Cursor::new(Vec::new());
+    match format {
+        ImageFormat::Jpeg => {
+            let quality = variant.quality.unwrap_or(default_quality);
+            let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+            rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut output, quality))?;
+        }
+        format => image.write_to(&mut output, format)?,
+    }
+    Ok(output.into_inner())
+}
+
+// 在 resolve 之后执行；原图从内层服务读取，缓存键包含原图的 ETag / Last-Modified，原图变化后生成新的缓存
+pub async fn respond(images: &Images, mut req: Request, next: Next) -> Response {
+    let is_head = req.method() == Method::HEAD;
+    if !(is_head || req.method() == Method::GET) {
+        return next.run(req).await;
+    }
+    let Some(format) = output_format(req.uri().path()) else {
+        return next.run(req).await;
+    };
+    let variant = match Variant::parse(req.uri().query()) {
+        Ok(Some(variant)) => variant,
+        Ok(None) => return next.run(req).await,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    let path = req.uri().path().to_string();
+    // 签名使用完整的请求路径，挂载点下同样包含前缀
+    let signed_path = req
+        .extensions()
+        .get::<OriginalUri>()
+        .map_or(path.as_str(), |original| original.path());
+    if let Err((status, message)) = images.check(&variant, signed_path) {
+        return (status, message).into_response();
+    }
+    let if_none_match = req.headers_mut().remove(header::IF_NONE_MATCH);
+    for name in [
+        header::RANGE,
+        header::IF_RANGE,
+        header::IF_MODIFIED_SINCE,
+        header::ACCEPT_ENCODING,
+    ] {
+        req.headers_mut().remove(name);
+    }
+    if is_head {
+        *req.method_mut() = Method::GET;
+    }
+    let response = next.run(req).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let validator = [header::ETAG, header::LAST_MODIFIED, header::CONTENT_LENGTH]
+        .iter()
+        .filter_map(|name| parts.headers.get(name)?.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(";");
+    let mut hasher = Sha256::new();
+    hasher.update(variant.canonical(&path).as_bytes());
+    hasher.update(validator.as_bytes());
+    hasher.update([images.config.quality]);
+    let key: String = hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let etag = HeaderValue::from_str(&format!("W/\"img-{}\"", &key[..16])).unwrap();
+    let ext = format.extensions_str()[0];
+    let cached = images.config.cache_dir.join(format!("{}.{}", key, ext));
+
+    let output = match tokio::fs::read(&cached).await {
+        Ok(output) => output,
+        Err(_) => {
+            let source = match axum::body::to_bytes(body, images.config.max_source_size).await {
+                Ok(source) => source,
+                Err(e) => {
+                    warn!("Not resizing {}: {}", path, e);
+                    return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+                }
+            };
+            let default_quality = images.config.quality;
+            let encoded = tokio::task::spawn_blocking(move || {
+                encode(&source, &variant, format, default_quality)
+            })
+            .await;
+            let output = match encoded {
+                Ok(Ok(output)) => output,
+                Ok(Err(e)) => {
+                    warn!("Failed to resize {}: {}", path, e);
+                    return (StatusCode::UNPROCESSABLE_ENTITY, "unsupported image").into_response();
+                }
+                Err(e) => {
+                    warn!("Failed to resize {}: {}", path, e);
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            };
+            // 先写临时文件再改名，并发请求不会读到不完整的缓存
+            let temp = cached.with_extension(format!("{}.tmp{}", ext, std::process::id()));
+            let written = tokio::fs::write(&temp, &output).await;
+            if let Err(e) = match written {
+                Ok(()) => tokio::fs::rename(&temp, &cached).await,
+                Err(e) => Err(e),
+            } {
+                warn!("Failed to cache resized {}: {}", path, e);
+                let _ = tokio::fs::remove_file(&temp).await;
+            }
+            output
+        }
+    };
+
+    for name in [
+        header::CONTENT_LENGTH,
+        header::ACCEPT_RANGES,
+        header::CONTENT_ENCODING,
+    ] {
+        parts.headers.remove(name);
+    }
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.to_mime_type()),
+    );
+    let not_modified = if_none_match
+        .as_ref()
+        .and_then(|tags| tags.to_str().ok())
+        .is_some_and(|tags| etag_matches(tags, &etag));
+    parts.headers.insert(header::ETAG, etag);
+    if not_modified {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_TYPE);
+        return Response::from_parts(parts, Body::empty());
+    }
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(output.len()));
+    let body = if is_head {
+        Body::empty()
+    } else {
+        Body::from(output)
+    };
+    Response::from_parts(parts, body)
+}
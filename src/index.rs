@@ -0,0 +1,257 @@
+// 启动时预建的目录索引：记录站点目录下每个路径的类型、mtime 与 ETag（由大小与 mtime 生成），
+// 解析路径与判断 404 时不再逐个请求访问文件系统
+use crate::site::FollowSymlinks;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::{header, HeaderValue, Response, StatusCode};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tower::Service;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone)]
+pub struct IndexEntry {
+    pub is_dir: bool,
+    pub modified: Option<SystemTime>,
+    pub etag: HeaderValue,
+    // 是否符合 follow_symlinks 策略
+    pub allowed: bool,
+}
+
+pub struct FileIndex {
+    root: PathBuf,
+    follow_symlinks: FollowSymlinks,
+    // 键为 site::fs_path 生成的路径（站点目录 + 各段）
+    entries: RwLock<HashMap<PathBuf, IndexEntry>>,
+}
+
+impl FileIndex {
+    pub fn build(root: &Path, follow_symlinks: FollowSymlinks) -> io::Result<Self> {
+        let index = FileIndex {
+            root: root.to_path_buf(),
+            follow_symlinks,
+            entries: RwLock::new(HashMap::new()),
+        };
+        index.refresh()?;
+        Ok(index)
+    }
+
+    // 由文件列表建立索引，用于内存中的站点；上级目录自动补全
+    pub fn from_files(root: &Path, files: impl IntoIterator<Item = (PathBuf, IndexEntry)>) -> Self {
+        let mut entries = HashMap::new();
+        entries.insert(root.to_path_buf(), entry(0, None, true, true));
+        for (path, file) in files {
+            let mut parent = path.parent();
+            while let Some(dir) = parent.filter(|dir| dir.starts_with(root)) {
+                entries
+                    .entry(dir.to_path_buf())
+                    .or_insert_with(|| entry(0, None, true, true));
+                parent = dir.parent();
+            }
+            entries.insert(path, file);
+        }
+        FileIndex {
+            root: root.to_path_buf(),
+            follow_symlinks: FollowSymlinks::Always,
+            entries: RwLock::new(entries),
+        }
+    }
+
+    // 重新遍历站点目录
+    pub fn refresh(&self) -> io::Result<()> {
+        let started = Instant::now();
+        let canonical_root = std::fs::canonicalize(&self.root)?;
+        let mut entries = HashMap::new();
+        let mut visited = HashSet::new();
+        visited.insert(canonical_root.clone());
+        let root_meta = std::fs::metadata(&self.root)?;
+        entries.insert(self.root.clone(), meta_entry(&root_meta, true));
+        self.walk(
+            &self.root,
+            false,
+            &canonical_root,
+            &mut visited,
+            &mut entries,
+        )?;
+        info!(
+            "Indexed {} entries under {} in {:?}",
+            entries.len(),
+            self.root.display(),
+            started.elapsed()
+        );
+        *self.entries.write().unwrap() = entries;
+        Ok(())
+    }
+
+    fn walk(
+        &self,
+        dir: &Path,
+        via_symlink: bool,
+        canonical_root: &Path,
+        visited: &mut HashSet<PathBuf>,
+        entries: &mut HashMap<PathBuf, IndexEntry>,
+    ) -> io::Result<()> {
+        let items = match std::fs::read_dir(dir) {
+            Ok(items) => items,
+            Err(e) => {
+                warn!("Failed to index {}: {}", dir.display(), e);
+                return Ok(());
+            }
+        };
+        for item in items.flatten() {
+            let path = item.path();
+            let is_symlink = item.file_type().is_ok_and(|t| t.is_symlink());
+            let via_symlink = via_symlink || is_symlink;
+            // 失效的符号链接等无法访问的条目不计入索引
+            let Ok(meta) = std::fs::metadata(&path) else {
+                continue;
+            };
+            let allowed = match self.follow_symlinks {
+                FollowSymlinks::Always => true,
+                FollowSymlinks::Never => !via_symlink,
+                FollowSymlinks::SameRoot => {
+                    !via_symlink
+                        || std::fs::canonicalize(&path)
+                            .is_ok_and(|real| real.starts_with(canonical_root))
+                }
+            };
+            entries.insert(path.clone(), meta_entry(&meta, allowed));
+            // 策略不允许访问的目录无需遍历
+            if meta.is_dir() && allowed {
+                // 符号链接可能形成环，每个真实目录只遍历一次
+                let Ok(real) = std::fs::canonicalize(&path) else {
+                    continue;
+                };
+                if visited.insert(real) {
+                    self.walk(&path, via_symlink, canonical_root, visited, entries)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, path: &Path) -> Option<IndexEntry> {
+        self.entries.read().unwrap().get(path).cloned()
+    }
+}
+
+fn meta_entry(meta: &std::fs::Metadata, allowed: bool) -> IndexEntry {
+    entry(meta.len(), meta.modified().ok(), meta.is_dir(), allowed)
+}
+
+// 内存中的文件；有内容摘要时以其作为 ETag，否则与磁盘文件一样由大小与 mtime 生成
+pub fn file_entry(len: u64, modified: Option<SystemTime>, etag: Option<HeaderValue>) -> IndexEntry {
+    let mut file = entry(len, modified, false, true);
+    if let Some(etag) = etag {
+        file.etag = etag;
+    }
+    file
+}
+
+fn entry(len: u64, modified: Option<SystemTime>, is_dir: bool, allowed: bool) -> IndexEntry {
+    let nanos = modified
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let etag = HeaderValue::from_str(&format!("W/\"{:x}-{:x}\"", len, nanos))
+        .unwrap_or(HeaderValue::from_static("W/\"0\""));
+    IndexEntry {
+        is_dir,
+        modified,
+        etag,
+        allowed,
+    }
+}
+
+// 为索引中的文件添加 ETag，If-None-Match 命中时直接返回 304
+#[derive(Clone)]
+pub struct EtagService<S> {
+    inner: S,
+    index: Option<Arc<FileIndex>>,
+}
+
+impl<S> EtagService<S> {
+    pub fn new(inner: S, index: Option<Arc<FileIndex>>) -> Self {
+        EtagService { inner, index }
+    }
+}
+
+impl<S> Service<Request<Body>> for EtagService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let entry = self.index.as_ref().and_then(|index| {
+            let path = crate::site::fs_path(&index.root, req.uri().path())?;
+            index.get(&path).filter(|entry| !entry.is_dir)
+        });
+        let Some(entry) = entry else {
+            return Box::pin(inner.call(req));
+        };
+
+        Box::pin(async move {
+            let matched = req
+                .headers()
+                .get(header::IF_NONE_MATCH)
+                .and_then(|v| v.to_str().ok())
+                .is_some_and(|tags| etag_matches(tags, &entry.etag));
+            if matched {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::NOT_MODIFIED;
+                response.headers_mut().insert(header::ETAG, entry.etag);
+                if let Some(modified) = entry.modified {
+                    if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(modified)) {
+                        response.headers_mut().insert(header::LAST_MODIFIED, value);
+                    }
+                }
+                return Ok(response);
+            }
+            let mut response = inner.call(req).await?;
+            if response.status() == StatusCode::OK
+                || response.status() == StatusCode::PARTIAL_CONTENT
+            {
+                // 预压缩的旁路文件与原文件内容不同，ETag 附加编码名
+                let etag = match response.headers().get(header::CONTENT_ENCODING) {
+                    Some(encoding) => {
+                        let tag = entry.etag.to_str().unwrap_or("W/\"0\"");
+                        let encoding = encoding.to_str().unwrap_or("");
+                        HeaderValue::from_str(&format!(
+                            "{}-{}\"",
+                            tag.trim_end_matches('"'),
+                            encoding
+                        ))
+                        .unwrap_or(entry.etag)
+                    }
+                    None => entry.etag,
+                };
+                response.headers_mut().insert(header::ETAG, etag);
+            }
+            Ok(response)
+        })
+    }
+}
+
+// If-None-Match 使用弱比较
+pub fn etag_matches(tags: &str, etag: &HeaderValue) -> bool {
+    let Ok(etag) = etag.to_str() else {
+        return false;
+    };
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    tags.trim() == "*" || tags.split(',').any(|tag| opaque(tag) == opaque(etag))
+}
@@ -0,0 +1,191 @@
+// 持久连接的限制（[keep_alive]）：空闲超时、每个连接的最大请求数与连接最长存活时间。
+// 达到请求数或存活时间后，HTTP/1 响应带上 Connection: close，由客户端关闭；
+// 没有进行中的请求时服务端优雅关闭连接（HTTP/2 发送 GOAWAY），长时间的下载、SSE 等不会被中断
+use axum::body::{Body, Bytes};
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct KeepAliveConfig {
+    // 两次请求之间允许空闲的秒数，0 表示不限制
+    #[serde(default = "default_idle_timeout")]
+    pub idle_timeout_secs: u64,
+    // 每个连接最多处理的请求数，0 表示不限制
+    #[serde(default = "default_max_requests")]
+    pub max_requests: u64,
+    // 连接的最长存活秒数，到期后在空闲时关闭，0 表示不限制
+    #[serde(default = "default_max_lifetime")]
+    pub max_lifetime_secs: u64,
+}
+
+fn default_idle_timeout() -> u64 {
+    75
+}
+
+fn default_max_requests() -> u64 {
+    1000
+}
+
+fn default_max_lifetime() -> u64 {
+    3600
+}
+
+impl Default for KeepAliveConfig {
+    fn default() -> Self {
+        KeepAliveConfig {
+            idle_timeout_secs: default_idle_timeout(),
+            max_requests: default_max_requests(),
+            max_lifetime_secs: default_max_lifetime(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct KeepAlive {
+    idle_timeout: Option<Duration>,
+    max_requests: Option<u64>,
+    max_lifetime: Option<Duration>,
+}
+
+impl KeepAlive {
+    pub fn new(config: &KeepAliveConfig) -> Self {
+        let secs = |secs: u64| Some(Duration::from_secs(secs)).filter(|d| !d.is_zero());
+        KeepAlive {
+            idle_timeout: secs(config.idle_timeout_secs),
+            max_requests: Some(config.max_requests).filter(|n| *n > 0),
+            max_lifetime: secs(config.max_lifetime_secs),
+        }
+    }
+}
+
+// 一个连接的请求计数与空闲状态
+pub struct Connection {
+    limits: KeepAlive,
+    started: Instant,
+    requests: AtomicU64,
+    in_flight: AtomicUsize,
+    idle_since: Mutex<Instant>,
+    changed: Notify,
+}
+
+impl Connection {
+    pub fn new(limits: KeepAlive) -> Arc<Self> {
+        let now = Instant::now();
+        Arc::new(Connection {
+            limits,
+            started: now,
+            requests: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+            idle_since: Mutex::new(now),
+            changed: Notify::new(),
+        })
+    }
+
+    // 开始处理一个请求；返回 true 时这是该连接上的最后一个请求
+    pub fn begin(self: &Arc<Self>) -> (InFlight, bool) {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        let requests = self.requests.fetch_add(1, Ordering::SeqCst) + 1;
+        let last = self.limits.max_requests.is_some_and(|max| requests >= max)
+            || self
+                .limits
+                .max_lifetime
+                .is_some_and(|lifetime| self.started.elapsed() >= lifetime);
+        (InFlight(self.clone()), last)
+    }
+
+    // 没有进行中的请求时，最早应当关闭连接的时刻
+    fn deadline(&self) -> Option<Instant> {
+        if self.in_flight.load(Ordering::SeqCst) > 0 {
+            return None;
+        }
+        let idle_since = *self.idle_since.lock().unwrap();
+        let mut deadlines = Vec::new();
+        if let Some(idle) = self.limits.idle_timeout {
+            deadlines.push(idle_since + idle);
+        }
+        if let Some(lifetime) = self.limits.max_lifetime {
+            deadlines.push(self.started + lifetime);
+        }
+        if let Some(max) = self.limits.max_requests {
+            if self.requests.load(Ordering::SeqCst) >= max {
+                deadlines.push(idle_since);
+            }
+        }
+        deadlines.into_iter().min()
+    }
+
+    // 连接应当关闭时返回
+    pub async fn expired(&self) {
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+            match self.deadline() {
+                Some(deadline) => tokio::select! {
+                    _ = changed => {}
+                    _ = tokio::time::sleep_until(deadline) => {
+                        if self.in_flight.load(Ordering::SeqCst) == 0 {
+                            return;
+                        }
+                    }
+                },
+                None => changed.await,
+            }
+        }
+    }
+}
+
+// 进行中的请求，响应体发送完毕（或被丢弃）时结束
+pub struct InFlight(Arc<Connection>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let conn = &self.0;
+        *conn.idle_since.lock().unwrap() = Instant::now();
+        conn.in_flight.fetch_sub(1, Ordering::SeqCst);
+        conn.changed.notify_one();
+    }
+}
+
+// 持有 InFlight 的响应体
+pub struct Tracked {
+    inner: Body,
+    _in_flight: InFlight,
+}
+
+impl Tracked {
+    pub fn new(inner: Body, in_flight: InFlight) -> Self {
+        Tracked {
+            inner,
+            _in_flight: in_flight,
+        }
+    }
+}
+
+impl HttpBody for Tracked {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,86 @@
+// 局域网地址：绑定 0.0.0.0 / [::] 时展开为本机的非回环地址，用于打开浏览器与显示访问地址
+use crate::listener::ListenAddr;
+use qrcode::render::unicode::Dense1x2;
+use std::net::{IpAddr, SocketAddr};
+use tracing::warn;
+
+// 本机的非回环地址，IPv4 在前；IPv6 链路本地地址需要指定网卡，浏览器难以直接使用，不计入
+pub fn addresses() -> Vec<IpAddr> {
+    let interfaces = match if_addrs::get_if_addrs() {
+        Ok(interfaces) => interfaces,
+        Err(e) => {
+            warn!("Failed to list network interfaces: {}", e);
+            return Vec::new();
+        }
+    };
+    let mut addrs: Vec<IpAddr> = interfaces
+        .iter()
+        .filter(|iface| !iface.is_loopback())
+        .map(|iface| iface.ip())
+        .filter(|ip| match ip {
+            IpAddr::V4(_) => true,
+            IpAddr::V6(v6) => !v6.is_unicast_link_local(),
+        })
+        .collect();
+    addrs.sort_by_key(|ip| ip.is_ipv6());
+    addrs.dedup();
+    addrs
+}
+
+// 未指定地址的 TCP 监听对应的局域网 URL
+pub fn lan_urls(bound: &[ListenAddr]) -> Vec<String> {
+    let lan = addresses();
+    let mut urls = Vec::new();
+    for addr in bound {
+        let ListenAddr::Tcp(addr) = addr else {
+            continue;
+        };
+        if !addr.ip().is_unspecified() {
+            continue;
+        }
+        // [::] 通常同时接受 IPv4 连接
+        for ip in lan.iter().filter(|ip| ip.is_ipv4() || addr.is_ipv6()) {
+            urls.push(format!("http://{}", SocketAddr::new(*ip, addr.port())));
+        }
+    }
+    urls.dedup();
+    urls
+}
+
+// 可在浏览器中访问的地址：未指定地址的 TCP 监听展开为各局域网地址（没有时使用回环地址），
+// unix socket 不计入
+pub fn urls(bound: &[ListenAddr]) -> Vec<String> {
+    let mut urls = Vec::new();
+    for addr in bound {
+        let ListenAddr::Tcp(addr) = addr else {
+            continue;
+        };
+        if !addr.ip().is_unspecified() {
+            urls.push(format!("http://{}", addr));
+            continue;
+        }
+        let lan = lan_urls(&[ListenAddr::Tcp(*addr)]);
+        if lan.is_empty() {
+            let loopback = match addr {
+                SocketAddr::V4(_) => IpAddr::from([127, 0, 0, 1]),
+                SocketAddr::V6(_) => IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1]),
+            };
+            urls.push(format!("http://{}", SocketAddr::new(loopback, addr.port())));
+        }
+        urls.extend(lan);
+    }
+    urls.dedup();
+    urls
+}
+
+// 终端二维码；按深色背景反色绘制（浅色模块用方块），手机扫码即可打开
+pub fn qr_code(url: &str) -> Option<String> {
+    let code = qrcode::QrCode::new(url.as_bytes()).ok()?;
+    Some(
+        code.render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .quiet_zone(true)
+            .build(),
+    )
+}
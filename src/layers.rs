@@ -0,0 +1,165 @@
+// 可单独使用的 tower 中间件：站点服务内部使用的同一套缓存策略与响应头，
+// 对请求体与响应体类型没有要求，可以组合进任意 tower / axum 服务栈
+use crate::fingerprint::Fingerprint;
+use crate::glob::PathPattern;
+use axum::http::header::{self, HeaderName, HeaderValue};
+use axum::http::{Request, Response, StatusCode};
+use percent_encoding::percent_decode_str;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+use tower_http::set_header::{SetResponseHeader, SetResponseHeaderLayer};
+
+/// 按路径选择 Cache-Control 的策略：先按规则匹配，其次是带内容哈希的文件名，
+/// 最后 HTML 页面（.html、目录、无扩展名的路径）使用 `html`，其余静态资源使用 `assets`；
+/// 错误响应始终使用 `html`，不会被长期缓存
+#[derive(Clone)]
+pub struct CachePolicy {
+    assets: HeaderValue,
+    html: HeaderValue,
+    rules: Arc<Vec<(PathPattern, HeaderValue)>>,
+    fingerprint: Option<Arc<Fingerprint>>,
+}
+
+fn cache_value(value: &str) -> Result<HeaderValue, String> {
+    HeaderValue::from_str(value).map_err(|_| format!("invalid cache_control `{}`", value))
+}
+
+impl CachePolicy {
+    /// `assets` 与 `html` 为 Cache-Control 的值，如 `"public, max-age=31536000, immutable"` 与 `"no-cache"`
+    pub fn new(assets: &str, html: &str) -> Result<Self, String> {
+        Ok(CachePolicy {
+            assets: cache_value(assets)?,
+            html: cache_value(html)?,
+            rules: Arc::new(Vec::new()),
+            fingerprint: None,
+        })
+    }
+
+    /// 追加一条路径规则（glob 规则与配置中的 `download` 相同），按添加顺序匹配，第一条匹配的规则生效
+    pub fn rule(mut self, pattern: &str, cache_control: &str) -> Result<Self, String> {
+        let value = cache_value(cache_control).map_err(|e| format!("{} for `{}`", e, pattern))?;
+        Arc::make_mut(&mut self.rules).push((PathPattern::new(pattern)?, value));
+        Ok(self)
+    }
+
+    // 启用后未匹配哈希的文件一律使用 html
+    pub(crate) fn with_fingerprint(mut self, fingerprint: Option<Arc<Fingerprint>>) -> Self {
+        self.fingerprint = fingerprint;
+        self
+    }
+
+    /// 请求路径（未解码）与响应状态对应的 Cache-Control
+    pub fn value(&self, path: &str, status: StatusCode) -> &HeaderValue {
+        let cacheable = status.is_success() || status == StatusCode::NOT_MODIFIED;
+        if !cacheable {
+            return &self.html;
+        }
+        let decoded = percent_decode_str(path).decode_utf8_lossy();
+        if let Some((_, value)) = self
+            .rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches(&decoded))
+        {
+            return value;
+        }
+        match &self.fingerprint {
+            Some(fingerprint) if fingerprint.matches(&decoded) => &fingerprint.cache_control,
+            Some(_) => &self.html,
+            None if is_html(path) => &self.html,
+            None => &self.assets,
+        }
+    }
+}
+
+// .html、目录与无扩展名的路径按 HTML 页面处理
+pub(crate) fn is_html(path: &str) -> bool {
+    path.ends_with(".html") || path.ends_with('/') || !path.contains('.')
+}
+
+/// 按 [`CachePolicy`] 设置（覆盖）响应的 Cache-Control
+#[derive(Clone)]
+pub struct CacheControlLayer {
+    policy: CachePolicy,
+}
+
+impl CacheControlLayer {
+    pub fn new(policy: CachePolicy) -> Self {
+        CacheControlLayer { policy }
+    }
+}
+
+impl<S> Layer<S> for CacheControlLayer {
+    type Service = CacheControl<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CacheControl {
+            inner,
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+/// [`CacheControlLayer`] 生成的服务
+#[derive(Clone)]
+pub struct CacheControl<S> {
+    inner: S,
+    policy: CachePolicy,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for CacheControl<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Future: Send + 'static,
+    S::Error: 'static,
+    ResBody: 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let path = req.uri().path().to_string();
+        let policy = self.policy.clone();
+        let future = self.inner.call(req);
+        Box::pin(async move {
+            let mut response = future.await?;
+            let value = policy.value(&path, response.status()).clone();
+            response.headers_mut().insert(header::CACHE_CONTROL, value);
+            Ok(response)
+        })
+    }
+}
+
+/// 跨源隔离：响应中没有时添加 `Cross-Origin-Opener-Policy: same-origin` 与
+/// `Cross-Origin-Embedder-Policy: require-corp`，页面才能使用 SharedArrayBuffer（FFmpeg.wasm 多线程）
+#[derive(Clone, Copy, Default)]
+pub struct CrossOriginIsolationLayer;
+
+impl CrossOriginIsolationLayer {
+    pub fn new() -> Self {
+        CrossOriginIsolationLayer
+    }
+}
+
+impl<S> Layer<S> for CrossOriginIsolationLayer {
+    type Service = SetResponseHeader<SetResponseHeader<S, HeaderValue>, HeaderValue>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        let embedder = SetResponseHeaderLayer::if_not_present(
+            HeaderName::from_static("cross-origin-embedder-policy"),
+            HeaderValue::from_static("require-corp"),
+        );
+        let opener = SetResponseHeaderLayer::if_not_present(
+            HeaderName::from_static("cross-origin-opener-policy"),
+            HeaderValue::from_static("same-origin"),
+        );
+        opener.layer(embedder.layer(inner))
+    }
+}
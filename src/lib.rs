@@ -0,0 +1,313 @@
+// Sonic Wave 静态站点服务：命令行程序（src/main.rs）只负责解析参数与初始化日志，
+// 其他 axum 应用可以通过 SonicWave::builder() 构建同样的 Router 嵌入自己的服务
+use axum::Router;
+use cli::{Cli, Command};
+use config::Config;
+use shutdown::Readiness;
+use std::path::Path;
+use tracing::subscriber::NoSubscriber;
+#[cfg(not(target_os = "linux"))]
+use tracing::warn;
+#[cfg(target_os = "linux")]
+use tracing::{error, info};
+
+mod access_log;
+mod analytics;
+mod app;
+mod archive;
+mod audio;
+mod audio_meta;
+mod basic_auth;
+mod bench;
+mod build;
+mod cache;
+mod canary;
+mod canonical_host;
+mod cdn;
+mod chaos;
+mod check;
+pub mod cli;
+mod client_auth;
+mod completions;
+mod compression;
+mod compression_cache;
+mod conditional;
+mod config;
+mod cookies;
+mod cors;
+#[cfg(unix)]
+mod daemon;
+mod debug_artifacts;
+mod digest;
+mod dir_overrides;
+mod early_hints;
+mod embed;
+mod error_pages;
+mod favicon;
+mod fingerprint;
+mod forward_auth;
+mod forwarded;
+mod geoip;
+mod git_site;
+mod glob;
+mod hls;
+#[cfg(any(feature = "wasm", feature = "lua"))]
+mod hooks;
+mod hotlink;
+mod i18n;
+mod image_variants;
+mod images;
+mod index;
+mod keep_alive;
+mod lan;
+pub mod layers;
+mod link_headers;
+mod listener;
+mod listing;
+mod live_reload;
+mod log_sink;
+mod login;
+mod maintenance;
+mod manifest;
+mod markdown;
+mod mdns;
+mod memfs;
+mod methods;
+mod metrics;
+mod middleware;
+mod mime;
+mod mirror;
+mod mmap;
+mod oidc;
+mod path_match;
+mod path_validation;
+mod playlist;
+mod plugins;
+mod podcast;
+mod precompress;
+mod preload;
+mod prerender;
+#[cfg(unix)]
+mod privileges;
+mod proxy;
+mod proxy_cache;
+mod proxy_protocol;
+mod ranges;
+mod releases;
+mod reports;
+mod request_id;
+mod rewrite;
+mod runtime;
+mod runtime_env;
+mod s3;
+#[cfg(target_os = "linux")]
+mod sandbox;
+mod script;
+mod search;
+mod security_headers;
+mod sentry;
+mod server;
+mod server_header;
+mod server_timing;
+#[cfg(windows)]
+mod service;
+mod service_worker;
+mod shadow;
+mod shutdown;
+mod site;
+mod sitemap;
+mod slow_client;
+mod sri;
+mod ssi;
+mod status;
+mod supervisor;
+#[cfg(unix)]
+mod systemd;
+mod templates;
+mod tenants;
+mod throttle;
+mod tls;
+mod tus;
+mod unicode_tables;
+#[cfg(unix)]
+mod upgrade;
+mod upload;
+mod uring;
+mod user_agent;
+mod vary;
+mod version;
+mod vhost;
+mod waveform;
+mod webdav;
+mod zip_download;
+
+// 按子命令运行；未指定子命令时运行服务
+pub async fn run(cli: Cli) {
+    match cli.command {
+        Some(Command::Supervise(args)) => supervisor::run(args).await,
+        Some(Command::Precompress(args)) => precompress::run(args),
+        Some(Command::Manifest(args)) => manifest::run(args),
+        Some(Command::Sri(args)) => sri::run(args),
+        Some(Command::HashPassword(args)) => login::run(args),
+        Some(Command::Check(args)) => check::run(args),
+        Some(Command::Bench(args)) => bench::run(args).await,
+        Some(Command::Completions(args)) => completions::run(args),
+        Some(Command::PrintConfig(args)) => config::print(args.format, cli.dev),
+        Some(Command::Schema) => config::print_schema(),
+        #[cfg(windows)]
+        Some(Command::Service(args)) => service::run(args),
+        Some(Command::Serve(args)) if args.dry_run => config::print(args.print.format, cli.dev),
+        Some(Command::Serve(_)) | None => {
+            server::run(cli.watch || cli.dev, cli.open, cli.dev).await
+        }
+    }
+}
+
+fn serving(cli: &Cli) -> bool {
+    match &cli.command {
+        Some(Command::Serve(args)) => !args.dry_run,
+        None => true,
+        Some(_) => false,
+    }
+}
+
+// --profile：写入 SONICWAVE_PROFILE，之后各处读取配置时使用，supervisor 启动的工作进程同样继承；
+// 需要在读取配置与创建其他线程之前调用
+pub fn select_profile(cli: &Cli) {
+    if let Some(profile) = &cli.profile {
+        std::env::set_var(config::PROFILE_ENV, profile);
+    }
+}
+
+// --daemon / --pid-file（serve 与 supervise）：fork 只复制调用线程，需要在启动 tokio 运行时之前调用；
+// 返回的 PID 文件在正常退出时删除
+#[cfg(unix)]
+pub fn daemonize(cli: &Cli) -> Option<PidFile> {
+    let applies = serving(cli) || matches!(cli.command, Some(Command::Supervise(_)));
+    if !applies {
+        return None;
+    }
+    let config = tracing::subscriber::with_default(NoSubscriber::default(), config::load_config);
+    match daemon::start(
+        cli.daemon,
+        cli.pid_file.as_deref(),
+        cli.log_file.as_deref(),
+        &config,
+    ) {
+        Ok(pid_file) => pid_file,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(unix)]
+pub use daemon::PidFile;
+
+// [logging] 选择的 syslog / journald 日志层（serve 与 supervise），由 main.rs 在初始化日志时加入；
+// 返回 None 时写到标准输出
+#[cfg(unix)]
+pub fn log_sink(cli: &Cli) -> Result<Option<LogSink>, String> {
+    let applies = serving(cli) || matches!(cli.command, Some(Command::Supervise(_)));
+    if !applies {
+        return Ok(None);
+    }
+    let config = tracing::subscriber::with_default(NoSubscriber::default(), config::load_config);
+    log_sink::open(&config.logging)
+}
+
+#[cfg(unix)]
+pub use log_sink::LogSink;
+
+// serve 的文件系统沙箱（sandbox = true）：只约束之后创建的线程，需要在启动 tokio 运行时之前调用
+pub fn sandbox(cli: &Cli) {
+    if !serving(cli) {
+        return;
+    }
+    let config = tracing::subscriber::with_default(NoSubscriber::default(), config::load_config);
+    if !config.sandbox {
+        return;
+    }
+    #[cfg(target_os = "linux")]
+    match sandbox::apply(&config, cli.pid_file.as_deref()) {
+        Ok(()) => info!("Filesystem sandbox enabled"),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    warn!("sandbox is only supported on Linux, ignoring it");
+}
+
+// tokio 运行时：serve 按配置中的 [runtime] 构建，其他子命令使用默认设置
+pub fn runtime(cli: &Cli) -> tokio::runtime::Runtime {
+    #[cfg(windows)]
+    let service = matches!(&cli.command, Some(Command::Service(args)) if matches!(args.action, cli::ServiceAction::Run(_)));
+    #[cfg(not(windows))]
+    let service = false;
+    let config = if serving(cli) || service {
+        tracing::subscriber::with_default(NoSubscriber::default(), config::load_config).runtime
+    } else {
+        runtime::RuntimeConfig::default()
+    };
+    // 服务线程通过 Handle::block_on 运行服务器，单线程运行时不会在那里驱动 IO
+    if service && config.flavor == runtime::Flavor::CurrentThread {
+        tracing::error!("the Windows service requires [runtime] flavor = \"multi_thread\"");
+        std::process::exit(1);
+    }
+    match runtime::build(&config) {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+// service run 的事件日志层，由 main.rs 加入日志订阅者
+#[cfg(windows)]
+pub use service::{event_log, EventLog};
+
+pub struct SonicWave;
+
+impl SonicWave {
+    pub fn builder() -> Builder {
+        Builder::default()
+    }
+}
+
+// 以默认配置为起点（与没有 config.toml 时相同），不读取环境变量；
+// 需要在 tokio 运行时中调用 build_router
+#[derive(Default)]
+pub struct Builder {
+    config: Config,
+}
+
+impl Builder {
+    // 读取 config.toml 格式的配置文件，替换之前的所有设置
+    pub fn config_file(mut self, path: impl AsRef<Path>) -> Result<Self, String> {
+        self.config = Config::from_file(path.as_ref())?;
+        Ok(self)
+    }
+
+    // 主站点目录，同时关闭内嵌资源
+    pub fn static_dir(mut self, dir: impl Into<String>) -> Self {
+        self.config.static_dir = Some(dir.into());
+        self.config.embedded = false;
+        self
+    }
+
+    // 静态资源与 HTML 的 Cache-Control
+    pub fn cache_policy(mut self, assets: impl Into<String>, html: impl Into<String>) -> Self {
+        self.config.cache_control = assets.into();
+        self.config.html_cache_control = html.into();
+        self
+    }
+
+    // 组装与 sonic-wave 命令相同的路由与中间件（包括就绪检查路径），不含监听与优雅关闭
+    pub fn build_router(self) -> Result<Router, String> {
+        app::build(&self.config, Readiness::new(), None)
+    }
+}
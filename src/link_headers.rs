@@ -0,0 +1,78 @@
+// Link 响应头规则（[[link_headers]]）：为匹配路径的 HTML 响应添加 preload / preconnect 等 Link 头，
+// 浏览器与 CDN 据此提前获取关键资源；与 [early_hints] 的自动扫描相互独立
+use crate::glob::PathPattern;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct LinkHeaderRule {
+    // 路径模式，规则与 download 相同，如 "/app/**"、"*.html"
+    pub pattern: String,
+    // Link 头的值，如 "</app.css>; rel=preload; as=style"
+    pub links: Vec<String>,
+}
+
+pub struct LinkHeaders {
+    rules: Vec<(PathPattern, Vec<HeaderValue>)>,
+}
+
+impl LinkHeaders {
+    pub fn new(rules: &[LinkHeaderRule]) -> Result<Self, String> {
+        let mut compiled = Vec::new();
+        for rule in rules {
+            let pattern = PathPattern::new(&rule.pattern)?;
+            let mut links = Vec::new();
+            for link in &rule.links {
+                let link = link.trim();
+                if !link.starts_with('<') || !link.contains(">;") {
+                    return Err(format!(
+                        "link header `{}` must look like `<url>; rel=...`",
+                        link
+                    ));
+                }
+                let value = HeaderValue::from_str(link)
+                    .map_err(|e| format!("invalid link header `{}`: {}", link, e))?;
+                links.push(value);
+            }
+            compiled.push((pattern, links));
+        }
+        Ok(LinkHeaders { rules: compiled })
+    }
+}
+
+fn is_html(response: &Response) -> bool {
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"))
+}
+
+// 按原始请求路径匹配，所有匹配的规则都生效，重复的值只添加一次
+pub async fn apply(State(links): State<Arc<LinkHeaders>>, req: Request, next: Next) -> Response {
+    let path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    let mut response = next.run(req).await;
+    if !response.status().is_success() || !is_html(&response) {
+        return response;
+    }
+    for (pattern, values) in &links.rules {
+        if !pattern.matches(&path) {
+            continue;
+        }
+        for value in values {
+            let headers = response.headers_mut();
+            if !headers.get_all(header::LINK).iter().any(|v| v == value) {
+                headers.append(header::LINK, value.clone());
+            }
+        }
+    }
+    response
+}
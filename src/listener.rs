@@ -0,0 +1,600 @@
+// 监听地址解析与套接字创建（TCP / Unix domain socket）
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+// 配置中的监听地址："0.0.0.0:8089" 或 "unix:/run/sonicwave.sock"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err("unix socket path is empty".to_string());
+            }
+            return Ok(ListenAddr::Unix(PathBuf::from(path)));
+        }
+        s.parse::<SocketAddr>()
+            .map(ListenAddr::Tcp)
+            .map_err(|e| format!("invalid listen address `{}`: {}", s, e))
+    }
+}
+
+// 一条监听配置展开后的绑定目标，可附带网卡名（"0.0.0.0:8089@eth0"，仅 Linux）
+#[derive(Debug, Clone)]
+pub struct ListenSpec {
+    pub addr: ListenAddr,
+    pub device: Option<String>,
+    pub options: ListenerOptions,
+    // 这个监听器自己的 socket 文件权限，未设置的项使用 [unix_socket]
+    pub unix_socket: UnixSocketConfig,
+}
+
+impl ListenSpec {
+    // 解析一条监听配置：
+    //   "8089" / ":8089"          -> 0.0.0.0:8089
+    //   "[::]:8089"               -> IPv6（与同端口的 IPv4 通配地址并存时自动设置 IPV6_V6ONLY）
+    //   "localhost:8089"          -> 解析主机名得到的全部地址
+    //   "0.0.0.0:8089@eth0"       -> 绑定到指定网卡
+    //   "unix:/run/sonicwave.sock"
+    pub fn parse_all(entry: &ListenEntry) -> Result<Vec<ListenSpec>, String> {
+        let s = entry.address.trim();
+        let options = entry.options;
+        if s.starts_with("unix:") {
+            return Ok(vec![ListenSpec {
+                addr: s.parse()?,
+                device: None,
+                options,
+                unix_socket: entry.unix_socket.clone(),
+            }]);
+        }
+        if !entry.unix_socket.is_empty() {
+            return Err(format!(
+                "listen address `{}`: mode / owner / group only apply to unix: addresses",
+                s
+            ));
+        }
+
+        let (addr_part, device) = match s.rsplit_once('@') {
+            Some((addr, dev)) if !dev.is_empty() => (addr, Some(dev.to_string())),
+            _ => (s, None),
+        };
+
+        let port_only = addr_part.strip_prefix(':').unwrap_or(addr_part);
+        if let Ok(port) = port_only.parse::<u16>() {
+            return Ok(vec![ListenSpec {
+                addr: ListenAddr::Tcp(SocketAddr::from(([0, 0, 0, 0], port))),
+                device,
+                options,
+                unix_socket: UnixSocketConfig::default(),
+            }]);
+        }
+
+        if let Ok(addr) = addr_part.parse::<SocketAddr>() {
+            return Ok(vec![ListenSpec {
+                addr: ListenAddr::Tcp(addr),
+                device,
+                options,
+                unix_socket: UnixSocketConfig::default(),
+            }]);
+        }
+
+        // 主机名，例如 localhost:8089
+        use std::net::ToSocketAddrs;
+        let resolved: Vec<SocketAddr> = addr_part
+            .to_socket_addrs()
+            .map_err(|e| format!("invalid listen address `{}`: {}", s, e))?
+            .collect();
+        if resolved.is_empty() {
+            return Err(format!("listen address `{}` resolved to nothing", s));
+        }
+        let mut specs: Vec<ListenSpec> = Vec::new();
+        for addr in resolved {
+            if specs.iter().all(|spec| spec.addr != ListenAddr::Tcp(addr)) {
+                specs.push(ListenSpec {
+                    addr: ListenAddr::Tcp(addr),
+                    device: device.clone(),
+                    options,
+                    unix_socket: UnixSocketConfig::default(),
+                });
+            }
+        }
+        Ok(specs)
+    }
+}
+
+// 同端口同时存在 IPv4 通配地址时，IPv6 通配地址只接受 IPv6，避免 EADDRINUSE
+pub fn needs_v6_only(addr: &SocketAddr, all: &[ListenSpec]) -> bool {
+    addr.is_ipv6()
+        && all.iter().any(|spec| {
+            matches!(spec.addr, ListenAddr::Tcp(other)
+                if other.is_ipv4() && other.port() == addr.port())
+        })
+}
+
+impl ListenAddr {
+    // 带协议的地址，TLS 监听器显示为 https://
+    pub fn url(&self, tls: bool) -> String {
+        match self {
+            ListenAddr::Tcp(addr) if tls => format!("https://{}", addr),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "http://{}", addr),
+            ListenAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+// Unix socket 文件权限与属主
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, JsonSchema)]
+pub struct UnixSocketConfig {
+    // 八进制权限，例如 "660"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    // 属主用户名或 uid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    // 属组名或 gid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+impl UnixSocketConfig {
+    fn is_empty(&self) -> bool {
+        *self == UnixSocketConfig::default()
+    }
+
+    // 逐项覆盖：本身设置的项优先，其余取 fallback
+    #[cfg(unix)]
+    fn or(&self, fallback: &UnixSocketConfig) -> UnixSocketConfig {
+        UnixSocketConfig {
+            mode: self.mode.clone().or_else(|| fallback.mode.clone()),
+            owner: self.owner.clone().or_else(|| fallback.owner.clone()),
+            group: self.group.clone().or_else(|| fallback.group.clone()),
+        }
+    }
+}
+
+// 一条监听配置：可以是地址字符串，也可以是带选项的表
+//   listen = ["0.0.0.0:8089", { address = "0.0.0.0:8443", proxy_protocol = true, tls = true, tcp_nodelay = true }]
+//   listen = [{ address = "unix:/run/sonicwave.sock", mode = "660", group = "www-data" }]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(from = "ListenEntryRaw", into = "ListenEntryRaw")]
+pub struct ListenEntry {
+    pub address: String,
+    pub options: ListenerOptions,
+    pub unix_socket: UnixSocketConfig,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ListenEntryRaw {
+    Address(String),
+    Table {
+        address: String,
+        #[serde(flatten)]
+        options: ListenerOptions,
+        #[serde(flatten)]
+        unix_socket: UnixSocketConfig,
+    },
+}
+
+impl From<ListenEntryRaw> for ListenEntry {
+    fn from(raw: ListenEntryRaw) -> Self {
+        match raw {
+            ListenEntryRaw::Address(address) => ListenEntry {
+                address,
+                options: ListenerOptions::default(),
+                unix_socket: UnixSocketConfig::default(),
+            },
+            ListenEntryRaw::Table {
+                address,
+                options,
+                unix_socket,
+            } => ListenEntry {
+                address,
+                options,
+                unix_socket,
+            },
+        }
+    }
+}
+
+// 没有选项时写回地址字符串
+impl From<ListenEntry> for ListenEntryRaw {
+    fn from(entry: ListenEntry) -> Self {
+        if entry.options != ListenerOptions::default() || !entry.unix_socket.is_empty() {
+            ListenEntryRaw::Table {
+                address: entry.address,
+                options: entry.options,
+                unix_socket: entry.unix_socket,
+            }
+        } else {
+            ListenEntryRaw::Address(entry.address)
+        }
+    }
+}
+
+// 单个监听器的选项：连接处理方式与 TCP 套接字参数
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, JsonSchema)]
+#[serde(default)]
+pub struct ListenerOptions {
+    // 连接开头携带 PROXY protocol v1/v2 头
+    pub proxy_protocol: bool,
+    // 提供 HTTPS，证书见 [tls]
+    pub tls: bool,
+    // 关闭 Nagle 算法，小响应与 HTTP/2 帧不再等待合并
+    pub tcp_nodelay: bool,
+    // 等待 accept 的连接队列长度，默认 1024（受 net.core.somaxconn 限制）
+    pub backlog: Option<u32>,
+    // 以 SO_REUSEPORT 绑定（仅 Unix），与顶层的 reuse_port 相同，只作用于这个监听器
+    pub reuse_port: bool,
+    // SO_SNDBUF / SO_RCVBUF（字节），由接受的连接继承；内核通常会把设置值翻倍
+    pub send_buffer: Option<usize>,
+    pub recv_buffer: Option<usize>,
+}
+
+// 单个值与数组两种写法，如 `listen = "..."` 与 `listen = ["...", {...}]`；
+// 字段的 JSON Schema 用 #[schemars(with = "OneOrMany<T>")] 描述
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+pub fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(entry) => vec![entry],
+        OneOrMany::Many(entries) => entries,
+    })
+}
+
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+}
+
+// 对端地址，Unix socket 没有有意义的地址
+#[derive(Debug, Clone, Copy)]
+pub enum PeerAddr {
+    Tcp(SocketAddr),
+    #[cfg(unix)]
+    Unix,
+}
+
+impl fmt::Display for PeerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerAddr::Tcp(addr) => write!(f, "{}", addr),
+            #[cfg(unix)]
+            PeerAddr::Unix => write!(f, "unix"),
+        }
+    }
+}
+
+impl Listener {
+    pub fn bind(
+        spec: &ListenSpec,
+        options: &TcpBindOptions,
+        unix_config: &UnixSocketConfig,
+    ) -> io::Result<Listener> {
+        match &spec.addr {
+            ListenAddr::Tcp(addr) => {
+                bind_tcp(*addr, spec.device.as_deref(), options).map(Listener::Tcp)
+            }
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => bind_unix(path, &spec.unix_socket.or(unix_config)),
+            #[cfg(not(unix))]
+            ListenAddr::Unix(_) => {
+                let _ = (unix_config, &spec.unix_socket);
+                Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "unix sockets are not supported on this platform",
+                ))
+            }
+        }
+    }
+
+    pub async fn accept(&self) -> io::Result<(Stream, PeerAddr)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, addr) = listener.accept().await?;
+                Ok((Stream::Tcp(stream), PeerAddr::Tcp(addr)))
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener, _) => {
+                let (stream, _) = listener.accept().await?;
+                Ok((Stream::Unix(stream), PeerAddr::Unix))
+            }
+        }
+    }
+
+    pub fn local_addr(&self) -> ListenAddr {
+        match self {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => ListenAddr::Tcp(addr),
+                Err(_) => ListenAddr::Tcp(SocketAddr::from(([0, 0, 0, 0], 0))),
+            },
+            #[cfg(unix)]
+            Listener::Unix(_, path) => ListenAddr::Unix(path.clone()),
+        }
+    }
+
+    #[cfg(unix)]
+    pub fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        use std::os::fd::AsRawFd;
+        match self {
+            Listener::Tcp(listener) => listener.as_raw_fd(),
+            Listener::Unix(listener, _) => listener.as_raw_fd(),
+        }
+    }
+
+    // 由继承的 FD 重建监听器（热重启 / systemd），根据套接字类型区分 TCP 与 Unix
+    #[cfg(unix)]
+    pub fn from_fd(fd: std::os::fd::RawFd) -> io::Result<Listener> {
+        use std::os::fd::FromRawFd;
+        // SAFETY: 调用方保证 FD 归本进程所有且为监听套接字
+        let socket = unsafe { Socket::from_raw_fd(fd) };
+        socket.set_nonblocking(true)?;
+        let local = socket.local_addr()?;
+        if local.is_unix() {
+            let path = local
+                .as_pathname()
+                .map(|p| p.to_path_buf())
+                .unwrap_or_default();
+            let std_listener: std::os::unix::net::UnixListener = socket.into();
+            Ok(Listener::Unix(UnixListener::from_std(std_listener)?, path))
+        } else {
+            let std_listener: std::net::TcpListener = socket.into();
+            Ok(Listener::Tcp(TcpListener::from_std(std_listener)?))
+        }
+    }
+
+    // 关闭后清理 socket 文件（已交接给新进程时保留）
+    pub fn cleanup(&self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = self {
+            if !crate::upgrade::handed_off() {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TcpBindOptions {
+    // 多个进程共享同一端口（仅 Unix）
+    pub reuse_port: bool,
+    // IPv6 套接字只接受 IPv6 连接
+    pub only_v6: bool,
+    pub backlog: Option<u32>,
+    pub send_buffer: Option<usize>,
+    pub recv_buffer: Option<usize>,
+}
+
+// 绑定 TCP 监听
+fn bind_tcp(
+    addr: SocketAddr,
+    device: Option<&str>,
+    options: &TcpBindOptions,
+) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    #[cfg(unix)]
+    {
+        socket.set_reuse_address(true)?;
+        if options.reuse_port {
+            socket.set_reuse_port(true)?;
+        }
+    }
+
+    #[cfg(not(unix))]
+    if options.reuse_port {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "SO_REUSEPORT is not supported on this platform",
+        ));
+    }
+
+    if addr.is_ipv6() {
+        socket.set_only_v6(options.only_v6)?;
+    }
+    // 接收缓冲区须在 listen 之前设置，窗口缩放因子在握手时确定
+    if let Some(size) = options.send_buffer {
+        socket.set_send_buffer_size(size)?;
+    }
+    if let Some(size) = options.recv_buffer {
+        socket.set_recv_buffer_size(size)?;
+    }
+
+    if let Some(device) = device {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        socket.bind_device(Some(device.as_bytes()))?;
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "binding to interface `{}` is only supported on Linux",
+                device
+            ),
+        ));
+    }
+
+    socket.bind(&addr.into())?;
+    socket.listen(
+        options
+            .backlog
+            .map_or(1024, |n| n.min(i32::MAX as u32) as i32),
+    )?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &std::path::Path, config: &UnixSocketConfig) -> io::Result<Listener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    // 清理上次异常退出遗留的 socket 文件，但绝不删除普通文件
+    if let Ok(meta) = std::fs::symlink_metadata(path) {
+        if meta.file_type().is_socket() {
+            std::fs::remove_file(path)?;
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+    }
+
+    let listener = UnixListener::bind(path)?;
+
+    if let Some(mode) = &config.mode {
+        let mode = u32::from_str_radix(mode.trim_start_matches("0o"), 8).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid unix socket mode `{}`", mode),
+            )
+        })?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+
+    if config.owner.is_some() || config.group.is_some() {
+        let uid = config.owner.as_deref().map(resolve_user).transpose()?;
+        let gid = config.group.as_deref().map(resolve_group).transpose()?;
+        nix::unistd::chown(path, uid, gid).map_err(io::Error::from)?;
+    }
+
+    Ok(Listener::Unix(listener, path.to_path_buf()))
+}
+
+#[cfg(unix)]
+fn resolve_user(name: &str) -> io::Result<nix::unistd::Uid> {
+    if let Ok(uid) = name.parse::<u32>() {
+        return Ok(nix::unistd::Uid::from_raw(uid));
+    }
+    nix::unistd::User::from_name(name)
+        .map_err(io::Error::from)?
+        .map(|u| u.uid)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no such user `{}`", name)))
+}
+
+#[cfg(unix)]
+fn resolve_group(name: &str) -> io::Result<nix::unistd::Gid> {
+    if let Ok(gid) = name.parse::<u32>() {
+        return Ok(nix::unistd::Gid::from_raw(gid));
+    }
+    nix::unistd::Group::from_name(name)
+        .map_err(io::Error::from)?
+        .map(|g| g.gid)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no such group `{}`", name)))
+}
+
+// 已接受的连接
+pub enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    pub fn set_nodelay(&self, nodelay: bool) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_nodelay(nodelay),
+            #[cfg(unix)]
+            Stream::Unix(_) => Ok(()),
+        }
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Stream::Tcp(s) => Pin::new(s).poll_write_vectored(cx, bufs),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            Stream::Tcp(s) => s.is_write_vectored(),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.is_write_vectored(),
+        }
+    }
+}
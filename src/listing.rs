@@ -0,0 +1,64 @@
+// 目录列表（directory_listing，--dev 时开启）：目录中没有索引文件时返回子项列表页，
+// 目录在前、按名称排序；子项同样经过 deny / dotfile / follow_symlinks 规则过滤
+use crate::error_pages::{html_escape, rfc3339};
+use crate::webdav::encode_segment;
+use axum::body::Body;
+use axum::http::header::{self, HeaderValue};
+use axum::response::Response;
+use percent_encoding::percent_decode_str;
+use std::time::SystemTime;
+
+pub struct ListingEntry {
+    pub name: String,
+    pub is_dir: bool,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+// base 为浏览器请求的目录地址（含挂载前缀、以 "/" 结尾），子项链接使用绝对路径
+pub fn render(base: &str, mut entries: Vec<ListingEntry>) -> Response {
+    entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    let title = html_escape(&format!(
+        "Index of {}",
+        percent_decode_str(base).decode_utf8_lossy()
+    ));
+    let mut body = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n<table>\n"
+    );
+    if base != "/" {
+        let parent = base.trim_end_matches('/');
+        let parent = &parent[..parent.rfind('/').map_or(0, |i| i + 1)];
+        body.push_str(&format!(
+            "<tr><td><a href=\"{}\">../</a></td><td></td><td></td></tr>\n",
+            html_escape(parent)
+        ));
+    }
+    for entry in &entries {
+        let slash = if entry.is_dir { "/" } else { "" };
+        let size = if entry.is_dir {
+            "-".to_string()
+        } else {
+            entry.len.to_string()
+        };
+        body.push_str(&format!(
+            "<tr><td><a href=\"{}{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            html_escape(base),
+            html_escape(&encode_segment(&entry.name)),
+            slash,
+            html_escape(&entry.name),
+            slash,
+            size,
+            entry.modified.map(rfc3339).unwrap_or_default()
+        ));
+    }
+    body.push_str("</table>\n</body>\n</html>\n");
+
+    let mut response = Response::new(Body::from(body));
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
@@ -0,0 +1,278 @@
+// 开发模式（--watch）：监听站点目录，通过 SSE 通知浏览器刷新；CSS 变化时只替换样式表，不刷新页面
+use crate::build;
+use crate::glob::PathPattern;
+use axum::body::{Body, HttpBody};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use futures_util::Stream;
+use notify::{RecursiveMode, Watcher};
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+pub const EVENTS_PATH: &str = "/__livereload";
+pub const SCRIPT_PATH: &str = "/__livereload.js";
+
+// 连续的文件事件合并为一次通知
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+const SCRIPT: &str = r#"(function () {
+  var source = new EventSource("/__livereload");
+  source.addEventListener("reload", function () { location.reload(); });
+  source.addEventListener("css", function () {
+    document.querySelectorAll('link[rel="stylesheet"]').forEach(function (link) {
+      var url = new URL(link.href, location.href);
+      url.searchParams.set("_lr", Date.now());
+      link.href = url.toString();
+    });
+  });
+})();
+"#;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    // 只有样式表变化
+    Css,
+    Reload,
+    // 服务器关闭，结束 SSE 连接以免拖住排空
+    Shutdown,
+}
+
+// 文件变化的广播中心，SSE 连接与缓存失效任务都订阅它
+pub struct ChangeHub {
+    tx: broadcast::Sender<Change>,
+}
+
+impl ChangeHub {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(16);
+        ChangeHub { tx }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Change> {
+        self.tx.subscribe()
+    }
+
+    pub fn shutdown(&self) {
+        let _ = self.tx.send(Change::Shutdown);
+    }
+}
+
+// [build] 设置了 watch 时，源文件变化后重新构建，完成后才通知浏览器
+pub struct OnChange {
+    command: String,
+    working_dir: Option<PathBuf>,
+    // 相对构建命令的工作目录匹配
+    patterns: Vec<PathPattern>,
+    debounce: Duration,
+    root: PathBuf,
+}
+
+impl OnChange {
+    pub fn new(build: &build::BuildConfig) -> Result<Option<Self>, String> {
+        if build.watch.is_empty() {
+            return Ok(None);
+        }
+        let patterns = build
+            .watch
+            .iter()
+            .map(|p| PathPattern::new(p))
+            .collect::<Result<_, _>>()?;
+        let root = match &build.working_dir {
+            Some(dir) => std::fs::canonicalize(dir)
+                .map_err(|e| format!("failed to resolve {}: {}", dir.display(), e))?,
+            None => std::env::current_dir()
+                .and_then(std::fs::canonicalize)
+                .map_err(|e| format!("failed to resolve current directory: {}", e))?,
+        };
+        Ok(Some(OnChange {
+            command: build.command.clone(),
+            working_dir: build.working_dir.clone(),
+            patterns,
+            debounce: Duration::from_millis(build.debounce_ms),
+            root,
+        }))
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        let url: String = relative
+            .components()
+            .map(|c| format!("/{}", c.as_os_str().to_string_lossy()))
+            .collect();
+        self.patterns.iter().any(|p| p.matches(&url))
+    }
+}
+
+// 监听目录；返回的 watcher 需要保持存活
+pub fn watch(
+    dirs: &[PathBuf],
+    hub: Arc<ChangeHub>,
+    on_change: Option<OnChange>,
+) -> notify::Result<notify::RecommendedWatcher> {
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if !event.kind.is_access() && !event.kind.is_other() => {
+                let _ = event_tx.send(event.paths);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("File watcher error: {}", e),
+        })?;
+    // 监听规范化后的绝对路径，事件路径可以直接与站点目录、命令的 glob 比较
+    let mut site_dirs = Vec::new();
+    for dir in dirs {
+        let dir = std::fs::canonicalize(dir)?;
+        if !site_dirs.contains(&dir) {
+            watcher.watch(&dir, RecursiveMode::Recursive)?;
+            info!("Watching {} for changes", dir.display());
+            site_dirs.push(dir);
+        }
+    }
+    if let Some(hook) = &on_change {
+        if !site_dirs.iter().any(|dir| hook.root.starts_with(dir)) {
+            watcher.watch(&hook.root, RecursiveMode::Recursive)?;
+            info!("Watching {} for build sources", hook.root.display());
+        }
+    }
+    let debounce = on_change.as_ref().map_or(DEBOUNCE, |h| h.debounce);
+
+    tokio::spawn(async move {
+        let drain =
+            |paths: &mut Vec<PathBuf>,
+             rx: &mut tokio::sync::mpsc::UnboundedReceiver<Vec<PathBuf>>| {
+                while let Ok(more) = rx.try_recv() {
+                    paths.extend(more);
+                }
+            };
+        while let Some(mut paths) = event_rx.recv().await {
+            // 编辑器保存时往往产生一串事件，等待片刻后一起处理
+            tokio::time::sleep(debounce).await;
+            drain(&mut paths, &mut event_rx);
+
+            let in_site = |p: &PathBuf| site_dirs.iter().any(|dir| p.starts_with(dir));
+            let mut ran = false;
+            if let Some(hook) = &on_change {
+                if paths.iter().any(|p| hook.matches(p)) {
+                    info!("Running build command: {}", hook.command);
+                    let result =
+                        build::run_command(&hook.command, hook.working_dir.as_deref()).await;
+                    // 命令运行期间的事件（通常是构建产物）并入本次通知，不再触发命令，避免循环
+                    tokio::time::sleep(DEBOUNCE).await;
+                    drain(&mut paths, &mut event_rx);
+                    match result {
+                        Ok(elapsed) => info!("Build finished in {:.2?}", elapsed),
+                        Err(e) => {
+                            warn!("Build failed: {}", e);
+                            continue;
+                        }
+                    }
+                    ran = true;
+                    paths.retain(|p| !hook.matches(p));
+                }
+            }
+
+            paths.retain(in_site);
+            if paths.is_empty() && !ran {
+                continue;
+            }
+            let css_only = !paths.is_empty()
+                && paths
+                    .iter()
+                    .all(|p| p.extension().is_some_and(|ext| ext == "css"));
+            let change = if css_only {
+                Change::Css
+            } else {
+                Change::Reload
+            };
+            debug!("Files changed ({:?}): {:?}", change, paths);
+            let _ = hub.tx.send(change);
+        }
+    });
+    Ok(watcher)
+}
+
+// SSE：每次变化推送 "reload" 或 "css" 事件
+pub async fn events(
+    State(hub): State<Arc<ChangeHub>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = futures_util::stream::unfold(hub.subscribe(), |mut rx| async move {
+        let name = match rx.recv().await {
+            Ok(Change::Css) => "css",
+            // 错过了部分通知时整页刷新
+            Ok(Change::Reload) | Err(broadcast::error::RecvError::Lagged(_)) => "reload",
+            Ok(Change::Shutdown) | Err(broadcast::error::RecvError::Closed) => return None,
+        };
+        Some((Ok(Event::default().event(name).data("")), rx))
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+pub async fn script() -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "text/javascript; charset=utf-8"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        SCRIPT,
+    )
+        .into_response()
+}
+
+// 在 HTML 响应的 </body> 前插入刷新脚本；开发模式下不使用预压缩，保证正文可以改写
+pub async fn inject(mut req: Request, next: Next) -> Response {
+    req.headers_mut().remove(header::ACCEPT_ENCODING);
+    let response = next.run(req).await;
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    if response.status() != StatusCode::OK
+        || !is_html
+        || response.headers().contains_key(header::CONTENT_ENCODING)
+    {
+        return response;
+    }
+    let tag = format!("<script src=\"{}\"></script>", SCRIPT_PATH);
+    let (mut parts, body) = response.into_parts();
+    let length = parts
+        .headers
+        .remove(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<usize>().ok());
+    parts.headers.remove(header::ETAG);
+    // HEAD 响应没有正文，Content-Length 按插入脚本后的长度计算
+    if body.size_hint().exact() == Some(0) {
+        if let Some(length) = length.filter(|length| *length > 0) {
+            parts.headers.insert(
+                header::CONTENT_LENGTH,
+                HeaderValue::from(length + tag.len()),
+            );
+        }
+        return Response::from_parts(parts, body);
+    }
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Failed to read HTML body for live reload: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let html = String::from_utf8_lossy(&bytes);
+    let injected = match html.rfind("</body>") {
+        Some(pos) => format!("{}{}{}", &html[..pos], tag, &html[pos..]),
+        None => format!("{}{}", html, tag),
+    };
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(injected.len()));
+    Response::from_parts(parts, Body::from(injected))
+}
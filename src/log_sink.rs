@@ -0,0 +1,287 @@
+// 日志输出（[logging]）：默认写到标准输出，也可以改为本机 syslog（/dev/log，RFC 3164）
+// 或 journald 原生协议（带结构化字段），用于无法收集文件与标准输出的部署环境；后两者仅 Unix
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogOutput {
+    #[default]
+    Stdout,
+    Syslog,
+    Journald,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct LoggingConfig {
+    // stdout（默认）、syslog 或 journald
+    #[serde(default)]
+    pub output: LogOutput,
+    // syslog 与 journald 的程序标识（SYSLOG_IDENTIFIER）
+    #[serde(default = "default_ident")]
+    pub ident: String,
+    // syslog facility，如 daemon、local0
+    #[serde(default = "default_facility")]
+    pub syslog_facility: String,
+    // syslog 的 Unix 数据报套接字
+    #[serde(default = "default_syslog_socket")]
+    pub syslog_socket: String,
+}
+
+fn default_ident() -> String {
+    "sonic-wave".to_string()
+}
+
+fn default_facility() -> String {
+    "daemon".to_string()
+}
+
+fn default_syslog_socket() -> String {
+    "/dev/log".to_string()
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            output: LogOutput::default(),
+            ident: default_ident(),
+            syslog_facility: default_facility(),
+            syslog_socket: default_syslog_socket(),
+        }
+    }
+}
+
+/// 把日志写入 syslog 或 journald 的 tracing 层
+#[cfg(unix)]
+pub use unix::LogSink;
+
+/// 按 [logging] 创建日志层；output = "stdout" 时返回 None
+#[cfg(unix)]
+pub fn open(config: &LoggingConfig) -> Result<Option<LogSink>, String> {
+    unix::open(config)
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::{LogOutput, LoggingConfig};
+    use std::fmt::Write as _;
+    use std::os::unix::net::UnixDatagram;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Level, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer};
+    use tracing_subscriber::registry::LookupSpan;
+
+    const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+    enum Target {
+        // facility 已乘以 8，与严重级别相加得到 PRI
+        Syslog { facility: u8 },
+        Journald,
+    }
+
+    pub struct LogSink {
+        socket: UnixDatagram,
+        target: Target,
+        ident: String,
+        pid: u32,
+    }
+
+    pub fn open(config: &LoggingConfig) -> Result<Option<LogSink>, String> {
+        let (target, path) = match config.output {
+            LogOutput::Stdout => return Ok(None),
+            LogOutput::Syslog => {
+                let facility = facility(&config.syslog_facility).ok_or_else(|| {
+                    format!("unknown syslog facility `{}`", config.syslog_facility)
+                })?;
+                (
+                    Target::Syslog {
+                        facility: facility << 3,
+                    },
+                    config.syslog_socket.as_str(),
+                )
+            }
+            LogOutput::Journald => (Target::Journald, JOURNALD_SOCKET),
+        };
+        let socket =
+            UnixDatagram::unbound().map_err(|e| format!("failed to create log socket: {}", e))?;
+        socket
+            .connect(path)
+            .map_err(|e| format!("cannot connect to {}: {}", path, e))?;
+        Ok(Some(LogSink {
+            socket,
+            target,
+            ident: config.ident.clone(),
+            pid: std::process::id(),
+        }))
+    }
+
+    fn facility(name: &str) -> Option<u8> {
+        let code = match name {
+            "kern" => 0,
+            "user" => 1,
+            "mail" => 2,
+            "daemon" => 3,
+            "auth" => 4,
+            "syslog" => 5,
+            "lpr" => 6,
+            "news" => 7,
+            "uucp" => 8,
+            "cron" => 9,
+            "authpriv" => 10,
+            "ftp" => 11,
+            _ => {
+                let n: u8 = name.strip_prefix("local")?.parse().ok()?;
+                return (n < 8).then_some(16 + n);
+            }
+        };
+        Some(code)
+    }
+
+    fn severity(level: &Level) -> u8 {
+        match *level {
+            Level::ERROR => 3,
+            Level::WARN => 4,
+            Level::INFO => 6,
+            _ => 7,
+        }
+    }
+
+    // 事件或 span 的字段：消息单独保存，其余按出现顺序
+    #[derive(Default)]
+    struct Fields {
+        message: String,
+        fields: Vec<(&'static str, String)>,
+    }
+
+    impl Visit for Fields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "message" {
+                self.message.push_str(value);
+            } else {
+                self.fields.push((field.name(), value.to_string()));
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                let _ = write!(self.message, "{:?}", value);
+            } else {
+                self.fields.push((field.name(), format!("{:?}", value)));
+            }
+        }
+    }
+
+    impl LogSink {
+        // 与控制台输出相同：消息在前，其余字段以 key=value 追加
+        fn syslog(&self, facility: u8, level: &Level, fields: &[(&str, String)], message: &str) {
+            let mut line = format!(
+                "<{}>{}[{}]: {}",
+                facility + severity(level),
+                self.ident,
+                self.pid,
+                message
+            );
+            for (name, value) in fields {
+                let _ = write!(line, " {}={}", name, value);
+            }
+            let _ = self.socket.send(line.as_bytes());
+        }
+
+        fn journald(&self, event: &Event<'_>, fields: &[(&str, String)], message: &str) {
+            let metadata = event.metadata();
+            let mut datagram = Vec::new();
+            append(&mut datagram, "MESSAGE", message);
+            append(
+                &mut datagram,
+                "PRIORITY",
+                &severity(metadata.level()).to_string(),
+            );
+            append(&mut datagram, "SYSLOG_IDENTIFIER", &self.ident);
+            append(&mut datagram, "SYSLOG_PID", &self.pid.to_string());
+            append(&mut datagram, "TARGET", metadata.target());
+            if let Some(file) = metadata.file() {
+                append(&mut datagram, "CODE_FILE", file);
+            }
+            if let Some(line) = metadata.line() {
+                append(&mut datagram, "CODE_LINE", &line.to_string());
+            }
+            for (name, value) in fields {
+                if let Some(name) = field_name(name) {
+                    append(&mut datagram, &name, value);
+                }
+            }
+            // 超过数据报上限（通常约 200 KiB）的日志直接丢弃
+            let _ = self.socket.send(&datagram);
+        }
+    }
+
+    // journald 字段名只能由大写字母、数字与下划线组成，且不能以下划线开头
+    fn field_name(name: &str) -> Option<String> {
+        let name: String = name
+            .chars()
+            .map(|c| match c {
+                'a'..='z' => c.to_ascii_uppercase(),
+                'A'..='Z' | '0'..='9' => c,
+                _ => '_',
+            })
+            .collect();
+        let name = name.trim_start_matches(|c: char| c == '_' || c.is_ascii_digit());
+        (!name.is_empty()).then(|| name.to_string())
+    }
+
+    // 值中含换行时使用二进制格式：字段名、换行、8 字节小端长度、值、换行
+    fn append(datagram: &mut Vec<u8>, name: &str, value: &str) {
+        datagram.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            datagram.push(b'\n');
+            datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            datagram.push(b'=');
+        }
+        datagram.extend_from_slice(value.as_bytes());
+        datagram.push(b'\n');
+    }
+
+    impl<S> Layer<S> for LogSink
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        // span 的字段（请求 ID、客户端地址等）附加到其中的每条日志
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(fields);
+            }
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.span(id) {
+                if let Some(fields) = span.extensions_mut().get_mut::<Fields>() {
+                    values.record(fields);
+                }
+            }
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let mut own = Fields::default();
+            event.record(&mut own);
+            let mut fields = Vec::new();
+            if let Some(scope) = ctx.event_scope(event) {
+                for span in scope.from_root() {
+                    if let Some(span_fields) = span.extensions().get::<Fields>() {
+                        fields.extend(span_fields.fields.iter().cloned());
+                    }
+                }
+            }
+            fields.append(&mut own.fields);
+            match self.target {
+                Target::Syslog { facility } => {
+                    self.syslog(facility, event.metadata().level(), &fields, &own.message)
+                }
+                Target::Journald => self.journald(event, &fields, &own.message),
+            }
+        }
+    }
+}
@@ -0,0 +1,568 @@
+// 登录页与 cookie 会话认证（[login]）：访问受保护的前缀时跳转到内置登录页，登录后以 HMAC 签名的 cookie 保持会话，
+// 比 Basic 认证的浏览器弹窗更适合把预览分享给非技术人员。用户与 PBKDF2 密码哈希写在配置或单独的文件中，
+// 哈希用 `sonic-wave hash-password` 生成；修改某个用户的密码后，该用户已有的会话随之失效
+use crate::basic_auth;
+use crate::cli::HashPasswordArgs;
+use crate::cookies;
+use crate::forwarded::ClientInfo;
+use crate::glob::PathPattern;
+use crate::site;
+use axum::extract::{FromRequest, Request, State};
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Response};
+use axum::Form;
+use base64::engine::general_purpose::{STANDARD_NO_PAD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+const SCHEME: &str = "pbkdf2-sha256";
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct LoginConfig {
+    // 需要登录的路径前缀
+    #[serde(default = "default_prefixes")]
+    pub prefixes: Vec<String>,
+    // 前缀下无需登录的路径（模式规则与 deny 相同），如 "/favicon.ico"
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // 用户名 -> 密码哈希
+    #[serde(default, serialize_with = "redact_users")]
+    pub users: BTreeMap<String, String>,
+    // 每行 "用户名:密码哈希" 的文件，# 开头为注释；与 users 合并，启动时读取
+    #[serde(default)]
+    pub users_file: Option<String>,
+    // 签名 cookie 的密钥；未设置时每次启动随机生成，重启后需要重新登录
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub secret: Option<String>,
+    #[serde(default = "default_session_ttl")]
+    pub session_ttl_secs: u64,
+    #[serde(default = "default_cookie_name")]
+    pub cookie_name: String,
+    #[serde(default = "default_login_path")]
+    pub login_path: String,
+    #[serde(default = "default_logout_path")]
+    pub logout_path: String,
+    // 登录页标题
+    #[serde(default = "default_title")]
+    pub title: String,
+}
+
+fn default_prefixes() -> Vec<String> {
+    vec!["/".to_string()]
+}
+
+fn default_session_ttl() -> u64 {
+    7 * 24 * 3600
+}
+
+fn default_cookie_name() -> String {
+    "sonicwave_session".to_string()
+}
+
+fn default_login_path() -> String {
+    "/__login".to_string()
+}
+
+fn default_logout_path() -> String {
+    "/__logout".to_string()
+}
+
+fn default_title() -> String {
+    "Sign in".to_string()
+}
+
+fn redact_users<S: serde::Serializer>(
+    users: &BTreeMap<String, String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeMap;
+    let mut map = serializer.serialize_map(Some(users.len()))?;
+    for name in users.keys() {
+        map.serialize_entry(name, crate::config::REDACTED)?;
+    }
+    map.end()
+}
+
+// "$pbkdf2-sha256$i=600000$<salt>$<hash>"，salt 与 hash 为不带填充的 base64
+struct PasswordHash {
+    iterations: u32,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+    // 原文参与会话签名，密码修改后旧会话失效
+    encoded: String,
+}
+
+impl PasswordHash {
+    fn parse(encoded: &str) -> Option<PasswordHash> {
+        let mut parts = encoded.strip_prefix('$')?.split('$');
+        if parts.next()? != SCHEME {
+            return None;
+        }
+        let iterations = parts.next()?.strip_prefix("i=")?.parse().ok()?;
+        let salt = STANDARD_NO_PAD.decode(parts.next()?).ok()?;
+        let hash = STANDARD_NO_PAD.decode(parts.next()?).ok()?;
+        if parts.next().is_some() || iterations == 0 || hash.len() != 32 {
+            return None;
+        }
+        Some(PasswordHash {
+            iterations,
+            salt,
+            hash,
+            encoded: encoded.to_string(),
+        })
+    }
+
+    fn verify(&self, password: &str) -> bool {
+        let derived = pbkdf2(password.as_bytes(), &self.salt, self.iterations);
+        crate::upload::constant_time_eq(&derived, &self.hash)
+    }
+}
+
+// PBKDF2-HMAC-SHA256，输出一个 32 字节的块
+fn pbkdf2(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let prf = Hmac::<Sha256>::new_from_slice(password).expect("HMAC accepts any key length");
+    let mut mac = prf.clone();
+    mac.update(salt);
+    mac.update(&1u32.to_be_bytes());
+    let mut block = mac.finalize().into_bytes();
+    let mut output: [u8; 32] = block.into();
+    for _ in 1..iterations {
+        let mut mac = prf.clone();
+        mac.update(&block);
+        block = mac.finalize().into_bytes();
+        for (out, byte) in output.iter_mut().zip(block.iter()) {
+            *out ^= byte;
+        }
+    }
+    output
+}
+
+pub fn random_bytes(len: usize) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![0u8; len];
+    tokio_rustls::rustls::crypto::ring::default_provider()
+        .secure_random
+        .fill(&mut bytes)
+        .map_err(|_| "cannot read random bytes from the operating system".to_string())?;
+    Ok(bytes)
+}
+
+pub fn hash_password(password: &str, iterations: u32) -> Result<String, String> {
+    let salt = random_bytes(16)?;
+    let hash = pbkdf2(password.as_bytes(), &salt, iterations);
+    Ok(format!(
+        "${}$i={}${}${}",
+        SCHEME,
+        iterations,
+        STANDARD_NO_PAD.encode(&salt),
+        STANDARD_NO_PAD.encode(hash)
+    ))
+}
+
+// sonic-wave hash-password：从标准输入读一行密码，输出可写入 users 的哈希
+pub fn run(args: HashPasswordArgs) {
+    if args.iterations == 0 {
+        eprintln!("--iterations must be at least 1");
+        std::process::exit(2);
+    }
+    if std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        eprintln!("Password (input is echoed):");
+    }
+    let mut password = String::new();
+    if let Err(e) = std::io::stdin().lock().read_line(&mut password) {
+        eprintln!("Failed to read password: {}", e);
+        std::process::exit(1);
+    }
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        eprintln!("Password must not be empty");
+        std::process::exit(2);
+    }
+    match hash_password(password, args.iterations) {
+        Ok(hash) => println!("{}", hash),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    next: String,
+}
+
+pub struct Login {
+    prefixes: Vec<String>,
+    exclude: Vec<PathPattern>,
+    users: HashMap<String, PasswordHash>,
+    // 未知用户名同样计算一次哈希，响应时间不泄露用户是否存在
+    dummy: PasswordHash,
+    key: Vec<u8>,
+    ttl: u64,
+    cookie_name: String,
+    login_path: String,
+    logout_path: String,
+    readiness_path: String,
+    title: String,
+}
+
+impl Login {
+    pub fn new(config: &LoginConfig, readiness_path: &str) -> Result<Self, String> {
+        let mut entries: Vec<(String, String)> = config
+            .users
+            .iter()
+            .map(|(name, hash)| (name.clone(), hash.clone()))
+            .collect();
+        if let Some(path) = &config.users_file {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| format!("cannot read [login] users_file {}: {}", path, e))?;
+            for line in content.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let (name, hash) = line
+                    .split_once(':')
+                    .ok_or_else(|| format!("{}: expected `name:hash`, got `{}`", path, line))?;
+                entries.push((name.trim().to_string(), hash.trim().to_string()));
+            }
+        }
+        let mut users = HashMap::new();
+        for (name, hash) in entries {
+            if name.is_empty() {
+                return Err("[login] user names must not be empty".to_string());
+            }
+            let parsed = PasswordHash::parse(&hash).ok_or_else(|| {
+                format!(
+                    "[login] password for `{}` is not a {} hash; generate one with `sonic-wave hash-password`",
+                    name, SCHEME
+                )
+            })?;
+            users.insert(name, parsed);
+        }
+        if users.is_empty() {
+            return Err("[login] needs at least one user in users or users_file".to_string());
+        }
+        for path in [&config.login_path, &config.logout_path] {
+            if !path.starts_with('/') {
+                return Err(format!("[login] `{}` must start with /", path));
+            }
+        }
+        if config.cookie_name.is_empty()
+            || !config
+                .cookie_name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+        {
+            return Err(format!(
+                "[login] invalid cookie_name `{}`",
+                config.cookie_name
+            ));
+        }
+        let key = match &config.secret {
+            Some(secret) if secret.len() < 16 => {
+                return Err("[login] secret must be at least 16 characters".to_string());
+            }
+            Some(secret) => secret.as_bytes().to_vec(),
+            // 每个工作进程各自生成的密钥无法验证其他进程签发的 cookie
+            None if crate::supervisor::worker_id().is_some() => {
+                return Err("[login] secret is required in supervisor mode".to_string());
+            }
+            None => {
+                warn!("[login] secret is not set, sessions end when the server restarts");
+                random_bytes(32)?
+            }
+        };
+        let exclude = config
+            .exclude
+            .iter()
+            .map(|pattern| PathPattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let dummy = PasswordHash {
+            iterations: users
+                .values()
+                .map(|user| user.iterations)
+                .max()
+                .unwrap_or(1),
+            salt: random_bytes(16)?,
+            hash: random_bytes(32)?,
+            encoded: String::new(),
+        };
+        Ok(Login {
+            prefixes: config.prefixes.clone(),
+            exclude,
+            users,
+            dummy,
+            key,
+            ttl: config.session_ttl_secs,
+            cookie_name: config.cookie_name.clone(),
+            login_path: config.login_path.clone(),
+            logout_path: config.logout_path.clone(),
+            readiness_path: readiness_path.to_string(),
+            title: config.title.clone(),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} users on {:?}", self.users.len(), self.prefixes)
+    }
+
+    fn protected(&self, path: &str) -> bool {
+        let path = site::normalize_path(path);
+        if path == self.readiness_path
+            || !self
+                .prefixes
+                .iter()
+                .any(|prefix| path.starts_with(prefix.as_str()))
+        {
+            return false;
+        }
+        !self.exclude.iter().any(|pattern| pattern.matches(&path))
+    }
+
+    fn sign(&self, payload: &str, user: &PasswordHash) -> Vec<u8> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        mac.update(b"\n");
+        mac.update(user.encoded.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    // "<base64url 用户名>.<过期时间>.<签名>"
+    fn issue(&self, name: &str) -> String {
+        let expires = unix_now() + self.ttl;
+        let payload = format!("{}.{}", URL_SAFE_NO_PAD.encode(name), expires);
+        let signature = self.sign(&payload, &self.users[name]);
+        format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    fn session(&self, headers: &HeaderMap) -> Option<String> {
+        let value = cookies::get(headers, &self.cookie_name)?;
+        let (payload, signature) = value.rsplit_once('.')?;
+        let (name, expires) = payload.split_once('.')?;
+        if expires.parse::<u64>().ok()? <= unix_now() {
+            return None;
+        }
+        let name = String::from_utf8(URL_SAFE_NO_PAD.decode(name).ok()?).ok()?;
+        let user = self.users.get(&name)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        crate::upload::constant_time_eq(&self.sign(payload, user), &signature).then_some(name)
+    }
+
+    fn set_cookie(&self, value: &str, max_age: u64, https: bool) -> HeaderValue {
+        let secure = if https { "; Secure" } else { "" };
+        let cookie = format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{}",
+            self.cookie_name, value, max_age, secure
+        );
+        HeaderValue::from_str(&cookie).expect("cookie values are ASCII")
+    }
+
+    fn page(&self, status: StatusCode, next: &str, error: Option<&str>) -> Response {
+        let error = error
+            .map(|e| format!("<p class=\"error\">{}</p>\n", escape(e)))
+            .unwrap_or_default();
+        let html = format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+body{{font-family:system-ui,sans-serif;max-width:20rem;margin:15vh auto;padding:0 1rem;color:#333}}
+label{{display:block;margin:.75rem 0 .25rem}}
+input{{box-sizing:border-box;width:100%;padding:.5rem;font-size:1rem}}
+button{{margin-top:1rem;width:100%;padding:.6rem;font-size:1rem}}
+.error{{color:#b00020}}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{error}<form method="post" action="{action}">
+<input type="hidden" name="next" value="{next}">
+<label for="username">Username</label>
+<input id="username" name="username" autocomplete="username" required autofocus>
+<label for="password">Password</label>
+<input id="password" name="password" type="password" autocomplete="current-password" required>
+<button type="submit">Sign in</button>
+</form>
+</body>
+</html>
+"#,
+            title = escape(&self.title),
+            error = error,
+            action = escape(&self.login_path),
+            next = escape(next),
+        );
+        no_store((status, Html(html)).into_response())
+    }
+
+    async fn submit(self: &Arc<Self>, req: Request) -> Response {
+        let https = is_https(&req);
+        let client = req
+            .extensions()
+            .get::<ClientInfo>()
+            .and_then(|info| info.ip)
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let Ok(Form(form)) = Form::<LoginForm>::from_request(req, &()).await else {
+            return self.page(
+                StatusCode::BAD_REQUEST,
+                "/",
+                Some("Invalid sign-in request."),
+            );
+        };
+        let next = safe_next(&form.next);
+        let login = self.clone();
+        let username = form.username.clone();
+        let verified = tokio::task::spawn_blocking(move || match login.users.get(&username) {
+            Some(user) => user.verify(&form.password),
+            None => {
+                login.dummy.verify(&form.password);
+                false
+            }
+        })
+        .await
+        .unwrap_or(false);
+        if !verified {
+            warn!("Failed sign-in for `{}` from {}", form.username, client);
+            return self.page(
+                StatusCode::UNAUTHORIZED,
+                next,
+                Some("Incorrect username or password."),
+            );
+        }
+        info!("`{}` signed in from {}", form.username, client);
+        let cookie = self.set_cookie(&self.issue(&form.username), self.ttl, https);
+        let mut response = redirect(next);
+        response.headers_mut().insert(header::SET_COOKIE, cookie);
+        response
+    }
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn is_https(req: &Request) -> bool {
+    req.extensions()
+        .get::<ClientInfo>()
+        .is_some_and(|info| info.scheme == "https")
+}
+
+// 只跳转到本站路径，"//evil.example" 与 "/\evil.example" 会被浏览器当作其他站点
+pub fn safe_next(next: &str) -> &str {
+    if next.starts_with('/') && !next.starts_with("//") && !next.starts_with("/\\") {
+        next
+    } else {
+        "/"
+    }
+}
+
+pub fn redirect(location: &str) -> Response {
+    let mut response = StatusCode::SEE_OTHER.into_response();
+    if let Ok(value) = HeaderValue::from_str(location) {
+        response.headers_mut().insert(header::LOCATION, value);
+    }
+    no_store(response)
+}
+
+pub fn no_store(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+// 浏览器的页面请求跳转到登录页，其余请求（fetch、脚本等）返回 401
+pub fn wants_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+pub async fn check(State(login): State<Arc<Login>>, req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    if path == login.login_path {
+        return match *req.method() {
+            Method::GET | Method::HEAD => {
+                let next = req
+                    .uri()
+                    .query()
+                    .and_then(|query| form_value(query, "next").filter(|next| !next.is_empty()))
+                    .unwrap_or_else(|| "/".to_string());
+                login.page(StatusCode::OK, safe_next(&next), None)
+            }
+            Method::POST => login.submit(req).await,
+            _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+        };
+    }
+    if path == login.logout_path {
+        let mut response = redirect(&login.login_path);
+        let cookie = login.set_cookie("", 0, is_https(&req));
+        response.headers_mut().insert(header::SET_COOKIE, cookie);
+        return response;
+    }
+    if !login.protected(path) {
+        return next.run(req).await;
+    }
+    if login.session(req.headers()).is_some() {
+        let mut response = next.run(req).await;
+        basic_auth::private(response.headers_mut());
+        return response;
+    }
+    if matches!(*req.method(), Method::GET | Method::HEAD) && wants_html(req.headers()) {
+        let target = req
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/");
+        return redirect(&format!(
+            "{}?next={}",
+            login.login_path,
+            utf8_percent_encode(target, NON_ALPHANUMERIC)
+        ));
+    }
+    no_store(StatusCode::UNAUTHORIZED.into_response())
+}
+
+pub fn form_value(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| {
+            percent_decode_str(&value.replace('+', " "))
+                .decode_utf8_lossy()
+                .into_owned()
+        })
+}
@@ -0,0 +1,254 @@
+// 维护模式（[maintenance]）：开启后所有请求返回 503 维护页并带 Retry-After，部署期间用户不会拿到更新了一半的资源。
+// 可以在配置中直接开启，也可以通过 Bearer token 保护的端点切换；白名单中的 IP 与路径照常访问。
+// 状态只保存在当前进程，supervisor 模式下每个工作进程各自切换
+use crate::forwarded::ClientInfo;
+use crate::glob::PathPattern;
+use crate::login;
+use crate::upload;
+use axum::extract::{Query, Request, State};
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use ipnet::IpNet;
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::info;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct MaintenanceConfig {
+    // 启动时即处于维护模式
+    #[serde(default)]
+    pub enabled: bool,
+    // 维护页（HTML 文件），启动时读入内存；未设置时使用内置页面
+    #[serde(default)]
+    pub page: Option<String>,
+    #[serde(default = "default_retry_after")]
+    pub retry_after_secs: u64,
+    // 照常访问的客户端 IP 或网段，如 "10.0.0.0/8"
+    #[serde(default)]
+    pub allow_ips: Vec<String>,
+    // 照常访问的路径（模式规则与 deny 相同），如 "/api/health"
+    #[serde(default)]
+    pub allow_paths: Vec<String>,
+    // 切换端点的 Bearer token，未设置时不启用切换端点
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub token: Option<String>,
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_retry_after() -> u64 {
+    300
+}
+
+fn default_path() -> String {
+    "/__maintenance".to_string()
+}
+
+const DEFAULT_PAGE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Down for maintenance</title>
+<style>body{font-family:system-ui,sans-serif;max-width:32rem;margin:20vh auto;padding:0 1rem;color:#333;text-align:center}</style>
+</head>
+<body>
+<h1>Down for maintenance</h1>
+<p>We are updating the site and will be back shortly.</p>
+</body>
+</html>
+"#;
+
+struct Mode {
+    enabled: bool,
+    since: SystemTime,
+}
+
+pub struct Maintenance {
+    page: String,
+    retry_after: HeaderValue,
+    allow_ips: Vec<IpNet>,
+    allow_paths: Vec<PathPattern>,
+    // 切换端点与就绪检查始终放行
+    exempt: Vec<String>,
+    token: Option<String>,
+    path: String,
+    mode: Mutex<Mode>,
+}
+
+impl Maintenance {
+    pub fn new(config: &MaintenanceConfig, readiness_path: &str) -> Result<Self, String> {
+        let page = match &config.page {
+            Some(path) => std::fs::read_to_string(path)
+                .map_err(|e| format!("cannot read maintenance page {}: {}", path, e))?,
+            None => DEFAULT_PAGE.to_string(),
+        };
+        let allow_ips = config
+            .allow_ips
+            .iter()
+            .map(|entry| {
+                let entry = entry.trim();
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("[maintenance] invalid allow_ips entry `{}`", entry))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let allow_paths = config
+            .allow_paths
+            .iter()
+            .map(|pattern| PathPattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !config.path.starts_with('/') {
+            return Err("[maintenance] path must start with /".to_string());
+        }
+        if config.token.as_deref() == Some("") {
+            return Err("[maintenance] token must not be empty".to_string());
+        }
+        let mut exempt = vec![readiness_path.to_string()];
+        if config.token.is_some() {
+            exempt.push(config.path.clone());
+        }
+        Ok(Maintenance {
+            page,
+            retry_after: HeaderValue::from(config.retry_after_secs),
+            allow_ips,
+            allow_paths,
+            exempt,
+            token: config.token.clone(),
+            path: config.path.clone(),
+            mode: Mutex::new(Mode {
+                enabled: config.enabled,
+                since: SystemTime::now(),
+            }),
+        })
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.token.as_ref().map(|_| self.path.as_str())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.mode.lock().unwrap().enabled
+    }
+
+    fn set(&self, enabled: bool) -> bool {
+        let mut mode = self.mode.lock().unwrap();
+        if mode.enabled == enabled {
+            return false;
+        }
+        mode.enabled = enabled;
+        mode.since = SystemTime::now();
+        info!(
+            "Maintenance mode {}",
+            if enabled { "enabled" } else { "disabled" }
+        );
+        true
+    }
+
+    fn allowed(&self, path: &str, ip: Option<IpAddr>) -> bool {
+        if self.exempt.iter().any(|exempt| exempt == path) {
+            return true;
+        }
+        let decoded = percent_decode_str(path).decode_utf8_lossy();
+        if self
+            .allow_paths
+            .iter()
+            .any(|pattern| pattern.matches(&decoded))
+        {
+            return true;
+        }
+        // IPv4-mapped IPv6 地址按 IPv4 匹配
+        let ip = ip.map(|ip| match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            ip => ip,
+        });
+        ip.is_some_and(|ip| self.allow_ips.iter().any(|net| net.contains(&ip)))
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        upload::bearer_authorized(headers, self.token.as_deref())
+    }
+
+    fn status(&self) -> serde_json::Value {
+        let mode = self.mode.lock().unwrap();
+        json!({
+            "enabled": mode.enabled,
+            "since": crate::error_pages::rfc3339(mode.since),
+        })
+    }
+}
+
+// 在客户端还原之内，按真实客户端 IP 判断白名单
+pub async fn check(
+    State(maintenance): State<Arc<Maintenance>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !maintenance.enabled() {
+        return next.run(req).await;
+    }
+    let ip = req
+        .extensions()
+        .get::<ClientInfo>()
+        .and_then(|info| info.ip);
+    if maintenance.allowed(req.uri().path(), ip) {
+        return next.run(req).await;
+    }
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/html; charset=utf-8"),
+            ),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-store")),
+            (header::RETRY_AFTER, maintenance.retry_after.clone()),
+        ],
+        maintenance.page.clone(),
+    )
+        .into_response()
+}
+
+// GET 返回当前状态；POST ?enabled=true 开启，?enabled=false 关闭
+pub async fn status(State(maintenance): State<Arc<Maintenance>>, headers: HeaderMap) -> Response {
+    if !maintenance.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    login::no_store(Json(maintenance.status()).into_response())
+}
+
+pub async fn toggle(
+    State(maintenance): State<Arc<Maintenance>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if !maintenance.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    let enabled = match query.get("enabled").map(String::as_str) {
+        Some("true" | "1" | "on") => true,
+        Some("false" | "0" | "off") => false,
+        _ => {
+            return login::no_store(
+                (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": "expected ?enabled=true or ?enabled=false" })),
+                )
+                    .into_response(),
+            )
+        }
+    };
+    let changed = maintenance.set(enabled);
+    let mut status = maintenance.status();
+    status["changed"] = changed.into();
+    login::no_store(Json(status).into_response())
+}
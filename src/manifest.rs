@@ -0,0 +1,180 @@
+// 资源清单：将带哈希的文件名映射回原始文件名，附带大小与 SRI 完整性哈希，
+// 供部署工具与服务端渲染查找资源；manifest 子命令写入文件，[fingerprint] manifest 按请求生成
+use crate::cli::ManifestArgs;
+use crate::fingerprint::{Fingerprint, FingerprintConfig};
+use axum::extract::State;
+use axum::http::header::{self, HeaderValue};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use serde::Serialize;
+use sha2::{Digest, Sha384};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::warn;
+
+// 默认的清单文件名，与 [fingerprint] manifest 的常用路径一致
+const DEFAULT_NAME: &str = "asset-manifest.json";
+
+// 预压缩的旁路文件不单独列出
+const SIDECARS: &[&str] = &["br", "gz", "zst"];
+
+#[derive(Serialize)]
+pub struct Entry {
+    // 相对站点目录的实际文件路径
+    file: String,
+    size: u64,
+    // Subresource Integrity，如 "sha384-..."
+    integrity: String,
+}
+
+// 键为原始文件路径（相对站点目录，"/" 分隔），如 "assets/app.js"
+pub fn build(dir: &Path, fingerprint: &Fingerprint) -> io::Result<BTreeMap<String, Entry>> {
+    let mut files = Vec::new();
+    collect(dir, &mut files)?;
+    let mut manifest = BTreeMap::new();
+    for path in files {
+        let Ok(relative) = path.strip_prefix(dir) else {
+            continue;
+        };
+        let relative: Vec<String> = relative
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let Some((name, parents)) = relative.split_last() else {
+            continue;
+        };
+        let Some(logical) = fingerprint.logical_name(name) else {
+            continue;
+        };
+        let data = fs::read(&path)?;
+        let prefix: String = parents.iter().map(|part| format!("{}/", part)).collect();
+        manifest.insert(
+            format!("{}{}", prefix, logical),
+            Entry {
+                file: format!("{}{}", prefix, name),
+                size: data.len() as u64,
+                integrity: integrity(&data),
+            },
+        );
+    }
+    Ok(manifest)
+}
+
+// Subresource Integrity 的值，sri 子命令与 [sri] 共用
+pub fn integrity(data: &[u8]) -> String {
+    format!(
+        "sha384-{}",
+        base64::engine::general_purpose::STANDARD.encode(Sha384::digest(data))
+    )
+}
+
+// 跳过以 "." 开头的文件与目录以及预压缩旁路文件
+pub fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for item in fs::read_dir(dir)? {
+        let item = item?;
+        let path = item.path();
+        if item.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let file_type = item.file_type()?;
+        if file_type.is_dir() {
+            collect(&path, files)?;
+            continue;
+        }
+        let sidecar = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| SIDECARS.contains(&ext));
+        if file_type.is_file() && !sidecar {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn render(dir: &Path, fingerprint: &Fingerprint) -> io::Result<String> {
+    let manifest = build(dir, fingerprint)?;
+    serde_json::to_string_pretty(&manifest).map_err(io::Error::other)
+}
+
+pub fn run(args: ManifestArgs) {
+    let config = FingerprintConfig {
+        pattern: args
+            .pattern
+            .unwrap_or_else(|| FingerprintConfig::default().pattern),
+        ..FingerprintConfig::default()
+    };
+    let fingerprint = match Fingerprint::new(&config) {
+        Ok(fingerprint) if fingerprint.has_hash_group() => fingerprint,
+        Ok(_) => {
+            eprintln!(
+                "Pattern `{}` has no capture group for the hash",
+                config.pattern
+            );
+            std::process::exit(2);
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let json = match render(&args.dir, &fingerprint) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", args.dir.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let output = args.output.unwrap_or_else(|| args.dir.join(DEFAULT_NAME));
+    if output == Path::new("-") {
+        println!("{}", json);
+        return;
+    }
+    // 先写临时文件再改名，服务中的进程不会读到半个文件
+    let mut temp = output.clone().into_os_string();
+    temp.push(".tmp");
+    if let Err(e) = fs::write(&temp, json).and_then(|_| fs::rename(&temp, &output)) {
+        eprintln!("Failed to write {}: {}", output.display(), e);
+        std::process::exit(1);
+    }
+    println!("Wrote {}", output.display());
+}
+
+pub struct ManifestRoute {
+    pub dir: PathBuf,
+    pub fingerprint: Arc<Fingerprint>,
+}
+
+// 每次请求时重新生成，部署新版本后无需重启
+pub async fn serve(State(route): State<Arc<ManifestRoute>>) -> Response {
+    let rendered = {
+        let route = route.clone();
+        tokio::task::spawn_blocking(move || render(&route.dir, &route.fingerprint)).await
+    };
+    match rendered {
+        Ok(Ok(json)) => (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                ),
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            ],
+            json,
+        )
+            .into_response(),
+        Ok(Err(e)) => {
+            warn!(
+                "Failed to generate asset manifest for {}: {}",
+                route.dir.display(),
+                e
+            );
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -0,0 +1,226 @@
+// Markdown 渲染（[markdown]）：.md 文件渲染为 HTML 并套用模板，?raw=1 时返回源文件
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::percent_decode_str;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    // 内置样式，跟随系统的浅色 / 深色模式
+    #[default]
+    Default,
+    None,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct MarkdownConfig {
+    // HTML 模板文件，可使用 {{title}}、{{content}}、{{style}}、{{path}}；未设置时使用内置模板
+    #[serde(default)]
+    pub template: Option<PathBuf>,
+    #[serde(default)]
+    pub theme: Theme,
+    // 额外引用的样式表 URL，如 "/assets/docs.css"
+    #[serde(default)]
+    pub stylesheet: Option<String>,
+    // 超过该大小的文件按原样返回
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+}
+
+fn default_max_size() -> usize {
+    8 * 1024 * 1024
+}
+
+const DEFAULT_TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{{title}}</title>
+{{style}}
+</head>
+<body>
+<main class=\"markdown-body\">
+{{content}}
+</main>
+</body>
+</html>
+";
+
+const DEFAULT_CSS: &str = ":root{color-scheme:light dark}
+body{margin:0;font:16px/1.6 -apple-system,BlinkMacSystemFont,\"Segoe UI\",Helvetica,Arial,sans-serif;color:#1f2328;background:#fff}
+.markdown-body{max-width:860px;margin:0 auto;padding:32px 24px}
+h1,h2{border-bottom:1px solid #d1d9e0;padding-bottom:.3em}
+a{color:#0969da}
+code,pre{font-family:ui-monospace,SFMono-Regular,Menlo,Consolas,monospace;font-size:85%;background:#f6f8fa;border-radius:6px}
+code{padding:.2em .4em}
+pre{padding:16px;overflow:auto}
+pre code{padding:0;background:none;font-size:100%}
+blockquote{margin:0;padding:0 1em;color:#59636e;border-left:.25em solid #d1d9e0}
+table{border-collapse:collapse}
+th,td{border:1px solid #d1d9e0;padding:6px 13px}
+img{max-width:100%}
+@media (prefers-color-scheme:dark){
+body{color:#e6edf3;background:#0d1117}
+h1,h2,th,td{border-color:#30363d}
+a{color:#4493f8}
+code,pre{background:#151b23}
+blockquote{color:#9198a1;border-color:#30363d}
+}
+";
+
+pub struct Markdown {
+    template: String,
+    style: String,
+    max_size: usize,
+}
+
+impl Markdown {
+    pub fn new(config: &MarkdownConfig) -> Result<Self, String> {
+        let template = match &config.template {
+            Some(path) => std::fs::read_to_string(path).map_err(|e| {
+                format!("failed to read markdown template {}: {}", path.display(), e)
+            })?,
+            None => DEFAULT_TEMPLATE.to_string(),
+        };
+        let mut style = match config.theme {
+            Theme::Default => format!("<style>\n{}</style>", DEFAULT_CSS),
+            Theme::None => String::new(),
+        };
+        if let Some(href) = &config.stylesheet {
+            style.push_str(&format!(
+                "\n<link rel=\"stylesheet\" href=\"{}\">",
+                escape(href)
+            ));
+        }
+        Ok(Markdown {
+            template,
+            style,
+            max_size: config.max_size,
+        })
+    }
+
+    // 标题取第一个标题的文本，没有时使用文件名
+    pub fn render(&self, source: &str, path: &str) -> String {
+        let options = Options::ENABLE_TABLES
+            | Options::ENABLE_FOOTNOTES
+            | Options::ENABLE_STRIKETHROUGH
+            | Options::ENABLE_TASKLISTS
+            | Options::ENABLE_HEADING_ATTRIBUTES;
+        let mut title = None;
+        let mut heading: Option<String> = None;
+        let events = Parser::new_ext(source, options).inspect(|event| match event {
+            Event::Start(Tag::Heading { .. }) if title.is_none() => heading = Some(String::new()),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = &mut heading {
+                    heading.push_str(text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(text) = heading.take() {
+                    title.get_or_insert(text);
+                }
+            }
+            _ => {}
+        });
+        let mut content = String::with_capacity(source.len() * 3 / 2);
+        pulldown_cmark::html::push_html(&mut content, events);
+        let title = title.unwrap_or_else(|| {
+            path.rsplit('/')
+                .next()
+                .unwrap_or(path)
+                .trim_end_matches(".md")
+                .to_string()
+        });
+        // {{content}} 最后替换，正文中的 "{{" 不会被当作占位符
+        self.template
+            .replace("{{title}}", &escape(&title))
+            .replace("{{style}}", &self.style)
+            .replace("{{path}}", &escape(path))
+            .replace("{{content}}", &content)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn raw_requested(query: Option<&str>) -> bool {
+    query.is_some_and(|query| query.split('&').any(|pair| pair == "raw=1"))
+}
+
+// 在 resolve 之后执行，索引文件（如 README.md）改写后的路径同样会被渲染；
+// 渲染结果按 HTML 使用 html_cache_control
+pub async fn respond(
+    markdown: &Markdown,
+    cache_control: &str,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let is_head = req.method() == Method::HEAD;
+    if !(is_head || req.method() == Method::GET)
+        || !req.uri().path().to_ascii_lowercase().ends_with(".md")
+        || raw_requested(req.uri().query())
+    {
+        return next.run(req).await;
+    }
+    let path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    // 渲染需要完整的源文件：不使用 Range 与预压缩的旁路文件，HEAD 按 GET 读取
+    req.headers_mut().remove(header::RANGE);
+    req.headers_mut().remove(header::ACCEPT_ENCODING);
+    if is_head {
+        *req.method_mut() = Method::GET;
+    }
+    let response = next.run(req).await;
+    let too_large = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| len > markdown.max_size);
+    if response.status() != StatusCode::OK || too_large {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, markdown.max_size).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Not rendering {}: {}", path, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let html = markdown.render(&String::from_utf8_lossy(&bytes), &path);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::ACCEPT_RANGES);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    if let Ok(value) = HeaderValue::from_str(cache_control) {
+        parts.headers.insert(header::CACHE_CONTROL, value);
+    }
+    let body = if is_head {
+        // HEAD 响应没有正文，长度与 GET 一致
+        parts
+            .headers
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(html.len()));
+        Body::empty()
+    } else {
+        Body::from(html)
+    };
+    Response::from_parts(parts, body)
+}
@@ -0,0 +1,104 @@
+// mDNS / Bonjour 服务发布：在局域网中将服务器广播为 _http._tcp，其他设备无需输入 IP 即可发现
+use crate::listener::ListenAddr;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const SERVICE_TYPE: &str = "_http._tcp.local.";
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct MdnsConfig {
+    // 服务实例名，显示在发现列表中
+    #[serde(default = "default_name")]
+    pub name: String,
+    // TXT 记录中的 path，浏览器类客户端打开的路径
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_name() -> String {
+    "Sonic Wave".to_string()
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+}
+
+// 为第一个非回环的 TCP 监听发布服务；绑定 0.0.0.0 时自动使用本机所有网卡地址
+pub fn advertise(config: &MdnsConfig, bound: &[ListenAddr]) -> Option<Advertisement> {
+    let addr = bound.iter().find_map(|addr| match addr {
+        ListenAddr::Tcp(addr) if !addr.ip().is_loopback() => Some(*addr),
+        _ => None,
+    });
+    let Some(addr) = addr else {
+        warn!("mDNS advertisement needs a non-loopback TCP listener");
+        return None;
+    };
+
+    // 主机名由实例名生成，A / AAAA 记录由守护进程应答
+    let host: String = config
+        .name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let host_name = format!("{}.local.", host.trim_matches('-').to_ascii_lowercase());
+    let properties = [("path", config.path.as_str())];
+    let service = if addr.ip().is_unspecified() {
+        ServiceInfo::new(
+            SERVICE_TYPE,
+            &config.name,
+            &host_name,
+            "",
+            addr.port(),
+            &properties[..],
+        )
+        .map(ServiceInfo::enable_addr_auto)
+    } else {
+        ServiceInfo::new(
+            SERVICE_TYPE,
+            &config.name,
+            &host_name,
+            addr.ip(),
+            addr.port(),
+            &properties[..],
+        )
+    };
+
+    let result = ServiceDaemon::new().and_then(|daemon| {
+        let service = service?;
+        let fullname = service.get_fullname().to_string();
+        daemon.register(service)?;
+        Ok(Advertisement { daemon, fullname })
+    });
+    match result {
+        Ok(advertisement) => {
+            info!(
+                "Advertising {} on port {} via mDNS",
+                advertisement.fullname,
+                addr.port()
+            );
+            Some(advertisement)
+        }
+        Err(e) => {
+            warn!("Failed to start mDNS advertisement: {}", e);
+            None
+        }
+    }
+}
+
+impl Advertisement {
+    // 发送注销（goodbye）报文后关闭守护进程，其他设备的列表会立即移除该服务
+    pub fn stop(self) {
+        if let Ok(status) = self.daemon.unregister(&self.fullname) {
+            let _ = status.recv_timeout(Duration::from_secs(1));
+        }
+        let _ = self.daemon.shutdown();
+    }
+}
@@ -0,0 +1,192 @@
+// 内存中的只读文件树（内嵌资源等）：路径与 site::fs_path 一致（根目录 + 各段），
+// 由索引完成路径解析，MemoryService 代替 ServeDir 直接返回文件内容
+use crate::index::{self, FileIndex};
+use crate::mmap::{file_response, parse_range};
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::{header, HeaderValue, Method, Response, StatusCode};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tower::Service;
+
+pub struct MemFile {
+    pub data: Bytes,
+    pub modified: Option<SystemTime>,
+    // 内容摘要生成的强 ETag
+    pub etag: Option<HeaderValue>,
+}
+
+pub struct MemoryFs {
+    root: PathBuf,
+    files: HashMap<PathBuf, MemFile>,
+}
+
+impl MemoryFs {
+    // root 只是路径前缀，不对应磁盘目录，如 "<embedded>"
+    pub fn new(root: &str) -> Self {
+        MemoryFs {
+            root: PathBuf::from(root),
+            files: HashMap::new(),
+        }
+    }
+
+    // relative 为 "/" 分隔的相对路径
+    pub fn insert(&mut self, relative: &str, file: MemFile) {
+        if let Some(path) = crate::site::fs_path(&self.root, relative) {
+            self.files.insert(path, file);
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&MemFile> {
+        self.files.get(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.files.values().map(|f| f.data.len()).sum()
+    }
+
+    pub fn index(&self) -> FileIndex {
+        FileIndex::from_files(
+            &self.root,
+            self.files.iter().map(|(path, file)| {
+                let entry =
+                    index::file_entry(file.data.len() as u64, file.modified, file.etag.clone());
+                (path.clone(), entry)
+            }),
+        )
+    }
+}
+
+// 代替 ServeDir 返回内存中的文件；找不到时返回 fallback 文件或 404
+#[derive(Clone)]
+pub struct MemoryService {
+    files: Arc<MemoryFs>,
+    fallback: Option<PathBuf>,
+}
+
+impl MemoryService {
+    pub fn new(files: Arc<MemoryFs>, fallback: Option<&str>) -> Self {
+        let fallback = fallback.and_then(|f| crate::site::fs_path(files.root(), f));
+        MemoryService { files, fallback }
+    }
+
+    fn respond(&self, req: &Request<Body>) -> Response<Body> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+            response
+                .headers_mut()
+                .insert(header::ALLOW, HeaderValue::from_static("GET,HEAD"));
+            return response;
+        }
+        let found = crate::site::fs_path(self.files.root(), req.uri().path())
+            .and_then(|path| Some((self.files.get(&path)?, path)))
+            .or_else(|| {
+                let path = self.fallback.clone()?;
+                Some((self.files.get(&path)?, path))
+            });
+        let Some((file, path)) = found else {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::NOT_FOUND;
+            return response;
+        };
+
+        serve(req, &path, file)
+    }
+}
+
+// 返回一个内存中的文件：处理 If-None-Match / If-Modified-Since、单段 Range 与 HEAD
+pub fn serve<B>(req: &Request<B>, path: &Path, file: &MemFile) -> Response<Body> {
+    let len = file.data.len() as u64;
+    // If-None-Match 存在时忽略 If-Modified-Since
+    let not_modified = match req.headers().get(header::IF_NONE_MATCH) {
+        Some(tags) => file.etag.as_ref().is_some_and(|etag| {
+            tags.to_str()
+                .is_ok_and(|tags| index::etag_matches(tags, etag))
+        }),
+        None => not_modified(req, file.modified),
+    };
+    if not_modified {
+        let mut response = file_response(path, len, file.modified, None);
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        response.headers_mut().remove(header::CONTENT_LENGTH);
+        if let Some(etag) = &file.etag {
+            response.headers_mut().insert(header::ETAG, etag.clone());
+        }
+        return response;
+    }
+    // 多段 Range 由 ranges::multipart 处理，这里按完整内容返回；无法满足的范围返回 416
+    let range = match req.headers().get(header::RANGE) {
+        Some(value) => match parse_range(value, len) {
+            Some(range) => Some(range),
+            None if value.to_str().is_ok_and(|v| v.contains(',')) => None,
+            None => {
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                if let Ok(value) = HeaderValue::from_str(&format!("bytes */{}", len)) {
+                    response.headers_mut().insert(header::CONTENT_RANGE, value);
+                }
+                return response;
+            }
+        },
+        None => None,
+    };
+    let body = match &range {
+        _ if req.method() == Method::HEAD => Bytes::new(),
+        Some(range) => file.data.slice(range.start as usize..range.end as usize),
+        None => file.data.clone(),
+    };
+    let mut response = file_response(path, len, file.modified, range);
+    if let Some(etag) = &file.etag {
+        response.headers_mut().insert(header::ETAG, etag.clone());
+    }
+    *response.body_mut() = Body::from(body);
+    response
+}
+
+// If-Modified-Since 不早于文件 mtime（按秒比较）
+fn not_modified<B>(req: &Request<B>, modified: Option<SystemTime>) -> bool {
+    let Some(modified) = modified else {
+        return false;
+    };
+    let Some(since) = req
+        .headers()
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| httpdate::parse_http_date(v).ok())
+    else {
+        return false;
+    };
+    let secs = |t: SystemTime| {
+        t.duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_secs()
+    };
+    secs(modified) <= secs(since)
+}
+
+impl Service<Request<Body>> for MemoryService {
+    type Response = Response<Body>;
+    type Error = Infallible;
+    type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        std::future::ready(Ok(self.respond(&req)))
+    }
+}
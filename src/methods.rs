@@ -0,0 +1,97 @@
+// 请求方法处理：OPTIONS * 返回整个服务器支持的方法；OPTIONS 某个路径时把路由的 405 换成 204，
+// Allow 列出该路由实际接受的方法并加上 OPTIONS；TRACE 一律 405，不交给路由与反向代理的上游
+use crate::config::Config;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::sync::Arc;
+
+pub struct Methods {
+    // OPTIONS * 与 TRACE 响应的 Allow
+    allow: HeaderValue,
+}
+
+impl Methods {
+    pub fn new(config: &Config) -> Self {
+        let mut methods = vec!["OPTIONS", "GET", "HEAD"];
+        if config.webdav {
+            methods.push("PROPFIND");
+        }
+        if let Some(upload) = &config.upload {
+            methods.extend(["PUT", "POST", "DELETE"]);
+            if upload.tus_endpoint.is_some() {
+                methods.push("PATCH");
+            }
+        }
+        Methods {
+            allow: HeaderValue::from_str(&methods.join(", ")).unwrap(),
+        }
+    }
+}
+
+// 在响应已有的 Allow 上追加方法，没有 Allow 时按 GET / HEAD 计算
+pub fn extend_allow(response: &mut Response, extra: &[&str]) {
+    let mut methods = allowed(response);
+    for method in extra {
+        if !methods.iter().any(|m| m == method) {
+            methods.push(method.to_string());
+        }
+    }
+    if let Ok(value) = HeaderValue::from_str(&methods.join(", ")) {
+        response.headers_mut().insert(header::ALLOW, value);
+    }
+}
+
+fn allowed(response: &Response) -> Vec<String> {
+    let mut methods: Vec<String> = response
+        .headers()
+        .get(header::ALLOW)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("GET, HEAD")
+        .split(',')
+        .map(|m| m.trim().to_ascii_uppercase())
+        .filter(|m| !m.is_empty())
+        .collect();
+    if !methods.iter().any(|m| m == "OPTIONS") {
+        methods.insert(0, "OPTIONS".to_string());
+    }
+    methods
+}
+
+pub async fn handle(State(methods): State<Arc<Methods>>, req: Request, next: Next) -> Response {
+    // 防止跨站追踪（XST）读取 Cookie 等请求头
+    if req.method() == Method::TRACE {
+        return (
+            StatusCode::METHOD_NOT_ALLOWED,
+            [(header::ALLOW, methods.allow.clone())],
+        )
+            .into_response();
+    }
+    if req.uri().path() == "*" {
+        if req.method() != Method::OPTIONS {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+        return (
+            StatusCode::NO_CONTENT,
+            [(header::ALLOW, methods.allow.clone())],
+        )
+            .into_response();
+    }
+    if req.method() != Method::OPTIONS {
+        return next.run(req).await;
+    }
+    let response = next.run(req).await;
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+    let allow = allowed(&response).join(", ");
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = StatusCode::NO_CONTENT;
+    if let Ok(value) = HeaderValue::from_str(&allow) {
+        response.headers_mut().insert(header::ALLOW, value);
+    }
+    response
+}
@@ -0,0 +1,707 @@
+// 进程内请求统计，以及按内容类别与缓存结果划分的响应耗时直方图
+use crate::listener::PeerAddr;
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::{Extensions, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+// metrics_endpoint = true 时以 Prometheus 文本格式导出
+pub const METRICS_PATH: &str = "/__metrics";
+
+// 直方图桶的上界（秒），最后还有一个 +Inf 桶
+const BUCKETS: [f64; 14] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+// 响应的内容类别，按 Content-Type 划分
+#[derive(Clone, Copy)]
+pub enum ContentClass {
+    Html,
+    Asset,
+    Media,
+    Other,
+}
+
+impl ContentClass {
+    pub const ALL: [ContentClass; 4] = [
+        ContentClass::Html,
+        ContentClass::Asset,
+        ContentClass::Media,
+        ContentClass::Other,
+    ];
+
+    fn of(headers: &HeaderMap) -> Self {
+        let Some(content_type) = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+        else {
+            return ContentClass::Other;
+        };
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        match mime.as_str() {
+            "text/html" | "application/xhtml+xml" => ContentClass::Html,
+            "text/css" | "text/javascript" | "application/javascript" => ContentClass::Asset,
+            "application/ogg" | "application/vnd.apple.mpegurl" | "application/x-mpegurl" => {
+                ContentClass::Media
+            }
+            _ if mime.starts_with("audio/") || mime.starts_with("video/") => ContentClass::Media,
+            _ => ContentClass::Other,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ContentClass::Html => "html",
+            ContentClass::Asset => "js_css",
+            ContentClass::Media => "media",
+            ContentClass::Other => "other",
+        }
+    }
+}
+
+// 内存缓存的查找结果；未经过缓存（未配置 [cache] 或不可缓存的请求）时为 None
+#[derive(Clone, Copy)]
+pub enum CacheResult {
+    None,
+    Hit,
+    Miss,
+}
+
+impl CacheResult {
+    pub const ALL: [CacheResult; 3] = [CacheResult::Hit, CacheResult::Miss, CacheResult::None];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CacheResult::None => "none",
+            CacheResult::Hit => "hit",
+            CacheResult::Miss => "miss",
+        }
+    }
+}
+
+// 由 track 放入请求扩展，缓存层写入查找结果
+#[derive(Clone, Default)]
+pub struct CacheLookup(Arc<AtomicU8>);
+
+impl CacheLookup {
+    pub fn mark(extensions: &Extensions, hit: bool) {
+        if let Some(lookup) = extensions.get::<CacheLookup>() {
+            lookup.0.store(if hit { 1 } else { 2 }, Ordering::Relaxed);
+        }
+    }
+
+    fn result(&self) -> CacheResult {
+        match self.0.load(Ordering::Relaxed) {
+            1 => CacheResult::Hit,
+            2 => CacheResult::Miss,
+            _ => CacheResult::None,
+        }
+    }
+}
+
+pub struct Histogram {
+    buckets: [AtomicU64; BUCKETS.len() + 1],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Histogram {
+            buckets: [const { AtomicU64::new(0) }; BUCKETS.len() + 1],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, secs: f64) {
+        let index = BUCKETS
+            .iter()
+            .position(|bound| secs <= *bound)
+            .unwrap_or(BUCKETS.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add((secs * 1_000_000.0) as u64, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub fn mean(&self) -> f64 {
+        let count = self.count();
+        if count == 0 {
+            return 0.0;
+        }
+        self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0 / count as f64
+    }
+
+    // 分位数所在桶的上界；落在 +Inf 桶时返回 None
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let count = self.count();
+        let target = ((count as f64 * q).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= target {
+                return BUCKETS.get(i).copied();
+            }
+        }
+        None
+    }
+}
+
+// 反向代理上游的统计，按上游地址各一份，重新加载配置后沿用
+pub struct UpstreamStats {
+    pub url: String,
+    pub requests: AtomicU64,
+    pub failures: AtomicU64,
+    pub ejections: AtomicU64,
+    // 正在进行的请求数（到响应体发送完毕），least-connections 按它选择
+    pub active: AtomicU64,
+    pub healthy: AtomicBool,
+}
+
+// 多租户模式下每个租户的统计，删除租户时一并移除
+pub struct TenantStats {
+    pub name: String,
+    pub requests: AtomicU64,
+    // 按响应的 Content-Length 累计
+    pub sent_bytes: AtomicU64,
+    pub storage_bytes: AtomicU64,
+    pub quota_bytes: AtomicU64,
+}
+
+pub struct Metrics {
+    requests: AtomicU64,
+    status_2xx: AtomicU64,
+    status_3xx: AtomicU64,
+    status_4xx: AtomicU64,
+    status_5xx: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    negative_cache_hits: AtomicU64,
+    ua_blocked: AtomicU64,
+    path_rejected: AtomicU64,
+    // 压缩结果磁盘缓存的命中、未命中与淘汰次数，以及当前占用的字节数
+    compression_cache_hits: AtomicU64,
+    compression_cache_misses: AtomicU64,
+    compression_cache_evictions: AtomicU64,
+    compression_cache_bytes: AtomicU64,
+    // 当前打开的连接数，以及每个客户端 IP 的连接数
+    connections: AtomicU64,
+    clients: Mutex<BTreeMap<IpAddr, u64>>,
+    // 响应耗时（到响应体发送完毕），按 [内容类别][缓存结果] 划分
+    latency: [[Histogram; 3]; 4],
+    upstreams: Mutex<Vec<Arc<UpstreamStats>>>,
+    tenants: Mutex<Vec<Arc<TenantStats>>>,
+}
+
+pub static METRICS: Metrics = Metrics::new();
+
+impl Metrics {
+    const fn new() -> Self {
+        Metrics {
+            requests: AtomicU64::new(0),
+            status_2xx: AtomicU64::new(0),
+            status_3xx: AtomicU64::new(0),
+            status_4xx: AtomicU64::new(0),
+            status_5xx: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            negative_cache_hits: AtomicU64::new(0),
+            ua_blocked: AtomicU64::new(0),
+            path_rejected: AtomicU64::new(0),
+            compression_cache_hits: AtomicU64::new(0),
+            compression_cache_misses: AtomicU64::new(0),
+            compression_cache_evictions: AtomicU64::new(0),
+            compression_cache_bytes: AtomicU64::new(0),
+            connections: AtomicU64::new(0),
+            clients: Mutex::new(BTreeMap::new()),
+            latency: [const { [const { Histogram::new() }; 3] }; 4],
+            upstreams: Mutex::new(Vec::new()),
+            tenants: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn record(&self, status: StatusCode) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let counter = match status.as_u16() {
+            200..=299 => &self.status_2xx,
+            300..=399 => &self.status_3xx,
+            400..=499 => &self.status_4xx,
+            500..=599 => &self.status_5xx,
+            _ => return,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_cache(&self, hit: bool) {
+        let counter = if hit {
+            &self.cache_hits
+        } else {
+            &self.cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_negative_hit(&self) {
+        self.negative_cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_ua_blocked(&self) {
+        self.ua_blocked.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_path_rejected(&self) {
+        self.path_rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_compression_cache(&self, hit: bool) {
+        let counter = if hit {
+            &self.compression_cache_hits
+        } else {
+            &self.compression_cache_misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_compression_evictions(&self, count: u64) {
+        self.compression_cache_evictions
+            .fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn set_compression_cache_bytes(&self, bytes: u64) {
+        self.compression_cache_bytes.store(bytes, Ordering::Relaxed);
+    }
+
+    pub fn upstream(&self, url: &str) -> Arc<UpstreamStats> {
+        let mut upstreams = self.upstreams.lock().unwrap();
+        if let Some(stats) = upstreams.iter().find(|stats| stats.url == url) {
+            return stats.clone();
+        }
+        let stats = Arc::new(UpstreamStats {
+            url: url.to_string(),
+            requests: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            ejections: AtomicU64::new(0),
+            active: AtomicU64::new(0),
+            healthy: AtomicBool::new(true),
+        });
+        upstreams.push(stats.clone());
+        stats
+    }
+
+    pub fn tenant(&self, name: &str) -> Arc<TenantStats> {
+        let mut tenants = self.tenants.lock().unwrap();
+        if let Some(stats) = tenants.iter().find(|stats| stats.name == name) {
+            return stats.clone();
+        }
+        let stats = Arc::new(TenantStats {
+            name: name.to_string(),
+            requests: AtomicU64::new(0),
+            sent_bytes: AtomicU64::new(0),
+            storage_bytes: AtomicU64::new(0),
+            quota_bytes: AtomicU64::new(0),
+        });
+        tenants.push(stats.clone());
+        stats
+    }
+
+    pub fn remove_tenant(&self, name: &str) {
+        self.tenants
+            .lock()
+            .unwrap()
+            .retain(|stats| stats.name != name);
+    }
+
+    pub fn latency(&self, class: ContentClass, cache: CacheResult) -> &Histogram {
+        &self.latency[class as usize][cache as usize]
+    }
+
+    // (打开的连接数, 不同的客户端 IP 数)
+    pub fn connections(&self) -> (u64, usize) {
+        (
+            self.connections.load(Ordering::Relaxed),
+            self.clients.lock().unwrap().len(),
+        )
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            status_2xx: self.status_2xx.load(Ordering::Relaxed),
+            status_3xx: self.status_3xx.load(Ordering::Relaxed),
+            status_4xx: self.status_4xx.load(Ordering::Relaxed),
+            status_5xx: self.status_5xx.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+            negative_cache_hits: self.negative_cache_hits.load(Ordering::Relaxed),
+            ua_blocked: self.ua_blocked.load(Ordering::Relaxed),
+            path_rejected: self.path_rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// 统计快照，也用于工作进程向 supervisor 汇报
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub struct Snapshot {
+    pub requests: u64,
+    pub status_2xx: u64,
+    pub status_3xx: u64,
+    pub status_4xx: u64,
+    pub status_5xx: u64,
+    // 内存缓存命中 / 未命中次数
+    #[serde(default)]
+    pub cache_hits: u64,
+    #[serde(default)]
+    pub cache_misses: u64,
+    // 404 缓存命中次数
+    #[serde(default)]
+    pub negative_cache_hits: u64,
+    // 被 [[user_agent]] 规则拒绝的请求数
+    #[serde(default)]
+    pub ua_blocked: u64,
+    // 被 [path_validation] 拒绝的请求数
+    #[serde(default)]
+    pub path_rejected: u64,
+}
+
+impl std::ops::AddAssign for Snapshot {
+    fn add_assign(&mut self, other: Snapshot) {
+        self.requests += other.requests;
+        self.status_2xx += other.status_2xx;
+        self.status_3xx += other.status_3xx;
+        self.status_4xx += other.status_4xx;
+        self.status_5xx += other.status_5xx;
+        self.cache_hits += other.cache_hits;
+        self.cache_misses += other.cache_misses;
+        self.negative_cache_hits += other.negative_cache_hits;
+        self.ua_blocked += other.ua_blocked;
+        self.path_rejected += other.path_rejected;
+    }
+}
+
+// 连接存续期间计入 METRICS 的连接数，Unix socket 的对端没有 IP
+pub struct Connected(Option<IpAddr>);
+
+impl Connected {
+    pub fn new(peer: &PeerAddr) -> Self {
+        let ip = match peer {
+            PeerAddr::Tcp(addr) => Some(addr.ip().to_canonical()),
+            #[cfg(unix)]
+            PeerAddr::Unix => None,
+        };
+        METRICS.connections.fetch_add(1, Ordering::Relaxed);
+        if let Some(ip) = ip {
+            *METRICS.clients.lock().unwrap().entry(ip).or_insert(0) += 1;
+        }
+        Connected(ip)
+    }
+}
+
+impl Drop for Connected {
+    fn drop(&mut self) {
+        METRICS.connections.fetch_sub(1, Ordering::Relaxed);
+        if let Some(ip) = self.0 {
+            let mut clients = METRICS.clients.lock().unwrap();
+            if let Some(count) = clients.get_mut(&ip) {
+                *count -= 1;
+                if *count == 0 {
+                    clients.remove(&ip);
+                }
+            }
+        }
+    }
+}
+
+pub async fn track(mut req: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let lookup = CacheLookup::default();
+    req.extensions_mut().insert(lookup.clone());
+    let response = next.run(req).await;
+    METRICS.record(response.status());
+    let histogram = METRICS.latency(ContentClass::of(response.headers()), lookup.result());
+    response.map(|body| {
+        Body::new(Timed {
+            inner: body,
+            started,
+            histogram,
+        })
+    })
+}
+
+// 响应体发送完毕或连接中断（被丢弃）时记录耗时
+struct Timed {
+    inner: Body,
+    started: Instant,
+    histogram: &'static Histogram,
+}
+
+impl Drop for Timed {
+    fn drop(&mut self) {
+        self.histogram.observe(self.started.elapsed().as_secs_f64());
+    }
+}
+
+impl HttpBody for Timed {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(
+        out,
+        "# HELP {} {}\n# TYPE {} counter\n{} {}",
+        name, help, name, name, value
+    );
+}
+
+// Prometheus 文本格式，只包含当前进程的统计
+pub async fn export() -> Response {
+    let snapshot = METRICS.snapshot();
+    let (connections, clients) = METRICS.connections();
+    let mut out = String::new();
+    counter(
+        &mut out,
+        "sonicwave_requests_total",
+        "Requests handled.",
+        snapshot.requests,
+    );
+    let _ = writeln!(
+        out,
+        "# HELP sonicwave_responses_total Responses by status class.\n# TYPE sonicwave_responses_total counter"
+    );
+    for (class, value) in [
+        ("2xx", snapshot.status_2xx),
+        ("3xx", snapshot.status_3xx),
+        ("4xx", snapshot.status_4xx),
+        ("5xx", snapshot.status_5xx),
+    ] {
+        let _ = writeln!(
+            out,
+            "sonicwave_responses_total{{status=\"{}\"}} {}",
+            class, value
+        );
+    }
+    counter(
+        &mut out,
+        "sonicwave_cache_hits_total",
+        "File cache hits.",
+        snapshot.cache_hits,
+    );
+    counter(
+        &mut out,
+        "sonicwave_cache_misses_total",
+        "File cache misses.",
+        snapshot.cache_misses,
+    );
+    counter(
+        &mut out,
+        "sonicwave_negative_cache_hits_total",
+        "404 cache hits.",
+        snapshot.negative_cache_hits,
+    );
+    counter(
+        &mut out,
+        "sonicwave_user_agent_blocked_total",
+        "Requests rejected by user_agent rules.",
+        snapshot.ua_blocked,
+    );
+    counter(
+        &mut out,
+        "sonicwave_path_rejected_total",
+        "Requests rejected by path_validation.",
+        snapshot.path_rejected,
+    );
+    counter(
+        &mut out,
+        "sonicwave_compression_cache_hits_total",
+        "Compression cache hits.",
+        METRICS.compression_cache_hits.load(Ordering::Relaxed),
+    );
+    counter(
+        &mut out,
+        "sonicwave_compression_cache_misses_total",
+        "Compression cache misses.",
+        METRICS.compression_cache_misses.load(Ordering::Relaxed),
+    );
+    counter(
+        &mut out,
+        "sonicwave_compression_cache_evictions_total",
+        "Entries removed from the compression cache to stay within cache_max_bytes.",
+        METRICS.compression_cache_evictions.load(Ordering::Relaxed),
+    );
+    let _ = writeln!(
+        out,
+        "# HELP sonicwave_compression_cache_bytes Size of the compression cache on disk.\n# TYPE sonicwave_compression_cache_bytes gauge\nsonicwave_compression_cache_bytes {}",
+        METRICS.compression_cache_bytes.load(Ordering::Relaxed)
+    );
+    let _ = writeln!(
+        out,
+        "# HELP sonicwave_open_connections Open client connections.\n# TYPE sonicwave_open_connections gauge\nsonicwave_open_connections {}",
+        connections
+    );
+    let _ = writeln!(
+        out,
+        "# HELP sonicwave_connected_clients Distinct client IPs with open connections.\n# TYPE sonicwave_connected_clients gauge\nsonicwave_connected_clients {}",
+        clients
+    );
+    let _ = writeln!(
+        out,
+        "# HELP sonicwave_response_duration_seconds Time until the response body was sent, by content class and cache result.\n# TYPE sonicwave_response_duration_seconds histogram"
+    );
+    for class in ContentClass::ALL {
+        for cache in CacheResult::ALL {
+            let histogram = METRICS.latency(class, cache);
+            let labels = format!("content=\"{}\",cache=\"{}\"", class.name(), cache.name());
+            let mut cumulative = 0;
+            for (i, bucket) in histogram.buckets.iter().enumerate() {
+                cumulative += bucket.load(Ordering::Relaxed);
+                let le = BUCKETS
+                    .get(i)
+                    .map_or("+Inf".to_string(), |bound| bound.to_string());
+                let _ = writeln!(
+                    out,
+                    "sonicwave_response_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, le, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "sonicwave_response_duration_seconds_sum{{{}}} {}\nsonicwave_response_duration_seconds_count{{{}}} {}",
+                labels,
+                histogram.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0,
+                labels,
+                cumulative
+            );
+        }
+    }
+    let upstreams = METRICS.upstreams.lock().unwrap().clone();
+    if !upstreams.is_empty() {
+        for (name, kind, help, value) in [
+            (
+                "sonicwave_upstream_requests_total",
+                "counter",
+                "Requests forwarded to each proxy upstream.",
+                (|stats: &UpstreamStats| stats.requests.load(Ordering::Relaxed))
+                    as fn(&UpstreamStats) -> u64,
+            ),
+            (
+                "sonicwave_upstream_failures_total",
+                "counter",
+                "Failed requests (connection errors, timeouts, 502/503/504) per upstream.",
+                |stats| stats.failures.load(Ordering::Relaxed),
+            ),
+            (
+                "sonicwave_upstream_ejections_total",
+                "counter",
+                "Times each upstream was taken out of rotation.",
+                |stats| stats.ejections.load(Ordering::Relaxed),
+            ),
+            (
+                "sonicwave_upstream_active_requests",
+                "gauge",
+                "Requests in flight per upstream.",
+                |stats| stats.active.load(Ordering::Relaxed),
+            ),
+            (
+                "sonicwave_upstream_healthy",
+                "gauge",
+                "1 if the upstream is in rotation, 0 while ejected.",
+                |stats| stats.healthy.load(Ordering::Relaxed) as u64,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+            for stats in &upstreams {
+                let _ = writeln!(
+                    out,
+                    "{}{{upstream=\"{}\"}} {}",
+                    name,
+                    stats.url.replace('\\', "\\\\").replace('"', "\\\""),
+                    value(stats)
+                );
+            }
+        }
+    }
+    let tenants = METRICS.tenants.lock().unwrap().clone();
+    if !tenants.is_empty() {
+        for (name, kind, help, value) in [
+            (
+                "sonicwave_tenant_requests_total",
+                "counter",
+                "Requests handled per tenant.",
+                (|stats: &TenantStats| stats.requests.load(Ordering::Relaxed))
+                    as fn(&TenantStats) -> u64,
+            ),
+            (
+                "sonicwave_tenant_sent_bytes_total",
+                "counter",
+                "Response bytes per tenant, by Content-Length.",
+                |stats| stats.sent_bytes.load(Ordering::Relaxed),
+            ),
+            (
+                "sonicwave_tenant_storage_bytes",
+                "gauge",
+                "Bytes stored in each tenant directory.",
+                |stats| stats.storage_bytes.load(Ordering::Relaxed),
+            ),
+            (
+                "sonicwave_tenant_quota_bytes",
+                "gauge",
+                "Storage quota of each tenant.",
+                |stats| stats.quota_bytes.load(Ordering::Relaxed),
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+            for stats in &tenants {
+                let _ = writeln!(
+                    out,
+                    "{}{{tenant=\"{}\"}} {}",
+                    name,
+                    stats.name,
+                    value(stats)
+                );
+            }
+        }
+    }
+    let mut response = out.into_response();
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; version=0.0.4; charset=utf-8"),
+    );
+    response
+}
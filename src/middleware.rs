@@ -0,0 +1,642 @@
+// 全局中间件：包在整个 Router 外面，默认由内到外依次是重写规则、Lua 脚本、WASM 插件、故障注入、Link 头、CDN 缓存策略、--dev 的 no-store、防盗链、调试文件拦截、
+// 爬虫快照、User-Agent 过滤、live reload 注入、实时压缩、OPTIONS / TRACE 处理、CORS、Vary 合并、GeoIP、客户端证书、限速、登录、OIDC 单点登录、转发认证、规范主机重定向、路径校验、维护模式、指标、下载与状态页统计、Server-Timing、流量镜像、Sentry、访问日志、请求 ID、Server 头与客户端还原。
+// pipeline 可以调整顺序、关闭某一层或只对部分路径生效；客户端还原始终在最外层
+use crate::analytics::{self, Analytics};
+use crate::canonical_host::{self, CanonicalHost};
+use crate::cdn::{self, Cdn};
+use crate::chaos::{self, Chaos};
+use crate::client_auth::{self, ClientRules};
+use crate::compression::{self, Compression};
+use crate::config::Config;
+use crate::cors::{self, Cors};
+use crate::debug_artifacts::{self, DebugArtifacts};
+use crate::forward_auth::{self, ForwardAuth};
+use crate::geoip::{self, Geoip};
+use crate::hotlink::{self, Hotlink};
+use crate::link_headers::{self, LinkHeaders};
+use crate::login::{self, Login};
+use crate::maintenance::{self, Maintenance};
+use crate::methods::{self, Methods};
+use crate::oidc::{self, Oidc};
+use crate::path_validation::{self, PathValidation};
+use crate::plugins::{self, Plugins};
+use crate::prerender::{self, Prerender};
+use crate::script::{self, Script};
+use crate::server_header::{self, ServerHeader};
+use crate::server_timing::{self, ServerTiming};
+use crate::shadow::{self, Shadow};
+use crate::status::{self, Status};
+use crate::throttle::{self, Throttle};
+use crate::{
+    access_log, forwarded, live_reload, metrics, request_id, rewrite, site, user_agent, vary,
+};
+use axum::extract::Request;
+use axum::http::header::{self, HeaderValue};
+use axum::Router;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tower::ServiceExt;
+use tower_http::set_header::SetResponseHeaderLayer;
+use tracing::{info, warn};
+
+// pipeline 中使用的名称，按默认顺序由内到外；"serve"（站点本身）只能写在 pipeline 的最后
+const LAYERS: &[&str] = &[
+    "rewrite",
+    "script",
+    "plugins",
+    "chaos",
+    "link_headers",
+    "cdn",
+    "dev",
+    "hotlink",
+    "debug_artifacts",
+    "prerender",
+    "user_agent",
+    "live_reload",
+    "compression",
+    "methods",
+    "cors",
+    "vary",
+    "geoip",
+    "client_auth",
+    "throttle",
+    "login",
+    "oidc",
+    "forward_auth",
+    "canonical_host",
+    "path_validation",
+    "maintenance",
+    "metrics",
+    "analytics",
+    "status",
+    "server_timing",
+    "shadow",
+    "sentry",
+    "access_log",
+    "request_id",
+    "server_header",
+];
+
+// pipeline 中的一项：名称，或 { layer = "名称", enabled = false, prefixes = ["/api/"] }
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(untagged)]
+pub enum PipelineEntry {
+    Name(String),
+    Layer(PipelineLayer),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineLayer {
+    pub layer: String,
+    // false 时不启用，即使配置了对应的功能
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    // 只处理这些路径前缀下的请求，为空时处理所有请求
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+struct Placement {
+    name: &'static str,
+    prefixes: Vec<String>,
+}
+
+// 按 pipeline（由外到内）排出由内到外的顺序。未列出的中间件保持默认顺序中的相对位置，
+// 放在默认顺序里离它最近的内层中间件之外，只调整个别中间件时不必列出全部
+fn order(pipeline: &[PipelineEntry]) -> Result<Vec<Placement>, String> {
+    let mut listed = Vec::new();
+    let mut seen: Vec<&'static str> = Vec::new();
+    for (i, entry) in pipeline.iter().enumerate() {
+        let (name, enabled, prefixes) = match entry {
+            PipelineEntry::Name(name) => (name.as_str(), true, &[][..]),
+            PipelineEntry::Layer(layer) => (
+                layer.layer.as_str(),
+                layer.enabled,
+                layer.prefixes.as_slice(),
+            ),
+        };
+        if name == "serve" {
+            if i + 1 != pipeline.len() {
+                return Err("[pipeline] `serve` must be the last entry".to_string());
+            }
+            continue;
+        }
+        let Some(&name) = LAYERS.iter().find(|layer| **layer == name) else {
+            return Err(format!(
+                "[pipeline] unknown layer `{}`, expected one of: {}",
+                name,
+                LAYERS.join(", ")
+            ));
+        };
+        if seen.contains(&name) {
+            return Err(format!("[pipeline] `{}` is listed more than once", name));
+        }
+        if let Some(prefix) = prefixes.iter().find(|prefix| !prefix.starts_with('/')) {
+            return Err(format!(
+                "[pipeline] `{}`: prefix `{}` must start with /",
+                name, prefix
+            ));
+        }
+        seen.push(name);
+        if enabled {
+            listed.push(Placement {
+                name,
+                prefixes: prefixes.to_vec(),
+            });
+        } else {
+            info!("Middleware `{}` disabled by pipeline", name);
+        }
+    }
+    listed.reverse();
+    let mut order = listed;
+    for (i, &name) in LAYERS.iter().enumerate() {
+        if seen.contains(&name) {
+            continue;
+        }
+        let position = LAYERS[..i]
+            .iter()
+            .rev()
+            .find_map(|inner| order.iter().position(|placed| placed.name == *inner))
+            .map_or(0, |position| position + 1);
+        order.insert(
+            position,
+            Placement {
+                name,
+                prefixes: Vec::new(),
+            },
+        );
+    }
+    Ok(order)
+}
+
+type Wrap = Box<dyn FnOnce(Router) -> Router>;
+
+struct Stages {
+    order: Vec<Placement>,
+    wraps: HashMap<&'static str, Wrap>,
+}
+
+impl Stages {
+    fn new(config: &Config) -> Result<Self, String> {
+        let order = match &config.pipeline {
+            Some(pipeline) => order(pipeline)?,
+            None => LAYERS
+                .iter()
+                .map(|&name| Placement {
+                    name,
+                    prefixes: Vec::new(),
+                })
+                .collect(),
+        };
+        Ok(Stages {
+            order,
+            wraps: HashMap::new(),
+        })
+    }
+
+    // 被 pipeline 关闭的中间件不再创建，也不输出启用信息
+    fn enabled(&self, name: &str) -> bool {
+        self.order.iter().any(|placement| placement.name == name)
+    }
+
+    fn add(&mut self, name: &'static str, wrap: impl FnOnce(Router) -> Router + 'static) {
+        self.wraps.insert(name, Box::new(wrap));
+    }
+
+    // 由内到外包装，返回实际启用的中间件（由外到内，带上限定的前缀）
+    fn build(mut self, mut app: Router) -> (Router, Vec<String>) {
+        let mut applied = Vec::new();
+        for placement in self.order {
+            let Some(wrap) = self.wraps.remove(placement.name) else {
+                continue;
+            };
+            if placement.prefixes.is_empty() {
+                app = wrap(app);
+                applied.push(placement.name.to_string());
+            } else {
+                applied.push(format!(
+                    "{}({})",
+                    placement.name,
+                    placement.prefixes.join(", ")
+                ));
+                app = scoped(app, wrap, placement.prefixes);
+            }
+        }
+        applied.reverse();
+        (app, applied)
+    }
+}
+
+// 只有路径在 prefixes 之下的请求经过该中间件，其余请求直接交给内层
+fn scoped(inner: Router, wrap: Wrap, prefixes: Vec<String>) -> Router {
+    let outer = wrap(inner.clone());
+    Router::new().fallback_service(tower::service_fn(move |req: Request| {
+        let path = site::normalize_path(req.uri().path());
+        let router = if prefixes
+            .iter()
+            .any(|prefix| path.starts_with(prefix.as_str()))
+        {
+            outer.clone()
+        } else {
+            inner.clone()
+        };
+        router.oneshot(req)
+    }))
+}
+
+pub fn apply(
+    app: Router,
+    config: &Config,
+    live_reload: bool,
+    status: Option<Arc<Status>>,
+    maintenance: Option<Arc<Maintenance>>,
+    analytics: Option<Arc<Analytics>>,
+) -> Result<Router, String> {
+    let mut stages = Stages::new(config)?;
+    // Router::layer 在路由匹配之后执行，改写路径的中间件必须包在整个 Router 外面
+    let rules = rewrite::Rules::new(&config.redirect, &config.rewrite)?;
+    if !rules.is_empty() && stages.enabled("rewrite") {
+        let rules = Arc::new(rules);
+        stages.add("rewrite", move |app| {
+            Router::new().fallback_service(
+                tower::ServiceBuilder::new()
+                    .layer(axum::middleware::from_fn_with_state(rules, rewrite::apply))
+                    .service(app),
+            )
+        });
+    }
+    if let Some(path) = config.script.as_ref().filter(|_| stages.enabled("script")) {
+        let script = Arc::new(Script::new(path)?);
+        info!("Lua script enabled ({})", path.display());
+        stages.add("script", move |app| {
+            Router::new().fallback_service(
+                tower::ServiceBuilder::new()
+                    .layer(axum::middleware::from_fn_with_state(script, script::apply))
+                    .service(app),
+            )
+        });
+    }
+    if !config.plugin.is_empty() && stages.enabled("plugins") {
+        let plugins = Plugins::new(&config.plugin)?;
+        info!("WASM plugins enabled ({} modules)", plugins.len());
+        let plugins = Arc::new(plugins);
+        stages.add("plugins", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                plugins,
+                plugins::apply,
+            ))
+        });
+    }
+    // 按原始请求路径匹配；只在 --watch 开发模式下注入，避免误带到生产环境
+    if let Some(config) = config.chaos.as_ref().filter(|_| stages.enabled("chaos")) {
+        let chaos = Chaos::new(config)?;
+        if live_reload {
+            warn!("Chaos injection enabled ({} rules)", chaos.rules());
+            let chaos = Arc::new(chaos);
+            stages.add("chaos", move |app| {
+                app.layer(axum::middleware::from_fn_with_state(chaos, chaos::inject))
+            });
+        } else {
+            warn!("[chaos] is only used with --watch");
+        }
+    }
+    if !config.link_headers.is_empty() && stages.enabled("link_headers") {
+        let links = Arc::new(LinkHeaders::new(&config.link_headers)?);
+        stages.add("link_headers", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                links,
+                link_headers::apply,
+            ))
+        });
+    }
+    if let Some(cdn) = config.cdn.as_ref().filter(|_| stages.enabled("cdn")) {
+        let cdn = Arc::new(Cdn::new(cdn)?);
+        stages.add("cdn", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(cdn, cdn::apply))
+        });
+    }
+    // --dev：覆盖各处设置的 Cache-Control，包括缓存规则、指纹文件与虚拟主机
+    if config.dev && stages.enabled("dev") {
+        stages.add("dev", |app| {
+            app.layer(SetResponseHeaderLayer::overriding(
+                header::CACHE_CONTROL,
+                HeaderValue::from_static("no-store"),
+            ))
+        });
+    }
+    // 按原始请求路径判断，放在重写规则外层
+    if let Some(hotlink) = config
+        .hotlink
+        .as_ref()
+        .filter(|_| stages.enabled("hotlink"))
+    {
+        let checker = Arc::new(Hotlink::new(hotlink)?);
+        info!(
+            "Hotlink protection enabled for {} extensions",
+            hotlink.extensions.len()
+        );
+        stages.add("hotlink", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                checker,
+                hotlink::check,
+            ))
+        });
+    }
+    // 在 CDN 缓存策略外层，放行的响应一律 private；--dev 模式下不拦截
+    if let Some(artifacts) = config
+        .debug_artifacts
+        .as_ref()
+        .filter(|_| !config.dev && stages.enabled("debug_artifacts"))
+    {
+        let artifacts = Arc::new(DebugArtifacts::new(artifacts)?);
+        stages.add("debug_artifacts", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                artifacts,
+                debug_artifacts::check,
+            ))
+        });
+    }
+    // 在 User-Agent 过滤之内，被拒绝的爬虫拿不到快照；按原始请求路径查找快照
+    if let Some(snapshots) = config
+        .prerender
+        .as_ref()
+        .filter(|_| stages.enabled("prerender"))
+    {
+        let prerender = Prerender::new(snapshots, &config.html_cache_control)?;
+        info!("Crawler snapshots: {}", prerender.describe());
+        let prerender = Arc::new(prerender);
+        stages.add("prerender", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                prerender,
+                prerender::serve,
+            ))
+        });
+    }
+    if !config.user_agent.is_empty() && stages.enabled("user_agent") {
+        let filter = Arc::new(user_agent::Filter::new(&config.user_agent)?);
+        info!("User-Agent filter: {} rules", config.user_agent.len());
+        stages.add("user_agent", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                filter,
+                user_agent::check,
+            ))
+        });
+    }
+    if live_reload && stages.enabled("live_reload") {
+        stages.add("live_reload", |app| {
+            app.layer(axum::middleware::from_fn(live_reload::inject))
+        });
+    }
+    // 在内容改写（live reload 注入等）之后压缩
+    if let Some(config) = config
+        .compression
+        .as_ref()
+        .filter(|_| stages.enabled("compression"))
+    {
+        let compression = Arc::new(Compression::new(config)?);
+        stages.add("compression", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                compression,
+                compression::compress,
+            ))
+        });
+    }
+    // 在 CORS 之内，预检请求仍由 CORS 应答
+    if stages.enabled("methods") {
+        let methods = Arc::new(Methods::new(config));
+        stages.add("methods", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                methods,
+                methods::handle,
+            ))
+        });
+    }
+    // 预检请求在路由、上传认证与 WebDAV 之前应答
+    if let Some(cors) = config.cors.as_ref().filter(|_| stages.enabled("cors")) {
+        let cors = Arc::new(Cors::new(cors)?);
+        stages.add("cors", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(cors, cors::handle))
+        });
+    }
+    // 在所有添加 Vary 的中间件外层，合并为一个 Vary 头
+    if stages.enabled("vary") {
+        stages.add("vary", |app| {
+            app.layer(axum::middleware::from_fn(vary::merge))
+        });
+    }
+    // 在 resolve_client 之内，使用还原后的客户端 IP；拒绝的请求同样计入指标与访问日志
+    if let Some(geoip) = config.geoip.as_ref().filter(|_| stages.enabled("geoip")) {
+        let geoip = Geoip::new(geoip)?;
+        info!("GeoIP access rules enabled ({} rules)", geoip.rules());
+        let geoip = Arc::new(geoip);
+        stages.add("geoip", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(geoip, geoip::check))
+        });
+    }
+    if let Some(tls) = config
+        .tls
+        .as_ref()
+        .filter(|tls| !tls.client_rules.is_empty() && stages.enabled("client_auth"))
+    {
+        if tls.client_ca.is_none() {
+            return Err("[tls] client_rules require client_ca".to_string());
+        }
+        let rules = ClientRules::new(&tls.client_rules)?;
+        info!("Client certificate rules enabled ({} rules)", rules.rules());
+        let rules = Arc::new(rules);
+        stages.add("client_auth", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                rules,
+                client_auth::check,
+            ))
+        });
+    }
+    // 包装最终的响应体，按实际发送的字节计算
+    if let Some(config) = config
+        .throttle
+        .as_ref()
+        .filter(|_| stages.enabled("throttle"))
+    {
+        let throttle = Arc::new(Throttle::new(config)?);
+        stages.add("throttle", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                throttle,
+                throttle::apply,
+            ))
+        });
+    }
+    // 登录与 OIDC 在规范主机重定向之内，会话 cookie 只发给规范主机
+    if let Some(login) = config.login.as_ref().filter(|_| stages.enabled("login")) {
+        let login = Login::new(login, &config.shutdown.readiness_path)?;
+        info!("Login required: {}", login.describe());
+        let login = Arc::new(login);
+        stages.add("login", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(login, login::check))
+        });
+    }
+    if let Some(oidc) = config
+        .auth
+        .as_ref()
+        .and_then(|auth| auth.oidc.as_ref())
+        .filter(|_| stages.enabled("oidc"))
+    {
+        let oidc = Oidc::new(oidc, &config.shutdown.readiness_path)?;
+        info!("OIDC sign-in required: {}", oidc.describe());
+        let oidc = Arc::new(oidc);
+        stages.add("oidc", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(oidc, oidc::check))
+        });
+    }
+    // 在规范主机重定向之内，认证服务看到的是规范主机上的请求
+    if let Some(forward) = config
+        .forward_auth
+        .as_ref()
+        .filter(|_| stages.enabled("forward_auth"))
+    {
+        let auth = ForwardAuth::new(forward, &config.shutdown.readiness_path)?;
+        info!("Forward auth: {}", auth.describe());
+        let auth = Arc::new(auth);
+        stages.add("forward_auth", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                auth,
+                forward_auth::check,
+            ))
+        });
+    }
+    // 在访问控制与限速之前重定向；重定向同样计入指标与访问日志
+    if let Some(config) = config
+        .canonical_host
+        .as_ref()
+        .filter(|_| stages.enabled("canonical_host"))
+    {
+        let canonical = Arc::new(CanonicalHost::new(config)?);
+        info!("Canonical host: {}", config.host);
+        stages.add("canonical_host", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                canonical,
+                canonical_host::redirect,
+            ))
+        });
+    }
+    // 在重定向与其他所有按路径处理的中间件之前拒绝；拒绝的请求同样计入指标与访问日志
+    if let Some(config) = config
+        .path_validation
+        .as_ref()
+        .filter(|_| stages.enabled("path_validation"))
+    {
+        let validation = Arc::new(PathValidation::new(config)?);
+        stages.add("path_validation", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                validation,
+                path_validation::check,
+            ))
+        });
+    }
+    // 维护模式在其他所有处理之前应答；503 响应同样计入指标与访问日志
+    if let Some(maintenance) = maintenance.filter(|_| stages.enabled("maintenance")) {
+        stages.add("maintenance", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                maintenance,
+                maintenance::check,
+            ))
+        });
+    }
+    if stages.enabled("metrics") {
+        stages.add("metrics", |app| {
+            app.layer(axum::middleware::from_fn(metrics::track))
+        });
+    }
+    if let Some(analytics) = analytics.filter(|_| stages.enabled("analytics")) {
+        stages.add("analytics", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                analytics,
+                analytics::track,
+            ))
+        });
+    }
+    // 在重写规则外层，按原始请求路径统计
+    if let Some(status) = status.filter(|_| stages.enabled("status")) {
+        stages.add("status", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(status, status::track))
+        });
+    }
+    if let Some(timing) = config
+        .server_timing
+        .as_ref()
+        .filter(|_| stages.enabled("server_timing"))
+    {
+        let timing = Arc::new(ServerTiming::new(timing)?);
+        stages.add("server_timing", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                timing,
+                server_timing::track,
+            ))
+        });
+    }
+    // 在维护模式、路径校验等拒绝之外，镜像的是收到的全部请求
+    if let Some(config) = config.shadow.as_ref().filter(|_| stages.enabled("shadow")) {
+        let shadow = Shadow::new(config)?;
+        info!("Shadowing {}", shadow.describe());
+        let shadow = Arc::new(shadow);
+        stages.add("shadow", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(shadow, shadow::mirror))
+        });
+    }
+    // 在请求 ID 之内，事件带上请求 ID
+    #[cfg(feature = "sentry")]
+    if config.sentry.is_some() && stages.enabled("sentry") {
+        stages.add("sentry", |app| {
+            app.layer(axum::middleware::from_fn(crate::sentry::track))
+        });
+    }
+    if config.access_log && stages.enabled("access_log") {
+        let rules = Arc::new(access_log::Rules::new(config.access_log_rules.as_ref())?);
+        stages.add("access_log", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                rules,
+                access_log::log_request,
+            ))
+        });
+    } else if !config.access_log && config.access_log_rules.is_some() {
+        warn!("[access_log_rules] is only used with access_log = true");
+    }
+    // 在访问日志外层，访问日志与之后的日志都在带请求 ID 的 span 中
+    if config.request_id && stages.enabled("request_id") {
+        stages.add("request_id", |app| {
+            app.layer(axum::middleware::from_fn(request_id::assign))
+        });
+    }
+    // 在所有生成响应的中间件外层，拒绝、重定向与错误页的响应同样处理
+    if let Some(config) = config
+        .server_header
+        .as_ref()
+        .filter(|_| stages.enabled("server_header"))
+    {
+        let server = ServerHeader::new(config)?;
+        info!("Server header: {}", server.describe());
+        let server = Arc::new(server);
+        stages.add("server_header", move |app| {
+            app.layer(axum::middleware::from_fn_with_state(
+                server,
+                server_header::apply,
+            ))
+        });
+    }
+    let (app, applied) = stages.build(app);
+    if config.pipeline.is_some() {
+        info!("Middleware pipeline: {} > serve", applied.join(" > "));
+    }
+    // 最外层：先确定真实客户端，再交给访问日志等中间件
+    let trusted = Arc::new(forwarded::TrustedProxies::parse(&config.trusted_proxies)?);
+    Ok(app.layer(axum::middleware::from_fn_with_state(
+        trusted,
+        forwarded::resolve_client,
+    )))
+}
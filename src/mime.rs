@@ -0,0 +1,135 @@
+// MIME 类型表：修正 mime_guess 对现代 Web 资源的猜测，允许通过 [mime] 配置覆盖，并为文本类型补充字符集
+use axum::http::HeaderValue;
+use std::collections::{BTreeMap, HashMap};
+
+// 内置的现代默认值，优先于 ServeDir 的猜测结果
+const MODERN_DEFAULTS: &[(&str, &str)] = &[
+    ("wasm", "application/wasm"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("cjs", "text/javascript"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("avif", "image/avif"),
+    ("jxl", "image/jxl"),
+    ("webp", "image/webp"),
+    ("glb", "model/gltf-binary"),
+    ("gltf", "model/gltf+json"),
+    ("ktx2", "image/ktx2"),
+    ("woff2", "font/woff2"),
+    ("opus", "audio/ogg"),
+    ("flac", "audio/flac"),
+    ("m4a", "audio/mp4"),
+];
+
+#[derive(Debug)]
+pub struct MimeTable {
+    // 扩展名（小写、不含点）-> Content-Type
+    types: HashMap<String, HeaderValue>,
+    // 文本类型默认追加的字符集，None 表示不追加
+    charset: Option<String>,
+    // 按扩展名指定的字符集
+    charset_by_ext: HashMap<String, String>,
+}
+
+impl Default for MimeTable {
+    fn default() -> Self {
+        let types = MODERN_DEFAULTS
+            .iter()
+            .map(|(ext, mime)| (ext.to_string(), HeaderValue::from_static(mime)))
+            .collect();
+        MimeTable {
+            types,
+            charset: Some(default_charset()),
+            charset_by_ext: HashMap::new(),
+        }
+    }
+}
+
+pub fn default_charset() -> String {
+    "utf-8".to_string()
+}
+
+impl MimeTable {
+    pub fn new(
+        overrides: &BTreeMap<String, String>,
+        charset: &str,
+        charset_by_ext: &BTreeMap<String, String>,
+    ) -> Result<Self, String> {
+        let mut table = MimeTable::default();
+        for (ext, mime) in overrides {
+            let value = HeaderValue::from_str(mime)
+                .map_err(|_| format!("invalid MIME type `{}` for .{}", mime, ext))?;
+            table.types.insert(normalize_ext(ext), value);
+        }
+        let valid = |cs: &str| {
+            !cs.is_empty()
+                && cs
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.:".contains(c))
+        };
+        table.charset = if charset.is_empty() {
+            None
+        } else if valid(charset) {
+            Some(charset.to_string())
+        } else {
+            return Err(format!("invalid charset `{}`", charset));
+        };
+        for (ext, cs) in charset_by_ext {
+            if !valid(cs) {
+                return Err(format!("invalid charset `{}` for .{}", cs, ext));
+            }
+            table.charset_by_ext.insert(normalize_ext(ext), cs.clone());
+        }
+        Ok(table)
+    }
+
+    // 计算最终的 Content-Type：按扩展名覆盖类型，文本类型缺少 charset 时追加
+    pub fn content_type(&self, path: &str, current: Option<&HeaderValue>) -> Option<HeaderValue> {
+        let ext = extension(path);
+        let base = ext
+            .as_deref()
+            .and_then(|ext| self.types.get(ext))
+            .or(current)?;
+        let base_str = base.to_str().ok()?;
+        if base_str.to_ascii_lowercase().contains("charset=") || !is_textual(base_str) {
+            return Some(base.clone());
+        }
+        let charset = ext
+            .as_deref()
+            .and_then(|ext| self.charset_by_ext.get(ext))
+            .or(self.charset.as_ref());
+        match charset {
+            Some(charset) => {
+                HeaderValue::from_str(&format!("{}; charset={}", base_str, charset)).ok()
+            }
+            None => Some(base.clone()),
+        }
+    }
+}
+
+fn normalize_ext(ext: &str) -> String {
+    ext.trim_start_matches('.').to_ascii_lowercase()
+}
+
+fn extension(path: &str) -> Option<String> {
+    let name = path.rsplit('/').next()?;
+    let (_, ext) = name.rsplit_once('.')?;
+    Some(ext.to_ascii_lowercase())
+}
+
+// text/*、JavaScript 以及 JSON / XML 类类型
+fn is_textual(mime: &str) -> bool {
+    let essence = mime
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence == "application/javascript"
+        || essence == "application/json"
+        || essence == "application/xml"
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+}
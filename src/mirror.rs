@@ -0,0 +1,518 @@
+// 拉取式镜像（[mirror]）：主目录中不存在的文件从远端源站取回，写入主目录后按普通文件提供，
+// 之后的请求直接读磁盘；取回的文件超过 ttl_secs 后以 If-None-Match / If-Modified-Since 重新验证，
+// 源站删除的文件随之删除。取回记录保存在 meta_dir，主目录中原有的文件不会被验证或清除
+use crate::site;
+use crate::upload;
+use axum::body::Body;
+use axum::extract::{Query, Request, State};
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::{Method, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures_util::StreamExt;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::io::AsyncWriteExt;
+use tracing::{debug, info, warn};
+
+// 以 "/" 结尾的路径保存为该目录下的索引文件
+const INDEX_FILE: &str = "index.html";
+
+// 记住的源站缺失路径数上限
+const MISSING_CAPACITY: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct MirrorConfig {
+    // 源站地址，可以带路径前缀，如 "https://origin.example.com/site"
+    pub origin: String,
+    // 取回的文件在该时间内直接提供，过期后向源站重新验证；0 表示不再验证
+    #[serde(default = "default_ttl")]
+    pub ttl_secs: u64,
+    // 源站没有的路径在该时间内不再请求源站
+    #[serde(default = "default_negative_ttl")]
+    pub negative_ttl_secs: u64,
+    // 超过该大小的文件不镜像
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+    // 取回记录（时间、ETag、Last-Modified、Content-Type）所在的目录
+    #[serde(default = "default_meta_dir")]
+    pub meta_dir: String,
+    #[serde(default = "default_purge_path")]
+    pub purge_path: String,
+    // 清除端点的 Bearer token，未设置时不启用清除端点
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub token: Option<String>,
+}
+
+fn default_ttl() -> u64 {
+    3600
+}
+
+fn default_negative_ttl() -> u64 {
+    60
+}
+
+fn default_max_file_size() -> u64 {
+    256 * 1024 * 1024
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+fn default_meta_dir() -> String {
+    ".sonicwave-mirror".to_string()
+}
+
+fn default_purge_path() -> String {
+    "/__mirror/purge".to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Meta {
+    // 相对主目录的路径（"/" 分隔）
+    path: String,
+    // 取回或最近一次验证的时间（Unix 秒）
+    fetched: u64,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    content_type: Option<String>,
+}
+
+enum Fetched {
+    Stored(Meta),
+    NotModified,
+    // 源站返回的其他状态码
+    Missing(StatusCode),
+}
+
+type HttpClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+pub struct Mirror {
+    dir: PathBuf,
+    meta_dir: PathBuf,
+    origin: String,
+    ttl: Duration,
+    negative_ttl: Duration,
+    max_file_size: u64,
+    timeout: Duration,
+    purge_path: String,
+    token: Option<String>,
+    client: HttpClient,
+    // 同一路径同时只取回一次
+    inflight: Mutex<HashMap<String, Weak<tokio::sync::Mutex<()>>>>,
+    // 源站没有的路径 -> 记录时间
+    missing: Mutex<HashMap<String, Instant>>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Mirror {
+    pub fn new(config: &MirrorConfig, dir: &Path) -> Result<Self, String> {
+        let origin = config.origin.trim_end_matches('/');
+        let uri: Uri = origin
+            .parse()
+            .map_err(|e| format!("invalid [mirror] origin `{}`: {}", config.origin, e))?;
+        if !matches!(uri.scheme_str(), Some("http" | "https")) || uri.authority().is_none() {
+            return Err(format!(
+                "[mirror] origin `{}` must be an http:// or https:// URL",
+                config.origin
+            ));
+        }
+        if config.token.as_deref() == Some("") {
+            return Err("[mirror] token must not be empty".to_string());
+        }
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(connector);
+        Ok(Mirror {
+            dir: dir.to_path_buf(),
+            meta_dir: PathBuf::from(&config.meta_dir),
+            origin: origin.to_string(),
+            ttl: Duration::from_secs(config.ttl_secs),
+            negative_ttl: Duration::from_secs(config.negative_ttl_secs),
+            max_file_size: config.max_file_size,
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+            purge_path: config.purge_path.clone(),
+            token: config.token.clone(),
+            client: Client::builder(TokioExecutor::new()).build(https),
+            inflight: Mutex::new(HashMap::new()),
+            missing: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn origin(&self) -> &str {
+        &self.origin
+    }
+
+    pub fn purge_path(&self) -> Option<&str> {
+        self.token.as_ref().map(|_| self.purge_path.as_str())
+    }
+
+    fn meta_path(&self, relative: &str) -> PathBuf {
+        let digest = Sha256::digest(relative.as_bytes());
+        let name: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        self.meta_dir.join(format!("{}.json", name))
+    }
+
+    async fn read_meta(&self, relative: &str) -> Option<Meta> {
+        let data = tokio::fs::read(self.meta_path(relative)).await.ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    async fn write_meta(&self, meta: &Meta) -> std::io::Result<()> {
+        tokio::fs::create_dir_all(&self.meta_dir).await?;
+        let path = self.meta_path(&meta.path);
+        let temp = path.with_extension("tmp");
+        tokio::fs::write(&temp, serde_json::to_vec(meta)?).await?;
+        tokio::fs::rename(&temp, &path).await
+    }
+
+    fn stale(&self, meta: &Meta) -> bool {
+        !self.ttl.is_zero() && unix_now().saturating_sub(meta.fetched) >= self.ttl.as_secs()
+    }
+
+    fn recently_missing(&self, relative: &str) -> bool {
+        let mut missing = self.missing.lock().unwrap();
+        match missing.get(relative) {
+            Some(at) if at.elapsed() < self.negative_ttl => true,
+            Some(_) => {
+                missing.remove(relative);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn remember_missing(&self, relative: &str) {
+        let mut missing = self.missing.lock().unwrap();
+        if missing.len() >= MISSING_CAPACITY {
+            missing.retain(|_, at| at.elapsed() < self.negative_ttl);
+        }
+        if missing.len() < MISSING_CAPACITY {
+            missing.insert(relative.to_string(), Instant::now());
+        }
+    }
+
+    fn inflight(&self, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut inflight = self.inflight.lock().unwrap();
+        if let Some(lock) = inflight.get(key).and_then(Weak::upgrade) {
+            return lock;
+        }
+        inflight.retain(|_, lock| lock.strong_count() > 0);
+        let lock = Arc::new(tokio::sync::Mutex::new(()));
+        inflight.insert(key.to_string(), Arc::downgrade(&lock));
+        lock
+    }
+
+    // 请求源站并把 200 响应写入 local；meta 存在时发送条件请求
+    async fn fetch(
+        &self,
+        raw: &str,
+        relative: &str,
+        local: &Path,
+        meta: Option<&Meta>,
+    ) -> Result<Fetched, String> {
+        let uri = format!("{}{}", self.origin, raw);
+        let mut builder = axum::http::Request::get(&uri)
+            .header(header::ACCEPT_ENCODING, "identity")
+            .header(
+                header::USER_AGENT,
+                concat!("sonic-wave/", env!("CARGO_PKG_VERSION")),
+            );
+        if let Some(meta) = meta {
+            if let Some(etag) = &meta.etag {
+                builder = builder.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &meta.last_modified {
+                builder = builder.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let request = builder
+            .body(Body::empty())
+            .map_err(|e| format!("invalid origin URL {}: {}", uri, e))?;
+        let response = tokio::time::timeout(self.timeout, self.client.request(request))
+            .await
+            .map_err(|_| format!("timed out fetching {}", uri))?
+            .map_err(|e| format!("failed to fetch {}: {}", uri, e))?;
+        match response.status() {
+            StatusCode::OK => {}
+            StatusCode::NOT_MODIFIED if meta.is_some() => return Ok(Fetched::NotModified),
+            status => return Ok(Fetched::Missing(status)),
+        }
+        let text = |headers: &HeaderMap, name| {
+            headers
+                .get(name)
+                .and_then(|v: &HeaderValue| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let headers = response.headers();
+        let length = text(headers, header::CONTENT_LENGTH).and_then(|v| v.parse::<u64>().ok());
+        if length.is_some_and(|length| length > self.max_file_size) {
+            return Err(format!("{} is larger than max_file_size", uri));
+        }
+        let meta = Meta {
+            path: relative.to_string(),
+            fetched: unix_now(),
+            etag: text(headers, header::ETAG),
+            last_modified: text(headers, header::LAST_MODIFIED),
+            content_type: text(headers, header::CONTENT_TYPE),
+        };
+
+        // 先写临时文件再改名，正在读取旧文件的请求不受影响
+        let parent = local.parent().unwrap_or(&self.dir);
+        let name = local.file_name().unwrap_or_default().to_string_lossy();
+        let temp = parent.join(format!(".{}.mirror-tmp", name));
+        let result = async {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut file = tokio::fs::File::create(&temp)
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut stream = Body::new(response.into_body()).into_data_stream();
+            let mut written = 0u64;
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(|e| e.to_string())?;
+                written += chunk.len() as u64;
+                if written > self.max_file_size {
+                    return Err("larger than max_file_size".to_string());
+                }
+                file.write_all(&chunk).await.map_err(|e| e.to_string())?;
+            }
+            file.flush().await.map_err(|e| e.to_string())?;
+            // 修改时间沿用源站的 Last-Modified，站点提供的 Last-Modified 与源站一致
+            if let Some(modified) = meta
+                .last_modified
+                .as_deref()
+                .and_then(|v| httpdate::parse_http_date(v).ok())
+            {
+                let file = file.into_std().await;
+                let _ = file.set_modified(modified);
+            }
+            tokio::fs::rename(&temp, local)
+                .await
+                .map_err(|e| e.to_string())?;
+            self.write_meta(&meta).await.map_err(|e| e.to_string())
+        }
+        .await;
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&temp).await;
+            return Err(format!("failed to mirror {}: {}", uri, e));
+        }
+        Ok(Fetched::Stored(meta))
+    }
+
+    // 源站删除的文件同样删除；取回失败时继续提供旧文件，negative_ttl_secs 后再试
+    async fn revalidate(&self, raw: &str, relative: &str, local: &Path, meta: Meta) {
+        match self.fetch(raw, relative, local, Some(&meta)).await {
+            Ok(Fetched::Stored(_)) => debug!("Mirror refreshed {}", relative),
+            Ok(Fetched::NotModified) => {
+                let meta = Meta {
+                    fetched: unix_now(),
+                    ..meta
+                };
+                if let Err(e) = self.write_meta(&meta).await {
+                    warn!("Failed to update mirror record for {}: {}", relative, e);
+                }
+            }
+            Ok(Fetched::Missing(status))
+                if status == StatusCode::NOT_FOUND || status == StatusCode::GONE =>
+            {
+                info!(
+                    "Origin no longer has {}, removing the mirrored copy",
+                    relative
+                );
+                let _ = tokio::fs::remove_file(local).await;
+                let _ = tokio::fs::remove_file(self.meta_path(relative)).await;
+            }
+            result => {
+                if let Err(e) = result {
+                    warn!("{}, serving the mirrored copy", e);
+                }
+                let retry = self.ttl.saturating_sub(self.negative_ttl).as_secs();
+                let meta = Meta {
+                    fetched: unix_now().saturating_sub(retry),
+                    ..meta
+                };
+                let _ = self.write_meta(&meta).await;
+            }
+        }
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        upload::bearer_authorized(headers, self.token.as_deref())
+    }
+
+    // 删除 prefix 下取回的文件，下一次请求重新取回；返回删除的文件数
+    async fn purge(&self, prefix: &str) -> std::io::Result<usize> {
+        let prefix = prefix.trim_start_matches('/');
+        self.missing
+            .lock()
+            .unwrap()
+            .retain(|path, _| !path.starts_with(prefix));
+        let mut entries = match tokio::fs::read_dir(&self.meta_dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let mut purged = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(meta) = tokio::fs::read(&path)
+                .await
+                .ok()
+                .and_then(|data| serde_json::from_slice::<Meta>(&data).ok())
+            else {
+                continue;
+            };
+            if !meta.path.starts_with(prefix) {
+                continue;
+            }
+            if let Some(local) = site::fs_path(&self.dir, &meta.path) {
+                let _ = tokio::fs::remove_file(local).await;
+            }
+            tokio::fs::remove_file(&path).await?;
+            purged += 1;
+        }
+        Ok(purged)
+    }
+}
+
+// URL 路径 -> (转发给源站的路径, 相对主目录的路径, 本地文件)
+fn locate(dir: &Path, path: &str) -> Option<(String, String, PathBuf)> {
+    let raw = if path.ends_with('/') {
+        format!("{}{}", path, INDEX_FILE)
+    } else {
+        path.to_string()
+    };
+    let local = site::fs_path(dir, &raw)?;
+    let relative: Vec<String> = local
+        .strip_prefix(dir)
+        .ok()?
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if relative.is_empty() || relative.iter().any(|part| part.starts_with('.')) {
+        return None;
+    }
+    Some((raw, relative.join("/"), local))
+}
+
+// 在主站点之外执行：本地缺少的文件先取回，再交给站点按普通文件提供
+pub async fn handle(State(mirror): State<Arc<Mirror>>, req: Request, next: Next) -> Response {
+    if !(req.method() == Method::GET || req.method() == Method::HEAD) {
+        return next.run(req).await;
+    }
+    let Some((raw, relative, local)) = locate(&mirror.dir, req.uri().path()) else {
+        return next.run(req).await;
+    };
+    let meta = match tokio::fs::metadata(&local).await {
+        Ok(info) if info.is_file() => match mirror.read_meta(&relative).await {
+            Some(meta) if mirror.stale(&meta) => {
+                let lock = mirror.inflight(&relative);
+                let _guard = lock.lock().await;
+                // 等锁期间其他请求可能已经验证过
+                match mirror.read_meta(&relative).await {
+                    Some(meta) if mirror.stale(&meta) => {
+                        mirror.revalidate(&raw, &relative, &local, meta).await;
+                    }
+                    _ => {}
+                }
+                mirror.read_meta(&relative).await
+            }
+            meta => meta,
+        },
+        // 目录交给站点处理尾部斜杠与索引文件
+        Ok(_) => return next.run(req).await,
+        Err(_) if mirror.recently_missing(&relative) => return next.run(req).await,
+        Err(_) => {
+            let lock = mirror.inflight(&relative);
+            let _guard = lock.lock().await;
+            if tokio::fs::metadata(&local).await.is_ok() {
+                mirror.read_meta(&relative).await
+            } else {
+                match mirror.fetch(&raw, &relative, &local, None).await {
+                    Ok(Fetched::Stored(meta)) => {
+                        info!("Mirrored {} from {}", relative, mirror.origin);
+                        Some(meta)
+                    }
+                    Ok(Fetched::NotModified) => None,
+                    Ok(Fetched::Missing(status)) => {
+                        debug!("Origin returned {} for {}", status, relative);
+                        mirror.remember_missing(&relative);
+                        None
+                    }
+                    Err(e) => {
+                        warn!("{}", e);
+                        return StatusCode::BAD_GATEWAY.into_response();
+                    }
+                }
+            }
+        }
+    };
+    let mut response = next.run(req).await;
+    // 没有扩展名的文件按源站的 Content-Type 提供
+    let content_type = meta
+        .and_then(|meta| meta.content_type)
+        .filter(|_| local.extension().is_none())
+        .and_then(|v| HeaderValue::from_str(&v).ok());
+    if let Some(value) = content_type {
+        if response.status().is_success() {
+            response.headers_mut().insert(header::CONTENT_TYPE, value);
+        }
+    }
+    response
+}
+
+// POST ?path=/assets/ 清除该前缀下取回的文件，未指定时全部清除；主目录中原有的文件不受影响
+pub async fn purge(
+    State(mirror): State<Arc<Mirror>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if !mirror.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    let prefix = query.get("path").map(String::as_str).unwrap_or("/");
+    match mirror.purge(prefix).await {
+        Ok(purged) => {
+            info!("Purged {} mirrored files under {}", purged, prefix);
+            Json(json!({ "purged": purged })).into_response()
+        }
+        Err(e) => {
+            warn!("Failed to purge mirrored files under {}: {}", prefix, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({ "error": e.to_string() })),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// 大文件零拷贝发送：超过阈值的文件以内存映射方式返回，整个映射作为一个 Bytes 交给 hyper，避免逐块读取与复制
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::{header, HeaderValue, Method, Response, StatusCode};
+use memmap2::Mmap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+use tower::Service;
+use tracing::debug;
+
+// 包在 ServeDir 外面：GET/HEAD 且文件不小于 min_size 时直接映射文件；
+// 条件请求、无法满足的 Range 等交给 ServeDir 处理（多段 Range 已由 ranges::multipart 转换为完整请求）
+#[derive(Clone)]
+pub struct MmapService<S> {
+    inner: S,
+    min_size: Option<u64>,
+    dir: PathBuf,
+    precompressed: bool,
+}
+
+impl<S> MmapService<S> {
+    pub fn new(inner: S, min_size: Option<u64>, dir: &str, precompressed: bool) -> Self {
+        MmapService {
+            inner,
+            min_size,
+            dir: PathBuf::from(dir),
+            precompressed,
+        }
+    }
+}
+
+impl<S> Service<Request<Body>> for MmapService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let Some(min_size) = self.min_size else {
+            return Box::pin(inner.call(req));
+        };
+        let path = direct_path(&self.dir, &req);
+        // 启用旁路文件时记下 Accept-Encoding，存在匹配的预压缩版本则交给 ServeDir
+        let accept_encoding = req
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .filter(|_| self.precompressed)
+            .cloned();
+
+        Box::pin(async move {
+            let Some(path) = path else {
+                return inner.call(req).await;
+            };
+            if has_sidecar(&path, accept_encoding.as_ref()).await {
+                return inner.call(req).await;
+            }
+            let meta = match tokio::fs::metadata(&path).await {
+                Ok(meta) if meta.is_file() && meta.len() >= min_size => meta,
+                _ => return inner.call(req).await,
+            };
+            let len = meta.len();
+            let range = match req.headers().get(header::RANGE) {
+                Some(value) => match parse_range(value, len) {
+                    Some(range) => Some(range),
+                    // 无法满足的 Range 由 ServeDir 返回 416
+                    None => return inner.call(req).await,
+                },
+                None => None,
+            };
+
+            let mapped = {
+                let path = path.clone();
+                tokio::task::spawn_blocking(move || map_file(&path)).await
+            };
+            let bytes = match mapped {
+                Ok(Ok(bytes)) => bytes,
+                Ok(Err(e)) => {
+                    debug!("mmap {} failed, falling back: {}", path.display(), e);
+                    return inner.call(req).await;
+                }
+                Err(_) => return inner.call(req).await,
+            };
+            // 映射期间文件被截断时长度可能不一致，交给 ServeDir
+            if bytes.len() as u64 != len {
+                return inner.call(req).await;
+            }
+
+            let body = match &range {
+                Some(range) => bytes.slice(range.start as usize..range.end as usize),
+                None => bytes,
+            };
+            let mut response = file_response(&path, meta.len(), meta.modified().ok(), range);
+            if req.method() != Method::HEAD {
+                *response.body_mut() = Body::from(body);
+            }
+            Ok(response)
+        })
+    }
+}
+
+// 可以绕过 ServeDir 直接发送文件的请求：GET/HEAD 且不是条件请求，返回对应的文件路径
+pub fn direct_path(dir: &Path, req: &Request<Body>) -> Option<PathBuf> {
+    let eligible = (req.method() == Method::GET || req.method() == Method::HEAD)
+        && !req.headers().contains_key(header::IF_NONE_MATCH)
+        && !req.headers().contains_key(header::IF_MODIFIED_SINCE)
+        && !req.headers().contains_key(header::IF_RANGE);
+    crate::site::fs_path(dir, req.uri().path()).filter(|_| eligible)
+}
+
+// 请求接受的编码存在对应的预压缩旁路文件（.br / .zst / .gz）
+pub async fn has_sidecar(path: &Path, accept_encoding: Option<&HeaderValue>) -> bool {
+    let Some(accept) = accept_encoding.and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    for (encoding, extension) in [("br", "br"), ("zstd", "zst"), ("gzip", "gz")] {
+        let accepted = accept.split(',').any(|item| {
+            let mut params = item.split(';');
+            params.next().is_some_and(|name| name.trim() == encoding)
+                && !params.any(|p| matches!(p.trim(), "q=0" | "q=0.0" | "q=0.00" | "q=0.000"))
+        });
+        if !accepted {
+            continue;
+        }
+        let mut sidecar = path.as_os_str().to_owned();
+        sidecar.push(".");
+        sidecar.push(extension);
+        if tokio::fs::metadata(&sidecar)
+            .await
+            .is_ok_and(|m| m.is_file())
+        {
+            return true;
+        }
+    }
+    false
+}
+
+// 直接发送文件时的响应头（Content-Type、Last-Modified、Range 等），正文由调用方填充
+pub fn file_response(
+    path: &Path,
+    len: u64,
+    modified: Option<SystemTime>,
+    range: Option<Range<u64>>,
+) -> Response<Body> {
+    let mime = mime_guess::from_path(path)
+        .first_raw()
+        .unwrap_or("application/octet-stream");
+    let mut response = Response::new(Body::empty());
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(mime));
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    if let Some(modified) = modified {
+        if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(modified)) {
+            headers.insert(header::LAST_MODIFIED, value);
+        }
+    }
+    let content_length = match range {
+        Some(range) => {
+            let content_range = format!("bytes {}-{}/{}", range.start, range.end - 1, len);
+            if let Ok(value) = HeaderValue::from_str(&content_range) {
+                headers.insert(header::CONTENT_RANGE, value);
+            }
+            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            range.end - range.start
+        }
+        None => len,
+    };
+    response
+        .headers_mut()
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+    response
+}
+
+pub fn map_file(path: &Path) -> std::io::Result<Bytes> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: 静态文件在服务期间视为只读；发布时应替换文件（rename）而不是原地改写，
+    // 否则映射内容可能随之变化
+    let mmap = unsafe { Mmap::map(&file)? };
+    #[cfg(unix)]
+    let _ = mmap.advise(memmap2::Advice::Sequential);
+    Ok(Bytes::from_owner(mmap))
+}
+
+// 解析单段 "bytes=start-end" / "bytes=start-" / "bytes=-suffix"，返回 [start, end)
+pub fn parse_range(value: &HeaderValue, len: u64) -> Option<Range<u64>> {
+    let spec = value.to_str().ok()?.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            if suffix == 0 {
+                return None;
+            }
+            (len.saturating_sub(suffix), len)
+        }
+        (start, "") => (start.parse().ok()?, len),
+        (start, end) => {
+            let end: u64 = end.parse().ok()?;
+            (start.parse().ok()?, end.saturating_add(1).min(len))
+        }
+    };
+    (start < end && start < len).then_some(start..end)
+}
@@ -0,0 +1,811 @@
+// OpenID Connect 单点登录（[auth.oidc]）：未登录的浏览器请求跳转到身份提供方，授权码流程（PKCE）完成后以 HMAC 签名的 cookie 保持会话，
+// 内部文档放在企业 SSO 之后不需要另外部署认证代理。ID token 直接经 TLS 从 token 端点取得，按 OIDC Core 3.1.3.7
+// 校验 iss / aud / exp / nonce 而不校验签名；可按邮箱与用户组限制访问，修改限制后已有的会话随之失效
+use crate::basic_auth;
+use crate::cookies;
+use crate::forwarded::{self, ClientInfo};
+use crate::glob::PathPattern;
+use crate::login::{
+    form_value, is_https, no_store, random_bytes, redirect, safe_next, unix_now, wants_html,
+};
+use crate::site;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Response};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::{info, warn};
+
+// 授权流程的状态 cookie 有效期
+const FLOW_TTL: u64 = 600;
+// ID token 过期时间允许的时钟偏差
+const LEEWAY: u64 = 60;
+// 身份提供方响应体的大小上限
+const MAX_RESPONSE: usize = 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OidcConfig {
+    // 身份提供方，首次登录时读取 <issuer>/.well-known/openid-configuration
+    pub issuer: String,
+    pub client_id: String,
+    // 未设置时作为公开客户端，只依靠 PKCE
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub client_secret: Option<String>,
+    // 在身份提供方注册的回调地址，如 "https://docs.example.com/__oidc/callback"，其路径即回调路径；
+    // 未设置时按请求的 Host 与 callback_path 生成
+    #[serde(default)]
+    pub redirect_url: Option<String>,
+    #[serde(default = "default_callback_path")]
+    pub callback_path: String,
+    #[serde(default = "default_logout_path")]
+    pub logout_path: String,
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    // 允许的邮箱，"@example.com" 表示整个域名；与 allowed_groups 都为空时任何登录成功的用户都可访问
+    #[serde(default)]
+    pub allowed_emails: Vec<String>,
+    #[serde(default)]
+    pub allowed_groups: Vec<String>,
+    // ID token 或 userinfo 中用户组所在的声明
+    #[serde(default = "default_groups_claim")]
+    pub groups_claim: String,
+    // 需要登录的路径前缀
+    #[serde(default = "default_prefixes")]
+    pub prefixes: Vec<String>,
+    // 前缀下无需登录的路径（模式规则与 deny 相同）
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // 签名 cookie 的密钥；未设置时每次启动随机生成，重启后需要重新登录
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub secret: Option<String>,
+    #[serde(default = "default_session_ttl")]
+    pub session_ttl_secs: u64,
+    #[serde(default = "default_cookie_name")]
+    pub cookie_name: String,
+    // 请求身份提供方的超时时间
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_callback_path() -> String {
+    "/__oidc/callback".to_string()
+}
+
+fn default_logout_path() -> String {
+    "/__oidc/logout".to_string()
+}
+
+fn default_scopes() -> Vec<String> {
+    vec![
+        "openid".to_string(),
+        "email".to_string(),
+        "profile".to_string(),
+    ]
+}
+
+fn default_groups_claim() -> String {
+    "groups".to_string()
+}
+
+fn default_prefixes() -> Vec<String> {
+    vec!["/".to_string()]
+}
+
+fn default_session_ttl() -> u64 {
+    8 * 3600
+}
+
+fn default_cookie_name() -> String {
+    "sonicwave_oidc".to_string()
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+type HttpClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    #[serde(default)]
+    userinfo_endpoint: Option<String>,
+    #[serde(default)]
+    token_endpoint_auth_methods_supported: Vec<String>,
+}
+
+struct Provider {
+    // 发现文档中的 issuer，ID token 的 iss 必须与之完全相同
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: Option<String>,
+    // client_secret 放在 Basic 认证头中（client_secret_basic），否则放在表单中（client_secret_post）
+    basic_auth: bool,
+}
+
+// 跳转到身份提供方之前保存在状态 cookie 中，回调时取回
+#[derive(Serialize, Deserialize)]
+struct Flow {
+    state: String,
+    nonce: String,
+    verifier: String,
+    next: String,
+    redirect_uri: String,
+}
+
+#[derive(Deserialize)]
+struct Tokens {
+    id_token: String,
+    #[serde(default)]
+    access_token: Option<String>,
+}
+
+struct Identity {
+    subject: String,
+    email: Option<String>,
+    groups: Vec<String>,
+}
+
+impl Identity {
+    fn name(&self) -> &str {
+        self.email.as_deref().unwrap_or(&self.subject)
+    }
+}
+
+pub struct Oidc {
+    issuer: String,
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_url: Option<String>,
+    callback_path: String,
+    logout_path: String,
+    scope: String,
+    allowed_emails: Vec<String>,
+    allowed_groups: Vec<String>,
+    groups_claim: String,
+    prefixes: Vec<String>,
+    exclude: Vec<PathPattern>,
+    readiness_path: String,
+    key: Vec<u8>,
+    // 访问限制参与会话签名
+    policy: String,
+    ttl: u64,
+    cookie_name: String,
+    state_cookie: String,
+    timeout: Duration,
+    client: HttpClient,
+    // 首次登录时读取，失败时下次登录重试
+    provider: OnceCell<Provider>,
+}
+
+impl Oidc {
+    pub fn new(config: &OidcConfig, readiness_path: &str) -> Result<Self, String> {
+        let issuer = config.issuer.trim_end_matches('/').to_string();
+        check_url("issuer", &issuer)?;
+        if config.client_id.is_empty() {
+            return Err("[auth.oidc] client_id must not be empty".to_string());
+        }
+        if config.client_secret.as_deref() == Some("") {
+            return Err("[auth.oidc] client_secret must not be empty".to_string());
+        }
+        if !config.scopes.iter().any(|scope| scope == "openid") {
+            return Err("[auth.oidc] scopes must include `openid`".to_string());
+        }
+        let callback_path = match &config.redirect_url {
+            Some(url) => {
+                check_url("redirect_url", url)?;
+                url.parse::<Uri>()
+                    .map_err(|e| format!("[auth.oidc] invalid redirect_url `{}`: {}", url, e))?
+                    .path()
+                    .to_string()
+            }
+            None => config.callback_path.clone(),
+        };
+        for path in [&callback_path, &config.logout_path] {
+            if !path.starts_with('/') {
+                return Err(format!("[auth.oidc] `{}` must start with /", path));
+            }
+        }
+        if config.cookie_name.is_empty()
+            || !config
+                .cookie_name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+        {
+            return Err(format!(
+                "[auth.oidc] invalid cookie_name `{}`",
+                config.cookie_name
+            ));
+        }
+        let key = match &config.secret {
+            Some(secret) if secret.len() < 16 => {
+                return Err("[auth.oidc] secret must be at least 16 characters".to_string());
+            }
+            Some(secret) => secret.as_bytes().to_vec(),
+            // 每个工作进程各自生成的密钥无法验证其他进程签发的 cookie
+            None if crate::supervisor::worker_id().is_some() => {
+                return Err("[auth.oidc] secret is required in supervisor mode".to_string());
+            }
+            None => {
+                warn!("[auth.oidc] secret is not set, sessions end when the server restarts");
+                random_bytes(32)?
+            }
+        };
+        let exclude = config
+            .exclude
+            .iter()
+            .map(|pattern| PathPattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let allowed_emails: Vec<String> = config
+            .allowed_emails
+            .iter()
+            .map(|email| email.trim().to_ascii_lowercase())
+            .collect();
+        let policy = format!(
+            "{}\n{}\n{}",
+            allowed_emails.join(","),
+            config.allowed_groups.join(","),
+            config.groups_claim
+        );
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(connector);
+        Ok(Oidc {
+            issuer,
+            client_id: config.client_id.clone(),
+            client_secret: config.client_secret.clone(),
+            redirect_url: config.redirect_url.clone(),
+            callback_path,
+            logout_path: config.logout_path.clone(),
+            scope: config.scopes.join(" "),
+            allowed_emails,
+            allowed_groups: config.allowed_groups.clone(),
+            groups_claim: config.groups_claim.clone(),
+            prefixes: config.prefixes.clone(),
+            exclude,
+            readiness_path: readiness_path.to_string(),
+            key,
+            policy,
+            ttl: config.session_ttl_secs,
+            state_cookie: format!("{}_state", config.cookie_name),
+            cookie_name: config.cookie_name.clone(),
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+            client: Client::builder(TokioExecutor::new()).build(https),
+            provider: OnceCell::new(),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{} on {:?}", self.issuer, self.prefixes)
+    }
+
+    fn protected(&self, path: &str) -> bool {
+        let path = site::normalize_path(path);
+        if path == self.readiness_path
+            || !self
+                .prefixes
+                .iter()
+                .any(|prefix| path.starts_with(prefix.as_str()))
+        {
+            return false;
+        }
+        !self.exclude.iter().any(|pattern| pattern.matches(&path))
+    }
+
+    async fn provider(&self) -> Result<&Provider, String> {
+        self.provider.get_or_try_init(|| self.discover()).await
+    }
+
+    async fn discover(&self) -> Result<Provider, String> {
+        let uri = format!("{}/.well-known/openid-configuration", self.issuer);
+        let request = Request::builder()
+            .uri(&uri)
+            .header(header::ACCEPT, "application/json")
+            .body(Body::empty())
+            .map_err(|e| format!("invalid discovery URL {}: {}", uri, e))?;
+        let discovery: Discovery = serde_json::from_value(self.fetch_json(request).await?)
+            .map_err(|e| format!("invalid discovery document {}: {}", uri, e))?;
+        if discovery.issuer.trim_end_matches('/') != self.issuer {
+            return Err(format!(
+                "discovery document {} is for issuer `{}`",
+                uri, discovery.issuer
+            ));
+        }
+        info!("OIDC provider {} discovered", discovery.issuer);
+        let methods = &discovery.token_endpoint_auth_methods_supported;
+        Ok(Provider {
+            basic_auth: methods.is_empty() || methods.iter().any(|m| m == "client_secret_basic"),
+            issuer: discovery.issuer,
+            authorization_endpoint: discovery.authorization_endpoint,
+            token_endpoint: discovery.token_endpoint,
+            userinfo_endpoint: discovery.userinfo_endpoint,
+        })
+    }
+
+    async fn fetch_json(&self, request: Request) -> Result<Value, String> {
+        let uri = request.uri().to_string();
+        let response = tokio::time::timeout(self.timeout, self.client.request(request))
+            .await
+            .map_err(|_| format!("timed out fetching {}", uri))?
+            .map_err(|e| format!("failed to fetch {}: {}", uri, e))?;
+        let status = response.status();
+        let body = axum::body::to_bytes(Body::new(response.into_body()), MAX_RESPONSE)
+            .await
+            .map_err(|e| format!("failed to read {}: {}", uri, e))?;
+        if !status.is_success() {
+            let text = String::from_utf8_lossy(&body);
+            return Err(format!(
+                "{} returned {}: {}",
+                uri,
+                status,
+                text.chars().take(200).collect::<String>()
+            ));
+        }
+        serde_json::from_slice(&body).map_err(|e| format!("invalid JSON from {}: {}", uri, e))
+    }
+
+    fn sign(&self, kind: &str, payload: &str) -> Vec<u8> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        for part in [kind, &self.policy, payload] {
+            mac.update(part.as_bytes());
+            mac.update(b"\n");
+        }
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    // "<base64url 内容>.<过期时间>.<签名>"，kind 区分会话与授权流程的 cookie
+    fn seal(&self, kind: &str, data: &str, ttl: u64) -> String {
+        let payload = format!("{}.{}", URL_SAFE_NO_PAD.encode(data), unix_now() + ttl);
+        let signature = self.sign(kind, &payload);
+        format!("{}.{}", payload, URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    fn open(&self, kind: &str, value: &str) -> Option<String> {
+        let (payload, signature) = value.rsplit_once('.')?;
+        let (data, expires) = payload.split_once('.')?;
+        if expires.parse::<u64>().ok()? <= unix_now() {
+            return None;
+        }
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+        if !crate::upload::constant_time_eq(&self.sign(kind, payload), &signature) {
+            return None;
+        }
+        String::from_utf8(URL_SAFE_NO_PAD.decode(data).ok()?).ok()
+    }
+
+    fn session(&self, req: &Request) -> Option<String> {
+        self.open("session", cookies::get(req.headers(), &self.cookie_name)?)
+    }
+
+    fn redirect_uri(&self, req: &Request) -> String {
+        match &self.redirect_url {
+            Some(url) => url.clone(),
+            None => format!(
+                "{}{}",
+                forwarded::origin(req.headers(), req.extensions().get::<ClientInfo>()),
+                self.callback_path
+            ),
+        }
+    }
+
+    // 跳转到身份提供方的授权端点
+    async fn begin(&self, next: String, redirect_uri: String, https: bool) -> Response {
+        let provider = match self.provider().await {
+            Ok(provider) => provider,
+            Err(e) => {
+                warn!("OIDC discovery failed: {}", e);
+                return page(
+                    StatusCode::BAD_GATEWAY,
+                    "The sign-in provider is unavailable. Please try again later.",
+                );
+            }
+        };
+        let random = || random_bytes(32).map(|bytes| URL_SAFE_NO_PAD.encode(bytes));
+        let (Ok(state), Ok(nonce), Ok(verifier)) = (random(), random(), random()) else {
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        };
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()));
+        let flow = Flow {
+            next,
+            redirect_uri,
+            state,
+            nonce,
+            verifier,
+        };
+        let separator = if provider.authorization_endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        let location = format!(
+            "{}{}response_type=code&client_id={}&redirect_uri={}&scope={}&state={}&nonce={}&code_challenge={}&code_challenge_method=S256",
+            provider.authorization_endpoint,
+            separator,
+            encode(&self.client_id),
+            encode(&flow.redirect_uri),
+            encode(&self.scope),
+            flow.state,
+            flow.nonce,
+            challenge
+        );
+        let sealed = self.seal(
+            "flow",
+            &serde_json::to_string(&flow).expect("flow state serializes"),
+            FLOW_TTL,
+        );
+        let mut response = redirect(&location);
+        response.headers_mut().append(
+            header::SET_COOKIE,
+            set_cookie(
+                &self.state_cookie,
+                &sealed,
+                &self.callback_path,
+                FLOW_TTL,
+                https,
+            ),
+        );
+        response
+    }
+
+    // 身份提供方带着 code 与 state 跳转回来
+    async fn callback(&self, req: Request) -> Response {
+        let client = req
+            .extensions()
+            .get::<ClientInfo>()
+            .and_then(|info| info.ip)
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let https = is_https(&req);
+        let query = req.uri().query().unwrap_or("");
+        let flow = cookies::get(req.headers(), &self.state_cookie)
+            .and_then(|value| self.open("flow", value))
+            .and_then(|json| serde_json::from_str::<Flow>(&json).ok());
+        let Some(flow) = flow else {
+            return page(
+                StatusCode::BAD_REQUEST,
+                "The sign-in attempt has expired. Please go back and try again.",
+            );
+        };
+        if let Some(error) = form_value(query, "error") {
+            warn!(
+                "OIDC sign-in from {} returned `{}`: {}",
+                client,
+                error,
+                form_value(query, "error_description").unwrap_or_default()
+            );
+            return page(StatusCode::FORBIDDEN, "Sign-in was cancelled or denied.");
+        }
+        let state = form_value(query, "state").unwrap_or_default();
+        let code = form_value(query, "code").unwrap_or_default();
+        if code.is_empty()
+            || !crate::upload::constant_time_eq(state.as_bytes(), flow.state.as_bytes())
+        {
+            return page(
+                StatusCode::BAD_REQUEST,
+                "Invalid sign-in response. Please go back and try again.",
+            );
+        }
+        let identity = match self.exchange(&code, &flow).await {
+            Ok(identity) => identity,
+            Err(e) => {
+                warn!("OIDC sign-in from {} failed: {}", client, e);
+                return page(
+                    StatusCode::BAD_GATEWAY,
+                    "Sign-in failed. Please try again later.",
+                );
+            }
+        };
+        if !self.allowed(&identity) {
+            warn!(
+                "`{}` is not allowed to sign in (from {})",
+                identity.name(),
+                client
+            );
+            return page(
+                StatusCode::FORBIDDEN,
+                "Your account is not allowed to access this site.",
+            );
+        }
+        info!("`{}` signed in via OIDC from {}", identity.name(), client);
+        let session = self.seal("session", identity.name(), self.ttl);
+        let mut response = redirect(safe_next(&flow.next));
+        let headers = response.headers_mut();
+        headers.append(
+            header::SET_COOKIE,
+            set_cookie(&self.cookie_name, &session, "/", self.ttl, https),
+        );
+        headers.append(
+            header::SET_COOKIE,
+            set_cookie(&self.state_cookie, "", &self.callback_path, 0, https),
+        );
+        response
+    }
+
+    // 用 code 换取 ID token，校验后取出用户信息
+    async fn exchange(&self, code: &str, flow: &Flow) -> Result<Identity, String> {
+        let provider = self.provider().await?;
+        let mut form = format!(
+            "grant_type=authorization_code&code={}&redirect_uri={}&code_verifier={}",
+            encode(code),
+            encode(&flow.redirect_uri),
+            encode(&flow.verifier)
+        );
+        let mut builder = Request::builder()
+            .method(Method::POST)
+            .uri(&provider.token_endpoint)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::ACCEPT, "application/json");
+        match &self.client_secret {
+            Some(secret) if provider.basic_auth => {
+                // RFC 6749 2.3.1：用户名与密码先做表单编码
+                let credentials = format!("{}:{}", encode(&self.client_id), encode(secret));
+                builder = builder.header(
+                    header::AUTHORIZATION,
+                    format!("Basic {}", STANDARD.encode(credentials)),
+                );
+            }
+            Some(secret) => {
+                form.push_str(&format!(
+                    "&client_id={}&client_secret={}",
+                    encode(&self.client_id),
+                    encode(secret)
+                ));
+            }
+            None => form.push_str(&format!("&client_id={}", encode(&self.client_id))),
+        }
+        let request = builder
+            .body(Body::from(form))
+            .map_err(|e| format!("invalid token endpoint: {}", e))?;
+        let tokens: Tokens = serde_json::from_value(self.fetch_json(request).await?)
+            .map_err(|e| format!("invalid token response: {}", e))?;
+        let mut claims = id_token_claims(&tokens.id_token)?;
+        self.validate(provider, &claims, &flow.nonce)?;
+
+        // ID token 中缺少邮箱或用户组时向 userinfo 端点补充
+        let needs_groups =
+            !self.allowed_groups.is_empty() && !claims.contains_key(&self.groups_claim);
+        if !claims.contains_key("email") || needs_groups {
+            if let (Some(endpoint), Some(access_token)) =
+                (&provider.userinfo_endpoint, &tokens.access_token)
+            {
+                let request = Request::builder()
+                    .uri(endpoint)
+                    .header(header::ACCEPT, "application/json")
+                    .header(header::AUTHORIZATION, format!("Bearer {}", access_token))
+                    .body(Body::empty())
+                    .map_err(|e| format!("invalid userinfo endpoint: {}", e))?;
+                let Value::Object(userinfo) = self.fetch_json(request).await? else {
+                    return Err("userinfo response is not a JSON object".to_string());
+                };
+                if userinfo.get("sub") != claims.get("sub") {
+                    return Err("userinfo response is for a different subject".to_string());
+                }
+                for (key, value) in userinfo {
+                    claims.entry(key).or_insert(value);
+                }
+            }
+        }
+        Ok(self.identity(&claims))
+    }
+
+    fn validate(
+        &self,
+        provider: &Provider,
+        claims: &Map<String, Value>,
+        nonce: &str,
+    ) -> Result<(), String> {
+        let text = |key: &str| claims.get(key).and_then(Value::as_str);
+        if text("iss") != Some(provider.issuer.as_str()) {
+            return Err(format!("ID token issuer is {:?}", text("iss")));
+        }
+        let audience = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == &self.client_id,
+            Some(Value::Array(auds)) => auds
+                .iter()
+                .any(|aud| aud.as_str() == Some(self.client_id.as_str())),
+            _ => false,
+        };
+        if !audience {
+            return Err("ID token is not issued for this client_id".to_string());
+        }
+        let expires = claims.get("exp").and_then(Value::as_u64).unwrap_or(0);
+        if expires + LEEWAY <= unix_now() {
+            return Err("ID token has expired".to_string());
+        }
+        if !text("nonce").is_some_and(|claim| {
+            crate::upload::constant_time_eq(claim.as_bytes(), nonce.as_bytes())
+        }) {
+            return Err("ID token nonce does not match".to_string());
+        }
+        if text("sub").is_none_or(str::is_empty) {
+            return Err("ID token has no subject".to_string());
+        }
+        Ok(())
+    }
+
+    fn identity(&self, claims: &Map<String, Value>) -> Identity {
+        // 明确标记为未验证的邮箱不用于访问控制
+        let verified = claims.get("email_verified") != Some(&Value::Bool(false));
+        let email = claims
+            .get("email")
+            .and_then(Value::as_str)
+            .filter(|_| verified)
+            .map(str::to_ascii_lowercase);
+        let groups = match claims.get(&self.groups_claim) {
+            Some(Value::Array(groups)) => groups
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            Some(Value::String(group)) => vec![group.clone()],
+            _ => Vec::new(),
+        };
+        Identity {
+            subject: claims
+                .get("sub")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            email,
+            groups,
+        }
+    }
+
+    fn allowed(&self, identity: &Identity) -> bool {
+        if self.allowed_emails.is_empty() && self.allowed_groups.is_empty() {
+            return true;
+        }
+        let email = identity.email.as_deref().is_some_and(|email| {
+            self.allowed_emails.iter().any(|allowed| {
+                if allowed.starts_with('@') {
+                    email.ends_with(allowed.as_str())
+                } else {
+                    email == allowed
+                }
+            })
+        });
+        email
+            || identity
+                .groups
+                .iter()
+                .any(|group| self.allowed_groups.contains(group))
+    }
+}
+
+// 身份提供方必须使用 https，本机地址上测试用的 http 除外
+fn check_url(name: &str, url: &str) -> Result<(), String> {
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|e| format!("[auth.oidc] invalid {} `{}`: {}", name, url, e))?;
+    let host = uri.host().unwrap_or("");
+    let local = matches!(host, "localhost" | "127.0.0.1" | "[::1]");
+    match uri.scheme_str() {
+        Some("https") => Ok(()),
+        Some("http") if local => Ok(()),
+        _ => Err(format!(
+            "[auth.oidc] {} `{}` must be an https:// URL",
+            name, url
+        )),
+    }
+}
+
+// JWT 的第二段为 base64url 编码的 JSON 声明
+fn id_token_claims(token: &str) -> Result<Map<String, Value>, String> {
+    let mut parts = token.split('.');
+    let (Some(_), Some(payload), Some(_), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err("ID token is not a JWT".to_string());
+    };
+    let payload = URL_SAFE_NO_PAD
+        .decode(payload.trim_end_matches('='))
+        .map_err(|_| "ID token payload is not base64url".to_string())?;
+    match serde_json::from_slice(&payload) {
+        Ok(Value::Object(claims)) => Ok(claims),
+        _ => Err("ID token payload is not a JSON object".to_string()),
+    }
+}
+
+fn encode(value: &str) -> String {
+    utf8_percent_encode(value, NON_ALPHANUMERIC).to_string()
+}
+
+fn set_cookie(name: &str, value: &str, path: &str, max_age: u64, https: bool) -> HeaderValue {
+    let secure = if https { "; Secure" } else { "" };
+    let cookie = format!(
+        "{}={}; Path={}; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        name, value, path, max_age, secure
+    );
+    HeaderValue::from_str(&cookie).expect("cookie values are ASCII")
+}
+
+fn page(status: StatusCode, message: &str) -> Response {
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>Sign in</title>
+<style>body{{font-family:system-ui,sans-serif;max-width:28rem;margin:15vh auto;padding:0 1rem;color:#333;text-align:center}}</style>
+</head>
+<body>
+<p>{}</p>
+<p><a href="/">Back to the site</a></p>
+</body>
+</html>
+"#,
+        escape(message)
+    );
+    no_store((status, Html(html)).into_response())
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+pub async fn check(State(oidc): State<Arc<Oidc>>, req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    if path == oidc.callback_path {
+        return match *req.method() {
+            Method::GET | Method::HEAD => oidc.callback(req).await,
+            _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+        };
+    }
+    // 只结束本站的会话，身份提供方的登录状态不受影响
+    if path == oidc.logout_path {
+        let mut response = page(StatusCode::OK, "You have been signed out.");
+        let cookie = set_cookie(&oidc.cookie_name, "", "/", 0, is_https(&req));
+        response.headers_mut().insert(header::SET_COOKIE, cookie);
+        return response;
+    }
+    if !oidc.protected(path) {
+        return next.run(req).await;
+    }
+    if oidc.session(&req).is_some() {
+        let mut response = next.run(req).await;
+        basic_auth::private(response.headers_mut());
+        return response;
+    }
+    // 浏览器的页面请求跳转到身份提供方，其余请求返回 401
+    if matches!(*req.method(), Method::GET | Method::HEAD) && wants_html(req.headers()) {
+        let target = req
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str().to_string())
+            .unwrap_or_else(|| "/".to_string());
+        let redirect_uri = oidc.redirect_uri(&req);
+        return oidc.begin(target, redirect_uri, is_https(&req)).await;
+    }
+    no_store(StatusCode::UNAUTHORIZED.into_response())
+}
@@ -0,0 +1,174 @@
+// 路径匹配策略（[path_matching]）：精确路径不存在时，按不区分大小写（从 Windows / IIS 迁移的内容）
+// 和 / 或 Unicode 规范等价（macOS 上保存的 NFD 文件名与浏览器发送的 NFC 路径）逐段查找磁盘上的文件，
+// 可选 301 到磁盘上的实际写法。只用于磁盘上的站点
+use crate::unicode_tables::{COMBINING_CLASSES, DECOMPOSITIONS};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// URL 路径段中需要编码的字符
+const SEGMENT_CHARS: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct PathMatchConfig {
+    // /Assets/App.JS 匹配 assets/app.js
+    #[serde(default)]
+    pub case_insensitive: bool,
+    // 规范等价的 Unicode 写法（如 "é" 与 "e" + U+0301）视为同一个名字
+    #[serde(default)]
+    pub unicode: bool,
+    // 301 到磁盘上的实际写法，而不是直接返回文件
+    #[serde(default)]
+    pub redirect: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PathMatch {
+    case_insensitive: bool,
+    unicode: bool,
+    pub redirect: bool,
+}
+
+impl PathMatch {
+    // 两项都未开启时返回 None
+    pub fn new(config: &PathMatchConfig) -> Option<Self> {
+        (config.case_insensitive || config.unicode).then_some(PathMatch {
+            case_insensitive: config.case_insensitive,
+            unicode: config.unicode,
+            redirect: config.redirect,
+        })
+    }
+
+    fn key(&self, name: &str) -> String {
+        let chars: Vec<char> = if self.unicode {
+            decompose(name)
+        } else {
+            name.chars().collect()
+        };
+        if self.case_insensitive {
+            chars.into_iter().flat_map(char::to_lowercase).collect()
+        } else {
+            chars.into_iter().collect()
+        }
+    }
+
+    // 把原始 URL 路径（未解码）对应到 dir 下实际存在的写法，返回新的 URL 路径；
+    // 找不到或已经是实际写法时返回 None。逐段读取目录，在阻塞线程中调用
+    pub fn find(&self, dir: &Path, path: &str) -> Option<String> {
+        let mut current = dir.to_path_buf();
+        let mut rewritten = String::new();
+        let mut changed = false;
+        for raw in path.split('/') {
+            if raw.is_empty() {
+                continue;
+            }
+            let segment = percent_decode_str(raw).decode_utf8().ok()?;
+            if segment == "." || segment == ".." || segment.contains(['/', '\\', '\0']) {
+                return None;
+            }
+            rewritten.push('/');
+            let exact = current.join(segment.as_ref());
+            if std::fs::symlink_metadata(&exact).is_ok() {
+                rewritten.push_str(raw);
+                current = exact;
+                continue;
+            }
+            let name = self.entry(&current, &segment)?;
+            rewritten.push_str(&utf8_percent_encode(&name, SEGMENT_CHARS).to_string());
+            current.push(name);
+            changed = true;
+        }
+        if path.ends_with('/') && !rewritten.is_empty() {
+            rewritten.push('/');
+        }
+        changed.then_some(rewritten)
+    }
+
+    // 目录中与 segment 等价的条目；有多个时取名字最小的一个，结果与目录顺序无关
+    fn entry(&self, dir: &Path, segment: &str) -> Option<String> {
+        let wanted = self.key(segment);
+        std::fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| self.key(name) == wanted)
+            .min()
+    }
+}
+
+// 规范分解（NFD）：分解后组合符号按组合类稳定排序，规范等价的字符串结果相同
+fn decompose(s: &str) -> Vec<char> {
+    let mut out = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        decompose_char(c, &mut out);
+    }
+    let mut i = 0;
+    while i < out.len() {
+        if combining_class(out[i]) == 0 {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < out.len() && combining_class(out[i]) != 0 {
+            i += 1;
+        }
+        out[start..i].sort_by_key(|c| combining_class(*c));
+    }
+    out
+}
+
+fn decompose_char(c: char, out: &mut Vec<char>) {
+    // 韩文音节按 Unicode 第 3.12 节的算法分解
+    const S_BASE: u32 = 0xac00;
+    const L_BASE: u32 = 0x1100;
+    const V_BASE: u32 = 0x1161;
+    const T_BASE: u32 = 0x11a7;
+    const T_COUNT: u32 = 28;
+    const N_COUNT: u32 = 21 * T_COUNT;
+    let code = c as u32;
+    if (S_BASE..S_BASE + 19 * N_COUNT).contains(&code) {
+        let index = code - S_BASE;
+        let jamo = [
+            L_BASE + index / N_COUNT,
+            V_BASE + index % N_COUNT / T_COUNT,
+            T_BASE + index % T_COUNT,
+        ];
+        for (i, code) in jamo.into_iter().enumerate() {
+            if i < 2 || code != T_BASE {
+                out.extend(char::from_u32(code));
+            }
+        }
+        return;
+    }
+    match DECOMPOSITIONS.binary_search_by_key(&c, |(composed, _)| *composed) {
+        Ok(i) => out.extend_from_slice(DECOMPOSITIONS[i].1),
+        Err(_) => out.push(c),
+    }
+}
+
+fn combining_class(c: char) -> u8 {
+    match COMBINING_CLASSES.binary_search_by(|(start, end, _)| {
+        if *end < c {
+            std::cmp::Ordering::Less
+        } else if *start > c {
+            std::cmp::Ordering::Greater
+        } else {
+            std::cmp::Ordering::Equal
+        }
+    }) {
+        Ok(i) => COMBINING_CLASSES[i].2,
+        Err(_) => 0,
+    }
+}
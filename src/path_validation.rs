@@ -0,0 +1,133 @@
+// 请求路径校验（[path_validation]）：在路由与文件服务之前拒绝可疑的路径，返回 400 并计入指标。
+// basic 拒绝空字节、反斜杠、编码后的路径分隔符与 ".."、过长的路径段与过深的路径；
+// strict 另外拒绝非法 UTF-8（包括 %c0%ae 这类超长编码）、控制字符、二次编码、"." 与空路径段
+use crate::metrics::METRICS;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strictness {
+    #[default]
+    Basic,
+    Strict,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct PathValidationConfig {
+    #[serde(default)]
+    pub strictness: Strictness,
+    // 路径段数上限
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+    // 单个路径段解码后的字节数上限
+    #[serde(default = "default_max_component")]
+    pub max_component: usize,
+}
+
+fn default_max_depth() -> usize {
+    32
+}
+
+fn default_max_component() -> usize {
+    255
+}
+
+pub struct PathValidation {
+    strict: bool,
+    max_depth: usize,
+    max_component: usize,
+}
+
+impl PathValidation {
+    pub fn new(config: &PathValidationConfig) -> Result<Self, String> {
+        if config.max_depth == 0 || config.max_component == 0 {
+            return Err(
+                "[path_validation] max_depth and max_component must be greater than 0".to_string(),
+            );
+        }
+        Ok(PathValidation {
+            strict: config.strictness == Strictness::Strict,
+            max_depth: config.max_depth,
+            max_component: config.max_component,
+        })
+    }
+
+    // 返回拒绝的原因
+    fn check(&self, path: &str) -> Result<(), &'static str> {
+        if path.contains('\\') {
+            return Err("backslash");
+        }
+        if self.strict && double_encoded(path) {
+            return Err("double encoding");
+        }
+        let segments: Vec<&str> = path.strip_prefix('/').unwrap_or(path).split('/').collect();
+        if segments.len() > self.max_depth {
+            return Err("too many segments");
+        }
+        let last = segments.len() - 1;
+        for (i, raw) in segments.iter().enumerate() {
+            let decoded: Vec<u8> = percent_decode_str(raw).collect();
+            if decoded.contains(&0) {
+                return Err("null byte");
+            }
+            if decoded.contains(&b'/') || decoded.contains(&b'\\') {
+                return Err("encoded separator");
+            }
+            if decoded == b".." {
+                return Err("traversal");
+            }
+            if decoded.len() > self.max_component {
+                return Err("segment too long");
+            }
+            if !self.strict {
+                continue;
+            }
+            let Ok(decoded) = std::str::from_utf8(&decoded) else {
+                return Err("invalid UTF-8");
+            };
+            if decoded.chars().any(char::is_control) {
+                return Err("control character");
+            }
+            // 末尾的空段是目录的斜杠
+            if decoded == "." || (decoded.is_empty() && i != last) {
+                return Err("dot or empty segment");
+            }
+        }
+        Ok(())
+    }
+}
+
+// "%252e" 之类解码一次后仍是百分号编码
+fn double_encoded(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.windows(5).any(|w| {
+        w[0] == b'%'
+            && w[1] == b'2'
+            && w[2] == b'5'
+            && w[3].is_ascii_hexdigit()
+            && w[4].is_ascii_hexdigit()
+    })
+}
+
+pub async fn check(
+    State(validation): State<Arc<PathValidation>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    match validation.check(req.uri().path()) {
+        Ok(()) => next.run(req).await,
+        Err(reason) => {
+            debug!("Rejected request path {} ({})", req.uri().path(), reason);
+            METRICS.record_path_rejected();
+            (StatusCode::BAD_REQUEST, "Invalid request path\n").into_response()
+        }
+    }
+}
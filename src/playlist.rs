@@ -0,0 +1,173 @@
+// 播放列表（[playlist]）：GET /music/playlist.m3u（.m3u8 / .pls）列出目录中的音频文件，
+// 条目为绝对 URL，播放器可以直接串流整个目录
+use crate::audio;
+use axum::body::Body;
+use axum::http::header::{self, HeaderValue};
+use axum::response::Response;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct PlaylistConfig {
+    // 播放列表的文件名（不含扩展名），目录中存在同名文件时返回该文件
+    #[serde(default = "default_name")]
+    pub name: String,
+    // 包含子目录中的文件，可用 ?recursive=0 / 1 覆盖
+    #[serde(default)]
+    pub recursive: bool,
+    // 条目顺序，可用 ?sort= 覆盖
+    #[serde(default)]
+    pub sort: Sort,
+    // 单个播放列表的条目数上限
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sort {
+    // 按路径排序
+    #[default]
+    Name,
+    // 按修改时间，旧的在前
+    Modified,
+    // 按修改时间，新的在前
+    ModifiedDesc,
+}
+
+fn default_name() -> String {
+    "playlist".to_string()
+}
+
+fn default_max_entries() -> usize {
+    10_000
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    M3u,
+    M3u8,
+    Pls,
+}
+
+impl Format {
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::M3u | Format::M3u8 => "audio/x-mpegurl; charset=utf-8",
+            Format::Pls => "audio/x-scpls; charset=utf-8",
+        }
+    }
+}
+
+pub struct PlaylistEntry {
+    // 相对目录的路径，用作默认排序键
+    pub name: String,
+    // 站点内的 URL 路径（已编码）
+    pub url: String,
+    pub path: PathBuf,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl PlaylistConfig {
+    // /music/playlist.m3u 返回目录路径 "/music/" 与格式
+    pub fn requested<'a>(&self, path: &'a str) -> Option<(&'a str, Format)> {
+        let (dir, file) = path.split_at(path.rfind('/')? + 1);
+        let (stem, ext) = file.rsplit_once('.')?;
+        if stem != self.name {
+            return None;
+        }
+        let format = match ext.to_ascii_lowercase().as_str() {
+            "m3u" => Format::M3u,
+            "m3u8" => Format::M3u8,
+            "pls" => Format::Pls,
+            _ => return None,
+        };
+        Some((dir, format))
+    }
+
+    // 查询参数覆盖 recursive 与 sort，无法识别的值按配置处理
+    pub fn options(&self, query: Option<&str>) -> (bool, Sort) {
+        let mut recursive = self.recursive;
+        let mut sort = self.sort;
+        for pair in query.unwrap_or_default().split('&') {
+            match pair.split_once('=') {
+                Some(("recursive", "1" | "true")) => recursive = true,
+                Some(("recursive", "0" | "false")) => recursive = false,
+                Some(("sort", "name")) => sort = Sort::Name,
+                Some(("sort", "modified")) => sort = Sort::Modified,
+                Some(("sort", "modified-desc")) => sort = Sort::ModifiedDesc,
+                _ => {}
+            }
+        }
+        (recursive, sort)
+    }
+}
+
+fn title(name: &str) -> &str {
+    let file = name.rsplit('/').next().unwrap_or(name);
+    file.rsplit_once('.').map_or(file, |(stem, _)| stem)
+}
+
+// 生成播放列表正文；时长在阻塞线程中从文件头读取，未知时写 -1
+pub async fn render(
+    format: Format,
+    mut entries: Vec<PlaylistEntry>,
+    sort: Sort,
+    origin: &str,
+) -> Response {
+    // 先按路径排序，修改时间相同的条目保持路径顺序
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    match sort {
+        Sort::Name => {}
+        Sort::Modified => entries.sort_by_key(|entry| entry.modified),
+        Sort::ModifiedDesc => entries.sort_by_key(|entry| Reverse(entry.modified)),
+    }
+    let origin = origin.to_string();
+    let body = tokio::task::spawn_blocking(move || {
+        let mut body = String::new();
+        match format {
+            Format::M3u | Format::M3u8 => body.push_str("#EXTM3U\n"),
+            Format::Pls => body.push_str("[playlist]\n"),
+        }
+        for (i, entry) in entries.iter().enumerate() {
+            let secs = audio::duration(&entry.path, entry.len)
+                .ok()
+                .flatten()
+                .map_or(-1, |secs| secs as i64);
+            // 标题中的换行会破坏逐行的格式
+            let title = title(&entry.name).replace(['\r', '\n'], " ");
+            let url = format!("{}{}", origin, entry.url);
+            match format {
+                Format::M3u | Format::M3u8 => {
+                    body.push_str(&format!("#EXTINF:{},{}\n{}\n", secs, title, url));
+                }
+                Format::Pls => body.push_str(&format!(
+                    "File{n}={}\nTitle{n}={}\nLength{n}={}\n",
+                    url,
+                    title,
+                    secs,
+                    n = i + 1
+                )),
+            }
+        }
+        if format == Format::Pls {
+            body.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+        }
+        body
+    })
+    .await
+    .unwrap_or_default();
+
+    let mut response = Response::new(Body::from(body));
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static(format.content_type()),
+    );
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
@@ -0,0 +1,323 @@
+// WASM 插件（[[plugin]]，cargo feature "wasm"）：对匹配路径的请求调用模块导出的 on_request / on_response，
+// 可以增删请求头与响应头，或直接返回响应。模块只能导出、不能导入任何函数（没有 WASI），
+// 每次调用都在新的实例中运行，并限制 fuel 与线性内存。
+//
+// 模块需要导出 memory 与 alloc(len: i32) -> i32，钩子的签名为 (ptr: i32, len: i32) -> i64：
+// 输入是写在 alloc 返回位置的 JSON { method, path, query, headers }（on_response 另有 status），
+// 返回 0 表示不做修改，否则为 (ptr << 32) | len，指向 JSON 结果
+// { set_headers: { 名称: 值 }, remove_headers: [名称], response: { status, headers, body } }，
+// 带 response 时直接返回该响应（on_response 中替换原响应）
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct PluginConfig {
+    // 模块文件：.wasm，或者文本格式的 .wat
+    pub module: PathBuf,
+    // 路径模式，规则与 download 相同，如 "/api/**"、"*.html"；为空时处理所有请求
+    #[serde(default)]
+    pub paths: Vec<String>,
+    // 每次调用可消耗的 fuel（大致相当于执行的指令数），用完时中止调用并返回 500
+    #[serde(default = "default_fuel")]
+    pub fuel: u64,
+    // 线性内存上限（MiB）
+    #[serde(default = "default_memory_mb")]
+    pub memory_mb: usize,
+}
+
+fn default_fuel() -> u64 {
+    10_000_000
+}
+
+fn default_memory_mb() -> usize {
+    16
+}
+
+#[cfg(feature = "wasm")]
+pub use host::{apply, Plugins};
+
+#[cfg(not(feature = "wasm"))]
+pub struct Plugins;
+
+#[cfg(not(feature = "wasm"))]
+impl Plugins {
+    pub fn new(_plugins: &[PluginConfig]) -> Result<Self, String> {
+        Err(
+            "[[plugin]] requires a build with the wasm feature (cargo build --features wasm)"
+                .to_string(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        0
+    }
+}
+
+#[cfg(not(feature = "wasm"))]
+pub async fn apply(
+    _state: axum::extract::State<std::sync::Arc<Plugins>>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    next.run(req).await
+}
+
+#[cfg(feature = "wasm")]
+mod host {
+    use super::PluginConfig;
+    use crate::glob::PathPattern;
+    use crate::hooks::{self, header_map, Reply};
+    use axum::extract::{Request, State};
+    use axum::http::StatusCode;
+    use axum::middleware::Next;
+    use axum::response::{IntoResponse, Response};
+    use percent_encoding::percent_decode_str;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use tracing::error;
+    use wasmtime::{
+        Config, Engine, InstancePre, Linker, Module, Store, StoreLimits, StoreLimitsBuilder,
+    };
+
+    // 传给钩子的请求（on_response 时带上响应状态码与响应头）
+    #[derive(Serialize)]
+    struct Input<'a> {
+        method: &'a str,
+        path: &'a str,
+        query: &'a str,
+        headers: BTreeMap<&'a str, String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        status: Option<u16>,
+    }
+
+    #[derive(Deserialize, Default)]
+    #[serde(default, deny_unknown_fields)]
+    struct Output {
+        set_headers: BTreeMap<String, String>,
+        remove_headers: Vec<String>,
+        response: Option<Reply>,
+    }
+
+    struct Plugin {
+        name: String,
+        patterns: Vec<PathPattern>,
+        engine: Engine,
+        instance: InstancePre<StoreLimits>,
+        fuel: u64,
+        memory: usize,
+        on_request: bool,
+        on_response: bool,
+    }
+
+    pub struct Plugins {
+        plugins: Vec<Arc<Plugin>>,
+    }
+
+    impl Plugins {
+        pub fn new(configs: &[PluginConfig]) -> Result<Self, String> {
+            let mut config = Config::new();
+            config.consume_fuel(true);
+            let engine = Engine::new(&config).map_err(|e| format!("[plugin] {}", e))?;
+            let mut plugins = Vec::new();
+            for config in configs {
+                let name = config.module.display().to_string();
+                let module = Module::from_file(&engine, &config.module)
+                    .map_err(|e| format!("[plugin] failed to load {}: {:#}", name, e))?;
+                if let Some(import) = module.imports().next() {
+                    return Err(format!(
+                        "[plugin] {} must not import anything (imports {}::{})",
+                        name,
+                        import.module(),
+                        import.name()
+                    ));
+                }
+                let exports = |export: &str| module.get_export(export).is_some();
+                if !exports("memory") || !exports("alloc") {
+                    return Err(format!(
+                        "[plugin] {} must export `memory` and `alloc`",
+                        name
+                    ));
+                }
+                let (on_request, on_response) = (exports("on_request"), exports("on_response"));
+                if !on_request && !on_response {
+                    return Err(format!(
+                        "[plugin] {} exports neither `on_request` nor `on_response`",
+                        name
+                    ));
+                }
+                let patterns = config
+                    .paths
+                    .iter()
+                    .map(|pattern| PathPattern::new(pattern))
+                    .collect::<Result<_, String>>()?;
+                let instance = Linker::new(&engine)
+                    .instantiate_pre(&module)
+                    .map_err(|e| format!("[plugin] {}: {}", name, e))?;
+                plugins.push(Arc::new(Plugin {
+                    name,
+                    patterns,
+                    engine: engine.clone(),
+                    instance,
+                    fuel: config.fuel,
+                    memory: config.memory_mb.saturating_mul(1024 * 1024),
+                    on_request,
+                    on_response,
+                }));
+            }
+            Ok(Plugins { plugins })
+        }
+
+        pub fn len(&self) -> usize {
+            self.plugins.len()
+        }
+    }
+
+    impl Plugin {
+        fn matches(&self, path: &str) -> bool {
+            self.patterns.is_empty() || self.patterns.iter().any(|p| p.matches(path))
+        }
+
+        // 在新的实例中调用钩子：alloc 分配输入缓冲区，钩子返回指向结果的 (ptr << 32) | len
+        fn call(&self, hook: &str, input: &[u8]) -> Result<Option<Output>, String> {
+            let limits = StoreLimitsBuilder::new()
+                .memory_size(self.memory)
+                .instances(1)
+                .build();
+            let mut store = Store::new(&self.engine, limits);
+            store.limiter(|limits| limits);
+            store.set_fuel(self.fuel).map_err(|e| e.to_string())?;
+            let instance = self
+                .instance
+                .instantiate(&mut store)
+                .map_err(|e| format!("{:#}", e))?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or("`memory` is not a memory")?;
+            let alloc = instance
+                .get_typed_func::<i32, i32>(&mut store, "alloc")
+                .map_err(|e| format!("alloc: {}", e))?;
+            let func = instance
+                .get_typed_func::<(i32, i32), i64>(&mut store, hook)
+                .map_err(|e| format!("{}: {}", hook, e))?;
+            let len = i32::try_from(input.len()).map_err(|_| "input too large")?;
+            let ptr = alloc
+                .call(&mut store, len)
+                .map_err(|e| format!("alloc: {:#}", e))?;
+            memory
+                .write(&mut store, ptr as u32 as usize, input)
+                .map_err(|_| "alloc returned an out-of-bounds pointer")?;
+            let packed = func
+                .call(&mut store, (ptr, len))
+                .map_err(|e| format!("{}: {:#}", hook, e))? as u64;
+            if packed == 0 {
+                return Ok(None);
+            }
+            let start = (packed >> 32) as usize;
+            let end = start + (packed & 0xffff_ffff) as usize;
+            let output = memory
+                .data(&store)
+                .get(start..end)
+                .ok_or("result is out of bounds")?;
+            serde_json::from_slice(output)
+                .map(Some)
+                .map_err(|e| format!("invalid result: {}", e))
+        }
+    }
+
+    // wasm 代码同步执行，放到阻塞线程池中，避免占用异步工作线程
+    async fn run(
+        plugin: &Arc<Plugin>,
+        hook: &'static str,
+        input: Input<'_>,
+    ) -> Result<Option<Output>, String> {
+        let input = serde_json::to_vec(&input).map_err(|e| e.to_string())?;
+        let plugin = plugin.clone();
+        tokio::task::spawn_blocking(move || plugin.call(hook, &input))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    fn failed(plugin: &Plugin, hook: &str, e: String) -> Response {
+        error!("Plugin {} {} failed: {}", plugin.name, hook, e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+
+    // 按解码后的请求路径匹配，所有匹配的插件按配置顺序调用 on_request，响应按相反顺序调用 on_response
+    pub async fn apply(
+        State(plugins): State<Arc<Plugins>>,
+        mut req: Request,
+        next: Next,
+    ) -> Response {
+        let path = percent_decode_str(req.uri().path())
+            .decode_utf8_lossy()
+            .into_owned();
+        let matched: Vec<_> = plugins
+            .plugins
+            .iter()
+            .filter(|plugin| plugin.matches(&path))
+            .cloned()
+            .collect();
+        if matched.is_empty() {
+            return next.run(req).await;
+        }
+        let method = req.method().to_string();
+        let query = req.uri().query().unwrap_or("").to_string();
+        for plugin in matched.iter().filter(|plugin| plugin.on_request) {
+            let input = Input {
+                method: &method,
+                path: &path,
+                query: &query,
+                headers: header_map(req.headers()),
+                status: None,
+            };
+            let mut output = match run(plugin, "on_request", input).await {
+                Ok(Some(output)) => output,
+                Ok(None) => continue,
+                Err(e) => return failed(plugin, "on_request", e),
+            };
+            let result = match output.response.take() {
+                Some(short) => short.build().map(Some),
+                None => hooks::edit(
+                    req.headers_mut(),
+                    &output.set_headers,
+                    &output.remove_headers,
+                )
+                .map(|_| None),
+            };
+            match result {
+                Ok(Some(response)) => return response,
+                Ok(None) => {}
+                Err(e) => return failed(plugin, "on_request", e),
+            }
+        }
+        let mut response = next.run(req).await;
+        for plugin in matched.iter().rev().filter(|plugin| plugin.on_response) {
+            let input = Input {
+                method: &method,
+                path: &path,
+                query: &query,
+                headers: header_map(response.headers()),
+                status: Some(response.status().as_u16()),
+            };
+            let mut output = match run(plugin, "on_response", input).await {
+                Ok(Some(output)) => output,
+                Ok(None) => continue,
+                Err(e) => return failed(plugin, "on_response", e),
+            };
+            let result = match output.response.take() {
+                Some(replacement) => replacement.build().map(|replaced| response = replaced),
+                None => hooks::edit(
+                    response.headers_mut(),
+                    &output.set_headers,
+                    &output.remove_headers,
+                ),
+            };
+            if let Err(e) = result {
+                return failed(plugin, "on_response", e);
+            }
+        }
+        response
+    }
+}
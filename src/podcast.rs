@@ -0,0 +1,302 @@
+// 播客订阅（[[podcast]]）：把一个音频目录生成为 RSS 2.0 feed（含 iTunes 扩展），
+// 文件增删或修改后下次请求时重新生成
+use crate::audio;
+use crate::forwarded::{self, ClientInfo};
+use crate::webdav::encode_segment;
+use axum::extract::State;
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct PodcastConfig {
+    // feed 的 URL 路径，如 "/podcast.xml"
+    pub path: String,
+    // 音频目录的 URL 路径（相对站点根目录），如 "/episodes/"
+    pub dir: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    // 站点地址，如 "https://example.com"；未设置时按请求的 Host 生成绝对地址
+    #[serde(default)]
+    pub link: Option<String>,
+    // 封面图片地址，可以是站点内路径
+    #[serde(default)]
+    pub artwork: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default = "default_language")]
+    pub language: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub explicit: bool,
+}
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Episode {
+    name: String,
+    len: u64,
+    modified: SystemTime,
+    mime: &'static str,
+}
+
+struct Cached {
+    episodes: Vec<Episode>,
+    base: String,
+    xml: String,
+}
+
+pub struct Podcast {
+    config: PodcastConfig,
+    dir: PathBuf,
+    // 时长解析结果，按文件名、大小与修改时间缓存
+    durations: Mutex<HashMap<Episode, Option<u64>>>,
+    feed: Mutex<Option<Cached>>,
+}
+
+impl Podcast {
+    pub fn new(config: &PodcastConfig, root: &Path) -> Result<Self, String> {
+        if !config.path.starts_with('/') {
+            return Err(format!(
+                "podcast path `{}` must start with '/'",
+                config.path
+            ));
+        }
+        let dir = crate::site::fs_path(root, &config.dir)
+            .ok_or_else(|| format!("invalid podcast dir `{}`", config.dir))?;
+        if !dir.is_dir() {
+            return Err(format!("podcast dir {} is not a directory", dir.display()));
+        }
+        Ok(Podcast {
+            config: config.clone(),
+            dir,
+            durations: Mutex::new(HashMap::new()),
+            feed: Mutex::new(None),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.config.path
+    }
+
+    fn scan(&self) -> io::Result<Vec<Episode>> {
+        let mut episodes = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if name.starts_with('.') {
+                continue;
+            }
+            let Some(mime) = audio::content_type(&name) else {
+                continue;
+            };
+            let meta = entry.metadata()?;
+            if !meta.is_file() {
+                continue;
+            }
+            episodes.push(Episode {
+                name,
+                len: meta.len(),
+                modified: meta.modified().unwrap_or(UNIX_EPOCH),
+                mime,
+            });
+        }
+        // 最新的节目在前
+        episodes.sort_by(|a, b| b.modified.cmp(&a.modified).then(a.name.cmp(&b.name)));
+        Ok(episodes)
+    }
+
+    fn duration(&self, episode: &Episode) -> Option<u64> {
+        if let Some(secs) = self.durations.lock().unwrap().get(episode) {
+            return *secs;
+        }
+        let path = self.dir.join(&episode.name);
+        let secs = audio::duration(&path, episode.len).unwrap_or_else(|e| {
+            warn!("Failed to read duration of {}: {}", path.display(), e);
+            None
+        });
+        self.durations.lock().unwrap().insert(episode.clone(), secs);
+        secs
+    }
+
+    // 目录内容与请求地址都没有变化时返回缓存的 feed
+    fn render(&self, base: &str) -> io::Result<String> {
+        let episodes = self.scan()?;
+        if let Some(cached) = &*self.feed.lock().unwrap() {
+            if cached.episodes == episodes && cached.base == base {
+                return Ok(cached.xml.clone());
+            }
+        }
+        // 已删除或修改过的文件不再保留旧的时长
+        self.durations
+            .lock()
+            .unwrap()
+            .retain(|episode, _| episodes.contains(episode));
+        let config = &self.config;
+        let absolute = |path: &str| {
+            if path.starts_with("http://") || path.starts_with("https://") {
+                path.to_string()
+            } else {
+                format!("{}/{}", base, path.trim_start_matches('/'))
+            }
+        };
+        let dir_url = format!("/{}/", config.dir.trim_matches('/')).replace("//", "/");
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\" \
+             xmlns:atom=\"http://www.w3.org/2005/Atom\">\n<channel>\n",
+        );
+        xml.push_str(&format!("<title>{}</title>\n", escape(&config.title)));
+        xml.push_str(&format!("<link>{}</link>\n", escape(&absolute("/"))));
+        xml.push_str(&format!(
+            "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+            escape(&absolute(&config.path))
+        ));
+        xml.push_str(&format!(
+            "<description>{}</description>\n<itunes:summary>{}</itunes:summary>\n",
+            escape(&config.description),
+            escape(&config.description)
+        ));
+        xml.push_str(&format!(
+            "<language>{}</language>\n",
+            escape(&config.language)
+        ));
+        if let Some(author) = &config.author {
+            xml.push_str(&format!(
+                "<itunes:author>{}</itunes:author>\n",
+                escape(author)
+            ));
+        }
+        if let Some(artwork) = &config.artwork {
+            let url = escape(&absolute(artwork));
+            xml.push_str(&format!(
+                "<image><url>{}</url><title>{}</title><link>{}</link></image>\n<itunes:image href=\"{}\"/>\n",
+                url,
+                escape(&config.title),
+                escape(&absolute("/")),
+                url
+            ));
+        }
+        if let Some(category) = &config.category {
+            xml.push_str(&format!(
+                "<itunes:category text=\"{}\"/>\n",
+                escape(category)
+            ));
+        }
+        xml.push_str(&format!(
+            "<itunes:explicit>{}</itunes:explicit>\n",
+            config.explicit
+        ));
+        if let Some(latest) = episodes.first() {
+            xml.push_str(&format!(
+                "<lastBuildDate>{}</lastBuildDate>\n",
+                rfc2822(latest.modified)
+            ));
+        }
+        for episode in &episodes {
+            let title = episode
+                .name
+                .rsplit_once('.')
+                .map_or(episode.name.as_str(), |(stem, _)| stem);
+            let url = absolute(&format!("{}{}", dir_url, encode_segment(&episode.name)));
+            xml.push_str("<item>\n");
+            xml.push_str(&format!("<title>{}</title>\n", escape(title)));
+            xml.push_str(&format!(
+                "<enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n",
+                escape(&url),
+                episode.len,
+                episode.mime
+            ));
+            xml.push_str(&format!(
+                "<guid isPermaLink=\"true\">{}</guid>\n",
+                escape(&url)
+            ));
+            xml.push_str(&format!(
+                "<pubDate>{}</pubDate>\n",
+                rfc2822(episode.modified)
+            ));
+            if let Some(secs) = self.duration(episode) {
+                xml.push_str(&format!(
+                    "<itunes:duration>{:02}:{:02}:{:02}</itunes:duration>\n",
+                    secs / 3600,
+                    secs / 60 % 60,
+                    secs % 60
+                ));
+            }
+            xml.push_str("</item>\n");
+        }
+        xml.push_str("</channel>\n</rss>\n");
+        *self.feed.lock().unwrap() = Some(Cached {
+            episodes,
+            base: base.to_string(),
+            xml: xml.clone(),
+        });
+        Ok(xml)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// RSS 使用 RFC 2822 日期，与 HTTP 日期格式一致
+fn rfc2822(time: SystemTime) -> String {
+    httpdate::fmt_http_date(time)
+}
+
+// 站点地址：优先使用配置，否则按客户端协议（可信代理的转发头）与 Host 推断
+fn base_url(config: &PodcastConfig, headers: &HeaderMap, client: Option<&ClientInfo>) -> String {
+    if let Some(link) = &config.link {
+        return link.trim_end_matches('/').to_string();
+    }
+    forwarded::origin(headers, client)
+}
+
+pub async fn feed(
+    State(podcast): State<Arc<Podcast>>,
+    client: Option<Extension<ClientInfo>>,
+    headers: HeaderMap,
+) -> Response {
+    let base = base_url(&podcast.config, &headers, client.as_deref());
+    let rendered = {
+        let podcast = podcast.clone();
+        tokio::task::spawn_blocking(move || podcast.render(&base)).await
+    };
+    match rendered {
+        Ok(Ok(xml)) => (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/rss+xml; charset=utf-8"),
+                ),
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            ],
+            xml,
+        )
+            .into_response(),
+        Ok(Err(e)) => {
+            warn!("Failed to generate podcast feed {}: {}", podcast.path(), e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
@@ -0,0 +1,203 @@
+// precompress 子命令：为可压缩文件预先生成 .br / .gz / .zst 旁路文件，配合 precompressed 选项直接返回
+use crate::cli::PrecompressArgs;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+// 值得压缩的文本类资源；图片、音视频、字体（woff2）等已压缩格式不在其中
+const COMPRESSIBLE: &[&str] = &[
+    "html",
+    "htm",
+    "css",
+    "js",
+    "mjs",
+    "cjs",
+    "json",
+    "map",
+    "webmanifest",
+    "svg",
+    "xml",
+    "txt",
+    "csv",
+    "md",
+    "wasm",
+    "ttf",
+    "otf",
+    "eot",
+    "ico",
+];
+
+#[derive(Clone, Copy)]
+enum Encoding {
+    Brotli,
+    Gzip,
+    Zstd,
+}
+
+impl Encoding {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim() {
+            "br" | "brotli" => Some(Encoding::Brotli),
+            "gz" | "gzip" => Some(Encoding::Gzip),
+            "zst" | "zstd" => Some(Encoding::Zstd),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gz",
+            Encoding::Zstd => "zst",
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut out = Vec::new();
+                {
+                    let mut writer = brotli::CompressorWriter::new(&mut out, 64 * 1024, 11, 22);
+                    writer.write_all(data)?;
+                }
+                Ok(out)
+            }
+            Encoding::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Zstd => zstd::encode_all(data, 19),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    written: AtomicU64,
+    skipped: AtomicU64,
+    failed: AtomicU64,
+    saved_bytes: AtomicU64,
+}
+
+pub fn run(args: PrecompressArgs) {
+    let encodings: Vec<Encoding> = match args
+        .formats
+        .split(',')
+        .map(|name| Encoding::parse(name).ok_or(name))
+        .collect()
+    {
+        Ok(encodings) => encodings,
+        Err(name) => {
+            eprintln!("Unknown format `{}` (expected br, gz, zst)", name);
+            std::process::exit(2);
+        }
+    };
+    let mut files = Vec::new();
+    if let Err(e) = collect(&args.dir, args.min_size, &mut files) {
+        eprintln!("Failed to read {}: {}", args.dir.display(), e);
+        std::process::exit(1);
+    }
+
+    let started = Instant::now();
+    let stats = Stats::default();
+    let queue = Mutex::new(files.iter());
+    let threads = args.jobs.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+    std::thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                let Some(file) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                for &encoding in &encodings {
+                    match compress_file(file, encoding, args.force) {
+                        Ok(Some(saved)) => {
+                            stats.written.fetch_add(1, Ordering::Relaxed);
+                            stats.saved_bytes.fetch_add(saved, Ordering::Relaxed);
+                        }
+                        Ok(None) => {
+                            stats.skipped.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(e) => {
+                            eprintln!("{}: {}", file.display(), e);
+                            stats.failed.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+            });
+        }
+    });
+
+    println!(
+        "Precompressed {} files in {:.1?}: {} written, {} up to date or not smaller, {} failed, {} KiB saved",
+        files.len(),
+        started.elapsed(),
+        stats.written.load(Ordering::Relaxed),
+        stats.skipped.load(Ordering::Relaxed),
+        stats.failed.load(Ordering::Relaxed),
+        stats.saved_bytes.load(Ordering::Relaxed) / 1024
+    );
+    if stats.failed.load(Ordering::Relaxed) > 0 {
+        std::process::exit(1);
+    }
+}
+
+fn collect(dir: &Path, min_size: u64, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for item in fs::read_dir(dir)? {
+        let item = item?;
+        let path = item.path();
+        let file_type = item.file_type()?;
+        if file_type.is_dir() {
+            collect(&path, min_size, files)?;
+            continue;
+        }
+        let compressible = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| COMPRESSIBLE.contains(&ext.to_ascii_lowercase().as_str()));
+        if file_type.is_file() && compressible && item.metadata()?.len() >= min_size {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+// 写入旁路文件，返回节省的字节数；旁路文件不旧于源文件、或压缩后没有变小时跳过
+fn compress_file(path: &Path, encoding: Encoding, force: bool) -> io::Result<Option<u64>> {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".");
+    sidecar.push(encoding.extension());
+    let sidecar = PathBuf::from(sidecar);
+
+    let modified = fs::metadata(path)?.modified()?;
+    if !force {
+        let up_to_date = fs::metadata(&sidecar)
+            .and_then(|m| m.modified())
+            .is_ok_and(|sidecar_modified| sidecar_modified >= modified);
+        if up_to_date {
+            return Ok(None);
+        }
+    }
+
+    let data = fs::read(path)?;
+    let compressed = encoding.compress(&data)?;
+    if compressed.len() >= data.len() {
+        // 旧的旁路文件可能比新内容更大，删除以免返回过期内容
+        let _ = fs::remove_file(&sidecar);
+        return Ok(None);
+    }
+    // 先写临时文件再改名，服务中的进程不会读到半个文件
+    let mut temp = sidecar.clone().into_os_string();
+    temp.push(".tmp");
+    fs::write(&temp, &compressed)?;
+    fs::rename(&temp, &sidecar)?;
+    Ok(Some((data.len() - compressed.len()) as u64))
+}
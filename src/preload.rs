@@ -0,0 +1,145 @@
+// 启动预热（preload）：在开始接受连接之前按路径模式请求一遍主站点的文件，
+// 填充内存缓存（[cache]）中各常见 Accept-Encoding 的版本（包括预压缩的旁路文件），
+// 部署后的第一批访问者不必等待冷缓存。热重启时新进程预热完成后才接管监听
+use crate::app;
+use crate::glob::PathPattern;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::Router;
+use futures_util::StreamExt;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tower::ServiceExt;
+use tracing::{debug, info, warn};
+
+// 缓存按 Accept-Encoding 原文区分，使用不带压缩与主流浏览器实际发送的值
+const ENCODINGS: &[&str] = &["", "gzip, deflate, br", "gzip, deflate, br, zstd"];
+
+// 预压缩的旁路文件随原文件一起返回，不单独请求
+const SIDECARS: &[&str] = &["br", "gz", "zst"];
+
+// URL 路径中需要编码的字符
+const PATH_CHARS: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+// 不含通配符的模式直接请求；含通配符的模式在站点目录中展开
+pub async fn warm(app: &Router, patterns: &[String], dir: Option<&str>) -> Result<(), String> {
+    let mut literal = Vec::new();
+    let mut globs = Vec::new();
+    for pattern in patterns {
+        if pattern.contains(['*', '?']) {
+            globs.push(PathPattern::new(pattern)?);
+        } else if pattern.starts_with('/') {
+            literal.push(pattern.clone());
+        } else {
+            return Err(format!("preload path `{}` must start with '/'", pattern));
+        }
+    }
+    let started = Instant::now();
+    let mut paths = literal;
+    if !globs.is_empty() {
+        match dir {
+            Some(dir) => {
+                let dir = PathBuf::from(dir);
+                let found = tokio::task::spawn_blocking(move || {
+                    let mut found = Vec::new();
+                    walk(&dir, "", &globs, &mut found);
+                    found
+                })
+                .await
+                .unwrap_or_default();
+                paths.extend(found);
+            }
+            None => warn!("preload patterns with wildcards need static_dir on disk"),
+        }
+    }
+    paths.sort();
+    paths.dedup();
+
+    let (mut files, mut bytes) = (0, 0);
+    for path in &paths {
+        let mut loaded = false;
+        for encoding in ENCODINGS {
+            match fetch(app, path, encoding).await {
+                Ok(Some(size)) => {
+                    loaded = true;
+                    bytes += size;
+                }
+                Ok(None) => {}
+                Err(e) => warn!("Failed to preload {}: {}", path, e),
+            }
+        }
+        if loaded {
+            files += 1;
+        }
+    }
+    info!(
+        "Preloaded {} of {} paths ({} bytes) in {:.0?}",
+        files,
+        paths.len(),
+        bytes,
+        started.elapsed()
+    );
+    Ok(())
+}
+
+// 读完整个响应体，缓存在此时写入；非 200 的响应不计入
+async fn fetch(app: &Router, path: &str, encoding: &'static str) -> Result<Option<u64>, String> {
+    let mut req = app::local_request(path)?;
+    if !encoding.is_empty() {
+        req.headers_mut()
+            .insert(header::ACCEPT_ENCODING, HeaderValue::from_static(encoding));
+    }
+    let response = match app.clone().oneshot(req).await {
+        Ok(response) => response,
+        Err(e) => match e {},
+    };
+    let status = response.status();
+    let mut stream = response.into_body().into_data_stream();
+    let mut size = 0;
+    while let Some(chunk) = stream.next().await {
+        size += chunk.map_err(|e| e.to_string())?.len() as u64;
+    }
+    if status != StatusCode::OK {
+        debug!("Preload {} returned {}", path, status);
+        return Ok(None);
+    }
+    Ok(Some(size))
+}
+
+fn walk(dir: &Path, prefix: &str, globs: &[PathPattern], found: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        let url = format!("{}/{}", prefix, utf8_percent_encode(name, PATH_CHARS));
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() {
+            walk(&entry.path(), &url, globs, found);
+            continue;
+        }
+        let sidecar = Path::new(name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SIDECARS.contains(&ext));
+        // 与请求一样按编码后的 URL 路径匹配
+        if !sidecar && globs.iter().any(|glob| glob.matches(&url)) {
+            found.push(url);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+// 爬虫快照（[prerender]）：User-Agent 属于搜索引擎与社交预览的爬虫时，页面请求返回快照目录中预渲染的 HTML，
+// 普通访问者仍得到 SPA 的入口页面，客户端渲染的站点同样可以被收录。/about 对应 about.html 或 about/index.html，
+// / 与 /blog/ 对应 index.html 与 blog/index.html；没有快照的页面与静态资源照常处理
+use crate::glob::PathPattern;
+use crate::site;
+use crate::vary;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct PrerenderConfig {
+    // 快照目录（相对当前目录），结构与站点的页面路径对应
+    pub dir: PathBuf,
+    // 爬虫 User-Agent 中的关键字，不区分大小写
+    #[serde(default = "default_user_agents")]
+    pub user_agents: Vec<String>,
+    // 不返回快照的路径（模式规则与 deny 相同）
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+fn default_user_agents() -> Vec<String> {
+    [
+        "googlebot",
+        "bingbot",
+        "yandex",
+        "baiduspider",
+        "duckduckbot",
+        "slurp",
+        "applebot",
+        "facebookexternalhit",
+        "twitterbot",
+        "linkedinbot",
+        "slackbot",
+        "discordbot",
+        "telegrambot",
+        "whatsapp",
+        "pinterest",
+        "embedly",
+    ]
+    .iter()
+    .map(|agent| agent.to_string())
+    .collect()
+}
+
+pub struct Prerender {
+    dir: PathBuf,
+    // 小写
+    user_agents: Vec<String>,
+    exclude: Vec<PathPattern>,
+    cache_control: HeaderValue,
+}
+
+impl Prerender {
+    pub fn new(config: &PrerenderConfig, html_cache_control: &str) -> Result<Self, String> {
+        if !config.dir.is_dir() {
+            return Err(format!(
+                "[prerender] dir {} is not a directory",
+                config.dir.display()
+            ));
+        }
+        if config.user_agents.is_empty() {
+            return Err("[prerender] user_agents must not be empty".to_string());
+        }
+        let exclude = config
+            .exclude
+            .iter()
+            .map(|pattern| PathPattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let cache_control = HeaderValue::from_str(html_cache_control)
+            .map_err(|_| format!("invalid html_cache_control `{}`", html_cache_control))?;
+        Ok(Prerender {
+            dir: config.dir.clone(),
+            user_agents: config
+                .user_agents
+                .iter()
+                .map(|agent| agent.to_ascii_lowercase())
+                .collect(),
+            exclude,
+            cache_control,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} ({} crawler user agents)",
+            self.dir.display(),
+            self.user_agents.len()
+        )
+    }
+
+    fn crawler(&self, req: &Request) -> bool {
+        let Some(agent) = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+        else {
+            return false;
+        };
+        let agent = agent.to_ascii_lowercase();
+        self.user_agents
+            .iter()
+            .any(|keyword| agent.contains(keyword.as_str()))
+    }
+
+    // 页面路径对应的快照文件；带扩展名的路径（.html 除外）是静态资源，没有快照
+    async fn snapshot(&self, path: &str) -> Option<PathBuf> {
+        let decoded = percent_decode_str(path).decode_utf8().ok()?;
+        if self.exclude.iter().any(|pattern| pattern.matches(&decoded)) {
+            return None;
+        }
+        let name = decoded.rsplit('/').next().unwrap_or("");
+        let candidates = if name.is_empty() {
+            vec![format!("{}index.html", path)]
+        } else if name.ends_with(".html") {
+            vec![path.to_string()]
+        } else if name.contains('.') {
+            return None;
+        } else {
+            vec![format!("{}.html", path), format!("{}/index.html", path)]
+        };
+        for candidate in candidates {
+            let Some(file) = site::fs_path(&self.dir, &candidate) else {
+                continue;
+            };
+            if is_file(&file).await {
+                return Some(file);
+            }
+        }
+        None
+    }
+}
+
+async fn is_file(path: &Path) -> bool {
+    tokio::fs::metadata(path)
+        .await
+        .is_ok_and(|meta| meta.is_file())
+}
+
+pub async fn serve(State(prerender): State<Arc<Prerender>>, req: Request, next: Next) -> Response {
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return next.run(req).await;
+    }
+    let Some(snapshot) = prerender.snapshot(req.uri().path()).await else {
+        return next.run(req).await;
+    };
+    // 有快照的页面按 User-Agent 返回不同内容，共享缓存需要分开保存
+    if !prerender.crawler(&req) {
+        let mut response = next.run(req).await;
+        vary::add(response.headers_mut(), "User-Agent");
+        return response;
+    }
+    debug!("Serving snapshot {} to crawler", snapshot.display());
+    let mut response = match ServeFile::new(&snapshot).oneshot(req).await {
+        Ok(response) => response.into_response(),
+        Err(never) => match never {},
+    };
+    let headers = response.headers_mut();
+    if headers.contains_key(header::CONTENT_TYPE) {
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+    }
+    headers.insert(header::CACHE_CONTROL, prerender.cache_control.clone());
+    vary::add(headers, "User-Agent");
+    response
+}
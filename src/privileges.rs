@@ -0,0 +1,86 @@
+// 降权（仅 Unix）：以 root 绑定 80 / 443 等端口后切换到 user / group 配置的非特权账号再开始服务
+use nix::unistd::{self, Gid, Group, Uid, User};
+use std::io;
+use std::path::Path;
+
+pub fn lookup_user(name: &str) -> io::Result<User> {
+    let user = match name.parse::<u32>() {
+        Ok(uid) => User::from_uid(Uid::from_raw(uid)),
+        Err(_) => User::from_name(name),
+    };
+    user.map_err(io::Error::from)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no such user `{}`", name)))
+}
+
+pub fn lookup_group(name: &str) -> io::Result<Gid> {
+    if let Ok(gid) = name.parse::<u32>() {
+        return Ok(Gid::from_raw(gid));
+    }
+    Group::from_name(name)
+        .map_err(io::Error::from)?
+        .map(|g| g.gid)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no such group `{}`", name)))
+}
+
+// 以 root 启动并配置了 user 时，把降权前创建的文件或目录交给该账号
+pub fn hand_over(path: &Path, user: Option<&str>, group: Option<&str>) -> io::Result<()> {
+    if user.is_none() || !unistd::geteuid().is_root() {
+        return Ok(());
+    }
+    let user = user.map(lookup_user).transpose()?;
+    let gid = match group {
+        Some(group) => Some(lookup_group(group)?),
+        None => user.as_ref().map(|u| u.gid),
+    };
+    unistd::chown(path, user.map(|u| u.uid), gid).map_err(io::Error::from)
+}
+
+// 未指定 group 时使用该用户的主组；补充组按 /etc/group 设置为该用户所属的组
+pub fn drop(user: Option<&str>, group: Option<&str>) -> io::Result<()> {
+    let user = user.map(lookup_user).transpose()?;
+    let gid = match (group, &user) {
+        (Some(group), _) => lookup_group(group)?,
+        (None, Some(user)) => user.gid,
+        (None, None) => return Ok(()),
+    };
+    let uid = user.as_ref().map(|u| u.uid);
+
+    if !unistd::geteuid().is_root() {
+        // SIGUSR2 重启后的新进程已经是目标账号
+        let same_user = uid.is_none_or(|uid| uid == unistd::geteuid());
+        if same_user && gid == unistd::getegid() {
+            return Ok(());
+        }
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "switching user or group requires starting as root",
+        ));
+    }
+
+    // 先处理组：切换用户之后就没有权限再修改
+    match &user {
+        #[cfg(not(target_vendor = "apple"))]
+        Some(user) => {
+            let name = std::ffi::CString::new(user.name.as_str())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid user name"))?;
+            unistd::initgroups(&name, gid).map_err(io::Error::from)?;
+        }
+        #[cfg(target_vendor = "apple")]
+        Some(_) => {}
+        #[cfg(not(target_vendor = "apple"))]
+        None => unistd::setgroups(&[gid]).map_err(io::Error::from)?,
+        #[cfg(target_vendor = "apple")]
+        None => {}
+    }
+    unistd::setgid(gid).map_err(io::Error::from)?;
+    if let Some(uid) = uid {
+        unistd::setuid(uid).map_err(io::Error::from)?;
+        // 确认无法再切回 root
+        if !uid.is_root() && unistd::setuid(Uid::from_raw(0)).is_ok() {
+            return Err(io::Error::other(
+                "privileges could be regained after setuid",
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,613 @@
+// 反向代理：将指定 URL 前缀的请求转发到上游服务（[[proxy]] 配置）；配置多个上游时按轮询或最少连接选择，
+// 连续失败的上游暂停一段时间（被动健康检查）
+use crate::forwarded::ClientInfo;
+use crate::listener;
+use crate::metrics::{UpstreamStats, METRICS};
+use crate::proxy_cache::{ProxyCache, ProxyCacheConfig};
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use axum::http::uri::{PathAndQuery, Uri};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::any_service;
+use axum::Router;
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use hyper::upgrade::Upgraded;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{debug, info, warn};
+
+// 逐跳头，不能转发给对端
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ProxyRule {
+    // 匹配的 URL 前缀，如 "/api"
+    pub prefix: String,
+    // 上游地址，如 "http://127.0.0.1:3000" 或 "https://backend.example.com/v1"；
+    // 可以是多个地址的数组，按 balance 选择，路径部分各自保留
+    #[serde(deserialize_with = "listener::one_or_many")]
+    #[schemars(with = "listener::OneOrMany<String>")]
+    pub upstream: Vec<String>,
+    // 多个上游时的选择方式：round-robin（默认）/ least-connections
+    #[serde(default)]
+    pub balance: Balance,
+    // 连续失败（连接错误、超时、502 / 503 / 504）达到该次数后暂停使用该上游，0 表示不暂停
+    #[serde(default = "default_max_fails")]
+    pub max_fails: u32,
+    // 暂停的时间（秒），之后重新尝试；所有上游都暂停时选择最早恢复的一个
+    #[serde(default = "default_fail_timeout")]
+    pub fail_timeout_secs: u64,
+    // 转发前去掉匹配的前缀：/api/users -> {upstream}/users
+    #[serde(default)]
+    pub strip_prefix: bool,
+    // 保留客户端的 Host 头，默认改写为上游主机
+    #[serde(default)]
+    pub preserve_host: bool,
+    // 建立上游连接的超时（秒）
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout_secs: u64,
+    // 等待上游响应头的超时（秒），0 表示不限
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+    // WebSocket 连接在两个方向都没有数据的最长秒数，超时后向双方发送关闭帧并断开，0 表示不限
+    #[serde(default = "default_websocket_idle_timeout")]
+    pub websocket_idle_timeout_secs: u64,
+    // GET / HEAD 响应的短时缓存（[proxy.cache]），未配置时每个请求都转发到上游
+    #[serde(default)]
+    pub cache: Option<ProxyCacheConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Balance {
+    #[default]
+    RoundRobin,
+    LeastConnections,
+}
+
+fn default_max_fails() -> u32 {
+    3
+}
+
+fn default_fail_timeout() -> u64 {
+    10
+}
+
+fn default_websocket_idle_timeout() -> u64 {
+    300
+}
+
+fn default_connect_timeout() -> u64 {
+    5
+}
+
+fn default_timeout() -> u64 {
+    60
+}
+
+type HttpClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+// 一个上游地址及其被动健康检查状态
+struct Backend {
+    // 上游的 scheme + authority
+    origin: Uri,
+    // 上游 URL 中的路径部分（不含末尾的 /）
+    base_path: String,
+    // 连续失败次数
+    fails: AtomicU32,
+    // 暂停使用直到该时刻
+    ejected_until: Mutex<Option<Instant>>,
+    stats: Arc<UpstreamStats>,
+}
+
+impl Backend {
+    fn new(upstream: &str) -> Result<Self, String> {
+        let uri: Uri = upstream
+            .parse()
+            .map_err(|e| format!("invalid proxy upstream `{}`: {}", upstream, e))?;
+        match uri.scheme_str() {
+            Some("http") | Some("https") => {}
+            _ => {
+                return Err(format!(
+                    "proxy upstream `{}` must be an http:// or https:// URL",
+                    upstream
+                ))
+            }
+        }
+        let authority = uri
+            .authority()
+            .ok_or_else(|| format!("proxy upstream `{}` has no host", upstream))?;
+        let origin = Uri::builder()
+            .scheme(uri.scheme_str().unwrap_or("http"))
+            .authority(authority.clone())
+            .path_and_query("/")
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Backend {
+            origin,
+            base_path: uri.path().trim_end_matches('/').to_string(),
+            fails: AtomicU32::new(0),
+            ejected_until: Mutex::new(None),
+            stats: METRICS.upstream(upstream),
+        })
+    }
+
+    fn ejected_until(&self, now: Instant) -> Option<Instant> {
+        self.ejected_until
+            .lock()
+            .unwrap()
+            .filter(|until| *until > now)
+    }
+
+    fn succeeded(&self) {
+        self.fails.store(0, Ordering::Relaxed);
+        if self.ejected_until.lock().unwrap().take().is_some() {
+            info!("Upstream {} recovered", self.origin);
+        }
+        self.stats.healthy.store(true, Ordering::Relaxed);
+    }
+
+    fn failed(&self, max_fails: u32, fail_timeout: Duration) {
+        self.stats.failures.fetch_add(1, Ordering::Relaxed);
+        let fails = self.fails.fetch_add(1, Ordering::Relaxed) + 1;
+        if max_fails == 0 || fails < max_fails {
+            return;
+        }
+        // 恢复后再次失败时立即重新暂停
+        *self.ejected_until.lock().unwrap() = Some(Instant::now() + fail_timeout);
+        self.stats.ejections.fetch_add(1, Ordering::Relaxed);
+        self.stats.healthy.store(false, Ordering::Relaxed);
+        warn!(
+            "Upstream {} failed {} times in a row, ejected for {}s",
+            self.origin,
+            fails,
+            fail_timeout.as_secs()
+        );
+    }
+}
+
+// 响应体发送完毕（或连接中断）时减少上游的进行中请求数
+struct ActiveGuard(Arc<UpstreamStats>);
+
+impl ActiveGuard {
+    fn new(stats: &Arc<UpstreamStats>) -> Self {
+        stats.requests.fetch_add(1, Ordering::Relaxed);
+        stats.active.fetch_add(1, Ordering::Relaxed);
+        ActiveGuard(stats.clone())
+    }
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+struct GuardedBody {
+    inner: Body,
+    _guard: ActiveGuard,
+}
+
+impl HttpBody for GuardedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[derive(Clone)]
+struct Upstream {
+    prefix: Arc<str>,
+    backends: Arc<Vec<Backend>>,
+    balance: Balance,
+    // 轮询计数
+    next: Arc<AtomicUsize>,
+    max_fails: u32,
+    fail_timeout: Duration,
+    strip_prefix: bool,
+    preserve_host: bool,
+    timeout: Option<Duration>,
+    websocket_idle_timeout: Option<Duration>,
+    client: HttpClient,
+    cache: Option<Arc<ProxyCache>>,
+}
+
+impl ProxyRule {
+    fn upstream(&self) -> Result<Upstream, String> {
+        let prefix = self.prefix.trim_end_matches('/');
+        if !prefix.starts_with('/') {
+            return Err(format!(
+                "proxy prefix `{}` must start with '/' and must not be the root",
+                self.prefix
+            ));
+        }
+        if self.upstream.is_empty() {
+            return Err(format!("proxy `{}` has no upstream", self.prefix));
+        }
+        let backends = self
+            .upstream
+            .iter()
+            .map(|upstream| Backend::new(upstream))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        connector.set_connect_timeout(Some(Duration::from_secs(self.connect_timeout_secs)));
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(connector);
+
+        Ok(Upstream {
+            prefix: prefix.into(),
+            backends: Arc::new(backends),
+            balance: self.balance,
+            next: Arc::new(AtomicUsize::new(0)),
+            max_fails: self.max_fails,
+            fail_timeout: Duration::from_secs(self.fail_timeout_secs),
+            strip_prefix: self.strip_prefix,
+            preserve_host: self.preserve_host,
+            timeout: (self.timeout_secs > 0).then(|| Duration::from_secs(self.timeout_secs)),
+            websocket_idle_timeout: (self.websocket_idle_timeout_secs > 0)
+                .then(|| Duration::from_secs(self.websocket_idle_timeout_secs)),
+            client: Client::builder(TokioExecutor::new()).build(https),
+            cache: match &self.cache {
+                Some(cache) => Some(Arc::new(ProxyCache::new(cache)?)),
+                None => None,
+            },
+        })
+    }
+}
+
+// 将代理规则注册为路由；前缀本身与其下所有路径均转发
+pub fn routes(rules: &[ProxyRule]) -> Result<Router, String> {
+    let mut router = Router::new();
+    for rule in rules {
+        let upstream = rule.upstream()?;
+        let prefix = upstream.prefix.to_string();
+        let service = any_service(tower::service_fn(move |req: Request| {
+            let upstream = upstream.clone();
+            async move { Ok::<_, Infallible>(upstream.handle(req).await) }
+        }));
+        router = router
+            .route_service(&prefix, service.clone())
+            .route_service(&format!("{}/*rest", prefix), service);
+    }
+    Ok(router)
+}
+
+impl Upstream {
+    async fn handle(&self, req: Request) -> Response {
+        let Some((cache, key)) = self
+            .cache
+            .clone()
+            .and_then(|cache| ProxyCache::key(&req).map(|key| (cache, key)))
+        else {
+            return self.forward(req).await;
+        };
+        if let Some(response) = self.cached(&cache, &key, &req) {
+            return response;
+        }
+        let lock = cache.inflight(&key);
+        let _guard = lock.lock().await;
+        // 等待期间同一地址的请求可能已经写入缓存
+        if let Some(response) = self.cached(&cache, &key, &req) {
+            return response;
+        }
+        let headers = req.headers().clone();
+        let response = self.forward(req).await;
+        cache.store(&key, &headers, response).await
+    }
+
+    // 命中过期但仍在 stale-while-revalidate 时间内的响应时，用同样的请求头在后台刷新
+    fn cached(&self, cache: &Arc<ProxyCache>, key: &str, req: &Request) -> Option<Response> {
+        let hit = cache.lookup(key, req.method(), req.headers())?;
+        if hit.refresh {
+            let mut refresh = Request::new(Body::empty());
+            *refresh.method_mut() = req.method().clone();
+            *refresh.uri_mut() = req.uri().clone();
+            *refresh.headers_mut() = req.headers().clone();
+            if let Some(info) = req.extensions().get::<ClientInfo>() {
+                refresh.extensions_mut().insert(info.clone());
+            }
+            let upstream = self.clone();
+            let cache = cache.clone();
+            let key = key.to_string();
+            tokio::spawn(async move {
+                let headers = refresh.headers().clone();
+                let response = upstream.forward(refresh).await;
+                // 只需要写入缓存，响应体随后丢弃
+                drop(cache.store(&key, &headers, response).await);
+            });
+        }
+        Some(hit.response)
+    }
+
+    // 跳过暂停中的上游；全部暂停时选择最早恢复的一个
+    fn select(&self) -> &Backend {
+        let now = Instant::now();
+        let available: Vec<&Backend> = self
+            .backends
+            .iter()
+            .filter(|backend| backend.ejected_until(now).is_none())
+            .collect();
+        if available.is_empty() {
+            return self
+                .backends
+                .iter()
+                .min_by_key(|backend| backend.ejected_until(now))
+                .unwrap_or(&self.backends[0]);
+        }
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % available.len();
+        match self.balance {
+            Balance::RoundRobin => available[start],
+            // 进行中请求数相同时按轮询顺序
+            Balance::LeastConnections => (0..available.len())
+                .map(|i| available[(start + i) % available.len()])
+                .min_by_key(|backend| backend.stats.active.load(Ordering::Relaxed))
+                .unwrap_or(available[start]),
+        }
+    }
+
+    async fn forward(&self, mut req: Request) -> Response {
+        // WebSocket 升级：Upgrade / Connection 是逐跳头，转发时重新加上，握手成功后在两端之间转发原始字节
+        let websocket = is_websocket(req.headers()).then(|| hyper::upgrade::on(&mut req));
+        let (mut parts, body) = req.into_parts();
+        let backend = self.select();
+
+        let uri = match self.target_uri(backend, &parts.uri) {
+            Ok(uri) => uri,
+            Err(e) => {
+                warn!("Failed to build upstream URI for {}: {}", parts.uri, e);
+                return StatusCode::BAD_GATEWAY.into_response();
+            }
+        };
+
+        let info = parts.extensions.get::<ClientInfo>().cloned();
+        // HTTP/2 请求没有 Host 头，主机名在 URI 的 authority 中
+        let original_host = parts.headers.get(header::HOST).cloned().or_else(|| {
+            parts
+                .uri
+                .authority()
+                .and_then(|a| HeaderValue::from_str(a.as_str()).ok())
+        });
+        strip_hop_by_hop(&mut parts.headers);
+        if websocket.is_some() {
+            parts
+                .headers
+                .insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+            parts
+                .headers
+                .insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+        }
+
+        // 追加转发头
+        if let Some(ip) = info.as_ref().and_then(|info| info.ip) {
+            let forwarded_for = match parts.headers.get("x-forwarded-for") {
+                Some(prev) => format!("{}, {}", prev.to_str().unwrap_or_default(), ip),
+                None => ip.to_string(),
+            };
+            if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+                parts.headers.insert("x-forwarded-for", value);
+            }
+        }
+        let scheme = info.as_ref().map(|info| info.scheme).unwrap_or("http");
+        parts
+            .headers
+            .insert("x-forwarded-proto", HeaderValue::from_static(scheme));
+        if let Some(host) = &original_host {
+            parts.headers.insert("x-forwarded-host", host.clone());
+        }
+        if !self.preserve_host {
+            if let Some(value) = uri
+                .authority()
+                .and_then(|a| HeaderValue::from_str(a.as_str()).ok())
+            {
+                parts.headers.insert(header::HOST, value);
+            }
+        }
+
+        parts.uri = uri;
+        // 上游连接统一使用 HTTP/1.1
+        parts.version = axum::http::Version::HTTP_11;
+        let upstream_req = Request::from_parts(parts, body);
+
+        let guard = ActiveGuard::new(&backend.stats);
+        let pending = self.client.request(upstream_req);
+        let result = match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, pending).await {
+                Ok(result) => result,
+                Err(_) => {
+                    warn!("Upstream {} timed out", backend.origin);
+                    backend.failed(self.max_fails, self.fail_timeout);
+                    return StatusCode::GATEWAY_TIMEOUT.into_response();
+                }
+            },
+            None => pending.await,
+        };
+
+        match result {
+            Ok(mut response) => {
+                if matches!(response.status().as_u16(), 502..=504) {
+                    backend.failed(self.max_fails, self.fail_timeout);
+                } else {
+                    backend.succeeded();
+                }
+                if let Some(client) = websocket {
+                    if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+                        let upstream = hyper::upgrade::on(&mut response);
+                        let idle = self.websocket_idle_timeout;
+                        let origin = backend.origin.clone();
+                        // 连接存续期间计入上游的进行中请求
+                        tokio::spawn(async move {
+                            let _guard = guard;
+                            match tokio::try_join!(client, upstream) {
+                                Ok((client, upstream)) => tunnel(client, upstream, idle).await,
+                                Err(e) => warn!("WebSocket upgrade with {} failed: {}", origin, e),
+                            }
+                        });
+                        let (mut parts, _) = response.into_parts();
+                        strip_hop_by_hop(&mut parts.headers);
+                        parts
+                            .headers
+                            .insert(header::CONNECTION, HeaderValue::from_static("upgrade"));
+                        parts
+                            .headers
+                            .insert(header::UPGRADE, HeaderValue::from_static("websocket"));
+                        return Response::from_parts(parts, Body::empty());
+                    }
+                }
+                let (mut parts, body) = response.into_parts();
+                strip_hop_by_hop(&mut parts.headers);
+                let body = GuardedBody {
+                    inner: Body::new(body),
+                    _guard: guard,
+                };
+                Response::from_parts(parts, Body::new(body))
+            }
+            Err(e) => {
+                warn!("Upstream {} request failed: {}", backend.origin, e);
+                backend.failed(self.max_fails, self.fail_timeout);
+                StatusCode::BAD_GATEWAY.into_response()
+            }
+        }
+    }
+
+    fn target_uri(&self, backend: &Backend, uri: &Uri) -> Result<Uri, axum::http::Error> {
+        let path = uri.path();
+        let rest = if self.strip_prefix {
+            path.strip_prefix(&*self.prefix).unwrap_or(path)
+        } else {
+            path
+        };
+        let mut target = format!("{}{}", backend.base_path, rest);
+        if target.is_empty() {
+            target.push('/');
+        } else if !target.starts_with('/') {
+            target.insert(0, '/');
+        }
+        if let Some(query) = uri.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+
+        let mut parts = backend.origin.clone().into_parts();
+        parts.path_and_query = Some(PathAndQuery::try_from(target)?);
+        Ok(Uri::from_parts(parts)?)
+    }
+}
+
+// Connection 中带 upgrade、Upgrade 为 websocket 的 HTTP/1.1 请求
+fn is_websocket(headers: &HeaderMap) -> bool {
+    let upgrade = headers
+        .get(header::UPGRADE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
+    let connection = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|token| token.trim().eq_ignore_ascii_case("upgrade"));
+    upgrade && connection
+}
+
+// 关闭帧，状态码 1001（going away）；发往上游的帧必须带掩码，这里使用全 0 的掩码
+const CLOSE_TO_CLIENT: [u8; 4] = [0x88, 0x02, 0x03, 0xe9];
+const CLOSE_TO_UPSTREAM: [u8; 8] = [0x88, 0x82, 0, 0, 0, 0, 0x03, 0xe9];
+
+// 两个方向的数据（含 ping / pong 与关闭帧）原样转发，任一端断开时关闭另一端
+async fn tunnel(client: Upgraded, upstream: Upgraded, idle: Option<Duration>) {
+    let (mut client_read, mut client_write) = tokio::io::split(TokioIo::new(client));
+    let (mut upstream_read, mut upstream_write) = tokio::io::split(TokioIo::new(upstream));
+    let mut from_client = vec![0u8; 16 * 1024];
+    let mut from_upstream = vec![0u8; 16 * 1024];
+    loop {
+        let timer = async {
+            match idle {
+                Some(idle) => tokio::time::sleep(idle).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            read = client_read.read(&mut from_client) => match read {
+                Ok(n) if n > 0 => {
+                    if upstream_write.write_all(&from_client[..n]).await.is_err() {
+                        break;
+                    }
+                }
+                _ => break,
+            },
+            read = upstream_read.read(&mut from_upstream) => match read {
+                Ok(n) if n > 0 => {
+                    if client_write.write_all(&from_upstream[..n]).await.is_err() {
+                        break;
+                    }
+                }
+                _ => break,
+            },
+            _ = timer => {
+                // 空闲期间没有未完成的帧，可以直接插入关闭帧
+                debug!("Closing idle WebSocket connection");
+                let _ = client_write.write_all(&CLOSE_TO_CLIENT).await;
+                let _ = upstream_write.write_all(&CLOSE_TO_UPSTREAM).await;
+                break;
+            }
+        }
+    }
+    let _ = client_write.shutdown().await;
+    let _ = upstream_write.shutdown().await;
+}
+
+pub fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    // Connection 头中列出的字段同样是逐跳的
+    let listed: Vec<HeaderName> = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    for name in listed {
+        headers.remove(name);
+    }
+    for name in HOP_BY_HOP {
+        headers.remove(name);
+    }
+}
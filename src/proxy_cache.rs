@@ -0,0 +1,315 @@
+// 反向代理的微缓存（[[proxy]] 的 [proxy.cache]）：GET / HEAD 响应按方法、主机、路径与上游 Vary 列出的请求头
+// 缓存几秒，遵守上游的 Cache-Control；过期后在 stale-while-revalidate 时间内先返回旧响应并在后台刷新，
+// 同一地址的并发未命中只向上游发送一个请求，慢接口的突发流量不会全部落到上游
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ProxyCacheConfig {
+    // 上游没有给出 s-maxage / max-age 时的缓存时间（秒）
+    #[serde(default = "default_ttl")]
+    pub ttl_secs: u64,
+    // 过期后仍先返回旧响应、在后台刷新的时间（秒），上游的 stale-while-revalidate 优先
+    #[serde(default = "default_stale_while_revalidate")]
+    pub stale_while_revalidate_secs: u64,
+    // 缓存的响应数上限
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+    // 单个响应体的大小上限（字节），超过或长度未知（如 SSE）的响应不缓存
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: u64,
+}
+
+fn default_ttl() -> u64 {
+    1
+}
+
+fn default_stale_while_revalidate() -> u64 {
+    10
+}
+
+fn default_max_entries() -> usize {
+    1000
+}
+
+fn default_max_body_size() -> u64 {
+    1024 * 1024
+}
+
+// RFC 9110 中默认可缓存的状态码
+const CACHEABLE: [u16; 10] = [200, 203, 204, 300, 301, 404, 405, 410, 414, 501];
+
+struct Entry {
+    // 上游 Vary 列出的请求头及写入时请求中的值
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    stored: Instant,
+    fresh: Duration,
+    stale: Duration,
+    // 已有请求在后台刷新
+    refreshing: bool,
+}
+
+impl Entry {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        self.vary
+            .iter()
+            .all(|(name, value)| headers.get(name) == value.as_ref())
+    }
+
+    fn expired(&self, now: Instant) -> bool {
+        now.duration_since(self.stored) >= self.fresh + self.stale
+    }
+
+    fn response(&self, method: &Method) -> Response {
+        let body = if method == Method::HEAD {
+            Body::empty()
+        } else {
+            Body::from(self.body.clone())
+        };
+        let mut response = Response::new(body);
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response.headers_mut().insert(
+            header::AGE,
+            HeaderValue::from(self.stored.elapsed().as_secs()),
+        );
+        response
+    }
+}
+
+pub struct ProxyCache {
+    ttl: Duration,
+    stale: Duration,
+    max_entries: usize,
+    max_body_size: u64,
+    entries: Mutex<HashMap<String, Vec<Entry>>>,
+    // 正在向上游请求的地址，并发的未命中等待同一个请求
+    inflight: Mutex<HashMap<String, Weak<tokio::sync::Mutex<()>>>>,
+}
+
+// 命中时的响应；refresh 为 true 时由调用方在后台刷新
+pub struct Hit {
+    pub response: Response,
+    pub refresh: bool,
+}
+
+impl ProxyCache {
+    pub fn new(config: &ProxyCacheConfig) -> Result<Self, String> {
+        if config.max_entries == 0 {
+            return Err("[proxy.cache] max_entries must be greater than 0".to_string());
+        }
+        Ok(ProxyCache {
+            ttl: Duration::from_secs(config.ttl_secs),
+            stale: Duration::from_secs(config.stale_while_revalidate_secs),
+            max_entries: config.max_entries,
+            max_body_size: config.max_body_size,
+            entries: Mutex::new(HashMap::new()),
+            inflight: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // 带凭据的请求可能得到因人而异的响应，与 WebSocket 升级请求一样不经过缓存
+    pub fn key(req: &Request) -> Option<String> {
+        if !(req.method() == Method::GET || req.method() == Method::HEAD)
+            || req.headers().contains_key(header::AUTHORIZATION)
+            || req.headers().contains_key(header::COOKIE)
+            || req.headers().contains_key(header::UPGRADE)
+        {
+            return None;
+        }
+        let host = req
+            .headers()
+            .get(header::HOST)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| req.uri().authority().map(|a| a.as_str()))
+            .unwrap_or_default();
+        let path = req
+            .uri()
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or("/");
+        Some(format!("{} {}{}", req.method(), host, path))
+    }
+
+    pub fn lookup(&self, key: &str, method: &Method, headers: &HeaderMap) -> Option<Hit> {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .get_mut(key)?
+            .iter_mut()
+            .find(|entry| entry.matches(headers) && !entry.expired(now))?;
+        let refresh = now.duration_since(entry.stored) >= entry.fresh && !entry.refreshing;
+        if refresh {
+            entry.refreshing = true;
+        }
+        Some(Hit {
+            response: entry.response(method),
+            refresh,
+        })
+    }
+
+    // 同一地址的未命中依次执行，后来的请求拿到锁时通常已经可以命中
+    pub fn inflight(&self, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        let mut inflight = self.inflight.lock().unwrap();
+        if let Some(lock) = inflight.get(key).and_then(Weak::upgrade) {
+            return lock;
+        }
+        inflight.retain(|_, lock| lock.strong_count() > 0);
+        let lock = Arc::new(tokio::sync::Mutex::new(()));
+        inflight.insert(key.to_string(), Arc::downgrade(&lock));
+        lock
+    }
+
+    // 按上游的 Cache-Control 得到新鲜时间与 stale-while-revalidate 时间，不可缓存时返回 None
+    fn lifetime(&self, status: StatusCode, headers: &HeaderMap) -> Option<(Duration, Duration)> {
+        if !CACHEABLE.contains(&status.as_u16()) || headers.contains_key(header::SET_COOKIE) {
+            return None;
+        }
+        if headers
+            .get_all(header::VARY)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| v.split(',').any(|name| name.trim() == "*"))
+        {
+            return None;
+        }
+        let mut max_age = None;
+        let mut s_maxage = None;
+        let mut stale = self.stale;
+        for directive in headers
+            .get_all(header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+        {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let secs = value
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_secs);
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" | "no-cache" | "private" => return None,
+                "max-age" => max_age = secs,
+                "s-maxage" => s_maxage = secs,
+                "stale-while-revalidate" => stale = secs.unwrap_or(stale),
+                _ => {}
+            }
+        }
+        let fresh = s_maxage.or(max_age).unwrap_or(self.ttl);
+        if fresh.is_zero() {
+            return None;
+        }
+        Some((fresh, stale))
+    }
+
+    // 可缓存的响应读入内存后写入缓存，返回内容相同的响应；其余响应原样返回
+    pub async fn store(&self, key: &str, req_headers: &HeaderMap, response: Response) -> Response {
+        let Some((fresh, stale)) = self.lifetime(response.status(), response.headers()) else {
+            self.refresh_done(key);
+            return response;
+        };
+        let length = response
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        let sized = length.is_some_and(|length| length <= self.max_body_size)
+            || response.status() == StatusCode::NO_CONTENT;
+        if !sized {
+            self.refresh_done(key);
+            return response;
+        }
+        let (parts, body) = response.into_parts();
+        let body = match axum::body::to_bytes(body, self.max_body_size as usize).await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to read upstream response for {}: {}", key, e);
+                self.refresh_done(key);
+                return StatusCode::BAD_GATEWAY.into_response();
+            }
+        };
+        let vary = parts
+            .headers
+            .get_all(header::VARY)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+            .map(|name| {
+                let value = req_headers.get(&name).cloned();
+                (name, value)
+            })
+            .collect();
+        let entry = Entry {
+            vary,
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: body.clone(),
+            stored: Instant::now(),
+            fresh,
+            stale,
+            refreshing: false,
+        };
+        self.insert(key, req_headers, entry);
+        debug!("Cached proxy response {} for {:?}", key, fresh);
+        Response::from_parts(parts, Body::from(body))
+    }
+
+    fn insert(&self, key: &str, req_headers: &HeaderMap, entry: Entry) {
+        let mut entries = self.entries.lock().unwrap();
+        let variants = entries.entry(key.to_string()).or_default();
+        variants.retain(|old| !old.matches(req_headers));
+        variants.push(entry);
+        let mut count: usize = entries.values().map(Vec::len).sum();
+        if count <= self.max_entries {
+            return;
+        }
+        let now = Instant::now();
+        entries.values_mut().for_each(|variants| {
+            variants.retain(|entry| !entry.expired(now));
+        });
+        entries.retain(|_, variants| !variants.is_empty());
+        count = entries.values().map(Vec::len).sum();
+        // 仍然超出时淘汰最早写入的响应
+        while count > self.max_entries {
+            let Some(oldest) = entries
+                .iter()
+                .flat_map(|(key, variants)| variants.iter().map(move |entry| (key, entry.stored)))
+                .min_by_key(|(_, stored)| *stored)
+                .map(|(key, stored)| (key.clone(), stored))
+            else {
+                break;
+            };
+            if let Some(variants) = entries.get_mut(&oldest.0) {
+                variants.retain(|entry| entry.stored != oldest.1);
+                if variants.is_empty() {
+                    entries.remove(&oldest.0);
+                }
+            }
+            count = entries.values().map(Vec::len).sum();
+        }
+    }
+
+    // 后台刷新得到不可缓存的响应时，下一次命中旧响应的请求再次尝试
+    fn refresh_done(&self, key: &str) {
+        if let Some(variants) = self.entries.lock().unwrap().get_mut(key) {
+            variants
+                .iter_mut()
+                .for_each(|entry| entry.refreshing = false);
+        }
+    }
+}
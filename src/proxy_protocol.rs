@@ -0,0 +1,218 @@
+// PROXY protocol v1/v2 解析：位于 TCP 层负载均衡之后时取得真实客户端地址
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+// v1 头部最长 107 字节（含 CRLF）
+const V1_MAX_LEN: usize = 107;
+// 等待 PROXY 头的最长时间
+const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 读取并解析连接开头的 PROXY 头。
+// 返回头部声明的源地址（LOCAL / UNKNOWN 时为 None），以及已读取但属于后续 HTTP 数据的字节
+pub async fn read_header<S>(stream: &mut S) -> io::Result<(Option<SocketAddr>, Vec<u8>)>
+where
+    S: AsyncRead + Unpin,
+{
+    tokio::time::timeout(HEADER_TIMEOUT, read_header_inner(stream))
+        .await
+        .map_err(|_| invalid("timed out waiting for PROXY protocol header"))?
+}
+
+async fn read_header_inner<S>(stream: &mut S) -> io::Result<(Option<SocketAddr>, Vec<u8>)>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buf = Vec::with_capacity(256);
+
+    // 先读到能区分 v1/v2 的长度
+    while buf.len() < 6 {
+        read_more(stream, &mut buf).await?;
+        if !V2_SIGNATURE.starts_with(&buf[..buf.len().min(12)])
+            && !b"PROXY ".starts_with(&buf[..buf.len().min(6)])
+        {
+            return Err(invalid(
+                "connection did not start with a PROXY protocol header",
+            ));
+        }
+    }
+
+    if buf.starts_with(b"PROXY ") {
+        loop {
+            if let Some(end) = buf.windows(2).position(|w| w == b"\r\n") {
+                let line = std::str::from_utf8(&buf[..end])
+                    .map_err(|_| invalid("PROXY v1 header is not ASCII"))?;
+                let addr = parse_v1(line)?;
+                let rest = buf[end + 2..].to_vec();
+                return Ok((addr, rest));
+            }
+            if buf.len() >= V1_MAX_LEN {
+                return Err(invalid("PROXY v1 header too long"));
+            }
+            read_more(stream, &mut buf).await?;
+        }
+    }
+
+    while buf.len() < 16 {
+        read_more(stream, &mut buf).await?;
+    }
+    if buf[..12] != V2_SIGNATURE {
+        return Err(invalid("invalid PROXY v2 signature"));
+    }
+    let len = u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    while buf.len() < 16 + len {
+        read_more(stream, &mut buf).await?;
+    }
+    let addr = parse_v2(buf[12], buf[13], &buf[16..16 + len])?;
+    let rest = buf[16 + len..].to_vec();
+    Ok((addr, rest))
+}
+
+async fn read_more<S>(stream: &mut S, buf: &mut Vec<u8>) -> io::Result<()>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut chunk = [0u8; 256];
+    let n = stream.read(&mut chunk).await?;
+    if n == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed during PROXY protocol header",
+        ));
+    }
+    buf.extend_from_slice(&chunk[..n]);
+    Ok(())
+}
+
+// "PROXY TCP4 192.0.2.1 198.51.100.1 51234 443"
+fn parse_v1(line: &str) -> io::Result<Option<SocketAddr>> {
+    let mut parts = line.split(' ');
+    parts.next(); // "PROXY"
+    match parts.next() {
+        Some("UNKNOWN") => Ok(None),
+        Some("TCP4") | Some("TCP6") => {
+            let src: IpAddr = parts
+                .next()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| invalid("invalid PROXY v1 source address"))?;
+            let _dst = parts.next();
+            let port: u16 = parts
+                .next()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| invalid("invalid PROXY v1 source port"))?;
+            Ok(Some(SocketAddr::new(src, port)))
+        }
+        _ => Err(invalid("unsupported PROXY v1 protocol")),
+    }
+}
+
+fn parse_v2(ver_cmd: u8, family: u8, body: &[u8]) -> io::Result<Option<SocketAddr>> {
+    if ver_cmd >> 4 != 2 {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    match ver_cmd & 0x0f {
+        // LOCAL：负载均衡器自身的健康检查等，使用真实对端地址
+        0 => return Ok(None),
+        1 => {}
+        _ => return Err(invalid("unsupported PROXY v2 command")),
+    }
+
+    match family >> 4 {
+        // AF_INET
+        1 if body.len() >= 12 => {
+            let ip = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let port = u16::from_be_bytes([body[8], body[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        }
+        // AF_INET6
+        2 if body.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&body[..16]);
+            let port = u16::from_be_bytes([body[32], body[33]]);
+            Ok(Some(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::from(octets)),
+                port,
+            )))
+        }
+        // AF_UNSPEC / AF_UNIX：没有可用的 IP 地址
+        0 | 3 => Ok(None),
+        _ => Err(invalid("truncated PROXY v2 address block")),
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// 在底层流前回放已读取的字节
+pub struct Rewind<S> {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: S,
+}
+
+impl<S> Rewind<S> {
+    pub fn with_prefix(inner: S, prefix: Vec<u8>) -> Self {
+        Rewind {
+            prefix,
+            pos: 0,
+            inner,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Rewind<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if this.pos < this.prefix.len() {
+            let remaining = &this.prefix[this.pos..];
+            let n = remaining.len().min(buf.remaining());
+            buf.put_slice(&remaining[..n]);
+            this.pos += n;
+            if this.pos == this.prefix.len() {
+                this.prefix = Vec::new();
+                this.pos = 0;
+            }
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Rewind<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
@@ -0,0 +1,225 @@
+// 多段 Range：bytes=0-99,200-299 返回 multipart/byteranges。内层服务（ServeDir、内存文件等）
+// 只处理单段 Range，多段请求在这里改为完整请求，再从响应正文中截取各段
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures_util::StreamExt;
+use std::io;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+// 超过该段数的请求忽略 Range，按完整内容返回，防止大量小段放大响应
+const MAX_RANGES: usize = 64;
+
+enum Spec {
+    FromTo(u64, Option<u64>),
+    Suffix(u64),
+}
+
+// 解析 "bytes=a-b,c-,-d"；只有一段、格式错误或段数过多时返回 None，交给内层服务按原样处理
+fn parse_specs(value: &HeaderValue) -> Option<Vec<Spec>> {
+    let specs = value.to_str().ok()?.trim().strip_prefix("bytes=")?;
+    if !specs.contains(',') {
+        return None;
+    }
+    let specs: Vec<Spec> = specs
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .map(|spec| {
+            let (start, end) = spec.split_once('-')?;
+            match (start.trim(), end.trim()) {
+                ("", suffix) => Some(Spec::Suffix(suffix.parse().ok()?)),
+                (start, "") => Some(Spec::FromTo(start.parse().ok()?, None)),
+                (start, end) => {
+                    let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+                    (start <= end).then_some(Spec::FromTo(start, Some(end)))
+                }
+            }
+        })
+        .collect::<Option<_>>()?;
+    (specs.len() <= MAX_RANGES).then_some(specs)
+}
+
+// 按文件长度解析为 [start, end)，丢弃无法满足的段，排序并合并重叠或相邻的段
+fn satisfiable(specs: &[Spec], len: u64) -> Vec<Range<u64>> {
+    let mut ranges: Vec<Range<u64>> = specs
+        .iter()
+        .filter_map(|spec| {
+            let range = match *spec {
+                Spec::Suffix(suffix) => len.saturating_sub(suffix)..len,
+                Spec::FromTo(start, end) => {
+                    start..end.map_or(len, |end| end.saturating_add(1).min(len))
+                }
+            };
+            (range.start < range.end).then_some(range)
+        })
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+fn boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    format!(
+        "{:016x}{:08x}",
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+fn part_header(boundary: &str, content_type: Option<&str>, range: &Range<u64>, len: u64) -> String {
+    let mut head = format!("\r\n--{}\r\n", boundary);
+    if let Some(content_type) = content_type {
+        head.push_str(&format!("Content-Type: {}\r\n", content_type));
+    }
+    head.push_str(&format!(
+        "Content-Range: bytes {}-{}/{}\r\n\r\n",
+        range.start,
+        range.end - 1,
+        len
+    ));
+    head
+}
+
+// 从完整正文中按顺序截取各段，每段前插入分段头；段已排序，正文只需读一遍
+fn select(body: Body, ranges: Vec<Range<u64>>, heads: Vec<String>, tail: String) -> Body {
+    let (tx, mut rx) = mpsc::channel::<io::Result<Bytes>>(8);
+    tokio::spawn(async move {
+        let mut stream = body.into_data_stream();
+        let mut offset = 0u64;
+        let mut index = 0;
+        let mut head_sent = false;
+        while index < ranges.len() {
+            let chunk = match stream.next().await {
+                Some(Ok(chunk)) => chunk,
+                Some(Err(e)) => {
+                    let _ = tx.send(Err(io::Error::other(e))).await;
+                    return;
+                }
+                None => return,
+            };
+            let chunk_start = offset;
+            let chunk_end = offset + chunk.len() as u64;
+            offset = chunk_end;
+            while let Some(range) = ranges.get(index).filter(|r| r.start < chunk_end) {
+                if !head_sent {
+                    head_sent = true;
+                    if !heads[index].is_empty()
+                        && tx
+                            .send(Ok(Bytes::from(heads[index].clone())))
+                            .await
+                            .is_err()
+                    {
+                        return;
+                    }
+                }
+                let from = range.start.max(chunk_start) - chunk_start;
+                let to = range.end.min(chunk_end) - chunk_start;
+                if from < to
+                    && tx
+                        .send(Ok(chunk.slice(from as usize..to as usize)))
+                        .await
+                        .is_err()
+                {
+                    return;
+                }
+                if range.end > chunk_end {
+                    break;
+                }
+                index += 1;
+                head_sent = false;
+            }
+        }
+        if !tail.is_empty() {
+            let _ = tx.send(Ok(Bytes::from(tail))).await;
+        }
+    });
+    Body::from_stream(futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx)))
+}
+
+pub async fn multipart(mut req: Request, next: Next) -> Response {
+    let is_head = req.method() == Method::HEAD;
+    if !(is_head || req.method() == Method::GET) {
+        return next.run(req).await;
+    }
+    let Some(specs) = req.headers().get(header::RANGE).and_then(parse_specs) else {
+        return next.run(req).await;
+    };
+    req.headers_mut().remove(header::RANGE);
+    let response = next.run(req).await;
+    let len = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let Some(len) = len.filter(|_| response.status() == StatusCode::OK) else {
+        return response;
+    };
+    let ranges = satisfiable(&specs, len);
+    if ranges.is_empty() {
+        let content_range = format!("bytes */{}", len);
+        return (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(header::CONTENT_RANGE, content_range)],
+        )
+            .into_response();
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.status = StatusCode::PARTIAL_CONTENT;
+    let (heads, tail, content_length) = if ranges.len() == 1 {
+        // 合并后只剩一段时按普通 206 返回
+        let range = &ranges[0];
+        let content_range = format!("bytes {}-{}/{}", range.start, range.end - 1, len);
+        if let Ok(value) = HeaderValue::from_str(&content_range) {
+            parts.headers.insert(header::CONTENT_RANGE, value);
+        }
+        (vec![String::new()], String::new(), range.end - range.start)
+    } else {
+        let boundary = boundary();
+        let content_type = parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let heads: Vec<String> = ranges
+            .iter()
+            .map(|range| part_header(&boundary, content_type.as_deref(), range, len))
+            .collect();
+        let tail = format!("\r\n--{}--\r\n", boundary);
+        let content_length = heads.iter().map(|head| head.len() as u64).sum::<u64>()
+            + ranges.iter().map(|r| r.end - r.start).sum::<u64>()
+            + tail.len() as u64;
+        if let Ok(value) =
+            HeaderValue::from_str(&format!("multipart/byteranges; boundary={}", boundary))
+        {
+            parts.headers.insert(header::CONTENT_TYPE, value);
+        }
+        (heads, tail, content_length)
+    };
+    parts
+        .headers
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+    let body = if is_head {
+        Body::empty()
+    } else {
+        select(body, ranges, heads, tail)
+    };
+    Response::from_parts(parts, body)
+}
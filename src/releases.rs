@@ -0,0 +1,335 @@
+// 蓝绿部署（[releases]）：发布目录下每个子目录是一个版本，部署端点把主目录原子切换到另一个版本，不需要重启。
+// 切换时为新版本建好站点再整体替换，处理中的请求仍由旧版本完成，不会出现新旧资源混在一起的响应；
+// 上一个版本保留在内存中，回滚立即生效。static_dir 是指向某个版本的符号链接时，切换同时原子更新链接，重启后仍提供新版本
+use crate::login;
+use crate::site::{self, SiteOptions};
+use crate::upload;
+use axum::extract::{Query, Request, State};
+use axum::http::header::HeaderMap;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tower::ServiceExt;
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ReleasesConfig {
+    // 发布目录，?to= 为其中的子目录名
+    pub dir: PathBuf,
+    #[serde(default = "default_path")]
+    pub path: String,
+    // 部署端点的 Bearer token，未设置时不启用部署端点
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub token: Option<String>,
+}
+
+fn default_path() -> String {
+    "/__release".to_string()
+}
+
+struct Release {
+    // 发布目录中的子目录名；不在发布目录中的初始版本为完整路径
+    name: String,
+    dir: PathBuf,
+    at: SystemTime,
+    router: Router,
+}
+
+struct Deployed {
+    current: Release,
+    previous: Option<Release>,
+}
+
+// 部署的目标
+enum Target {
+    Name(String),
+    // 与上一个版本互换
+    Rollback,
+    // 重新读取符号链接（如部署脚本执行 ln -sfn 之后）
+    Link,
+}
+
+pub struct Releases {
+    config: ReleasesConfig,
+    dir: PathBuf,
+    // static_dir 为符号链接时的链接路径
+    link: Option<PathBuf>,
+    options: SiteOptions,
+    deployed: RwLock<Deployed>,
+    // 部署依次执行
+    deploying: tokio::sync::Mutex<()>,
+}
+
+impl Releases {
+    pub fn new(
+        config: &ReleasesConfig,
+        static_dir: &str,
+        options: SiteOptions,
+    ) -> Result<Self, String> {
+        let dir = config
+            .dir
+            .canonicalize()
+            .map_err(|e| format!("[releases] cannot open dir {}: {}", config.dir.display(), e))?;
+        if !dir.is_dir() {
+            return Err(format!(
+                "[releases] dir {} is not a directory",
+                config.dir.display()
+            ));
+        }
+        if !config.path.starts_with('/') {
+            return Err("[releases] path must start with /".to_string());
+        }
+        if config.token.as_deref() == Some("") {
+            return Err("[releases] token must not be empty".to_string());
+        }
+        let link = std::fs::symlink_metadata(static_dir)
+            .is_ok_and(|meta| meta.file_type().is_symlink())
+            .then(|| PathBuf::from(static_dir));
+        let current = build(&dir, &options, Path::new(static_dir))?;
+        Ok(Releases {
+            config: config.clone(),
+            dir,
+            link,
+            options,
+            deployed: RwLock::new(Deployed {
+                current,
+                previous: None,
+            }),
+            deploying: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let deployed = self.deployed.read().unwrap();
+        match &self.link {
+            Some(link) => format!(
+                "{} -> {} in {}",
+                link.display(),
+                deployed.current.name,
+                self.dir.display()
+            ),
+            None => format!("{} in {}", deployed.current.name, self.dir.display()),
+        }
+    }
+
+    pub fn symlinked(&self) -> bool {
+        self.link.is_some()
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.config
+            .token
+            .as_ref()
+            .map(|_| self.config.path.as_str())
+    }
+
+    // 每个请求交给当前版本的站点，部署时整体替换
+    pub fn router(self: &Arc<Self>) -> Router {
+        let releases = self.clone();
+        Router::new().fallback_service(tower::service_fn(move |req: Request| {
+            let router = releases.deployed.read().unwrap().current.router.clone();
+            router.oneshot(req)
+        }))
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        upload::bearer_authorized(headers, self.config.token.as_deref())
+    }
+
+    fn status(&self) -> serde_json::Value {
+        let deployed = self.deployed.read().unwrap();
+        json!({
+            "current": deployed.current.name,
+            "previous": deployed.previous.as_ref().map(|release| &release.name),
+            "deployed_at": crate::error_pages::rfc3339(deployed.current.at),
+            "link": self.link.as_ref().map(|link| link.display().to_string()),
+        })
+    }
+
+    fn deploy(&self, target: Target) -> Result<bool, (StatusCode, String)> {
+        let bad_request = |e| (StatusCode::BAD_REQUEST, e);
+        let internal = |e| (StatusCode::INTERNAL_SERVER_ERROR, e);
+        let (current, previous) = {
+            let deployed = self.deployed.read().unwrap();
+            (
+                deployed.current.dir.clone(),
+                deployed
+                    .previous
+                    .as_ref()
+                    .map(|release| release.dir.clone()),
+            )
+        };
+        let dir = match &target {
+            Target::Name(name) => {
+                // 只能是发布目录中的一个子目录
+                let valid = !name.is_empty()
+                    && !name.starts_with('.')
+                    && !name.contains(['/', '\\'])
+                    && Path::new(name).components().count() == 1;
+                if !valid {
+                    return Err(bad_request(format!("invalid release name `{}`", name)));
+                }
+                self.dir
+                    .join(name)
+                    .canonicalize()
+                    .map_err(|e| bad_request(format!("unknown release `{}`: {}", name, e)))?
+            }
+            Target::Rollback => previous
+                .clone()
+                .ok_or_else(|| (StatusCode::CONFLICT, "no previous release".to_string()))?,
+            Target::Link => {
+                let Some(link) = &self.link else {
+                    return Err(bad_request(
+                        "static_dir is not a symlink, expected ?to=<release> or ?rollback=true"
+                            .to_string(),
+                    ));
+                };
+                link.canonicalize()
+                    .map_err(|e| internal(format!("cannot resolve {}: {}", link.display(), e)))?
+            }
+        };
+        if dir == current {
+            return Ok(false);
+        }
+        // 切回上一个版本时直接使用保留的站点
+        let release = if previous.as_ref() == Some(&dir) {
+            None
+        } else {
+            Some(build(&self.dir, &self.options, &dir).map_err(bad_request)?)
+        };
+        // 重新读取链接时链接已经指向新版本
+        if let Some(link) = self
+            .link
+            .as_ref()
+            .filter(|_| !matches!(target, Target::Link))
+        {
+            repoint(link, &dir).map_err(internal)?;
+        }
+        let (from, to) = {
+            let mut deployed = self.deployed.write().unwrap();
+            let release = match release {
+                Some(release) => release,
+                None => {
+                    let mut release = deployed.previous.take().expect("previous release");
+                    release.at = SystemTime::now();
+                    release
+                }
+            };
+            let old = std::mem::replace(&mut deployed.current, release);
+            let from = old.name.clone();
+            deployed.previous = Some(old);
+            (from, deployed.current.name.clone())
+        };
+        // 缓存中是旧版本的文件内容
+        if let Some(cache) = &self.options.cache {
+            cache.clear();
+        }
+        info!("Switched release {} -> {}", from, to);
+        Ok(true)
+    }
+}
+
+// 站点建在解析后的真实目录上，之后链接再变化也不会影响这个版本
+fn build(releases_dir: &Path, options: &SiteOptions, dir: &Path) -> Result<Release, String> {
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| format!("cannot open release {}: {}", dir.display(), e))?;
+    if !dir.is_dir() {
+        return Err(format!("release {} is not a directory", dir.display()));
+    }
+    let name = match dir.parent() {
+        Some(parent) if parent == releases_dir => dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        _ => dir.display().to_string(),
+    };
+    let router = site::router(&dir.to_string_lossy(), options.clone())?;
+    Ok(Release {
+        name,
+        dir,
+        at: SystemTime::now(),
+        router,
+    })
+}
+
+// 先在链接旁边建好指向新版本的临时链接，再改名覆盖原链接
+#[cfg(unix)]
+fn repoint(link: &Path, target: &Path) -> Result<(), String> {
+    let name = link
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let temp = link.with_file_name(format!(".{}.tmp{}", name, std::process::id()));
+    let _ = std::fs::remove_file(&temp);
+    std::os::unix::fs::symlink(target, &temp)
+        .and_then(|()| std::fs::rename(&temp, link))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&temp);
+            format!("failed to update {}: {}", link.display(), e)
+        })
+}
+
+#[cfg(not(unix))]
+fn repoint(link: &Path, _target: &Path) -> Result<(), String> {
+    Err(format!(
+        "cannot update symlink {} on this platform",
+        link.display()
+    ))
+}
+
+// GET 返回当前与上一个版本；POST ?to=<版本> 切换，?rollback=true 切回上一个版本，
+// 不带参数时重新读取 static_dir 符号链接的指向
+pub async fn status(State(releases): State<Arc<Releases>>, headers: HeaderMap) -> Response {
+    if !releases.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    login::no_store(Json(releases.status()).into_response())
+}
+
+pub async fn deploy(
+    State(releases): State<Arc<Releases>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if !releases.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    let target = if let Some(to) = query.get("to") {
+        Target::Name(to.clone())
+    } else if matches!(
+        query.get("rollback").map(String::as_str),
+        Some("true" | "1" | "on")
+    ) {
+        Target::Rollback
+    } else {
+        Target::Link
+    };
+    let _guard = releases.deploying.lock().await;
+    let worker = releases.clone();
+    let result = tokio::task::spawn_blocking(move || worker.deploy(target)).await;
+    let response = match result {
+        Ok(Ok(changed)) => {
+            let mut status = releases.status();
+            status["changed"] = changed.into();
+            Json(status).into_response()
+        }
+        Ok(Err((status, e))) => {
+            warn!("Release switch failed: {}", e);
+            (status, Json(json!({ "error": e }))).into_response()
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        )
+            .into_response(),
+    };
+    login::no_store(response)
+}
@@ -0,0 +1,246 @@
+// 浏览器报告收集端点（[reports]）：接收 CSP 违规报告（report-uri 的 application/csp-report）、
+// Reporting API（report-to 与 NEL 的 application/reports+json），逐条记录为结构化日志事件。
+// 配合 [security_headers] 的 content_security_policy_report_only 与 reporting_endpoints，
+// 不需要另外部署收集服务就能先以 report-only 方式试行 CSP
+use crate::chaos;
+use crate::forwarded::ClientInfo;
+use crate::server::ClientAddr;
+use axum::extract::{Request, State};
+use axum::http::header;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use tracing::{debug, info};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ReportsConfig {
+    // 收集端点路径，只接受 POST
+    #[serde(default = "default_path")]
+    pub path: String,
+    // 请求体大小上限，超出返回 413
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    // 一次请求中最多记录的报告条数，其余丢弃
+    #[serde(default = "default_max_reports")]
+    pub max_reports: usize,
+    // 记录报告的比例，0.0 ~ 1.0；流量大时调低以免日志被刷屏
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+}
+
+fn default_path() -> String {
+    "/__reports".to_string()
+}
+
+fn default_max_body_size() -> usize {
+    64 * 1024
+}
+
+fn default_max_reports() -> usize {
+    100
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+pub struct Reports {
+    path: String,
+    max_body_size: usize,
+    max_reports: usize,
+    sample_rate: f64,
+}
+
+impl Reports {
+    pub fn new(config: &ReportsConfig) -> Result<Self, String> {
+        if !config.path.starts_with('/') {
+            return Err("[reports] path must start with /".to_string());
+        }
+        if !(0.0..=1.0).contains(&config.sample_rate) {
+            return Err("[reports] sample_rate must be between 0.0 and 1.0".to_string());
+        }
+        Ok(Reports {
+            path: config.path.clone(),
+            max_body_size: config.max_body_size,
+            max_reports: config.max_reports,
+            sample_rate: config.sample_rate,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn sampled(&self) -> bool {
+        self.sample_rate >= 1.0 || chaos::random() < self.sample_rate
+    }
+}
+
+// 一条报告中用于日志的字段
+struct Report {
+    kind: String,
+    url: String,
+    summary: String,
+    body: Value,
+}
+
+// report-uri 的旧格式：{"csp-report": {"document-uri": ..., "violated-directive": ...}}
+fn legacy_csp(report: &Map<String, Value>) -> Report {
+    let text = |key: &str| report.get(key).and_then(Value::as_str).unwrap_or("");
+    let directive = match text("effective-directive") {
+        "" => text("violated-directive"),
+        directive => directive,
+    };
+    Report {
+        kind: "csp-violation".to_string(),
+        url: text("document-uri").to_string(),
+        summary: violation(
+            directive,
+            text("blocked-uri"),
+            text("disposition"),
+            text("source-file"),
+            report.get("line-number"),
+        ),
+        body: Value::Object(report.clone()),
+    }
+}
+
+// Reporting API：[{"type": "csp-violation", "url": ..., "body": {...}}, ...]
+fn reporting_api(report: &Map<String, Value>) -> Report {
+    let kind = report
+        .get("type")
+        .and_then(Value::as_str)
+        .unwrap_or("unknown");
+    let url = report.get("url").and_then(Value::as_str).unwrap_or("");
+    let body = report.get("body").cloned().unwrap_or(Value::Null);
+    let text = |key: &str| body.get(key).and_then(Value::as_str).unwrap_or("");
+    let summary = match kind {
+        "csp-violation" => violation(
+            text("effectiveDirective"),
+            text("blockedURL"),
+            text("disposition"),
+            text("sourceFile"),
+            body.get("lineNumber"),
+        ),
+        // NEL：{"type": "tcp.timed_out", "phase": "connection", "status_code": 0, ...}
+        "network-error" => format!(
+            "{} during {} (status {})",
+            text("type"),
+            text("phase"),
+            body.get("status_code").unwrap_or(&Value::Null)
+        ),
+        _ => match text("message") {
+            "" => text("id").to_string(),
+            message => message.to_string(),
+        },
+    };
+    Report {
+        kind: kind.to_string(),
+        url: url.to_string(),
+        summary,
+        body,
+    }
+}
+
+fn violation(
+    directive: &str,
+    blocked: &str,
+    disposition: &str,
+    source: &str,
+    line: Option<&Value>,
+) -> String {
+    let mut summary = format!("{} blocked {}", directive, blocked);
+    if !disposition.is_empty() {
+        summary.push_str(&format!(" ({})", disposition));
+    }
+    if !source.is_empty() {
+        summary.push_str(&format!(" at {}", source));
+        if let Some(line) = line {
+            summary.push_str(&format!(":{}", line));
+        }
+    }
+    summary
+}
+
+fn parse(data: &[u8]) -> Result<Vec<Report>, String> {
+    let value: Value = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+    match value {
+        Value::Object(object) => match object.get("csp-report") {
+            Some(Value::Object(report)) => Ok(vec![legacy_csp(report)]),
+            _ => Ok(vec![reporting_api(&object)]),
+        },
+        Value::Array(items) => Ok(items
+            .iter()
+            .filter_map(Value::as_object)
+            .map(reporting_api)
+            .collect()),
+        _ => Err("expected a JSON object or array".to_string()),
+    }
+}
+
+// 浏览器不关心响应内容，成功时返回 204
+pub async fn collect(State(reports): State<Arc<Reports>>, req: Request) -> Response {
+    let json = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .is_some_and(|v| {
+            matches!(
+                v.as_str(),
+                "application/csp-report" | "application/reports+json" | "application/json"
+            )
+        });
+    if !json {
+        return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+    }
+    let client = req
+        .extensions()
+        .get::<ClientInfo>()
+        .and_then(|info| info.ip)
+        .map(|ip| ip.to_string())
+        .or_else(|| {
+            req.extensions()
+                .get::<ClientAddr>()
+                .map(|addr| addr.0.to_string())
+        })
+        .unwrap_or_else(|| "-".to_string());
+    let user_agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("")
+        .to_string();
+    let data = match axum::body::to_bytes(req.into_body(), reports.max_body_size).await {
+        Ok(data) => data,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    let parsed = match parse(&data) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            debug!("Rejected report from {}: {}", client, e);
+            return (StatusCode::BAD_REQUEST, e).into_response();
+        }
+    };
+    for report in parsed.into_iter().take(reports.max_reports) {
+        if !reports.sampled() {
+            continue;
+        }
+        info!(
+            report_type = %report.kind,
+            url = %report.url,
+            client = %client,
+            user_agent = %user_agent,
+            body = %report.body,
+            "{} report for {}: {}",
+            report.kind,
+            report.url,
+            report.summary
+        );
+    }
+    StatusCode::NO_CONTENT.into_response()
+}
@@ -0,0 +1,116 @@
+// 请求 ID 与 W3C Trace Context：为每个请求分配 X-Request-ID（可信代理传入时沿用），
+// 写入日志 span、错误页与响应头；traceparent 以本请求为父节点继续传给反向代理的上游
+use crate::forwarded::ClientInfo;
+use axum::extract::Request;
+use axum::http::header::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::Instrument;
+
+const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+// 沿用的请求 ID 长度上限，防止日志被超长的值污染
+const MAX_LEN: usize = 128;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// 请求扩展，访问日志等使用
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+// 进程号、时间与序号的摘要：前 16 字节作为 trace-id，后 8 字节作为 span-id
+pub fn random_ids() -> (String, String) {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let seed = format!(
+        "{}-{}-{}",
+        std::process::id(),
+        nanos,
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    );
+    let digest = Sha256::digest(seed.as_bytes());
+    let hex = |bytes: &[u8]| -> String { bytes.iter().map(|b| format!("{:02x}", b)).collect() };
+    (hex(&digest[..16]), hex(&digest[16..24]))
+}
+
+// "00-<trace-id>-<parent-id>-<flags>"，返回 trace-id 与 flags；全零的 ID 无效
+fn parse_traceparent(value: &str) -> Option<(&str, &str)> {
+    let mut parts = value.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+    let is_hex = |s: &str, len: usize| {
+        s.len() == len
+            && s.bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    };
+    let valid = is_hex(version, 2)
+        && version != "ff"
+        && is_hex(trace_id, 32)
+        && is_hex(parent_id, 16)
+        && is_hex(flags, 2)
+        && trace_id.bytes().any(|b| b != b'0')
+        && parent_id.bytes().any(|b| b != b'0')
+        // 版本 00 不允许多余的字段
+        && (version != "00" || parts.next().is_none());
+    valid.then_some((trace_id, flags))
+}
+
+fn usable_id(value: &HeaderValue) -> Option<&str> {
+    let id = value.to_str().ok()?.trim();
+    (!id.is_empty() && id.len() <= MAX_LEN && id.bytes().all(|b| b.is_ascii_graphic()))
+        .then_some(id)
+}
+
+pub async fn assign(mut req: Request, next: Next) -> Response {
+    let trusted = req
+        .extensions()
+        .get::<ClientInfo>()
+        .is_some_and(|info| info.trusted);
+    let headers = req.headers();
+    let incoming_id = headers
+        .get(REQUEST_ID)
+        .filter(|_| trusted)
+        .and_then(usable_id)
+        .map(str::to_string);
+    let incoming_trace = headers
+        .get(TRACEPARENT)
+        .filter(|_| trusted)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_traceparent)
+        .map(|(trace_id, flags)| (trace_id.to_string(), flags.to_string()));
+
+    let (new_trace_id, span_id) = random_ids();
+    let continued = incoming_trace.is_some();
+    let (trace_id, flags) = incoming_trace.unwrap_or((new_trace_id, "01".to_string()));
+    // 没有传入请求 ID 时使用 trace-id，日志与链路追踪系统中可以直接对应
+    let request_id = incoming_id.unwrap_or_else(|| trace_id.clone());
+
+    let headers = req.headers_mut();
+    if !continued {
+        headers.remove(TRACESTATE);
+    }
+    if let Ok(value) = HeaderValue::from_str(&format!("00-{}-{}-{}", trace_id, span_id, flags)) {
+        headers.insert(TRACEPARENT, value);
+    }
+    let value = HeaderValue::from_str(&request_id).ok();
+    if let Some(value) = &value {
+        headers.insert(REQUEST_ID, value.clone());
+    }
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let span = tracing::info_span!("request", id = %request_id);
+    let mut response = next.run(req).instrument(span).await;
+    if let Some(value) = value {
+        response.headers_mut().insert(REQUEST_ID, value);
+    }
+    response
+}
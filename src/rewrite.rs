@@ -0,0 +1,139 @@
+// 重写与重定向规则：按正则或 glob 匹配请求路径，支持 $1 / ${name} 捕获替换
+use axum::extract::{OriginalUri, Request, State};
+use axum::http::{header, HeaderValue, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct RewriteConfig {
+    // 正则表达式（与 glob 二选一）
+    #[serde(default)]
+    pub regex: Option<String>,
+    // glob 模式：* 匹配单段、** 匹配任意层级、? 匹配单个字符，每个通配符依次对应 $1、$2…
+    #[serde(default)]
+    pub glob: Option<String>,
+    // 目标路径，可引用捕获组
+    pub to: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct RedirectConfig {
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub glob: Option<String>,
+    // 目标地址，可以是路径或完整 URL
+    pub to: String,
+    // 301 / 302 / 303 / 307 / 308
+    #[serde(default = "default_redirect_status")]
+    pub status: u16,
+}
+
+fn default_redirect_status() -> u16 {
+    301
+}
+
+struct Rule {
+    pattern: Regex,
+    to: String,
+}
+
+impl Rule {
+    fn new(regex: &Option<String>, glob: &Option<String>, to: &str) -> Result<Self, String> {
+        let source = match (regex, glob) {
+            (Some(regex), None) => regex.clone(),
+            (None, Some(glob)) => crate::glob::to_regex(glob),
+            _ => {
+                return Err(format!(
+                    "rule for `{}` must set exactly one of `regex` and `glob`",
+                    to
+                ))
+            }
+        };
+        let pattern =
+            Regex::new(&source).map_err(|e| format!("invalid pattern `{}`: {}", source, e))?;
+        Ok(Rule {
+            pattern,
+            to: to.to_string(),
+        })
+    }
+
+    // 匹配成功时返回替换后的目标；未包含查询串时保留原请求的查询串
+    fn apply(&self, uri: &Uri) -> Option<String> {
+        let captures = self.pattern.captures(uri.path())?;
+        let mut target = String::new();
+        captures.expand(&self.to, &mut target);
+        if let Some(query) = uri.query().filter(|_| !target.contains('?')) {
+            target.push('?');
+            target.push_str(query);
+        }
+        Some(target)
+    }
+}
+
+pub struct Rules {
+    redirects: Vec<(Rule, StatusCode)>,
+    rewrites: Vec<Rule>,
+}
+
+impl Rules {
+    pub fn new(redirects: &[RedirectConfig], rewrites: &[RewriteConfig]) -> Result<Self, String> {
+        let redirects = redirects
+            .iter()
+            .map(|r| {
+                let status = match r.status {
+                    301 | 302 | 303 | 307 | 308 => StatusCode::from_u16(r.status).unwrap(),
+                    other => return Err(format!("invalid redirect status {}", other)),
+                };
+                Ok((Rule::new(&r.regex, &r.glob, &r.to)?, status))
+            })
+            .collect::<Result<_, String>>()?;
+        let rewrites = rewrites
+            .iter()
+            .map(|r| Rule::new(&r.regex, &r.glob, &r.to))
+            .collect::<Result<_, String>>()?;
+        Ok(Rules {
+            redirects,
+            rewrites,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.redirects.is_empty() && self.rewrites.is_empty()
+    }
+}
+
+// 先检查重定向，再按顺序应用第一条匹配的重写规则
+pub async fn apply(State(rules): State<Arc<Rules>>, mut req: Request, next: Next) -> Response {
+    for (rule, status) in &rules.redirects {
+        if let Some(location) = rule.apply(req.uri()) {
+            debug!("Redirect {} -> {} ({})", req.uri(), location, status);
+            return match HeaderValue::from_str(&location) {
+                Ok(value) => (*status, [(header::LOCATION, value)]).into_response(),
+                Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            };
+        }
+    }
+
+    for rule in &rules.rewrites {
+        if let Some(target) = rule.apply(req.uri()) {
+            match target.parse::<Uri>() {
+                Ok(uri) => {
+                    debug!("Rewrite {} -> {}", req.uri(), uri);
+                    // 内部重写对后续路由完全透明，挂载点等按重写后的地址计算跳转
+                    req.extensions_mut().insert(OriginalUri(uri.clone()));
+                    *req.uri_mut() = uri;
+                }
+                Err(e) => debug!("Ignoring invalid rewrite target {}: {}", target, e),
+            }
+            break;
+        }
+    }
+
+    next.run(req).await
+}
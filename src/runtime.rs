@@ -0,0 +1,77 @@
+// tokio 运行时（[runtime]）：工作线程数、阻塞线程池上限与线程名；
+// 小容器中可以改用单线程运行时，所有任务在主线程上执行
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::{Builder, Runtime};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Flavor {
+    #[default]
+    MultiThread,
+    CurrentThread,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct RuntimeConfig {
+    // multi_thread（默认）或 current_thread
+    #[serde(default)]
+    pub flavor: Flavor,
+    // 工作线程数，默认等于 CPU 核数（也可以用环境变量 TOKIO_WORKER_THREADS 设置）
+    #[serde(default)]
+    pub worker_threads: Option<usize>,
+    // 阻塞线程池（读文件、压缩、图片处理等）的线程上限，默认 512
+    #[serde(default)]
+    pub max_blocking_threads: Option<usize>,
+    // 线程名，显示在 top -H、调试器与崩溃信息中
+    #[serde(default = "default_thread_name")]
+    pub thread_name: String,
+}
+
+fn default_thread_name() -> String {
+    "sonic-wave".to_string()
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            flavor: Flavor::default(),
+            worker_threads: None,
+            max_blocking_threads: None,
+            thread_name: default_thread_name(),
+        }
+    }
+}
+
+// 检查 Builder 会 panic 的设置
+pub fn validate(config: &RuntimeConfig) -> Result<(), String> {
+    if config.flavor == Flavor::CurrentThread && config.worker_threads.is_some() {
+        return Err("[runtime] worker_threads requires flavor = \"multi_thread\"".to_string());
+    }
+    if config.worker_threads == Some(0) {
+        return Err("[runtime] worker_threads must be greater than 0".to_string());
+    }
+    if config.max_blocking_threads == Some(0) {
+        return Err("[runtime] max_blocking_threads must be greater than 0".to_string());
+    }
+    Ok(())
+}
+
+pub fn build(config: &RuntimeConfig) -> Result<Runtime, String> {
+    validate(config)?;
+    let mut builder = match config.flavor {
+        Flavor::MultiThread => Builder::new_multi_thread(),
+        Flavor::CurrentThread => Builder::new_current_thread(),
+    };
+    if let Some(threads) = config.worker_threads {
+        builder.worker_threads(threads);
+    }
+    if let Some(threads) = config.max_blocking_threads {
+        builder.max_blocking_threads(threads);
+    }
+    builder
+        .thread_name(config.thread_name.clone())
+        .enable_all()
+        .build()
+        .map_err(|e| format!("failed to start the tokio runtime: {}", e))
+}
@@ -0,0 +1,218 @@
+// 运行时环境注入（[runtime_env]）：返回 HTML 时替换 __NAME__ 占位符并注入 window.ENV，
+// 同一份构建产物可以按部署环境配置
+use crate::index::etag_matches;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct RuntimeEnvConfig {
+    // 从进程环境变量读取的变量名，启动时读取一次
+    #[serde(default)]
+    pub env: Vec<String>,
+    // 固定值，与环境变量同名时以环境变量为准
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+    // 将 HTML 中的 __NAME__ 替换为变量值
+    #[serde(default = "default_placeholders")]
+    pub placeholders: bool,
+    // 在 </head> 前注入 <script>window.<global>={...}</script>；空字符串表示不注入
+    #[serde(default = "default_global")]
+    pub global: String,
+    // 超过该大小的页面按原样返回
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+}
+
+fn default_placeholders() -> bool {
+    true
+}
+
+fn default_global() -> String {
+    "ENV".to_string()
+}
+
+fn default_max_size() -> usize {
+    8 * 1024 * 1024
+}
+
+pub struct RuntimeEnv {
+    vars: BTreeMap<String, String>,
+    placeholders: bool,
+    script: Option<String>,
+    max_size: usize,
+    // 变量内容的摘要，参与 ETag 计算，配置变化后浏览器缓存随之失效
+    digest: String,
+}
+
+impl RuntimeEnv {
+    pub fn new(config: &RuntimeEnvConfig) -> Result<Self, String> {
+        let mut vars = config.values.clone();
+        for name in &config.env {
+            match std::env::var(name) {
+                Ok(value) => {
+                    vars.insert(name.clone(), value);
+                }
+                Err(_) => warn!("Environment variable {} is not set, not injecting it", name),
+            }
+        }
+        let global = config.global.trim();
+        let script = if global.is_empty() {
+            None
+        } else {
+            if !global.split('.').all(|part| {
+                !part.is_empty()
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+            }) {
+                return Err(format!("invalid runtime_env global `{}`", global));
+            }
+            let json = serde_json::to_string(&vars).map_err(|e| e.to_string())?;
+            // 防止变量值中的 "</script>" 提前结束脚本
+            let json = json.replace("</", "<\\/").replace("<!--", "<\\!--");
+            Some(format!("<script>window.{}={};</script>", global, json))
+        };
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_string(&vars).unwrap_or_default().as_bytes());
+        hasher.update([config.placeholders as u8]);
+        hasher.update(script.as_deref().unwrap_or_default().as_bytes());
+        let digest: String = hasher.finalize()[..8]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        Ok(RuntimeEnv {
+            vars,
+            placeholders: config.placeholders,
+            script,
+            max_size: config.max_size,
+            digest,
+        })
+    }
+
+    fn apply(&self, html: &str) -> String {
+        let mut html = html.to_string();
+        if self.placeholders {
+            for (name, value) in &self.vars {
+                html = html.replace(&format!("__{}__", name), value);
+            }
+        }
+        if let Some(script) = &self.script {
+            match find_ignore_case(&html, "</head>").or_else(|| find_ignore_case(&html, "<body")) {
+                Some(pos) => html.insert_str(pos, script),
+                None => html.insert_str(0, script),
+            }
+        }
+        html
+    }
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack.to_ascii_lowercase().find(needle)
+}
+
+// 目录、无扩展名（clean URL、SPA 路由）、.md 与 HTML 类型的路径可能返回页面，响应类型最终由 Content-Type 判断
+fn page_like(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    !name.contains('.')
+        || name.to_ascii_lowercase().ends_with(".md")
+        || mime_guess::from_path(name)
+            .first()
+            .is_some_and(|mime| mime.essence_str() == "text/html")
+}
+
+// 在 resolve 之后执行，SPA 的 fallback 页面同样会被注入
+pub async fn inject(State(env): State<Arc<RuntimeEnv>>, mut req: Request, next: Next) -> Response {
+    if !(req.method() == Method::GET || req.method() == Method::HEAD)
+        || !page_like(req.uri().path())
+    {
+        return next.run(req).await;
+    }
+    let is_head = req.method() == Method::HEAD;
+    // 需要完整的未压缩页面；条件请求按注入后的结果判断
+    let if_none_match = req.headers_mut().remove(header::IF_NONE_MATCH);
+    for name in [
+        header::RANGE,
+        header::IF_RANGE,
+        header::IF_MODIFIED_SINCE,
+        header::ACCEPT_ENCODING,
+    ] {
+        req.headers_mut().remove(name);
+    }
+    if is_head {
+        *req.method_mut() = Method::GET;
+    }
+    let response = next.run(req).await;
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    let too_large = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| len > env.max_size);
+    if response.status() != StatusCode::OK
+        || !is_html
+        || too_large
+        || response.headers().contains_key(header::CONTENT_ENCODING)
+    {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, env.max_size).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Not injecting runtime environment: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let html = env.apply(&String::from_utf8_lossy(&bytes));
+
+    let validator = [header::ETAG, header::LAST_MODIFIED]
+        .iter()
+        .filter_map(|name| parts.headers.get(name)?.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(";");
+    let mut hasher = Sha256::new();
+    hasher.update(validator.as_bytes());
+    hasher.update(env.digest.as_bytes());
+    let hex: String = hasher.finalize()[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let etag = HeaderValue::from_str(&format!("W/\"env-{}\"", hex)).unwrap();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::ACCEPT_RANGES);
+    parts.headers.remove(header::LAST_MODIFIED);
+    let not_modified = if_none_match
+        .as_ref()
+        .and_then(|tags| tags.to_str().ok())
+        .is_some_and(|tags| etag_matches(tags, &etag));
+    parts.headers.insert(header::ETAG, etag);
+    if not_modified {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_TYPE);
+        return Response::from_parts(parts, Body::empty());
+    }
+    let body = if is_head {
+        parts
+            .headers
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(html.len()));
+        Body::empty()
+    } else {
+        Body::from(html)
+    };
+    Response::from_parts(parts, body)
+}
@@ -0,0 +1,717 @@
+// 对象存储后端（backend = "s3"）：站点内容来自 S3 兼容存储的 bucket，按 AWS SigV4 签名请求；
+// 可选将取回的对象缓存到本地磁盘，过期后以 If-None-Match 重新验证
+use crate::memfs::{self, MemFile};
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use axum::http::{Method, Response, StatusCode, Uri};
+use hmac::{Hmac, Mac};
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+use tower::Service;
+use tracing::{debug, warn};
+
+pub const ROOT: &str = "<s3>";
+
+// 路径查找结果的缓存时间，避免解析索引文件、clean URL 时反复请求存储
+const LOOKUP_TTL: Duration = Duration::from_secs(10);
+const LOOKUP_CAPACITY: usize = 10_000;
+
+// 空请求体的 SHA-256
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+// SigV4 只保留 RFC 3986 的非保留字符
+const KEY_CHARS: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+const PATH_CHARS: &AsciiSet = &KEY_CHARS.remove(b'/');
+
+// 原样返回给客户端的对象响应头；Cache-Control 由站点的缓存策略决定
+const PASS_HEADERS: [HeaderName; 7] = [
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::CONTENT_RANGE,
+    header::CONTENT_ENCODING,
+    header::ETAG,
+    header::LAST_MODIFIED,
+    header::ACCEPT_RANGES,
+];
+
+// 转发给存储的条件请求与 Range 头
+const FORWARD_HEADERS: [HeaderName; 4] = [
+    header::RANGE,
+    header::IF_NONE_MATCH,
+    header::IF_MODIFIED_SINCE,
+    header::IF_RANGE,
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    #[default]
+    Fs,
+    S3,
+    Git,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct S3Config {
+    pub bucket: String,
+    // 对象键前缀，如 "site/"
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    // S3 兼容服务（MinIO、R2 等）的地址，默认 https://s3.{region}.amazonaws.com
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    // 以 {endpoint}/{bucket}/key 访问，而不是 {bucket}.{endpoint}/key
+    #[serde(default)]
+    pub path_style: bool,
+    // 访问凭证，未设置时读取 AWS_ACCESS_KEY_ID / AWS_SECRET_ACCESS_KEY / AWS_SESSION_TOKEN；
+    // 都没有时发送匿名请求（公开 bucket）
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub secret_access_key: Option<String>,
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub session_token: Option<String>,
+    // 本地磁盘缓存目录，未设置时不缓存
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+    // 缓存的对象在该时间内直接返回，过期后重新验证
+    #[serde(default = "default_cache_ttl")]
+    pub cache_ttl_secs: u64,
+    // 超过该大小的对象不缓存
+    #[serde(default = "default_cache_max_file_size")]
+    pub cache_max_file_size: u64,
+    // 等待存储响应头的超时（秒）
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_cache_ttl() -> u64 {
+    60
+}
+
+fn default_cache_max_file_size() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+type HttpClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+pub struct S3Store {
+    root: PathBuf,
+    // scheme + authority，如 https://bucket.s3.us-east-1.amazonaws.com
+    origin: String,
+    host: String,
+    // path-style 时为 "/bucket"
+    base_path: String,
+    prefix: String,
+    region: String,
+    credentials: Option<Credentials>,
+    client: HttpClient,
+    timeout: Duration,
+    // 路径 -> (查询时间, 是否为目录；None 表示不存在)
+    lookups: Mutex<HashMap<PathBuf, (Instant, Option<bool>)>>,
+    cache: Option<DiskCache>,
+}
+
+impl S3Store {
+    pub fn new(config: &S3Config) -> Result<Self, String> {
+        if config.bucket.is_empty() {
+            return Err("s3 bucket must not be empty".to_string());
+        }
+        let endpoint = config
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", config.region));
+        let uri: Uri = endpoint
+            .parse()
+            .map_err(|e| format!("invalid s3 endpoint `{}`: {}", endpoint, e))?;
+        let scheme = match uri.scheme_str() {
+            Some(scheme @ ("http" | "https")) => scheme,
+            _ => {
+                return Err(format!(
+                    "s3 endpoint `{}` must be an http:// or https:// URL",
+                    endpoint
+                ))
+            }
+        };
+        let authority = uri
+            .authority()
+            .ok_or_else(|| format!("s3 endpoint `{}` has no host", endpoint))?
+            .as_str();
+        let (host, base_path) = if config.path_style {
+            (authority.to_string(), format!("/{}", config.bucket))
+        } else {
+            (format!("{}.{}", config.bucket, authority), String::new())
+        };
+
+        let access_key_id = config
+            .access_key_id
+            .clone()
+            .or_else(|| std::env::var("AWS_ACCESS_KEY_ID").ok());
+        let secret_access_key = config
+            .secret_access_key
+            .clone()
+            .or_else(|| std::env::var("AWS_SECRET_ACCESS_KEY").ok());
+        let credentials = match (access_key_id, secret_access_key) {
+            (Some(access_key_id), Some(secret_access_key)) => Some(Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: config
+                    .session_token
+                    .clone()
+                    .or_else(|| std::env::var("AWS_SESSION_TOKEN").ok()),
+            }),
+            (None, None) => None,
+            _ => {
+                return Err(
+                    "s3 access_key_id and secret_access_key must be set together".to_string(),
+                )
+            }
+        };
+
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(connector);
+
+        let prefix = config.prefix.trim_matches('/');
+        Ok(S3Store {
+            root: PathBuf::from(ROOT),
+            origin: format!("{}://{}", scheme, host),
+            host,
+            base_path,
+            prefix: if prefix.is_empty() {
+                String::new()
+            } else {
+                format!("{}/", prefix)
+            },
+            region: config.region.clone(),
+            credentials,
+            client: Client::builder(TokioExecutor::new()).build(https),
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+            lookups: Mutex::new(HashMap::new()),
+            cache: config.cache_dir.as_ref().map(|dir| DiskCache {
+                dir: dir.clone(),
+                ttl: Duration::from_secs(config.cache_ttl_secs),
+                max_file_size: config.cache_max_file_size,
+            }),
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn describe(&self) -> String {
+        format!("{}{}/{}", self.origin, self.base_path, self.prefix)
+    }
+
+    // site::fs_path 形式的路径 -> 对象键
+    fn key(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let segments: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        Some(format!("{}{}", self.prefix, segments.join("/")))
+    }
+
+    // 发送签名请求；query 需按参数名排序
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        forward: &HeaderMap,
+    ) -> Result<Response<hyper::body::Incoming>, String> {
+        let path = format!(
+            "{}/{}",
+            self.base_path,
+            utf8_percent_encode(key, PATH_CHARS)
+        );
+        let query = query
+            .iter()
+            .map(|(name, value)| {
+                format!(
+                    "{}={}",
+                    utf8_percent_encode(name, KEY_CHARS),
+                    utf8_percent_encode(value, KEY_CHARS)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        let uri = if query.is_empty() {
+            format!("{}{}", self.origin, path)
+        } else {
+            format!("{}{}?{}", self.origin, path, query)
+        };
+
+        let mut builder = axum::http::Request::builder()
+            .method(method.clone())
+            .uri(&uri)
+            .header(header::HOST, &self.host);
+        for name in FORWARD_HEADERS {
+            if let Some(value) = forward.get(&name) {
+                builder = builder.header(name, value);
+            }
+        }
+        if let Some(credentials) = &self.credentials {
+            let amz_date = crate::error_pages::rfc3339(SystemTime::now()).replace(['-', ':'], "");
+            let date = &amz_date[..8];
+            let mut signed = vec![
+                ("host", self.host.clone()),
+                ("x-amz-content-sha256", EMPTY_SHA256.to_string()),
+                ("x-amz-date", amz_date.clone()),
+            ];
+            if let Some(token) = &credentials.session_token {
+                signed.push(("x-amz-security-token", token.clone()));
+            }
+            let canonical_headers: String = signed
+                .iter()
+                .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+                .collect();
+            let signed_headers = signed
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>()
+                .join(";");
+            let canonical_request = format!(
+                "{}\n{}\n{}\n{}\n{}\n{}",
+                method, path, query, canonical_headers, signed_headers, EMPTY_SHA256
+            );
+            let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+            let string_to_sign = format!(
+                "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+                amz_date,
+                scope,
+                hex(&Sha256::digest(canonical_request.as_bytes()))
+            );
+            let mut key = hmac(
+                format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+                date.as_bytes(),
+            );
+            for part in [self.region.as_str(), "s3", "aws4_request"] {
+                key = hmac(&key, part.as_bytes());
+            }
+            let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+            let authorization = format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                credentials.access_key_id, scope, signed_headers, signature
+            );
+            for (name, value) in signed.into_iter().skip(1) {
+                builder = builder.header(name, value);
+            }
+            builder = builder.header(header::AUTHORIZATION, authorization);
+        }
+        let request = builder.body(Body::empty()).map_err(|e| e.to_string())?;
+        match tokio::time::timeout(self.timeout, self.client.request(request)).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(e)) => Err(format!("request to {} failed: {}", uri, e)),
+            Err(_) => Err(format!("request to {} timed out", uri)),
+        }
+    }
+
+    // 路径是否存在、是否为"目录"（存在以其为前缀的对象）
+    pub async fn is_dir(&self, path: &Path) -> Option<bool> {
+        if path == self.root {
+            return Some(true);
+        }
+        if let Some((at, found)) = self.lookups.lock().unwrap().get(path) {
+            if at.elapsed() < LOOKUP_TTL {
+                return *found;
+            }
+        }
+        let key = self.key(path)?;
+        let found = match self.send(Method::HEAD, &key, &[], &HeaderMap::new()).await {
+            Ok(response) if response.status().is_success() => Some(false),
+            Ok(_) => {
+                let dir_prefix = format!("{}/", key);
+                let query = [
+                    ("list-type", "2"),
+                    ("max-keys", "1"),
+                    ("prefix", dir_prefix.as_str()),
+                ];
+                match self.send(Method::GET, "", &query, &HeaderMap::new()).await {
+                    Ok(response) if response.status().is_success() => {
+                        let body = axum::body::to_bytes(Body::new(response.into_body()), 1 << 20)
+                            .await
+                            .unwrap_or_default();
+                        let body = String::from_utf8_lossy(&body);
+                        (body.contains("<Key>")).then_some(true)
+                    }
+                    _ => None,
+                }
+            }
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        };
+        let mut lookups = self.lookups.lock().unwrap();
+        if lookups.len() >= LOOKUP_CAPACITY {
+            lookups.clear();
+        }
+        lookups.insert(path.to_path_buf(), (Instant::now(), found));
+        found
+    }
+
+    // 读取整个对象（错误页等）
+    pub async fn read(&self, path: &Path) -> std::io::Result<Bytes> {
+        let key = self.key(path).ok_or(std::io::ErrorKind::NotFound)?;
+        let response = self
+            .send(Method::GET, &key, &[], &HeaderMap::new())
+            .await
+            .map_err(std::io::Error::other)?;
+        if !response.status().is_success() {
+            return Err(std::io::ErrorKind::NotFound.into());
+        }
+        axum::body::to_bytes(Body::new(response.into_body()), usize::MAX)
+            .await
+            .map_err(std::io::Error::other)
+    }
+
+    async fn fetch(&self, req: &Request<()>, path: &Path) -> Response<Body> {
+        let Some(key) = self.key(path) else {
+            return status(StatusCode::NOT_FOUND);
+        };
+        // 普通 GET 可以使用磁盘缓存
+        let cacheable = req.method() == Method::GET
+            && !FORWARD_HEADERS
+                .iter()
+                .any(|name| req.headers().contains_key(name));
+        let cache = self.cache.as_ref().filter(|_| cacheable);
+        let mut forward = req.headers().clone();
+        let cached = match cache {
+            Some(cache) => cache.get(&key).await,
+            None => None,
+        };
+        if let (Some(cache), Some(entry)) = (cache, &cached) {
+            if entry.fresh {
+                return entry.respond(req, path);
+            }
+            // 以 ETag 重新验证
+            if let Some(etag) = &entry.file.etag {
+                forward.insert(header::IF_NONE_MATCH, etag.clone());
+            }
+            match self.send(Method::GET, &key, &[], &forward).await {
+                Ok(response) if response.status() == StatusCode::NOT_MODIFIED => {
+                    cache.touch(&key, &entry.meta).await;
+                    return entry.respond(req, path);
+                }
+                Ok(response) => return self.respond(response, path, Some(cache), &key).await,
+                Err(e) => {
+                    // 存储不可用时继续使用过期的缓存
+                    warn!("{}", e);
+                    return entry.respond(req, path);
+                }
+            }
+        }
+        match self.send(req.method().clone(), &key, &[], &forward).await {
+            Ok(response) => self.respond(response, path, cache, &key).await,
+            Err(e) => {
+                warn!("{}", e);
+                status(StatusCode::BAD_GATEWAY)
+            }
+        }
+    }
+
+    async fn respond(
+        &self,
+        upstream: Response<hyper::body::Incoming>,
+        path: &Path,
+        cache: Option<&DiskCache>,
+        key: &str,
+    ) -> Response<Body> {
+        let code = upstream.status();
+        // 没有 ListBucket 权限时，不存在的对象返回 403
+        if code == StatusCode::NOT_FOUND || code == StatusCode::FORBIDDEN {
+            return status(StatusCode::NOT_FOUND);
+        }
+        if code.is_server_error() {
+            warn!("Object storage returned {} for {}", code, key);
+            return status(StatusCode::BAD_GATEWAY);
+        }
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = code;
+        for name in PASS_HEADERS {
+            if let Some(value) = upstream.headers().get(&name) {
+                response.headers_mut().insert(name, value.clone());
+            }
+        }
+        // 上传时未设置类型的对象按扩展名推断
+        let generic = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_none_or(|v| v == "binary/octet-stream" || v == "application/octet-stream");
+        if generic {
+            if let Some(mime) = mime_guess::from_path(path).first_raw() {
+                response
+                    .headers_mut()
+                    .insert(header::CONTENT_TYPE, HeaderValue::from_static(mime));
+            }
+        }
+
+        let length = upstream
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok());
+        let cache = cache.filter(|cache| {
+            code == StatusCode::OK && length.is_some_and(|len| len <= cache.max_file_size)
+        });
+        let Some(cache) = cache else {
+            *response.body_mut() = Body::new(upstream.into_body());
+            return response;
+        };
+        match axum::body::to_bytes(Body::new(upstream.into_body()), usize::MAX).await {
+            Ok(data) => {
+                cache.put(key, response.headers(), &data).await;
+                *response.body_mut() = Body::from(data);
+                response
+            }
+            Err(e) => {
+                warn!("Failed to read object {}: {}", key, e);
+                status(StatusCode::BAD_GATEWAY)
+            }
+        }
+    }
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// 磁盘缓存：文件名为对象键的 SHA-256，旁边的 .json 保存响应头与取回时间
+struct DiskCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_file_size: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct CacheMeta {
+    content_type: Option<String>,
+    content_encoding: Option<String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    // 取回或最近一次验证的时间（Unix 秒）
+    fetched: u64,
+}
+
+struct CacheEntry {
+    file: MemFile,
+    meta: CacheMeta,
+    fresh: bool,
+}
+
+impl CacheEntry {
+    fn respond(&self, req: &Request<()>, path: &Path) -> Response<Body> {
+        let mut response = memfs::serve(req, path, &self.file);
+        if let Some(value) = self
+            .meta
+            .content_type
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            response.headers_mut().insert(header::CONTENT_TYPE, value);
+        }
+        if let Some(value) = self
+            .meta
+            .content_encoding
+            .as_deref()
+            .and_then(|v| HeaderValue::from_str(v).ok())
+        {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_ENCODING, value);
+        }
+        response
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl DiskCache {
+    fn paths(&self, key: &str) -> (PathBuf, PathBuf) {
+        let name = hex(&Sha256::digest(key.as_bytes()));
+        (
+            self.dir.join(&name),
+            self.dir.join(format!("{}.json", name)),
+        )
+    }
+
+    async fn get(&self, key: &str) -> Option<CacheEntry> {
+        let (data_path, meta_path) = self.paths(key);
+        let meta: CacheMeta =
+            serde_json::from_slice(&tokio::fs::read(&meta_path).await.ok()?).ok()?;
+        let data = tokio::task::spawn_blocking(move || crate::mmap::map_file(&data_path))
+            .await
+            .ok()?
+            .ok()?;
+        let fresh = unix_now().saturating_sub(meta.fetched) < self.ttl.as_secs();
+        let file = MemFile {
+            data,
+            modified: meta
+                .last_modified
+                .as_deref()
+                .and_then(|v| httpdate::parse_http_date(v).ok()),
+            etag: meta
+                .etag
+                .as_deref()
+                .and_then(|v| HeaderValue::from_str(v).ok()),
+        };
+        Some(CacheEntry { file, meta, fresh })
+    }
+
+    async fn put(&self, key: &str, headers: &HeaderMap, data: &Bytes) {
+        let text = |name: HeaderName| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let meta = CacheMeta {
+            content_type: text(header::CONTENT_TYPE),
+            content_encoding: text(header::CONTENT_ENCODING),
+            etag: text(header::ETAG),
+            last_modified: text(header::LAST_MODIFIED),
+            fetched: unix_now(),
+        };
+        let (data_path, meta_path) = self.paths(key);
+        // 先写临时文件再改名，正在读取旧缓存的请求不受影响
+        let result = async {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            let temp = data_path.with_extension("tmp");
+            tokio::fs::write(&temp, data).await?;
+            tokio::fs::rename(&temp, &data_path).await?;
+            write_meta(&meta_path, &meta).await
+        }
+        .await;
+        match result {
+            Ok(()) => debug!("Cached object {} ({} bytes)", key, data.len()),
+            Err(e) => warn!("Failed to cache object {}: {}", key, e),
+        }
+    }
+
+    // 重新验证成功后更新取回时间
+    async fn touch(&self, key: &str, meta: &CacheMeta) {
+        let mut meta = meta.clone();
+        meta.fetched = unix_now();
+        let (_, meta_path) = self.paths(key);
+        if let Err(e) = write_meta(&meta_path, &meta).await {
+            warn!("Failed to update cache entry for {}: {}", key, e);
+        }
+    }
+}
+
+async fn write_meta(path: &Path, meta: &CacheMeta) -> std::io::Result<()> {
+    let temp = path.with_extension("json.tmp");
+    tokio::fs::write(&temp, serde_json::to_vec(meta)?).await?;
+    tokio::fs::rename(&temp, path).await
+}
+
+// 代替 ServeDir 从对象存储返回文件；找不到时返回 fallback 对象或 404
+#[derive(Clone)]
+pub struct S3Service {
+    store: Arc<S3Store>,
+    fallback: Option<PathBuf>,
+}
+
+impl S3Service {
+    pub fn new(store: Arc<S3Store>, fallback: Option<&str>) -> Self {
+        let fallback = fallback.and_then(|f| crate::site::fs_path(store.root(), f));
+        S3Service { store, fallback }
+    }
+}
+
+impl Service<Request<Body>> for S3Service {
+    type Response = Response<Body>;
+    type Error = Infallible;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let store = self.store.clone();
+        let fallback = self.fallback.clone();
+        // 请求体不会用到，去掉后 future 才能跨线程
+        let req = req.map(|_| ());
+        Box::pin(async move {
+            if req.method() != Method::GET && req.method() != Method::HEAD {
+                let mut response = status(StatusCode::METHOD_NOT_ALLOWED);
+                response
+                    .headers_mut()
+                    .insert(header::ALLOW, HeaderValue::from_static("GET,HEAD"));
+                return Ok(response);
+            }
+            let Some(path) = crate::site::fs_path(store.root(), req.uri().path()) else {
+                return Ok(status(StatusCode::NOT_FOUND));
+            };
+            let response = store.fetch(&req, &path).await;
+            if response.status() == StatusCode::NOT_FOUND {
+                if let Some(fallback) = fallback {
+                    return Ok(store.fetch(&req, &fallback).await);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
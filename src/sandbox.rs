@@ -0,0 +1,343 @@
+// 文件系统沙箱（仅 Linux，sandbox = true）：用 Landlock 把进程限制在站点目录、缓存目录与启动所需的系统文件之内，
+// 即使将来出现路径穿越漏洞也读不到其他文件。Landlock 只约束调用线程及其之后创建的线程，
+// 因此在启动 tokio 运行时之前应用，启动阶段读取的文件（GeoIP 数据库、模板等）同样要列入
+use crate::config::Config;
+use crate::listener::{ListenAddr, ListenSpec};
+use crate::privileges;
+use crate::s3::Backend;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+// 内核 ABI（include/uapi/linux/landlock.h）
+const CREATE_RULESET_VERSION: u32 = 1;
+const RULE_PATH_BENEATH: u32 = 1;
+
+const ACCESS_EXECUTE: u64 = 1 << 0;
+const ACCESS_WRITE_FILE: u64 = 1 << 1;
+const ACCESS_READ_FILE: u64 = 1 << 2;
+const ACCESS_READ_DIR: u64 = 1 << 3;
+const ACCESS_REMOVE_FILE: u64 = 1 << 5;
+const ACCESS_MAKE_SOCK: u64 = 1 << 9;
+const ACCESS_MAKE_SYM: u64 = 1 << 12;
+// ABI 1 的全部文件系统权限（EXECUTE 到 MAKE_SYM）
+const ACCESS_ABI_1: u64 = (1 << 13) - 1;
+const ACCESS_REFER: u64 = 1 << 13;
+const ACCESS_TRUNCATE: u64 = 1 << 14;
+// 可以授予普通文件的权限，其余只对目录有意义
+const ACCESS_FILE: u64 = ACCESS_EXECUTE | ACCESS_WRITE_FILE | ACCESS_READ_FILE | ACCESS_TRUNCATE;
+
+const READ: u64 = ACCESS_READ_FILE | ACCESS_READ_DIR;
+const EXECUTE: u64 = READ | ACCESS_EXECUTE;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+// 启动与解析主机名、查询用户所需的系统文件；NSS 模块按需从库目录加载
+const SYSTEM_READ: &[&str] = &[
+    "/etc/passwd",
+    "/etc/group",
+    "/etc/nsswitch.conf",
+    "/etc/hosts",
+    "/etc/host.conf",
+    "/etc/resolv.conf",
+    "/etc/gai.conf",
+    "/etc/services",
+    "/etc/localtime",
+    "/proc/self",
+    "/sys/fs/cgroup",
+];
+const SYSTEM_EXECUTE: &[&str] = &["/lib", "/lib64", "/usr/lib", "/usr/lib64"];
+const SYSTEM_WRITE: &[&str] = &["/dev/null", "/dev/urandom"];
+
+// 允许访问的路径与权限
+struct Rules {
+    rules: Vec<(PathBuf, u64)>,
+}
+
+impl Rules {
+    fn add(&mut self, path: impl Into<PathBuf>, access: u64) {
+        self.rules.push((path.into(), access));
+    }
+}
+
+fn collect(config: &Config, write: u64, pid_file: Option<&Path>) -> io::Result<Rules> {
+    let mut rules = Rules { rules: Vec::new() };
+    for path in SYSTEM_READ {
+        rules.add(*path, READ);
+    }
+    for path in SYSTEM_EXECUTE {
+        rules.add(*path, EXECUTE);
+    }
+    for path in SYSTEM_WRITE {
+        rules.add(*path, ACCESS_READ_FILE | ACCESS_WRITE_FILE);
+    }
+    // SIGUSR2 热重启重新执行自身，新进程再读取配置文件
+    rules.add(std::env::current_exe()?, EXECUTE);
+    rules.add("config.toml", READ);
+    for dir in &config.include_dirs {
+        rules.add(dir, READ);
+    }
+
+    // 写入模式与镜像直接写主目录
+    let site_access = if config.upload.is_some() || config.mirror.is_some() {
+        write
+    } else {
+        READ
+    };
+    if !config.embedded && config.backend == Backend::Fs {
+        rules.add(config.site_dir(), site_access);
+    }
+    // git 后端每次部署都读取仓库，部署端点推进 ref 时写入仓库
+    if let Some(git) = config
+        .git
+        .as_ref()
+        .filter(|_| config.backend == Backend::Git)
+    {
+        let repo_access = if git.token.is_some() { write } else { READ };
+        rules.add(&git.repo, repo_access);
+    }
+    // 切换版本后主目录的规则不再覆盖新版本，整个发布目录可读
+    if let Some(releases) = &config.releases {
+        rules.add(&releases.dir, READ);
+    }
+    if let Some(canary) = &config.canary {
+        rules.add(&canary.dir, READ);
+    }
+    for mount in &config.mount {
+        rules.add(&mount.dir, READ);
+    }
+    for vhost in &config.vhost {
+        rules.add(&vhost.dir, READ);
+    }
+    for path in &config.sandbox_paths {
+        rules.add(path, READ);
+    }
+
+    // 启动时读取的文件
+    if let Some(geoip) = &config.geoip {
+        rules.add(&geoip.database, READ);
+    }
+    if let Some(path) = config.hotlink.as_ref().and_then(|h| h.placeholder.as_ref()) {
+        rules.add(path, READ);
+    }
+    if let Some(path) = config.markdown.as_ref().and_then(|m| m.template.as_ref()) {
+        rules.add(path, READ);
+    }
+    if let Some(path) = config.maintenance.as_ref().and_then(|m| m.page.as_ref()) {
+        rules.add(path, READ);
+    }
+    if let Some(path) = config.login.as_ref().and_then(|l| l.users_file.as_ref()) {
+        rules.add(path, READ);
+    }
+    // 证书轮换时 ACME 客户端以新文件替换，重新加载需要读取所在目录
+    if let Some(tls) = &config.tls {
+        for path in [&tls.cert, &tls.key, &tls.client_ca].into_iter().flatten() {
+            rules.add(parent_dir(Path::new(path)), READ);
+        }
+        if let Some(dir) = &tls.cert_dir {
+            rules.add(dir, READ);
+        }
+    }
+    for vhost in &config.vhost {
+        for path in [&vhost.tls_cert, &vhost.tls_key].into_iter().flatten() {
+            rules.add(parent_dir(Path::new(path)), READ);
+        }
+    }
+    if let Some(prerender) = &config.prerender {
+        rules.add(&prerender.dir, READ);
+    }
+    for rule in &config.user_agent {
+        if let Some(path) = &rule.file {
+            rules.add(path, READ);
+        }
+    }
+
+    // 运行时写入的缓存与暂存目录
+    let mut writable = Vec::new();
+    if let Some(images) = &config.images {
+        writable.push(images.cache_dir.clone());
+    }
+    if let Some(waveform) = &config.waveform {
+        writable.push(waveform.cache_dir.clone());
+    }
+    if let Some(hls) = &config.hls {
+        writable.push(hls.cache_dir.clone());
+    }
+    if let Some(path) = config.s3.as_ref().and_then(|s3| s3.cache_dir.clone()) {
+        writable.push(path);
+    }
+    if let Some(path) = config
+        .compression
+        .as_ref()
+        .and_then(|compression| compression.cache_dir.clone())
+    {
+        writable.push(path);
+    }
+    // 租户发布的文件与租户记录都写在租户目录中
+    if let Some(tenants) = &config.tenants {
+        writable.push(tenants.dir.clone());
+    }
+    if let Some(mirror) = &config.mirror {
+        writable.push(PathBuf::from(&mirror.meta_dir));
+    }
+    if let Some(upload) = config.upload.as_ref().filter(|u| u.tus_endpoint.is_some()) {
+        writable.push(upload.staging_dir.clone());
+    }
+    for dir in writable {
+        ensure_dir(config, &dir)?;
+        rules.add(dir, write);
+    }
+
+    // 下载统计先写临时文件再改名
+    if let Some(path) = config
+        .analytics
+        .as_ref()
+        .and_then(|a| a.state_file.as_ref())
+    {
+        rules.add(parent_dir(Path::new(path)), write);
+    }
+
+    // 切换版本时在链接所在目录中新建临时链接，再改名覆盖原链接
+    let site_link = Path::new(config.static_dir.as_deref().unwrap_or("."));
+    let symlinked = std::fs::symlink_metadata(site_link).is_ok_and(|meta| meta.is_symlink());
+    if config.releases.as_ref().is_some_and(|r| r.token.is_some()) && symlinked {
+        rules.add(parent_dir(site_link), ACCESS_MAKE_SYM | ACCESS_REMOVE_FILE);
+    }
+
+    // Unix socket 在所在目录中创建，退出时删除
+    for entry in &config.listen {
+        for spec in ListenSpec::parse_all(entry).unwrap_or_default() {
+            if let ListenAddr::Unix(path) = spec.addr {
+                rules.add(parent_dir(&path), ACCESS_MAKE_SOCK | ACCESS_REMOVE_FILE);
+            }
+        }
+    }
+
+    // 退出时读取并删除 PID 文件，热重启的新进程会重写它
+    if let Some(path) = pid_file {
+        rules.add(path, ACCESS_READ_FILE | ACCESS_WRITE_FILE | ACCESS_TRUNCATE);
+        rules.add(parent_dir(path), ACCESS_REMOVE_FILE);
+    }
+    Ok(rules)
+}
+
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+// 缓存目录必须在应用沙箱之前存在；以 root 启动并配置了 user 时交给该账号
+fn ensure_dir(config: &Config, dir: &Path) -> io::Result<()> {
+    if dir.is_dir() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir)?;
+    privileges::hand_over(dir, config.user.as_deref(), config.group.as_deref())
+}
+
+fn syscall_result(ret: libc::c_long) -> io::Result<libc::c_long> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+pub fn apply(config: &Config, pid_file: Option<&Path>) -> Result<(), String> {
+    if config.build.is_some() {
+        return Err(
+            "sandbox cannot be combined with [build]: the build command would run inside it"
+                .to_string(),
+        );
+    }
+    // SAFETY: 查询版本时 attr 为空指针、size 为 0
+    let abi = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0usize,
+            CREATE_RULESET_VERSION,
+        )
+    };
+    let abi = syscall_result(abi)
+        .map_err(|e| format!("Landlock is not available on this kernel: {}", e))?;
+    let mut handled = ACCESS_ABI_1;
+    if abi >= 2 {
+        handled |= ACCESS_REFER;
+    }
+    if abi >= 3 {
+        handled |= ACCESS_TRUNCATE;
+    }
+    let rules = collect(config, handled, pid_file).map_err(|e| format!("sandbox: {}", e))?;
+
+    let attr = RulesetAttr {
+        handled_access_fs: handled,
+    };
+    // SAFETY: attr 在调用期间有效，size 与结构体一致
+    let ruleset = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr as *const RulesetAttr,
+            std::mem::size_of::<RulesetAttr>(),
+            0u32,
+        )
+    };
+    let ruleset = syscall_result(ruleset)
+        .map_err(|e| format!("failed to create Landlock ruleset: {}", e))?
+        as libc::c_int;
+    // SAFETY: ruleset 是刚创建的 FD，由 File 负责关闭
+    let ruleset = unsafe { <File as std::os::fd::FromRawFd>::from_raw_fd(ruleset) };
+
+    for (path, access) in &rules.rules {
+        // 不存在的系统路径直接跳过；O_PATH 不需要任何访问权限，热重启的新进程在继承的沙箱中同样能打开
+        let file = match OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH)
+            .open(path)
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("sandbox: cannot open {}: {}", path.display(), e)),
+        };
+        let is_dir = file.metadata().map(|m| m.is_dir()).unwrap_or(false);
+        let access = access & handled;
+        let beneath = PathBeneathAttr {
+            allowed_access: if is_dir { access } else { access & ACCESS_FILE },
+            parent_fd: file.as_raw_fd(),
+        };
+        // SAFETY: beneath 与 file 在调用期间有效
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset.as_raw_fd(),
+                RULE_PATH_BENEATH,
+                &beneath as *const PathBeneathAttr,
+                0u32,
+            )
+        };
+        syscall_result(ret)
+            .map_err(|e| format!("sandbox: cannot allow {}: {}", path.display(), e))?;
+    }
+
+    // 非 root 进程必须先设置 no_new_privs
+    // SAFETY: 只传整数参数
+    let ret = unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+    syscall_result(ret.into()).map_err(|e| format!("failed to set no_new_privs: {}", e))?;
+    // SAFETY: ruleset 是有效的 Landlock FD
+    let ret = unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) };
+    syscall_result(ret).map_err(|e| format!("failed to enable Landlock: {}", e))?;
+    Ok(())
+}
@@ -0,0 +1,244 @@
+// Lua 脚本（script = "rules.lua"，cargo feature "lua"）：在路由之前对每个请求调用脚本中的 on_request(req)，
+// 可以改写路径（从而选择挂载点、反向代理或静态目录）、改写 Host 头选择虚拟主机、增删请求头、添加响应头或直接返回响应。
+// 脚本只能使用 table / string / math / utf8 标准库与 print（写入日志），每次调用限制执行的指令数，内存也有上限；
+// 所有请求共用同一个 Lua 状态，全局变量在请求之间保留
+use std::path::Path;
+
+#[cfg(feature = "lua")]
+pub use engine::{apply, Script};
+
+#[cfg(not(feature = "lua"))]
+pub struct Script;
+
+#[cfg(not(feature = "lua"))]
+impl Script {
+    pub fn new(_path: &Path) -> Result<Self, String> {
+        Err("script requires a build with the lua feature (cargo build --features lua)".to_string())
+    }
+}
+
+#[cfg(not(feature = "lua"))]
+pub async fn apply(
+    _state: axum::extract::State<std::sync::Arc<Script>>,
+    req: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    next.run(req).await
+}
+
+#[cfg(feature = "lua")]
+mod engine {
+    use super::Path;
+    use crate::forwarded::ClientInfo;
+    use crate::hooks::{self, header_map, Reply};
+    use crate::vhost;
+    use axum::extract::{OriginalUri, Request, State};
+    use axum::http::{HeaderMap, StatusCode, Uri};
+    use axum::middleware::Next;
+    use axum::response::{IntoResponse, Response};
+    use mlua::serde::SerializeOptions;
+    use mlua::{
+        Function, HookTriggers, Lua, LuaOptions, LuaSerdeExt, StdLib, Value, Variadic, VmState,
+    };
+    use percent_encoding::percent_decode_str;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::{debug, error, info};
+
+    // 每次调用（包括加载脚本）可执行的指令数，每 HOOK_INTERVAL 条指令检查一次
+    const INSTRUCTION_LIMIT: u32 = 1_000_000;
+    const HOOK_INTERVAL: u32 = 1000;
+    const MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+
+    // 传给 on_request 的 req 表
+    #[derive(Serialize)]
+    struct Input {
+        method: String,
+        path: String,
+        query: String,
+        host: Option<String>,
+        client: Option<String>,
+        headers: BTreeMap<String, String>,
+    }
+
+    // on_request 返回的表，返回 nil 表示不做修改
+    #[derive(Deserialize, Default)]
+    #[serde(default, deny_unknown_fields)]
+    struct Output {
+        // 新的请求路径（已编码，可带 "?查询串"，不带时保留原查询串）
+        path: Option<String>,
+        set_headers: BTreeMap<String, String>,
+        remove_headers: Vec<String>,
+        response_headers: BTreeMap<String, String>,
+        response: Option<Reply>,
+    }
+
+    pub struct Script {
+        name: String,
+        lua: Mutex<Lua>,
+        // 本次调用剩余的检查次数
+        budget: Arc<AtomicU32>,
+    }
+
+    fn lua_error(name: &str, e: mlua::Error) -> String {
+        format!("[script] {}: {}", name, e)
+    }
+
+    impl Script {
+        pub fn new(path: &Path) -> Result<Self, String> {
+            let name = path.display().to_string();
+            let source = std::fs::read_to_string(path)
+                .map_err(|e| format!("[script] failed to read {}: {}", name, e))?;
+            let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH | StdLib::UTF8;
+            let lua =
+                Lua::new_with(libs, LuaOptions::default()).map_err(|e| lua_error(&name, e))?;
+            lua.set_memory_limit(MEMORY_LIMIT)
+                .map_err(|e| lua_error(&name, e))?;
+            let globals = lua.globals();
+            // 基础库中能读取文件或加载任意代码的函数
+            for unsafe_fn in ["dofile", "loadfile", "load", "collectgarbage"] {
+                globals
+                    .raw_remove(unsafe_fn)
+                    .map_err(|e| lua_error(&name, e))?;
+            }
+            let print = lua
+                .create_function(|_, args: Variadic<String>| {
+                    info!("script: {}", args.join("\t"));
+                    Ok(())
+                })
+                .map_err(|e| lua_error(&name, e))?;
+            globals
+                .raw_set("print", print)
+                .map_err(|e| lua_error(&name, e))?;
+            let budget = Arc::new(AtomicU32::new(INSTRUCTION_LIMIT / HOOK_INTERVAL));
+            let left = budget.clone();
+            lua.set_hook(
+                HookTriggers::new().every_nth_instruction(HOOK_INTERVAL),
+                move |_, _| match left.load(Ordering::Relaxed) {
+                    0 => Err(mlua::Error::runtime("instruction limit exceeded")),
+                    n => {
+                        left.store(n - 1, Ordering::Relaxed);
+                        Ok(VmState::Continue)
+                    }
+                },
+            )
+            .map_err(|e| lua_error(&name, e))?;
+            lua.load(&source)
+                .set_name(format!("@{}", name))
+                .exec()
+                .map_err(|e| lua_error(&name, e))?;
+            if globals.get::<Function>("on_request").is_err() {
+                return Err(format!(
+                    "[script] {} must define a function on_request(req)",
+                    name
+                ));
+            }
+            Ok(Script {
+                name,
+                lua: Mutex::new(lua),
+                budget,
+            })
+        }
+
+        fn call(&self, input: &Input) -> Result<Option<Output>, mlua::Error> {
+            let lua = self.lua.lock().unwrap();
+            self.budget
+                .store(INSTRUCTION_LIMIT / HOOK_INTERVAL, Ordering::Relaxed);
+            let req =
+                lua.to_value_with(input, SerializeOptions::new().serialize_none_to_null(false))?;
+            let on_request: Function = lua.globals().get("on_request")?;
+            match on_request.call::<Value>(req)? {
+                Value::Nil => Ok(None),
+                value => lua.from_value(value).map(Some),
+            }
+        }
+    }
+
+    fn failed(script: &Script, e: String) -> Response {
+        error!("Script {} failed: {}", script.name, e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    }
+
+    // 改写后的路径对后续路由完全透明，与 [[rewrite]] 相同
+    fn rewrite(req: &mut Request, path: String, query: &str) -> Result<(), String> {
+        let target = if path.contains('?') || query.is_empty() {
+            path
+        } else {
+            format!("{}?{}", path, query)
+        };
+        if !target.starts_with('/') {
+            return Err(format!("path `{}` must start with '/'", target));
+        }
+        let uri = target
+            .parse::<Uri>()
+            .map_err(|e| format!("invalid path `{}`: {}", target, e))?;
+        debug!("Script rewrite {} -> {}", req.uri(), uri);
+        req.extensions_mut().insert(OriginalUri(uri.clone()));
+        *req.uri_mut() = uri;
+        Ok(())
+    }
+
+    pub async fn apply(
+        State(script): State<Arc<Script>>,
+        mut req: Request,
+        next: Next,
+    ) -> Response {
+        let input = Input {
+            method: req.method().to_string(),
+            path: percent_decode_str(req.uri().path())
+                .decode_utf8_lossy()
+                .into_owned(),
+            query: req.uri().query().unwrap_or("").to_string(),
+            host: vhost::request_host(&req),
+            client: req
+                .extensions()
+                .get::<ClientInfo>()
+                .and_then(|client| client.ip)
+                .map(|ip| ip.to_string()),
+            headers: header_map(req.headers())
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+        };
+        // Lua 代码同步执行并持有锁，放到阻塞线程池中，避免占用异步工作线程
+        let called = script.clone();
+        let query = input.query.clone();
+        let output = tokio::task::spawn_blocking(move || called.call(&input))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|result| result.map_err(|e| e.to_string()));
+        let output = match output {
+            Ok(Some(output)) => output,
+            Ok(None) => return next.run(req).await,
+            Err(e) => return failed(&script, e),
+        };
+        if let Some(reply) = output.response {
+            return match reply.build().and_then(|mut response| {
+                hooks::edit(response.headers_mut(), &output.response_headers, &[])?;
+                Ok(response)
+            }) {
+                Ok(response) => response,
+                Err(e) => failed(&script, e),
+            };
+        }
+        let mut extra = HeaderMap::new();
+        let edited = hooks::edit(
+            req.headers_mut(),
+            &output.set_headers,
+            &output.remove_headers,
+        )
+        .and_then(|_| hooks::edit(&mut extra, &output.response_headers, &[]))
+        .and_then(|_| match output.path {
+            Some(path) => rewrite(&mut req, path, &query),
+            None => Ok(()),
+        });
+        if let Err(e) = edited {
+            return failed(&script, e);
+        }
+        let mut response = next.run(req).await;
+        response.headers_mut().extend(extra);
+        response
+    }
+}
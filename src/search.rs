@@ -0,0 +1,592 @@
+// 站内搜索（[search]）：启动时为主站点目录中的 HTML、Markdown 与文本文件建立倒排索引，
+// `/__search?q=` 返回按 BM25 排序、带摘要的 JSON 结果，静态文档站点不需要另外部署搜索服务。
+// 中日韩文字按相邻两字切分；最后一个词按前缀匹配，适合边输入边搜索。--watch 下文件变化后重建索引
+use crate::glob::PathPattern;
+use crate::live_reload::{Change, ChangeHub};
+use crate::manifest;
+use axum::extract::{Query, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+// URL 路径段中保留原样的字符
+const SEGMENT_CHARS: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+// BM25 参数
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+// 标题中的词按出现多次计算
+const TITLE_BOOST: u32 = 3;
+// 前缀匹配最多展开的词数
+const MAX_EXPANSIONS: usize = 50;
+const MAX_QUERY_LEN: usize = 256;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SearchConfig {
+    #[serde(default = "default_path")]
+    pub path: String,
+    // 建立索引的文件扩展名
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    // 不建立索引的路径（模式规则与 deny 相同），站点的 deny 规则同样排除
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // 超过该大小的文件不建立索引
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    // 一次最多返回的结果数，?limit= 不能超过该值
+    #[serde(default = "default_max_results")]
+    pub max_results: usize,
+    // 摘要的字符数
+    #[serde(default = "default_snippet_length")]
+    pub snippet_length: usize,
+}
+
+fn default_path() -> String {
+    "/__search".to_string()
+}
+
+fn default_extensions() -> Vec<String> {
+    ["html", "htm", "md", "markdown", "txt"]
+        .iter()
+        .map(|ext| ext.to_string())
+        .collect()
+}
+
+fn default_max_file_size() -> u64 {
+    1024 * 1024
+}
+
+fn default_max_results() -> usize {
+    50
+}
+
+fn default_snippet_length() -> usize {
+    160
+}
+
+struct Document {
+    url: String,
+    title: String,
+    text: String,
+    // 词数（含标题加权），用于 BM25 的长度归一化
+    length: u32,
+}
+
+#[derive(Default)]
+struct Index {
+    documents: Vec<Document>,
+    // 词 -> (文档序号, 词频)
+    postings: BTreeMap<String, Vec<(u32, u32)>>,
+    average_length: f64,
+}
+
+impl Index {
+    fn add(&mut self, url: String, title: String, text: String) {
+        let id = self.documents.len() as u32;
+        let mut frequencies: HashMap<String, u32> = HashMap::new();
+        for (token, _) in tokenize(&title) {
+            *frequencies.entry(token).or_default() += TITLE_BOOST;
+        }
+        for (token, _) in tokenize(&text) {
+            *frequencies.entry(token).or_default() += 1;
+        }
+        let length = frequencies.values().sum();
+        for (token, frequency) in frequencies {
+            self.postings
+                .entry(token)
+                .or_default()
+                .push((id, frequency));
+        }
+        self.documents.push(Document {
+            url,
+            title,
+            text,
+            length,
+        });
+    }
+
+    fn finish(&mut self) {
+        let total: u64 = self.documents.iter().map(|doc| doc.length as u64).sum();
+        self.average_length = total as f64 / self.documents.len().max(1) as f64;
+    }
+
+    // 最后一个词按前缀展开；每个词（或其任一展开）都必须出现
+    fn search(&self, terms: &[String]) -> Vec<(u32, f64)> {
+        let count = self.documents.len() as f64;
+        let mut scores: HashMap<u32, (f64, usize)> = HashMap::new();
+        for (i, term) in terms.iter().enumerate() {
+            let expansions: Vec<&Vec<(u32, u32)>> = if i + 1 == terms.len() {
+                self.postings
+                    .range(term.clone()..)
+                    .take_while(|(key, _)| key.starts_with(term.as_str()))
+                    .take(MAX_EXPANSIONS)
+                    .map(|(_, postings)| postings)
+                    .collect()
+            } else {
+                self.postings.get(term).into_iter().collect()
+            };
+            let mut matched: HashMap<u32, f64> = HashMap::new();
+            for postings in expansions {
+                let df = postings.len() as f64;
+                let idf = ((count - df + 0.5) / (df + 0.5) + 1.0).ln();
+                for &(id, frequency) in postings {
+                    let tf = frequency as f64;
+                    let length = self.documents[id as usize].length as f64;
+                    let norm = K1 * (1.0 - B + B * length / self.average_length.max(1.0));
+                    let score = idf * tf * (K1 + 1.0) / (tf + norm);
+                    let best = matched.entry(id).or_default();
+                    *best = best.max(score);
+                }
+            }
+            for (id, score) in matched {
+                let entry = scores.entry(id).or_default();
+                entry.0 += score;
+                entry.1 += 1;
+            }
+        }
+        let mut results: Vec<(u32, f64)> = scores
+            .into_iter()
+            .filter(|(_, (_, matched))| *matched == terms.len())
+            .map(|(id, (score, _))| (id, score))
+            .collect();
+        results.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        results
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30ff | 0x3400..=0x4dbf | 0x4e00..=0x9fff | 0xac00..=0xd7af | 0xf900..=0xfaff)
+}
+
+// 小写的词及其在原文中的字节位置；中日韩文字连续两字为一个词，单独一个字时自成一词
+fn tokenize(text: &str) -> Vec<(String, usize)> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut word_start = 0;
+    let mut cjk: Vec<(char, usize)> = Vec::new();
+    let flush_cjk = |cjk: &mut Vec<(char, usize)>, tokens: &mut Vec<(String, usize)>| {
+        if cjk.len() == 1 {
+            tokens.push((cjk[0].0.to_string(), cjk[0].1));
+        }
+        for pair in cjk.windows(2) {
+            tokens.push((format!("{}{}", pair[0].0, pair[1].0), pair[0].1));
+        }
+        cjk.clear();
+    };
+    for (offset, c) in text.char_indices() {
+        if is_cjk(c) {
+            if !word.is_empty() {
+                tokens.push((std::mem::take(&mut word), word_start));
+            }
+            cjk.push((c, offset));
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk, &mut tokens);
+            if word.is_empty() {
+                word_start = offset;
+            }
+            word.extend(c.to_lowercase());
+        } else {
+            flush_cjk(&mut cjk, &mut tokens);
+            if !word.is_empty() {
+                tokens.push((std::mem::take(&mut word), word_start));
+            }
+        }
+    }
+    flush_cjk(&mut cjk, &mut tokens);
+    if !word.is_empty() {
+        tokens.push((word, word_start));
+    }
+    tokens
+}
+
+// 去掉 script / style 等元素与所有标签，标题取 <title> 或第一个 <h1>；带 noindex 的页面不建立索引
+fn html_text(html: &str) -> Option<(String, String)> {
+    let lower = html.to_ascii_lowercase();
+    if lower.contains("name=\"robots\"") && lower.contains("noindex") {
+        return None;
+    }
+    let element = |name: &str| -> Option<String> {
+        let start = lower.find(&format!("<{}", name))?;
+        let open_end = start + lower[start..].find('>')? + 1;
+        let close = open_end + lower[open_end..].find(&format!("</{}", name))?;
+        Some(strip_tags(&html[open_end..close]))
+    };
+    let title = element("title")
+        .filter(|title| !title.is_empty())
+        .or_else(|| element("h1"))
+        .unwrap_or_default();
+    let body_start = lower.find("<body").unwrap_or(0);
+    let mut text = String::new();
+    let mut rest = &html[body_start..];
+    let mut rest_lower = &lower[body_start..];
+    // 跳过其中的文字不属于正文的元素
+    'outer: loop {
+        let next = ["<script", "<style", "<noscript", "<template", "<svg"]
+            .iter()
+            .filter_map(|tag| rest_lower.find(tag).map(|pos| (pos, &tag[1..])))
+            .min();
+        let Some((pos, name)) = next else {
+            text.push_str(rest);
+            break;
+        };
+        text.push_str(&rest[..pos]);
+        text.push(' ');
+        let close = format!("</{}", name);
+        match rest_lower[pos..].find(&close) {
+            Some(end) => {
+                let after = pos + end + close.len();
+                let after = after + rest_lower[after..].find('>').map_or(0, |p| p + 1);
+                rest = &rest[after..];
+                rest_lower = &rest_lower[after..];
+            }
+            None => break 'outer,
+        }
+    }
+    Some((title, strip_tags(&text)))
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    collapse(&decode_entities(&text))
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('&') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let Some(end) = rest[..rest.len().min(12)].find(';') else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" | "#39" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|n| n.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// 标题取第一个标题行
+fn markdown_text(source: &str) -> (String, String) {
+    let mut title = String::new();
+    let mut text = String::new();
+    let mut in_heading = false;
+    let mut first_heading = true;
+    for event in Parser::new(source) {
+        match event {
+            Event::Start(Tag::Heading { .. }) => in_heading = true,
+            Event::End(TagEnd::Heading(_)) => {
+                in_heading = false;
+                if !title.is_empty() {
+                    first_heading = false;
+                }
+                text.push(' ');
+            }
+            Event::Text(t) | Event::Code(t) => {
+                if in_heading && first_heading {
+                    title.push_str(&t);
+                }
+                text.push_str(&t);
+            }
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+    (collapse(&title), collapse(&text))
+}
+
+pub struct Search {
+    path: String,
+    dir: PathBuf,
+    extensions: Vec<String>,
+    exclude: Vec<PathPattern>,
+    index_files: Vec<String>,
+    max_file_size: u64,
+    max_results: usize,
+    snippet_length: usize,
+    index: RwLock<Arc<Index>>,
+}
+
+impl Search {
+    // 站点的 deny 规则同样排除，搜索结果不会泄露无法访问的文件
+    pub fn new(
+        config: &SearchConfig,
+        dir: &Path,
+        deny: &[String],
+        index_files: &[String],
+    ) -> Result<Self, String> {
+        if !config.path.starts_with('/') {
+            return Err("[search] path must start with /".to_string());
+        }
+        let exclude = [&config.exclude[..], deny]
+            .concat()
+            .iter()
+            .map(|pattern| PathPattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let search = Search {
+            path: config.path.clone(),
+            dir: dir.to_path_buf(),
+            extensions: config
+                .extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+            exclude,
+            index_files: index_files.to_vec(),
+            max_file_size: config.max_file_size,
+            max_results: config.max_results.max(1),
+            snippet_length: config.snippet_length,
+            index: RwLock::new(Arc::new(Index::default())),
+        };
+        search.rebuild();
+        Ok(search)
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn rebuild(&self) {
+        let started = Instant::now();
+        match self.build() {
+            Ok(index) => {
+                info!(
+                    "Search index: {} documents, {} terms in {:.1?}",
+                    index.documents.len(),
+                    index.postings.len(),
+                    started.elapsed()
+                );
+                *self.index.write().unwrap() = Arc::new(index);
+            }
+            Err(e) => warn!("Failed to build the search index: {}", e),
+        }
+    }
+
+    fn build(&self) -> std::io::Result<Index> {
+        let mut files = Vec::new();
+        manifest::collect(&self.dir, &mut files)?;
+        files.sort();
+        let mut index = Index::default();
+        for path in files {
+            let Ok(relative) = path.strip_prefix(&self.dir) else {
+                continue;
+            };
+            let extension = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(str::to_ascii_lowercase)
+                .unwrap_or_default();
+            if !self.extensions.contains(&extension) {
+                continue;
+            }
+            let segments: Vec<String> = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            if self
+                .exclude
+                .iter()
+                .any(|p| p.matches(&format!("/{}", segments.join("/"))))
+            {
+                continue;
+            }
+            let Ok(meta) = std::fs::metadata(&path) else {
+                continue;
+            };
+            if meta.len() > self.max_file_size {
+                continue;
+            }
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let name = segments.last().cloned().unwrap_or_default();
+            let (title, text) = match extension.as_str() {
+                "html" | "htm" => match html_text(&content) {
+                    Some(extracted) => extracted,
+                    None => continue,
+                },
+                "md" | "markdown" => markdown_text(&content),
+                _ => (String::new(), collapse(&content)),
+            };
+            let title = if title.is_empty() {
+                name.clone()
+            } else {
+                title
+            };
+            // 目录索引文件以目录地址作为结果
+            let mut url: String = segments
+                .iter()
+                .map(|segment| format!("/{}", utf8_percent_encode(segment, SEGMENT_CHARS)))
+                .collect();
+            if self.index_files.contains(&name) {
+                url.truncate(
+                    url.len() - utf8_percent_encode(&name, SEGMENT_CHARS).to_string().len(),
+                );
+            }
+            index.add(url, title, text);
+        }
+        index.finish();
+        Ok(index)
+    }
+
+    // --watch：文件变化后在后台重建索引
+    pub fn watch(self: &Arc<Self>, changes: &ChangeHub) {
+        let mut rx = changes.subscribe();
+        let search = self.clone();
+        tokio::spawn(async move {
+            loop {
+                match rx.recv().await {
+                    Ok(Change::Shutdown) | Err(RecvError::Closed) => break,
+                    // 积压的通知合并为一次重建
+                    Ok(_) | Err(RecvError::Lagged(_)) => {
+                        while rx.try_recv().is_ok() {}
+                        let search = search.clone();
+                        let _ = tokio::task::spawn_blocking(move || search.rebuild()).await;
+                    }
+                }
+            }
+        });
+    }
+
+    // 第一个命中的词前后截取摘要
+    fn snippet(&self, text: &str, terms: &[String]) -> String {
+        let last = terms.len().saturating_sub(1);
+        let position = tokenize(text)
+            .into_iter()
+            .find(|(token, _)| {
+                terms.iter().enumerate().any(|(i, term)| {
+                    token == term || (i == last && token.starts_with(term.as_str()))
+                })
+            })
+            .map_or(0, |(_, offset)| offset);
+        let before = self.snippet_length / 3;
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let index = chars.partition_point(|(offset, _)| *offset < position);
+        let start = index.saturating_sub(before);
+        let end = (start + self.snippet_length).min(chars.len());
+        let mut snippet: String = chars[start..end].iter().map(|(_, c)| c).collect();
+        if start > 0 {
+            snippet.insert(0, '…');
+        }
+        if end < chars.len() {
+            snippet.push('…');
+        }
+        snippet
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+// GET ?q=...&limit=10
+pub async fn handler(
+    State(search): State<Arc<Search>>,
+    Query(query): Query<SearchQuery>,
+) -> Response {
+    let q = query.q.trim();
+    if q.is_empty() || q.len() > MAX_QUERY_LEN {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("expected ?q= with 1 to {} bytes", MAX_QUERY_LEN) })),
+        )
+            .into_response();
+    }
+    let mut terms: Vec<String> = Vec::new();
+    for (token, _) in tokenize(q) {
+        if !terms.contains(&token) {
+            terms.push(token);
+        }
+    }
+    let limit = query.limit.unwrap_or(10).clamp(1, search.max_results);
+    let index = search.index.read().unwrap().clone();
+    let matches = if terms.is_empty() {
+        Vec::new()
+    } else {
+        index.search(&terms)
+    };
+    let results: Vec<serde_json::Value> = matches
+        .iter()
+        .take(limit)
+        .map(|&(id, score)| {
+            let doc = &index.documents[id as usize];
+            json!({
+                "url": doc.url,
+                "title": doc.title,
+                "snippet": search.snippet(&doc.text, &terms),
+                "score": (score * 1000.0).round() / 1000.0,
+            })
+        })
+        .collect();
+    let mut response = Json(json!({
+        "query": q,
+        "total": matches.len(),
+        "results": results,
+    }))
+    .into_response();
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
@@ -0,0 +1,320 @@
+// 安全响应头（[security_headers]）：顶层设置为默认值，[[mount]] / [[vhost]] 逐项覆盖，
+// 未设置的项沿用上一级；字符串设为 "" 表示不发送继承来的头。响应中已有的同名头不覆盖
+use crate::forwarded::ClientInfo;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Extension;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct SecurityHeadersConfig {
+    // Cross-Origin-Opener-Policy: same-origin 与 Cross-Origin-Embedder-Policy: require-corp，
+    // 页面才能使用 SharedArrayBuffer（FFmpeg.wasm 多线程），默认开启
+    #[serde(default)]
+    pub cross_origin_isolation: Option<bool>,
+    // Strict-Transport-Security，如 "max-age=31536000; includeSubDomains"，只在 HTTPS 响应中发送
+    #[serde(default)]
+    pub hsts: Option<String>,
+    #[serde(default)]
+    pub content_security_policy: Option<String>,
+    // Content-Security-Policy-Report-Only：只报告不拦截，可与 content_security_policy 同时发送以试行新策略
+    #[serde(default)]
+    pub content_security_policy_report_only: Option<String>,
+    // Reporting-Endpoints，如 'default="/__reports"'，供 CSP 的 report-to 指令引用
+    #[serde(default)]
+    pub reporting_endpoints: Option<String>,
+    // X-Frame-Options，如 "DENY"、"SAMEORIGIN"
+    #[serde(default)]
+    pub frame_options: Option<String>,
+    // X-Content-Type-Options: nosniff
+    #[serde(default)]
+    pub content_type_options: Option<bool>,
+    #[serde(default)]
+    pub referrer_policy: Option<String>,
+    #[serde(default)]
+    pub permissions_policy: Option<String>,
+}
+
+impl SecurityHeadersConfig {
+    // 以 self 为默认值，应用 overrides 中设置的项
+    pub fn merged(&self, overrides: &SecurityHeadersConfig) -> SecurityHeadersConfig {
+        let pick = |over: &Option<String>, base: &Option<String>| over.clone().or(base.clone());
+        SecurityHeadersConfig {
+            cross_origin_isolation: overrides
+                .cross_origin_isolation
+                .or(self.cross_origin_isolation),
+            hsts: pick(&overrides.hsts, &self.hsts),
+            content_security_policy: pick(
+                &overrides.content_security_policy,
+                &self.content_security_policy,
+            ),
+            content_security_policy_report_only: pick(
+                &overrides.content_security_policy_report_only,
+                &self.content_security_policy_report_only,
+            ),
+            reporting_endpoints: pick(&overrides.reporting_endpoints, &self.reporting_endpoints),
+            frame_options: pick(&overrides.frame_options, &self.frame_options),
+            content_type_options: overrides.content_type_options.or(self.content_type_options),
+            referrer_policy: pick(&overrides.referrer_policy, &self.referrer_policy),
+            permissions_policy: pick(&overrides.permissions_policy, &self.permissions_policy),
+        }
+    }
+}
+
+// 逐项配置的 Permissions-Policy（[permissions_policy]），生成的头作为 [security_headers] permissions_policy 的值；
+// 每项为允许列表："self"、"src"、"*"、源（"https://example.com"），[] 表示全部禁止
+//   autoplay = ["self"]
+//   microphone = ["self", "https://meet.example.com"]
+//   camera = []
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PermissionsPolicyConfig {
+    #[serde(default)]
+    pub accelerometer: Option<Allowlist>,
+    #[serde(default)]
+    pub autoplay: Option<Allowlist>,
+    #[serde(default)]
+    pub camera: Option<Allowlist>,
+    #[serde(default)]
+    pub clipboard_read: Option<Allowlist>,
+    #[serde(default)]
+    pub clipboard_write: Option<Allowlist>,
+    #[serde(default)]
+    pub display_capture: Option<Allowlist>,
+    #[serde(default)]
+    pub encrypted_media: Option<Allowlist>,
+    #[serde(default)]
+    pub fullscreen: Option<Allowlist>,
+    #[serde(default)]
+    pub geolocation: Option<Allowlist>,
+    #[serde(default)]
+    pub gyroscope: Option<Allowlist>,
+    #[serde(default)]
+    pub magnetometer: Option<Allowlist>,
+    #[serde(default)]
+    pub microphone: Option<Allowlist>,
+    #[serde(default)]
+    pub midi: Option<Allowlist>,
+    #[serde(default)]
+    pub payment: Option<Allowlist>,
+    #[serde(default)]
+    pub picture_in_picture: Option<Allowlist>,
+    #[serde(default)]
+    pub screen_wake_lock: Option<Allowlist>,
+    #[serde(default)]
+    pub speaker_selection: Option<Allowlist>,
+    #[serde(default)]
+    pub usb: Option<Allowlist>,
+    #[serde(default)]
+    pub web_share: Option<Allowlist>,
+    // 上面未列出的特性，按头中的名称书写，如 { "xr-spatial-tracking" = [] }
+    #[serde(default)]
+    pub other: BTreeMap<String, Allowlist>,
+}
+
+// 单个值与数组两种写法：autoplay = "*" 与 autoplay = ["self", "https://example.com"]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[serde(from = "AllowlistRaw", into = "Vec<String>")]
+pub struct Allowlist(Vec<String>);
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum AllowlistRaw {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<AllowlistRaw> for Allowlist {
+    fn from(raw: AllowlistRaw) -> Self {
+        match raw {
+            AllowlistRaw::One(one) => Allowlist(vec![one]),
+            AllowlistRaw::Many(many) => Allowlist(many),
+        }
+    }
+}
+
+impl From<Allowlist> for Vec<String> {
+    fn from(allowlist: Allowlist) -> Self {
+        allowlist.0
+    }
+}
+
+impl Allowlist {
+    // 结构化字段的写法：*、(self "https://example.com")、()
+    fn render(&self, feature: &str) -> Result<String, String> {
+        if self.0.iter().any(|item| item == "*") {
+            return Ok("*".to_string());
+        }
+        let items = self
+            .0
+            .iter()
+            .map(|item| match item.as_str() {
+                "self" | "src" => Ok(item.clone()),
+                origin if is_origin(origin) => Ok(format!("\"{}\"", origin)),
+                _ => Err(format!(
+                    "[permissions_policy] {}: `{}` is not \"self\", \"src\", \"*\" or an origin",
+                    feature, item
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("({})", items.join(" ")))
+    }
+}
+
+fn is_origin(value: &str) -> bool {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return false;
+    };
+    !scheme.is_empty()
+        && !rest.is_empty()
+        && !rest.contains('/')
+        && value.bytes().all(|b| b.is_ascii_graphic() && b != b'"')
+}
+
+impl PermissionsPolicyConfig {
+    // 生成 Permissions-Policy 头的值，如 `autoplay=(self), camera=(), microphone=(self "https://a.example")`
+    pub fn header(&self) -> Result<String, String> {
+        let named = [
+            ("accelerometer", &self.accelerometer),
+            ("autoplay", &self.autoplay),
+            ("camera", &self.camera),
+            ("clipboard-read", &self.clipboard_read),
+            ("clipboard-write", &self.clipboard_write),
+            ("display-capture", &self.display_capture),
+            ("encrypted-media", &self.encrypted_media),
+            ("fullscreen", &self.fullscreen),
+            ("geolocation", &self.geolocation),
+            ("gyroscope", &self.gyroscope),
+            ("magnetometer", &self.magnetometer),
+            ("microphone", &self.microphone),
+            ("midi", &self.midi),
+            ("payment", &self.payment),
+            ("picture-in-picture", &self.picture_in_picture),
+            ("screen-wake-lock", &self.screen_wake_lock),
+            ("speaker-selection", &self.speaker_selection),
+            ("usb", &self.usb),
+            ("web-share", &self.web_share),
+        ];
+        let mut directives = Vec::new();
+        for (feature, allowlist) in named {
+            if let Some(allowlist) = allowlist {
+                directives.push(format!("{}={}", feature, allowlist.render(feature)?));
+            }
+        }
+        for (feature, allowlist) in &self.other {
+            let valid = !feature.is_empty()
+                && feature
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+            if !valid {
+                return Err(format!(
+                    "[permissions_policy] `{}` is not a valid feature name",
+                    feature
+                ));
+            }
+            directives.push(format!("{}={}", feature, allowlist.render(feature)?));
+        }
+        if directives.is_empty() {
+            return Err("[permissions_policy] declares no features".to_string());
+        }
+        Ok(directives.join(", "))
+    }
+}
+
+pub struct SecurityHeaders {
+    headers: Vec<(HeaderName, HeaderValue)>,
+    hsts: Option<HeaderValue>,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &SecurityHeadersConfig) -> Result<Self, String> {
+        let mut headers = Vec::new();
+        if config.cross_origin_isolation.unwrap_or(true) {
+            headers.push((
+                HeaderName::from_static("cross-origin-opener-policy"),
+                HeaderValue::from_static("same-origin"),
+            ));
+            headers.push((
+                HeaderName::from_static("cross-origin-embedder-policy"),
+                HeaderValue::from_static("require-corp"),
+            ));
+        }
+        if config.content_type_options == Some(true) {
+            headers.push((
+                header::X_CONTENT_TYPE_OPTIONS,
+                HeaderValue::from_static("nosniff"),
+            ));
+        }
+        let values = [
+            (
+                header::CONTENT_SECURITY_POLICY,
+                &config.content_security_policy,
+            ),
+            (
+                header::CONTENT_SECURITY_POLICY_REPORT_ONLY,
+                &config.content_security_policy_report_only,
+            ),
+            (
+                HeaderName::from_static("reporting-endpoints"),
+                &config.reporting_endpoints,
+            ),
+            (header::X_FRAME_OPTIONS, &config.frame_options),
+            (header::REFERRER_POLICY, &config.referrer_policy),
+            (
+                HeaderName::from_static("permissions-policy"),
+                &config.permissions_policy,
+            ),
+        ];
+        for (name, value) in values {
+            if let Some(value) = value.as_deref().and_then(|v| parse(&name, v).transpose()) {
+                headers.push((name, value?));
+            }
+        }
+        let hsts = match config.hsts.as_deref() {
+            Some(value) => parse(&header::STRICT_TRANSPORT_SECURITY, value)?,
+            None => None,
+        };
+        Ok(SecurityHeaders { headers, hsts })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty() && self.hsts.is_none()
+    }
+}
+
+// "" 表示不发送
+fn parse(name: &HeaderName, value: &str) -> Result<Option<HeaderValue>, String> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    HeaderValue::from_str(value)
+        .map(Some)
+        .map_err(|_| format!("invalid [security_headers] value for `{}`", name))
+}
+
+pub async fn apply(
+    State(security): State<Arc<SecurityHeaders>>,
+    client: Option<Extension<ClientInfo>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let https = client.is_some_and(|client| client.scheme == "https");
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    for (name, value) in &security.headers {
+        if !headers.contains_key(name) {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+    if let Some(hsts) = security.hsts.as_ref().filter(|_| https) {
+        if !headers.contains_key(header::STRICT_TRANSPORT_SECURITY) {
+            headers.insert(header::STRICT_TRANSPORT_SECURITY, hsts.clone());
+        }
+    }
+    response
+}
@@ -0,0 +1,363 @@
+// Sentry 错误上报（cargo feature "sentry"，[sentry] 或环境变量 SENTRY_DSN）：panic、5xx 响应与监听错误
+// 连同请求路径、请求 ID 发送到 Sentry，无人值守的设备上的故障不再无声无息。
+// 事件在后台任务中发送，队列满时丢弃；同一事件一分钟内只上报一次
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SentryConfig {
+    // 项目的 DSN，如 "https://<key>@o0.ingest.sentry.io/<project>"；环境变量 SENTRY_DSN 优先
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub dsn: Option<String>,
+    // 事件的 environment，如 "production"、"kiosk"
+    #[serde(default)]
+    pub environment: Option<String>,
+    // 事件的 server_name，默认使用主机名
+    #[serde(default)]
+    pub server_name: Option<String>,
+    // 上报 5xx 响应
+    #[serde(default = "default_report_5xx")]
+    pub report_5xx: bool,
+    // 发送一个事件的超时秒数
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_report_5xx() -> bool {
+    true
+}
+
+fn default_timeout() -> u64 {
+    5
+}
+
+impl Default for SentryConfig {
+    fn default() -> Self {
+        SentryConfig {
+            dsn: None,
+            environment: None,
+            server_name: None,
+            report_5xx: default_report_5xx(),
+            timeout_secs: default_timeout(),
+        }
+    }
+}
+
+#[cfg(feature = "sentry")]
+pub use reporter::{capture_error, flush, init, track};
+
+#[cfg(not(feature = "sentry"))]
+pub fn init(config: Option<&SentryConfig>) {
+    if config.is_some_and(|config| config.dsn.is_some()) {
+        tracing::warn!("[sentry] is set but this build does not include the sentry feature");
+    }
+}
+
+#[cfg(not(feature = "sentry"))]
+pub fn capture_error(_kind: &str, _message: String) {}
+
+#[cfg(not(feature = "sentry"))]
+pub async fn flush() {}
+
+#[cfg(feature = "sentry")]
+mod reporter {
+    use super::SentryConfig;
+    use crate::request_id::{self, RequestId};
+    use axum::body::Body;
+    use axum::extract::Request;
+    use axum::http::{header, HeaderValue, Method, Uri};
+    use axum::middleware::Next;
+    use axum::response::Response;
+    use hyper_rustls::HttpsConnector;
+    use hyper_util::client::legacy::connect::HttpConnector;
+    use hyper_util::client::legacy::Client;
+    use hyper_util::rt::TokioExecutor;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::{Mutex, OnceLock};
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+    use tokio::sync::{mpsc, oneshot};
+    use tracing::{info, warn};
+
+    // 同一事件（类型与消息相同）的最短上报间隔
+    const DEDUP_INTERVAL: Duration = Duration::from_secs(60);
+    const QUEUE: usize = 64;
+    // 退出前等待队列发送完毕的上限
+    const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+    enum Message {
+        Event(Value),
+        Flush(oneshot::Sender<()>),
+    }
+
+    struct Reporter {
+        tx: mpsc::Sender<Message>,
+        environment: Option<String>,
+        server_name: Option<String>,
+        report_5xx: bool,
+        recent: Mutex<HashMap<String, Instant>>,
+    }
+
+    static REPORTER: OnceLock<Reporter> = OnceLock::new();
+
+    // 正在处理的请求，panic 时附加到事件中
+    #[derive(Clone)]
+    struct RequestContext {
+        method: Method,
+        path: String,
+        request_id: Option<String>,
+    }
+
+    tokio::task_local! {
+        static REQUEST: RequestContext;
+    }
+
+    // 解析后的 DSN：envelope 端点与公钥
+    struct Dsn {
+        raw: String,
+        endpoint: Uri,
+        auth: HeaderValue,
+    }
+
+    impl Dsn {
+        fn parse(dsn: &str) -> Result<Self, String> {
+            let invalid = || format!("invalid Sentry DSN `{}`", dsn);
+            let uri: Uri = dsn.parse().map_err(|_| invalid())?;
+            let scheme = uri.scheme_str().ok_or_else(invalid)?;
+            let authority = uri.authority().ok_or_else(invalid)?.as_str();
+            let (userinfo, host) = authority.rsplit_once('@').ok_or_else(invalid)?;
+            let key = userinfo.split(':').next().unwrap_or_default();
+            let (prefix, project) = uri
+                .path()
+                .trim_end_matches('/')
+                .rsplit_once('/')
+                .ok_or_else(invalid)?;
+            if key.is_empty() || project.is_empty() {
+                return Err(invalid());
+            }
+            let endpoint = format!("{}://{}{}/api/{}/envelope/", scheme, host, prefix, project)
+                .parse()
+                .map_err(|_| invalid())?;
+            let auth = HeaderValue::from_str(&format!(
+                "Sentry sentry_version=7, sentry_key={}, sentry_client=sonic-wave/{}",
+                key,
+                env!("CARGO_PKG_VERSION")
+            ))
+            .map_err(|_| invalid())?;
+            Ok(Dsn {
+                raw: dsn.to_string(),
+                endpoint,
+                auth,
+            })
+        }
+    }
+
+    // 启动后台发送任务并安装 panic 钩子；没有配置 DSN 时不启用
+    pub fn init(config: Option<&SentryConfig>) {
+        let Some(config) = config else {
+            return;
+        };
+        let Some(raw) = config.dsn.as_deref() else {
+            return;
+        };
+        let dsn = match Dsn::parse(raw) {
+            Ok(dsn) => dsn,
+            Err(e) => {
+                warn!("Sentry disabled: {}", e);
+                return;
+            }
+        };
+        let (tx, rx) = mpsc::channel(QUEUE);
+        let reporter = Reporter {
+            tx,
+            environment: config.environment.clone(),
+            server_name: config.server_name.clone().or_else(hostname),
+            report_5xx: config.report_5xx,
+            recent: Mutex::new(HashMap::new()),
+        };
+        if REPORTER.set(reporter).is_err() {
+            return;
+        }
+        tokio::spawn(send_events(
+            dsn,
+            Duration::from_secs(config.timeout_secs.max(1)),
+            rx,
+        ));
+
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |panic| {
+            let payload = panic.payload();
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Box<dyn Any>".to_string());
+            let location = panic
+                .location()
+                .map(|l| format!("{}:{}", l.file(), l.line()))
+                .unwrap_or_default();
+            let thread = std::thread::current()
+                .name()
+                .unwrap_or("<unnamed>")
+                .to_string();
+            let mut event = json!({
+                "level": "fatal",
+                "exception": {"values": [{
+                    "type": "panic",
+                    "value": message,
+                    "mechanism": {"type": "panic", "handled": false},
+                }]},
+                "extra": {"location": location, "thread": thread},
+            });
+            let _ = REQUEST.try_with(|request| request.apply(&mut event));
+            capture("panic", &format!("{} at {}", message, location), event);
+            previous(panic);
+        }));
+        info!("Sentry error reporting enabled");
+    }
+
+    impl RequestContext {
+        fn apply(&self, event: &mut Value) {
+            event["request"] = json!({"method": self.method.as_str(), "url": self.path});
+            event["transaction"] = json!(format!("{} {}", self.method, self.path));
+            if let Some(id) = &self.request_id {
+                event["tags"]["request_id"] = json!(id);
+            }
+        }
+    }
+
+    fn hostname() -> Option<String> {
+        #[cfg(target_os = "linux")]
+        {
+            let mut buf = [0u8; 256];
+            // SAFETY: buf 在调用期间有效，长度与传入的一致
+            let ret = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+            if ret == 0 {
+                let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+                return Some(String::from_utf8_lossy(&buf[..len]).into_owned());
+            }
+        }
+        std::env::var("HOSTNAME")
+            .or_else(|_| std::env::var("COMPUTERNAME"))
+            .ok()
+    }
+
+    // 补全公共字段后放入发送队列；key 用于去重
+    fn capture(kind: &str, key: &str, mut event: Value) {
+        let Some(reporter) = REPORTER.get() else {
+            return;
+        };
+        {
+            let mut recent = reporter.recent.lock().unwrap();
+            let now = Instant::now();
+            recent.retain(|_, sent| now.duration_since(*sent) < DEDUP_INTERVAL);
+            let key = format!("{}:{}", kind, key);
+            if recent.contains_key(&key) {
+                return;
+            }
+            recent.insert(key, now);
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or(0.0);
+        event["event_id"] = json!(request_id::random_ids().0);
+        event["timestamp"] = json!(timestamp);
+        event["platform"] = json!("other");
+        event["logger"] = json!("sonic-wave");
+        event["release"] = json!(concat!("sonic-wave@", env!("CARGO_PKG_VERSION")));
+        event["tags"]["kind"] = json!(kind);
+        if let Some(environment) = &reporter.environment {
+            event["environment"] = json!(environment);
+        }
+        if let Some(server_name) = &reporter.server_name {
+            event["server_name"] = json!(server_name);
+        }
+        let _ = reporter.tx.try_send(Message::Event(event));
+    }
+
+    // 监听器等非请求错误
+    pub fn capture_error(kind: &str, message: String) {
+        let event = json!({
+            "level": "error",
+            "message": {"formatted": message},
+        });
+        capture(kind, &message, event);
+    }
+
+    // 等待队列中的事件发送完毕，用于启动失败退出之前
+    pub async fn flush() {
+        let Some(reporter) = REPORTER.get() else {
+            return;
+        };
+        let (tx, rx) = oneshot::channel();
+        if reporter.tx.send(Message::Flush(tx)).await.is_ok() {
+            let _ = tokio::time::timeout(FLUSH_TIMEOUT, rx).await;
+        }
+    }
+
+    // 记录请求上下文供 panic 钩子使用，并上报 5xx 响应
+    pub async fn track(req: Request, next: Next) -> Response {
+        let context = RequestContext {
+            method: req.method().clone(),
+            path: req.uri().path().to_string(),
+            request_id: req.extensions().get::<RequestId>().map(|id| id.0.clone()),
+        };
+        let response = REQUEST.scope(context.clone(), next.run(req)).await;
+        let status = response.status();
+        if status.is_server_error() && REPORTER.get().is_some_and(|r| r.report_5xx) {
+            let message = format!("{} for {} {}", status, context.method, context.path);
+            let mut event = json!({
+                "level": "error",
+                "message": {"formatted": message},
+                "tags": {"status_code": status.as_u16().to_string()},
+            });
+            context.apply(&mut event);
+            capture("http_5xx", &format!("{} {}", status, context.path), event);
+        }
+        response
+    }
+
+    async fn send_events(dsn: Dsn, timeout: Duration, mut rx: mpsc::Receiver<Message>) {
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(connector);
+        let client: Client<HttpsConnector<HttpConnector>, Body> =
+            Client::builder(TokioExecutor::new()).build(https);
+        while let Some(message) = rx.recv().await {
+            let event = match message {
+                Message::Event(event) => event,
+                Message::Flush(done) => {
+                    let _ = done.send(());
+                    continue;
+                }
+            };
+            // envelope：头部、条目头部与事件各占一行
+            let body = format!(
+                "{}\n{}\n{}\n",
+                json!({"event_id": event["event_id"], "dsn": dsn.raw}),
+                json!({"type": "event"}),
+                event
+            );
+            let request = match axum::http::Request::post(dsn.endpoint.clone())
+                .header(header::CONTENT_TYPE, "application/x-sentry-envelope")
+                .header("x-sentry-auth", dsn.auth.clone())
+                .body(Body::from(body))
+            {
+                Ok(request) => request,
+                Err(_) => continue,
+            };
+            // 上报失败只记录日志，避免出错时反复上报
+            match tokio::time::timeout(timeout, client.request(request)).await {
+                Ok(Ok(response)) if response.status().is_success() => {}
+                Ok(Ok(response)) => warn!("Sentry rejected an event: {}", response.status()),
+                Ok(Err(e)) => warn!("Failed to send an event to Sentry: {}", e),
+                Err(_) => warn!("Timed out sending an event to Sentry"),
+            }
+        }
+    }
+}
@@ -0,0 +1,593 @@
+// 服务进程：绑定监听器并处理热重启、优雅关闭与 --watch；
+// 连接接受循环在多个监听器上用同一个 Router 提供 HTTP/1 与 HTTP/2 服务
+use crate::config::{load_config, Config};
+use crate::keep_alive::{self, KeepAlive};
+use crate::listener::{
+    self, ListenAddr, ListenSpec, Listener, ListenerOptions, PeerAddr, Stream, TcpBindOptions,
+    UnixSocketConfig,
+};
+use crate::live_reload::{self, ChangeHub, OnChange};
+use crate::proxy_protocol::{self, Rewind};
+use crate::s3::Backend;
+use crate::shutdown::{Readiness, Shutdown};
+use crate::slow_client::{self, Limits};
+use crate::throttle::ConnectionThrottle;
+use crate::tls::{self, Tls, TlsInfo};
+use crate::{app, early_hints, lan, mdns, metrics, preload, sentry, supervisor};
+#[cfg(unix)]
+use crate::{privileges, systemd, upgrade};
+use axum::body::Body;
+use axum::http::{header, HeaderValue, Request, Version};
+use axum::Router;
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use std::convert::Infallible;
+use std::future::Future;
+use std::io::IsTerminal;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, watch};
+use tower::ServiceExt;
+use tracing::{debug, info, warn, Instrument};
+
+// 客户端地址（经 PROXY protocol 还原后），作为请求扩展供后续中间件使用
+#[derive(Debug, Clone, Copy)]
+pub struct ClientAddr(pub PeerAddr);
+
+// 连接级的设置：hyper 的协议参数、慢速客户端与持久连接的限制
+struct ConnectionSettings {
+    builder: Builder<TokioExecutor>,
+    limits: Limits,
+    keep_alive: KeepAlive,
+}
+
+struct Accepted {
+    stream: Stream,
+    peer: PeerAddr,
+    listener: Arc<str>,
+    options: ListenerOptions,
+}
+
+pub async fn serve(
+    listeners: Vec<(Listener, ListenerOptions)>,
+    app: Router,
+    tls: Option<Arc<Tls>>,
+    limits: Limits,
+    keep_alive: KeepAlive,
+    stopped: impl Future<Output = ()>,
+    drain_deadline: impl Future<Output = ()>,
+) {
+    let listeners: Vec<(Arc<Listener>, ListenerOptions)> = listeners
+        .into_iter()
+        .map(|(listener, options)| (Arc::new(listener), options))
+        .collect();
+    let (tx, mut rx) = mpsc::channel::<Accepted>(256);
+
+    let mut accept_tasks = Vec::with_capacity(listeners.len());
+    for (listener, options) in &listeners {
+        let listener = listener.clone();
+        let options = *options;
+        let tx = tx.clone();
+        // 每个监听器的日志都带上自己的地址
+        let name: Arc<str> = listener.local_addr().to_string().into();
+        accept_tasks.push(tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, peer)) => {
+                        debug!("Accepted connection from {} on {}", peer, name);
+                        if options.tcp_nodelay {
+                            if let Err(e) = stream.set_nodelay(true) {
+                                debug!("Failed to set TCP_NODELAY for {}: {}", peer, e);
+                            }
+                        }
+                        let accepted = Accepted {
+                            stream,
+                            peer,
+                            listener: name.clone(),
+                            options,
+                        };
+                        if tx.send(accepted).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => {
+                        // EMFILE 等错误时稍作等待，避免空转
+                        warn!("Accept error on {}: {}", name, e);
+                        sentry::capture_error(
+                            "listener",
+                            format!("Accept error on {}: {}", name, e),
+                        );
+                        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                    }
+                }
+            }
+        }));
+    }
+    drop(tx);
+
+    let mut builder = Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .header_read_timeout(limits.header_read_timeout);
+    let settings = Arc::new(ConnectionSettings {
+        builder,
+        limits,
+        keep_alive,
+    });
+    // 关闭信号：每个连接持有一个接收端，全部释放即排空完成
+    let (closing_tx, closing_rx) = watch::channel(());
+    tokio::pin!(stopped);
+
+    loop {
+        tokio::select! {
+            conn = rx.recv() => {
+                let Some(accepted) = conn else { break };
+                let closing = closing_rx.clone();
+                let settings = settings.clone();
+                let app = app.clone();
+                let tls = tls.clone();
+                tokio::spawn(async move {
+                    let Accepted { mut stream, peer, listener, options } = accepted;
+
+                    // 解析 PROXY 头，得到真实客户端地址
+                    let (client, prefix) = if options.proxy_protocol {
+                        match proxy_protocol::read_header(&mut stream).await {
+                            Ok((addr, rest)) => (addr.map(PeerAddr::Tcp).unwrap_or(peer), rest),
+                            Err(e) => {
+                                warn!("Rejected connection from {} on {}: {}", peer, listener, e);
+                                return;
+                            }
+                        }
+                    } else {
+                        (peer, Vec::new())
+                    };
+
+                    let span = tracing::info_span!("conn", listener = %listener, client = %client);
+                    async move {
+                        // TLS 握手在 PROXY 头之后
+                        let stream = Rewind::with_prefix(stream, prefix);
+                        match tls.as_deref().filter(|_| options.tls) {
+                            Some(tls) => match tls.accept(stream).await {
+                                Ok((stream, info)) => {
+                                    debug!(
+                                        "TLS handshake completed, SNI {:?}, client {:?}",
+                                        info.server_name, info.client_subject
+                                    );
+                                    serve_connection(stream, Some(info), client, app, &settings, closing)
+                                        .await
+                                }
+                                Err(e) => debug!("TLS handshake failed: {}", e),
+                            },
+                            None => serve_connection(stream, None, client, app, &settings, closing).await,
+                        }
+                    }
+                    .instrument(span)
+                    .await;
+                });
+            }
+            _ = &mut stopped => break,
+        }
+    }
+
+    // 停止接受新连接
+    for task in accept_tasks {
+        task.abort();
+    }
+    drop(rx);
+    for (listener, _) in &listeners {
+        listener.cleanup();
+    }
+
+    drop(closing_rx);
+    let _ = closing_tx.send(());
+    tokio::select! {
+        _ = closing_tx.closed() => info!("All connections drained"),
+        _ = drain_deadline => {}
+    }
+}
+
+async fn serve_connection<S>(
+    stream: S,
+    tls: Option<TlsInfo>,
+    client: PeerAddr,
+    app: Router,
+    settings: &ConnectionSettings,
+    mut closing: watch::Receiver<()>,
+) where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    // 处理函数可以通过写入端在最终响应之前发送 103 Early Hints
+    let (io, informational) =
+        early_hints::wrap(slow_client::Guarded::new(stream, &settings.limits));
+    let _connected = metrics::Connected::new(&client);
+    let throttle = ConnectionThrottle::default();
+    let limits = settings.limits;
+    let connection = keep_alive::Connection::new(settings.keep_alive);
+    let tracked = connection.clone();
+    let service = tower::service_fn(move |req: Request<Incoming>| {
+        let mut req = req.map(|body| slow_client::RequestBody::new(body, &limits));
+        req.extensions_mut().insert(ClientAddr(client));
+        req.extensions_mut().insert(throttle.clone());
+        req.extensions_mut().insert(informational.clone());
+        if let Some(tls) = &tls {
+            req.extensions_mut().insert(tls.clone());
+        }
+        let (in_flight, last) = tracked.begin();
+        // HTTP/2 不允许 Connection 头，达到限制后在空闲时关闭
+        let close = last && req.version() < Version::HTTP_2;
+        let app = app.clone();
+        async move {
+            let mut response = app.oneshot(req).await?;
+            if close {
+                response
+                    .headers_mut()
+                    .insert(header::CONNECTION, HeaderValue::from_static("close"));
+            }
+            Ok::<_, Infallible>(
+                response.map(|body| Body::new(keep_alive::Tracked::new(body, in_flight))),
+            )
+        }
+    });
+    let conn = settings
+        .builder
+        .serve_connection_with_upgrades(TokioIo::new(io), TowerToHyperService::new(service))
+        .into_owned();
+    tokio::pin!(conn);
+    // 服务器关闭或连接到达限制时优雅关闭：HTTP/1 处理完当前请求，HTTP/2 发送 GOAWAY
+    let mut shutting_down = false;
+    loop {
+        tokio::select! {
+            result = conn.as_mut() => {
+                if let Err(e) = result {
+                    debug!("Connection closed with error: {}", e);
+                }
+                break;
+            }
+            _ = closing.changed(), if !shutting_down => {
+                shutting_down = true;
+                conn.as_mut().graceful_shutdown();
+            }
+            _ = connection.expired(), if !shutting_down => {
+                debug!("Closing idle connection");
+                shutting_down = true;
+                conn.as_mut().graceful_shutdown();
+            }
+        }
+    }
+}
+
+// 命令行的 serve：读取配置、组装站点、绑定监听器并运行到关闭
+pub async fn run(watch: bool, open_path: Option<String>, dev: bool) {
+    let mut config = load_config();
+    if dev {
+        config.apply_dev();
+    }
+    sentry::init(config.sentry.as_ref());
+    let worker_id = supervisor::worker_id();
+    let port = config.port.unwrap_or(8089);
+    let static_dir = config.site_dir();
+
+    info!("Starting Sonic Wave server");
+    if dev {
+        warn!("Development mode: caching disabled, CORS open to any origin, directory listing and live reload on");
+    }
+    info!("Port: {}", port);
+    info!("Static directory: {}", static_dir);
+    info!("Cache-Control (static): {}", config.cache_control);
+    info!("Cache-Control (HTML): {}", config.html_cache_control);
+
+    // 优先接管旧进程交接过来的监听 FD（SIGUSR2 热重启），其次是 systemd socket activation
+    #[cfg(unix)]
+    let mut listeners = upgrade::inherited_listeners();
+    #[cfg(unix)]
+    if listeners.is_empty() {
+        listeners = systemd::activated_listeners();
+    }
+    #[cfg(not(unix))]
+    let mut listeners: Vec<Listener> = Vec::new();
+
+    let specs = listen_specs(&config, port);
+    if listeners.is_empty() {
+        for spec in &specs {
+            let options = TcpBindOptions {
+                reuse_port: config.reuse_port || spec.options.reuse_port || worker_id.is_some(),
+                only_v6: match &spec.addr {
+                    ListenAddr::Tcp(addr) => listener::needs_v6_only(addr, &specs),
+                    ListenAddr::Unix(_) => false,
+                },
+                backlog: spec.options.backlog,
+                send_buffer: spec.options.send_buffer,
+                recv_buffer: spec.options.recv_buffer,
+            };
+            match Listener::bind(spec, &options, &config.unix_socket) {
+                Ok(listener) => listeners.push(listener),
+                Err(e) => {
+                    tracing::error!("Failed to bind {}: {}", spec.addr, e);
+                    sentry::capture_error(
+                        "listener",
+                        format!("Failed to bind {}: {}", spec.addr, e),
+                    );
+                    sentry::flush().await;
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+
+    // 绑定之后、读取站点文件与运行构建命令之前降权
+    #[cfg(unix)]
+    if let Err(e) = privileges::drop(config.user.as_deref(), config.group.as_deref()) {
+        tracing::error!("Failed to drop privileges: {}", e);
+        std::process::exit(1);
+    }
+    #[cfg(not(unix))]
+    if config.user.is_some() || config.group.is_some() {
+        warn!("user / group are only supported on Unix");
+    }
+
+    // 启动前先运行一次站点构建，构建失败则不启动服务
+    if let Some(build) = &config.build {
+        if build.run_on_start {
+            if let Err(e) = build.run().await {
+                tracing::error!("Build failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let readiness = Readiness::new();
+    let changes = watch.then(|| Arc::new(ChangeHub::new()));
+    let app = match app::build(&config, readiness.clone(), changes.clone()) {
+        Ok(app) => app,
+        Err(e) => {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // 在通知就绪（与热重启的旧进程）之前完成预热
+    if !config.preload.is_empty() {
+        if config.cache.is_none() {
+            warn!("preload without [cache] only warms the operating system's page cache");
+        }
+        let dir =
+            (config.backend == Backend::Fs && !config.embedded).then_some(static_dir.as_str());
+        if let Err(e) = preload::warm(&app, &config.preload, dir).await {
+            tracing::error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let bound: Vec<ListenAddr> = listeners.iter().map(|l| l.local_addr()).collect();
+    // 继承来的监听器按地址匹配配置中的选项
+    let listeners: Vec<(Listener, ListenerOptions)> = listeners
+        .into_iter()
+        .map(|listener| {
+            let addr = listener.local_addr();
+            let options = specs
+                .iter()
+                .find(|spec| spec.addr == addr)
+                .map(|spec| spec.options)
+                .unwrap_or_default();
+            (listener, options)
+        })
+        .collect();
+
+    // 证书在启动时加载，出错时不启动
+    let tls = if listeners.iter().any(|(_, options)| options.tls) {
+        match Tls::new(config.tls.as_ref(), &config.vhost) {
+            Ok(tls) => Some(Arc::new(tls)),
+            Err(e) => {
+                tracing::error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        if config.tls.is_some() {
+            warn!("[tls] is set but no listen entry has tls = true");
+        }
+        None
+    };
+    // 证书轮换后无需重启，watcher 在服务期间保持存活
+    let _tls_watcher = tls.clone().and_then(tls::watch);
+    let urls: Vec<String> = listeners
+        .iter()
+        .map(|(listener, options)| listener.local_addr().url(options.tls))
+        .collect();
+
+    if let Some(id) = worker_id {
+        // supervisor 模式下的工作进程：共享端口并定期上报统计
+        let interval = std::env::var("SONICWAVE_STATS_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60);
+        supervisor::spawn_stats_reporter(std::time::Duration::from_secs(interval));
+        info!("Running as worker {}", id);
+    } else {
+        print_banner(&config, &bound, &urls, port, &static_dir);
+    }
+
+    for url in &urls {
+        info!("Server ready, listening on {}", url);
+    }
+
+    // --open：监听就绪后打开浏览器，绑定 0.0.0.0 时使用第一个局域网地址
+    if let Some(path) = open_path.filter(|_| worker_id.is_none()) {
+        match lan::urls(&bound).first() {
+            Some(url) => {
+                let url = format!("{}/{}", url, path.trim_start_matches('/'));
+                info!("Opening {} in the browser", url);
+                if let Err(e) = open::that_detached(&url) {
+                    warn!("Failed to open browser: {}", e);
+                }
+            }
+            None => warn!("--open needs a TCP listener"),
+        }
+    }
+
+    #[cfg(unix)]
+    if worker_id.is_none() {
+        upgrade::spawn_upgrade_handler(listeners.iter().map(|(l, _)| l.as_raw_fd()).collect());
+    }
+
+    // 优雅关闭：信号 -> 就绪失败 -> 停止接受连接 -> 排空（超时后丢弃剩余连接）
+    let shutdown = Shutdown::new(config.shutdown.clone(), readiness);
+    let drain_deadline = shutdown.drain_deadline();
+    let stopped = shutdown.stopped();
+    // 监听站点目录（主目录、挂载点与虚拟主机），watcher 在服务期间保持存活
+    let _watcher = match &changes {
+        Some(changes) => {
+            // 内嵌资源不会变化，对象存储无法监听
+            let dirs: Vec<PathBuf> = std::iter::once(&static_dir)
+                .filter(|_| !config.embedded && config.backend == Backend::Fs)
+                .chain(config.mount.iter().map(|m| &m.dir))
+                .chain(config.vhost.iter().map(|v| &v.dir))
+                .map(PathBuf::from)
+                .collect();
+            // 关闭时结束 SSE 连接，避免拖住排空
+            let hub = changes.clone();
+            let closed = shutdown.stopped();
+            tokio::spawn(async move {
+                closed.await;
+                hub.shutdown();
+            });
+            let on_change = config
+                .build
+                .as_ref()
+                .map(OnChange::new)
+                .transpose()
+                .unwrap_or_else(|e| {
+                    tracing::error!("Invalid [build] watch: {}", e);
+                    std::process::exit(1);
+                })
+                .flatten();
+            match live_reload::watch(&dirs, changes.clone(), on_change) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    tracing::error!("Failed to watch site directories: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => {
+            if config.build.as_ref().is_some_and(|b| !b.watch.is_empty()) {
+                warn!("[build] watch is only used with --watch");
+            }
+            None
+        }
+    };
+    tokio::spawn(shutdown.run());
+
+    #[cfg(unix)]
+    {
+        upgrade::notify_parent_ready();
+        systemd::notify_ready();
+        systemd::spawn_watchdog();
+    }
+
+    // supervisor 模式下由各工作进程共享端口，只在单进程模式下发布
+    let advertisement = match &config.mdns {
+        Some(mdns) if worker_id.is_none() => mdns::advertise(mdns, &bound),
+        _ => None,
+    };
+
+    serve(
+        listeners,
+        app,
+        tls,
+        Limits::new(&config.slow_clients),
+        KeepAlive::new(&config.keep_alive),
+        stopped,
+        drain_deadline,
+    )
+    .await;
+    if let Some(advertisement) = advertisement {
+        advertisement.stop();
+    }
+
+    info!("Server stopped");
+}
+
+// 解析配置的监听地址，无效地址直接退出
+fn listen_specs(config: &Config, port: u16) -> Vec<ListenSpec> {
+    if config.listen.is_empty() {
+        return vec![ListenSpec {
+            addr: ListenAddr::Tcp(SocketAddr::from(([0, 0, 0, 0], port))),
+            device: None,
+            options: ListenerOptions::default(),
+            unix_socket: UnixSocketConfig::default(),
+        }];
+    }
+    let mut specs = Vec::new();
+    for entry in &config.listen {
+        match ListenSpec::parse_all(entry) {
+            Ok(mut parsed) => specs.append(&mut parsed),
+            Err(e) => {
+                tracing::error!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    specs
+}
+
+fn print_banner(
+    config: &Config,
+    bound: &[ListenAddr],
+    urls: &[String],
+    port: u16,
+    static_dir: &str,
+) {
+    println!("🎵 Sonic Wave Server");
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    for url in urls {
+        println!("🌐 Listening on: {}", url);
+    }
+    // 绑定 0.0.0.0 时列出局域网地址，并为第一个地址显示二维码，方便在手机上打开
+    let lan_urls = lan::lan_urls(bound);
+    for url in &lan_urls {
+        println!("📱 LAN: {}", url);
+    }
+    if config.qr_code && std::io::stdout().is_terminal() {
+        if let Some(qr) = lan_urls.first().and_then(|url| lan::qr_code(url)) {
+            println!("{}", qr);
+        }
+    }
+    println!("📁 Static directory: {}", static_dir);
+    println!("🔒 Headers: COOP/COEP enabled");
+    if let Some(cors) = &config.cors {
+        println!(
+            "   CORS origins: {}",
+            cors.policy
+                .origins
+                .as_deref()
+                .unwrap_or_default()
+                .join(", ")
+        );
+    }
+    println!("💾 Cache-Control:");
+    println!("   HTML files: {}", config.html_cache_control);
+    match &config.fingerprint {
+        Some(fingerprint) => println!(
+            "   Fingerprinted assets ({}): {}",
+            fingerprint.pattern, fingerprint.cache_control
+        ),
+        None => println!("   Static assets: {}", config.cache_control),
+    }
+    for rule in &config.cache_rules {
+        println!("   {}: {}", rule.pattern, rule.cache_control);
+    }
+    if let Some(s_maxage) = config.cdn.as_ref().and_then(|cdn| cdn.s_maxage) {
+        println!("   Shared caches: s-maxage={}", s_maxage);
+    }
+    println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    println!("✨ Configuration priority: ENV > config.toml > default");
+    println!("   PORT={}", port);
+    println!("   STATIC_DIR={}", static_dir);
+    println!("\n🛑 Press Ctrl+C to stop the server\n");
+}
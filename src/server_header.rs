@@ -0,0 +1,145 @@
+// Server 响应头（[server_header]）：统一设置、随机选择或去掉 Server 头，并去掉 X-Powered-By 等暴露实现的头。
+// 在全局中间件的外层处理，静态文件、反向代理（上游的 Server 头同样被替换）与错误页的响应一致
+use crate::chaos;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+// set：发送 value；random：每个响应从 values 中随机选一个；remove：不发送 Server 头
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ServerHeaderMode {
+    #[default]
+    Set,
+    Random,
+    Remove,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ServerHeaderConfig {
+    #[serde(default)]
+    pub mode: ServerHeaderMode,
+    // mode = "set" 时的值
+    #[serde(default = "default_value")]
+    pub value: String,
+    // mode = "random" 时的候选值
+    #[serde(default = "default_values")]
+    pub values: Vec<String>,
+    // 从所有响应中去掉的头；以 * 结尾时按前缀匹配，如 "X-Amz-*"
+    #[serde(default = "default_strip")]
+    pub strip: Vec<String>,
+}
+
+fn default_value() -> String {
+    concat!("sonic-wave/", env!("CARGO_PKG_VERSION")).to_string()
+}
+
+fn default_values() -> Vec<String> {
+    ["nginx", "Apache", "Caddy", "openresty", "LiteSpeed"]
+        .iter()
+        .map(|value| value.to_string())
+        .collect()
+}
+
+fn default_strip() -> Vec<String> {
+    [
+        "X-Powered-By",
+        "X-AspNet-Version",
+        "X-AspNetMvc-Version",
+        "X-Generator",
+        "X-Runtime",
+        "X-Backend-Server",
+        "X-Amz-*",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect()
+}
+
+pub struct ServerHeader {
+    values: Vec<HeaderValue>,
+    strip: Vec<HeaderName>,
+    // 小写的前缀
+    strip_prefixes: Vec<String>,
+}
+
+impl ServerHeader {
+    pub fn new(config: &ServerHeaderConfig) -> Result<Self, String> {
+        let value = |value: &String| {
+            HeaderValue::from_str(value)
+                .map_err(|_| format!("[server_header] invalid value `{}`", value))
+        };
+        let values = match config.mode {
+            ServerHeaderMode::Set => vec![value(&config.value)?],
+            ServerHeaderMode::Random => {
+                config.values.iter().map(value).collect::<Result<_, _>>()?
+            }
+            ServerHeaderMode::Remove => Vec::new(),
+        };
+        if config.mode == ServerHeaderMode::Random && values.is_empty() {
+            return Err("[server_header] mode = \"random\" requires values".to_string());
+        }
+        let mut strip = Vec::new();
+        let mut strip_prefixes = Vec::new();
+        for name in &config.strip {
+            match name.strip_suffix('*') {
+                Some(prefix) => strip_prefixes.push(prefix.to_ascii_lowercase()),
+                None => strip.push(
+                    HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|_| format!("[server_header] invalid header name `{}`", name))?,
+                ),
+            }
+        }
+        Ok(ServerHeader {
+            values,
+            strip,
+            strip_prefixes,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        match self.values.as_slice() {
+            [] => "removed".to_string(),
+            [value] => format!("`{}`", value.to_str().unwrap_or("")),
+            values => format!("random of {}", values.len()),
+        }
+    }
+
+    fn pick(&self) -> Option<&HeaderValue> {
+        let index = (chaos::random() * self.values.len() as f64) as usize;
+        self.values
+            .get(index.min(self.values.len().saturating_sub(1)))
+    }
+}
+
+pub async fn apply(State(server): State<Arc<ServerHeader>>, req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    let headers = response.headers_mut();
+    for name in &server.strip {
+        headers.remove(name);
+    }
+    if !server.strip_prefixes.is_empty() {
+        let matched: Vec<HeaderName> = headers
+            .keys()
+            .filter(|name| {
+                server
+                    .strip_prefixes
+                    .iter()
+                    .any(|prefix| name.as_str().starts_with(prefix.as_str()))
+            })
+            .cloned()
+            .collect();
+        for name in matched {
+            headers.remove(name);
+        }
+    }
+    match server.pick() {
+        Some(value) => headers.insert(header::SERVER, value.clone()),
+        None => headers.remove(header::SERVER),
+    };
+    response
+}
@@ -0,0 +1,136 @@
+// Server-Timing（[server_timing]）：记录缓存查找、读取文件等阶段的耗时并写入响应头，
+// 浏览器开发者工具与 RUM 工具可以据此拆分静态资源的延迟
+use axum::extract::{Request, State};
+use axum::http::header::{HeaderName, HeaderValue};
+use axum::http::Extensions;
+use axum::middleware::Next;
+use axum::response::Response;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ServerTimingConfig {
+    // Timing-Allow-Origin 的来源列表，如 ["*"]；为空时不发送，跨域页面只能看到总耗时
+    #[serde(default)]
+    pub timing_allow_origin: Vec<String>,
+}
+
+struct Phase {
+    name: &'static str,
+    duration: Option<Duration>,
+    desc: Option<String>,
+}
+
+// 各阶段的耗时，作为请求扩展传给内层服务
+#[derive(Clone, Default)]
+pub struct Timings(Arc<Mutex<Vec<Phase>>>);
+
+impl Timings {
+    pub fn from_extensions(extensions: &Extensions) -> Option<Self> {
+        extensions.get::<Timings>().cloned()
+    }
+
+    pub fn record(&self, name: &'static str, duration: Duration, desc: Option<&str>) {
+        self.0.lock().unwrap().push(Phase {
+            name,
+            duration: Some(duration),
+            desc: desc.map(str::to_string),
+        });
+    }
+
+    // 记录一个异步阶段的耗时；未启用 Server-Timing 时直接执行
+    pub async fn measure<F: Future>(
+        timings: Option<&Timings>,
+        name: &'static str,
+        future: F,
+    ) -> F::Output {
+        let started = Instant::now();
+        let output = future.await;
+        if let Some(timings) = timings {
+            timings.record(name, started.elapsed(), None);
+        }
+        output
+    }
+
+    fn header(&self, total: Duration) -> String {
+        let phases = self.0.lock().unwrap();
+        let mut metrics: Vec<String> = phases
+            .iter()
+            .map(|phase| {
+                let mut metric = phase.name.to_string();
+                if let Some(desc) = &phase.desc {
+                    metric.push_str(&format!(";desc=\"{}\"", desc.replace(['"', '\\'], "")));
+                }
+                if let Some(duration) = phase.duration {
+                    metric.push_str(&format!(";dur={:.3}", duration.as_secs_f64() * 1000.0));
+                }
+                metric
+            })
+            .collect();
+        metrics.push(format!("total;dur={:.3}", total.as_secs_f64() * 1000.0));
+        metrics.join(", ")
+    }
+}
+
+pub struct ServerTiming {
+    allow_origin: Option<HeaderValue>,
+}
+
+impl ServerTiming {
+    pub fn new(config: &ServerTimingConfig) -> Result<Self, String> {
+        let allow_origin = if config.timing_allow_origin.is_empty() {
+            None
+        } else {
+            Some(
+                HeaderValue::from_str(&config.timing_allow_origin.join(", "))
+                    .map_err(|e| format!("invalid timing_allow_origin: {}", e))?,
+            )
+        };
+        Ok(ServerTiming { allow_origin })
+    }
+}
+
+// 耗时只统计到响应头产生为止，流式发送的正文不计入
+pub async fn track(
+    State(timing): State<Arc<ServerTiming>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let started = Instant::now();
+    let timings = Timings::default();
+    req.extensions_mut().insert(timings.clone());
+    let mut response = next.run(req).await;
+    // 实时压缩自己记录耗时；预压缩旁路文件或上游已压缩时只标出返回的编码
+    let recorded = timings
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|phase| phase.name == "compress");
+    if let Some(encoding) = response
+        .headers()
+        .get(axum::http::header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| !recorded)
+    {
+        timings.0.lock().unwrap().push(Phase {
+            name: "compress",
+            duration: None,
+            desc: Some(encoding.to_string()),
+        });
+    }
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&timings.header(started.elapsed())) {
+        headers.append(HeaderName::from_static("server-timing"), value);
+    }
+    if let Some(origin) = &timing.allow_origin {
+        headers.insert(
+            HeaderName::from_static("timing-allow-origin"),
+            origin.clone(),
+        );
+    }
+    response
+}
@@ -0,0 +1,414 @@
+// Windows 服务（仅 Windows）：service install 把当前程序注册为自动启动的服务，
+// 服务控制管理器（SCM）以 service run 启动进程，停止与关机请求走与 Ctrl+C 相同的优雅关闭流程；
+// 服务没有控制台，日志另外写入应用程序事件日志
+use crate::cli::{
+    Cli, Command, ServiceAction, ServiceArgs, ServiceInstallArgs, ServiceNameArgs, ServiceRunArgs,
+};
+use crate::config;
+use std::ffi::{c_void, OsStr};
+use std::fmt::Write as _;
+use std::io;
+use std::os::windows::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::field::{Field, Visit};
+use tracing::subscriber::NoSubscriber;
+use tracing::{error, info, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use windows_sys::core::PWSTR;
+use windows_sys::Win32::Foundation::{
+    ERROR_CALL_NOT_IMPLEMENTED, ERROR_FAILED_SERVICE_CONTROLLER_CONNECT, ERROR_SERVICE_EXISTS,
+    ERROR_SERVICE_NOT_ACTIVE, HANDLE, NO_ERROR,
+};
+use windows_sys::Win32::System::EventLog::{
+    RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE, EVENTLOG_INFORMATION_TYPE,
+    EVENTLOG_WARNING_TYPE,
+};
+use windows_sys::Win32::System::Services::{
+    ChangeServiceConfig2W, CloseServiceHandle, ControlService, CreateServiceW, DeleteService,
+    OpenSCManagerW, OpenServiceW, QueryServiceStatus, RegisterServiceCtrlHandlerExW,
+    SetServiceStatus, StartServiceCtrlDispatcherW, SC_HANDLE, SC_MANAGER_CONNECT,
+    SC_MANAGER_CREATE_SERVICE, SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_AUTO_START,
+    SERVICE_CHANGE_CONFIG, SERVICE_CONFIG_DESCRIPTION, SERVICE_CONTROL_INTERROGATE,
+    SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_DESCRIPTIONW, SERVICE_ERROR_NORMAL,
+    SERVICE_QUERY_STATUS, SERVICE_RUNNING, SERVICE_START_PENDING, SERVICE_STATUS, SERVICE_STOP,
+    SERVICE_STOPPED, SERVICE_STOP_PENDING, SERVICE_TABLE_ENTRYW, SERVICE_WIN32_OWN_PROCESS,
+};
+
+const DISPLAY_NAME: &str = "Sonic Wave";
+const DESCRIPTION: &str = "Sonic Wave static file server";
+// 标准访问权限 DELETE（winnt.h）
+const DELETE: u32 = 0x0001_0000;
+// uninstall 等待服务停止的最长时间
+const STOP_TIMEOUT: Duration = Duration::from_secs(60);
+
+// service_main 在 SCM 创建的线程上运行，通过这些全局状态与分派线程、控制回调交换信息
+static SERVICE_NAME: OnceLock<String> = OnceLock::new();
+static RUNTIME: OnceLock<tokio::runtime::Handle> = OnceLock::new();
+static STATUS_HANDLE: AtomicPtr<c_void> = AtomicPtr::new(std::ptr::null_mut());
+static CHECKPOINT: AtomicU32 = AtomicU32::new(0);
+// 停止请求里告诉 SCM 的预计耗时（毫秒），按关闭配置计算
+static STOP_WAIT_HINT: AtomicU32 = AtomicU32::new(0);
+static STOP: Notify = Notify::const_new();
+
+fn wide(s: impl AsRef<OsStr>) -> Vec<u16> {
+    s.as_ref().encode_wide().chain(std::iter::once(0)).collect()
+}
+
+// 服务命令行中的参数加引号；结尾的反斜杠需要成对，否则会转义收尾的引号
+fn quote(path: &Path) -> String {
+    let mut arg = path.display().to_string();
+    if arg.ends_with('\\') {
+        arg.push('\\');
+    }
+    format!("\"{}\"", arg)
+}
+
+// SCM 句柄，离开作用域时关闭
+struct ScHandle(SC_HANDLE);
+
+impl ScHandle {
+    fn new(handle: SC_HANDLE) -> io::Result<Self> {
+        if handle.is_null() {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(ScHandle(handle))
+        }
+    }
+}
+
+impl Drop for ScHandle {
+    fn drop(&mut self) {
+        // SAFETY: 句柄由 OpenSCManagerW / OpenServiceW / CreateServiceW 返回且只关闭一次
+        unsafe { CloseServiceHandle(self.0) };
+    }
+}
+
+fn open_manager(access: u32) -> io::Result<ScHandle> {
+    // SAFETY: 空指针表示本机与默认的服务数据库
+    ScHandle::new(unsafe { OpenSCManagerW(std::ptr::null(), std::ptr::null(), access) })
+}
+
+fn bool_result(ret: i32) -> io::Result<()> {
+    if ret == 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+pub fn run(args: ServiceArgs) {
+    let result = match args.action {
+        ServiceAction::Install(args) => install(args),
+        ServiceAction::Run(args) => dispatch(args),
+        ServiceAction::Uninstall(args) => uninstall(args),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn install(args: ServiceInstallArgs) -> Result<(), String> {
+    let name = &args.service.name;
+    let exe =
+        std::env::current_exe().map_err(|e| format!("cannot locate the executable: {}", e))?;
+    // 服务以 System32 为工作目录启动，因此记录绝对路径并在 service run 中切换过去
+    let dir = match &args.dir {
+        Some(dir) => std::path::absolute(dir),
+        None => std::env::current_dir(),
+    }
+    .map_err(|e| format!("cannot resolve the service directory: {}", e))?;
+    if !dir.join("config.toml").is_file() {
+        eprintln!(
+            "warning: {} has no config.toml, the service will use the defaults",
+            dir.display()
+        );
+    }
+    let command = format!(
+        "{} service run --name \"{}\" --dir {}",
+        quote(&exe),
+        name,
+        quote(&dir)
+    );
+
+    let manager = open_manager(SC_MANAGER_CREATE_SERVICE)
+        .map_err(|e| format!("cannot open the service control manager: {}", e))?;
+    let service_name = wide(name);
+    let display_name = wide(DISPLAY_NAME);
+    let command_line = wide(&command);
+    // SAFETY: 字符串均以 0 结尾且在调用期间有效；账号为空表示 LocalSystem
+    let service = unsafe {
+        CreateServiceW(
+            manager.0,
+            service_name.as_ptr(),
+            display_name.as_ptr(),
+            SERVICE_CHANGE_CONFIG,
+            SERVICE_WIN32_OWN_PROCESS,
+            SERVICE_AUTO_START,
+            SERVICE_ERROR_NORMAL,
+            command_line.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            std::ptr::null(),
+            std::ptr::null(),
+        )
+    };
+    let service = ScHandle::new(service).map_err(|e| match e.raw_os_error() {
+        Some(code) if code as u32 == ERROR_SERVICE_EXISTS => {
+            format!("service `{}` is already installed", name)
+        }
+        _ => format!("failed to create service `{}`: {}", name, e),
+    })?;
+
+    let mut description = wide(DESCRIPTION);
+    let info = SERVICE_DESCRIPTIONW {
+        lpDescription: description.as_mut_ptr(),
+    };
+    // SAFETY: info 与其中的字符串在调用期间有效；描述只是附加信息，失败时忽略
+    unsafe {
+        ChangeServiceConfig2W(
+            service.0,
+            SERVICE_CONFIG_DESCRIPTION,
+            &info as *const SERVICE_DESCRIPTIONW as *const c_void,
+        )
+    };
+    println!("Installed service `{}` serving {}", name, dir.display());
+    println!("Start it with: sc start {}", name);
+    Ok(())
+}
+
+fn uninstall(args: ServiceNameArgs) -> Result<(), String> {
+    let name = &args.name;
+    let manager = open_manager(SC_MANAGER_CONNECT)
+        .map_err(|e| format!("cannot open the service control manager: {}", e))?;
+    let service_name = wide(name);
+    // SAFETY: 名称以 0 结尾且在调用期间有效
+    let service = unsafe {
+        OpenServiceW(
+            manager.0,
+            service_name.as_ptr(),
+            SERVICE_STOP | SERVICE_QUERY_STATUS | DELETE,
+        )
+    };
+    let service =
+        ScHandle::new(service).map_err(|e| format!("cannot open service `{}`: {}", name, e))?;
+
+    // 先停止正在运行的服务，等它排空退出后再删除
+    let mut status = SERVICE_STATUS::default();
+    // SAFETY: status 在调用期间有效
+    let stopped =
+        bool_result(unsafe { ControlService(service.0, SERVICE_CONTROL_STOP, &mut status) });
+    match stopped {
+        Ok(()) => {
+            println!("Stopping service `{}`...", name);
+            let started = Instant::now();
+            while status.dwCurrentState != SERVICE_STOPPED {
+                if started.elapsed() > STOP_TIMEOUT {
+                    return Err(format!("service `{}` did not stop in time", name));
+                }
+                std::thread::sleep(Duration::from_millis(500));
+                // SAFETY: status 在调用期间有效
+                bool_result(unsafe { QueryServiceStatus(service.0, &mut status) })
+                    .map_err(|e| format!("cannot query service `{}`: {}", name, e))?;
+            }
+        }
+        Err(e) if e.raw_os_error().map(|c| c as u32) == Some(ERROR_SERVICE_NOT_ACTIVE) => {}
+        Err(e) => return Err(format!("failed to stop service `{}`: {}", name, e)),
+    }
+
+    // SAFETY: 句柄带有 DELETE 权限
+    bool_result(unsafe { DeleteService(service.0) })
+        .map_err(|e| format!("failed to delete service `{}`: {}", name, e))?;
+    println!("Uninstalled service `{}`", name);
+    Ok(())
+}
+
+// service run：在当前线程运行 SCM 分派循环直到服务停止；服务本身在 SCM 创建的线程上复用当前的 tokio 运行时
+fn dispatch(args: ServiceRunArgs) -> Result<(), String> {
+    std::env::set_current_dir(&args.dir)
+        .map_err(|e| format!("cannot enter {}: {}", args.dir.display(), e))?;
+    let config = tracing::subscriber::with_default(NoSubscriber::default(), config::load_config);
+    let shutdown = &config.shutdown;
+    let stop_secs = shutdown
+        .hard_kill_secs
+        .unwrap_or(shutdown.readiness_grace_secs + shutdown.drain_timeout_secs)
+        + 5;
+    STOP_WAIT_HINT.store(
+        u32::try_from(stop_secs * 1000).unwrap_or(u32::MAX),
+        Ordering::Relaxed,
+    );
+
+    let _ = SERVICE_NAME.set(args.service.name.clone());
+    let _ = RUNTIME.set(tokio::runtime::Handle::current());
+    let mut service_name = wide(&args.service.name);
+    let table = [
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: service_name.as_mut_ptr(),
+            lpServiceProc: Some(service_main),
+        },
+        SERVICE_TABLE_ENTRYW {
+            lpServiceName: std::ptr::null_mut(),
+            lpServiceProc: None,
+        },
+    ];
+    // SAFETY: 表以空项结尾，分派循环返回之前 service_name 保持有效
+    let ret = unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) };
+    bool_result(ret).map_err(|e| match e.raw_os_error() {
+        Some(code) if code as u32 == ERROR_FAILED_SERVICE_CONTROLLER_CONNECT => {
+            "`service run` is started by the service control manager; use `service install` and `sc start` instead".to_string()
+        }
+        _ => format!("service dispatcher failed: {}", e),
+    })
+}
+
+extern "system" fn service_main(_argc: u32, _argv: *mut PWSTR) {
+    let name = wide(SERVICE_NAME.get().map(String::as_str).unwrap_or_default());
+    // SAFETY: 名称以 0 结尾；回调不使用上下文指针
+    let handle = unsafe {
+        RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(control_handler), std::ptr::null())
+    };
+    if handle.is_null() {
+        error!(
+            "Failed to register the service control handler: {}",
+            io::Error::last_os_error()
+        );
+        return;
+    }
+    STATUS_HANDLE.store(handle, Ordering::Release);
+    set_status(SERVICE_START_PENDING, 3000);
+
+    let Some(runtime) = RUNTIME.get() else {
+        set_status(SERVICE_STOPPED, 0);
+        return;
+    };
+    info!(
+        "Running as Windows service `{}`",
+        SERVICE_NAME.get().map(String::as_str).unwrap_or_default()
+    );
+    set_status(SERVICE_RUNNING, 0);
+    runtime.block_on(crate::server::run(false, None, false));
+    set_status(SERVICE_STOPPED, 0);
+}
+
+extern "system" fn control_handler(
+    control: u32,
+    _event_type: u32,
+    _event_data: *mut c_void,
+    _context: *mut c_void,
+) -> u32 {
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+            set_status(SERVICE_STOP_PENDING, STOP_WAIT_HINT.load(Ordering::Relaxed));
+            STOP.notify_one();
+            NO_ERROR
+        }
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+        _ => ERROR_CALL_NOT_IMPLEMENTED,
+    }
+}
+
+fn set_status(state: u32, wait_hint: u32) {
+    let handle = STATUS_HANDLE.load(Ordering::Acquire);
+    if handle.is_null() {
+        return;
+    }
+    let pending = state == SERVICE_START_PENDING || state == SERVICE_STOP_PENDING;
+    let status = SERVICE_STATUS {
+        dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+        dwCurrentState: state,
+        dwControlsAccepted: if state == SERVICE_RUNNING {
+            SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+        } else {
+            0
+        },
+        dwWin32ExitCode: NO_ERROR,
+        dwServiceSpecificExitCode: 0,
+        dwCheckPoint: if pending {
+            CHECKPOINT.fetch_add(1, Ordering::Relaxed) + 1
+        } else {
+            0
+        },
+        dwWaitHint: wait_hint,
+    };
+    // SAFETY: handle 来自 RegisterServiceCtrlHandlerExW，status 在调用期间有效
+    unsafe { SetServiceStatus(handle, &status) };
+}
+
+// SCM 发来停止或关机请求的时刻，由 shutdown 的信号处理等待
+pub(crate) async fn stop_requested() {
+    STOP.notified().await;
+}
+
+// 事件日志句柄可以在任意线程上使用
+struct EventSource(HANDLE);
+
+// SAFETY: 见上
+unsafe impl Send for EventSource {}
+unsafe impl Sync for EventSource {}
+
+/// 把日志写入应用程序事件日志的 tracing 层，只在 `service run` 时启用
+pub struct EventLog {
+    source: EventSource,
+}
+
+/// `service run` 时返回事件日志层，其他命令返回 None
+pub fn event_log(cli: &Cli) -> Option<EventLog> {
+    let Some(Command::Service(ServiceArgs {
+        action: ServiceAction::Run(args),
+    })) = &cli.command
+    else {
+        return None;
+    };
+    let source_name = wide(&args.service.name);
+    // SAFETY: 名称以 0 结尾；空指针表示本机
+    let source = unsafe { RegisterEventSourceW(std::ptr::null(), source_name.as_ptr()) };
+    (!source.is_null()).then_some(EventLog {
+        source: EventSource(source),
+    })
+}
+
+impl<S: Subscriber> Layer<S> for EventLog {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        let kind = match *event.metadata().level() {
+            Level::ERROR => EVENTLOG_ERROR_TYPE,
+            Level::WARN => EVENTLOG_WARNING_TYPE,
+            _ => EVENTLOG_INFORMATION_TYPE,
+        };
+        let text = wide(&message.0);
+        let strings = [text.as_ptr()];
+        // SAFETY: strings 中的字符串以 0 结尾且在调用期间有效
+        unsafe {
+            ReportEventW(
+                self.source.0,
+                kind,
+                0,
+                0,
+                std::ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                std::ptr::null(),
+            )
+        };
+    }
+}
+
+// 与控制台输出相同：消息在前，其余字段以 key=value 追加
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
@@ -0,0 +1,349 @@
+// Service worker 与离线预缓存（[service_worker]）：按请求列出主目录中匹配的文件及其内容哈希作为预缓存清单，
+// 并提供一个生成的 service worker：安装时缓存清单中的文件，之后优先从缓存返回，离线时导航请求返回 app shell。
+// 文件变化后清单与脚本内容随之变化，浏览器检查更新时安装新版本并清理旧缓存
+use crate::glob::PathPattern;
+use crate::manifest;
+use axum::extract::State;
+use axum::http::header::{self, HeaderValue};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::warn;
+
+// URL 路径段中保留原样的字符
+const SEGMENT_CHARS: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'@')
+    .remove(b'+');
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ServiceWorkerConfig {
+    // 预缓存清单的 URL 路径
+    #[serde(default = "default_manifest")]
+    pub manifest: String,
+    // 生成的 service worker 脚本路径；空字符串表示只提供清单，由应用自己的 service worker 读取
+    #[serde(default = "default_script")]
+    pub script: String,
+    // 预缓存的文件（模式规则与 deny 相同）
+    #[serde(default = "default_include")]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    // 超过该大小的文件不预缓存
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    // 离线时导航请求返回的页面（app shell）；空字符串表示不返回
+    #[serde(default = "default_navigation_fallback")]
+    pub navigation_fallback: String,
+    // 缓存名前缀，激活时删除同一前缀下的旧版本
+    #[serde(default = "default_cache_name")]
+    pub cache_name: String,
+}
+
+fn default_manifest() -> String {
+    "/precache-manifest.json".to_string()
+}
+
+fn default_script() -> String {
+    "/sw.js".to_string()
+}
+
+fn default_include() -> Vec<String> {
+    [
+        "*.html",
+        "*.js",
+        "*.mjs",
+        "*.css",
+        "*.json",
+        "*.webmanifest",
+        "*.svg",
+        "*.png",
+        "*.ico",
+        "*.woff2",
+    ]
+    .iter()
+    .map(|pattern| pattern.to_string())
+    .collect()
+}
+
+fn default_max_file_size() -> u64 {
+    2 * 1024 * 1024
+}
+
+fn default_navigation_fallback() -> String {
+    "/index.html".to_string()
+}
+
+fn default_cache_name() -> String {
+    "sonic-wave".to_string()
+}
+
+#[derive(Serialize)]
+struct Entry {
+    url: String,
+    // 内容摘要，文件变化时改变
+    revision: String,
+}
+
+struct Revision {
+    len: u64,
+    modified: Option<SystemTime>,
+    revision: String,
+}
+
+pub struct ServiceWorker {
+    dir: PathBuf,
+    manifest_path: String,
+    script_path: Option<String>,
+    include: Vec<PathPattern>,
+    exclude: Vec<PathPattern>,
+    max_file_size: u64,
+    navigation_fallback: Option<String>,
+    cache_name: String,
+    revisions: Mutex<HashMap<PathBuf, Revision>>,
+}
+
+impl ServiceWorker {
+    // 站点的 deny 规则同样排除，安装时任一文件请求失败整个预缓存都会失败
+    pub fn new(config: &ServiceWorkerConfig, dir: &Path, deny: &[String]) -> Result<Self, String> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| PathPattern::new(pattern))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        if !config.manifest.starts_with('/') {
+            return Err("[service_worker] manifest must start with /".to_string());
+        }
+        if !(config.script.is_empty() || config.script.starts_with('/')) {
+            return Err("[service_worker] script must start with /".to_string());
+        }
+        if config.cache_name.is_empty() {
+            return Err("[service_worker] cache_name must not be empty".to_string());
+        }
+        Ok(ServiceWorker {
+            dir: dir.to_path_buf(),
+            manifest_path: config.manifest.clone(),
+            script_path: Some(config.script.clone()).filter(|script| !script.is_empty()),
+            include: compile(&config.include)?,
+            exclude: compile(&[&config.exclude[..], deny].concat())?,
+            max_file_size: config.max_file_size,
+            navigation_fallback: Some(config.navigation_fallback.clone())
+                .filter(|fallback| !fallback.is_empty()),
+            cache_name: config.cache_name.clone(),
+            revisions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn manifest_path(&self) -> &str {
+        &self.manifest_path
+    }
+
+    pub fn script_path(&self) -> Option<&str> {
+        self.script_path.as_deref()
+    }
+
+    // 文件大小与修改时间不变时使用上次的摘要
+    fn revision(&self, path: &Path, len: u64, modified: Option<SystemTime>) -> io::Result<String> {
+        if let Some(cached) = self.revisions.lock().unwrap().get(path) {
+            if cached.len == len && cached.modified == modified {
+                return Ok(cached.revision.clone());
+            }
+        }
+        let digest = Sha256::digest(fs::read(path)?);
+        let revision: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        self.revisions.lock().unwrap().insert(
+            path.to_path_buf(),
+            Revision {
+                len,
+                modified,
+                revision: revision.clone(),
+            },
+        );
+        Ok(revision)
+    }
+
+    fn entries(&self) -> io::Result<Vec<Entry>> {
+        let mut files = Vec::new();
+        manifest::collect(&self.dir, &mut files)?;
+        files.sort();
+        let mut entries = Vec::new();
+        let mut seen = HashSet::new();
+        for path in files {
+            let Ok(relative) = path.strip_prefix(&self.dir) else {
+                continue;
+            };
+            let segments: Vec<String> = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            let decoded = format!("/{}", segments.join("/"));
+            if !self.include.iter().any(|p| p.matches(&decoded))
+                || self.exclude.iter().any(|p| p.matches(&decoded))
+            {
+                continue;
+            }
+            let meta = fs::metadata(&path)?;
+            if meta.len() > self.max_file_size {
+                continue;
+            }
+            let revision = self.revision(&path, meta.len(), meta.modified().ok())?;
+            let url: String = segments
+                .iter()
+                .map(|segment| format!("/{}", utf8_percent_encode(segment, SEGMENT_CHARS)))
+                .collect();
+            seen.insert(path);
+            entries.push(Entry { url, revision });
+        }
+        // 删除的文件不再保留摘要
+        self.revisions
+            .lock()
+            .unwrap()
+            .retain(|path, _| seen.contains(path));
+        Ok(entries)
+    }
+
+    fn manifest_json(&self) -> io::Result<String> {
+        serde_json::to_string_pretty(&self.entries()?).map_err(io::Error::other)
+    }
+
+    // 缓存名带上清单的摘要，任一文件变化都会得到新的脚本
+    fn script(&self) -> io::Result<String> {
+        let entries = self.entries()?;
+        let json = serde_json::to_string(&entries).map_err(io::Error::other)?;
+        let digest = Sha256::digest(json.as_bytes());
+        let version: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        let urls: Vec<&str> = entries.iter().map(|entry| entry.url.as_str()).collect();
+        Ok(SCRIPT
+            .replace("__PREFIX__", &literal(&format!("{}-", self.cache_name)))
+            .replace(
+                "__CACHE__",
+                &literal(&format!("{}-{}", self.cache_name, version)),
+            )
+            .replace("__URLS__", &literal(&urls))
+            .replace("__FALLBACK__", &literal(&self.navigation_fallback)))
+    }
+}
+
+// 嵌入脚本的值一律以 JSON 字面量写入
+fn literal<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "null".to_string())
+}
+
+// 导航请求优先走网络，页面保持最新；其余预缓存的资源优先从缓存返回
+const SCRIPT: &str = r#"// Generated by Sonic Wave; regenerated when the site changes
+const PREFIX = __PREFIX__;
+const CACHE = __CACHE__;
+const PRECACHE = __URLS__;
+const FALLBACK = __FALLBACK__;
+const PRECACHED = new Set(PRECACHE);
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(
+    caches
+      .open(CACHE)
+      .then((cache) => cache.addAll(PRECACHE.map((url) => new Request(url, { cache: "reload" }))))
+      .then(() => self.skipWaiting())
+  );
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches
+      .keys()
+      .then((keys) =>
+        Promise.all(
+          keys
+            .filter((key) => key.startsWith(PREFIX) && key !== CACHE)
+            .map((key) => caches.delete(key))
+        )
+      )
+      .then(() => self.clients.claim())
+  );
+});
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+  if (request.method !== "GET") return;
+  const url = new URL(request.url);
+  if (url.origin !== self.location.origin) return;
+  if (request.mode === "navigate") {
+    event.respondWith(
+      fetch(request).catch(() =>
+        caches
+          .open(CACHE)
+          .then((cache) =>
+            cache
+              .match(request, { ignoreSearch: true })
+              .then((hit) => hit || (FALLBACK && cache.match(FALLBACK)) || Response.error())
+          )
+      )
+    );
+    return;
+  }
+  if (PRECACHED.has(url.pathname)) {
+    event.respondWith(
+      caches
+        .open(CACHE)
+        .then((cache) => cache.match(url.pathname).then((hit) => hit || fetch(request)))
+    );
+  }
+});
+"#;
+
+// 每次请求时重新生成，未变化的文件使用缓存的摘要
+async fn respond(
+    worker: Arc<ServiceWorker>,
+    content_type: &'static str,
+    render: fn(&ServiceWorker) -> io::Result<String>,
+) -> Response {
+    let rendered = {
+        let worker = worker.clone();
+        tokio::task::spawn_blocking(move || render(&worker)).await
+    };
+    match rendered {
+        Ok(Ok(body)) => (
+            [
+                (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+                // 浏览器每次检查更新都需要拿到最新内容
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            ],
+            body,
+        )
+            .into_response(),
+        Ok(Err(e)) => {
+            warn!(
+                "Failed to generate precache manifest for {}: {}",
+                worker.dir.display(),
+                e
+            );
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+pub async fn manifest(State(worker): State<Arc<ServiceWorker>>) -> Response {
+    respond(worker, "application/json", ServiceWorker::manifest_json).await
+}
+
+pub async fn script(State(worker): State<Arc<ServiceWorker>>) -> Response {
+    respond(
+        worker,
+        "text/javascript; charset=utf-8",
+        ServiceWorker::script,
+    )
+    .await
+}
@@ -0,0 +1,273 @@
+// 流量镜像（[shadow]）：按比例把收到的请求复制一份异步发给另一个地址，不等待、丢弃其响应，
+// 可以用真实流量验证新的部署或 CDN 配置。只复制没有请求体的请求，镜像失败或超时不影响原请求
+use crate::chaos;
+use crate::forwarded::{self, ClientInfo};
+use crate::glob::PathPattern;
+use crate::proxy::strip_hop_by_hop;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderName, HeaderValue};
+use axum::http::{Method, Uri};
+use axum::middleware::Next;
+use axum::response::Response;
+use futures_util::StreamExt;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ShadowConfig {
+    // 镜像地址，如 "http://staging.internal:8089"；原请求的路径与查询串附加在其路径之后
+    pub url: String,
+    // 镜像的请求比例，0.0 ~ 1.0
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    // 镜像的方法；带请求体的请求一律不镜像
+    #[serde(default = "default_methods")]
+    pub methods: Vec<String>,
+    // 镜像的路径前缀
+    #[serde(default = "default_prefixes")]
+    pub prefixes: Vec<String>,
+    // 前缀下不镜像的路径（模式规则与 deny 相同）
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_secs: u64,
+    // 同时进行的镜像请求上限，镜像地址变慢时超出的请求直接丢弃，不会堆积
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+fn default_methods() -> Vec<String> {
+    vec!["GET".to_string(), "HEAD".to_string()]
+}
+
+fn default_prefixes() -> Vec<String> {
+    vec!["/".to_string()]
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+
+fn default_max_in_flight() -> usize {
+    100
+}
+
+type HttpClient = Client<HttpsConnector<HttpConnector>, Body>;
+
+pub struct Shadow {
+    url: Uri,
+    // 镜像地址的路径部分，去掉末尾的 /
+    base_path: String,
+    sample_rate: f64,
+    methods: Vec<Method>,
+    prefixes: Vec<String>,
+    exclude: Vec<PathPattern>,
+    timeout: Duration,
+    max_in_flight: usize,
+    in_flight: AtomicUsize,
+    client: HttpClient,
+}
+
+// 镜像请求结束（含超时）时释放名额
+struct Slot(Arc<Shadow>);
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Shadow {
+    pub fn new(config: &ShadowConfig) -> Result<Self, String> {
+        let url = config
+            .url
+            .parse::<Uri>()
+            .map_err(|e| format!("[shadow] invalid url `{}`: {}", config.url, e))?;
+        if !matches!(url.scheme_str(), Some("http" | "https")) || url.host().is_none() {
+            return Err(format!(
+                "[shadow] url `{}` must be an http:// or https:// URL",
+                config.url
+            ));
+        }
+        if url.query().is_some() {
+            return Err(format!(
+                "[shadow] url `{}` must not contain a query string",
+                config.url
+            ));
+        }
+        if !(0.0..=1.0).contains(&config.sample_rate) {
+            return Err("[shadow] sample_rate must be between 0.0 and 1.0".to_string());
+        }
+        let methods = config
+            .methods
+            .iter()
+            .map(|method| {
+                Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                    .map_err(|_| format!("[shadow] invalid method `{}`", method))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let exclude = config
+            .exclude
+            .iter()
+            .map(|pattern| PathPattern::new(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        connector.set_connect_timeout(Some(Duration::from_secs(5)));
+        let https = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_webpki_roots()
+            .https_or_http()
+            .enable_http1()
+            .wrap_connector(connector);
+        Ok(Shadow {
+            base_path: url.path().trim_end_matches('/').to_string(),
+            url,
+            sample_rate: config.sample_rate,
+            methods,
+            prefixes: config.prefixes.clone(),
+            exclude,
+            timeout: Duration::from_secs(config.timeout_secs.max(1)),
+            max_in_flight: config.max_in_flight.max(1),
+            in_flight: AtomicUsize::new(0),
+            client: Client::builder(TokioExecutor::new()).build(https),
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{:.0}% of requests to {}",
+            self.sample_rate * 100.0,
+            self.url
+        )
+    }
+
+    fn selected(&self, req: &Request) -> bool {
+        let path = req.uri().path();
+        if !self.methods.contains(req.method())
+            || !self.prefixes.iter().any(|prefix| path.starts_with(prefix))
+        {
+            return false;
+        }
+        // 有 Content-Length 且不为 0，或者分块传输的请求带有请求体
+        let has_body = req.headers().contains_key(header::TRANSFER_ENCODING)
+            || req
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .is_some_and(|v| v.as_bytes() != b"0");
+        if has_body {
+            return false;
+        }
+        let decoded = percent_decode_str(path).decode_utf8_lossy();
+        if self.exclude.iter().any(|pattern| pattern.matches(&decoded)) {
+            return false;
+        }
+        self.sample_rate >= 1.0 || chaos::random() < self.sample_rate
+    }
+
+    // 复制请求：原始请求头（逐跳头除外），Host 换成镜像地址，原来的主机与客户端放在 X-Forwarded-* 中
+    fn copy(&self, req: &Request) -> Result<Request, axum::http::Error> {
+        let path_and_query = req
+            .uri()
+            .path_and_query()
+            .map(|pq| pq.as_str())
+            .unwrap_or("/");
+        let mut uri = self.url.clone().into_parts();
+        uri.path_and_query = Some(format!("{}{}", self.base_path, path_and_query).parse()?);
+        let mut headers = req.headers().clone();
+        strip_hop_by_hop(&mut headers);
+        for name in [
+            header::HOST,
+            header::CONTENT_LENGTH,
+            HeaderName::from_static("x-forwarded-for"),
+        ] {
+            headers.remove(name);
+        }
+        let info = req.extensions().get::<ClientInfo>();
+        let origin = forwarded::origin(req.headers(), info);
+        let client = info.and_then(|info| info.ip).map(|ip| ip.to_string());
+        let values = [
+            (
+                "x-forwarded-host",
+                origin.split_once("://").map(|(_, host)| host),
+            ),
+            (
+                "x-forwarded-proto",
+                Some(info.map_or("http", |info| info.scheme)),
+            ),
+            ("x-forwarded-for", client.as_deref()),
+        ];
+        for (name, value) in values {
+            if let Some(value) = value.and_then(|v| HeaderValue::from_str(v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+
+        let mut copy = Request::builder()
+            .method(req.method().clone())
+            .uri(Uri::from_parts(uri)?)
+            .body(Body::empty())?;
+        *copy.headers_mut() = headers;
+        Ok(copy)
+    }
+
+    fn acquire(self: &Arc<Self>) -> Option<Slot> {
+        if self.in_flight.fetch_add(1, Ordering::Relaxed) >= self.max_in_flight {
+            self.in_flight.fetch_sub(1, Ordering::Relaxed);
+            return None;
+        }
+        Some(Slot(self.clone()))
+    }
+
+    fn send(self: &Arc<Self>, copy: Request) {
+        let Some(slot) = self.acquire() else {
+            debug!(
+                "Shadow request to {} dropped: too many in flight",
+                copy.uri()
+            );
+            return;
+        };
+        let shadow = self.clone();
+        tokio::spawn(async move {
+            let _slot = slot;
+            let method = copy.method().clone();
+            let uri = copy.uri().clone();
+            // 读完响应体，连接可以复用
+            let exchange = async {
+                let response = shadow.client.request(copy).await?;
+                let status = response.status();
+                let mut body = Body::new(response.into_body()).into_data_stream();
+                while body.next().await.transpose()?.is_some() {}
+                Ok::<_, Box<dyn std::error::Error + Send + Sync>>(status)
+            };
+            match tokio::time::timeout(shadow.timeout, exchange).await {
+                Ok(Ok(status)) => debug!("Shadow {} {} -> {}", method, uri, status.as_u16()),
+                Ok(Err(e)) => debug!("Shadow {} {} failed: {}", method, uri, e),
+                Err(_) => debug!("Shadow {} {} timed out", method, uri),
+            }
+        });
+    }
+}
+
+pub async fn mirror(State(shadow): State<Arc<Shadow>>, req: Request, next: Next) -> Response {
+    if shadow.selected(&req) {
+        match shadow.copy(&req) {
+            Ok(copy) => shadow.send(copy),
+            Err(e) => debug!("Failed to build shadow request: {}", e),
+        }
+    }
+    next.run(req).await
+}
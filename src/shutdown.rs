@@ -0,0 +1,205 @@
+// 优雅关闭：信号处理、就绪状态、排空超时与强制退出
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ShutdownConfig {
+    // 收到停止信号后等待在途请求完成的最长秒数，0 表示无限等待
+    #[serde(default = "default_drain_timeout")]
+    pub drain_timeout_secs: u64,
+    // 收到信号后立即让就绪检查返回 503
+    #[serde(default = "default_fail_readiness")]
+    pub fail_readiness: bool,
+    // 就绪检查失败后继续接受新连接的秒数，留给负载均衡器摘除实例
+    #[serde(default)]
+    pub readiness_grace_secs: u64,
+    // 从收到信号起的强制退出期限（秒），防止进程卡死
+    #[serde(default)]
+    pub hard_kill_secs: Option<u64>,
+    // 就绪检查路径
+    #[serde(default = "default_readiness_path")]
+    pub readiness_path: String,
+}
+
+fn default_drain_timeout() -> u64 {
+    30
+}
+
+fn default_fail_readiness() -> bool {
+    true
+}
+
+fn default_readiness_path() -> String {
+    "/__ready".to_string()
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            drain_timeout_secs: default_drain_timeout(),
+            fail_readiness: default_fail_readiness(),
+            readiness_grace_secs: 0,
+            hard_kill_secs: None,
+            readiness_path: default_readiness_path(),
+        }
+    }
+}
+
+// 就绪状态，关闭流程开始后变为 false
+#[derive(Clone)]
+pub struct Readiness(Arc<AtomicBool>);
+
+impl Readiness {
+    pub fn new() -> Self {
+        Readiness(Arc::new(AtomicBool::new(true)))
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub fn set_not_ready(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+pub async fn readiness_handler(
+    axum::extract::State(readiness): axum::extract::State<Readiness>,
+) -> impl IntoResponse {
+    if readiness.is_ready() {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "draining")
+    }
+}
+
+// 关闭协调器：停止接受连接的通知 + 排空期限
+pub struct Shutdown {
+    config: ShutdownConfig,
+    readiness: Readiness,
+    stop_tx: watch::Sender<bool>,
+    drain_tx: watch::Sender<bool>,
+}
+
+impl Shutdown {
+    pub fn new(config: ShutdownConfig, readiness: Readiness) -> Self {
+        let (stop_tx, _) = watch::channel(false);
+        let (drain_tx, _) = watch::channel(false);
+        Shutdown {
+            config,
+            readiness,
+            stop_tx,
+            drain_tx,
+        }
+    }
+
+    // 服务器停止接受新连接的时刻
+    pub fn stopped(&self) -> impl Future<Output = ()> + Send + 'static {
+        wait_for(self.stop_tx.subscribe())
+    }
+
+    // 排空期限到达的时刻（之后剩余连接将被丢弃）
+    pub fn drain_deadline(&self) -> impl Future<Output = ()> + Send + 'static {
+        wait_for(self.drain_tx.subscribe())
+    }
+
+    // 等待停止信号并驱动整个关闭时间线
+    pub async fn run(self) {
+        shutdown_signal().await;
+
+        #[cfg(unix)]
+        crate::systemd::notify_stopping();
+
+        if let Some(secs) = self.config.hard_kill_secs {
+            // 独立线程计时，即使异步运行时卡住也能退出
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_secs(secs));
+                eprintln!("Hard-kill deadline of {}s reached, exiting", secs);
+                std::process::exit(1);
+            });
+        }
+
+        if self.config.fail_readiness {
+            self.readiness.set_not_ready();
+            info!("Readiness check now failing");
+        }
+
+        if self.config.readiness_grace_secs > 0 {
+            info!(
+                "Still accepting connections for {}s before draining",
+                self.config.readiness_grace_secs
+            );
+            tokio::time::sleep(Duration::from_secs(self.config.readiness_grace_secs)).await;
+        }
+
+        self.readiness.set_not_ready();
+        let _ = self.stop_tx.send(true);
+
+        if self.config.drain_timeout_secs == 0 {
+            info!("Draining in-flight requests (no timeout)");
+            return;
+        }
+
+        info!(
+            "Draining in-flight requests (timeout {}s)",
+            self.config.drain_timeout_secs
+        );
+        tokio::time::sleep(Duration::from_secs(self.config.drain_timeout_secs)).await;
+        warn!("Drain timeout reached, dropping remaining connections");
+        let _ = self.drain_tx.send(true);
+    }
+}
+
+async fn wait_for(mut rx: watch::Receiver<bool>) {
+    // 发送端被丢弃时（例如无限排空）永远不会触发
+    if rx.wait_for(|v| *v).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+#[cfg(unix)]
+const TERMINATE: &str = "SIGTERM";
+#[cfg(not(unix))]
+const TERMINATE: &str = "service stop request";
+
+async fn shutdown_signal() {
+    use tokio::signal;
+
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    // 作为 Windows 服务运行时由 SCM 的停止与关机请求触发
+    #[cfg(windows)]
+    let terminate = crate::service::stop_requested();
+
+    #[cfg(not(any(unix, windows)))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {
+            info!("Received Ctrl+C, shutting down gracefully...");
+        },
+        _ = terminate => {
+            info!("Received {}, shutting down gracefully...", TERMINATE);
+        },
+    }
+}
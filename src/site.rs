@@ -0,0 +1,1753 @@
+// 静态站点服务：ServeDir 外加安全响应头（默认 COOP/COEP）、缓存策略、认证与自定义响应头；主目录、[[mount]] 挂载点与虚拟主机共用
+use crate::archive;
+use crate::audio;
+use crate::audio_meta::{self, AudioMeta};
+use crate::basic_auth::{self, BasicAuth, BasicAuthConfig};
+use crate::cache::{CacheService, FileCache, NegativeCache};
+use crate::conditional;
+use crate::dir_overrides::{self, DirOverrides, DirOverridesConfig, IndexFiles};
+use crate::early_hints::{self, EarlyHints};
+use crate::error_pages::{self, ErrorPages};
+use crate::fingerprint::Fingerprint;
+use crate::forwarded::{self, ClientInfo};
+use crate::glob::PathPattern;
+use crate::hls::{self, Hls};
+use crate::i18n::{self, I18n};
+use crate::image_variants;
+use crate::images::{self, Images};
+use crate::index::{EtagService, FileIndex};
+use crate::layers::{CacheControlLayer, CachePolicy};
+use crate::listing::{self, ListingEntry};
+use crate::live_reload::{Change, ChangeHub};
+use crate::markdown::{self, Markdown};
+use crate::memfs::{MemoryFs, MemoryService};
+use crate::metrics::METRICS;
+use crate::mime::MimeTable;
+use crate::mmap::MmapService;
+use crate::path_match::PathMatch;
+use crate::playlist::{self, PlaylistConfig, PlaylistEntry};
+use crate::ranges;
+use crate::runtime_env::{self, RuntimeEnv};
+use crate::s3::{S3Service, S3Store};
+use crate::security_headers::{self, SecurityHeaders, SecurityHeadersConfig};
+use crate::sri::{self, Sri};
+use crate::ssi::{self, Ssi};
+use crate::templates::{self, Templates};
+use crate::uring::{UringReader, UringService};
+use crate::vary;
+use crate::waveform::{self, Waveform};
+use crate::webdav::{self, DavEntry, Depth};
+use crate::zip_download::{self, ZipDownloadConfig, ZipEntry};
+use axum::body::Body;
+use axum::extract::{OriginalUri, Request, State};
+use axum::http::header::{self, HeaderName, HeaderValue};
+use axum::http::{Response, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect};
+use axum::routing::get_service;
+use axum::Router;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tower::{Service, ServiceBuilder};
+use tower_http::services::{ServeDir, ServeFile};
+use tracing::{debug, warn};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct MountConfig {
+    // URL 前缀，如 "/docs"
+    pub prefix: String,
+    // 对应的目录
+    pub dir: String,
+    #[serde(flatten)]
+    pub site: SiteOverrides,
+}
+
+// 按路径 glob 指定 Cache-Control，按顺序匹配，第一条匹配的规则生效
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct CacheRule {
+    // 路径模式，规则与 download 相同，如 "/sw.js"、"*.json"、"/assets/**/*.[hash].js"
+    pub pattern: String,
+    pub cache_control: String,
+}
+
+// 挂载点 / 虚拟主机可覆盖的站点选项，未设置的沿用全局值
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct SiteOverrides {
+    // 静态资源 / HTML 缓存策略
+    #[serde(default)]
+    pub cache_control: Option<String>,
+    #[serde(default)]
+    pub html_cache_control: Option<String>,
+    // 缓存规则，优先于全局规则匹配
+    #[serde(default)]
+    pub cache_rules: Vec<CacheRule>,
+    // 额外的响应头（覆盖同名头）
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    // 目录索引文件名，按顺序查找；空列表表示不解析索引文件
+    #[serde(default)]
+    pub index_files: Option<Vec<String>>,
+    // 找不到文件时返回的文件，如 SPA 的 "index.html"
+    #[serde(default)]
+    pub fallback: Option<String>,
+    // 错误页（相对站点目录），如 { 404 = "404.html", "5xx" = "50x.html" }
+    #[serde(default)]
+    pub error_pages: Option<BTreeMap<String, String>>,
+    // /about 对应 about.html
+    #[serde(default)]
+    pub clean_urls: Option<bool>,
+    // 开启 clean_urls 时将 /about.html 重定向到 /about
+    #[serde(default)]
+    pub clean_urls_redirect: Option<bool>,
+    // 目录与页面路径末尾斜杠的规范形式
+    #[serde(default)]
+    pub trailing_slash: Option<TrailingSlash>,
+    // 允许访问以 "." 开头的文件与目录（.well-known 始终允许）
+    #[serde(default)]
+    pub allow_dotfiles: Option<bool>,
+    // 额外拒绝访问的路径 glob，追加到全局列表之后
+    #[serde(default)]
+    pub deny: Vec<String>,
+    // 符号链接策略
+    #[serde(default)]
+    pub follow_symlinks: Option<FollowSymlinks>,
+    // 以附件形式下载的路径 glob，追加到全局列表之后
+    #[serde(default)]
+    pub download: Vec<String>,
+    // 安全响应头，逐项覆盖全局设置
+    #[serde(default)]
+    pub security_headers: SecurityHeadersConfig,
+    // Basic 认证，替代全局设置；enabled = false 时不需要认证
+    #[serde(default)]
+    pub auth: Option<BasicAuthConfig>,
+}
+
+// never：路径中出现符号链接即拒绝；same-root：解析真实路径，不允许指向站点目录之外；always：不检查
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum FollowSymlinks {
+    Never,
+    #[default]
+    SameRoot,
+    Always,
+}
+
+// 末尾斜杠策略：redirect-add 目录带斜杠、页面不带；redirect-strip 一律不带；serve-both 不跳转
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrailingSlash {
+    #[default]
+    RedirectAdd,
+    RedirectStrip,
+    ServeBoth,
+}
+
+// 单个站点目录的响应策略
+#[derive(Clone)]
+pub struct SiteOptions {
+    pub cache_control: String,
+    pub html_cache_control: String,
+    // 按路径匹配的缓存规则，未匹配时按 HTML / 静态资源区分
+    pub cache_rules: Vec<CacheRule>,
+    // 带内容哈希的文件使用长期缓存，其余文件一律使用 html_cache_control
+    pub fingerprint: Option<Arc<Fingerprint>>,
+    pub headers: Arc<Vec<(HeaderName, HeaderValue)>>,
+    // 目录索引文件名，默认 ["index.html"]
+    pub index_files: Vec<String>,
+    // 找不到文件时返回的文件（相对站点目录），如 SPA 的 "index.html"
+    pub fallback: Option<String>,
+    pub error_pages: BTreeMap<String, String>,
+    pub clean_urls: bool,
+    pub clean_urls_redirect: bool,
+    pub trailing_slash: TrailingSlash,
+    pub allow_dotfiles: bool,
+    pub deny: Vec<String>,
+    pub follow_symlinks: FollowSymlinks,
+    // 按扩展名覆盖 Content-Type
+    pub mime: Arc<MimeTable>,
+    pub download: Vec<String>,
+    // 热点文件内存缓存，所有站点共用
+    pub cache: Option<Arc<FileCache>>,
+    // 不小于该大小（字节）的文件以内存映射方式发送
+    pub mmap_min_size: Option<u64>,
+    // io_backend = "uring" 且可用时的 io_uring 读取线程
+    pub uring: Option<Arc<UringReader>>,
+    // 启动时预建目录索引
+    pub preindex: bool,
+    // 404 结果的缓存时间（秒）
+    pub negative_cache_secs: Option<u64>,
+    // 返回预压缩的旁路文件
+    pub precompressed: bool,
+    // --watch 模式下的文件变化通知，用于刷新索引与 404 缓存
+    pub changes: Option<Arc<ChangeHub>>,
+    // 响应只读 WebDAV 请求（OPTIONS / PROPFIND）
+    pub webdav: bool,
+    // 没有索引文件的目录返回文件列表
+    pub directory_listing: bool,
+    // 目录打包下载（?download=zip）
+    pub zip_download: Option<Arc<ZipDownloadConfig>>,
+    // 目录的播放列表（/dir/playlist.m3u）
+    pub playlist: Option<Arc<PlaylistConfig>>,
+    // 将 .md 渲染为 HTML
+    pub markdown: Option<Arc<Markdown>>,
+    // 服务端包含
+    pub ssi: Option<Arc<Ssi>>,
+    // 向 HTML 注入运行时环境变量
+    pub runtime_env: Option<Arc<RuntimeEnv>>,
+    // 渲染 .hbs / .tera 模板
+    pub templates: Option<Arc<Templates>>,
+    // 按 Accept-Language 选择语言目录或带语言后缀的文件
+    pub i18n: Option<Arc<I18n>>,
+    // 图片按需缩放（?w=&h=&q=）
+    pub images: Option<Arc<Images>>,
+    // 按 Accept 返回 .avif / .webp 旁路文件
+    pub image_variants: bool,
+    // 音频元数据（?meta=json）
+    pub audio_meta: Option<Arc<AudioMeta>>,
+    // 波形峰值（?waveform=json）
+    pub waveform: Option<Arc<Waveform>>,
+    // 音频的 HLS 分段（?hls）
+    pub hls: Option<Arc<Hls>>,
+    // HTML 页面的 103 Early Hints
+    pub early_hints: Option<Arc<EarlyHints>>,
+    // 为 HTML 中的脚本与样式表添加 integrity 属性
+    pub sri: Option<Arc<Sri>>,
+    // 目录内的覆盖文件（.sonicwave.toml）
+    pub dir_overrides: Option<Arc<DirOverridesConfig>>,
+    // 不区分大小写 / Unicode 规范等价的路径查找
+    pub path_match: Option<PathMatch>,
+    // COOP/COEP、HSTS、CSP 等安全响应头
+    pub security_headers: SecurityHeadersConfig,
+    // 整个站点的 Basic 认证
+    pub auth: Option<BasicAuthConfig>,
+}
+
+pub fn default_index_files() -> Vec<String> {
+    vec!["index.html".to_string()]
+}
+
+// 常见的敏感文件，dotfile 规则之外默认拒绝
+pub fn default_deny() -> Vec<String> {
+    vec!["*.pem".to_string(), "*.key".to_string()]
+}
+
+impl SiteOptions {
+    pub fn new(cache_control: String, html_cache_control: String) -> Self {
+        SiteOptions {
+            cache_control,
+            html_cache_control,
+            cache_rules: Vec::new(),
+            fingerprint: None,
+            headers: Arc::new(Vec::new()),
+            index_files: default_index_files(),
+            fallback: None,
+            error_pages: BTreeMap::new(),
+            clean_urls: false,
+            clean_urls_redirect: false,
+            trailing_slash: TrailingSlash::default(),
+            allow_dotfiles: false,
+            deny: default_deny(),
+            follow_symlinks: FollowSymlinks::default(),
+            mime: Arc::new(MimeTable::default()),
+            download: Vec::new(),
+            cache: None,
+            mmap_min_size: None,
+            uring: None,
+            preindex: false,
+            negative_cache_secs: None,
+            precompressed: false,
+            changes: None,
+            webdav: false,
+            directory_listing: false,
+            zip_download: None,
+            playlist: None,
+            markdown: None,
+            ssi: None,
+            runtime_env: None,
+            templates: None,
+            i18n: None,
+            images: None,
+            image_variants: false,
+            audio_meta: None,
+            waveform: None,
+            hls: None,
+            early_hints: None,
+            sri: None,
+            dir_overrides: None,
+            path_match: None,
+            security_headers: SecurityHeadersConfig::default(),
+            auth: None,
+        }
+    }
+
+    // 以全局选项为默认值，应用挂载点 / 虚拟主机自己的覆盖项
+    pub fn with_overrides(&self, overrides: &SiteOverrides) -> Result<Self, String> {
+        Ok(SiteOptions {
+            cache_control: overrides
+                .cache_control
+                .clone()
+                .unwrap_or_else(|| self.cache_control.clone()),
+            html_cache_control: overrides
+                .html_cache_control
+                .clone()
+                .unwrap_or_else(|| self.html_cache_control.clone()),
+            cache_rules: overrides
+                .cache_rules
+                .iter()
+                .chain(&self.cache_rules)
+                .cloned()
+                .collect(),
+            fingerprint: self.fingerprint.clone(),
+            headers: Arc::new(parse_headers(&overrides.headers)?),
+            index_files: overrides
+                .index_files
+                .clone()
+                .unwrap_or_else(|| self.index_files.clone()),
+            fallback: overrides.fallback.clone().or_else(|| self.fallback.clone()),
+            error_pages: overrides
+                .error_pages
+                .clone()
+                .unwrap_or_else(|| self.error_pages.clone()),
+            clean_urls: overrides.clean_urls.unwrap_or(self.clean_urls),
+            clean_urls_redirect: overrides
+                .clean_urls_redirect
+                .unwrap_or(self.clean_urls_redirect),
+            trailing_slash: overrides.trailing_slash.unwrap_or(self.trailing_slash),
+            allow_dotfiles: overrides.allow_dotfiles.unwrap_or(self.allow_dotfiles),
+            deny: self.deny.iter().chain(&overrides.deny).cloned().collect(),
+            follow_symlinks: overrides.follow_symlinks.unwrap_or(self.follow_symlinks),
+            mime: self.mime.clone(),
+            download: self
+                .download
+                .iter()
+                .chain(&overrides.download)
+                .cloned()
+                .collect(),
+            cache: self.cache.clone(),
+            mmap_min_size: self.mmap_min_size,
+            uring: self.uring.clone(),
+            preindex: self.preindex,
+            negative_cache_secs: self.negative_cache_secs,
+            precompressed: self.precompressed,
+            changes: self.changes.clone(),
+            webdav: self.webdav,
+            directory_listing: self.directory_listing,
+            zip_download: self.zip_download.clone(),
+            playlist: self.playlist.clone(),
+            markdown: self.markdown.clone(),
+            ssi: self.ssi.clone(),
+            runtime_env: self.runtime_env.clone(),
+            templates: self.templates.clone(),
+            i18n: self.i18n.clone(),
+            images: self.images.clone(),
+            image_variants: self.image_variants,
+            audio_meta: self.audio_meta.clone(),
+            waveform: self.waveform.clone(),
+            hls: self.hls.clone(),
+            early_hints: self.early_hints.clone(),
+            sri: self.sri.clone(),
+            dir_overrides: self.dir_overrides.clone(),
+            path_match: self.path_match,
+            security_headers: self.security_headers.merged(&overrides.security_headers),
+            auth: overrides.auth.clone().or_else(|| self.auth.clone()),
+        })
+    }
+}
+
+pub fn parse_headers(
+    headers: &BTreeMap<String, String>,
+) -> Result<Vec<(HeaderName, HeaderValue)>, String> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("invalid header name `{}`", name))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| format!("invalid value for header `{}`", name))?;
+            Ok((name, value))
+        })
+        .collect()
+}
+
+// 自定义中间件：覆盖 Content-Type、强制下载，并附加自定义响应头（可覆盖 Cache-Control）
+#[derive(Clone)]
+struct SiteHeadersService<S> {
+    inner: S,
+    options: SiteOptions,
+    // 强制下载的路径
+    download: Arc<Vec<PathPattern>>,
+}
+
+impl<S> Service<Request<Body>> for SiteHeadersService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let path = req.uri().path().to_string();
+        let options = self.options.clone();
+        let download = self.download.clone();
+
+        Box::pin(async move {
+            let mut response = inner.call(req).await?;
+
+            let status = response.status();
+            if status.is_success() {
+                let current = response.headers().get(header::CONTENT_TYPE);
+                if let Some(mime) = options.mime.content_type(&path, current) {
+                    response.headers_mut().insert(header::CONTENT_TYPE, mime);
+                }
+                if let Some(disposition) = attachment(&path, &download) {
+                    response
+                        .headers_mut()
+                        .insert(header::CONTENT_DISPOSITION, disposition);
+                }
+                // 同一 URL 可能返回不同编码的旁路文件
+                if options.precompressed {
+                    vary::add(response.headers_mut(), "Accept-Encoding");
+                }
+            }
+            for (name, value) in options.headers.iter() {
+                response.headers_mut().insert(name.clone(), value.clone());
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<PathPattern>, String> {
+    patterns
+        .iter()
+        .map(|pattern| PathPattern::new(pattern))
+        .collect()
+}
+
+// 站点的缓存规则与带哈希文件名的设置合并为一个策略
+fn cache_policy(options: &SiteOptions) -> Result<CachePolicy, String> {
+    let mut policy = CachePolicy::new(&options.cache_control, &options.html_cache_control)?;
+    for rule in &options.cache_rules {
+        policy = policy.rule(&rule.pattern, &rule.cache_control)?;
+    }
+    Ok(policy.with_fingerprint(options.fingerprint.clone()))
+}
+
+// 构建一个目录的静态文件服务，添加 COOP/COEP headers 和动态缓存策略
+pub fn router(dir: &str, options: SiteOptions) -> Result<Router, String> {
+    // 归档文件（.zip / .tar / .tar.gz / .tar.zst）按内存中的站点提供
+    if archive::is_archive(dir) {
+        let files = archive::load(dir).map_err(|e| format!("failed to load {}: {}", dir, e))?;
+        return memory_router(Arc::new(files), options);
+    }
+    // 索引文件由 resolve 解析，ServeDir 不再自动追加 index.html
+    let mut serve_dir = ServeDir::new(dir).append_index_html_on_directories(false);
+    if options.precompressed {
+        serve_dir = serve_dir
+            .precompressed_br()
+            .precompressed_zstd()
+            .precompressed_gzip();
+    }
+    let index = if options.preindex {
+        let index = FileIndex::build(Path::new(dir), options.follow_symlinks)
+            .map_err(|e| format!("failed to index {}: {}", dir, e))?;
+        Some(Arc::new(index))
+    } else {
+        None
+    };
+    let resolver = Arc::new(Resolver::new(Path::new(dir), &options, index.clone())?);
+    if let Some(changes) = &options.changes {
+        spawn_invalidation(changes.subscribe(), resolver.clone());
+    }
+    let error_pages = ErrorPages::new(Path::new(dir), &options.error_pages)?;
+    let download = compile_patterns(&options.download)?;
+    let policy = cache_policy(&options)?;
+    let dir_overrides = options
+        .dir_overrides
+        .as_ref()
+        .map(|config| Arc::new(DirOverrides::new(config, Path::new(dir))));
+    let access = options.clone();
+    let router = match &options.fallback {
+        Some(fallback) => {
+            let fallback = ServeFile::new(Path::new(dir).join(fallback));
+            let service = get_service(serve_dir.fallback(fallback));
+            with_file_services(options, dir, download, policy, index, service)
+        }
+        None => with_file_services(
+            options,
+            dir,
+            download,
+            policy,
+            index,
+            get_service(serve_dir),
+        ),
+    };
+    let router = with_resolver(router, resolver, error_pages);
+    // 认证先于 WebDAV、打包下载等所有处理，响应头覆盖错误页在内的最终响应
+    let router = match dir_overrides {
+        Some(dirs) => router.layer(axum::middleware::from_fn_with_state(
+            dirs,
+            dir_overrides::apply,
+        )),
+        None => router,
+    };
+    with_access(router, &access)
+}
+
+// 内存中的站点（内嵌资源）：路径解析只使用其索引，文件由 MemoryService 返回
+pub fn memory_router(files: Arc<MemoryFs>, mut options: SiteOptions) -> Result<Router, String> {
+    // 内存中没有旁路文件；WebDAV 与 directory_listing 目录列表、打包下载、播放列表、服务端包含与音频元数据、波形、HLS 分段、
+    // Early Hints、integrity 属性、路径匹配策略只支持磁盘上的站点
+    options.precompressed = false;
+    options.webdav = false;
+    options.directory_listing = false;
+    options.zip_download = None;
+    options.playlist = None;
+    options.ssi = None;
+    options.audio_meta = None;
+    options.waveform = None;
+    options.hls = None;
+    options.early_hints = None;
+    options.sri = None;
+    options.dir_overrides = None;
+    options.path_match = None;
+    let index = Arc::new(files.index());
+    let resolver = Arc::new(Resolver::new(files.root(), &options, Some(index.clone()))?);
+    let error_pages =
+        ErrorPages::new(files.root(), &options.error_pages)?.with_files(files.clone());
+    let download = compile_patterns(&options.download)?;
+    let policy = cache_policy(&options)?;
+    let service = MemoryService::new(files, options.fallback.as_deref());
+    let service = EtagService::new(service, Some(index));
+    let access = options.clone();
+    let router = with_headers(options, download, policy, service);
+    with_access(with_resolver(router, resolver, error_pages), &access)
+}
+
+// 对象存储中的站点：路径解析向存储查询对象是否存在，文件由 S3Service 取回
+pub fn s3_router(store: Arc<S3Store>, mut options: SiteOptions) -> Result<Router, String> {
+    // 对象存储中不查找旁路文件，也无法预建索引
+    options.precompressed = false;
+    options.webdav = false;
+    options.directory_listing = false;
+    options.zip_download = None;
+    options.playlist = None;
+    options.ssi = None;
+    options.audio_meta = None;
+    options.waveform = None;
+    options.hls = None;
+    options.early_hints = None;
+    options.sri = None;
+    options.dir_overrides = None;
+    options.path_match = None;
+    let mut resolver = Resolver::new(store.root(), &options, None)?;
+    resolver.remote = Some(store.clone());
+    let error_pages =
+        ErrorPages::new(store.root(), &options.error_pages)?.with_store(store.clone());
+    let download = compile_patterns(&options.download)?;
+    let policy = cache_policy(&options)?;
+    let service = S3Service::new(store, options.fallback.as_deref());
+    let access = options.clone();
+    let router = with_headers(options, download, policy, service);
+    with_access(
+        with_resolver(router, Arc::new(resolver), error_pages),
+        &access,
+    )
+}
+
+// 站点最外层：安全响应头与 Basic 认证，401 响应同样带安全响应头；
+// 目录覆盖文件中的 auth 只能在此之上追加认证，不能关闭站点的认证
+fn with_access(router: Router, options: &SiteOptions) -> Result<Router, String> {
+    let router = match options.auth.as_ref().map(BasicAuth::new).transpose()? {
+        Some(Some(auth)) => router.layer(axum::middleware::from_fn_with_state(
+            Arc::new(auth),
+            basic_auth::check,
+        )),
+        _ => router,
+    };
+    let security = SecurityHeaders::new(&options.security_headers)?;
+    Ok(if security.is_empty() {
+        router
+    } else {
+        router.layer(axum::middleware::from_fn_with_state(
+            Arc::new(security),
+            security_headers::apply,
+        ))
+    })
+}
+
+fn with_resolver(router: Router, resolver: Arc<Resolver>, error_pages: ErrorPages) -> Router {
+    // 多段 Range 在最内层转换，外层的渲染、缩放等处理会去掉 Range，看到的是完整请求
+    let router = router.layer(axum::middleware::from_fn(ranges::multipart));
+    let router = match resolver.images.clone() {
+        Some(images) => router.layer(axum::middleware::from_fn_with_state(images, resize_image)),
+        None => router,
+    };
+    let router = if resolver.audio_meta.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            read_audio_meta,
+        ))
+    } else {
+        router
+    };
+    let router = if resolver.waveform.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            audio_waveform,
+        ))
+    } else {
+        router
+    };
+    let router = if resolver.hls.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            audio_segments,
+        ))
+    } else {
+        router
+    };
+    let router = if resolver.ssi.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            process_includes,
+        ))
+    } else {
+        router
+    };
+    let router = if resolver.markdown.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            render_markdown,
+        ))
+    } else {
+        router
+    };
+    let router = if resolver.templates.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            render_template,
+        ))
+    } else {
+        router
+    };
+    // 在服务端包含与 Markdown 渲染之后注入，生成的页面同样可以使用占位符
+    let router = match resolver.runtime_env.clone() {
+        Some(env) => router.layer(axum::middleware::from_fn_with_state(
+            env,
+            runtime_env::inject,
+        )),
+        None => router,
+    };
+    let router = if resolver.image_variants {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            negotiate_image,
+        ))
+    } else {
+        router
+    };
+    let router = if resolver.early_hints.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            send_early_hints,
+        ))
+    } else {
+        router
+    };
+    // 语言后缀在索引文件改写之后选择，服务端包含等处理的是选中的文件
+    let router = if resolver.i18n.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            localize_file,
+        ))
+    } else {
+        router
+    };
+    // 紧贴在 resolve 之内：找到索引文件的目录已被改写，到这里的目录路径都没有索引文件
+    let router = if resolver.directory_listing {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            list_directory,
+        ))
+    } else {
+        router
+    };
+    // 错误页在最外层，resolve 拒绝访问时同样返回错误页
+    let router = router.layer(axum::middleware::from_fn_with_state(
+        resolver.clone(),
+        resolve,
+    ));
+    // 在 resolve 之外，相对 URL 按浏览器请求的页面地址解析
+    let router = if resolver.sri.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            add_integrity,
+        ))
+    } else {
+        router
+    };
+    let router = if resolver.i18n.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            localize_tree,
+        ))
+    } else {
+        router
+    };
+    // WebDAV 请求在路径改写之前处理，目录本身不会被改写为索引文件
+    let router = if resolver.webdav {
+        router.layer(axum::middleware::from_fn_with_state(resolver.clone(), dav))
+    } else {
+        router
+    };
+    let router = if resolver.zip_download.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver.clone(),
+            zip_directory,
+        ))
+    } else {
+        router
+    };
+    let router = if resolver.playlist.is_some() {
+        router.layer(axum::middleware::from_fn_with_state(
+            resolver,
+            serve_playlist,
+        ))
+    } else {
+        router
+    };
+    // 前提条件按各项处理之后的最终响应判断
+    let router = router.layer(axum::middleware::from_fn(conditional::evaluate));
+    router.layer(axum::middleware::from_fn_with_state(
+        Arc::new(error_pages),
+        error_pages::replace,
+    ))
+}
+
+// 文件变化后重建索引、清空 404 缓存
+fn spawn_invalidation(mut changes: broadcast::Receiver<Change>, resolver: Arc<Resolver>) {
+    tokio::spawn(async move {
+        loop {
+            match changes.recv().await {
+                Ok(Change::Shutdown) | Err(broadcast::error::RecvError::Closed) => break,
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+            }
+            if let Some(negative) = &resolver.negative {
+                negative.clear();
+            }
+            if let Some(index) = resolver.index.clone() {
+                let refreshed = tokio::task::spawn_blocking(move || index.refresh()).await;
+                if let Ok(Err(e)) = refreshed {
+                    warn!(
+                        "Failed to refresh index of {}: {}",
+                        resolver.dir.display(),
+                        e
+                    );
+                }
+            }
+        }
+    });
+}
+
+fn with_file_services<S>(
+    options: SiteOptions,
+    dir: &str,
+    download: Vec<PathPattern>,
+    policy: CachePolicy,
+    index: Option<Arc<FileIndex>>,
+    service: S,
+) -> Router
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = std::convert::Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    // 小文件走内存缓存，大文件走内存映射，其余文件按 io_backend 读取
+    let precompressed = options.precompressed;
+    let service = UringService::new(service, options.uring.clone(), dir, precompressed);
+    let service = MmapService::new(service, options.mmap_min_size, dir, precompressed);
+    let service = CacheService::new(service, options.cache.clone(), dir);
+    let service = EtagService::new(service, index);
+    with_headers(options, download, policy, service)
+}
+
+fn with_headers<S>(
+    options: SiteOptions,
+    download: Vec<PathPattern>,
+    policy: CachePolicy,
+    service: S,
+) -> Router
+where
+    S: Service<Request<Body>, Response = Response<Body>, Error = std::convert::Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+{
+    let download = Arc::new(download);
+    Router::new().fallback_service(
+        ServiceBuilder::new()
+            .layer(tower::layer::layer_fn(move |service| SiteHeadersService {
+                inner: service,
+                options: options.clone(),
+                download: download.clone(),
+            }))
+            .layer(CacheControlLayer::new(policy))
+            .service(service),
+    )
+}
+
+// RFC 6266 / RFC 5987 attr-char 之外的字符需要编码
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+// 匹配下载规则时生成 Content-Disposition: attachment，文件名取路径最后一段
+fn attachment(path: &str, download: &[PathPattern]) -> Option<HeaderValue> {
+    let decoded = percent_decode_str(path).decode_utf8().ok()?;
+    if !download.iter().any(|pattern| pattern.matches(&decoded)) {
+        return None;
+    }
+    let name = decoded.rsplit('/').next().filter(|name| !name.is_empty())?;
+    content_disposition(name)
+}
+
+// Content-Disposition: attachment，附带 RFC 5987 编码的文件名
+pub fn content_disposition(name: &str) -> Option<HeaderValue> {
+    // 旧客户端使用的 ASCII 回退文件名
+    let fallback: String = name
+        .chars()
+        .map(|c| {
+            if (c.is_ascii_graphic() || c == ' ') && c != '"' && c != '\\' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let value = format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback,
+        utf8_percent_encode(name, ATTR_CHAR)
+    );
+    HeaderValue::from_str(&value).ok()
+}
+
+// 在交给 ServeDir 之前，将请求路径解析为实际文件（自定义索引文件、clean URL），并拒绝敏感路径
+pub struct Resolver {
+    dir: PathBuf,
+    index_files: Vec<String>,
+    clean_urls: bool,
+    clean_urls_redirect: bool,
+    trailing_slash: TrailingSlash,
+    allow_dotfiles: bool,
+    deny: Vec<PathPattern>,
+    follow_symlinks: FollowSymlinks,
+    // same-root 模式下站点目录的真实路径
+    canonical_root: Option<PathBuf>,
+    // 预建索引，存在时不再访问文件系统
+    index: Option<Arc<FileIndex>>,
+    has_fallback: bool,
+    // 404 结果缓存
+    negative: Option<NegativeCache>,
+    // 对象存储后端，存在时向存储查询路径
+    remote: Option<Arc<S3Store>>,
+    webdav: bool,
+    directory_listing: bool,
+    zip_download: Option<Arc<ZipDownloadConfig>>,
+    playlist: Option<Arc<PlaylistConfig>>,
+    markdown: Option<Arc<Markdown>>,
+    ssi: Option<Arc<Ssi>>,
+    runtime_env: Option<Arc<RuntimeEnv>>,
+    templates: Option<Arc<Templates>>,
+    i18n: Option<Arc<I18n>>,
+    images: Option<Arc<Images>>,
+    image_variants: bool,
+    audio_meta: Option<Arc<AudioMeta>>,
+    waveform: Option<Arc<Waveform>>,
+    hls: Option<Arc<Hls>>,
+    early_hints: Option<Arc<EarlyHints>>,
+    sri: Option<Arc<Sri>>,
+    html_cache_control: String,
+    path_match: Option<PathMatch>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    File,
+    Dir,
+}
+
+impl Resolver {
+    fn new(
+        dir: &Path,
+        options: &SiteOptions,
+        index: Option<Arc<FileIndex>>,
+    ) -> Result<Self, String> {
+        Ok(Resolver {
+            dir: dir.to_path_buf(),
+            index_files: options.index_files.clone(),
+            clean_urls: options.clean_urls,
+            clean_urls_redirect: options.clean_urls_redirect,
+            trailing_slash: options.trailing_slash,
+            allow_dotfiles: options.allow_dotfiles,
+            deny: compile_patterns(&options.deny)?,
+            follow_symlinks: options.follow_symlinks,
+            canonical_root: std::fs::canonicalize(dir).ok(),
+            index,
+            has_fallback: options.fallback.is_some(),
+            negative: options
+                .negative_cache_secs
+                .filter(|secs| *secs > 0)
+                .map(|secs| NegativeCache::new(Duration::from_secs(secs))),
+            remote: None,
+            webdav: options.webdav,
+            directory_listing: options.directory_listing,
+            zip_download: options.zip_download.clone(),
+            playlist: options.playlist.clone(),
+            markdown: options.markdown.clone(),
+            ssi: options.ssi.clone(),
+            runtime_env: options.runtime_env.clone(),
+            templates: options.templates.clone(),
+            i18n: options.i18n.clone(),
+            images: options.images.clone(),
+            image_variants: options.image_variants,
+            audio_meta: options.audio_meta.clone(),
+            waveform: options.waveform.clone(),
+            hls: options.hls.clone(),
+            early_hints: options.early_hints.clone(),
+            sri: options.sri.clone(),
+            html_cache_control: options.html_cache_control.clone(),
+            path_match: options.path_match,
+        })
+    }
+
+    async fn kind(&self, path: &Path) -> Option<Kind> {
+        let is_dir = match (&self.index, &self.remote) {
+            (Some(index), _) => index.get(path)?.is_dir,
+            (None, Some(remote)) => remote.is_dir(path).await?,
+            (None, None) => tokio::fs::metadata(path).await.ok()?.is_dir(),
+        };
+        Some(if is_dir { Kind::Dir } else { Kind::File })
+    }
+
+    async fn match_path(&self, path: &str) -> Option<String> {
+        let path_match = self.path_match?;
+        let dir = self.dir.clone();
+        let path = path.to_string();
+        tokio::task::spawn_blocking(move || path_match.find(&dir, &path))
+            .await
+            .ok()?
+    }
+
+    async fn is_file(&self, path: &Path) -> bool {
+        self.kind(path).await == Some(Kind::File)
+    }
+
+    // 按 follow_symlinks 策略检查最终要返回的文件
+    async fn symlink_allowed(&self, target: &Path) -> bool {
+        if let Some(index) = &self.index {
+            return index.get(target).is_none_or(|entry| entry.allowed);
+        }
+        // 对象存储中没有符号链接
+        if self.remote.is_some() {
+            return true;
+        }
+        match self.follow_symlinks {
+            FollowSymlinks::Always => true,
+            FollowSymlinks::Never => {
+                let Ok(relative) = target.strip_prefix(&self.dir) else {
+                    return false;
+                };
+                let mut current = self.dir.clone();
+                for component in relative.components() {
+                    current.push(component);
+                    match tokio::fs::symlink_metadata(&current).await {
+                        Ok(meta) if meta.file_type().is_symlink() => return false,
+                        Ok(_) => {}
+                        // 不存在的路径交给 ServeDir 返回 404
+                        Err(_) => return true,
+                    }
+                }
+                true
+            }
+            FollowSymlinks::SameRoot => {
+                let Some(root) = &self.canonical_root else {
+                    return true;
+                };
+                match tokio::fs::canonicalize(target).await {
+                    Ok(real) => real.starts_with(root),
+                    Err(_) => true,
+                }
+            }
+        }
+    }
+
+    fn denied(&self, path: &str) -> bool {
+        let Ok(decoded) = percent_decode_str(path).decode_utf8() else {
+            return false;
+        };
+        if !self.allow_dotfiles
+            && decoded
+                .split('/')
+                .any(|segment| segment.starts_with('.') && segment != ".well-known")
+        {
+            return true;
+        }
+        self.deny.iter().any(|pattern| pattern.matches(&decoded))
+    }
+
+    pub fn fs_path(&self, path: &str) -> Option<PathBuf> {
+        fs_path(&self.dir, path)
+    }
+
+    // 路径存在（文件或目录）且允许访问
+    pub async fn exists(&self, path: &str) -> bool {
+        if self.denied(path) {
+            return false;
+        }
+        match self.fs_path(path) {
+            Some(fs_path) => {
+                self.kind(&fs_path).await.is_some() && self.symlink_allowed(&fs_path).await
+            }
+            None => false,
+        }
+    }
+
+    // 服务端包含引用的文件：与直接访问一样遵守拒绝规则与符号链接策略
+    pub async fn include_path(&self, path: &str) -> Option<PathBuf> {
+        if self.denied(path) {
+            return None;
+        }
+        let fs_path = self.fs_path(path)?;
+        if !self.is_file(&fs_path).await || !self.symlink_allowed(&fs_path).await {
+            return None;
+        }
+        Some(fs_path)
+    }
+}
+
+// URL 路径对应的文件系统路径，包含 ".." 等无法安全映射的路径时返回 None
+pub fn fs_path(dir: &Path, path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode_str(path).decode_utf8().ok()?;
+    let mut fs_path = dir.to_path_buf();
+    for segment in decoded.split('/') {
+        if segment.is_empty() || segment == "." {
+            continue;
+        }
+        if segment == ".." || segment.contains('\\') || segment.contains('\0') {
+            return None;
+        }
+        fs_path.push(segment);
+    }
+    Some(fs_path)
+}
+
+// 按路径前缀匹配的规则在规范化后的路径上比较：与 fs_path 一样先解码，合并重复的 / 并去掉 . 与 ..，
+// 编码写法（/%70rivate/）或 /public/../private/ 这样的路径不能绕过前缀
+pub fn normalize_path(path: &str) -> String {
+    let decoded = percent_decode_str(path).decode_utf8_lossy();
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let mut normalized = String::with_capacity(decoded.len());
+    for segment in &segments {
+        normalized.push('/');
+        normalized.push_str(segment);
+    }
+    // 保留目录的结尾斜杠，"/docs/" 这样的前缀仍能匹配目录本身
+    let directory = decoded.ends_with('/') || decoded.ends_with("/.") || decoded.ends_with("/..");
+    if normalized.is_empty() || directory {
+        normalized.push('/');
+    }
+    normalized
+}
+
+async fn resolve(
+    State(resolver): State<Arc<Resolver>>,
+    OriginalUri(original): OriginalUri,
+    mut req: Request,
+    next: Next,
+) -> axum::response::Response {
+    let path = req.uri().path().to_string();
+    if resolver.denied(&path) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if let Some(negative) = &resolver.negative {
+        if negative.contains(&path) {
+            METRICS.record_negative_hit();
+            return StatusCode::NOT_FOUND.into_response();
+        }
+    }
+    let Some(fs_path) = resolver.fs_path(&path) else {
+        return next.run(req).await;
+    };
+    // 跳转地址基于原始 URI 计算，挂载点下同样正确
+    let redirect = |location: String| {
+        let location = match original.query() {
+            Some(query) => format!("{}?{}", location, query),
+            None => location,
+        };
+        (
+            StatusCode::MOVED_PERMANENTLY,
+            [(header::LOCATION, location)],
+        )
+            .into_response()
+    };
+    let add_slash = || format!("{}/", original.path());
+    let strip_slash = || original.path().trim_end_matches('/').to_string();
+    let policy = resolver.trailing_slash;
+
+    let mut path = path;
+    let mut fs_path = fs_path;
+    let mut kind = resolver.kind(&fs_path).await;
+    // 精确路径不存在时按 [path_matching] 查找磁盘上的实际写法
+    if kind.is_none() {
+        if let Some(matched) = resolver.match_path(&path).await {
+            // 实际写法同样要经过拒绝规则，/PRIVATE/x 不能绕过 "/private/**"
+            if resolver.denied(&matched) {
+                return StatusCode::NOT_FOUND.into_response();
+            }
+            if resolver.path_match.is_some_and(|m| m.redirect) {
+                if let Some(prefix) = original.path().strip_suffix(path.as_str()) {
+                    return redirect(format!("{}{}", prefix, matched));
+                }
+            }
+            let uri = match req.uri().query() {
+                Some(query) => format!("{}?{}", matched, query),
+                None => matched.clone(),
+            };
+            if let (Ok(uri), Some(matched_fs)) = (uri.parse(), resolver.fs_path(&matched)) {
+                *req.uri_mut() = uri;
+                kind = resolver.kind(&matched_fs).await;
+                fs_path = matched_fs;
+                path = matched;
+            }
+        }
+    }
+    let mut rewritten = None;
+    if kind == Some(Kind::Dir) {
+        let dir_path = if path.ends_with('/') {
+            if policy == TrailingSlash::RedirectStrip && path != "/" {
+                return redirect(strip_slash());
+            }
+            path.clone()
+        } else {
+            if policy == TrailingSlash::RedirectAdd {
+                return redirect(add_slash());
+            }
+            format!("{}/", path)
+        };
+        // 目录请求改写为第一个存在的索引文件；目录覆盖文件可以替换索引文件列表
+        rewritten = (dir_path != path).then(|| dir_path.clone());
+        let overridden = req.extensions().get::<IndexFiles>().cloned();
+        let index_files = match &overridden {
+            Some(IndexFiles(files)) => files.as_slice(),
+            None => resolver.index_files.as_slice(),
+        };
+        for index in index_files {
+            if resolver.is_file(&fs_path.join(index)).await {
+                rewritten = Some(format!("{}{}", dir_path, index));
+                break;
+            }
+        }
+    } else if resolver.clean_urls {
+        if resolver.clean_urls_redirect && path.ends_with(".html") && kind == Some(Kind::File) {
+            let clean = original.path().trim_end_matches(".html");
+            let clean = match clean.strip_suffix("/index") {
+                Some(dir) if policy == TrailingSlash::RedirectStrip && !dir.is_empty() => {
+                    dir.to_string()
+                }
+                Some(dir) => format!("{}/", dir),
+                None => clean.to_string(),
+            };
+            return redirect(clean);
+        }
+        if kind.is_none() && path != "/" {
+            let mut html = fs_path.into_os_string();
+            html.push(".html");
+            if resolver.is_file(Path::new(&html)).await {
+                let bare = path.trim_end_matches('/');
+                // 页面是文件，除 serve-both 外规范形式都不带末尾斜杠
+                if path.ends_with('/') && policy != TrailingSlash::ServeBoth {
+                    return redirect(strip_slash());
+                }
+                rewritten = Some(format!("{}.html", bare));
+            }
+        }
+    }
+
+    if let Some(rewritten) = rewritten {
+        let rewritten = match req.uri().query() {
+            Some(query) => format!("{}?{}", rewritten, query),
+            None => rewritten,
+        };
+        if let Ok(uri) = rewritten.parse() {
+            *req.uri_mut() = uri;
+        }
+    }
+
+    if let Some(target) = resolver.fs_path(req.uri().path()) {
+        if !resolver.symlink_allowed(&target).await {
+            debug!("Refusing symlinked path {}", target.display());
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        // 索引中不存在的路径直接返回 404（配置了 fallback 时仍交给 ServeDir）
+        if let Some(index) = &resolver.index {
+            if !resolver.has_fallback && index.get(&target).is_none() {
+                return StatusCode::NOT_FOUND.into_response();
+            }
+        }
+    }
+    let response = next.run(req).await;
+    if let Some(negative) = &resolver.negative {
+        if response.status() == StatusCode::NOT_FOUND {
+            negative.insert(path);
+        }
+    }
+    response
+}
+
+// 只读 WebDAV：OPTIONS 声明 DAV 能力，PROPFIND 列出路径本身（Depth: 0）或目录及其子项（Depth: 1）；
+// 与 GET 相同遵守 deny / dotfile / follow_symlinks 规则
+async fn dav(
+    State(resolver): State<Arc<Resolver>>,
+    OriginalUri(original): OriginalUri,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    if req.method() == axum::http::Method::OPTIONS {
+        return webdav::options();
+    }
+    if req.method().as_str() != "PROPFIND" {
+        return next.run(req).await;
+    }
+    let Some(depth) = webdav::depth(req.headers()) else {
+        return webdav::infinite_depth();
+    };
+    let path = req.uri().path();
+    if resolver.denied(path) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Some(fs_path) = resolver.fs_path(path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !resolver.symlink_allowed(&fs_path).await {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Ok(meta) = tokio::fs::metadata(&fs_path).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    // 目录的 href 以 "/" 结尾，客户端据此拼接子项地址
+    let mut href = original.path().to_string();
+    if meta.is_dir() && !href.ends_with('/') {
+        href.push('/');
+    }
+    let name = if fs_path == resolver.dir {
+        String::new()
+    } else {
+        fs_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let mut entries = vec![DavEntry {
+        name,
+        is_dir: meta.is_dir(),
+        len: meta.len(),
+        modified: meta.modified().ok(),
+        href: href.clone(),
+    }];
+    if depth == Depth::One && meta.is_dir() {
+        let mut children = Vec::new();
+        if let Ok(mut items) = tokio::fs::read_dir(&fs_path).await {
+            while let Ok(Some(item)) = items.next_entry().await {
+                let Ok(name) = item.file_name().into_string() else {
+                    continue;
+                };
+                let child = format!(
+                    "{}/{}",
+                    path.trim_end_matches('/'),
+                    webdav::encode_segment(&name)
+                );
+                if resolver.denied(&child) || !resolver.symlink_allowed(&item.path()).await {
+                    continue;
+                }
+                // 失效的符号链接等无法访问的条目不列出
+                let Ok(meta) = tokio::fs::metadata(item.path()).await else {
+                    continue;
+                };
+                children.push(DavEntry::child(&href, &name, meta.is_dir(), &meta));
+            }
+        }
+        children.sort_by(|a, b| a.name.cmp(&b.name));
+        entries.extend(children);
+    }
+    webdav::multistatus(&entries)
+}
+
+// 目录打包下载：GET /dir/?download=zip 返回目录下所有文件的 zip 流，同样遵守 deny / dotfile / follow_symlinks 规则；
+// 文件请求忽略该参数
+async fn zip_directory(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    let Some(config) = resolver.zip_download.clone() else {
+        return next.run(req).await;
+    };
+    let is_head = req.method() == axum::http::Method::HEAD;
+    if !(is_head || req.method() == axum::http::Method::GET)
+        || !zip_download::requested(req.uri().query())
+    {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    if resolver.denied(&path) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Some(fs_path) = resolver.fs_path(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !tokio::fs::metadata(&fs_path)
+        .await
+        .is_ok_and(|meta| meta.is_dir())
+    {
+        return next.run(req).await;
+    }
+    if !resolver.symlink_allowed(&fs_path).await {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    // 先收集文件列表，再在阻塞线程中边读边写
+    let mut entries = Vec::new();
+    let mut total = 0u64;
+    let mut visited = std::collections::HashSet::new();
+    let mut pending = vec![(
+        fs_path.clone(),
+        path.trim_end_matches('/').to_string(),
+        String::new(),
+    )];
+    while let Some((dir, url, prefix)) = pending.pop() {
+        // 符号链接可能形成环，每个真实目录只打包一次
+        if let Ok(real) = tokio::fs::canonicalize(&dir).await {
+            if !visited.insert(real) {
+                continue;
+            }
+        }
+        let Ok(mut items) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(item)) = items.next_entry().await {
+            let Ok(name) = item.file_name().into_string() else {
+                continue;
+            };
+            let child_url = format!("{}/{}", url, webdav::encode_segment(&name));
+            if resolver.denied(&child_url) || !resolver.symlink_allowed(&item.path()).await {
+                continue;
+            }
+            let Ok(meta) = tokio::fs::metadata(item.path()).await else {
+                continue;
+            };
+            let child_name = format!("{}{}", prefix, name);
+            if meta.is_dir() {
+                pending.push((item.path(), child_url, format!("{}/", child_name)));
+                continue;
+            }
+            total += meta.len();
+            entries.push(ZipEntry {
+                name: child_name,
+                path: item.path(),
+                modified: meta.modified().ok(),
+            });
+            if entries.len() > config.max_files || config.max_size.is_some_and(|max| total > max) {
+                warn!(
+                    "Refusing ZIP download of {}: directory exceeds the configured limits",
+                    path
+                );
+                return StatusCode::FORBIDDEN.into_response();
+            }
+        }
+    }
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let name = fs_path
+        .file_name()
+        .filter(|_| fs_path != resolver.dir)
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "site".to_string());
+    let mut response = if is_head {
+        Response::new(Body::empty())
+    } else {
+        Response::new(zip_download::stream(entries, config.compression))
+    };
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/zip"),
+    );
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    if let Some(disposition) = content_disposition(&format!("{}.zip", name)) {
+        headers.insert(header::CONTENT_DISPOSITION, disposition);
+    }
+    response
+}
+
+// 没有索引文件的目录返回文件列表；目录本身的 deny 规则已在 resolve 中检查
+async fn list_directory(
+    State(resolver): State<Arc<Resolver>>,
+    OriginalUri(original): OriginalUri,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    let is_head = req.method() == axum::http::Method::HEAD;
+    if !(is_head || req.method() == axum::http::Method::GET) {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    if !path.ends_with('/') {
+        return next.run(req).await;
+    }
+    let Some(fs_path) = resolver.fs_path(&path) else {
+        return next.run(req).await;
+    };
+    let Ok(mut items) = tokio::fs::read_dir(&fs_path).await else {
+        return next.run(req).await;
+    };
+    let mut entries = Vec::new();
+    while let Ok(Some(item)) = items.next_entry().await {
+        let Ok(name) = item.file_name().into_string() else {
+            continue;
+        };
+        let child_url = format!("{}{}", path, webdav::encode_segment(&name));
+        if resolver.denied(&child_url) || !resolver.symlink_allowed(&item.path()).await {
+            continue;
+        }
+        let Ok(meta) = tokio::fs::metadata(item.path()).await else {
+            continue;
+        };
+        entries.push(ListingEntry {
+            name,
+            is_dir: meta.is_dir(),
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        });
+    }
+    let base = format!("{}/", original.path().trim_end_matches('/'));
+    let response = listing::render(&base, entries);
+    if is_head {
+        let (parts, _) = response.into_parts();
+        return Response::from_parts(parts, Body::empty());
+    }
+    response
+}
+
+// 目录中不存在同名文件时，由目录中的音频文件生成播放列表
+async fn serve_playlist(
+    State(resolver): State<Arc<Resolver>>,
+    original: OriginalUri,
+    client: Option<axum::Extension<ClientInfo>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    let Some(config) = resolver.playlist.clone() else {
+        return next.run(req).await;
+    };
+    let is_head = req.method() == axum::http::Method::HEAD;
+    if !(is_head || req.method() == axum::http::Method::GET) {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    let Some((dir, format)) = config.requested(&path) else {
+        return next.run(req).await;
+    };
+    if resolver.exists(&path).await {
+        return next.run(req).await;
+    }
+    if resolver.denied(dir) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Some(fs_path) = resolver.fs_path(dir) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !tokio::fs::metadata(&fs_path)
+        .await
+        .is_ok_and(|meta| meta.is_dir())
+        || !resolver.symlink_allowed(&fs_path).await
+    {
+        return next.run(req).await;
+    }
+    let (recursive, sort) = config.options(req.uri().query());
+
+    let mut entries = Vec::new();
+    let mut visited = std::collections::HashSet::new();
+    let mut pending = vec![(
+        fs_path,
+        dir.trim_end_matches('/').to_string(),
+        String::new(),
+    )];
+    'walk: while let Some((dir, url, prefix)) = pending.pop() {
+        if let Ok(real) = tokio::fs::canonicalize(&dir).await {
+            if !visited.insert(real) {
+                continue;
+            }
+        }
+        let Ok(mut items) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(item)) = items.next_entry().await {
+            let Ok(name) = item.file_name().into_string() else {
+                continue;
+            };
+            let child_url = format!("{}/{}", url, webdav::encode_segment(&name));
+            if resolver.denied(&child_url) || !resolver.symlink_allowed(&item.path()).await {
+                continue;
+            }
+            let Ok(meta) = tokio::fs::metadata(item.path()).await else {
+                continue;
+            };
+            let child_name = format!("{}{}", prefix, name);
+            if meta.is_dir() {
+                if recursive {
+                    pending.push((item.path(), child_url, format!("{}/", child_name)));
+                }
+                continue;
+            }
+            if audio::content_type(&name).is_none() {
+                continue;
+            }
+            if entries.len() >= config.max_entries {
+                warn!(
+                    "Truncating playlist {} at {} entries",
+                    path, config.max_entries
+                );
+                break 'walk;
+            }
+            entries.push(PlaylistEntry {
+                name: child_name,
+                url: child_url,
+                path: item.path(),
+                len: meta.len(),
+                modified: meta.modified().ok(),
+            });
+        }
+    }
+
+    // 条目使用完整的请求路径，挂载点下同样包含前缀
+    let base = original
+        .path()
+        .strip_suffix(path.as_str())
+        .unwrap_or_default();
+    let origin = format!(
+        "{}{}",
+        forwarded::origin(req.headers(), client.as_deref()),
+        base
+    );
+    let response = playlist::render(format, entries, sort, &origin).await;
+    if is_head {
+        let (parts, _) = response.into_parts();
+        return Response::from_parts(parts, Body::empty());
+    }
+    response
+}
+
+async fn render_markdown(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match &resolver.markdown {
+        Some(md) => markdown::respond(md, &resolver.html_cache_control, req, next).await,
+        None => next.run(req).await,
+    }
+}
+
+async fn process_includes(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match &resolver.ssi {
+        Some(ssi) => ssi::respond(ssi, &resolver, &resolver.html_cache_control, req, next).await,
+        None => next.run(req).await,
+    }
+}
+
+async fn resize_image(
+    State(images): State<Arc<Images>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    images::respond(&images, req, next).await
+}
+
+async fn read_audio_meta(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match &resolver.audio_meta {
+        Some(meta) => {
+            audio_meta::respond(meta, &resolver, &resolver.html_cache_control, req, next).await
+        }
+        None => next.run(req).await,
+    }
+}
+
+async fn audio_waveform(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match &resolver.waveform {
+        Some(peaks) => {
+            waveform::respond(peaks, &resolver, &resolver.html_cache_control, req, next).await
+        }
+        None => next.run(req).await,
+    }
+}
+
+async fn audio_segments(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match &resolver.hls {
+        Some(hls) => hls::respond(hls, &resolver, &resolver.html_cache_control, req, next).await,
+        None => next.run(req).await,
+    }
+}
+
+async fn send_early_hints(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match &resolver.early_hints {
+        Some(hints) => early_hints::respond(hints, &resolver, req, next).await,
+        None => next.run(req).await,
+    }
+}
+
+async fn add_integrity(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match resolver.sri.clone() {
+        Some(sri) => sri::inject(sri, &resolver, req, next).await,
+        None => next.run(req).await,
+    }
+}
+
+async fn negotiate_image(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    image_variants::respond(&resolver, req, next).await
+}
+
+async fn localize_tree(
+    State(resolver): State<Arc<Resolver>>,
+    original: OriginalUri,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match &resolver.i18n {
+        Some(i18n) => i18n::redirect_tree(i18n, &resolver, &original, req, next).await,
+        None => next.run(req).await,
+    }
+}
+
+async fn localize_file(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match &resolver.i18n {
+        Some(i18n) => i18n::negotiate_file(i18n, &resolver, req, next).await,
+        None => next.run(req).await,
+    }
+}
+
+async fn render_template(
+    State(resolver): State<Arc<Resolver>>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match &resolver.templates {
+        Some(t) => templates::respond(t, &resolver.html_cache_control, req, next).await,
+        None => next.run(req).await,
+    }
+}
+
+// 将站点挂载到 URL 前缀下；访问前缀本身时重定向到带 / 的地址，保证页面内相对路径正确
+pub fn mount(app: Router, prefix: &str, site: Router) -> Result<Router, String> {
+    let prefix = prefix.trim_end_matches('/');
+    if !prefix.starts_with('/') {
+        return Err(format!(
+            "mount prefix `{}` must start with '/' and must not be the root",
+            prefix
+        ));
+    }
+    let site = site.layer(axum::middleware::from_fn_with_state(
+        Arc::<str>::from(prefix),
+        redirect_bare_prefix,
+    ));
+    Ok(app.nest_service(prefix, site))
+}
+
+async fn redirect_bare_prefix(
+    State(prefix): State<Arc<str>>,
+    OriginalUri(original): OriginalUri,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    if original.path() == &*prefix {
+        let location = match original.query() {
+            Some(query) => format!("{}/?{}", prefix, query),
+            None => format!("{}/", prefix),
+        };
+        return Redirect::permanent(&location).into_response();
+    }
+    next.run(req).await
+}
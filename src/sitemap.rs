@@ -0,0 +1,321 @@
+// robots.txt 与 sitemap.xml（[robots] / [sitemap]）：robots.txt 按配置的规则生成；
+// sitemap.xml 列出站点目录中的 HTML 页面，lastmod 取文件修改时间，遵守 deny 与 dotfile 规则，
+// 目录中的页面增删或修改后下次请求时重新生成。生成的地址优先于目录中的同名文件
+use crate::config::Config;
+use crate::error_pages::rfc3339;
+use crate::forwarded::{self, ClientInfo};
+use crate::glob::PathPattern;
+use crate::webdav::encode_segment;
+use axum::extract::State;
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+pub const ROBOTS_PATH: &str = "/robots.txt";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct RobotsConfig {
+    // 按 User-agent 分组的规则；为空时允许所有爬虫抓取全部内容
+    #[serde(default)]
+    pub rules: Vec<RobotsRule>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct RobotsRule {
+    #[serde(default = "default_user_agent")]
+    pub user_agent: String,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub disallow: Vec<String>,
+    // 两次抓取之间的秒数（非标准，部分爬虫支持）
+    #[serde(default)]
+    pub crawl_delay: Option<u64>,
+}
+
+fn default_user_agent() -> String {
+    "*".to_string()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SitemapConfig {
+    #[serde(default = "default_sitemap_path")]
+    pub path: String,
+    // 站点地址，如 "https://example.com"；未设置时按请求的 Host 生成绝对地址
+    #[serde(default)]
+    pub link: Option<String>,
+    // 不列出的页面 glob，在 deny 规则之外
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+fn default_sitemap_path() -> String {
+    "/sitemap.xml".to_string()
+}
+
+// 站点地址：优先使用配置，否则按客户端协议与 Host 推断
+fn base_url(link: Option<&str>, headers: &HeaderMap, client: Option<&ClientInfo>) -> String {
+    match link {
+        Some(link) => link.trim_end_matches('/').to_string(),
+        None => forwarded::origin(headers, client),
+    }
+}
+
+pub struct Robots {
+    rules: Vec<RobotsRule>,
+    // 同时配置了 [sitemap] 时在末尾声明 Sitemap
+    sitemap: Option<(Option<String>, String)>,
+}
+
+impl Robots {
+    pub fn new(config: &RobotsConfig, sitemap: Option<&SitemapConfig>) -> Result<Self, String> {
+        for rule in &config.rules {
+            if rule.user_agent.is_empty() || rule.user_agent.contains(['\r', '\n']) {
+                return Err(format!("[robots] invalid user_agent `{}`", rule.user_agent));
+            }
+            for path in rule.allow.iter().chain(&rule.disallow) {
+                if path.contains(['\r', '\n'])
+                    || !(path.is_empty() || path.starts_with('/') || path.starts_with('*'))
+                {
+                    return Err(format!("[robots] path `{}` must start with '/'", path));
+                }
+            }
+        }
+        Ok(Robots {
+            rules: config.rules.clone(),
+            sitemap: sitemap.map(|sitemap| (sitemap.link.clone(), sitemap.path.clone())),
+        })
+    }
+
+    fn render(&self, base: &str) -> String {
+        let mut out = String::new();
+        if self.rules.is_empty() {
+            out.push_str("User-agent: *\nDisallow:\n");
+        }
+        for (i, rule) in self.rules.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.push_str(&format!("User-agent: {}\n", rule.user_agent));
+            for path in &rule.allow {
+                out.push_str(&format!("Allow: {}\n", path));
+            }
+            for path in &rule.disallow {
+                out.push_str(&format!("Disallow: {}\n", path));
+            }
+            // 没有任何规则的分组表示允许全部
+            if rule.allow.is_empty() && rule.disallow.is_empty() {
+                out.push_str("Disallow:\n");
+            }
+            if let Some(delay) = rule.crawl_delay {
+                out.push_str(&format!("Crawl-delay: {}\n", delay));
+            }
+        }
+        if let Some((_, path)) = &self.sitemap {
+            out.push_str(&format!("\nSitemap: {}{}\n", base, path));
+        }
+        out
+    }
+}
+
+pub async fn robots(
+    State(robots): State<Arc<Robots>>,
+    client: Option<Extension<ClientInfo>>,
+    headers: HeaderMap,
+) -> Response {
+    let link = robots
+        .sitemap
+        .as_ref()
+        .and_then(|(link, _)| link.as_deref());
+    let base = base_url(link, &headers, client.as_deref());
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/plain; charset=utf-8"),
+            ),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+        ],
+        robots.render(&base),
+    )
+        .into_response()
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct Page {
+    url: String,
+    modified: SystemTime,
+}
+
+struct Cached {
+    pages: Vec<Page>,
+    base: String,
+    xml: String,
+}
+
+pub struct Sitemap {
+    config: SitemapConfig,
+    dir: PathBuf,
+    index_files: Vec<String>,
+    clean_urls: bool,
+    allow_dotfiles: bool,
+    deny: Vec<PathPattern>,
+    exclude: Vec<PathPattern>,
+    cached: Mutex<Option<Cached>>,
+}
+
+impl Sitemap {
+    pub fn new(sitemap: &SitemapConfig, config: &Config, root: &Path) -> Result<Self, String> {
+        if !sitemap.path.starts_with('/') {
+            return Err(format!(
+                "sitemap path `{}` must start with '/'",
+                sitemap.path
+            ));
+        }
+        let compile = |patterns: &[String]| -> Result<Vec<PathPattern>, String> {
+            patterns.iter().map(|p| PathPattern::new(p)).collect()
+        };
+        Ok(Sitemap {
+            config: sitemap.clone(),
+            dir: root.to_path_buf(),
+            index_files: config.index_files.clone(),
+            clean_urls: config.clean_urls,
+            allow_dotfiles: config.allow_dotfiles,
+            deny: compile(&config.deny)?,
+            exclude: compile(&sitemap.exclude)?,
+            cached: Mutex::new(None),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.config.path
+    }
+
+    // 跳过符号链接，避免目录循环与指向站点之外的文件
+    fn walk(&self, dir: &Path, prefix: &str, decoded: &str, pages: &mut Vec<Page>) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if name.starts_with('.') && !self.allow_dotfiles {
+                continue;
+            }
+            let Ok(kind) = entry.file_type() else {
+                continue;
+            };
+            let url = format!("{}/{}", prefix, encode_segment(&name));
+            let path = format!("{}/{}", decoded, name);
+            if kind.is_dir() {
+                self.walk(&entry.path(), &url, &path, pages);
+                continue;
+            }
+            let is_html = Path::new(&name)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    ext.eq_ignore_ascii_case("html") || ext.eq_ignore_ascii_case("htm")
+                });
+            if !kind.is_file() || !is_html {
+                continue;
+            }
+            // 规则按解码后的路径匹配，与请求一致
+            if self
+                .deny
+                .iter()
+                .chain(&self.exclude)
+                .any(|p| p.matches(&path))
+            {
+                continue;
+            }
+            let url = if self.index_files.contains(&name) {
+                format!("{}/", prefix)
+            } else if self.clean_urls {
+                url.strip_suffix(".html").unwrap_or(&url).to_string()
+            } else {
+                url
+            };
+            let modified = entry
+                .metadata()
+                .and_then(|meta| meta.modified())
+                .unwrap_or(UNIX_EPOCH);
+            pages.push(Page { url, modified });
+        }
+    }
+
+    // 页面列表与请求地址都没有变化时返回缓存的结果
+    fn render(&self, base: &str) -> String {
+        let mut pages = Vec::new();
+        self.walk(&self.dir, "", "", &mut pages);
+        pages.sort_by(|a, b| a.url.cmp(&b.url));
+        pages.dedup_by(|a, b| a.url == b.url);
+        if let Some(cached) = &*self.cached.lock().unwrap() {
+            if cached.pages == pages && cached.base == base {
+                return cached.xml.clone();
+            }
+        }
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+        );
+        for page in &pages {
+            xml.push_str(&format!(
+                "<url><loc>{}</loc><lastmod>{}</lastmod></url>\n",
+                escape(&format!("{}{}", base, page.url)),
+                rfc3339(page.modified)
+            ));
+        }
+        xml.push_str("</urlset>\n");
+        *self.cached.lock().unwrap() = Some(Cached {
+            pages,
+            base: base.to_string(),
+            xml: xml.clone(),
+        });
+        xml
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub async fn sitemap(
+    State(sitemap): State<Arc<Sitemap>>,
+    client: Option<Extension<ClientInfo>>,
+    headers: HeaderMap,
+) -> Response {
+    let base = base_url(sitemap.config.link.as_deref(), &headers, client.as_deref());
+    let rendered = {
+        let sitemap = sitemap.clone();
+        tokio::task::spawn_blocking(move || sitemap.render(&base)).await
+    };
+    match rendered {
+        Ok(xml) => (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/xml; charset=utf-8"),
+                ),
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            ],
+            xml,
+        )
+            .into_response(),
+        Err(_) => {
+            warn!("Failed to generate sitemap {}", sitemap.path());
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
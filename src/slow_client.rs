@@ -0,0 +1,298 @@
+// 慢速客户端防护（[slow_clients]）：请求头读取超时、响应写入超时与最低传输速率，
+// 防止 slowloris 一类的客户端长期占住连接。速率只在等待客户端的时间内计算：
+// 写入被对端阻塞、或处理函数在等待请求体时计时，服务端自己的限速与处理耗时不计入
+use axum::body::Bytes;
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use hyper::body::Incoming;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SlowClientConfig {
+    // 读取完整请求头的最长秒数（HTTP/1），0 表示不限制
+    #[serde(default = "default_header_read_timeout")]
+    pub header_read_timeout_secs: u64,
+    // 响应数据写不出去（客户端不读取）的最长秒数，0 表示不限制
+    #[serde(default = "default_write_timeout")]
+    pub write_timeout_secs: u64,
+    // 最低传输速率（字节/秒），收发任一方向低于该速率时断开连接，0 表示不检查
+    #[serde(default)]
+    pub min_rate: u64,
+    // 计算速率的时间窗口（秒），也是开始检查前的宽限期
+    #[serde(default = "default_min_rate_grace")]
+    pub min_rate_grace_secs: u64,
+}
+
+fn default_header_read_timeout() -> u64 {
+    30
+}
+
+fn default_write_timeout() -> u64 {
+    60
+}
+
+fn default_min_rate_grace() -> u64 {
+    10
+}
+
+impl Default for SlowClientConfig {
+    fn default() -> Self {
+        SlowClientConfig {
+            header_read_timeout_secs: default_header_read_timeout(),
+            write_timeout_secs: default_write_timeout(),
+            min_rate: 0,
+            min_rate_grace_secs: default_min_rate_grace(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub header_read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    min_rate: u64,
+    grace: Duration,
+}
+
+impl Limits {
+    pub fn new(config: &SlowClientConfig) -> Self {
+        let secs = |secs: u64| Some(Duration::from_secs(secs)).filter(|d| !d.is_zero());
+        Limits {
+            header_read_timeout: secs(config.header_read_timeout_secs),
+            write_timeout: secs(config.write_timeout_secs),
+            min_rate: config.min_rate,
+            // 窗口太短时速率波动过大
+            grace: Duration::from_secs(config.min_rate_grace_secs.max(1)),
+        }
+    }
+}
+
+// 记录等待客户端的时间与期间传输的字节数
+struct Meter {
+    timeout: Option<Duration>,
+    min_rate: u64,
+    grace: Duration,
+    // 本次等待的开始时间，用于超时
+    waiting_since: Option<Instant>,
+    // 本次等待中计入当前窗口的起点，窗口可能在等待中途切换
+    window_mark: Option<Instant>,
+    // 当前窗口内已结束的等待时间与传输的字节数
+    window_wait: Duration,
+    window_bytes: u64,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl Meter {
+    fn new(timeout: Option<Duration>, limits: &Limits) -> Self {
+        Meter {
+            timeout,
+            min_rate: limits.min_rate,
+            grace: limits.grace,
+            waiting_since: None,
+            window_mark: None,
+            window_wait: Duration::ZERO,
+            window_bytes: 0,
+            sleep: None,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.timeout.is_some() || self.min_rate > 0
+    }
+
+    fn transferred(&mut self, bytes: usize) {
+        self.waiting_since = None;
+        if let Some(mark) = self.window_mark.take() {
+            self.window_wait += mark.elapsed();
+        }
+        self.window_bytes += bytes as u64;
+    }
+
+    // 操作返回 Pending 时调用：超出限制时返回错误，否则注册下一次检查的定时器
+    fn waiting(&mut self, cx: &mut Context<'_>) -> io::Result<()> {
+        if !self.is_active() {
+            return Ok(());
+        }
+        loop {
+            let now = Instant::now();
+            let since = *self.waiting_since.get_or_insert(now);
+            let mut deadline = None;
+            if let Some(timeout) = self.timeout {
+                if now >= since + timeout {
+                    return Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "client stopped reading the response",
+                    ));
+                }
+                deadline = Some(since + timeout);
+            }
+            if self.min_rate > 0 {
+                let mark = *self.window_mark.get_or_insert(now);
+                let waited = self.window_wait + (now - mark);
+                if waited >= self.grace {
+                    let rate = self.window_bytes as f64 / waited.as_secs_f64();
+                    if rate < self.min_rate as f64 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!("client transfer rate {:.0} B/s below minimum", rate),
+                        ));
+                    }
+                    // 开始新的窗口，超时仍从本次等待开始计算
+                    self.window_wait = Duration::ZERO;
+                    self.window_bytes = 0;
+                    self.window_mark = Some(now);
+                }
+                let mark = self.window_mark.unwrap_or(now);
+                let check = now + self.grace.saturating_sub(self.window_wait + (now - mark));
+                deadline = Some(deadline.map_or(check, |d: Instant| d.min(check)));
+            }
+            let Some(deadline) = deadline else {
+                return Ok(());
+            };
+            let sleep = match &mut self.sleep {
+                Some(sleep) => {
+                    sleep.as_mut().reset(deadline);
+                    sleep
+                }
+                None => self
+                    .sleep
+                    .insert(Box::pin(tokio::time::sleep_until(deadline))),
+            };
+            if sleep.as_mut().poll(cx).is_pending() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// 连接的写入方向：写不出去的时间超过 write_timeout 或速率过低时返回错误，hyper 随即关闭连接
+pub struct Guarded<S> {
+    inner: S,
+    meter: Meter,
+}
+
+impl<S> Guarded<S> {
+    pub fn new(inner: S, limits: &Limits) -> Self {
+        Guarded {
+            inner,
+            meter: Meter::new(limits.write_timeout, limits),
+        }
+    }
+
+    fn track<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<T>>,
+        bytes: impl Fn(&T) -> usize,
+    ) -> Poll<io::Result<T>> {
+        match poll {
+            Poll::Ready(Ok(value)) => {
+                self.meter.transferred(bytes(&value));
+                Poll::Ready(Ok(value))
+            }
+            Poll::Pending => match self.meter.waiting(cx) {
+                Ok(()) => Poll::Pending,
+                Err(e) => Poll::Ready(Err(e)),
+            },
+            other => other,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Guarded<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Guarded<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.track(cx, poll, |n| *n)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.track(cx, poll, |n| *n)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let poll = Pin::new(&mut self.inner).poll_flush(cx);
+        self.track(cx, poll, |_| 0)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+// 请求体：处理函数等待上传数据时计算速率；请求头之后的读取没有单独的超时
+pub struct RequestBody {
+    inner: Incoming,
+    meter: Meter,
+}
+
+impl RequestBody {
+    pub fn new(inner: Incoming, limits: &Limits) -> Self {
+        RequestBody {
+            inner,
+            meter: Meter::new(None, limits),
+        }
+    }
+}
+
+impl HttpBody for RequestBody {
+    type Data = Bytes;
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let this = &mut *self;
+        match Pin::new(&mut this.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => {
+                this.meter
+                    .transferred(frame.data_ref().map_or(0, |data| data.len()));
+                Poll::Ready(Some(Ok(frame)))
+            }
+            Poll::Ready(Some(Err(e))) => Poll::Ready(Some(Err(e.into()))),
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => match this.meter.waiting(cx) {
+                Ok(()) => Poll::Pending,
+                Err(e) => Poll::Ready(Some(Err(e.into()))),
+            },
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
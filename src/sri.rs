@@ -0,0 +1,450 @@
+// Subresource Integrity（[sri]）：为 JS / CSS 计算 sha384 哈希，sri 子命令写入 JSON 清单，
+// [sri] manifest 按请求生成；inject 开启时为返回的 HTML 中同源的 <script src> 与样式表、
+// modulepreload 添加 integrity 属性。哈希按文件大小与修改时间缓存，只支持磁盘上的站点
+use crate::cli::SriArgs;
+use crate::early_hints;
+use crate::index::etag_matches;
+use crate::manifest;
+use crate::site::Resolver;
+use axum::body::Body;
+use axum::extract::{OriginalUri, Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::{debug, warn};
+
+// 默认的清单文件名
+const DEFAULT_NAME: &str = "sri.json";
+
+// 缓存的哈希数上限，超出时清空
+const CACHE_ENTRIES: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SriConfig {
+    // 清单的 URL 路径，如 "/sri.json"；未设置时不提供
+    #[serde(default)]
+    pub manifest: Option<String>,
+    // 为 HTML 中引用的同源资源添加 integrity 属性
+    #[serde(default)]
+    pub inject: bool,
+    // 计算哈希的文件扩展名
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    // 超过该大小的页面按原样返回
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+}
+
+fn default_extensions() -> Vec<String> {
+    vec!["js".to_string(), "mjs".to_string(), "css".to_string()]
+}
+
+fn default_max_size() -> usize {
+    8 * 1024 * 1024
+}
+
+struct Hashed {
+    len: u64,
+    modified: Option<SystemTime>,
+    integrity: String,
+}
+
+pub struct Sri {
+    extensions: Vec<String>,
+    inject: bool,
+    max_size: usize,
+    cache: Mutex<HashMap<PathBuf, Arc<Hashed>>>,
+}
+
+impl Sri {
+    pub fn new(config: &SriConfig) -> Result<Self, String> {
+        if config.extensions.is_empty() {
+            return Err("[sri] extensions must not be empty".to_string());
+        }
+        Ok(Sri {
+            extensions: normalize(&config.extensions),
+            inject: config.inject,
+            max_size: config.max_size,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn injects(&self) -> bool {
+        self.inject
+    }
+
+    fn applies(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|ext| self.extensions.contains(&ext.to_ascii_lowercase()))
+    }
+
+    // 文件大小与修改时间不变时使用缓存的哈希
+    fn integrity(&self, path: &Path) -> io::Result<String> {
+        let meta = fs::metadata(path)?;
+        let modified = meta.modified().ok();
+        let cached = self.cache.lock().unwrap().get(path).cloned();
+        if let Some(hashed) = cached {
+            if hashed.len == meta.len() && hashed.modified == modified {
+                return Ok(hashed.integrity.clone());
+            }
+        }
+        let integrity = manifest::integrity(&fs::read(path)?);
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= CACHE_ENTRIES {
+            cache.clear();
+        }
+        cache.insert(
+            path.to_path_buf(),
+            Arc::new(Hashed {
+                len: meta.len(),
+                modified,
+                integrity: integrity.clone(),
+            }),
+        );
+        Ok(integrity)
+    }
+
+    // 键为相对站点目录的路径（"/" 分隔），如 "assets/app.js"
+    fn manifest(&self, dir: &Path) -> io::Result<BTreeMap<String, String>> {
+        let mut files = Vec::new();
+        manifest::collect(dir, &mut files)?;
+        let mut entries = BTreeMap::new();
+        for path in files.into_iter().filter(|path| self.applies(path)) {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let relative: Vec<String> = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            entries.insert(relative.join("/"), self.integrity(&path)?);
+        }
+        Ok(entries)
+    }
+}
+
+fn normalize(extensions: &[String]) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+fn render(sri: &Sri, dir: &Path) -> io::Result<String> {
+    let manifest = sri.manifest(dir)?;
+    serde_json::to_string_pretty(&manifest).map_err(io::Error::other)
+}
+
+pub fn run(args: SriArgs) {
+    let extensions: Vec<String> = args.extensions.split(',').map(str::to_string).collect();
+    let config = SriConfig {
+        manifest: None,
+        inject: false,
+        extensions,
+        max_size: default_max_size(),
+    };
+    let sri = match Sri::new(&config) {
+        Ok(sri) => sri,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    let json = match render(&sri, &args.dir) {
+        Ok(json) => json,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", args.dir.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let output = args.output.unwrap_or_else(|| args.dir.join(DEFAULT_NAME));
+    if output == Path::new("-") {
+        println!("{}", json);
+        return;
+    }
+    // 先写临时文件再改名，服务中的进程不会读到半个文件
+    let mut temp = output.clone().into_os_string();
+    temp.push(".tmp");
+    if let Err(e) = fs::write(&temp, json).and_then(|_| fs::rename(&temp, &output)) {
+        eprintln!("Failed to write {}: {}", output.display(), e);
+        std::process::exit(1);
+    }
+    println!("Wrote {}", output.display());
+}
+
+pub struct SriRoute {
+    pub dir: PathBuf,
+    pub sri: Arc<Sri>,
+}
+
+// 每次请求时重新生成，未变化的文件使用缓存的哈希
+pub async fn serve(State(route): State<Arc<SriRoute>>) -> Response {
+    let rendered = {
+        let route = route.clone();
+        tokio::task::spawn_blocking(move || render(&route.sri, &route.dir)).await
+    };
+    match rendered {
+        Ok(Ok(json)) => (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_static("application/json"),
+                ),
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            ],
+            json,
+        )
+            .into_response(),
+        Ok(Err(e)) => {
+            warn!(
+                "Failed to generate SRI manifest for {}: {}",
+                route.dir.display(),
+                e
+            );
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+// 需要添加 integrity 的标签：插入位置（">" 或 "/>" 之前）与引用的 URL
+fn candidates(html: &str) -> Vec<(usize, String)> {
+    let lower = html.to_ascii_lowercase();
+    // <base href> 会改变相对 URL 的解析，不去猜测
+    if lower.contains("<base") {
+        return Vec::new();
+    }
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find('<').map(|offset| pos + offset) {
+        if lower[start..].starts_with("<!--") {
+            pos = lower[start..]
+                .find("-->")
+                .map_or(html.len(), |offset| start + offset + 3);
+            continue;
+        }
+        let Some(close) = lower[start..].find('>').map(|offset| start + offset) else {
+            break;
+        };
+        pos = close + 1;
+        let tag = &html[start + 1..close];
+        let name_end = tag
+            .find(|c: char| c.is_ascii_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        let name = tag[..name_end].to_ascii_lowercase();
+        if name != "link" && name != "script" {
+            continue;
+        }
+        // 跳过内联脚本的内容
+        if name == "script" {
+            pos = lower[pos..]
+                .find("</script")
+                .map_or(html.len(), |offset| pos + offset);
+        }
+        let attrs = early_hints::attributes(&tag[name_end..]);
+        let get = |key: &str| {
+            attrs
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str())
+        };
+        if get("integrity").is_some() {
+            continue;
+        }
+        let url = if name == "script" {
+            get("src")
+        } else {
+            let rels: Vec<String> = get("rel")
+                .unwrap_or_default()
+                .split_ascii_whitespace()
+                .map(str::to_ascii_lowercase)
+                .collect();
+            let has = |rel: &str| rels.iter().any(|r| r == rel);
+            let preload = has("preload") && matches!(get("as"), Some("script" | "style"));
+            if has("stylesheet") || has("modulepreload") || preload {
+                get("href")
+            } else {
+                None
+            }
+        };
+        let Some(url) = url.map(str::trim).filter(|url| same_origin(url)) else {
+            continue;
+        };
+        let at = if tag.ends_with('/') { close - 1 } else { close };
+        found.push((at, url.to_string()));
+    }
+    found
+}
+
+// 只处理同源的路径；带协议（https:、data:）或以 "//" 开头的 URL 跳过
+fn same_origin(url: &str) -> bool {
+    if url.is_empty() || url.starts_with("//") || url.contains(['"', '<', '>']) {
+        return false;
+    }
+    let end = url.find(['/', '?', '#']).unwrap_or(url.len());
+    !url[..end].contains(':')
+}
+
+// 按浏览器的规则把 URL 解析为站点内的路径：相对路径基于页面所在目录 base，
+// 挂载点中的绝对路径需要带着挂载前缀 prefix，其余的绝对路径不属于这个站点
+fn join(prefix: &str, base: &str, url: &str) -> Option<String> {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let joined = if url.starts_with('/') {
+        let rest = url.strip_prefix(prefix)?;
+        if !rest.starts_with('/') {
+            return None;
+        }
+        rest.to_string()
+    } else {
+        format!("{}{}", base, url)
+    };
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in joined.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Some(format!("/{}", segments.join("/")))
+}
+
+// 在 resolve 之外执行，看到的是浏览器请求的 URL；SPA 的 fallback 页面、渲染后的 Markdown 同样会处理
+pub async fn inject(sri: Arc<Sri>, resolver: &Resolver, mut req: Request, next: Next) -> Response {
+    if !(req.method() == Method::GET || req.method() == Method::HEAD) {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    let prefix = req
+        .extensions()
+        .get::<OriginalUri>()
+        .and_then(|original| original.path().strip_suffix(path.as_str()))
+        .unwrap_or_default()
+        .to_string();
+    let base = path[..path.rfind('/').map_or(0, |i| i + 1)].to_string();
+    let is_head = req.method() == Method::HEAD;
+    // 需要完整的未压缩页面；条件请求按改写后的结果判断
+    let if_none_match = req.headers_mut().remove(header::IF_NONE_MATCH);
+    for name in [
+        header::RANGE,
+        header::IF_RANGE,
+        header::IF_MODIFIED_SINCE,
+        header::ACCEPT_ENCODING,
+    ] {
+        req.headers_mut().remove(name);
+    }
+    if is_head {
+        *req.method_mut() = Method::GET;
+    }
+    let response = next.run(req).await;
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    let too_large = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| len > sri.max_size);
+    if response.status() != StatusCode::OK
+        || !is_html
+        || too_large
+        || response.headers().contains_key(header::CONTENT_ENCODING)
+    {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, sri.max_size).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Not adding integrity attributes: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let html = String::from_utf8_lossy(&bytes).into_owned();
+
+    let mut inserts = Vec::new();
+    for (at, url) in candidates(&html) {
+        let Some(site_path) = join(&prefix, &base, &url) else {
+            continue;
+        };
+        let Some(fs_path) = resolver.include_path(&site_path).await else {
+            continue;
+        };
+        if !sri.applies(&fs_path) {
+            continue;
+        }
+        let hashed = {
+            let sri = sri.clone();
+            tokio::task::spawn_blocking(move || sri.integrity(&fs_path)).await
+        };
+        match hashed {
+            Ok(Ok(integrity)) => inserts.push((at, integrity)),
+            Ok(Err(e)) => debug!("Failed to hash {}: {}", site_path, e),
+            Err(_) => {}
+        }
+    }
+    let mut rewritten = String::with_capacity(html.len() + inserts.len() * 80);
+    let mut last = 0;
+    for (at, integrity) in &inserts {
+        rewritten.push_str(&html[last..*at]);
+        rewritten.push_str(&format!(" integrity=\"{}\"", integrity));
+        last = *at;
+    }
+    rewritten.push_str(&html[last..]);
+
+    // 引用的资源变化后页面的 ETag 随之变化
+    let validator = [header::ETAG, header::LAST_MODIFIED]
+        .iter()
+        .filter_map(|name| parts.headers.get(name)?.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(";");
+    let mut hasher = Sha256::new();
+    hasher.update(validator.as_bytes());
+    for (_, integrity) in &inserts {
+        hasher.update(integrity.as_bytes());
+    }
+    let hex: String = hasher.finalize()[..8]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let etag = HeaderValue::from_str(&format!("W/\"sri-{}\"", hex)).unwrap();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::ACCEPT_RANGES);
+    parts.headers.remove(header::LAST_MODIFIED);
+    let not_modified = if_none_match
+        .as_ref()
+        .and_then(|tags| tags.to_str().ok())
+        .is_some_and(|tags| etag_matches(tags, &etag));
+    parts.headers.insert(header::ETAG, etag);
+    if not_modified {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_TYPE);
+        return Response::from_parts(parts, Body::empty());
+    }
+    let body = if is_head {
+        parts
+            .headers
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(rewritten.len()));
+        Body::empty()
+    } else {
+        Body::from(rewritten)
+    };
+    Response::from_parts(parts, body)
+}
@@ -0,0 +1,328 @@
+// 服务端包含（[ssi]）：处理 <!--#include virtual="..." --> 与 <!--#include file="..." -->，
+// 让多页面站点无需构建步骤即可共用页头页脚
+use crate::index::etag_matches;
+use crate::site::Resolver;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use percent_encoding::percent_decode_str;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct SsiConfig {
+    // 需要处理的扩展名；普通页面需显式加入 "html"
+    #[serde(default = "default_extensions")]
+    pub extensions: Vec<String>,
+    // 嵌套包含的最大层数
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+    // 超过该大小的页面与片段不处理
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+}
+
+fn default_extensions() -> Vec<String> {
+    vec!["shtml".to_string()]
+}
+
+fn default_max_depth() -> usize {
+    8
+}
+
+fn default_max_size() -> usize {
+    8 * 1024 * 1024
+}
+
+// 与 Apache 默认的 errmsg 一致
+const ERROR_MESSAGE: &str = "[an error occurred while processing this directive]";
+
+// 片段缓存的条目数上限
+const MAX_CACHED_FRAGMENTS: usize = 1024;
+
+// 片段按修改时间与大小校验，文件变化后自动重新读取
+struct Fragment {
+    modified: Option<SystemTime>,
+    len: u64,
+    text: Arc<str>,
+}
+
+// 页面依赖的片段，用于计算 ETag
+struct Dependency {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+pub struct Ssi {
+    extensions: Vec<String>,
+    max_depth: usize,
+    max_size: usize,
+    directive: Regex,
+    attribute: Regex,
+    fragments: Mutex<HashMap<PathBuf, Arc<Fragment>>>,
+}
+
+type Expansion<'a> = Pin<Box<dyn Future<Output = String> + Send + 'a>>;
+
+impl Ssi {
+    pub fn new(config: &SsiConfig) -> Self {
+        Ssi {
+            extensions: config
+                .extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+            max_depth: config.max_depth,
+            max_size: config.max_size,
+            directive: Regex::new(r"(?s)<!--#include\s(.*?)-->").unwrap(),
+            attribute: Regex::new(r#"(\w+)\s*=\s*"([^"]*)""#).unwrap(),
+            fragments: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn applies(&self, path: &str) -> bool {
+        let path = path.to_ascii_lowercase();
+        self.extensions
+            .iter()
+            .any(|ext| path.ends_with(&format!(".{}", ext)))
+    }
+
+    async fn load(&self, path: &Path) -> Option<(Arc<Fragment>, Dependency)> {
+        let meta = tokio::fs::metadata(path).await.ok()?;
+        let modified = meta.modified().ok();
+        let dependency = Dependency {
+            path: path.to_path_buf(),
+            modified,
+            len: meta.len(),
+        };
+        let cached = self.fragments.lock().unwrap().get(path).cloned();
+        if let Some(fragment) = cached {
+            if fragment.modified == modified && fragment.len == meta.len() {
+                return Some((fragment, dependency));
+            }
+        }
+        if meta.len() > self.max_size as u64 {
+            warn!("Not including {}: file too large", path.display());
+            return None;
+        }
+        let bytes = tokio::fs::read(path).await.ok()?;
+        let fragment = Arc::new(Fragment {
+            modified,
+            len: meta.len(),
+            text: String::from_utf8_lossy(&bytes).into(),
+        });
+        let mut fragments = self.fragments.lock().unwrap();
+        if fragments.len() >= MAX_CACHED_FRAGMENTS {
+            fragments.clear();
+        }
+        fragments.insert(path.to_path_buf(), fragment.clone());
+        Some((fragment, dependency))
+    }
+
+    // 展开 text 中的 include 指令；url 为 text 所在文件的站点内路径，用于解析相对路径
+    fn expand<'a>(
+        &'a self,
+        resolver: &'a Resolver,
+        text: &'a str,
+        url: &'a str,
+        stack: &'a mut Vec<PathBuf>,
+        dependencies: &'a mut Vec<Dependency>,
+    ) -> Expansion<'a> {
+        Box::pin(async move {
+            let mut output = String::with_capacity(text.len());
+            let mut last = 0;
+            for captures in self.directive.captures_iter(text) {
+                let all = captures.get(0).unwrap();
+                output.push_str(&text[last..all.start()]);
+                last = all.end();
+                let Some(target) = self.target(&captures[1], url) else {
+                    warn!("Unsupported include directive in {}: {}", url, all.as_str());
+                    output.push_str(ERROR_MESSAGE);
+                    continue;
+                };
+                let Some(path) = resolver.include_path(&target).await else {
+                    warn!("Include {} in {} not found", target, url);
+                    output.push_str(ERROR_MESSAGE);
+                    continue;
+                };
+                if stack.len() > self.max_depth || stack.contains(&path) {
+                    warn!(
+                        "Include {} in {} is recursive or nested too deeply",
+                        target, url
+                    );
+                    output.push_str(ERROR_MESSAGE);
+                    continue;
+                }
+                let Some((fragment, dependency)) = self.load(&path).await else {
+                    output.push_str(ERROR_MESSAGE);
+                    continue;
+                };
+                dependencies.push(dependency);
+                stack.push(path);
+                let expanded = self
+                    .expand(resolver, &fragment.text, &target, stack, dependencies)
+                    .await;
+                stack.pop();
+                output.push_str(&expanded);
+            }
+            output.push_str(&text[last..]);
+            output
+        })
+    }
+
+    // virtual 为站点内的 URL 路径，可以是相对路径；file 只能是相对于当前文件的路径
+    fn target(&self, directive: &str, url: &str) -> Option<String> {
+        let captures = self.attribute.captures(directive)?;
+        let value = captures.get(2)?.as_str();
+        let base = &url[..url.rfind('/').map_or(0, |i| i + 1)];
+        match &captures[1] {
+            "virtual" => {
+                let value = value.split(['?', '#']).next().unwrap_or(value);
+                if value.starts_with('/') {
+                    normalize(value)
+                } else {
+                    normalize(&format!("{}{}", base, value))
+                }
+            }
+            "file" if !value.starts_with('/') => Some(format!("{}{}", base, value)),
+            _ => None,
+        }
+    }
+}
+
+// 展开 "." 与 ".."；越过站点根目录时返回 None
+fn normalize(path: &str) -> Option<String> {
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Some(format!("/{}", segments.join("/")))
+}
+
+// 页面 ETag 由原始响应的校验信息与所有片段的修改时间、大小决定，任一片段变化后缓存即失效
+fn etag(validator: &str, dependencies: &[Dependency]) -> HeaderValue {
+    let mut hasher = Sha256::new();
+    hasher.update(validator.as_bytes());
+    for dependency in dependencies {
+        hasher.update(dependency.path.as_os_str().as_encoded_bytes());
+        let modified = dependency
+            .modified
+            .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map_or(0, |age| age.as_nanos());
+        hasher.update(modified.to_le_bytes());
+        hasher.update(dependency.len.to_le_bytes());
+    }
+    let digest = hasher.finalize();
+    let hex: String = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+    HeaderValue::from_str(&format!("W/\"ssi-{}\"", hex)).unwrap()
+}
+
+// 在 resolve 之后执行，索引文件改写后的路径同样会被处理；结果按 HTML 使用 html_cache_control
+pub async fn respond(
+    ssi: &Ssi,
+    resolver: &Resolver,
+    cache_control: &str,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let is_head = req.method() == Method::HEAD;
+    if !(is_head || req.method() == Method::GET) || !ssi.applies(req.uri().path()) {
+        return next.run(req).await;
+    }
+    let url = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    // 需要完整的页面源文件；条件请求按处理后的结果判断
+    let if_none_match = req.headers_mut().remove(header::IF_NONE_MATCH);
+    for name in [
+        header::RANGE,
+        header::IF_RANGE,
+        header::IF_MODIFIED_SINCE,
+        header::ACCEPT_ENCODING,
+    ] {
+        req.headers_mut().remove(name);
+    }
+    if is_head {
+        *req.method_mut() = Method::GET;
+    }
+    let response = next.run(req).await;
+    let too_large = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| len > ssi.max_size);
+    if response.status() != StatusCode::OK || too_large {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, ssi.max_size).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Not processing includes in {}: {}", url, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let source = String::from_utf8_lossy(&bytes);
+    // 当前页面也在栈中，页面包含自身时同样视为递归
+    let mut stack: Vec<PathBuf> = resolver.fs_path(&url).into_iter().collect();
+    let mut dependencies = Vec::new();
+    let html = ssi
+        .expand(resolver, &source, &url, &mut stack, &mut dependencies)
+        .await;
+
+    let validator = [header::ETAG, header::LAST_MODIFIED]
+        .iter()
+        .filter_map(|name| parts.headers.get(name)?.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(";");
+    let etag = etag(&validator, &dependencies);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::ACCEPT_RANGES);
+    parts.headers.remove(header::LAST_MODIFIED);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    if let Ok(value) = HeaderValue::from_str(cache_control) {
+        parts.headers.insert(header::CACHE_CONTROL, value);
+    }
+    let not_modified = if_none_match
+        .as_ref()
+        .and_then(|tags| tags.to_str().ok())
+        .is_some_and(|tags| etag_matches(tags, &etag));
+    parts.headers.insert(header::ETAG, etag);
+    if not_modified {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_TYPE);
+        return Response::from_parts(parts, Body::empty());
+    }
+    let body = if is_head {
+        parts
+            .headers
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(html.len()));
+        Body::empty()
+    } else {
+        Body::from(html)
+    };
+    Response::from_parts(parts, body)
+}
@@ -0,0 +1,361 @@
+// 状态页（[status]）：Basic 认证保护的 HTML 页面，显示运行时间、请求速率、状态码分布、
+// 响应耗时、访问最多的路径、缓存命中率、当前连接数与下载统计，小型部署不必另外搭建 Prometheus + Grafana。
+// 统计只覆盖当前进程，supervisor 模式下每个工作进程各自统计
+use crate::analytics::{Analytics, Sort};
+use crate::basic_auth;
+use crate::metrics::{CacheResult, ContentClass, METRICS};
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Instant;
+
+// 最多记录的不同路径数，超出时所有计数减半并丢弃归零的路径
+const MAX_PATHS: usize = 10_000;
+// 按秒记录请求数的时长，用于最近 1 分钟与 5 分钟的速率
+const WINDOW_SECS: u64 = 300;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct StatusConfig {
+    // 状态页路径
+    #[serde(default = "default_path")]
+    pub path: String,
+    // Basic 认证的用户名，未设置时接受任意用户名
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(serialize_with = "crate::config::redact")]
+    pub password: String,
+    // 显示访问最多的路径条数
+    #[serde(default = "default_top_paths")]
+    pub top_paths: usize,
+}
+
+fn default_path() -> String {
+    "/__status".to_string()
+}
+
+fn default_top_paths() -> usize {
+    10
+}
+
+struct Stats {
+    started: Instant,
+    recent: Mutex<Recent>,
+}
+
+#[derive(Default)]
+struct Recent {
+    // (启动以来的秒数, 该秒内的请求数)，按时间顺序
+    seconds: VecDeque<(u64, u64)>,
+    paths: HashMap<String, u64>,
+}
+
+static STATS: LazyLock<Stats> = LazyLock::new(|| Stats {
+    started: Instant::now(),
+    recent: Mutex::new(Recent::default()),
+});
+
+impl Stats {
+    fn record(&self, path: &str) {
+        let now = self.started.elapsed().as_secs();
+        let mut recent = self.recent.lock().unwrap();
+        match recent.seconds.back_mut() {
+            Some((second, count)) if *second == now => *count += 1,
+            _ => recent.seconds.push_back((now, 1)),
+        }
+        while recent
+            .seconds
+            .front()
+            .is_some_and(|(second, _)| second + WINDOW_SECS <= now)
+        {
+            recent.seconds.pop_front();
+        }
+        if let Some(count) = recent.paths.get_mut(path) {
+            *count += 1;
+            return;
+        }
+        if recent.paths.len() >= MAX_PATHS {
+            recent.paths.retain(|_, count| {
+                *count /= 2;
+                *count > 0
+            });
+        }
+        recent.paths.insert(path.to_string(), 1);
+    }
+
+    // 最近 secs 秒（不含当前这一秒）的平均每秒请求数
+    fn rate(recent: &Recent, now: u64, secs: u64) -> f64 {
+        let secs = secs.min(now).max(1);
+        let total: u64 = recent
+            .seconds
+            .iter()
+            .filter(|(second, _)| *second < now && second + secs >= now)
+            .map(|(_, count)| count)
+            .sum();
+        total as f64 / secs as f64
+    }
+}
+
+pub struct Status {
+    path: String,
+    username: Option<String>,
+    password: String,
+    top_paths: usize,
+    challenge: HeaderValue,
+    analytics: Option<Arc<Analytics>>,
+}
+
+impl Status {
+    pub fn new(config: &StatusConfig, analytics: Option<Arc<Analytics>>) -> Result<Self, String> {
+        if !config.path.starts_with('/') {
+            return Err(format!("status path `{}` must start with '/'", config.path));
+        }
+        if config.password.is_empty() {
+            return Err("[status] password must not be empty".to_string());
+        }
+        // 运行时间从组装站点时开始计算
+        LazyLock::force(&STATS);
+        Ok(Status {
+            path: config.path.clone(),
+            username: config.username.clone(),
+            password: config.password.clone(),
+            top_paths: config.top_paths,
+            challenge: HeaderValue::from_static("Basic realm=\"status\", charset=\"UTF-8\""),
+            analytics,
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        basic_auth::matches(headers, self.username.as_deref(), &self.password)
+    }
+}
+
+// 按原始请求路径计数；状态页自身定时刷新，不计入
+pub async fn track(State(status): State<Arc<Status>>, req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    if path != status.path {
+        STATS.record(path);
+    }
+    next.run(req).await
+}
+
+pub async fn page(State(status): State<Arc<Status>>, headers: HeaderMap) -> Response {
+    if !status.authorized(&headers) {
+        let mut response = StatusCode::UNAUTHORIZED.into_response();
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, status.challenge.clone());
+        return response;
+    }
+    let mut response = Html(render(&status)).into_response();
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+fn render(status: &Status) -> String {
+    let elapsed = STATS.started.elapsed();
+    let now = elapsed.as_secs();
+    let metrics = METRICS.snapshot();
+    let (connections, clients) = METRICS.connections();
+    let (rate_1m, rate_5m, mut paths) = {
+        let recent = STATS.recent.lock().unwrap();
+        let paths: Vec<(String, u64)> = recent
+            .paths
+            .iter()
+            .map(|(path, count)| (path.clone(), *count))
+            .collect();
+        (
+            Stats::rate(&recent, now, 60),
+            Stats::rate(&recent, now, WINDOW_SECS),
+            paths,
+        )
+    };
+    paths.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    paths.truncate(status.top_paths);
+
+    let average = metrics.requests as f64 / elapsed.as_secs_f64().max(1.0);
+    let lookups = metrics.cache_hits + metrics.cache_misses;
+    let hit_ratio = if lookups > 0 {
+        format!("{:.1}%", metrics.cache_hits as f64 * 100.0 / lookups as f64)
+    } else {
+        "-".to_string()
+    };
+
+    let mut html = String::new();
+    html.push_str(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+         <meta http-equiv=\"refresh\" content=\"5\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+         <title>Sonic Wave status</title><style>\
+         body{font-family:system-ui,sans-serif;margin:2em;color:#222}\
+         table{border-collapse:collapse;margin-bottom:1.5em}\
+         th,td{padding:.3em .8em;border-bottom:1px solid #ddd;text-align:left}\
+         td.n{text-align:right;font-variant-numeric:tabular-nums}\
+         </style></head><body>\n<h1>Sonic Wave status</h1>\n",
+    );
+    let _ = write!(
+        html,
+        "<table>\n<tr><th>Uptime</th><td class=\"n\">{}</td></tr>\n\
+         <tr><th>Requests</th><td class=\"n\">{}</td></tr>\n\
+         <tr><th>Requests/s (1 min)</th><td class=\"n\">{:.2}</td></tr>\n\
+         <tr><th>Requests/s (5 min)</th><td class=\"n\">{:.2}</td></tr>\n\
+         <tr><th>Requests/s (average)</th><td class=\"n\">{:.2}</td></tr>\n\
+         <tr><th>Open connections</th><td class=\"n\">{}</td></tr>\n\
+         <tr><th>Connected clients</th><td class=\"n\">{}</td></tr>\n\
+         <tr><th>Cache hit ratio</th><td class=\"n\">{} ({} / {})</td></tr>\n\
+         <tr><th>404 cache hits</th><td class=\"n\">{}</td></tr>\n\
+         <tr><th>Blocked user agents</th><td class=\"n\">{}</td></tr>\n\
+         <tr><th>Rejected paths</th><td class=\"n\">{}</td></tr>\n</table>\n",
+        uptime(now),
+        metrics.requests,
+        rate_1m,
+        rate_5m,
+        average,
+        connections,
+        clients,
+        hit_ratio,
+        metrics.cache_hits,
+        lookups,
+        metrics.negative_cache_hits,
+        metrics.ua_blocked,
+        metrics.path_rejected,
+    );
+    html.push_str("<h2>Status codes</h2>\n<table>\n");
+    for (class, count) in [
+        ("2xx", metrics.status_2xx),
+        ("3xx", metrics.status_3xx),
+        ("4xx", metrics.status_4xx),
+        ("5xx", metrics.status_5xx),
+    ] {
+        let share = if metrics.requests > 0 {
+            count as f64 * 100.0 / metrics.requests as f64
+        } else {
+            0.0
+        };
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td class=\"n\">{}</td><td class=\"n\">{:.1}%</td></tr>",
+            class, count, share
+        );
+    }
+    // 分位数为所在直方图桶的上界
+    html.push_str(
+        "</table>\n<h2>Response time</h2>\n<table>\n\
+         <tr><th>Content</th><th>Cache</th><th>Requests</th><th>Mean</th><th>p95</th><th>p99</th></tr>\n",
+    );
+    for class in ContentClass::ALL {
+        for cache in CacheResult::ALL {
+            let histogram = METRICS.latency(class, cache);
+            if histogram.count() == 0 {
+                continue;
+            }
+            let quantile = |q| {
+                histogram
+                    .quantile(q)
+                    .map_or("&gt; 30 s".to_string(), |secs| {
+                        format!("&le; {}", duration(secs))
+                    })
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td>\
+                 <td class=\"n\">{}</td><td class=\"n\">{}</td></tr>",
+                class.name(),
+                cache.name(),
+                histogram.count(),
+                duration(histogram.mean()),
+                quantile(0.95),
+                quantile(0.99)
+            );
+        }
+    }
+    html.push_str("</table>\n<h2>Top paths</h2>\n<table>\n");
+    for (path, count) in &paths {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td class=\"n\">{}</td></tr>",
+            escape(path),
+            count
+        );
+    }
+    html.push_str("</table>\n");
+    if let Some(analytics) = &status.analytics {
+        let (total, entries, since, _) = analytics.top(Sort::Hits, status.top_paths);
+        let _ = write!(
+            html,
+            "<h2>Downloads</h2>\n<p>Since {}; clients are estimated.</p>\n<table>\n\
+             <tr><th>Path</th><th>Hits</th><th>Bytes</th><th>Clients</th></tr>\n",
+            crate::error_pages::rfc3339(since)
+        );
+        for entry in entries.iter().chain(std::iter::once(&total)) {
+            let path = if entry.path.is_empty() {
+                "<strong>Total</strong>".to_string()
+            } else {
+                escape(&entry.path)
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td></tr>",
+                path,
+                entry.hits,
+                bytes(entry.bytes),
+                entry.clients
+            );
+        }
+        html.push_str("</table>\n");
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+fn bytes(n: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if n < 1024 {
+        return format!("{} B", n);
+    }
+    let mut value = n as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn duration(secs: f64) -> String {
+    if secs < 1.0 {
+        format!("{:.1} ms", secs * 1000.0)
+    } else {
+        format!("{:.2} s", secs)
+    }
+}
+
+// "3d 04:05:06"
+fn uptime(secs: u64) -> String {
+    let (days, rem) = (secs / 86400, secs % 86400);
+    let clock = format!("{:02}:{:02}:{:02}", rem / 3600, rem % 3600 / 60, rem % 60);
+    if days > 0 {
+        format!("{}d {}", days, clock)
+    } else {
+        clock
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
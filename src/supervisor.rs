@@ -0,0 +1,272 @@
+// 多进程 supervisor：启动 N 个共享端口（SO_REUSEPORT）的工作进程，
+// 崩溃后自动重启，并汇总各进程上报的统计
+use crate::cli::SuperviseArgs;
+use crate::metrics::{Snapshot, METRICS};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+// 工作进程编号环境变量，存在时进程以 worker 身份运行
+pub const WORKER_ID_ENV: &str = "SONICWAVE_WORKER_ID";
+
+// 工作进程 stdout 中的统计行前缀
+const STATS_PREFIX: &str = "@@sonicwave-stats ";
+
+// 进程稳定运行超过该时长后重置重启退避
+const STABLE_AFTER: Duration = Duration::from_secs(30);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+pub fn worker_id() -> Option<usize> {
+    std::env::var(WORKER_ID_ENV).ok()?.parse().ok()
+}
+
+// 工作进程侧：定期把统计快照写到 stdout，由 supervisor 解析
+pub fn spawn_stats_reporter(interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Ok(line) = serde_json::to_string(&METRICS.snapshot()) {
+                println!("{}{}", STATS_PREFIX, line);
+            }
+        }
+    });
+}
+
+#[derive(Default)]
+struct WorkerState {
+    pid: Option<u32>,
+    restarts: u64,
+    // 当前进程上报的最新快照
+    current: Snapshot,
+    // 已退出的历代进程累计值
+    retired: Snapshot,
+}
+
+type SharedState = Arc<Mutex<HashMap<usize, WorkerState>>>;
+
+pub async fn run(args: SuperviseArgs) {
+    if !cfg!(unix) {
+        eprintln!("supervise mode requires SO_REUSEPORT, which is only available on Unix");
+        std::process::exit(1);
+    }
+
+    let workers = args.workers.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    });
+    let stats_interval = Duration::from_secs(args.stats_interval.max(1));
+    let exe = std::env::current_exe().expect("Failed to resolve current executable");
+
+    info!("Supervising {} worker processes", workers);
+
+    let state: SharedState = Arc::new(Mutex::new(HashMap::new()));
+    let (stop_tx, stop_rx) = watch::channel(false);
+
+    let mut handles = Vec::with_capacity(workers);
+    for id in 1..=workers {
+        handles.push(tokio::spawn(supervise_worker(
+            id,
+            exe.clone(),
+            stats_interval,
+            state.clone(),
+            stop_rx.clone(),
+        )));
+    }
+
+    let reporter = tokio::spawn(report_loop(state.clone(), workers, stats_interval));
+
+    wait_for_signal().await;
+    info!("Stopping workers...");
+    let _ = stop_tx.send(true);
+
+    for handle in handles {
+        let _ = handle.await;
+    }
+    reporter.abort();
+    log_totals(&state, workers);
+    info!("Supervisor stopped");
+}
+
+async fn supervise_worker(
+    id: usize,
+    exe: std::path::PathBuf,
+    stats_interval: Duration,
+    state: SharedState,
+    mut stop_rx: watch::Receiver<bool>,
+) {
+    let mut backoff = Duration::from_secs(1);
+
+    loop {
+        let started = Instant::now();
+        let mut child = match Command::new(&exe)
+            .arg("serve")
+            .env(WORKER_ID_ENV, id.to_string())
+            .env(
+                "SONICWAVE_STATS_INTERVAL",
+                stats_interval.as_secs().to_string(),
+            )
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                error!("Worker {} failed to spawn: {}", id, e);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+
+        let pid = child.id();
+        info!("Worker {} started (pid {:?})", id, pid);
+        {
+            let mut guard = state.lock().unwrap();
+            let worker = guard.entry(id).or_default();
+            worker.pid = pid;
+        }
+
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(forward_output(id, stdout, state.clone()));
+        }
+
+        let stopping = tokio::select! {
+            status = child.wait() => {
+                match status {
+                    Ok(status) => warn!("Worker {} exited with {}", id, status),
+                    Err(e) => error!("Worker {} wait failed: {}", id, e),
+                }
+                false
+            }
+            _ = stop_rx.wait_for(|v| *v) => true,
+        };
+
+        if stopping {
+            terminate(&mut child).await;
+            retire(&state, id, false);
+            return;
+        }
+
+        retire(&state, id, true);
+
+        if started.elapsed() >= STABLE_AFTER {
+            backoff = Duration::from_secs(1);
+        }
+        warn!("Restarting worker {} in {:?}", id, backoff);
+        tokio::select! {
+            _ = tokio::time::sleep(backoff) => {}
+            _ = stop_rx.wait_for(|v| *v) => return,
+        }
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+// 把进程累计值转入 retired，避免重启后统计归零
+fn retire(state: &SharedState, id: usize, restarted: bool) {
+    let mut guard = state.lock().unwrap();
+    let worker = guard.entry(id).or_default();
+    let current = std::mem::take(&mut worker.current);
+    worker.retired += current;
+    worker.pid = None;
+    if restarted {
+        worker.restarts += 1;
+    }
+}
+
+// 发送 SIGTERM 让工作进程自行优雅关闭
+async fn terminate(child: &mut Child) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        use nix::sys::signal::{kill, Signal};
+        use nix::unistd::Pid;
+        let _ = kill(Pid::from_raw(pid as i32), Signal::SIGTERM);
+    }
+
+    #[cfg(not(unix))]
+    let _ = child.start_kill();
+
+    let _ = child.wait().await;
+}
+
+async fn forward_output(id: usize, stdout: tokio::process::ChildStdout, state: SharedState) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Some(payload) = line.strip_prefix(STATS_PREFIX) {
+            if let Ok(snapshot) = serde_json::from_str::<Snapshot>(payload) {
+                let mut guard = state.lock().unwrap();
+                guard.entry(id).or_default().current = snapshot;
+            }
+            continue;
+        }
+        println!("[worker {}] {}", id, line);
+    }
+}
+
+async fn report_loop(state: SharedState, workers: usize, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        log_totals(&state, workers);
+    }
+}
+
+fn log_totals(state: &SharedState, workers: usize) {
+    let guard = state.lock().unwrap();
+    let mut total = Snapshot::default();
+    let mut restarts = 0;
+    let mut running = 0;
+    for worker in guard.values() {
+        total += worker.retired;
+        total += worker.current;
+        restarts += worker.restarts;
+        if worker.pid.is_some() {
+            running += 1;
+        }
+    }
+    info!(
+        "Workers {}/{} running, {} restarts | requests={} 2xx={} 3xx={} 4xx={} 5xx={} cache_hits={} cache_misses={} negative_hits={} ua_blocked={} path_rejected={}",
+        running,
+        workers,
+        restarts,
+        total.requests,
+        total.status_2xx,
+        total.status_3xx,
+        total.status_4xx,
+        total.status_5xx,
+        total.cache_hits,
+        total.cache_misses,
+        total.negative_cache_hits,
+        total.ua_blocked,
+        total.path_rejected
+    );
+}
+
+async fn wait_for_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
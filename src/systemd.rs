@@ -0,0 +1,129 @@
+// systemd 集成（仅 Unix）：socket activation（LISTEN_FDS）、sd_notify 状态通知与 watchdog
+use crate::listener::Listener;
+use std::os::fd::RawFd;
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+// systemd 传入的第一个 FD 编号（SD_LISTEN_FDS_START）
+const LISTEN_FDS_START: RawFd = 3;
+
+// 取出 systemd 预先绑定的监听套接字，仅当 LISTEN_PID 指向本进程时有效
+pub fn activated_listeners() -> Vec<Listener> {
+    let pid_matches = std::env::var("LISTEN_PID")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .is_some_and(|pid| pid == std::process::id());
+    let count = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|v| v.parse::<RawFd>().ok())
+        .unwrap_or(0);
+
+    // 无论是否使用都清除，避免子进程误认
+    std::env::remove_var("LISTEN_PID");
+    std::env::remove_var("LISTEN_FDS");
+    std::env::remove_var("LISTEN_FDNAMES");
+
+    if !pid_matches || count <= 0 {
+        return Vec::new();
+    }
+
+    let mut listeners = Vec::new();
+    for fd in LISTEN_FDS_START..LISTEN_FDS_START + count {
+        if !is_socket(fd) {
+            warn!("Ignoring socket-activated fd {}: not a socket", fd);
+            continue;
+        }
+        // systemd 约定 3..3+LISTEN_FDS 的 FD 归本进程所有
+        match Listener::from_fd(fd) {
+            Ok(listener) => {
+                info!(
+                    "Using socket-activated listener fd {} ({})",
+                    fd,
+                    listener.local_addr()
+                );
+                listeners.push(listener);
+            }
+            Err(e) => warn!("Ignoring socket-activated fd {}: {}", fd, e),
+        }
+    }
+    listeners
+}
+
+fn is_socket(fd: RawFd) -> bool {
+    use nix::sys::stat::{fstat, SFlag};
+    // SAFETY: 仅用于 fstat 查询，不转移所有权
+    let fd = unsafe { std::os::fd::BorrowedFd::borrow_raw(fd) };
+    fstat(fd)
+        .is_ok_and(|st| SFlag::from_bits_truncate(st.st_mode) & SFlag::S_IFMT == SFlag::S_IFSOCK)
+}
+
+// 向 NOTIFY_SOCKET 发送一条状态消息，未运行在 systemd 下时静默忽略
+pub fn notify(state: &str) {
+    let Ok(path) = std::env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+
+    let result = UnixDatagram::unbound().and_then(|socket| {
+        if let Some(name) = path.strip_prefix('@') {
+            // 抽象命名空间套接字
+            #[cfg(target_os = "linux")]
+            {
+                use std::os::linux::net::SocketAddrExt;
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+                return socket.send_to_addr(state.as_bytes(), &addr);
+            }
+            #[cfg(not(target_os = "linux"))]
+            {
+                let _ = name;
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "abstract notify socket",
+                ));
+            }
+        }
+        socket.send_to(state.as_bytes(), &path)
+    });
+
+    match result {
+        Ok(_) => debug!("sd_notify: {}", state.replace('\n', " ")),
+        Err(e) => warn!("sd_notify to {} failed: {}", path, e),
+    }
+}
+
+pub fn notify_ready() {
+    // 带上 MAINPID，SIGUSR2 热重启后 systemd 会跟踪新进程
+    notify(&format!(
+        "READY=1\nMAINPID={}\nSTATUS=Serving requests",
+        std::process::id()
+    ));
+}
+
+pub fn notify_stopping() {
+    notify("STOPPING=1\nSTATUS=Draining connections");
+}
+
+// WatchdogSec= 启用时按周期的一半发送 WATCHDOG=1
+pub fn spawn_watchdog() {
+    let usec = std::env::var("WATCHDOG_USEC")
+        .ok()
+        .and_then(|v| v.parse::<u64>().ok());
+    let pid_matches = std::env::var("WATCHDOG_PID")
+        .ok()
+        .and_then(|v| v.parse::<u32>().ok())
+        .is_none_or(|pid| pid == std::process::id());
+
+    let Some(usec) = usec.filter(|_| pid_matches) else {
+        return;
+    };
+
+    let interval = Duration::from_micros(usec / 2).max(Duration::from_millis(100));
+    info!("systemd watchdog enabled, pinging every {:?}", interval);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            notify("WATCHDOG=1");
+        }
+    });
+}
@@ -0,0 +1,282 @@
+// 模板渲染（[templates]）：.hbs（Handlebars）与 .tera（Tera）文件在请求时渲染，
+// 上下文由配置中的值、允许的环境变量与查询参数组成
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use handlebars::Handlebars;
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, RwLock};
+use tera::Tera;
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct TemplatesConfig {
+    // 启用的模板引擎，按扩展名区分
+    #[serde(default = "default_engines")]
+    pub engines: Vec<Engine>,
+    // 模板中的 {{ config.* }}
+    #[serde(default)]
+    #[schemars(with = "BTreeMap<String, Value>")]
+    pub context: BTreeMap<String, toml::Value>,
+    // 模板中的 {{ env.* }}，只暴露列出的环境变量，启动时读取一次
+    #[serde(default)]
+    pub env: Vec<String>,
+    // 超过该大小的模板按原样返回
+    #[serde(default = "default_max_size")]
+    pub max_size: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Engine {
+    Handlebars,
+    Tera,
+}
+
+impl Engine {
+    fn extension(self) -> &'static str {
+        match self {
+            Engine::Handlebars => ".hbs",
+            Engine::Tera => ".tera",
+        }
+    }
+}
+
+fn default_engines() -> Vec<Engine> {
+    vec![Engine::Handlebars, Engine::Tera]
+}
+
+fn default_max_size() -> usize {
+    1024 * 1024
+}
+
+// 编译后的模板按路径缓存，源文件内容变化（摘要不同）时重新编译
+pub struct Templates {
+    engines: Vec<Engine>,
+    config: Value,
+    env: Value,
+    max_size: usize,
+    handlebars: RwLock<Handlebars<'static>>,
+    tera: RwLock<Tera>,
+    digests: Mutex<HashMap<String, [u8; 32]>>,
+}
+
+impl Templates {
+    pub fn new(config: &TemplatesConfig) -> Result<Self, String> {
+        let context = serde_json::to_value(&config.context)
+            .map_err(|e| format!("invalid templates context: {}", e))?;
+        let env: Map<String, Value> = config
+            .env
+            .iter()
+            .filter_map(|name| {
+                let value = std::env::var(name).ok();
+                if value.is_none() {
+                    warn!(
+                        "Environment variable {} is not set, not exposing it to templates",
+                        name
+                    );
+                }
+                Some((name.clone(), Value::String(value?)))
+            })
+            .collect();
+        let mut tera = Tera::default();
+        // 模板名以 .tera 结尾，Tera 默认不会转义；查询参数来自访问者，一律转义
+        tera.autoescape_on(vec![""]);
+        Ok(Templates {
+            engines: config.engines.clone(),
+            config: context,
+            env: Value::Object(env),
+            max_size: config.max_size,
+            handlebars: RwLock::new(Handlebars::new()),
+            tera: RwLock::new(tera),
+            digests: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn engine(&self, path: &str) -> Option<Engine> {
+        let path = path.to_ascii_lowercase();
+        self.engines
+            .iter()
+            .copied()
+            .find(|engine| path.ends_with(engine.extension()))
+    }
+
+    fn render(
+        &self,
+        engine: Engine,
+        name: &str,
+        source: &str,
+        context: &Value,
+    ) -> Result<String, String> {
+        let digest: [u8; 32] = Sha256::digest(source.as_bytes()).into();
+        let fresh = self.digests.lock().unwrap().get(name) == Some(&digest);
+        match engine {
+            Engine::Handlebars => {
+                if !fresh {
+                    self.handlebars
+                        .write()
+                        .unwrap()
+                        .register_template_string(name, source)
+                        .map_err(|e| e.to_string())?;
+                }
+                let output = self.handlebars.read().unwrap().render(name, context);
+                self.digests
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_string(), digest);
+                output.map_err(|e| e.to_string())
+            }
+            Engine::Tera => {
+                if !fresh {
+                    self.tera
+                        .write()
+                        .unwrap()
+                        .add_raw_template(name, source)
+                        .map_err(|e| e.to_string())?;
+                }
+                let context =
+                    tera::Context::from_value(context.clone()).map_err(|e| e.to_string())?;
+                let output = self.tera.read().unwrap().render(name, &context);
+                self.digests
+                    .lock()
+                    .unwrap()
+                    .insert(name.to_string(), digest);
+                output.map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+// 查询参数按 application/x-www-form-urlencoded 解析，重复的参数取最后一个
+fn query_params(query: Option<&str>) -> Map<String, Value> {
+    let decode = |s: &str| {
+        percent_decode_str(&s.replace('+', " "))
+            .decode_utf8_lossy()
+            .into_owned()
+    };
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), Value::String(decode(value)))
+        })
+        .collect()
+}
+
+// a.html.hbs 按 a.html 的类型返回，没有内层扩展名时按 HTML 返回
+fn content_type(path: &str, engine: Engine) -> HeaderValue {
+    let inner = &path[..path.len() - engine.extension().len()];
+    let name = inner.rsplit('/').next().unwrap_or(inner);
+    let mime = if name.contains('.') {
+        mime_guess::from_path(name).first()
+    } else {
+        None
+    };
+    match mime {
+        Some(mime) if mime.type_() == "text" => {
+            HeaderValue::from_str(&format!("{}; charset=utf-8", mime.essence_str()))
+                .unwrap_or(HeaderValue::from_static("text/html; charset=utf-8"))
+        }
+        Some(mime) => HeaderValue::from_str(mime.essence_str())
+            .unwrap_or(HeaderValue::from_static("text/html; charset=utf-8")),
+        None => HeaderValue::from_static("text/html; charset=utf-8"),
+    }
+}
+
+// 在 resolve 之后执行；结果随查询参数变化，不返回 Last-Modified / ETag
+pub async fn respond(
+    templates: &Templates,
+    cache_control: &str,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let is_head = req.method() == Method::HEAD;
+    if !(is_head || req.method() == Method::GET) {
+        return next.run(req).await;
+    }
+    let Some(engine) = templates.engine(req.uri().path()) else {
+        return next.run(req).await;
+    };
+    let path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    let context = json!({
+        "config": templates.config,
+        "env": templates.env,
+        "query": query_params(req.uri().query()),
+        "request": {
+            "method": req.method().as_str(),
+            "path": path,
+        },
+    });
+    for name in [
+        header::RANGE,
+        header::IF_RANGE,
+        header::IF_NONE_MATCH,
+        header::IF_MODIFIED_SINCE,
+        header::ACCEPT_ENCODING,
+    ] {
+        req.headers_mut().remove(name);
+    }
+    if is_head {
+        *req.method_mut() = Method::GET;
+    }
+    let response = next.run(req).await;
+    let too_large = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| len > templates.max_size);
+    if response.status() != StatusCode::OK || too_large {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, templates.max_size).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            warn!("Not rendering {}: {}", path, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    let output = match templates.render(engine, &path, &String::from_utf8_lossy(&bytes), &context) {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Failed to render template {}: {}", path, e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    for name in [
+        header::CONTENT_LENGTH,
+        header::ACCEPT_RANGES,
+        header::LAST_MODIFIED,
+        header::ETAG,
+    ] {
+        parts.headers.remove(name);
+    }
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, content_type(&path, engine));
+    if let Ok(value) = HeaderValue::from_str(cache_control) {
+        parts.headers.insert(header::CACHE_CONTROL, value);
+    }
+    let body = if is_head {
+        parts
+            .headers
+            .insert(header::CONTENT_LENGTH, HeaderValue::from(output.len()));
+        Body::empty()
+    } else {
+        Body::from(output)
+    };
+    Response::from_parts(parts, body)
+}
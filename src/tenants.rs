@@ -0,0 +1,575 @@
+// 多租户模式（[tenants]）：第一段路径（/alice/...）或子域名（alice.sites.example.com）对应租户目录中的一个子目录，
+// 每个租户有自己的 token 与存储配额，用 token 通过 PUT / DELETE / multipart POST 发布文件（与 [upload] 相同的写入方式），
+// 页面公开访问。管理端点创建、调整与删除租户；租户记录保存在租户目录的 .tenants.json 中，没有记录的子目录不提供
+use crate::login;
+use crate::metrics::{TenantStats, METRICS};
+use crate::site::{self, MountConfig, SiteOptions};
+use crate::upload::{self, UploadConfig, Uploader};
+use crate::vhost;
+use axum::extract::{Query, Request, State};
+use axum::http::header::{self, HeaderMap};
+use axum::http::{Method, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::{Json, Router};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tower::ServiceExt;
+use tracing::{info, warn};
+
+const STATE_FILE: &str = ".tenants.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum TenantMode {
+    // /<租户>/...
+    #[default]
+    Path,
+    // <租户>.<domain>
+    Subdomain,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct TenantsConfig {
+    // 租户目录，每个租户是其中以租户名命名的子目录
+    pub dir: PathBuf,
+    #[serde(default)]
+    pub mode: TenantMode,
+    // mode = "subdomain" 时租户子域名所在的域名，如 "sites.example.com"
+    #[serde(default)]
+    pub domain: Option<String>,
+    // 新建租户的默认存储配额（字节）
+    #[serde(default = "default_quota")]
+    pub quota_bytes: u64,
+    // 管理端点的 Bearer token，未设置时不启用管理端点
+    #[serde(default, serialize_with = "crate::config::redact_option")]
+    pub token: Option<String>,
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_quota() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_path() -> String {
+    "/__tenants".to_string()
+}
+
+// .tenants.json 中的一个租户
+#[derive(Serialize, Deserialize, Clone)]
+struct Record {
+    token: String,
+    quota_bytes: u64,
+    created: String,
+}
+
+struct Tenant {
+    record: Record,
+    dir: PathBuf,
+    site: Router,
+    uploader: Arc<Uploader>,
+    stats: Arc<TenantStats>,
+    // 同一租户的写入依次执行，配额按写入前的用量计算
+    writing: tokio::sync::Mutex<()>,
+}
+
+pub struct Tenants {
+    dir: PathBuf,
+    mode: TenantMode,
+    // ".sites.example.com"
+    suffix: String,
+    quota_bytes: u64,
+    token: Option<String>,
+    path: String,
+    options: SiteOptions,
+    allow_dotfiles: bool,
+    deny: Vec<String>,
+    // 路径模式下主站点的目录（不在磁盘上时为 None）与挂载点的第一段，租户名不能与之重名
+    site_dir: Option<PathBuf>,
+    mounts: Vec<String>,
+    tenants: RwLock<HashMap<String, Arc<Tenant>>>,
+    // 管理操作依次执行，保存的记录与内存中一致
+    provisioning: tokio::sync::Mutex<()>,
+}
+
+// 租户名同时用作路径段与 DNS 标签：小写字母、数字与连字符
+fn valid_name(name: &str) -> bool {
+    (1..=63).contains(&name.len())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+}
+
+fn new_token() -> Result<String, String> {
+    Ok(URL_SAFE_NO_PAD.encode(login::random_bytes(32)?))
+}
+
+// 目录中普通文件的总大小，不跟随符号链接
+fn disk_usage(dir: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => disk_usage(&entry.path()),
+            Ok(kind) if kind.is_file() => entry.metadata().map_or(0, |meta| meta.len()),
+            _ => 0,
+        })
+        .sum()
+}
+
+impl Tenants {
+    pub fn new(
+        config: &TenantsConfig,
+        options: SiteOptions,
+        allow_dotfiles: bool,
+        deny: &[String],
+        site_dir: Option<&Path>,
+        mounts: &[MountConfig],
+    ) -> Result<Self, String> {
+        if crate::supervisor::worker_id().is_some() {
+            return Err("[tenants] is not supported in supervisor mode".to_string());
+        }
+        std::fs::create_dir_all(&config.dir).map_err(|e| {
+            format!(
+                "[tenants] cannot create dir {}: {}",
+                config.dir.display(),
+                e
+            )
+        })?;
+        let suffix = match (config.mode, &config.domain) {
+            (TenantMode::Subdomain, Some(domain)) if !domain.is_empty() => {
+                format!(".{}", domain.trim_matches('.').to_ascii_lowercase())
+            }
+            (TenantMode::Subdomain, _) => {
+                return Err("[tenants] mode = \"subdomain\" requires domain".to_string())
+            }
+            (TenantMode::Path, _) => String::new(),
+        };
+        if !config.path.starts_with('/') {
+            return Err("[tenants] path must start with /".to_string());
+        }
+        if config.token.as_deref() == Some("") {
+            return Err("[tenants] token must not be empty".to_string());
+        }
+        let tenants = Tenants {
+            dir: config.dir.clone(),
+            mode: config.mode,
+            suffix,
+            quota_bytes: config.quota_bytes,
+            token: config.token.clone(),
+            path: config.path.clone(),
+            options,
+            allow_dotfiles,
+            deny: deny.to_vec(),
+            site_dir: site_dir.map(Path::to_path_buf),
+            mounts: mounts
+                .iter()
+                .filter_map(|mount| {
+                    let first = mount.prefix.trim_start_matches('/').split('/').next()?;
+                    (!first.is_empty()).then(|| first.to_string())
+                })
+                .collect(),
+            tenants: RwLock::new(HashMap::new()),
+            provisioning: tokio::sync::Mutex::new(()),
+        };
+        let state = tenants.dir.join(STATE_FILE);
+        let records: BTreeMap<String, Record> = match std::fs::read_to_string(&state) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| format!("[tenants] invalid {}: {}", state.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(format!("[tenants] cannot read {}: {}", state.display(), e)),
+        };
+        {
+            let mut map = tenants.tenants.write().unwrap();
+            for (name, record) in records {
+                if !valid_name(&name) {
+                    return Err(format!(
+                        "[tenants] invalid tenant name `{}` in {}",
+                        name,
+                        state.display()
+                    ));
+                }
+                if tenants.taken(&name) {
+                    warn!(
+                        "Tenant `{}` has the same name as a top-level path of the main site and takes it over",
+                        name
+                    );
+                }
+                let tenant = tenants.build(&name, record)?;
+                map.insert(name, Arc::new(tenant));
+            }
+        }
+        Ok(tenants)
+    }
+
+    pub fn describe(&self) -> String {
+        let count = self.tenants.read().unwrap().len();
+        match self.mode {
+            TenantMode::Path => format!("{} ({} tenants, /<tenant>/)", self.dir.display(), count),
+            TenantMode::Subdomain => format!(
+                "{} ({} tenants, <tenant>{})",
+                self.dir.display(),
+                count,
+                self.suffix
+            ),
+        }
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.token.as_ref().map(|_| self.path.as_str())
+    }
+
+    // 租户的站点：与主目录相同的站点设置，外面包一层以租户 token 认证的写入
+    fn build(&self, name: &str, record: Record) -> Result<Tenant, String> {
+        let dir = self.dir.join(name);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("cannot create tenant dir {}: {}", dir.display(), e))?;
+        let upload = UploadConfig {
+            prefixes: vec!["/".to_string()],
+            max_size: record.quota_bytes,
+            username: None,
+            password: record.token.clone(),
+            tus_endpoint: None,
+            staging_dir: PathBuf::new(),
+        };
+        let uploader = Arc::new(Uploader::new(
+            &upload,
+            &dir,
+            self.allow_dotfiles,
+            &self.deny,
+        )?);
+        let site = site::router(&dir.to_string_lossy(), self.options.clone())?.layer(
+            axum::middleware::from_fn_with_state(uploader.clone(), upload::handle),
+        );
+        let stats = METRICS.tenant(name);
+        stats
+            .quota_bytes
+            .store(record.quota_bytes, Ordering::Relaxed);
+        stats
+            .storage_bytes
+            .store(disk_usage(&dir), Ordering::Relaxed);
+        Ok(Tenant {
+            record,
+            dir,
+            site,
+            uploader,
+            stats,
+            writing: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    // 路径模式下 /<租户>/ 先于主站点匹配，与主站点顶层条目或挂载点重名的租户会接管主站点的这些路径
+    fn taken(&self, name: &str) -> bool {
+        if self.mode != TenantMode::Path {
+            return false;
+        }
+        self.mounts.iter().any(|mount| mount == name)
+            || self
+                .site_dir
+                .as_ref()
+                .is_some_and(|dir| std::fs::symlink_metadata(dir.join(name)).is_ok())
+    }
+
+    // 请求所属的租户与去掉租户前缀后的路径
+    fn select(&self, req: &Request) -> Option<(Arc<Tenant>, Option<String>)> {
+        let tenants = self.tenants.read().unwrap();
+        match self.mode {
+            TenantMode::Path => {
+                let rest = req.uri().path().strip_prefix('/')?;
+                let (name, rest) = match rest.find('/') {
+                    Some(i) => (&rest[..i], &rest[i..]),
+                    None => (rest, ""),
+                };
+                let tenant = tenants.get(name)?.clone();
+                Some((tenant, Some(rest.to_string())))
+            }
+            TenantMode::Subdomain => {
+                let host = vhost::request_host(req)?;
+                let name = host.strip_suffix(self.suffix.as_str())?;
+                Some((tenants.get(name)?.clone(), None))
+            }
+        }
+    }
+
+    fn authorized(&self, headers: &HeaderMap) -> bool {
+        upload::bearer_authorized(headers, self.token.as_deref())
+    }
+
+    fn status(&self, name: &str, tenant: &Tenant) -> serde_json::Value {
+        json!({
+            "name": name,
+            "quota_bytes": tenant.record.quota_bytes,
+            "storage_bytes": tenant.stats.storage_bytes.load(Ordering::Relaxed),
+            "requests": tenant.stats.requests.load(Ordering::Relaxed),
+            "sent_bytes": tenant.stats.sent_bytes.load(Ordering::Relaxed),
+            "created": tenant.record.created,
+        })
+    }
+
+    // 先写临时文件再改名；记录中有各租户的 token，只有当前用户可读
+    fn save(&self) -> Result<(), String> {
+        let records: BTreeMap<String, Record> = self
+            .tenants
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, tenant)| (name.clone(), tenant.record.clone()))
+            .collect();
+        let data = serde_json::to_vec_pretty(&records).map_err(|e| e.to_string())?;
+        let path = self.dir.join(STATE_FILE);
+        let temp = path.with_extension("tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&temp)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, &data))
+            .and_then(|_| std::fs::rename(&temp, &path))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl Tenant {
+    // 认证通过后检查配额：需要 Content-Length，写入前的用量加上请求体不能超过配额
+    async fn write(&self, req: Request) -> Response {
+        if !self.uploader.authorized(req.headers()) {
+            return upload::unauthorized();
+        }
+        let _guard = self.writing.lock().await;
+        if req.method() != Method::DELETE {
+            let Some(length) = req
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+            else {
+                return StatusCode::LENGTH_REQUIRED.into_response();
+            };
+            let used = self.stats.storage_bytes.load(Ordering::Relaxed);
+            if used.saturating_add(length) > self.record.quota_bytes {
+                return (StatusCode::INSUFFICIENT_STORAGE, "quota exceeded\n").into_response();
+            }
+        }
+        let response = match self.site.clone().oneshot(req).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        };
+        if response.status().is_success() {
+            let dir = self.dir.clone();
+            let usage = tokio::task::spawn_blocking(move || disk_usage(&dir))
+                .await
+                .unwrap_or(0);
+            self.stats.storage_bytes.store(usage, Ordering::Relaxed);
+        }
+        response
+    }
+}
+
+// 包在主目录外面：属于某个租户的请求交给租户的站点，其余请求照常处理
+pub async fn serve(State(tenants): State<Arc<Tenants>>, mut req: Request, next: Next) -> Response {
+    let Some((tenant, rest)) = tenants.select(&req) else {
+        return next.run(req).await;
+    };
+    if let Some(rest) = rest {
+        // "/alice" 跳转到 "/alice/"，与挂载点相同
+        if rest.is_empty() {
+            let location = match req.uri().query() {
+                Some(query) => format!("{}/?{}", req.uri().path(), query),
+                None => format!("{}/", req.uri().path()),
+            };
+            return Redirect::permanent(&location).into_response();
+        }
+        let target = match req.uri().query() {
+            Some(query) => format!("{}?{}", rest, query),
+            None => rest,
+        };
+        match target.parse::<Uri>() {
+            Ok(uri) => *req.uri_mut() = uri,
+            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+        }
+    }
+    tenant.stats.requests.fetch_add(1, Ordering::Relaxed);
+    let method = req.method().clone();
+    let response = if method == Method::PUT || method == Method::POST || method == Method::DELETE {
+        tenant.write(req).await
+    } else {
+        match tenant.site.clone().oneshot(req).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        }
+    };
+    if let Some(length) = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+    {
+        tenant.stats.sent_bytes.fetch_add(length, Ordering::Relaxed);
+    }
+    response
+}
+
+// GET 列出租户与用量；POST ?name=alice 创建租户并返回 token，已存在时按 ?quota_bytes= 调整配额，
+// ?rotate=true 重新生成 token；DELETE ?name=alice 删除租户及其文件
+pub async fn list(State(tenants): State<Arc<Tenants>>, headers: HeaderMap) -> Response {
+    if !tenants.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    let list: Vec<_> = {
+        let map = tenants.tenants.read().unwrap();
+        let mut names: Vec<_> = map.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| tenants.status(name, &map[name]))
+            .collect()
+    };
+    login::no_store(Json(json!({ "tenants": list })).into_response())
+}
+
+pub async fn provision(
+    State(tenants): State<Arc<Tenants>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if !tenants.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    let Some(name) = query.get("name").filter(|name| valid_name(name)).cloned() else {
+        return bad_request("expected ?name= with lowercase letters, digits and hyphens");
+    };
+    let quota = match query.get("quota_bytes").map(|v| v.parse::<u64>()) {
+        Some(Ok(quota)) => Some(quota),
+        Some(Err(_)) => return bad_request("invalid quota_bytes"),
+        None => None,
+    };
+    let rotate = matches!(
+        query.get("rotate").map(String::as_str),
+        Some("true" | "1" | "on")
+    );
+    let _guard = tenants.provisioning.lock().await;
+    let existing = tenants.tenants.read().unwrap().get(&name).cloned();
+    let created = existing.is_none();
+    if created && tenants.taken(&name) {
+        return login::no_store(
+            (
+                StatusCode::CONFLICT,
+                Json(json!({ "error": format!("`{}` is already a path of the main site", name) })),
+            )
+                .into_response(),
+        );
+    }
+    let mut record = match &existing {
+        Some(tenant) => tenant.record.clone(),
+        None => Record {
+            token: String::new(),
+            quota_bytes: tenants.quota_bytes,
+            created: crate::error_pages::rfc3339(SystemTime::now()),
+        },
+    };
+    if created || rotate {
+        record.token = match new_token() {
+            Ok(token) => token,
+            Err(e) => return server_error(&e),
+        };
+    }
+    if let Some(quota) = quota {
+        record.quota_bytes = quota;
+    }
+    let worker = tenants.clone();
+    let built = {
+        let name = name.clone();
+        let record = record.clone();
+        tokio::task::spawn_blocking(move || worker.build(&name, record)).await
+    };
+    let tenant = match built {
+        Ok(Ok(tenant)) => Arc::new(tenant),
+        Ok(Err(e)) => return server_error(&e),
+        Err(e) => return server_error(&e.to_string()),
+    };
+    tenants
+        .tenants
+        .write()
+        .unwrap()
+        .insert(name.clone(), tenant.clone());
+    if let Err(e) = tenants.save() {
+        return server_error(&format!("failed to save tenants: {}", e));
+    }
+    if created {
+        info!("Created tenant {}", name);
+    } else {
+        info!("Updated tenant {}", name);
+    }
+    let mut status = tenants.status(&name, &tenant);
+    if created || rotate {
+        status["token"] = record.token.into();
+    }
+    let code = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    login::no_store((code, Json(status)).into_response())
+}
+
+pub async fn remove(
+    State(tenants): State<Arc<Tenants>>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    if !tenants.authorized(&headers) {
+        return upload::bearer_unauthorized();
+    }
+    let Some(name) = query.get("name").filter(|name| valid_name(name)).cloned() else {
+        return bad_request("expected ?name=");
+    };
+    let _guard = tenants.provisioning.lock().await;
+    let Some(tenant) = tenants.tenants.write().unwrap().remove(&name) else {
+        return login::no_store(StatusCode::NOT_FOUND.into_response());
+    };
+    if let Err(e) = tenants.save() {
+        tenants.tenants.write().unwrap().insert(name, tenant);
+        return server_error(&format!("failed to save tenants: {}", e));
+    }
+    // 等正在进行的写入完成后再删除文件
+    let _writing = tenant.writing.lock().await;
+    METRICS.remove_tenant(&name);
+    if let Err(e) = tokio::fs::remove_dir_all(&tenant.dir).await {
+        warn!(
+            "Failed to remove tenant dir {}: {}",
+            tenant.dir.display(),
+            e
+        );
+    }
+    info!("Deleted tenant {}", name);
+    login::no_store(StatusCode::NO_CONTENT.into_response())
+}
+
+fn bad_request(message: &str) -> Response {
+    login::no_store((StatusCode::BAD_REQUEST, Json(json!({ "error": message }))).into_response())
+}
+
+fn server_error(message: &str) -> Response {
+    warn!("Tenant provisioning failed: {}", message);
+    login::no_store(
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": message })),
+        )
+            .into_response(),
+    )
+}
@@ -0,0 +1,232 @@
+// 下载限速（[throttle]）：用令牌桶包装响应体，按全局、每个连接与路径规则限制发送速率，
+// 同一响应受多个限制时取最慢的一个；每个桶最多积攒一秒的额度，小文件基本不受影响
+use crate::glob::PathPattern;
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use http_body::{Body as HttpBody, Frame, SizeHint};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+
+// 额度不足时至少攒够这么多字节再发送，避免产生大量小帧
+const MIN_CHUNK: usize = 16 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, JsonSchema)]
+pub struct ThrottleConfig {
+    // 所有下载合计的速率上限（字节/秒）
+    pub rate: Option<u64>,
+    // 每个连接的速率上限（字节/秒），HTTP/2 连接上的所有请求共用
+    pub per_connection: Option<u64>,
+    // 按顺序匹配，第一条匹配的规则生效
+    #[serde(default)]
+    pub rules: Vec<ThrottleRuleConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ThrottleRuleConfig {
+    // 路径模式，如 "/downloads/**"、"*.iso"
+    pub pattern: String,
+    // 每个匹配请求的速率上限（字节/秒），0 表示不受任何限速
+    pub rate: u64,
+}
+
+// 令牌桶：额度按速率持续补充，最多积攒一秒；允许透支，并发响应同时扣减时只会略微放慢
+struct Bucket {
+    rate: f64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl Bucket {
+    fn new(rate: u64) -> Self {
+        Bucket {
+            rate: rate as f64,
+            state: Mutex::new((rate as f64, Instant::now())),
+        }
+    }
+
+    fn available(&self, now: Instant) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        let (tokens, last) = *state;
+        let tokens = (tokens + now.duration_since(last).as_secs_f64() * self.rate).min(self.rate);
+        *state = (tokens, now);
+        tokens
+    }
+
+    fn consume(&self, bytes: usize) {
+        self.state.lock().unwrap().0 -= bytes as f64;
+    }
+
+    // 攒够 bytes 还需等待的时间
+    fn wait(&self, bytes: usize, now: Instant) -> Duration {
+        let missing = bytes as f64 - self.available(now);
+        Duration::from_secs_f64((missing / self.rate).max(0.0))
+    }
+}
+
+// 每个连接的令牌桶，由 server 在连接建立时放入请求扩展，首次限速时创建
+#[derive(Clone, Default)]
+pub struct ConnectionThrottle(Arc<OnceLock<Arc<Bucket>>>);
+
+struct Rule {
+    pattern: PathPattern,
+    rate: u64,
+}
+
+pub struct Throttle {
+    global: Option<Arc<Bucket>>,
+    per_connection: Option<u64>,
+    rules: Vec<Rule>,
+}
+
+impl Throttle {
+    pub fn new(config: &ThrottleConfig) -> Result<Self, String> {
+        if config.rate == Some(0) || config.per_connection == Some(0) {
+            return Err("throttle rates must be greater than 0".to_string());
+        }
+        let mut rules = Vec::new();
+        for rule in &config.rules {
+            rules.push(Rule {
+                pattern: PathPattern::new(&rule.pattern)?,
+                rate: rule.rate,
+            });
+        }
+        Ok(Throttle {
+            global: config.rate.map(|rate| Arc::new(Bucket::new(rate))),
+            per_connection: config.per_connection,
+            rules,
+        })
+    }
+
+    fn buckets(&self, req: &Request) -> Vec<Arc<Bucket>> {
+        let path = req.uri().path();
+        let rule = self.rules.iter().find(|rule| rule.pattern.matches(path));
+        if rule.is_some_and(|rule| rule.rate == 0) {
+            return Vec::new();
+        }
+        let mut buckets: Vec<Arc<Bucket>> = self.global.iter().cloned().collect();
+        if let Some(rate) = self.per_connection {
+            if let Some(conn) = req.extensions().get::<ConnectionThrottle>() {
+                buckets.push(conn.0.get_or_init(|| Arc::new(Bucket::new(rate))).clone());
+            }
+        }
+        if let Some(rule) = rule {
+            buckets.push(Arc::new(Bucket::new(rule.rate)));
+        }
+        buckets
+    }
+}
+
+pub async fn apply(State(throttle): State<Arc<Throttle>>, req: Request, next: Next) -> Response {
+    let buckets = throttle.buckets(&req);
+    let response = next.run(req).await;
+    if buckets.is_empty() {
+        return response;
+    }
+    response.map(|body| {
+        Body::new(Throttled {
+            inner: body,
+            buckets,
+            pending: None,
+            sleep: None,
+        })
+    })
+}
+
+// 以固定速率发送响应体，供 [chaos] 模拟慢速网络
+pub fn limit(body: Body, rate: u64) -> Body {
+    Body::new(Throttled {
+        inner: body,
+        buckets: vec![Arc::new(Bucket::new(rate))],
+        pending: None,
+        sleep: None,
+    })
+}
+
+// 限速的响应体：取出一帧后按额度切分发送，额度不足时等待
+struct Throttled {
+    inner: Body,
+    buckets: Vec<Arc<Bucket>>,
+    pending: Option<Bytes>,
+    sleep: Option<Pin<Box<Sleep>>>,
+}
+
+impl HttpBody for Throttled {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Self::Error>>> {
+        let this = &mut *self;
+        loop {
+            if let Some(sleep) = &mut this.sleep {
+                ready!(sleep.as_mut().poll(cx));
+                this.sleep = None;
+            }
+            let mut data = match this.pending.take() {
+                Some(data) => data,
+                None => match ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                    Some(Ok(frame)) => match frame.into_data() {
+                        Ok(data) if data.is_empty() => continue,
+                        Ok(data) => data,
+                        // trailers 不计入额度
+                        Err(frame) => return Poll::Ready(Some(Ok(frame))),
+                    },
+                    other => return Poll::Ready(other),
+                },
+            };
+
+            let now = Instant::now();
+            let need = data.len().min(MIN_CHUNK);
+            let wait = this
+                .buckets
+                .iter()
+                .map(|bucket| bucket.wait(need, now))
+                .max()
+                .unwrap_or_default();
+            if !wait.is_zero() {
+                this.pending = Some(data);
+                this.sleep = Some(Box::pin(tokio::time::sleep(wait)));
+                continue;
+            }
+            let allowed = this
+                .buckets
+                .iter()
+                .map(|bucket| bucket.available(now) as usize)
+                .min()
+                .unwrap_or(data.len())
+                .clamp(need, data.len());
+            let chunk = data.split_to(allowed);
+            for bucket in &this.buckets {
+                bucket.consume(chunk.len());
+            }
+            if !data.is_empty() {
+                this.pending = Some(data);
+            }
+            return Poll::Ready(Some(Ok(Frame::data(chunk))));
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.pending.is_none() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let pending = self.pending.as_ref().map_or(0, |data| data.len() as u64);
+        let inner = self.inner.size_hint();
+        let mut hint = SizeHint::new();
+        hint.set_lower(inner.lower() + pending);
+        if let Some(upper) = inner.upper() {
+            hint.set_upper(upper + pending);
+        }
+        hint
+    }
+}
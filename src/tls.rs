@@ -0,0 +1,391 @@
+// HTTPS：listen 条目设置 tls = true 的监听器先在连接上完成 TLS 握手（在 PROXY 头之后）；
+// 按 SNI 选择证书：虚拟主机自己的 tls_cert / tls_key，其次在 [tls] cert_dir 中按主机名查找，最后是 [tls] 的默认证书；
+// 设置 client_ca 时要求（或允许）客户端出示由该 CA 签发的证书。证书文件变化或收到 SIGHUP 时重新加载，
+// 新连接使用新证书，已建立的连接不受影响；加载失败时继续使用原来的证书
+use crate::client_auth::{self, ClientRuleConfig};
+use crate::vhost::VhostConfig;
+use notify::{RecursiveMode, Watcher};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct TlsConfig {
+    // 默认证书链与私钥（PEM），客户端没有发送 SNI 或没有匹配的主机时使用
+    pub cert: Option<String>,
+    pub key: Option<String>,
+    // 按主机名查找证书的目录，布局与 certbot 相同：<cert_dir>/<主机名>/fullchain.pem 与 privkey.pem，
+    // 通配主机 "*.example.com" 对应 <cert_dir>/example.com
+    pub cert_dir: Option<String>,
+    // 握手超时（秒），防止慢速客户端占住连接
+    #[serde(default = "default_handshake_timeout")]
+    pub handshake_timeout_secs: u64,
+    // 签发客户端证书的 CA（PEM，可包含多个），设置后启用双向 TLS
+    pub client_ca: Option<String>,
+    // require：握手时必须出示有效证书；optional：可以不出示，由 client_rules 按路径要求
+    #[serde(default)]
+    pub client_auth: ClientAuth,
+    // 按路径前缀限制允许访问的客户端证书主题，第一条匹配的规则生效
+    #[serde(default)]
+    pub client_rules: Vec<ClientRuleConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ClientAuth {
+    #[default]
+    Require,
+    Optional,
+}
+
+fn default_handshake_timeout() -> u64 {
+    10
+}
+
+// TLS 连接的信息，作为请求扩展供后续中间件使用
+#[derive(Debug, Clone)]
+pub struct TlsInfo {
+    // 客户端在 SNI 中请求的主机名
+    pub server_name: Option<Arc<str>>,
+    // 已验证的客户端证书主题（RFC 4514 格式），没有出示证书时为 None
+    pub client_subject: Option<Arc<str>>,
+}
+
+#[derive(Debug, Default)]
+struct SniResolver {
+    exact: HashMap<String, Arc<CertifiedKey>>,
+    // (".example.com", 证书)，按后缀长度降序，最具体的优先
+    wildcard: Vec<(String, Arc<CertifiedKey>)>,
+    default: Option<Arc<CertifiedKey>>,
+}
+
+impl SniResolver {
+    fn insert(&mut self, name: &str, key: &Arc<CertifiedKey>) -> Result<(), String> {
+        let name = name.trim().to_ascii_lowercase();
+        if let Some(suffix) = name.strip_prefix('*') {
+            self.wildcard.push((suffix.to_string(), key.clone()));
+        } else if self.exact.insert(name.clone(), key.clone()).is_some() {
+            return Err(format!("duplicate TLS certificate for `{}`", name));
+        }
+        Ok(())
+    }
+}
+
+// 与虚拟主机相同的匹配顺序：精确主机名、通配子域名、默认证书
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let Some(name) = hello.server_name() else {
+            return self.default.clone();
+        };
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        if let Some(key) = self.exact.get(&name) {
+            return Some(key.clone());
+        }
+        self.wildcard
+            .iter()
+            .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .map(|(_, key)| key.clone())
+            .or_else(|| self.default.clone())
+    }
+}
+
+// 证书变化后等待片刻再加载，ACME 客户端通常先后写入证书与私钥
+const RELOAD_DEBOUNCE: Duration = Duration::from_secs(1);
+
+pub struct Tls {
+    acceptor: RwLock<TlsAcceptor>,
+    handshake_timeout: Duration,
+    config: Option<TlsConfig>,
+    vhosts: Vec<VhostConfig>,
+}
+
+impl Tls {
+    pub fn new(config: Option<&TlsConfig>, vhosts: &[VhostConfig]) -> Result<Self, String> {
+        Ok(Tls {
+            acceptor: RwLock::new(acceptor(config, vhosts)?),
+            handshake_timeout: Duration::from_secs(
+                config.map_or_else(default_handshake_timeout, |tls| tls.handshake_timeout_secs),
+            ),
+            config: config.cloned(),
+            vhosts: vhosts.to_vec(),
+        })
+    }
+
+    // 按启动时的配置重新读取全部证书
+    pub fn reload(&self) -> Result<(), String> {
+        let acceptor = acceptor(self.config.as_ref(), &self.vhosts)?;
+        *self.acceptor.write().unwrap() = acceptor;
+        Ok(())
+    }
+
+    // 需要监听的目录：证书文件所在目录（ACME 客户端通常以重命名替换文件）与 cert_dir
+    fn watched(&self) -> Vec<(PathBuf, RecursiveMode)> {
+        let mut files: Vec<&String> = Vec::new();
+        let mut dirs = Vec::new();
+        if let Some(tls) = &self.config {
+            files.extend([&tls.cert, &tls.key, &tls.client_ca].into_iter().flatten());
+            if let Some(dir) = &tls.cert_dir {
+                dirs.push((PathBuf::from(dir), RecursiveMode::Recursive));
+            }
+        }
+        for vhost in &self.vhosts {
+            files.extend([&vhost.tls_cert, &vhost.tls_key].into_iter().flatten());
+        }
+        for file in files {
+            let dir = match Path::new(file).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                _ => PathBuf::from("."),
+            };
+            if !dirs.iter().any(|(watched, _)| dir.starts_with(watched)) {
+                dirs.push((dir, RecursiveMode::NonRecursive));
+            }
+        }
+        dirs
+    }
+
+    pub async fn accept<S>(&self, stream: S) -> io::Result<(TlsStream<S>, TlsInfo)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let acceptor = self.acceptor.read().unwrap().clone();
+        let stream = tokio::time::timeout(self.handshake_timeout, acceptor.accept(stream))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out"))??;
+        let conn = stream.get_ref().1;
+        let info = TlsInfo {
+            server_name: conn.server_name().map(Arc::from),
+            client_subject: conn
+                .peer_certificates()
+                .and_then(|chain| chain.first())
+                .and_then(|cert| client_auth::subject(cert))
+                .map(Arc::from),
+        };
+        Ok((stream, info))
+    }
+}
+
+fn acceptor(config: Option<&TlsConfig>, vhosts: &[VhostConfig]) -> Result<TlsAcceptor, String> {
+    let provider = Arc::new(ring::default_provider());
+    let mut resolver = SniResolver::default();
+
+    if let Some(tls) = config {
+        resolver.default = match (&tls.cert, &tls.key) {
+            (Some(cert), Some(key)) => Some(load(cert, key, &provider)?),
+            (None, None) => None,
+            _ => return Err("[tls] cert and key must be set together".to_string()),
+        };
+    }
+    let cert_dir = config.and_then(|tls| tls.cert_dir.as_deref());
+    let mut vhost_default = None;
+    for vhost in vhosts {
+        let key = match (&vhost.tls_cert, &vhost.tls_key) {
+            (Some(cert), Some(key)) => Some(load(cert, key, &provider)?),
+            (None, None) => match cert_dir {
+                Some(dir) => from_cert_dir(dir, &vhost.host, &provider)?,
+                None => None,
+            },
+            _ => {
+                return Err(format!(
+                    "[[vhost]] {}: tls_cert and tls_key must be set together",
+                    vhost.host
+                ))
+            }
+        };
+        let Some(key) = key else {
+            warn!(
+                "No TLS certificate for vhost {}, falling back to the default",
+                vhost.host
+            );
+            continue;
+        };
+        for name in std::iter::once(&vhost.host).chain(&vhost.aliases) {
+            resolver.insert(name, &key)?;
+        }
+        if vhost.default {
+            vhost_default = Some(key);
+        }
+    }
+    // 没有配置默认证书时使用默认虚拟主机的证书
+    if resolver.default.is_none() {
+        resolver.default = vhost_default;
+    }
+    if resolver.default.is_none() && resolver.exact.is_empty() && resolver.wildcard.is_empty() {
+        return Err(
+                "TLS listeners need a certificate: set [tls] cert and key, cert_dir, or tls_cert and tls_key on a [[vhost]]"
+                    .to_string(),
+            );
+    }
+    resolver
+        .wildcard
+        .sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+    info!(
+        "TLS certificates: {} host(s), {} wildcard(s), default {}",
+        resolver.exact.len(),
+        resolver.wildcard.len(),
+        if resolver.default.is_some() {
+            "set"
+        } else {
+            "none"
+        }
+    );
+
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("TLS: {}", e))?;
+    let builder = match config.and_then(|tls| tls.client_ca.as_ref().map(|ca| (tls, ca))) {
+        Some((tls, ca)) => {
+            let roots = load_roots(ca)?;
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+            let verifier = match tls.client_auth {
+                ClientAuth::Require => verifier,
+                ClientAuth::Optional => verifier.allow_unauthenticated(),
+            };
+            let verifier = verifier
+                .build()
+                .map_err(|e| format!("invalid client_ca {}: {}", ca, e))?;
+            info!(
+                "TLS client certificates {} (CA {})",
+                match tls.client_auth {
+                    ClientAuth::Require => "required",
+                    ClientAuth::Optional => "optional",
+                },
+                ca
+            );
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut server = builder.with_cert_resolver(Arc::new(resolver));
+    server.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(server)))
+}
+
+// 监听证书文件并在 Unix 上响应 SIGHUP；返回的 watcher 需要保持存活
+pub fn watch(tls: Arc<Tls>) -> Option<notify::RecommendedWatcher> {
+    let (event_tx, mut event_rx) = tokio::sync::mpsc::unbounded_channel();
+    #[cfg(unix)]
+    {
+        let event_tx = event_tx.clone();
+        tokio::spawn(async move {
+            use tokio::signal::unix::{signal, SignalKind};
+            let mut hup = match signal(SignalKind::hangup()) {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("Failed to install SIGHUP handler: {}", e);
+                    return;
+                }
+            };
+            while hup.recv().await.is_some() {
+                info!("Received SIGHUP, reloading TLS certificates");
+                let _ = event_tx.send(());
+            }
+        });
+    }
+
+    let watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            Ok(event) if !event.kind.is_access() && !event.kind.is_other() => {
+                let _ = event_tx.send(());
+            }
+            Ok(_) => {}
+            Err(e) => warn!("TLS certificate watcher error: {}", e),
+        });
+    let watcher = match watcher {
+        Ok(mut watcher) => {
+            for (dir, mode) in tls.watched() {
+                if let Err(e) = watcher.watch(&dir, mode) {
+                    warn!(
+                        "Cannot watch {} for certificate changes: {}",
+                        dir.display(),
+                        e
+                    );
+                }
+            }
+            Some(watcher)
+        }
+        Err(e) => {
+            warn!(
+                "Cannot watch TLS certificates, reload with SIGHUP only: {}",
+                e
+            );
+            None
+        }
+    };
+
+    tokio::spawn(async move {
+        while event_rx.recv().await.is_some() {
+            tokio::time::sleep(RELOAD_DEBOUNCE).await;
+            while event_rx.try_recv().is_ok() {}
+            match tls.reload() {
+                Ok(()) => info!("TLS certificates reloaded"),
+                Err(e) => warn!(
+                    "Failed to reload TLS certificates, keeping the current ones: {}",
+                    e
+                ),
+            }
+        }
+    });
+    watcher
+}
+
+// certbot 布局中的证书；目录不存在时返回 None
+fn from_cert_dir(
+    dir: &str,
+    host: &str,
+    provider: &CryptoProvider,
+) -> Result<Option<Arc<CertifiedKey>>, String> {
+    let lineage = Path::new(dir).join(host.trim().trim_start_matches("*."));
+    let cert = lineage.join("fullchain.pem");
+    let key = lineage.join("privkey.pem");
+    if !cert.is_file() {
+        return Ok(None);
+    }
+    load(&cert.to_string_lossy(), &key.to_string_lossy(), provider).map(Some)
+}
+
+fn load(cert: &str, key: &str, provider: &CryptoProvider) -> Result<Arc<CertifiedKey>, String> {
+    let pem =
+        std::fs::read(cert).map_err(|e| format!("cannot read certificate {}: {}", cert, e))?;
+    let chain = CertificateDer::pem_slice_iter(&pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid certificate {}: {}", cert, e))?;
+    if chain.is_empty() {
+        return Err(format!("no certificate found in {}", cert));
+    }
+    let pem = std::fs::read(key).map_err(|e| format!("cannot read private key {}: {}", key, e))?;
+    let private_key = PrivateKeyDer::from_pem_slice(&pem)
+        .map_err(|e| format!("invalid private key {}: {}", key, e))?;
+    // 同时检查私钥与证书的公钥是否匹配
+    CertifiedKey::from_der(chain, private_key, provider)
+        .map(Arc::new)
+        .map_err(|e| format!("invalid key pair {} / {}: {}", cert, key, e))
+}
+
+fn load_roots(path: &str) -> Result<RootCertStore, String> {
+    let pem = std::fs::read(path).map_err(|e| format!("cannot read client_ca {}: {}", path, e))?;
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_slice_iter(&pem) {
+        let cert = cert.map_err(|e| format!("invalid client_ca {}: {}", path, e))?;
+        roots
+            .add(cert)
+            .map_err(|e| format!("invalid client_ca {}: {}", path, e))?;
+    }
+    if roots.is_empty() {
+        return Err(format!("no certificate found in client_ca {}", path));
+    }
+    Ok(roots)
+}
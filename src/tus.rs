@@ -0,0 +1,475 @@
+// tus 1.0 断点续传（[upload] tus_endpoint）：支持 creation、termination 与 checksum 扩展；
+// 未完成的上传保存在暂存目录（数据 + .json 描述），连接中断或重启后客户端以 HEAD 查询偏移量继续 PATCH，
+// 完成后移动到 Upload-Metadata 中 path（默认第一个上传前缀）指定的目录
+use crate::upload::{self, Uploader};
+use axum::body::Bytes;
+use axum::extract::Request;
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+pub const VERSION: &str = "1.0.0";
+const EXTENSIONS: &str = "creation,termination,checksum";
+const CHECKSUM_ALGORITHMS: &str = "sha1,sha256";
+const OFFSET_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+// 暂存目录中每个上传的描述
+#[derive(Serialize, Deserialize)]
+struct UploadInfo {
+    length: u64,
+    file_name: String,
+    // 目标目录的 URL 路径（已解码，以 "/" 结尾）
+    dir: String,
+}
+
+pub struct Tus {
+    // 以 "/" 结尾
+    endpoint: String,
+    staging: PathBuf,
+    // 正在 PATCH 的上传，同一上传的并发请求返回 423
+    active: Mutex<HashSet<String>>,
+}
+
+// 请求结束时释放上传的锁
+struct ActiveGuard<'a> {
+    tus: &'a Tus,
+    id: String,
+}
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        self.tus.active.lock().unwrap().remove(&self.id);
+    }
+}
+
+enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha1(h) => h.finalize().to_vec(),
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+        }
+    }
+}
+
+impl Tus {
+    pub fn new(endpoint: &str, staging: &Path) -> Result<Self, String> {
+        if !endpoint.starts_with('/') {
+            return Err(format!("tus endpoint `{}` must start with '/'", endpoint));
+        }
+        std::fs::create_dir_all(staging)
+            .map_err(|e| format!("tus staging dir {}: {}", staging.display(), e))?;
+        Ok(Tus {
+            endpoint: format!("{}/", endpoint.trim_end_matches('/')),
+            staging: staging.to_path_buf(),
+            active: Mutex::new(HashSet::new()),
+        })
+    }
+
+    pub fn matches(&self, decoded: &str) -> bool {
+        decoded.starts_with(&self.endpoint) || decoded == self.endpoint.trim_end_matches('/')
+    }
+
+    fn paths(&self, id: &str) -> (PathBuf, PathBuf) {
+        (
+            self.staging.join(format!("{}.bin", id)),
+            self.staging.join(format!("{}.json", id)),
+        )
+    }
+
+    async fn info(&self, id: &str) -> Option<UploadInfo> {
+        let (_, info_path) = self.paths(id);
+        serde_json::from_slice(&tokio::fs::read(info_path).await.ok()?).ok()
+    }
+
+    async fn offset(&self, id: &str) -> Option<u64> {
+        let (data_path, _) = self.paths(id);
+        Some(tokio::fs::metadata(data_path).await.ok()?.len())
+    }
+
+    pub async fn handle(&self, uploader: &Uploader, req: Request, decoded: &str) -> Response {
+        // OPTIONS 用于能力发现（包括 CORS 预检），不需要认证
+        if req.method() == Method::OPTIONS {
+            let mut response = StatusCode::NO_CONTENT.into_response();
+            let headers = response.headers_mut();
+            headers.insert("tus-version", HeaderValue::from_static(VERSION));
+            headers.insert("tus-extension", HeaderValue::from_static(EXTENSIONS));
+            headers.insert(
+                "tus-checksum-algorithm",
+                HeaderValue::from_static(CHECKSUM_ALGORITHMS),
+            );
+            headers.insert("tus-max-size", HeaderValue::from(uploader.max_size()));
+            return with_resumable(response);
+        }
+        if !uploader.authorized(req.headers()) {
+            return with_resumable(upload::unauthorized());
+        }
+        if req
+            .headers()
+            .get("tus-resumable")
+            .and_then(|v| v.to_str().ok())
+            != Some(VERSION)
+        {
+            let mut response = StatusCode::PRECONDITION_FAILED.into_response();
+            response
+                .headers_mut()
+                .insert("tus-version", HeaderValue::from_static(VERSION));
+            return with_resumable(response);
+        }
+        let id = decoded
+            .strip_prefix(&self.endpoint)
+            .unwrap_or("")
+            .to_string();
+        let response = if id.is_empty() {
+            match *req.method() {
+                Method::POST => self.create(uploader, req.headers()).await,
+                _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+            }
+        } else if !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            StatusCode::NOT_FOUND.into_response()
+        } else {
+            match *req.method() {
+                Method::HEAD => self.head(&id).await,
+                Method::PATCH => self.patch(uploader, &id, req).await,
+                Method::DELETE => self.terminate(&id).await,
+                _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+            }
+        };
+        with_resumable(response)
+    }
+
+    // creation：Upload-Length 必填，Upload-Metadata 中的 filename 必填，path 可选
+    async fn create(&self, uploader: &Uploader, headers: &HeaderMap) -> Response {
+        let Some(length) = header_u64(headers, "upload-length") else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        if length > uploader.max_size() {
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+        }
+        let metadata = parse_metadata(
+            headers
+                .get("upload-metadata")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or(""),
+        );
+        let Some(file_name) = metadata
+            .iter()
+            .find(|(key, _)| key == "filename")
+            .and_then(|(_, value)| upload::file_name(value))
+        else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        let dir = match metadata.iter().find(|(key, _)| key == "path") {
+            Some((_, path)) => format!("/{}/", path.trim_matches('/')).replace("//", "/"),
+            None => uploader.prefixes()[0].clone(),
+        };
+        let path = format!("{}{}", dir, file_name);
+        if !uploader.in_prefix(&path) || uploader.denied(&path) {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+
+        let id = new_id();
+        let (data_path, info_path) = self.paths(&id);
+        let info = UploadInfo {
+            length,
+            file_name,
+            dir,
+        };
+        let created = async {
+            tokio::fs::write(&data_path, b"").await?;
+            tokio::fs::write(&info_path, serde_json::to_vec(&info)?).await
+        }
+        .await;
+        if let Err(e) = created {
+            return upload::server_error("create", &data_path, e);
+        }
+        info!("Created upload {} for {} ({} bytes)", id, path, length);
+        if length == 0 {
+            if let Err(response) = self.finish(uploader, &id, &info).await {
+                return response;
+            }
+        }
+        let mut response = StatusCode::CREATED.into_response();
+        if let Ok(location) = HeaderValue::from_str(&format!("{}{}", self.endpoint, id)) {
+            response.headers_mut().insert(header::LOCATION, location);
+        }
+        response
+    }
+
+    async fn head(&self, id: &str) -> Response {
+        let (Some(info), Some(offset)) = (self.info(id).await, self.offset(id).await) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        let mut response = StatusCode::OK.into_response();
+        let headers = response.headers_mut();
+        headers.insert("upload-offset", HeaderValue::from(offset));
+        headers.insert("upload-length", HeaderValue::from(info.length));
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        response
+    }
+
+    async fn patch(&self, uploader: &Uploader, id: &str, req: Request) -> Response {
+        let content_type = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok());
+        if content_type != Some(OFFSET_CONTENT_TYPE) {
+            return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+        }
+        let Some(offset) = header_u64(req.headers(), "upload-offset") else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        // Upload-Checksum: <算法> <Base64 摘要>
+        let checksum = match req.headers().get("upload-checksum") {
+            Some(value) => {
+                let parsed = value.to_str().ok().and_then(|v| v.split_once(' '));
+                let Some((algorithm, digest)) = parsed else {
+                    return StatusCode::BAD_REQUEST.into_response();
+                };
+                let hasher = match algorithm {
+                    "sha1" => Hasher::Sha1(Sha1::new()),
+                    "sha256" => Hasher::Sha256(Sha256::new()),
+                    _ => return StatusCode::BAD_REQUEST.into_response(),
+                };
+                let Ok(expected) = base64::engine::general_purpose::STANDARD.decode(digest.trim())
+                else {
+                    return StatusCode::BAD_REQUEST.into_response();
+                };
+                Some((hasher, expected))
+            }
+            None => None,
+        };
+
+        if !self.active.lock().unwrap().insert(id.to_string()) {
+            return StatusCode::LOCKED.into_response();
+        }
+        let _guard = ActiveGuard {
+            tus: self,
+            id: id.to_string(),
+        };
+        let (Some(info), Some(current)) = (self.info(id).await, self.offset(id).await) else {
+            return StatusCode::NOT_FOUND.into_response();
+        };
+        if offset != current {
+            return StatusCode::CONFLICT.into_response();
+        }
+
+        let (data_path, _) = self.paths(id);
+        let mut file = match tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&data_path)
+            .await
+        {
+            Ok(file) => file,
+            Err(e) => return upload::server_error("open", &data_path, e),
+        };
+        let (mut hasher, expected) = match checksum {
+            Some((hasher, expected)) => (Some(hasher), Some(expected)),
+            None => (None, None),
+        };
+        let mut body = req.into_body().into_data_stream();
+        let mut written = 0u64;
+        let mut failure = None;
+        while let Some(chunk) = body.next().await {
+            let chunk: Bytes = match chunk {
+                Ok(chunk) => chunk,
+                // 连接中断：无校验时保留已收到的数据，客户端从新的偏移量继续
+                Err(e) => {
+                    warn!("Upload {} interrupted at {}: {}", id, offset + written, e);
+                    failure = Some(StatusCode::BAD_REQUEST.into_response());
+                    break;
+                }
+            };
+            if offset + written + chunk.len() as u64 > info.length {
+                failure = Some(StatusCode::PAYLOAD_TOO_LARGE.into_response());
+                break;
+            }
+            if let Some(hasher) = &mut hasher {
+                hasher.update(&chunk);
+            }
+            if let Err(e) = file.write_all(&chunk).await {
+                failure = Some(upload::server_error("write", &data_path, e));
+                break;
+            }
+            written += chunk.len() as u64;
+        }
+        if let Err(e) = file.sync_data().await {
+            failure.get_or_insert_with(|| upload::server_error("write", &data_path, e));
+        }
+        drop(file);
+        let checksummed = hasher.is_some();
+        let mismatch = failure.is_none()
+            && hasher
+                .zip(expected)
+                .is_some_and(|(hasher, expected)| hasher.finish() != expected);
+        // 校验失败、超出长度、写入出错或带校验的请求中断时，丢弃本次 PATCH 写入的数据
+        let keep = failure
+            .as_ref()
+            .is_some_and(|response| response.status() == StatusCode::BAD_REQUEST)
+            && !checksummed;
+        if mismatch || (failure.is_some() && !keep) {
+            if let Ok(file) = tokio::fs::OpenOptions::new()
+                .write(true)
+                .open(&data_path)
+                .await
+            {
+                let _ = file.set_len(offset).await;
+            }
+        }
+        if mismatch {
+            // 460 Checksum Mismatch（tus checksum 扩展）
+            return StatusCode::from_u16(460)
+                .unwrap_or(StatusCode::BAD_REQUEST)
+                .into_response();
+        }
+        if let Some(response) = failure {
+            return response;
+        }
+
+        let new_offset = offset + written;
+        if new_offset == info.length {
+            if let Err(response) = self.finish(uploader, id, &info).await {
+                return response;
+            }
+        }
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        response
+            .headers_mut()
+            .insert("upload-offset", HeaderValue::from(new_offset));
+        response
+    }
+
+    // termination：删除未完成的上传
+    async fn terminate(&self, id: &str) -> Response {
+        if self.active.lock().unwrap().contains(id) {
+            return StatusCode::LOCKED.into_response();
+        }
+        let (data_path, info_path) = self.paths(id);
+        if tokio::fs::remove_file(&info_path).await.is_err() {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        let _ = tokio::fs::remove_file(&data_path).await;
+        info!("Terminated upload {}", id);
+        StatusCode::NO_CONTENT.into_response()
+    }
+
+    // 上传完成：移动到目标目录（重名时追加序号），暂存目录与站点不在同一文件系统时复制
+    async fn finish(
+        &self,
+        uploader: &Uploader,
+        id: &str,
+        info: &UploadInfo,
+    ) -> Result<(), Response> {
+        let (data_path, info_path) = self.paths(id);
+        let Some(dir) = crate::site::fs_path(uploader.root(), &info.dir) else {
+            return Err(StatusCode::FORBIDDEN.into_response());
+        };
+        if !uploader.contained(&dir.join(&info.file_name)).await {
+            warn!("Refusing upload outside the site directory: {}", info.dir);
+            return Err(StatusCode::FORBIDDEN.into_response());
+        }
+        uploader.create_dirs(&dir).await?;
+        let target = upload::available_name(&dir, &info.file_name).await;
+        if tokio::fs::rename(&data_path, &target).await.is_err() {
+            let temp = dir.join(format!(".upload-{}.tmp", id));
+            let copied = async {
+                tokio::fs::copy(&data_path, &temp).await?;
+                tokio::fs::File::open(&temp).await?.sync_all().await
+            };
+            if let Err(e) = copied.await {
+                let _ = tokio::fs::remove_file(&temp).await;
+                return Err(upload::server_error("copy", &temp, e));
+            }
+            tokio::fs::rename(&temp, &target)
+                .await
+                .map_err(|e| upload::server_error("rename", &target, e))?;
+            let _ = tokio::fs::remove_file(&data_path).await;
+        }
+        upload::sync_dir(&dir).await;
+        let _ = tokio::fs::remove_file(&info_path).await;
+        info!(
+            "Completed upload {} -> {}{} ({} bytes)",
+            id,
+            info.dir,
+            target
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            info.length
+        );
+        Ok(())
+    }
+}
+
+fn with_resumable(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert("tus-resumable", HeaderValue::from_static(VERSION));
+    response
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+// "filename d29ybGQ=,path L3VwbG9hZHM=" -> [(key, value)]；没有值的键对应空字符串
+fn parse_metadata(value: &str) -> Vec<(String, String)> {
+    value
+        .split(',')
+        .filter_map(|pair| {
+            let mut parts = pair.trim().splitn(2, ' ');
+            let key = parts.next().filter(|key| !key.is_empty())?;
+            let value = match parts.next() {
+                Some(encoded) => base64::engine::general_purpose::STANDARD
+                    .decode(encoded.trim())
+                    .ok()
+                    .and_then(|bytes| String::from_utf8(bytes).ok())?,
+                None => String::new(),
+            };
+            Some((key.to_string(), value))
+        })
+        .collect()
+}
+
+// 进程号、时间与序号的摘要，不可预测且不会重复
+fn new_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let seed = format!(
+        "{}-{}-{}",
+        std::process::id(),
+        nanos,
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    );
+    Sha256::digest(seed.as_bytes())[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
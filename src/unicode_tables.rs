@@ -0,0 +1,1450 @@
+// 规范分解与组合类（Canonical_Combining_Class），由 Python unicodedata（Unicode 14.0.0）生成。
+// 分解已递归展开；韩文音节按算法分解，CJK 兼容表意文字不在表中
+
+pub const DECOMPOSITIONS: &[(char, &[char])] = &[
+    ('\u{c0}', &['\u{41}', '\u{300}']),
+    ('\u{c1}', &['\u{41}', '\u{301}']),
+    ('\u{c2}', &['\u{41}', '\u{302}']),
+    ('\u{c3}', &['\u{41}', '\u{303}']),
+    ('\u{c4}', &['\u{41}', '\u{308}']),
+    ('\u{c5}', &['\u{41}', '\u{30a}']),
+    ('\u{c7}', &['\u{43}', '\u{327}']),
+    ('\u{c8}', &['\u{45}', '\u{300}']),
+    ('\u{c9}', &['\u{45}', '\u{301}']),
+    ('\u{ca}', &['\u{45}', '\u{302}']),
+    ('\u{cb}', &['\u{45}', '\u{308}']),
+    ('\u{cc}', &['\u{49}', '\u{300}']),
+    ('\u{cd}', &['\u{49}', '\u{301}']),
+    ('\u{ce}', &['\u{49}', '\u{302}']),
+    ('\u{cf}', &['\u{49}', '\u{308}']),
+    ('\u{d1}', &['\u{4e}', '\u{303}']),
+    ('\u{d2}', &['\u{4f}', '\u{300}']),
+    ('\u{d3}', &['\u{4f}', '\u{301}']),
+    ('\u{d4}', &['\u{4f}', '\u{302}']),
+    ('\u{d5}', &['\u{4f}', '\u{303}']),
+    ('\u{d6}', &['\u{4f}', '\u{308}']),
+    ('\u{d9}', &['\u{55}', '\u{300}']),
+    ('\u{da}', &['\u{55}', '\u{301}']),
+    ('\u{db}', &['\u{55}', '\u{302}']),
+    ('\u{dc}', &['\u{55}', '\u{308}']),
+    ('\u{dd}', &['\u{59}', '\u{301}']),
+    ('\u{e0}', &['\u{61}', '\u{300}']),
+    ('\u{e1}', &['\u{61}', '\u{301}']),
+    ('\u{e2}', &['\u{61}', '\u{302}']),
+    ('\u{e3}', &['\u{61}', '\u{303}']),
+    ('\u{e4}', &['\u{61}', '\u{308}']),
+    ('\u{e5}', &['\u{61}', '\u{30a}']),
+    ('\u{e7}', &['\u{63}', '\u{327}']),
+    ('\u{e8}', &['\u{65}', '\u{300}']),
+    ('\u{e9}', &['\u{65}', '\u{301}']),
+    ('\u{ea}', &['\u{65}', '\u{302}']),
+    ('\u{eb}', &['\u{65}', '\u{308}']),
+    ('\u{ec}', &['\u{69}', '\u{300}']),
+    ('\u{ed}', &['\u{69}', '\u{301}']),
+    ('\u{ee}', &['\u{69}', '\u{302}']),
+    ('\u{ef}', &['\u{69}', '\u{308}']),
+    ('\u{f1}', &['\u{6e}', '\u{303}']),
+    ('\u{f2}', &['\u{6f}', '\u{300}']),
+    ('\u{f3}', &['\u{6f}', '\u{301}']),
+    ('\u{f4}', &['\u{6f}', '\u{302}']),
+    ('\u{f5}', &['\u{6f}', '\u{303}']),
+    ('\u{f6}', &['\u{6f}', '\u{308}']),
+    ('\u{f9}', &['\u{75}', '\u{300}']),
+    ('\u{fa}', &['\u{75}', '\u{301}']),
+    ('\u{fb}', &['\u{75}', '\u{302}']),
+    ('\u{fc}', &['\u{75}', '\u{308}']),
+    ('\u{fd}', &['\u{79}', '\u{301}']),
+    ('\u{ff}', &['\u{79}', '\u{308}']),
+    ('\u{100}', &['\u{41}', '\u{304}']),
+    ('\u{101}', &['\u{61}', '\u{304}']),
+    ('\u{102}', &['\u{41}', '\u{306}']),
+    ('\u{103}', &['\u{61}', '\u{306}']),
+    ('\u{104}', &['\u{41}', '\u{328}']),
+    ('\u{105}', &['\u{61}', '\u{328}']),
+    ('\u{106}', &['\u{43}', '\u{301}']),
+    ('\u{107}', &['\u{63}', '\u{301}']),
+    ('\u{108}', &['\u{43}', '\u{302}']),
+    ('\u{109}', &['\u{63}', '\u{302}']),
+    ('\u{10a}', &['\u{43}', '\u{307}']),
+    ('\u{10b}', &['\u{63}', '\u{307}']),
+    ('\u{10c}', &['\u{43}', '\u{30c}']),
+    ('\u{10d}', &['\u{63}', '\u{30c}']),
+    ('\u{10e}', &['\u{44}', '\u{30c}']),
+    ('\u{10f}', &['\u{64}', '\u{30c}']),
+    ('\u{112}', &['\u{45}', '\u{304}']),
+    ('\u{113}', &['\u{65}', '\u{304}']),
+    ('\u{114}', &['\u{45}', '\u{306}']),
+    ('\u{115}', &['\u{65}', '\u{306}']),
+    ('\u{116}', &['\u{45}', '\u{307}']),
+    ('\u{117}', &['\u{65}', '\u{307}']),
+    ('\u{118}', &['\u{45}', '\u{328}']),
+    ('\u{119}', &['\u{65}', '\u{328}']),
+    ('\u{11a}', &['\u{45}', '\u{30c}']),
+    ('\u{11b}', &['\u{65}', '\u{30c}']),
+    ('\u{11c}', &['\u{47}', '\u{302}']),
+    ('\u{11d}', &['\u{67}', '\u{302}']),
+    ('\u{11e}', &['\u{47}', '\u{306}']),
+    ('\u{11f}', &['\u{67}', '\u{306}']),
+    ('\u{120}', &['\u{47}', '\u{307}']),
+    ('\u{121}', &['\u{67}', '\u{307}']),
+    ('\u{122}', &['\u{47}', '\u{327}']),
+    ('\u{123}', &['\u{67}', '\u{327}']),
+    ('\u{124}', &['\u{48}', '\u{302}']),
+    ('\u{125}', &['\u{68}', '\u{302}']),
+    ('\u{128}', &['\u{49}', '\u{303}']),
+    ('\u{129}', &['\u{69}', '\u{303}']),
+    ('\u{12a}', &['\u{49}', '\u{304}']),
+    ('\u{12b}', &['\u{69}', '\u{304}']),
+    ('\u{12c}', &['\u{49}', '\u{306}']),
+    ('\u{12d}', &['\u{69}', '\u{306}']),
+    ('\u{12e}', &['\u{49}', '\u{328}']),
+    ('\u{12f}', &['\u{69}', '\u{328}']),
+    ('\u{130}', &['\u{49}', '\u{307}']),
+    ('\u{134}', &['\u{4a}', '\u{302}']),
+    ('\u{135}', &['\u{6a}', '\u{302}']),
+    ('\u{136}', &['\u{4b}', '\u{327}']),
+    ('\u{137}', &['\u{6b}', '\u{327}']),
+    ('\u{139}', &['\u{4c}', '\u{301}']),
+    ('\u{13a}', &['\u{6c}', '\u{301}']),
+    ('\u{13b}', &['\u{4c}', '\u{327}']),
+    ('\u{13c}', &['\u{6c}', '\u{327}']),
+    ('\u{13d}', &['\u{4c}', '\u{30c}']),
+    ('\u{13e}', &['\u{6c}', '\u{30c}']),
+    ('\u{143}', &['\u{4e}', '\u{301}']),
+    ('\u{144}', &['\u{6e}', '\u{301}']),
+    ('\u{145}', &['\u{4e}', '\u{327}']),
+    ('\u{146}', &['\u{6e}', '\u{327}']),
+    ('\u{147}', &['\u{4e}', '\u{30c}']),
+    ('\u{148}', &['\u{6e}', '\u{30c}']),
+    ('\u{14c}', &['\u{4f}', '\u{304}']),
+    ('\u{14d}', &['\u{6f}', '\u{304}']),
+    ('\u{14e}', &['\u{4f}', '\u{306}']),
+    ('\u{14f}', &['\u{6f}', '\u{306}']),
+    ('\u{150}', &['\u{4f}', '\u{30b}']),
+    ('\u{151}', &['\u{6f}', '\u{30b}']),
+    ('\u{154}', &['\u{52}', '\u{301}']),
+    ('\u{155}', &['\u{72}', '\u{301}']),
+    ('\u{156}', &['\u{52}', '\u{327}']),
+    ('\u{157}', &['\u{72}', '\u{327}']),
+    ('\u{158}', &['\u{52}', '\u{30c}']),
+    ('\u{159}', &['\u{72}', '\u{30c}']),
+    ('\u{15a}', &['\u{53}', '\u{301}']),
+    ('\u{15b}', &['\u{73}', '\u{301}']),
+    ('\u{15c}', &['\u{53}', '\u{302}']),
+    ('\u{15d}', &['\u{73}', '\u{302}']),
+    ('\u{15e}', &['\u{53}', '\u{327}']),
+    ('\u{15f}', &['\u{73}', '\u{327}']),
+    ('\u{160}', &['\u{53}', '\u{30c}']),
+    ('\u{161}', &['\u{73}', '\u{30c}']),
+    ('\u{162}', &['\u{54}', '\u{327}']),
+    ('\u{163}', &['\u{74}', '\u{327}']),
+    ('\u{164}', &['\u{54}', '\u{30c}']),
+    ('\u{165}', &['\u{74}', '\u{30c}']),
+    ('\u{168}', &['\u{55}', '\u{303}']),
+    ('\u{169}', &['\u{75}', '\u{303}']),
+    ('\u{16a}', &['\u{55}', '\u{304}']),
+    ('\u{16b}', &['\u{75}', '\u{304}']),
+    ('\u{16c}', &['\u{55}', '\u{306}']),
+    ('\u{16d}', &['\u{75}', '\u{306}']),
+    ('\u{16e}', &['\u{55}', '\u{30a}']),
+    ('\u{16f}', &['\u{75}', '\u{30a}']),
+    ('\u{170}', &['\u{55}', '\u{30b}']),
+    ('\u{171}', &['\u{75}', '\u{30b}']),
+    ('\u{172}', &['\u{55}', '\u{328}']),
+    ('\u{173}', &['\u{75}', '\u{328}']),
+    ('\u{174}', &['\u{57}', '\u{302}']),
+    ('\u{175}', &['\u{77}', '\u{302}']),
+    ('\u{176}', &['\u{59}', '\u{302}']),
+    ('\u{177}', &['\u{79}', '\u{302}']),
+    ('\u{178}', &['\u{59}', '\u{308}']),
+    ('\u{179}', &['\u{5a}', '\u{301}']),
+    ('\u{17a}', &['\u{7a}', '\u{301}']),
+    ('\u{17b}', &['\u{5a}', '\u{307}']),
+    ('\u{17c}', &['\u{7a}', '\u{307}']),
+    ('\u{17d}', &['\u{5a}', '\u{30c}']),
+    ('\u{17e}', &['\u{7a}', '\u{30c}']),
+    ('\u{1a0}', &['\u{4f}', '\u{31b}']),
+    ('\u{1a1}', &['\u{6f}', '\u{31b}']),
+    ('\u{1af}', &['\u{55}', '\u{31b}']),
+    ('\u{1b0}', &['\u{75}', '\u{31b}']),
+    ('\u{1cd}', &['\u{41}', '\u{30c}']),
+    ('\u{1ce}', &['\u{61}', '\u{30c}']),
+    ('\u{1cf}', &['\u{49}', '\u{30c}']),
+    ('\u{1d0}', &['\u{69}', '\u{30c}']),
+    ('\u{1d1}', &['\u{4f}', '\u{30c}']),
+    ('\u{1d2}', &['\u{6f}', '\u{30c}']),
+    ('\u{1d3}', &['\u{55}', '\u{30c}']),
+    ('\u{1d4}', &['\u{75}', '\u{30c}']),
+    ('\u{1d5}', &['\u{55}', '\u{308}', '\u{304}']),
+    ('\u{1d6}', &['\u{75}', '\u{308}', '\u{304}']),
+    ('\u{1d7}', &['\u{55}', '\u{308}', '\u{301}']),
+    ('\u{1d8}', &['\u{75}', '\u{308}', '\u{301}']),
+    ('\u{1d9}', &['\u{55}', '\u{308}', '\u{30c}']),
+    ('\u{1da}', &['\u{75}', '\u{308}', '\u{30c}']),
+    ('\u{1db}', &['\u{55}', '\u{308}', '\u{300}']),
+    ('\u{1dc}', &['\u{75}', '\u{308}', '\u{300}']),
+    ('\u{1de}', &['\u{41}', '\u{308}', '\u{304}']),
+    ('\u{1df}', &['\u{61}', '\u{308}', '\u{304}']),
+    ('\u{1e0}', &['\u{41}', '\u{307}', '\u{304}']),
+    ('\u{1e1}', &['\u{61}', '\u{307}', '\u{304}']),
+    ('\u{1e2}', &['\u{c6}', '\u{304}']),
+    ('\u{1e3}', &['\u{e6}', '\u{304}']),
+    ('\u{1e6}', &['\u{47}', '\u{30c}']),
+    ('\u{1e7}', &['\u{67}', '\u{30c}']),
+    ('\u{1e8}', &['\u{4b}', '\u{30c}']),
+    ('\u{1e9}', &['\u{6b}', '\u{30c}']),
+    ('\u{1ea}', &['\u{4f}', '\u{328}']),
+    ('\u{1eb}', &['\u{6f}', '\u{328}']),
+    ('\u{1ec}', &['\u{4f}', '\u{328}', '\u{304}']),
+    ('\u{1ed}', &['\u{6f}', '\u{328}', '\u{304}']),
+    ('\u{1ee}', &['\u{1b7}', '\u{30c}']),
+    ('\u{1ef}', &['\u{292}', '\u{30c}']),
+    ('\u{1f0}', &['\u{6a}', '\u{30c}']),
+    ('\u{1f4}', &['\u{47}', '\u{301}']),
+    ('\u{1f5}', &['\u{67}', '\u{301}']),
+    ('\u{1f8}', &['\u{4e}', '\u{300}']),
+    ('\u{1f9}', &['\u{6e}', '\u{300}']),
+    ('\u{1fa}', &['\u{41}', '\u{30a}', '\u{301}']),
+    ('\u{1fb}', &['\u{61}', '\u{30a}', '\u{301}']),
+    ('\u{1fc}', &['\u{c6}', '\u{301}']),
+    ('\u{1fd}', &['\u{e6}', '\u{301}']),
+    ('\u{1fe}', &['\u{d8}', '\u{301}']),
+    ('\u{1ff}', &['\u{f8}', '\u{301}']),
+    ('\u{200}', &['\u{41}', '\u{30f}']),
+    ('\u{201}', &['\u{61}', '\u{30f}']),
+    ('\u{202}', &['\u{41}', '\u{311}']),
+    ('\u{203}', &['\u{61}', '\u{311}']),
+    ('\u{204}', &['\u{45}', '\u{30f}']),
+    ('\u{205}', &['\u{65}', '\u{30f}']),
+    ('\u{206}', &['\u{45}', '\u{311}']),
+    ('\u{207}', &['\u{65}', '\u{311}']),
+    ('\u{208}', &['\u{49}', '\u{30f}']),
+    ('\u{209}', &['\u{69}', '\u{30f}']),
+    ('\u{20a}', &['\u{49}', '\u{311}']),
+    ('\u{20b}', &['\u{69}', '\u{311}']),
+    ('\u{20c}', &['\u{4f}', '\u{30f}']),
+    ('\u{20d}', &['\u{6f}', '\u{30f}']),
+    ('\u{20e}', &['\u{4f}', '\u{311}']),
+    ('\u{20f}', &['\u{6f}', '\u{311}']),
+    ('\u{210}', &['\u{52}', '\u{30f}']),
+    ('\u{211}', &['\u{72}', '\u{30f}']),
+    ('\u{212}', &['\u{52}', '\u{311}']),
+    ('\u{213}', &['\u{72}', '\u{311}']),
+    ('\u{214}', &['\u{55}', '\u{30f}']),
+    ('\u{215}', &['\u{75}', '\u{30f}']),
+    ('\u{216}', &['\u{55}', '\u{311}']),
+    ('\u{217}', &['\u{75}', '\u{311}']),
+    ('\u{218}', &['\u{53}', '\u{326}']),
+    ('\u{219}', &['\u{73}', '\u{326}']),
+    ('\u{21a}', &['\u{54}', '\u{326}']),
+    ('\u{21b}', &['\u{74}', '\u{326}']),
+    ('\u{21e}', &['\u{48}', '\u{30c}']),
+    ('\u{21f}', &['\u{68}', '\u{30c}']),
+    ('\u{226}', &['\u{41}', '\u{307}']),
+    ('\u{227}', &['\u{61}', '\u{307}']),
+    ('\u{228}', &['\u{45}', '\u{327}']),
+    ('\u{229}', &['\u{65}', '\u{327}']),
+    ('\u{22a}', &['\u{4f}', '\u{308}', '\u{304}']),
+    ('\u{22b}', &['\u{6f}', '\u{308}', '\u{304}']),
+    ('\u{22c}', &['\u{4f}', '\u{303}', '\u{304}']),
+    ('\u{22d}', &['\u{6f}', '\u{303}', '\u{304}']),
+    ('\u{22e}', &['\u{4f}', '\u{307}']),
+    ('\u{22f}', &['\u{6f}', '\u{307}']),
+    ('\u{230}', &['\u{4f}', '\u{307}', '\u{304}']),
+    ('\u{231}', &['\u{6f}', '\u{307}', '\u{304}']),
+    ('\u{232}', &['\u{59}', '\u{304}']),
+    ('\u{233}', &['\u{79}', '\u{304}']),
+    ('\u{340}', &['\u{300}']),
+    ('\u{341}', &['\u{301}']),
+    ('\u{343}', &['\u{313}']),
+    ('\u{344}', &['\u{308}', '\u{301}']),
+    ('\u{374}', &['\u{2b9}']),
+    ('\u{37e}', &['\u{3b}']),
+    ('\u{385}', &['\u{a8}', '\u{301}']),
+    ('\u{386}', &['\u{391}', '\u{301}']),
+    ('\u{387}', &['\u{b7}']),
+    ('\u{388}', &['\u{395}', '\u{301}']),
+    ('\u{389}', &['\u{397}', '\u{301}']),
+    ('\u{38a}', &['\u{399}', '\u{301}']),
+    ('\u{38c}', &['\u{39f}', '\u{301}']),
+    ('\u{38e}', &['\u{3a5}', '\u{301}']),
+    ('\u{38f}', &['\u{3a9}', '\u{301}']),
+    ('\u{390}', &['\u{3b9}', '\u{308}', '\u{301}']),
+    ('\u{3aa}', &['\u{399}', '\u{308}']),
+    ('\u{3ab}', &['\u{3a5}', '\u{308}']),
+    ('\u{3ac}', &['\u{3b1}', '\u{301}']),
+    ('\u{3ad}', &['\u{3b5}', '\u{301}']),
+    ('\u{3ae}', &['\u{3b7}', '\u{301}']),
+    ('\u{3af}', &['\u{3b9}', '\u{301}']),
+    ('\u{3b0}', &['\u{3c5}', '\u{308}', '\u{301}']),
+    ('\u{3ca}', &['\u{3b9}', '\u{308}']),
+    ('\u{3cb}', &['\u{3c5}', '\u{308}']),
+    ('\u{3cc}', &['\u{3bf}', '\u{301}']),
+    ('\u{3cd}', &['\u{3c5}', '\u{301}']),
+    ('\u{3ce}', &['\u{3c9}', '\u{301}']),
+    ('\u{3d3}', &['\u{3d2}', '\u{301}']),
+    ('\u{3d4}', &['\u{3d2}', '\u{308}']),
+    ('\u{400}', &['\u{415}', '\u{300}']),
+    ('\u{401}', &['\u{415}', '\u{308}']),
+    ('\u{403}', &['\u{413}', '\u{301}']),
+    ('\u{407}', &['\u{406}', '\u{308}']),
+    ('\u{40c}', &['\u{41a}', '\u{301}']),
+    ('\u{40d}', &['\u{418}', '\u{300}']),
+    ('\u{40e}', &['\u{423}', '\u{306}']),
+    ('\u{419}', &['\u{418}', '\u{306}']),
+    ('\u{439}', &['\u{438}', '\u{306}']),
+    ('\u{450}', &['\u{435}', '\u{300}']),
+    ('\u{451}', &['\u{435}', '\u{308}']),
+    ('\u{453}', &['\u{433}', '\u{301}']),
+    ('\u{457}', &['\u{456}', '\u{308}']),
+    ('\u{45c}', &['\u{43a}', '\u{301}']),
+    ('\u{45d}', &['\u{438}', '\u{300}']),
+    ('\u{45e}', &['\u{443}', '\u{306}']),
+    ('\u{476}', &['\u{474}', '\u{30f}']),
+    ('\u{477}', &['\u{475}', '\u{30f}']),
+    ('\u{4c1}', &['\u{416}', '\u{306}']),
+    ('\u{4c2}', &['\u{436}', '\u{306}']),
+    ('\u{4d0}', &['\u{410}', '\u{306}']),
+    ('\u{4d1}', &['\u{430}', '\u{306}']),
+    ('\u{4d2}', &['\u{410}', '\u{308}']),
+    ('\u{4d3}', &['\u{430}', '\u{308}']),
+    ('\u{4d6}', &['\u{415}', '\u{306}']),
+    ('\u{4d7}', &['\u{435}', '\u{306}']),
+    ('\u{4da}', &['\u{4d8}', '\u{308}']),
+    ('\u{4db}', &['\u{4d9}', '\u{308}']),
+    ('\u{4dc}', &['\u{416}', '\u{308}']),
+    ('\u{4dd}', &['\u{436}', '\u{308}']),
+    ('\u{4de}', &['\u{417}', '\u{308}']),
+    ('\u{4df}', &['\u{437}', '\u{308}']),
+    ('\u{4e2}', &['\u{418}', '\u{304}']),
+    ('\u{4e3}', &['\u{438}', '\u{304}']),
+    ('\u{4e4}', &['\u{418}', '\u{308}']),
+    ('\u{4e5}', &['\u{438}', '\u{308}']),
+    ('\u{4e6}', &['\u{41e}', '\u{308}']),
+    ('\u{4e7}', &['\u{43e}', '\u{308}']),
+    ('\u{4ea}', &['\u{4e8}', '\u{308}']),
+    ('\u{4eb}', &['\u{4e9}', '\u{308}']),
+    ('\u{4ec}', &['\u{42d}', '\u{308}']),
+    ('\u{4ed}', &['\u{44d}', '\u{308}']),
+    ('\u{4ee}', &['\u{423}', '\u{304}']),
+    ('\u{4ef}', &['\u{443}', '\u{304}']),
+    ('\u{4f0}', &['\u{423}', '\u{308}']),
+    ('\u{4f1}', &['\u{443}', '\u{308}']),
+    ('\u{4f2}', &['\u{423}', '\u{30b}']),
+    ('\u{4f3}', &['\u{443}', '\u{30b}']),
+    ('\u{4f4}', &['\u{427}', '\u{308}']),
+    ('\u{4f5}', &['\u{447}', '\u{308}']),
+    ('\u{4f8}', &['\u{42b}', '\u{308}']),
+    ('\u{4f9}', &['\u{44b}', '\u{308}']),
+    ('\u{622}', &['\u{627}', '\u{653}']),
+    ('\u{623}', &['\u{627}', '\u{654}']),
+    ('\u{624}', &['\u{648}', '\u{654}']),
+    ('\u{625}', &['\u{627}', '\u{655}']),
+    ('\u{626}', &['\u{64a}', '\u{654}']),
+    ('\u{6c0}', &['\u{6d5}', '\u{654}']),
+    ('\u{6c2}', &['\u{6c1}', '\u{654}']),
+    ('\u{6d3}', &['\u{6d2}', '\u{654}']),
+    ('\u{929}', &['\u{928}', '\u{93c}']),
+    ('\u{931}', &['\u{930}', '\u{93c}']),
+    ('\u{934}', &['\u{933}', '\u{93c}']),
+    ('\u{958}', &['\u{915}', '\u{93c}']),
+    ('\u{959}', &['\u{916}', '\u{93c}']),
+    ('\u{95a}', &['\u{917}', '\u{93c}']),
+    ('\u{95b}', &['\u{91c}', '\u{93c}']),
+    ('\u{95c}', &['\u{921}', '\u{93c}']),
+    ('\u{95d}', &['\u{922}', '\u{93c}']),
+    ('\u{95e}', &['\u{92b}', '\u{93c}']),
+    ('\u{95f}', &['\u{92f}', '\u{93c}']),
+    ('\u{9cb}', &['\u{9c7}', '\u{9be}']),
+    ('\u{9cc}', &['\u{9c7}', '\u{9d7}']),
+    ('\u{9dc}', &['\u{9a1}', '\u{9bc}']),
+    ('\u{9dd}', &['\u{9a2}', '\u{9bc}']),
+    ('\u{9df}', &['\u{9af}', '\u{9bc}']),
+    ('\u{a33}', &['\u{a32}', '\u{a3c}']),
+    ('\u{a36}', &['\u{a38}', '\u{a3c}']),
+    ('\u{a59}', &['\u{a16}', '\u{a3c}']),
+    ('\u{a5a}', &['\u{a17}', '\u{a3c}']),
+    ('\u{a5b}', &['\u{a1c}', '\u{a3c}']),
+    ('\u{a5e}', &['\u{a2b}', '\u{a3c}']),
+    ('\u{b48}', &['\u{b47}', '\u{b56}']),
+    ('\u{b4b}', &['\u{b47}', '\u{b3e}']),
+    ('\u{b4c}', &['\u{b47}', '\u{b57}']),
+    ('\u{b5c}', &['\u{b21}', '\u{b3c}']),
+    ('\u{b5d}', &['\u{b22}', '\u{b3c}']),
+    ('\u{b94}', &['\u{b92}', '\u{bd7}']),
+    ('\u{bca}', &['\u{bc6}', '\u{bbe}']),
+    ('\u{bcb}', &['\u{bc7}', '\u{bbe}']),
+    ('\u{bcc}', &['\u{bc6}', '\u{bd7}']),
+    ('\u{c48}', &['\u{c46}', '\u{c56}']),
+    ('\u{cc0}', &['\u{cbf}', '\u{cd5}']),
+    ('\u{cc7}', &['\u{cc6}', '\u{cd5}']),
+    ('\u{cc8}', &['\u{cc6}', '\u{cd6}']),
+    ('\u{cca}', &['\u{cc6}', '\u{cc2}']),
+    ('\u{ccb}', &['\u{cc6}', '\u{cc2}', '\u{cd5}']),
+    ('\u{d4a}', &['\u{d46}', '\u{d3e}']),
+    ('\u{d4b}', &['\u{d47}', '\u{d3e}']),
+    ('\u{d4c}', &['\u{d46}', '\u{d57}']),
+    ('\u{dda}', &['\u{dd9}', '\u{dca}']),
+    ('\u{ddc}', &['\u{dd9}', '\u{dcf}']),
+    ('\u{ddd}', &['\u{dd9}', '\u{dcf}', '\u{dca}']),
+    ('\u{dde}', &['\u{dd9}', '\u{ddf}']),
+    ('\u{f43}', &['\u{f42}', '\u{fb7}']),
+    ('\u{f4d}', &['\u{f4c}', '\u{fb7}']),
+    ('\u{f52}', &['\u{f51}', '\u{fb7}']),
+    ('\u{f57}', &['\u{f56}', '\u{fb7}']),
+    ('\u{f5c}', &['\u{f5b}', '\u{fb7}']),
+    ('\u{f69}', &['\u{f40}', '\u{fb5}']),
+    ('\u{f73}', &['\u{f71}', '\u{f72}']),
+    ('\u{f75}', &['\u{f71}', '\u{f74}']),
+    ('\u{f76}', &['\u{fb2}', '\u{f80}']),
+    ('\u{f78}', &['\u{fb3}', '\u{f80}']),
+    ('\u{f81}', &['\u{f71}', '\u{f80}']),
+    ('\u{f93}', &['\u{f92}', '\u{fb7}']),
+    ('\u{f9d}', &['\u{f9c}', '\u{fb7}']),
+    ('\u{fa2}', &['\u{fa1}', '\u{fb7}']),
+    ('\u{fa7}', &['\u{fa6}', '\u{fb7}']),
+    ('\u{fac}', &['\u{fab}', '\u{fb7}']),
+    ('\u{fb9}', &['\u{f90}', '\u{fb5}']),
+    ('\u{1026}', &['\u{1025}', '\u{102e}']),
+    ('\u{1b06}', &['\u{1b05}', '\u{1b35}']),
+    ('\u{1b08}', &['\u{1b07}', '\u{1b35}']),
+    ('\u{1b0a}', &['\u{1b09}', '\u{1b35}']),
+    ('\u{1b0c}', &['\u{1b0b}', '\u{1b35}']),
+    ('\u{1b0e}', &['\u{1b0d}', '\u{1b35}']),
+    ('\u{1b12}', &['\u{1b11}', '\u{1b35}']),
+    ('\u{1b3b}', &['\u{1b3a}', '\u{1b35}']),
+    ('\u{1b3d}', &['\u{1b3c}', '\u{1b35}']),
+    ('\u{1b40}', &['\u{1b3e}', '\u{1b35}']),
+    ('\u{1b41}', &['\u{1b3f}', '\u{1b35}']),
+    ('\u{1b43}', &['\u{1b42}', '\u{1b35}']),
+    ('\u{1e00}', &['\u{41}', '\u{325}']),
+    ('\u{1e01}', &['\u{61}', '\u{325}']),
+    ('\u{1e02}', &['\u{42}', '\u{307}']),
+    ('\u{1e03}', &['\u{62}', '\u{307}']),
+    ('\u{1e04}', &['\u{42}', '\u{323}']),
+    ('\u{1e05}', &['\u{62}', '\u{323}']),
+    ('\u{1e06}', &['\u{42}', '\u{331}']),
+    ('\u{1e07}', &['\u{62}', '\u{331}']),
+    ('\u{1e08}', &['\u{43}', '\u{327}', '\u{301}']),
+    ('\u{1e09}', &['\u{63}', '\u{327}', '\u{301}']),
+    ('\u{1e0a}', &['\u{44}', '\u{307}']),
+    ('\u{1e0b}', &['\u{64}', '\u{307}']),
+    ('\u{1e0c}', &['\u{44}', '\u{323}']),
+    ('\u{1e0d}', &['\u{64}', '\u{323}']),
+    ('\u{1e0e}', &['\u{44}', '\u{331}']),
+    ('\u{1e0f}', &['\u{64}', '\u{331}']),
+    ('\u{1e10}', &['\u{44}', '\u{327}']),
+    ('\u{1e11}', &['\u{64}', '\u{327}']),
+    ('\u{1e12}', &['\u{44}', '\u{32d}']),
+    ('\u{1e13}', &['\u{64}', '\u{32d}']),
+    ('\u{1e14}', &['\u{45}', '\u{304}', '\u{300}']),
+    ('\u{1e15}', &['\u{65}', '\u{304}', '\u{300}']),
+    ('\u{1e16}', &['\u{45}', '\u{304}', '\u{301}']),
+    ('\u{1e17}', &['\u{65}', '\u{304}', '\u{301}']),
+    ('\u{1e18}', &['\u{45}', '\u{32d}']),
+    ('\u{1e19}', &['\u{65}', '\u{32d}']),
+    ('\u{1e1a}', &['\u{45}', '\u{330}']),
+    ('\u{1e1b}', &['\u{65}', '\u{330}']),
+    ('\u{1e1c}', &['\u{45}', '\u{327}', '\u{306}']),
+    ('\u{1e1d}', &['\u{65}', '\u{327}', '\u{306}']),
+    ('\u{1e1e}', &['\u{46}', '\u{307}']),
+    ('\u{1e1f}', &['\u{66}', '\u{307}']),
+    ('\u{1e20}', &['\u{47}', '\u{304}']),
+    ('\u{1e21}', &['\u{67}', '\u{304}']),
+    ('\u{1e22}', &['\u{48}', '\u{307}']),
+    ('\u{1e23}', &['\u{68}', '\u{307}']),
+    ('\u{1e24}', &['\u{48}', '\u{323}']),
+    ('\u{1e25}', &['\u{68}', '\u{323}']),
+    ('\u{1e26}', &['\u{48}', '\u{308}']),
+    ('\u{1e27}', &['\u{68}', '\u{308}']),
+    ('\u{1e28}', &['\u{48}', '\u{327}']),
+    ('\u{1e29}', &['\u{68}', '\u{327}']),
+    ('\u{1e2a}', &['\u{48}', '\u{32e}']),
+    ('\u{1e2b}', &['\u{68}', '\u{32e}']),
+    ('\u{1e2c}', &['\u{49}', '\u{330}']),
+    ('\u{1e2d}', &['\u{69}', '\u{330}']),
+    ('\u{1e2e}', &['\u{49}', '\u{308}', '\u{301}']),
+    ('\u{1e2f}', &['\u{69}', '\u{308}', '\u{301}']),
+    ('\u{1e30}', &['\u{4b}', '\u{301}']),
+    ('\u{1e31}', &['\u{6b}', '\u{301}']),
+    ('\u{1e32}', &['\u{4b}', '\u{323}']),
+    ('\u{1e33}', &['\u{6b}', '\u{323}']),
+    ('\u{1e34}', &['\u{4b}', '\u{331}']),
+    ('\u{1e35}', &['\u{6b}', '\u{331}']),
+    ('\u{1e36}', &['\u{4c}', '\u{323}']),
+    ('\u{1e37}', &['\u{6c}', '\u{323}']),
+    ('\u{1e38}', &['\u{4c}', '\u{323}', '\u{304}']),
+    ('\u{1e39}', &['\u{6c}', '\u{323}', '\u{304}']),
+    ('\u{1e3a}', &['\u{4c}', '\u{331}']),
+    ('\u{1e3b}', &['\u{6c}', '\u{331}']),
+    ('\u{1e3c}', &['\u{4c}', '\u{32d}']),
+    ('\u{1e3d}', &['\u{6c}', '\u{32d}']),
+    ('\u{1e3e}', &['\u{4d}', '\u{301}']),
+    ('\u{1e3f}', &['\u{6d}', '\u{301}']),
+    ('\u{1e40}', &['\u{4d}', '\u{307}']),
+    ('\u{1e41}', &['\u{6d}', '\u{307}']),
+    ('\u{1e42}', &['\u{4d}', '\u{323}']),
+    ('\u{1e43}', &['\u{6d}', '\u{323}']),
+    ('\u{1e44}', &['\u{4e}', '\u{307}']),
+    ('\u{1e45}', &['\u{6e}', '\u{307}']),
+    ('\u{1e46}', &['\u{4e}', '\u{323}']),
+    ('\u{1e47}', &['\u{6e}', '\u{323}']),
+    ('\u{1e48}', &['\u{4e}', '\u{331}']),
+    ('\u{1e49}', &['\u{6e}', '\u{331}']),
+    ('\u{1e4a}', &['\u{4e}', '\u{32d}']),
+    ('\u{1e4b}', &['\u{6e}', '\u{32d}']),
+    ('\u{1e4c}', &['\u{4f}', '\u{303}', '\u{301}']),
+    ('\u{1e4d}', &['\u{6f}', '\u{303}', '\u{301}']),
+    ('\u{1e4e}', &['\u{4f}', '\u{303}', '\u{308}']),
+    ('\u{1e4f}', &['\u{6f}', '\u{303}', '\u{308}']),
+    ('\u{1e50}', &['\u{4f}', '\u{304}', '\u{300}']),
+    ('\u{1e51}', &['\u{6f}', '\u{304}', '\u{300}']),
+    ('\u{1e52}', &['\u{4f}', '\u{304}', '\u{301}']),
+    ('\u{1e53}', &['\u{6f}', '\u{304}', '\u{301}']),
+    ('\u{1e54}', &['\u{50}', '\u{301}']),
+    ('\u{1e55}', &['\u{70}', '\u{301}']),
+    ('\u{1e56}', &['\u{50}', '\u{307}']),
+    ('\u{1e57}', &['\u{70}', '\u{307}']),
+    ('\u{1e58}', &['\u{52}', '\u{307}']),
+    ('\u{1e59}', &['\u{72}', '\u{307}']),
+    ('\u{1e5a}', &['\u{52}', '\u{323}']),
+    ('\u{1e5b}', &['\u{72}', '\u{323}']),
+    ('\u{1e5c}', &['\u{52}', '\u{323}', '\u{304}']),
+    ('\u{1e5d}', &['\u{72}', '\u{323}', '\u{304}']),
+    ('\u{1e5e}', &['\u{52}', '\u{331}']),
+    ('\u{1e5f}', &['\u{72}', '\u{331}']),
+    ('\u{1e60}', &['\u{53}', '\u{307}']),
+    ('\u{1e61}', &['\u{73}', '\u{307}']),
+    ('\u{1e62}', &['\u{53}', '\u{323}']),
+    ('\u{1e63}', &['\u{73}', '\u{323}']),
+    ('\u{1e64}', &['\u{53}', '\u{301}', '\u{307}']),
+    ('\u{1e65}', &['\u{73}', '\u{301}', '\u{307}']),
+    ('\u{1e66}', &['\u{53}', '\u{30c}', '\u{307}']),
+    ('\u{1e67}', &['\u{73}', '\u{30c}', '\u{307}']),
+    ('\u{1e68}', &['\u{53}', '\u{323}', '\u{307}']),
+    ('\u{1e69}', &['\u{73}', '\u{323}', '\u{307}']),
+    ('\u{1e6a}', &['\u{54}', '\u{307}']),
+    ('\u{1e6b}', &['\u{74}', '\u{307}']),
+    ('\u{1e6c}', &['\u{54}', '\u{323}']),
+    ('\u{1e6d}', &['\u{74}', '\u{323}']),
+    ('\u{1e6e}', &['\u{54}', '\u{331}']),
+    ('\u{1e6f}', &['\u{74}', '\u{331}']),
+    ('\u{1e70}', &['\u{54}', '\u{32d}']),
+    ('\u{1e71}', &['\u{74}', '\u{32d}']),
+    ('\u{1e72}', &['\u{55}', '\u{324}']),
+    ('\u{1e73}', &['\u{75}', '\u{324}']),
+    ('\u{1e74}', &['\u{55}', '\u{330}']),
+    ('\u{1e75}', &['\u{75}', '\u{330}']),
+    ('\u{1e76}', &['\u{55}', '\u{32d}']),
+    ('\u{1e77}', &['\u{75}', '\u{32d}']),
+    ('\u{1e78}', &['\u{55}', '\u{303}', '\u{301}']),
+    ('\u{1e79}', &['\u{75}', '\u{303}', '\u{301}']),
+    ('\u{1e7a}', &['\u{55}', '\u{304}', '\u{308}']),
+    ('\u{1e7b}', &['\u{75}', '\u{304}', '\u{308}']),
+    ('\u{1e7c}', &['\u{56}', '\u{303}']),
+    ('\u{1e7d}', &['\u{76}', '\u{303}']),
+    ('\u{1e7e}', &['\u{56}', '\u{323}']),
+    ('\u{1e7f}', &['\u{76}', '\u{323}']),
+    ('\u{1e80}', &['\u{57}', '\u{300}']),
+    ('\u{1e81}', &['\u{77}', '\u{300}']),
+    ('\u{1e82}', &['\u{57}', '\u{301}']),
+    ('\u{1e83}', &['\u{77}', '\u{301}']),
+    ('\u{1e84}', &['\u{57}', '\u{308}']),
+    ('\u{1e85}', &['\u{77}', '\u{308}']),
+    ('\u{1e86}', &['\u{57}', '\u{307}']),
+    ('\u{1e87}', &['\u{77}', '\u{307}']),
+    ('\u{1e88}', &['\u{57}', '\u{323}']),
+    ('\u{1e89}', &['\u{77}', '\u{323}']),
+    ('\u{1e8a}', &['\u{58}', '\u{307}']),
+    ('\u{1e8b}', &['\u{78}', '\u{307}']),
+    ('\u{1e8c}', &['\u{58}', '\u{308}']),
+    ('\u{1e8d}', &['\u{78}', '\u{308}']),
+    ('\u{1e8e}', &['\u{59}', '\u{307}']),
+    ('\u{1e8f}', &['\u{79}', '\u{307}']),
+    ('\u{1e90}', &['\u{5a}', '\u{302}']),
+    ('\u{1e91}', &['\u{7a}', '\u{302}']),
+    ('\u{1e92}', &['\u{5a}', '\u{323}']),
+    ('\u{1e93}', &['\u{7a}', '\u{323}']),
+    ('\u{1e94}', &['\u{5a}', '\u{331}']),
+    ('\u{1e95}', &['\u{7a}', '\u{331}']),
+    ('\u{1e96}', &['\u{68}', '\u{331}']),
+    ('\u{1e97}', &['\u{74}', '\u{308}']),
+    ('\u{1e98}', &['\u{77}', '\u{30a}']),
+    ('\u{1e99}', &['\u{79}', '\u{30a}']),
+    ('\u{1e9b}', &['\u{17f}', '\u{307}']),
+    ('\u{1ea0}', &['\u{41}', '\u{323}']),
+    ('\u{1ea1}', &['\u{61}', '\u{323}']),
+    ('\u{1ea2}', &['\u{41}', '\u{309}']),
+    ('\u{1ea3}', &['\u{61}', '\u{309}']),
+    ('\u{1ea4}', &['\u{41}', '\u{302}', '\u{301}']),
+    ('\u{1ea5}', &['\u{61}', '\u{302}', '\u{301}']),
+    ('\u{1ea6}', &['\u{41}', '\u{302}', '\u{300}']),
+    ('\u{1ea7}', &['\u{61}', '\u{302}', '\u{300}']),
+    ('\u{1ea8}', &['\u{41}', '\u{302}', '\u{309}']),
+    ('\u{1ea9}', &['\u{61}', '\u{302}', '\u{309}']),
+    ('\u{1eaa}', &['\u{41}', '\u{302}', '\u{303}']),
+    ('\u{1eab}', &['\u{61}', '\u{302}', '\u{303}']),
+    ('\u{1eac}', &['\u{41}', '\u{323}', '\u{302}']),
+    ('\u{1ead}', &['\u{61}', '\u{323}', '\u{302}']),
+    ('\u{1eae}', &['\u{41}', '\u{306}', '\u{301}']),
+    ('\u{1eaf}', &['\u{61}', '\u{306}', '\u{301}']),
+    ('\u{1eb0}', &['\u{41}', '\u{306}', '\u{300}']),
+    ('\u{1eb1}', &['\u{61}', '\u{306}', '\u{300}']),
+    ('\u{1eb2}', &['\u{41}', '\u{306}', '\u{309}']),
+    ('\u{1eb3}', &['\u{61}', '\u{306}', '\u{309}']),
+    ('\u{1eb4}', &['\u{41}', '\u{306}', '\u{303}']),
+    ('\u{1eb5}', &['\u{61}', '\u{306}', '\u{303}']),
+    ('\u{1eb6}', &['\u{41}', '\u{323}', '\u{306}']),
+    ('\u{1eb7}', &['\u{61}', '\u{323}', '\u{306}']),
+    ('\u{1eb8}', &['\u{45}', '\u{323}']),
+    ('\u{1eb9}', &['\u{65}', '\u{323}']),
+    ('\u{1eba}', &['\u{45}', '\u{309}']),
+    ('\u{1ebb}', &['\u{65}', '\u{309}']),
+    ('\u{1ebc}', &['\u{45}', '\u{303}']),
+    ('\u{1ebd}', &['\u{65}', '\u{303}']),
+    ('\u{1ebe}', &['\u{45}', '\u{302}', '\u{301}']),
+    ('\u{1ebf}', &['\u{65}', '\u{302}', '\u{301}']),
+    ('\u{1ec0}', &['\u{45}', '\u{302}', '\u{300}']),
+    ('\u{1ec1}', &['\u{65}', '\u{302}', '\u{300}']),
+    ('\u{1ec2}', &['\u{45}', '\u{302}', '\u{309}']),
+    ('\u{1ec3}', &['\u{65}', '\u{302}', '\u{309}']),
+    ('\u{1ec4}', &['\u{45}', '\u{302}', '\u{303}']),
+    ('\u{1ec5}', &['\u{65}', '\u{302}', '\u{303}']),
+    ('\u{1ec6}', &['\u{45}', '\u{323}', '\u{302}']),
+    ('\u{1ec7}', &['\u{65}', '\u{323}', '\u{302}']),
+    ('\u{1ec8}', &['\u{49}', '\u{309}']),
+    ('\u{1ec9}', &['\u{69}', '\u{309}']),
+    ('\u{1eca}', &['\u{49}', '\u{323}']),
+    ('\u{1ecb}', &['\u{69}', '\u{323}']),
+    ('\u{1ecc}', &['\u{4f}', '\u{323}']),
+    ('\u{1ecd}', &['\u{6f}', '\u{323}']),
+    ('\u{1ece}', &['\u{4f}', '\u{309}']),
+    ('\u{1ecf}', &['\u{6f}', '\u{309}']),
+    ('\u{1ed0}', &['\u{4f}', '\u{302}', '\u{301}']),
+    ('\u{1ed1}', &['\u{6f}', '\u{302}', '\u{301}']),
+    ('\u{1ed2}', &['\u{4f}', '\u{302}', '\u{300}']),
+    ('\u{1ed3}', &['\u{6f}', '\u{302}', '\u{300}']),
+    ('\u{1ed4}', &['\u{4f}', '\u{302}', '\u{309}']),
+    ('\u{1ed5}', &['\u{6f}', '\u{302}', '\u{309}']),
+    ('\u{1ed6}', &['\u{4f}', '\u{302}', '\u{303}']),
+    ('\u{1ed7}', &['\u{6f}', '\u{302}', '\u{303}']),
+    ('\u{1ed8}', &['\u{4f}', '\u{323}', '\u{302}']),
+    ('\u{1ed9}', &['\u{6f}', '\u{323}', '\u{302}']),
+    ('\u{1eda}', &['\u{4f}', '\u{31b}', '\u{301}']),
+    ('\u{1edb}', &['\u{6f}', '\u{31b}', '\u{301}']),
+    ('\u{1edc}', &['\u{4f}', '\u{31b}', '\u{300}']),
+    ('\u{1edd}', &['\u{6f}', '\u{31b}', '\u{300}']),
+    ('\u{1ede}', &['\u{4f}', '\u{31b}', '\u{309}']),
+    ('\u{1edf}', &['\u{6f}', '\u{31b}', '\u{309}']),
+    ('\u{1ee0}', &['\u{4f}', '\u{31b}', '\u{303}']),
+    ('\u{1ee1}', &['\u{6f}', '\u{31b}', '\u{303}']),
+    ('\u{1ee2}', &['\u{4f}', '\u{31b}', '\u{323}']),
+    ('\u{1ee3}', &['\u{6f}', '\u{31b}', '\u{323}']),
+    ('\u{1ee4}', &['\u{55}', '\u{323}']),
+    ('\u{1ee5}', &['\u{75}', '\u{323}']),
+    ('\u{1ee6}', &['\u{55}', '\u{309}']),
+    ('\u{1ee7}', &['\u{75}', '\u{309}']),
+    ('\u{1ee8}', &['\u{55}', '\u{31b}', '\u{301}']),
+    ('\u{1ee9}', &['\u{75}', '\u{31b}', '\u{301}']),
+    ('\u{1eea}', &['\u{55}', '\u{31b}', '\u{300}']),
+    ('\u{1eeb}', &['\u{75}', '\u{31b}', '\u{300}']),
+    ('\u{1eec}', &['\u{55}', '\u{31b}', '\u{309}']),
+    ('\u{1eed}', &['\u{75}', '\u{31b}', '\u{309}']),
+    ('\u{1eee}', &['\u{55}', '\u{31b}', '\u{303}']),
+    ('\u{1eef}', &['\u{75}', '\u{31b}', '\u{303}']),
+    ('\u{1ef0}', &['\u{55}', '\u{31b}', '\u{323}']),
+    ('\u{1ef1}', &['\u{75}', '\u{31b}', '\u{323}']),
+    ('\u{1ef2}', &['\u{59}', '\u{300}']),
+    ('\u{1ef3}', &['\u{79}', '\u{300}']),
+    ('\u{1ef4}', &['\u{59}', '\u{323}']),
+    ('\u{1ef5}', &['\u{79}', '\u{323}']),
+    ('\u{1ef6}', &['\u{59}', '\u{309}']),
+    ('\u{1ef7}', &['\u{79}', '\u{309}']),
+    ('\u{1ef8}', &['\u{59}', '\u{303}']),
+    ('\u{1ef9}', &['\u{79}', '\u{303}']),
+    ('\u{1f00}', &['\u{3b1}', '\u{313}']),
+    ('\u{1f01}', &['\u{3b1}', '\u{314}']),
+    ('\u{1f02}', &['\u{3b1}', '\u{313}', '\u{300}']),
+    ('\u{1f03}', &['\u{3b1}', '\u{314}', '\u{300}']),
+    ('\u{1f04}', &['\u{3b1}', '\u{313}', '\u{301}']),
+    ('\u{1f05}', &['\u{3b1}', '\u{314}', '\u{301}']),
+    ('\u{1f06}', &['\u{3b1}', '\u{313}', '\u{342}']),
+    ('\u{1f07}', &['\u{3b1}', '\u{314}', '\u{342}']),
+    ('\u{1f08}', &['\u{391}', '\u{313}']),
+    ('\u{1f09}', &['\u{391}', '\u{314}']),
+    ('\u{1f0a}', &['\u{391}', '\u{313}', '\u{300}']),
+    ('\u{1f0b}', &['\u{391}', '\u{314}', '\u{300}']),
+    ('\u{1f0c}', &['\u{391}', '\u{313}', '\u{301}']),
+    ('\u{1f0d}', &['\u{391}', '\u{314}', '\u{301}']),
+    ('\u{1f0e}', &['\u{391}', '\u{313}', '\u{342}']),
+    ('\u{1f0f}', &['\u{391}', '\u{314}', '\u{342}']),
+    ('\u{1f10}', &['\u{3b5}', '\u{313}']),
+    ('\u{1f11}', &['\u{3b5}', '\u{314}']),
+    ('\u{1f12}', &['\u{3b5}', '\u{313}', '\u{300}']),
+    ('\u{1f13}', &['\u{3b5}', '\u{314}', '\u{300}']),
+    ('\u{1f14}', &['\u{3b5}', '\u{313}', '\u{301}']),
+    ('\u{1f15}', &['\u{3b5}', '\u{314}', '\u{301}']),
+    ('\u{1f18}', &['\u{395}', '\u{313}']),
+    ('\u{1f19}', &['\u{395}', '\u{314}']),
+    ('\u{1f1a}', &['\u{395}', '\u{313}', '\u{300}']),
+    ('\u{1f1b}', &['\u{395}', '\u{314}', '\u{300}']),
+    ('\u{1f1c}', &['\u{395}', '\u{313}', '\u{301}']),
+    ('\u{1f1d}', &['\u{395}', '\u{314}', '\u{301}']),
+    ('\u{1f20}', &['\u{3b7}', '\u{313}']),
+    ('\u{1f21}', &['\u{3b7}', '\u{314}']),
+    ('\u{1f22}', &['\u{3b7}', '\u{313}', '\u{300}']),
+    ('\u{1f23}', &['\u{3b7}', '\u{314}', '\u{300}']),
+    ('\u{1f24}', &['\u{3b7}', '\u{313}', '\u{301}']),
+    ('\u{1f25}', &['\u{3b7}', '\u{314}', '\u{301}']),
+    ('\u{1f26}', &['\u{3b7}', '\u{313}', '\u{342}']),
+    ('\u{1f27}', &['\u{3b7}', '\u{314}', '\u{342}']),
+    ('\u{1f28}', &['\u{397}', '\u{313}']),
+    ('\u{1f29}', &['\u{397}', '\u{314}']),
+    ('\u{1f2a}', &['\u{397}', '\u{313}', '\u{300}']),
+    ('\u{1f2b}', &['\u{397}', '\u{314}', '\u{300}']),
+    ('\u{1f2c}', &['\u{397}', '\u{313}', '\u{301}']),
+    ('\u{1f2d}', &['\u{397}', '\u{314}', '\u{301}']),
+    ('\u{1f2e}', &['\u{397}', '\u{313}', '\u{342}']),
+    ('\u{1f2f}', &['\u{397}', '\u{314}', '\u{342}']),
+    ('\u{1f30}', &['\u{3b9}', '\u{313}']),
+    ('\u{1f31}', &['\u{3b9}', '\u{314}']),
+    ('\u{1f32}', &['\u{3b9}', '\u{313}', '\u{300}']),
+    ('\u{1f33}', &['\u{3b9}', '\u{314}', '\u{300}']),
+    ('\u{1f34}', &['\u{3b9}', '\u{313}', '\u{301}']),
+    ('\u{1f35}', &['\u{3b9}', '\u{314}', '\u{301}']),
+    ('\u{1f36}', &['\u{3b9}', '\u{313}', '\u{342}']),
+    ('\u{1f37}', &['\u{3b9}', '\u{314}', '\u{342}']),
+    ('\u{1f38}', &['\u{399}', '\u{313}']),
+    ('\u{1f39}', &['\u{399}', '\u{314}']),
+    ('\u{1f3a}', &['\u{399}', '\u{313}', '\u{300}']),
+    ('\u{1f3b}', &['\u{399}', '\u{314}', '\u{300}']),
+    ('\u{1f3c}', &['\u{399}', '\u{313}', '\u{301}']),
+    ('\u{1f3d}', &['\u{399}', '\u{314}', '\u{301}']),
+    ('\u{1f3e}', &['\u{399}', '\u{313}', '\u{342}']),
+    ('\u{1f3f}', &['\u{399}', '\u{314}', '\u{342}']),
+    ('\u{1f40}', &['\u{3bf}', '\u{313}']),
+    ('\u{1f41}', &['\u{3bf}', '\u{314}']),
+    ('\u{1f42}', &['\u{3bf}', '\u{313}', '\u{300}']),
+    ('\u{1f43}', &['\u{3bf}', '\u{314}', '\u{300}']),
+    ('\u{1f44}', &['\u{3bf}', '\u{313}', '\u{301}']),
+    ('\u{1f45}', &['\u{3bf}', '\u{314}', '\u{301}']),
+    ('\u{1f48}', &['\u{39f}', '\u{313}']),
+    ('\u{1f49}', &['\u{39f}', '\u{314}']),
+    ('\u{1f4a}', &['\u{39f}', '\u{313}', '\u{300}']),
+    ('\u{1f4b}', &['\u{39f}', '\u{314}', '\u{300}']),
+    ('\u{1f4c}', &['\u{39f}', '\u{313}', '\u{301}']),
+    ('\u{1f4d}', &['\u{39f}', '\u{314}', '\u{301}']),
+    ('\u{1f50}', &['\u{3c5}', '\u{313}']),
+    ('\u{1f51}', &['\u{3c5}', '\u{314}']),
+    ('\u{1f52}', &['\u{3c5}', '\u{313}', '\u{300}']),
+    ('\u{1f53}', &['\u{3c5}', '\u{314}', '\u{300}']),
+    ('\u{1f54}', &['\u{3c5}', '\u{313}', '\u{301}']),
+    ('\u{1f55}', &['\u{3c5}', '\u{314}', '\u{301}']),
+    ('\u{1f56}', &['\u{3c5}', '\u{313}', '\u{342}']),
+    ('\u{1f57}', &['\u{3c5}', '\u{314}', '\u{342}']),
+    ('\u{1f59}', &['\u{3a5}', '\u{314}']),
+    ('\u{1f5b}', &['\u{3a5}', '\u{314}', '\u{300}']),
+    ('\u{1f5d}', &['\u{3a5}', '\u{314}', '\u{301}']),
+    ('\u{1f5f}', &['\u{3a5}', '\u{314}', '\u{342}']),
+    ('\u{1f60}', &['\u{3c9}', '\u{313}']),
+    ('\u{1f61}', &['\u{3c9}', '\u{314}']),
+    ('\u{1f62}', &['\u{3c9}', '\u{313}', '\u{300}']),
+    ('\u{1f63}', &['\u{3c9}', '\u{314}', '\u{300}']),
+    ('\u{1f64}', &['\u{3c9}', '\u{313}', '\u{301}']),
+    ('\u{1f65}', &['\u{3c9}', '\u{314}', '\u{301}']),
+    ('\u{1f66}', &['\u{3c9}', '\u{313}', '\u{342}']),
+    ('\u{1f67}', &['\u{3c9}', '\u{314}', '\u{342}']),
+    ('\u{1f68}', &['\u{3a9}', '\u{313}']),
+    ('\u{1f69}', &['\u{3a9}', '\u{314}']),
+    ('\u{1f6a}', &['\u{3a9}', '\u{313}', '\u{300}']),
+    ('\u{1f6b}', &['\u{3a9}', '\u{314}', '\u{300}']),
+    ('\u{1f6c}', &['\u{3a9}', '\u{313}', '\u{301}']),
+    ('\u{1f6d}', &['\u{3a9}', '\u{314}', '\u{301}']),
+    ('\u{1f6e}', &['\u{3a9}', '\u{313}', '\u{342}']),
+    ('\u{1f6f}', &['\u{3a9}', '\u{314}', '\u{342}']),
+    ('\u{1f70}', &['\u{3b1}', '\u{300}']),
+    ('\u{1f71}', &['\u{3b1}', '\u{301}']),
+    ('\u{1f72}', &['\u{3b5}', '\u{300}']),
+    ('\u{1f73}', &['\u{3b5}', '\u{301}']),
+    ('\u{1f74}', &['\u{3b7}', '\u{300}']),
+    ('\u{1f75}', &['\u{3b7}', '\u{301}']),
+    ('\u{1f76}', &['\u{3b9}', '\u{300}']),
+    ('\u{1f77}', &['\u{3b9}', '\u{301}']),
+    ('\u{1f78}', &['\u{3bf}', '\u{300}']),
+    ('\u{1f79}', &['\u{3bf}', '\u{301}']),
+    ('\u{1f7a}', &['\u{3c5}', '\u{300}']),
+    ('\u{1f7b}', &['\u{3c5}', '\u{301}']),
+    ('\u{1f7c}', &['\u{3c9}', '\u{300}']),
+    ('\u{1f7d}', &['\u{3c9}', '\u{301}']),
+    ('\u{1f80}', &['\u{3b1}', '\u{313}', '\u{345}']),
+    ('\u{1f81}', &['\u{3b1}', '\u{314}', '\u{345}']),
+    ('\u{1f82}', &['\u{3b1}', '\u{313}', '\u{300}', '\u{345}']),
+    ('\u{1f83}', &['\u{3b1}', '\u{314}', '\u{300}', '\u{345}']),
+    ('\u{1f84}', &['\u{3b1}', '\u{313}', '\u{301}', '\u{345}']),
+    ('\u{1f85}', &['\u{3b1}', '\u{314}', '\u{301}', '\u{345}']),
+    ('\u{1f86}', &['\u{3b1}', '\u{313}', '\u{342}', '\u{345}']),
+    ('\u{1f87}', &['\u{3b1}', '\u{314}', '\u{342}', '\u{345}']),
+    ('\u{1f88}', &['\u{391}', '\u{313}', '\u{345}']),
+    ('\u{1f89}', &['\u{391}', '\u{314}', '\u{345}']),
+    ('\u{1f8a}', &['\u{391}', '\u{313}', '\u{300}', '\u{345}']),
+    ('\u{1f8b}', &['\u{391}', '\u{314}', '\u{300}', '\u{345}']),
+    ('\u{1f8c}', &['\u{391}', '\u{313}', '\u{301}', '\u{345}']),
+    ('\u{1f8d}', &['\u{391}', '\u{314}', '\u{301}', '\u{345}']),
+    ('\u{1f8e}', &['\u{391}', '\u{313}', '\u{342}', '\u{345}']),
+    ('\u{1f8f}', &['\u{391}', '\u{314}', '\u{342}', '\u{345}']),
+    ('\u{1f90}', &['\u{3b7}', '\u{313}', '\u{345}']),
+    ('\u{1f91}', &['\u{3b7}', '\u{314}', '\u{345}']),
+    ('\u{1f92}', &['\u{3b7}', '\u{313}', '\u{300}', '\u{345}']),
+    ('\u{1f93}', &['\u{3b7}', '\u{314}', '\u{300}', '\u{345}']),
+    ('\u{1f94}', &['\u{3b7}', '\u{313}', '\u{301}', '\u{345}']),
+    ('\u{1f95}', &['\u{3b7}', '\u{314}', '\u{301}', '\u{345}']),
+    ('\u{1f96}', &['\u{3b7}', '\u{313}', '\u{342}', '\u{345}']),
+    ('\u{1f97}', &['\u{3b7}', '\u{314}', '\u{342}', '\u{345}']),
+    ('\u{1f98}', &['\u{397}', '\u{313}', '\u{345}']),
+    ('\u{1f99}', &['\u{397}', '\u{314}', '\u{345}']),
+    ('\u{1f9a}', &['\u{397}', '\u{313}', '\u{300}', '\u{345}']),
+    ('\u{1f9b}', &['\u{397}', '\u{314}', '\u{300}', '\u{345}']),
+    ('\u{1f9c}', &['\u{397}', '\u{313}', '\u{301}', '\u{345}']),
+    ('\u{1f9d}', &['\u{397}', '\u{314}', '\u{301}', '\u{345}']),
+    ('\u{1f9e}', &['\u{397}', '\u{313}', '\u{342}', '\u{345}']),
+    ('\u{1f9f}', &['\u{397}', '\u{314}', '\u{342}', '\u{345}']),
+    ('\u{1fa0}', &['\u{3c9}', '\u{313}', '\u{345}']),
+    ('\u{1fa1}', &['\u{3c9}', '\u{314}', '\u{345}']),
+    ('\u{1fa2}', &['\u{3c9}', '\u{313}', '\u{300}', '\u{345}']),
+    ('\u{1fa3}', &['\u{3c9}', '\u{314}', '\u{300}', '\u{345}']),
+    ('\u{1fa4}', &['\u{3c9}', '\u{313}', '\u{301}', '\u{345}']),
+    ('\u{1fa5}', &['\u{3c9}', '\u{314}', '\u{301}', '\u{345}']),
+    ('\u{1fa6}', &['\u{3c9}', '\u{313}', '\u{342}', '\u{345}']),
+    ('\u{1fa7}', &['\u{3c9}', '\u{314}', '\u{342}', '\u{345}']),
+    ('\u{1fa8}', &['\u{3a9}', '\u{313}', '\u{345}']),
+    ('\u{1fa9}', &['\u{3a9}', '\u{314}', '\u{345}']),
+    ('\u{1faa}', &['\u{3a9}', '\u{313}', '\u{300}', '\u{345}']),
+    ('\u{1fab}', &['\u{3a9}', '\u{314}', '\u{300}', '\u{345}']),
+    ('\u{1fac}', &['\u{3a9}', '\u{313}', '\u{301}', '\u{345}']),
+    ('\u{1fad}', &['\u{3a9}', '\u{314}', '\u{301}', '\u{345}']),
+    ('\u{1fae}', &['\u{3a9}', '\u{313}', '\u{342}', '\u{345}']),
+    ('\u{1faf}', &['\u{3a9}', '\u{314}', '\u{342}', '\u{345}']),
+    ('\u{1fb0}', &['\u{3b1}', '\u{306}']),
+    ('\u{1fb1}', &['\u{3b1}', '\u{304}']),
+    ('\u{1fb2}', &['\u{3b1}', '\u{300}', '\u{345}']),
+    ('\u{1fb3}', &['\u{3b1}', '\u{345}']),
+    ('\u{1fb4}', &['\u{3b1}', '\u{301}', '\u{345}']),
+    ('\u{1fb6}', &['\u{3b1}', '\u{342}']),
+    ('\u{1fb7}', &['\u{3b1}', '\u{342}', '\u{345}']),
+    ('\u{1fb8}', &['\u{391}', '\u{306}']),
+    ('\u{1fb9}', &['\u{391}', '\u{304}']),
+    ('\u{1fba}', &['\u{391}', '\u{300}']),
+    ('\u{1fbb}', &['\u{391}', '\u{301}']),
+    ('\u{1fbc}', &['\u{391}', '\u{345}']),
+    ('\u{1fbe}', &['\u{3b9}']),
+    ('\u{1fc1}', &['\u{a8}', '\u{342}']),
+    ('\u{1fc2}', &['\u{3b7}', '\u{300}', '\u{345}']),
+    ('\u{1fc3}', &['\u{3b7}', '\u{345}']),
+    ('\u{1fc4}', &['\u{3b7}', '\u{301}', '\u{345}']),
+    ('\u{1fc6}', &['\u{3b7}', '\u{342}']),
+    ('\u{1fc7}', &['\u{3b7}', '\u{342}', '\u{345}']),
+    ('\u{1fc8}', &['\u{395}', '\u{300}']),
+    ('\u{1fc9}', &['\u{395}', '\u{301}']),
+    ('\u{1fca}', &['\u{397}', '\u{300}']),
+    ('\u{1fcb}', &['\u{397}', '\u{301}']),
+    ('\u{1fcc}', &['\u{397}', '\u{345}']),
+    ('\u{1fcd}', &['\u{1fbf}', '\u{300}']),
+    ('\u{1fce}', &['\u{1fbf}', '\u{301}']),
+    ('\u{1fcf}', &['\u{1fbf}', '\u{342}']),
+    ('\u{1fd0}', &['\u{3b9}', '\u{306}']),
+    ('\u{1fd1}', &['\u{3b9}', '\u{304}']),
+    ('\u{1fd2}', &['\u{3b9}', '\u{308}', '\u{300}']),
+    ('\u{1fd3}', &['\u{3b9}', '\u{308}', '\u{301}']),
+    ('\u{1fd6}', &['\u{3b9}', '\u{342}']),
+    ('\u{1fd7}', &['\u{3b9}', '\u{308}', '\u{342}']),
+    ('\u{1fd8}', &['\u{399}', '\u{306}']),
+    ('\u{1fd9}', &['\u{399}', '\u{304}']),
+    ('\u{1fda}', &['\u{399}', '\u{300}']),
+    ('\u{1fdb}', &['\u{399}', '\u{301}']),
+    ('\u{1fdd}', &['\u{1ffe}', '\u{300}']),
+    ('\u{1fde}', &['\u{1ffe}', '\u{301}']),
+    ('\u{1fdf}', &['\u{1ffe}', '\u{342}']),
+    ('\u{1fe0}', &['\u{3c5}', '\u{306}']),
+    ('\u{1fe1}', &['\u{3c5}', '\u{304}']),
+    ('\u{1fe2}', &['\u{3c5}', '\u{308}', '\u{300}']),
+    ('\u{1fe3}', &['\u{3c5}', '\u{308}', '\u{301}']),
+    ('\u{1fe4}', &['\u{3c1}', '\u{313}']),
+    ('\u{1fe5}', &['\u{3c1}', '\u{314}']),
+    ('\u{1fe6}', &['\u{3c5}', '\u{342}']),
+    ('\u{1fe7}', &['\u{3c5}', '\u{308}', '\u{342}']),
+    ('\u{1fe8}', &['\u{3a5}', '\u{306}']),
+    ('\u{1fe9}', &['\u{3a5}', '\u{304}']),
+    ('\u{1fea}', &['\u{3a5}', '\u{300}']),
+    ('\u{1feb}', &['\u{3a5}', '\u{301}']),
+    ('\u{1fec}', &['\u{3a1}', '\u{314}']),
+    ('\u{1fed}', &['\u{a8}', '\u{300}']),
+    ('\u{1fee}', &['\u{a8}', '\u{301}']),
+    ('\u{1fef}', &['\u{60}']),
+    ('\u{1ff2}', &['\u{3c9}', '\u{300}', '\u{345}']),
+    ('\u{1ff3}', &['\u{3c9}', '\u{345}']),
+    ('\u{1ff4}', &['\u{3c9}', '\u{301}', '\u{345}']),
+    ('\u{1ff6}', &['\u{3c9}', '\u{342}']),
+    ('\u{1ff7}', &['\u{3c9}', '\u{342}', '\u{345}']),
+    ('\u{1ff8}', &['\u{39f}', '\u{300}']),
+    ('\u{1ff9}', &['\u{39f}', '\u{301}']),
+    ('\u{1ffa}', &['\u{3a9}', '\u{300}']),
+    ('\u{1ffb}', &['\u{3a9}', '\u{301}']),
+    ('\u{1ffc}', &['\u{3a9}', '\u{345}']),
+    ('\u{1ffd}', &['\u{b4}']),
+    ('\u{2000}', &['\u{2002}']),
+    ('\u{2001}', &['\u{2003}']),
+    ('\u{2126}', &['\u{3a9}']),
+    ('\u{212a}', &['\u{4b}']),
+    ('\u{212b}', &['\u{41}', '\u{30a}']),
+    ('\u{219a}', &['\u{2190}', '\u{338}']),
+    ('\u{219b}', &['\u{2192}', '\u{338}']),
+    ('\u{21ae}', &['\u{2194}', '\u{338}']),
+    ('\u{21cd}', &['\u{21d0}', '\u{338}']),
+    ('\u{21ce}', &['\u{21d4}', '\u{338}']),
+    ('\u{21cf}', &['\u{21d2}', '\u{338}']),
+    ('\u{2204}', &['\u{2203}', '\u{338}']),
+    ('\u{2209}', &['\u{2208}', '\u{338}']),
+    ('\u{220c}', &['\u{220b}', '\u{338}']),
+    ('\u{2224}', &['\u{2223}', '\u{338}']),
+    ('\u{2226}', &['\u{2225}', '\u{338}']),
+    ('\u{2241}', &['\u{223c}', '\u{338}']),
+    ('\u{2244}', &['\u{2243}', '\u{338}']),
+    ('\u{2247}', &['\u{2245}', '\u{338}']),
+    ('\u{2249}', &['\u{2248}', '\u{338}']),
+    ('\u{2260}', &['\u{3d}', '\u{338}']),
+    ('\u{2262}', &['\u{2261}', '\u{338}']),
+    ('\u{226d}', &['\u{224d}', '\u{338}']),
+    ('\u{226e}', &['\u{3c}', '\u{338}']),
+    ('\u{226f}', &['\u{3e}', '\u{338}']),
+    ('\u{2270}', &['\u{2264}', '\u{338}']),
+    ('\u{2271}', &['\u{2265}', '\u{338}']),
+    ('\u{2274}', &['\u{2272}', '\u{338}']),
+    ('\u{2275}', &['\u{2273}', '\u{338}']),
+    ('\u{2278}', &['\u{2276}', '\u{338}']),
+    ('\u{2279}', &['\u{2277}', '\u{338}']),
+    ('\u{2280}', &['\u{227a}', '\u{338}']),
+    ('\u{2281}', &['\u{227b}', '\u{338}']),
+    ('\u{2284}', &['\u{2282}', '\u{338}']),
+    ('\u{2285}', &['\u{2283}', '\u{338}']),
+    ('\u{2288}', &['\u{2286}', '\u{338}']),
+    ('\u{2289}', &['\u{2287}', '\u{338}']),
+    ('\u{22ac}', &['\u{22a2}', '\u{338}']),
+    ('\u{22ad}', &['\u{22a8}', '\u{338}']),
+    ('\u{22ae}', &['\u{22a9}', '\u{338}']),
+    ('\u{22af}', &['\u{22ab}', '\u{338}']),
+    ('\u{22e0}', &['\u{227c}', '\u{338}']),
+    ('\u{22e1}', &['\u{227d}', '\u{338}']),
+    ('\u{22e2}', &['\u{2291}', '\u{338}']),
+    ('\u{22e3}', &['\u{2292}', '\u{338}']),
+    ('\u{22ea}', &['\u{22b2}', '\u{338}']),
+    ('\u{22eb}', &['\u{22b3}', '\u{338}']),
+    ('\u{22ec}', &['\u{22b4}', '\u{338}']),
+    ('\u{22ed}', &['\u{22b5}', '\u{338}']),
+    ('\u{2329}', &['\u{3008}']),
+    ('\u{232a}', &['\u{3009}']),
+    ('\u{2adc}', &['\u{2add}', '\u{338}']),
+    ('\u{304c}', &['\u{304b}', '\u{3099}']),
+    ('\u{304e}', &['\u{304d}', '\u{3099}']),
+    ('\u{3050}', &['\u{304f}', '\u{3099}']),
+    ('\u{3052}', &['\u{3051}', '\u{3099}']),
+    ('\u{3054}', &['\u{3053}', '\u{3099}']),
+    ('\u{3056}', &['\u{3055}', '\u{3099}']),
+    ('\u{3058}', &['\u{3057}', '\u{3099}']),
+    ('\u{305a}', &['\u{3059}', '\u{3099}']),
+    ('\u{305c}', &['\u{305b}', '\u{3099}']),
+    ('\u{305e}', &['\u{305d}', '\u{3099}']),
+    ('\u{3060}', &['\u{305f}', '\u{3099}']),
+    ('\u{3062}', &['\u{3061}', '\u{3099}']),
+    ('\u{3065}', &['\u{3064}', '\u{3099}']),
+    ('\u{3067}', &['\u{3066}', '\u{3099}']),
+    ('\u{3069}', &['\u{3068}', '\u{3099}']),
+    ('\u{3070}', &['\u{306f}', '\u{3099}']),
+    ('\u{3071}', &['\u{306f}', '\u{309a}']),
+    ('\u{3073}', &['\u{3072}', '\u{3099}']),
+    ('\u{3074}', &['\u{3072}', '\u{309a}']),
+    ('\u{3076}', &['\u{3075}', '\u{3099}']),
+    ('\u{3077}', &['\u{3075}', '\u{309a}']),
+    ('\u{3079}', &['\u{3078}', '\u{3099}']),
+    ('\u{307a}', &['\u{3078}', '\u{309a}']),
+    ('\u{307c}', &['\u{307b}', '\u{3099}']),
+    ('\u{307d}', &['\u{307b}', '\u{309a}']),
+    ('\u{3094}', &['\u{3046}', '\u{3099}']),
+    ('\u{309e}', &['\u{309d}', '\u{3099}']),
+    ('\u{30ac}', &['\u{30ab}', '\u{3099}']),
+    ('\u{30ae}', &['\u{30ad}', '\u{3099}']),
+    ('\u{30b0}', &['\u{30af}', '\u{3099}']),
+    ('\u{30b2}', &['\u{30b1}', '\u{3099}']),
+    ('\u{30b4}', &['\u{30b3}', '\u{3099}']),
+    ('\u{30b6}', &['\u{30b5}', '\u{3099}']),
+    ('\u{30b8}', &['\u{30b7}', '\u{3099}']),
+    ('\u{30ba}', &['\u{30b9}', '\u{3099}']),
+    ('\u{30bc}', &['\u{30bb}', '\u{3099}']),
+    ('\u{30be}', &['\u{30bd}', '\u{3099}']),
+    ('\u{30c0}', &['\u{30bf}', '\u{3099}']),
+    ('\u{30c2}', &['\u{30c1}', '\u{3099}']),
+    ('\u{30c5}', &['\u{30c4}', '\u{3099}']),
+    ('\u{30c7}', &['\u{30c6}', '\u{3099}']),
+    ('\u{30c9}', &['\u{30c8}', '\u{3099}']),
+    ('\u{30d0}', &['\u{30cf}', '\u{3099}']),
+    ('\u{30d1}', &['\u{30cf}', '\u{309a}']),
+    ('\u{30d3}', &['\u{30d2}', '\u{3099}']),
+    ('\u{30d4}', &['\u{30d2}', '\u{309a}']),
+    ('\u{30d6}', &['\u{30d5}', '\u{3099}']),
+    ('\u{30d7}', &['\u{30d5}', '\u{309a}']),
+    ('\u{30d9}', &['\u{30d8}', '\u{3099}']),
+    ('\u{30da}', &['\u{30d8}', '\u{309a}']),
+    ('\u{30dc}', &['\u{30db}', '\u{3099}']),
+    ('\u{30dd}', &['\u{30db}', '\u{309a}']),
+    ('\u{30f4}', &['\u{30a6}', '\u{3099}']),
+    ('\u{30f7}', &['\u{30ef}', '\u{3099}']),
+    ('\u{30f8}', &['\u{30f0}', '\u{3099}']),
+    ('\u{30f9}', &['\u{30f1}', '\u{3099}']),
+    ('\u{30fa}', &['\u{30f2}', '\u{3099}']),
+    ('\u{30fe}', &['\u{30fd}', '\u{3099}']),
+    ('\u{fb1d}', &['\u{5d9}', '\u{5b4}']),
+    ('\u{fb1f}', &['\u{5f2}', '\u{5b7}']),
+    ('\u{fb2a}', &['\u{5e9}', '\u{5c1}']),
+    ('\u{fb2b}', &['\u{5e9}', '\u{5c2}']),
+    ('\u{fb2c}', &['\u{5e9}', '\u{5bc}', '\u{5c1}']),
+    ('\u{fb2d}', &['\u{5e9}', '\u{5bc}', '\u{5c2}']),
+    ('\u{fb2e}', &['\u{5d0}', '\u{5b7}']),
+    ('\u{fb2f}', &['\u{5d0}', '\u{5b8}']),
+    ('\u{fb30}', &['\u{5d0}', '\u{5bc}']),
+    ('\u{fb31}', &['\u{5d1}', '\u{5bc}']),
+    ('\u{fb32}', &['\u{5d2}', '\u{5bc}']),
+    ('\u{fb33}', &['\u{5d3}', '\u{5bc}']),
+    ('\u{fb34}', &['\u{5d4}', '\u{5bc}']),
+    ('\u{fb35}', &['\u{5d5}', '\u{5bc}']),
+    ('\u{fb36}', &['\u{5d6}', '\u{5bc}']),
+    ('\u{fb38}', &['\u{5d8}', '\u{5bc}']),
+    ('\u{fb39}', &['\u{5d9}', '\u{5bc}']),
+    ('\u{fb3a}', &['\u{5da}', '\u{5bc}']),
+    ('\u{fb3b}', &['\u{5db}', '\u{5bc}']),
+    ('\u{fb3c}', &['\u{5dc}', '\u{5bc}']),
+    ('\u{fb3e}', &['\u{5de}', '\u{5bc}']),
+    ('\u{fb40}', &['\u{5e0}', '\u{5bc}']),
+    ('\u{fb41}', &['\u{5e1}', '\u{5bc}']),
+    ('\u{fb43}', &['\u{5e3}', '\u{5bc}']),
+    ('\u{fb44}', &['\u{5e4}', '\u{5bc}']),
+    ('\u{fb46}', &['\u{5e6}', '\u{5bc}']),
+    ('\u{fb47}', &['\u{5e7}', '\u{5bc}']),
+    ('\u{fb48}', &['\u{5e8}', '\u{5bc}']),
+    ('\u{fb49}', &['\u{5e9}', '\u{5bc}']),
+    ('\u{fb4a}', &['\u{5ea}', '\u{5bc}']),
+    ('\u{fb4b}', &['\u{5d5}', '\u{5b9}']),
+    ('\u{fb4c}', &['\u{5d1}', '\u{5bf}']),
+    ('\u{fb4d}', &['\u{5db}', '\u{5bf}']),
+    ('\u{fb4e}', &['\u{5e4}', '\u{5bf}']),
+    ('\u{1109a}', &['\u{11099}', '\u{110ba}']),
+    ('\u{1109c}', &['\u{1109b}', '\u{110ba}']),
+    ('\u{110ab}', &['\u{110a5}', '\u{110ba}']),
+    ('\u{1112e}', &['\u{11131}', '\u{11127}']),
+    ('\u{1112f}', &['\u{11132}', '\u{11127}']),
+    ('\u{1134b}', &['\u{11347}', '\u{1133e}']),
+    ('\u{1134c}', &['\u{11347}', '\u{11357}']),
+    ('\u{114bb}', &['\u{114b9}', '\u{114ba}']),
+    ('\u{114bc}', &['\u{114b9}', '\u{114b0}']),
+    ('\u{114be}', &['\u{114b9}', '\u{114bd}']),
+    ('\u{115ba}', &['\u{115b8}', '\u{115af}']),
+    ('\u{115bb}', &['\u{115b9}', '\u{115af}']),
+    ('\u{11938}', &['\u{11935}', '\u{11930}']),
+    ('\u{1d15e}', &['\u{1d157}', '\u{1d165}']),
+    ('\u{1d15f}', &['\u{1d158}', '\u{1d165}']),
+    ('\u{1d160}', &['\u{1d158}', '\u{1d165}', '\u{1d16e}']),
+    ('\u{1d161}', &['\u{1d158}', '\u{1d165}', '\u{1d16f}']),
+    ('\u{1d162}', &['\u{1d158}', '\u{1d165}', '\u{1d170}']),
+    ('\u{1d163}', &['\u{1d158}', '\u{1d165}', '\u{1d171}']),
+    ('\u{1d164}', &['\u{1d158}', '\u{1d165}', '\u{1d172}']),
+    ('\u{1d1bb}', &['\u{1d1b9}', '\u{1d165}']),
+    ('\u{1d1bc}', &['\u{1d1ba}', '\u{1d165}']),
+    ('\u{1d1bd}', &['\u{1d1b9}', '\u{1d165}', '\u{1d16e}']),
+    ('\u{1d1be}', &['\u{1d1ba}', '\u{1d165}', '\u{1d16e}']),
+    ('\u{1d1bf}', &['\u{1d1b9}', '\u{1d165}', '\u{1d16f}']),
+    ('\u{1d1c0}', &['\u{1d1ba}', '\u{1d165}', '\u{1d16f}']),
+];
+
+// (起始, 结束, 组合类)，只列出组合类不为 0 的字符
+pub const COMBINING_CLASSES: &[(char, char, u8)] = &[
+    ('\u{300}', '\u{314}', 230),
+    ('\u{315}', '\u{315}', 232),
+    ('\u{316}', '\u{319}', 220),
+    ('\u{31a}', '\u{31a}', 232),
+    ('\u{31b}', '\u{31b}', 216),
+    ('\u{31c}', '\u{320}', 220),
+    ('\u{321}', '\u{322}', 202),
+    ('\u{323}', '\u{326}', 220),
+    ('\u{327}', '\u{328}', 202),
+    ('\u{329}', '\u{333}', 220),
+    ('\u{334}', '\u{338}', 1),
+    ('\u{339}', '\u{33c}', 220),
+    ('\u{33d}', '\u{344}', 230),
+    ('\u{345}', '\u{345}', 240),
+    ('\u{346}', '\u{346}', 230),
+    ('\u{347}', '\u{349}', 220),
+    ('\u{34a}', '\u{34c}', 230),
+    ('\u{34d}', '\u{34e}', 220),
+    ('\u{350}', '\u{352}', 230),
+    ('\u{353}', '\u{356}', 220),
+    ('\u{357}', '\u{357}', 230),
+    ('\u{358}', '\u{358}', 232),
+    ('\u{359}', '\u{35a}', 220),
+    ('\u{35b}', '\u{35b}', 230),
+    ('\u{35c}', '\u{35c}', 233),
+    ('\u{35d}', '\u{35e}', 234),
+    ('\u{35f}', '\u{35f}', 233),
+    ('\u{360}', '\u{361}', 234),
+    ('\u{362}', '\u{362}', 233),
+    ('\u{363}', '\u{36f}', 230),
+    ('\u{483}', '\u{487}', 230),
+    ('\u{591}', '\u{591}', 220),
+    ('\u{592}', '\u{595}', 230),
+    ('\u{596}', '\u{596}', 220),
+    ('\u{597}', '\u{599}', 230),
+    ('\u{59a}', '\u{59a}', 222),
+    ('\u{59b}', '\u{59b}', 220),
+    ('\u{59c}', '\u{5a1}', 230),
+    ('\u{5a2}', '\u{5a7}', 220),
+    ('\u{5a8}', '\u{5a9}', 230),
+    ('\u{5aa}', '\u{5aa}', 220),
+    ('\u{5ab}', '\u{5ac}', 230),
+    ('\u{5ad}', '\u{5ad}', 222),
+    ('\u{5ae}', '\u{5ae}', 228),
+    ('\u{5af}', '\u{5af}', 230),
+    ('\u{5b0}', '\u{5b0}', 10),
+    ('\u{5b1}', '\u{5b1}', 11),
+    ('\u{5b2}', '\u{5b2}', 12),
+    ('\u{5b3}', '\u{5b3}', 13),
+    ('\u{5b4}', '\u{5b4}', 14),
+    ('\u{5b5}', '\u{5b5}', 15),
+    ('\u{5b6}', '\u{5b6}', 16),
+    ('\u{5b7}', '\u{5b7}', 17),
+    ('\u{5b8}', '\u{5b8}', 18),
+    ('\u{5b9}', '\u{5ba}', 19),
+    ('\u{5bb}', '\u{5bb}', 20),
+    ('\u{5bc}', '\u{5bc}', 21),
+    ('\u{5bd}', '\u{5bd}', 22),
+    ('\u{5bf}', '\u{5bf}', 23),
+    ('\u{5c1}', '\u{5c1}', 24),
+    ('\u{5c2}', '\u{5c2}', 25),
+    ('\u{5c4}', '\u{5c4}', 230),
+    ('\u{5c5}', '\u{5c5}', 220),
+    ('\u{5c7}', '\u{5c7}', 18),
+    ('\u{610}', '\u{617}', 230),
+    ('\u{618}', '\u{618}', 30),
+    ('\u{619}', '\u{619}', 31),
+    ('\u{61a}', '\u{61a}', 32),
+    ('\u{64b}', '\u{64b}', 27),
+    ('\u{64c}', '\u{64c}', 28),
+    ('\u{64d}', '\u{64d}', 29),
+    ('\u{64e}', '\u{64e}', 30),
+    ('\u{64f}', '\u{64f}', 31),
+    ('\u{650}', '\u{650}', 32),
+    ('\u{651}', '\u{651}', 33),
+    ('\u{652}', '\u{652}', 34),
+    ('\u{653}', '\u{654}', 230),
+    ('\u{655}', '\u{656}', 220),
+    ('\u{657}', '\u{65b}', 230),
+    ('\u{65c}', '\u{65c}', 220),
+    ('\u{65d}', '\u{65e}', 230),
+    ('\u{65f}', '\u{65f}', 220),
+    ('\u{670}', '\u{670}', 35),
+    ('\u{6d6}', '\u{6dc}', 230),
+    ('\u{6df}', '\u{6e2}', 230),
+    ('\u{6e3}', '\u{6e3}', 220),
+    ('\u{6e4}', '\u{6e4}', 230),
+    ('\u{6e7}', '\u{6e8}', 230),
+    ('\u{6ea}', '\u{6ea}', 220),
+    ('\u{6eb}', '\u{6ec}', 230),
+    ('\u{6ed}', '\u{6ed}', 220),
+    ('\u{711}', '\u{711}', 36),
+    ('\u{730}', '\u{730}', 230),
+    ('\u{731}', '\u{731}', 220),
+    ('\u{732}', '\u{733}', 230),
+    ('\u{734}', '\u{734}', 220),
+    ('\u{735}', '\u{736}', 230),
+    ('\u{737}', '\u{739}', 220),
+    ('\u{73a}', '\u{73a}', 230),
+    ('\u{73b}', '\u{73c}', 220),
+    ('\u{73d}', '\u{73d}', 230),
+    ('\u{73e}', '\u{73e}', 220),
+    ('\u{73f}', '\u{741}', 230),
+    ('\u{742}', '\u{742}', 220),
+    ('\u{743}', '\u{743}', 230),
+    ('\u{744}', '\u{744}', 220),
+    ('\u{745}', '\u{745}', 230),
+    ('\u{746}', '\u{746}', 220),
+    ('\u{747}', '\u{747}', 230),
+    ('\u{748}', '\u{748}', 220),
+    ('\u{749}', '\u{74a}', 230),
+    ('\u{7eb}', '\u{7f1}', 230),
+    ('\u{7f2}', '\u{7f2}', 220),
+    ('\u{7f3}', '\u{7f3}', 230),
+    ('\u{7fd}', '\u{7fd}', 220),
+    ('\u{816}', '\u{819}', 230),
+    ('\u{81b}', '\u{823}', 230),
+    ('\u{825}', '\u{827}', 230),
+    ('\u{829}', '\u{82d}', 230),
+    ('\u{859}', '\u{85b}', 220),
+    ('\u{898}', '\u{898}', 230),
+    ('\u{899}', '\u{89b}', 220),
+    ('\u{89c}', '\u{89f}', 230),
+    ('\u{8ca}', '\u{8ce}', 230),
+    ('\u{8cf}', '\u{8d3}', 220),
+    ('\u{8d4}', '\u{8e1}', 230),
+    ('\u{8e3}', '\u{8e3}', 220),
+    ('\u{8e4}', '\u{8e5}', 230),
+    ('\u{8e6}', '\u{8e6}', 220),
+    ('\u{8e7}', '\u{8e8}', 230),
+    ('\u{8e9}', '\u{8e9}', 220),
+    ('\u{8ea}', '\u{8ec}', 230),
+    ('\u{8ed}', '\u{8ef}', 220),
+    ('\u{8f0}', '\u{8f0}', 27),
+    ('\u{8f1}', '\u{8f1}', 28),
+    ('\u{8f2}', '\u{8f2}', 29),
+    ('\u{8f3}', '\u{8f5}', 230),
+    ('\u{8f6}', '\u{8f6}', 220),
+    ('\u{8f7}', '\u{8f8}', 230),
+    ('\u{8f9}', '\u{8fa}', 220),
+    ('\u{8fb}', '\u{8ff}', 230),
+    ('\u{93c}', '\u{93c}', 7),
+    ('\u{94d}', '\u{94d}', 9),
+    ('\u{951}', '\u{951}', 230),
+    ('\u{952}', '\u{952}', 220),
+    ('\u{953}', '\u{954}', 230),
+    ('\u{9bc}', '\u{9bc}', 7),
+    ('\u{9cd}', '\u{9cd}', 9),
+    ('\u{9fe}', '\u{9fe}', 230),
+    ('\u{a3c}', '\u{a3c}', 7),
+    ('\u{a4d}', '\u{a4d}', 9),
+    ('\u{abc}', '\u{abc}', 7),
+    ('\u{acd}', '\u{acd}', 9),
+    ('\u{b3c}', '\u{b3c}', 7),
+    ('\u{b4d}', '\u{b4d}', 9),
+    ('\u{bcd}', '\u{bcd}', 9),
+    ('\u{c3c}', '\u{c3c}', 7),
+    ('\u{c4d}', '\u{c4d}', 9),
+    ('\u{c55}', '\u{c55}', 84),
+    ('\u{c56}', '\u{c56}', 91),
+    ('\u{cbc}', '\u{cbc}', 7),
+    ('\u{ccd}', '\u{ccd}', 9),
+    ('\u{d3b}', '\u{d3c}', 9),
+    ('\u{d4d}', '\u{d4d}', 9),
+    ('\u{dca}', '\u{dca}', 9),
+    ('\u{e38}', '\u{e39}', 103),
+    ('\u{e3a}', '\u{e3a}', 9),
+    ('\u{e48}', '\u{e4b}', 107),
+    ('\u{eb8}', '\u{eb9}', 118),
+    ('\u{eba}', '\u{eba}', 9),
+    ('\u{ec8}', '\u{ecb}', 122),
+    ('\u{f18}', '\u{f19}', 220),
+    ('\u{f35}', '\u{f35}', 220),
+    ('\u{f37}', '\u{f37}', 220),
+    ('\u{f39}', '\u{f39}', 216),
+    ('\u{f71}', '\u{f71}', 129),
+    ('\u{f72}', '\u{f72}', 130),
+    ('\u{f74}', '\u{f74}', 132),
+    ('\u{f7a}', '\u{f7d}', 130),
+    ('\u{f80}', '\u{f80}', 130),
+    ('\u{f82}', '\u{f83}', 230),
+    ('\u{f84}', '\u{f84}', 9),
+    ('\u{f86}', '\u{f87}', 230),
+    ('\u{fc6}', '\u{fc6}', 220),
+    ('\u{1037}', '\u{1037}', 7),
+    ('\u{1039}', '\u{103a}', 9),
+    ('\u{108d}', '\u{108d}', 220),
+    ('\u{135d}', '\u{135f}', 230),
+    ('\u{1714}', '\u{1715}', 9),
+    ('\u{1734}', '\u{1734}', 9),
+    ('\u{17d2}', '\u{17d2}', 9),
+    ('\u{17dd}', '\u{17dd}', 230),
+    ('\u{18a9}', '\u{18a9}', 228),
+    ('\u{1939}', '\u{1939}', 222),
+    ('\u{193a}', '\u{193a}', 230),
+    ('\u{193b}', '\u{193b}', 220),
+    ('\u{1a17}', '\u{1a17}', 230),
+    ('\u{1a18}', '\u{1a18}', 220),
+    ('\u{1a60}', '\u{1a60}', 9),
+    ('\u{1a75}', '\u{1a7c}', 230),
+    ('\u{1a7f}', '\u{1a7f}', 220),
+    ('\u{1ab0}', '\u{1ab4}', 230),
+    ('\u{1ab5}', '\u{1aba}', 220),
+    ('\u{1abb}', '\u{1abc}', 230),
+    ('\u{1abd}', '\u{1abd}', 220),
+    ('\u{1abf}', '\u{1ac0}', 220),
+    ('\u{1ac1}', '\u{1ac2}', 230),
+    ('\u{1ac3}', '\u{1ac4}', 220),
+    ('\u{1ac5}', '\u{1ac9}', 230),
+    ('\u{1aca}', '\u{1aca}', 220),
+    ('\u{1acb}', '\u{1ace}', 230),
+    ('\u{1b34}', '\u{1b34}', 7),
+    ('\u{1b44}', '\u{1b44}', 9),
+    ('\u{1b6b}', '\u{1b6b}', 230),
+    ('\u{1b6c}', '\u{1b6c}', 220),
+    ('\u{1b6d}', '\u{1b73}', 230),
+    ('\u{1baa}', '\u{1bab}', 9),
+    ('\u{1be6}', '\u{1be6}', 7),
+    ('\u{1bf2}', '\u{1bf3}', 9),
+    ('\u{1c37}', '\u{1c37}', 7),
+    ('\u{1cd0}', '\u{1cd2}', 230),
+    ('\u{1cd4}', '\u{1cd4}', 1),
+    ('\u{1cd5}', '\u{1cd9}', 220),
+    ('\u{1cda}', '\u{1cdb}', 230),
+    ('\u{1cdc}', '\u{1cdf}', 220),
+    ('\u{1ce0}', '\u{1ce0}', 230),
+    ('\u{1ce2}', '\u{1ce8}', 1),
+    ('\u{1ced}', '\u{1ced}', 220),
+    ('\u{1cf4}', '\u{1cf4}', 230),
+    ('\u{1cf8}', '\u{1cf9}', 230),
+    ('\u{1dc0}', '\u{1dc1}', 230),
+    ('\u{1dc2}', '\u{1dc2}', 220),
+    ('\u{1dc3}', '\u{1dc9}', 230),
+    ('\u{1dca}', '\u{1dca}', 220),
+    ('\u{1dcb}', '\u{1dcc}', 230),
+    ('\u{1dcd}', '\u{1dcd}', 234),
+    ('\u{1dce}', '\u{1dce}', 214),
+    ('\u{1dcf}', '\u{1dcf}', 220),
+    ('\u{1dd0}', '\u{1dd0}', 202),
+    ('\u{1dd1}', '\u{1df5}', 230),
+    ('\u{1df6}', '\u{1df6}', 232),
+    ('\u{1df7}', '\u{1df8}', 228),
+    ('\u{1df9}', '\u{1df9}', 220),
+    ('\u{1dfa}', '\u{1dfa}', 218),
+    ('\u{1dfb}', '\u{1dfb}', 230),
+    ('\u{1dfc}', '\u{1dfc}', 233),
+    ('\u{1dfd}', '\u{1dfd}', 220),
+    ('\u{1dfe}', '\u{1dfe}', 230),
+    ('\u{1dff}', '\u{1dff}', 220),
+    ('\u{20d0}', '\u{20d1}', 230),
+    ('\u{20d2}', '\u{20d3}', 1),
+    ('\u{20d4}', '\u{20d7}', 230),
+    ('\u{20d8}', '\u{20da}', 1),
+    ('\u{20db}', '\u{20dc}', 230),
+    ('\u{20e1}', '\u{20e1}', 230),
+    ('\u{20e5}', '\u{20e6}', 1),
+    ('\u{20e7}', '\u{20e7}', 230),
+    ('\u{20e8}', '\u{20e8}', 220),
+    ('\u{20e9}', '\u{20e9}', 230),
+    ('\u{20ea}', '\u{20eb}', 1),
+    ('\u{20ec}', '\u{20ef}', 220),
+    ('\u{20f0}', '\u{20f0}', 230),
+    ('\u{2cef}', '\u{2cf1}', 230),
+    ('\u{2d7f}', '\u{2d7f}', 9),
+    ('\u{2de0}', '\u{2dff}', 230),
+    ('\u{302a}', '\u{302a}', 218),
+    ('\u{302b}', '\u{302b}', 228),
+    ('\u{302c}', '\u{302c}', 232),
+    ('\u{302d}', '\u{302d}', 222),
+    ('\u{302e}', '\u{302f}', 224),
+    ('\u{3099}', '\u{309a}', 8),
+    ('\u{a66f}', '\u{a66f}', 230),
+    ('\u{a674}', '\u{a67d}', 230),
+    ('\u{a69e}', '\u{a69f}', 230),
+    ('\u{a6f0}', '\u{a6f1}', 230),
+    ('\u{a806}', '\u{a806}', 9),
+    ('\u{a82c}', '\u{a82c}', 9),
+    ('\u{a8c4}', '\u{a8c4}', 9),
+    ('\u{a8e0}', '\u{a8f1}', 230),
+    ('\u{a92b}', '\u{a92d}', 220),
+    ('\u{a953}', '\u{a953}', 9),
+    ('\u{a9b3}', '\u{a9b3}', 7),
+    ('\u{a9c0}', '\u{a9c0}', 9),
+    ('\u{aab0}', '\u{aab0}', 230),
+    ('\u{aab2}', '\u{aab3}', 230),
+    ('\u{aab4}', '\u{aab4}', 220),
+    ('\u{aab7}', '\u{aab8}', 230),
+    ('\u{aabe}', '\u{aabf}', 230),
+    ('\u{aac1}', '\u{aac1}', 230),
+    ('\u{aaf6}', '\u{aaf6}', 9),
+    ('\u{abed}', '\u{abed}', 9),
+    ('\u{fb1e}', '\u{fb1e}', 26),
+    ('\u{fe20}', '\u{fe26}', 230),
+    ('\u{fe27}', '\u{fe2d}', 220),
+    ('\u{fe2e}', '\u{fe2f}', 230),
+    ('\u{101fd}', '\u{101fd}', 220),
+    ('\u{102e0}', '\u{102e0}', 220),
+    ('\u{10376}', '\u{1037a}', 230),
+    ('\u{10a0d}', '\u{10a0d}', 220),
+    ('\u{10a0f}', '\u{10a0f}', 230),
+    ('\u{10a38}', '\u{10a38}', 230),
+    ('\u{10a39}', '\u{10a39}', 1),
+    ('\u{10a3a}', '\u{10a3a}', 220),
+    ('\u{10a3f}', '\u{10a3f}', 9),
+    ('\u{10ae5}', '\u{10ae5}', 230),
+    ('\u{10ae6}', '\u{10ae6}', 220),
+    ('\u{10d24}', '\u{10d27}', 230),
+    ('\u{10eab}', '\u{10eac}', 230),
+    ('\u{10f46}', '\u{10f47}', 220),
+    ('\u{10f48}', '\u{10f4a}', 230),
+    ('\u{10f4b}', '\u{10f4b}', 220),
+    ('\u{10f4c}', '\u{10f4c}', 230),
+    ('\u{10f4d}', '\u{10f50}', 220),
+    ('\u{10f82}', '\u{10f82}', 230),
+    ('\u{10f83}', '\u{10f83}', 220),
+    ('\u{10f84}', '\u{10f84}', 230),
+    ('\u{10f85}', '\u{10f85}', 220),
+    ('\u{11046}', '\u{11046}', 9),
+    ('\u{11070}', '\u{11070}', 9),
+    ('\u{1107f}', '\u{1107f}', 9),
+    ('\u{110b9}', '\u{110b9}', 9),
+    ('\u{110ba}', '\u{110ba}', 7),
+    ('\u{11100}', '\u{11102}', 230),
+    ('\u{11133}', '\u{11134}', 9),
+    ('\u{11173}', '\u{11173}', 7),
+    ('\u{111c0}', '\u{111c0}', 9),
+    ('\u{111ca}', '\u{111ca}', 7),
+    ('\u{11235}', '\u{11235}', 9),
+    ('\u{11236}', '\u{11236}', 7),
+    ('\u{112e9}', '\u{112e9}', 7),
+    ('\u{112ea}', '\u{112ea}', 9),
+    ('\u{1133b}', '\u{1133c}', 7),
+    ('\u{1134d}', '\u{1134d}', 9),
+    ('\u{11366}', '\u{1136c}', 230),
+    ('\u{11370}', '\u{11374}', 230),
+    ('\u{11442}', '\u{11442}', 9),
+    ('\u{11446}', '\u{11446}', 7),
+    ('\u{1145e}', '\u{1145e}', 230),
+    ('\u{114c2}', '\u{114c2}', 9),
+    ('\u{114c3}', '\u{114c3}', 7),
+    ('\u{115bf}', '\u{115bf}', 9),
+    ('\u{115c0}', '\u{115c0}', 7),
+    ('\u{1163f}', '\u{1163f}', 9),
+    ('\u{116b6}', '\u{116b6}', 9),
+    ('\u{116b7}', '\u{116b7}', 7),
+    ('\u{1172b}', '\u{1172b}', 9),
+    ('\u{11839}', '\u{11839}', 9),
+    ('\u{1183a}', '\u{1183a}', 7),
+    ('\u{1193d}', '\u{1193e}', 9),
+    ('\u{11943}', '\u{11943}', 7),
+    ('\u{119e0}', '\u{119e0}', 9),
+    ('\u{11a34}', '\u{11a34}', 9),
+    ('\u{11a47}', '\u{11a47}', 9),
+    ('\u{11a99}', '\u{11a99}', 9),
+    ('\u{11c3f}', '\u{11c3f}', 9),
+    ('\u{11d42}', '\u{11d42}', 7),
+    ('\u{11d44}', '\u{11d45}', 9),
+    ('\u{11d97}', '\u{11d97}', 9),
+    ('\u{16af0}', '\u{16af4}', 1),
+    ('\u{16b30}', '\u{16b36}', 230),
+    ('\u{16ff0}', '\u{16ff1}', 6),
+    ('\u{1bc9e}', '\u{1bc9e}', 1),
+    ('\u{1d165}', '\u{1d166}', 216),
+    ('\u{1d167}', '\u{1d169}', 1),
+    ('\u{1d16d}', '\u{1d16d}', 226),
+    ('\u{1d16e}', '\u{1d172}', 216),
+    ('\u{1d17b}', '\u{1d182}', 220),
+    ('\u{1d185}', '\u{1d189}', 230),
+    ('\u{1d18a}', '\u{1d18b}', 220),
+    ('\u{1d1aa}', '\u{1d1ad}', 230),
+    ('\u{1d242}', '\u{1d244}', 230),
+    ('\u{1e000}', '\u{1e006}', 230),
+    ('\u{1e008}', '\u{1e018}', 230),
+    ('\u{1e01b}', '\u{1e021}', 230),
+    ('\u{1e023}', '\u{1e024}', 230),
+    ('\u{1e026}', '\u{1e02a}', 230),
+    ('\u{1e130}', '\u{1e136}', 230),
+    ('\u{1e2ae}', '\u{1e2ae}', 230),
+    ('\u{1e2ec}', '\u{1e2ef}', 230),
+    ('\u{1e8d0}', '\u{1e8d6}', 220),
+    ('\u{1e944}', '\u{1e949}', 230),
+    ('\u{1e94a}', '\u{1e94a}', 7),
+];
@@ -0,0 +1,133 @@
+// 零停机重启（仅 Unix）：收到 SIGUSR2 时启动新进程并把监听 FD 交给它，
+// 新进程就绪后向旧进程发送 SIGTERM，旧进程随即进入优雅关闭
+use crate::listener::Listener;
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::os::fd::{BorrowedFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info, warn};
+
+// 继承的监听 FD
+const LISTEN_FD_ENV: &str = "SONICWAVE_LISTEN_FD";
+// 等待接管完成的旧进程 PID
+const PARENT_PID_ENV: &str = "SONICWAVE_UPGRADE_PARENT";
+
+static HANDED_OFF: AtomicBool = AtomicBool::new(false);
+
+// 若由旧进程拉起，取出继承的全部监听套接字（按监听顺序，逗号分隔）
+pub fn inherited_listeners() -> Vec<Listener> {
+    let Ok(value) = std::env::var(LISTEN_FD_ENV) else {
+        return Vec::new();
+    };
+    std::env::remove_var(LISTEN_FD_ENV);
+
+    let mut listeners = Vec::new();
+    for fd in value
+        .split(',')
+        .filter_map(|v| v.trim().parse::<RawFd>().ok())
+    {
+        match Listener::from_fd(fd) {
+            Ok(listener) => {
+                info!(
+                    "Took over listener fd {} ({}) from previous process",
+                    fd,
+                    listener.local_addr()
+                );
+                listeners.push(listener);
+            }
+            Err(e) => error!("Inherited listener fd {} is unusable: {}", fd, e),
+        }
+    }
+    listeners
+}
+
+// 是否已把监听套接字交给新进程（此时不应删除 unix socket 文件）
+pub fn handed_off() -> bool {
+    HANDED_OFF.load(Ordering::Relaxed)
+}
+
+// 由旧进程拉起时，等待接管完成的旧进程 PID
+pub fn parent_pid() -> Option<i32> {
+    std::env::var(PARENT_PID_ENV)
+        .ok()
+        .and_then(|v| v.parse::<i32>().ok())
+}
+
+// 新进程已开始接受连接，通知旧进程退出
+pub fn notify_parent_ready() {
+    let Some(pid) = parent_pid() else {
+        return;
+    };
+    crate::daemon::take_over();
+    std::env::remove_var(PARENT_PID_ENV);
+
+    match kill(Pid::from_raw(pid), Signal::SIGTERM) {
+        Ok(()) => info!("Upgrade complete, asked previous process {} to drain", pid),
+        Err(e) => warn!("Failed to signal previous process {}: {}", pid, e),
+    }
+}
+
+// 监听 SIGUSR2，每次收到都以相同参数重新执行当前二进制
+pub fn spawn_upgrade_handler(listener_fds: Vec<RawFd>) {
+    tokio::spawn(async move {
+        let mut usr2 = match signal(SignalKind::user_defined2()) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Failed to install SIGUSR2 handler: {}", e);
+                return;
+            }
+        };
+
+        while usr2.recv().await.is_some() {
+            info!("Received SIGUSR2, starting new process for handoff");
+            if let Err(e) = spawn_successor(&listener_fds) {
+                error!("Upgrade failed, continuing with current process: {}", e);
+            }
+        }
+    });
+}
+
+fn spawn_successor(listener_fds: &[RawFd]) -> std::io::Result<()> {
+    // 清除 CLOEXEC，让监听 FD 跨 exec 保留给子进程
+    for &fd in listener_fds {
+        set_cloexec(fd, false)?;
+    }
+
+    let fd_list = listener_fds
+        .iter()
+        .map(|fd| fd.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    let exe = std::env::current_exe()?;
+    let child = std::process::Command::new(exe)
+        .args(std::env::args_os().skip(1))
+        .env(LISTEN_FD_ENV, fd_list)
+        .env(PARENT_PID_ENV, std::process::id().to_string())
+        // 新进程会通过 MAINPID 接替主进程身份，watchdog 由它继续发送
+        .env_remove("WATCHDOG_PID")
+        .spawn();
+
+    // 恢复 CLOEXEC，避免泄漏给之后的其他子进程
+    for &fd in listener_fds {
+        let _ = set_cloexec(fd, true);
+    }
+
+    let child = child?;
+    HANDED_OFF.store(true, Ordering::Relaxed);
+    info!("Started successor process {}", child.id());
+    Ok(())
+}
+
+fn set_cloexec(fd: RawFd, enabled: bool) -> std::io::Result<()> {
+    // SAFETY: 监听 FD 在服务器运行期间始终有效
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+    let flags = if enabled {
+        FdFlag::FD_CLOEXEC
+    } else {
+        FdFlag::empty()
+    };
+    fcntl(fd, FcntlArg::F_SETFD(flags)).map_err(std::io::Error::from)?;
+    Ok(())
+}
@@ -0,0 +1,517 @@
+// 写入模式（[upload]）：PUT 创建或覆盖文件（先写临时文件、fsync 后再 rename），DELETE 删除文件，
+// multipart POST 接收浏览器表单上传；只允许配置的前缀，需要 Basic / Bearer 认证。
+// PUT 带 Content-MD5 / Repr-Digest 时校验收到的内容，不一致或传输中断时不留下写了一半的文件
+use crate::basic_auth;
+use crate::conditional;
+use crate::digest::Verifier;
+use crate::glob::PathPattern;
+use crate::index;
+use crate::methods;
+use crate::tus::Tus;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures_util::{Stream, StreamExt};
+use percent_encoding::percent_decode_str;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct UploadConfig {
+    // 允许写入的 URL 前缀，如 ["/drop/"]
+    #[serde(default = "default_prefixes")]
+    pub prefixes: Vec<String>,
+    // 单个文件的大小上限（字节）
+    #[serde(default = "default_max_size")]
+    pub max_size: u64,
+    // Basic 认证的用户名，未设置时接受任意用户名
+    #[serde(default)]
+    pub username: Option<String>,
+    // Basic 认证的密码，也可以作为 Bearer token 使用
+    #[serde(serialize_with = "crate::config::redact")]
+    pub password: String,
+    // tus 断点续传端点（如 "/tus/"），未设置时不启用
+    #[serde(default)]
+    pub tus_endpoint: Option<String>,
+    // 未完成的 tus 上传的暂存目录，重启后可以继续上传
+    #[serde(default = "default_staging_dir")]
+    pub staging_dir: PathBuf,
+}
+
+fn default_prefixes() -> Vec<String> {
+    vec!["/uploads/".to_string()]
+}
+
+fn default_max_size() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_staging_dir() -> PathBuf {
+    std::env::temp_dir().join("sonic-wave-tus")
+}
+
+pub struct Uploader {
+    root: PathBuf,
+    // 站点目录的真实路径，写入位置不能经由符号链接逃出该目录
+    canonical_root: PathBuf,
+    prefixes: Vec<String>,
+    max_size: u64,
+    username: Option<String>,
+    password: String,
+    allow_dotfiles: bool,
+    deny: Vec<PathPattern>,
+    tus: Option<Tus>,
+}
+
+// 同一进程内临时文件名的序号
+static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+impl Uploader {
+    pub fn new(
+        config: &UploadConfig,
+        root: &Path,
+        allow_dotfiles: bool,
+        deny: &[String],
+    ) -> Result<Self, String> {
+        if config.password.is_empty() {
+            return Err("upload password must not be empty".to_string());
+        }
+        if config.prefixes.is_empty() {
+            return Err("upload prefixes must not be empty".to_string());
+        }
+        let mut prefixes = Vec::new();
+        for prefix in &config.prefixes {
+            if !prefix.starts_with('/') {
+                return Err(format!("upload prefix `{}` must start with '/'", prefix));
+            }
+            // "/drop" 与 "/drop/" 等价，但不匹配 "/dropbox"
+            prefixes.push(format!("{}/", prefix.trim_end_matches('/')));
+        }
+        let canonical_root = std::fs::canonicalize(root)
+            .map_err(|e| format!("upload root {}: {}", root.display(), e))?;
+        Ok(Uploader {
+            root: root.to_path_buf(),
+            canonical_root,
+            prefixes,
+            max_size: config.max_size,
+            username: config.username.clone(),
+            password: config.password.clone(),
+            allow_dotfiles,
+            tus: config
+                .tus_endpoint
+                .as_deref()
+                .map(|endpoint| Tus::new(endpoint, &config.staging_dir))
+                .transpose()?,
+            deny: deny
+                .iter()
+                .map(|pattern| PathPattern::new(pattern))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn prefixes(&self) -> &[String] {
+        &self.prefixes
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    pub fn tus(&self) -> Option<&Tus> {
+        self.tus.as_ref()
+    }
+
+    pub fn authorized(&self, headers: &HeaderMap) -> bool {
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if let Some(token) = bearer {
+            return constant_time_eq(token.trim().as_bytes(), self.password.as_bytes());
+        }
+        basic_auth::matches(headers, self.username.as_deref(), &self.password)
+    }
+
+    // 解码后的请求路径是否在允许的前缀内
+    pub fn in_prefix(&self, decoded: &str) -> bool {
+        let dir = format!("{}/", decoded.trim_end_matches('/'));
+        self.prefixes.iter().any(|prefix| dir.starts_with(prefix))
+    }
+
+    // 与 GET 相同的 dotfile / deny 规则
+    pub fn denied(&self, decoded: &str) -> bool {
+        if !self.allow_dotfiles
+            && decoded
+                .split('/')
+                .any(|segment| segment.starts_with('.') && segment != ".well-known")
+        {
+            return true;
+        }
+        self.deny.iter().any(|pattern| pattern.matches(decoded))
+    }
+
+    // 最近一个已存在的上级目录位于站点目录内
+    pub async fn contained(&self, target: &Path) -> bool {
+        let mut dir = target.parent();
+        while let Some(current) = dir {
+            if let Ok(real) = tokio::fs::canonicalize(current).await {
+                return real.starts_with(&self.canonical_root);
+            }
+            dir = current.parent();
+        }
+        false
+    }
+
+    // 逐级创建站点目录下缺少的目录，路径上的符号链接一律拒绝：
+    // contained 检查之后才换成符号链接的目录也不会让写入落到站点目录之外
+    pub async fn create_dirs(&self, dir: &Path) -> Result<(), Response> {
+        let Ok(relative) = dir.strip_prefix(&self.root) else {
+            return Err(StatusCode::FORBIDDEN.into_response());
+        };
+        let mut current = self.root.clone();
+        for component in relative.components() {
+            current.push(component);
+            if tokio::fs::symlink_metadata(&current).await.is_err() {
+                if let Err(e) = tokio::fs::create_dir(&current).await {
+                    if e.kind() != std::io::ErrorKind::AlreadyExists {
+                        return Err(server_error("create directory", &current, e));
+                    }
+                }
+            }
+            match tokio::fs::symlink_metadata(&current).await {
+                Ok(meta) if meta.file_type().is_symlink() => {
+                    warn!("Refusing to write through symlink {}", current.display());
+                    return Err(StatusCode::FORBIDDEN.into_response());
+                }
+                Ok(meta) if meta.is_dir() => {}
+                Ok(_) => return Err(StatusCode::CONFLICT.into_response()),
+                Err(e) => return Err(server_error("create directory", &current, e)),
+            }
+        }
+        Ok(())
+    }
+
+    // 将请求体写入 target：先写同目录下的临时文件，校验摘要并 fsync 后 rename，读取方不会看到写了一半的文件
+    async fn write<S, E>(
+        &self,
+        target: &Path,
+        mut body: S,
+        mut verifier: Verifier,
+    ) -> Result<u64, Response>
+    where
+        S: Stream<Item = Result<axum::body::Bytes, E>> + Unpin,
+        E: std::fmt::Display,
+    {
+        let dir = target.parent().unwrap_or(&self.root);
+        self.create_dirs(dir).await?;
+        let temp = dir.join(format!(
+            ".upload-{}-{}.tmp",
+            std::process::id(),
+            NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = async {
+            let mut file = tokio::fs::File::create(&temp)
+                .await
+                .map_err(|e| server_error("create", &temp, e))?;
+            let mut written = 0u64;
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk.map_err(|e| {
+                    warn!("Upload to {} aborted: {}", target.display(), e);
+                    StatusCode::BAD_REQUEST.into_response()
+                })?;
+                written += chunk.len() as u64;
+                if written > self.max_size {
+                    return Err(StatusCode::PAYLOAD_TOO_LARGE.into_response());
+                }
+                verifier.update(&chunk);
+                file.write_all(&chunk)
+                    .await
+                    .map_err(|e| server_error("write", &temp, e))?;
+            }
+            if let Err(source) = verifier.verify() {
+                warn!(
+                    "Upload to {} rejected: {} mismatch",
+                    target.display(),
+                    source
+                );
+                return Err(
+                    (StatusCode::BAD_REQUEST, format!("{} mismatch\n", source)).into_response()
+                );
+            }
+            file.sync_all()
+                .await
+                .map_err(|e| server_error("write", &temp, e))?;
+            tokio::fs::rename(&temp, target)
+                .await
+                .map_err(|e| server_error("rename", target, e))?;
+            sync_dir(dir).await;
+            Ok(written)
+        }
+        .await;
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp).await;
+        }
+        result
+    }
+
+    async fn put(&self, req: Request, decoded: &str, target: PathBuf) -> Response {
+        if decoded.ends_with('/') {
+            return StatusCode::METHOD_NOT_ALLOWED.into_response();
+        }
+        if too_large(req.headers(), self.max_size) {
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+        }
+        let current = match tokio::fs::metadata(&target).await {
+            Ok(meta) if meta.is_dir() => return StatusCode::CONFLICT.into_response(),
+            Ok(meta) => Some(meta),
+            Err(_) => None,
+        };
+        if let Some(response) = conditional::check_write(req.headers(), current.as_ref()) {
+            return response;
+        }
+        let verifier = match Verifier::from_headers(req.headers()) {
+            Ok(verifier) => verifier,
+            Err(e) => return (StatusCode::BAD_REQUEST, format!("{}\n", e)).into_response(),
+        };
+        let body = req.into_body().into_data_stream();
+        match self.write(&target, body, verifier).await {
+            Ok(written) => {
+                info!("Uploaded {} ({} bytes)", decoded, written);
+                let status = if current.is_some() {
+                    StatusCode::NO_CONTENT
+                } else {
+                    StatusCode::CREATED
+                };
+                // 返回新文件的 ETag，下次覆盖时可以带上 If-Match
+                match tokio::fs::metadata(&target).await {
+                    Ok(meta) => {
+                        let entry = index::file_entry(meta.len(), meta.modified().ok(), None);
+                        (status, [(header::ETAG, entry.etag)]).into_response()
+                    }
+                    Err(_) => status.into_response(),
+                }
+            }
+            Err(response) => response,
+        }
+    }
+
+    async fn delete(&self, headers: &HeaderMap, decoded: &str, target: PathBuf) -> Response {
+        match tokio::fs::symlink_metadata(&target).await {
+            // 只删除文件，目录保留
+            Ok(meta) if meta.is_dir() => StatusCode::CONFLICT.into_response(),
+            Ok(meta) => {
+                if let Some(response) = conditional::check_write(headers, Some(&meta)) {
+                    return response;
+                }
+                match tokio::fs::remove_file(&target).await {
+                    Ok(()) => {
+                        info!("Deleted {}", decoded);
+                        StatusCode::NO_CONTENT.into_response()
+                    }
+                    Err(e) => server_error("delete", &target, e),
+                }
+            }
+            Err(_) => StatusCode::NOT_FOUND.into_response(),
+        }
+    }
+
+    // multipart/form-data 上传到目录：每个文件字段保存为目录下的同名文件，重名时追加序号
+    async fn post(&self, req: Request, decoded: &str, dir: PathBuf) -> Response {
+        let boundary = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| multer::parse_boundary(v).ok());
+        let Some(boundary) = boundary else {
+            return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+        };
+        if matches!(tokio::fs::metadata(&dir).await, Ok(meta) if !meta.is_dir()) {
+            return StatusCode::CONFLICT.into_response();
+        }
+        let base = format!("{}/", decoded.trim_end_matches('/'));
+        let mut multipart = multer::Multipart::new(req.into_body().into_data_stream(), boundary);
+        let mut saved = Vec::new();
+        loop {
+            let field = match multipart.next_field().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Malformed upload to {}: {}", decoded, e);
+                    return StatusCode::BAD_REQUEST.into_response();
+                }
+            };
+            // 普通表单字段忽略
+            let Some(name) = field.file_name().and_then(file_name) else {
+                continue;
+            };
+            if self.denied(&format!("{}{}", base, name)) {
+                return StatusCode::FORBIDDEN.into_response();
+            }
+            let target = available_name(&dir, &name).await;
+            if !self.contained(&target).await {
+                warn!(
+                    "Refusing upload outside the site directory: {}{}",
+                    base, name
+                );
+                return StatusCode::FORBIDDEN.into_response();
+            }
+            match self.write(&target, field, Verifier::default()).await {
+                Ok(written) => {
+                    let file_name = target
+                        .file_name()
+                        .map(|n| n.to_string_lossy().into_owned())
+                        .unwrap_or(name);
+                    info!("Uploaded {}{} ({} bytes)", base, file_name, written);
+                    saved.push(format!("{}{}", base, file_name));
+                }
+                Err(response) => return response,
+            }
+        }
+        let body = serde_json::json!({ "files": saved }).to_string();
+        (
+            StatusCode::CREATED,
+            [(header::CONTENT_TYPE, "application/json")],
+            body,
+        )
+            .into_response()
+    }
+}
+
+// 客户端提供的文件名只取最后一段
+pub fn file_name(raw: &str) -> Option<String> {
+    raw.rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty() && *name != ".." && !name.contains('\0'))
+        .map(str::to_string)
+}
+
+// 目标已存在时依次尝试 "name (1).ext"、"name (2).ext"……
+pub async fn available_name(dir: &Path, name: &str) -> PathBuf {
+    let candidate = dir.join(name);
+    if tokio::fs::symlink_metadata(&candidate).await.is_err() {
+        return candidate;
+    }
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, format!(".{}", ext)),
+        _ => (name, String::new()),
+    };
+    let mut n = 1;
+    loop {
+        let candidate = dir.join(format!("{} ({}){}", stem, n, ext));
+        if tokio::fs::symlink_metadata(&candidate).await.is_err() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+// rename 之后同步所在目录，断电后目录项同样是新文件
+pub async fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Err(e) = async { tokio::fs::File::open(dir).await?.sync_all().await }.await {
+        warn!("Failed to sync directory {}: {}", dir.display(), e);
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+fn too_large(headers: &HeaderMap, max_size: u64) -> bool {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .is_some_and(|len| len > max_size)
+}
+
+pub fn server_error(action: &str, path: &Path, e: std::io::Error) -> Response {
+    warn!("Failed to {} {}: {}", action, path.display(), e);
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// 管理端点（部署、切换、清除等）的 Bearer token 认证；未设置 token 时一律拒绝
+pub fn bearer_authorized(headers: &HeaderMap, expected: Option<&str>) -> bool {
+    let Some(expected) = expected else {
+        return false;
+    };
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|token| constant_time_eq(token.trim().as_bytes(), expected.as_bytes()))
+}
+
+pub fn bearer_unauthorized() -> Response {
+    let mut response = StatusCode::UNAUTHORIZED.into_response();
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+pub fn unauthorized() -> Response {
+    let mut response = StatusCode::UNAUTHORIZED.into_response();
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static("Basic realm=\"Sonic Wave uploads\", charset=\"UTF-8\""),
+    );
+    response
+}
+
+// 拦截 tus 端点与前缀内的 PUT / POST / DELETE，其余请求交给站点
+pub async fn handle(State(uploader): State<Arc<Uploader>>, req: Request, next: Next) -> Response {
+    let Ok(decoded) = percent_decode_str(req.uri().path()).decode_utf8() else {
+        return next.run(req).await;
+    };
+    let decoded = decoded.into_owned();
+    if let Some(tus) = uploader.tus().filter(|tus| tus.matches(&decoded)) {
+        return tus.handle(&uploader, req, &decoded).await;
+    }
+    let method = req.method().clone();
+    // 前缀内的 OPTIONS 在站点返回的 Allow 上加上上传的方法
+    if method == Method::OPTIONS && uploader.in_prefix(&decoded) {
+        let mut response = next.run(req).await;
+        methods::extend_allow(&mut response, &["PUT", "POST", "DELETE"]);
+        return response;
+    }
+    if method != Method::PUT && method != Method::POST && method != Method::DELETE {
+        return next.run(req).await;
+    }
+    if !uploader.in_prefix(&decoded) {
+        return next.run(req).await;
+    }
+    if !uploader.authorized(req.headers()) {
+        return unauthorized();
+    }
+    if uploader.denied(&decoded) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let Some(target) = crate::site::fs_path(&uploader.root, req.uri().path()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    if !uploader.contained(&target).await {
+        warn!("Refusing upload outside the site directory: {}", decoded);
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    if method == Method::PUT {
+        uploader.put(req, &decoded, target).await
+    } else if method == Method::DELETE {
+        uploader.delete(req.headers(), &decoded, target).await
+    } else {
+        uploader.post(req, &decoded, target).await
+    }
+}
@@ -0,0 +1,286 @@
+// io_uring 文件读取后端（仅 Linux，cargo feature "io-uring"）：专用线程持有 ring，
+// 请求处理中的读取通过 channel 提交，按块流式返回；不可用时回退到标准文件 I/O
+use crate::mmap::{direct_path, file_response, has_sidecar, parse_range};
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::{header, Method, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::Service;
+
+// 每次读取的块大小
+const CHUNK_SIZE: usize = 256 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoBackend {
+    #[default]
+    Std,
+    Uring,
+}
+
+pub struct UringReader {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    tx: std::sync::mpsc::Sender<ring::ReadOp>,
+}
+
+impl UringReader {
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub fn new() -> io::Result<Self> {
+        Ok(UringReader {
+            tx: ring::spawn(256)?,
+        })
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+    pub fn new() -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "built without the io-uring feature",
+        ))
+    }
+
+    // 从 offset 开始最多读取 len 字节，返回空 Bytes 表示已到文件末尾
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    async fn read_at(&self, file: Arc<File>, offset: u64, len: usize) -> io::Result<Bytes> {
+        let (reply, rx) = tokio::sync::oneshot::channel();
+        self.tx
+            .send(ring::ReadOp {
+                file,
+                offset,
+                len,
+                reply,
+            })
+            .map_err(|_| io::Error::other("io_uring thread exited"))?;
+        rx.await
+            .map_err(|_| io::Error::other("io_uring thread exited"))?
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+    async fn read_at(&self, _file: Arc<File>, _offset: u64, _len: usize) -> io::Result<Bytes> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod ring {
+    use axum::body::Bytes;
+    use io_uring::{opcode, types, IoUring, Probe};
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::io;
+    use std::os::fd::AsRawFd;
+    use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+    use std::sync::Arc;
+    use tokio::sync::oneshot;
+    use tracing::error;
+
+    pub struct ReadOp {
+        pub file: Arc<File>,
+        pub offset: u64,
+        pub len: usize,
+        pub reply: oneshot::Sender<io::Result<Bytes>>,
+    }
+
+    pub fn spawn(entries: u32) -> io::Result<Sender<ReadOp>> {
+        let ring = IoUring::new(entries)?;
+        // IORING_OP_READ 需要 Linux 5.6+
+        let mut probe = Probe::new();
+        ring.submitter().register_probe(&mut probe)?;
+        if !probe.is_supported(opcode::Read::CODE) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "kernel does not support IORING_OP_READ",
+            ));
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::Builder::new()
+            .name("io-uring".to_string())
+            .spawn(move || {
+                if let Err(e) = run(ring, rx) {
+                    error!("io_uring thread failed: {}", e);
+                }
+            })?;
+        Ok(tx)
+    }
+
+    fn run(mut ring: IoUring, rx: Receiver<ReadOp>) -> io::Result<()> {
+        // user_data -> (请求, 读缓冲区)；缓冲区在完成前必须保持有效
+        let mut pending: HashMap<u64, (ReadOp, Vec<u8>)> = HashMap::new();
+        let mut next_id = 0u64;
+        loop {
+            // 没有进行中的读取时阻塞等待新请求
+            let mut ops = Vec::new();
+            if pending.is_empty() {
+                match rx.recv() {
+                    Ok(op) => ops.push(op),
+                    Err(_) => return Ok(()),
+                }
+            }
+            loop {
+                match rx.try_recv() {
+                    Ok(op) => ops.push(op),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) if pending.is_empty() && ops.is_empty() => {
+                        return Ok(())
+                    }
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            }
+
+            for op in ops {
+                let mut buf = vec![0u8; op.len];
+                let entry = opcode::Read::new(
+                    types::Fd(op.file.as_raw_fd()),
+                    buf.as_mut_ptr(),
+                    op.len as u32,
+                )
+                .offset(op.offset)
+                .build()
+                .user_data(next_id);
+                // SAFETY: 缓冲区与文件保存在 pending 中，直到对应的完成事件返回
+                loop {
+                    let pushed = unsafe { ring.submission().push(&entry).is_ok() };
+                    if pushed {
+                        break;
+                    }
+                    ring.submit()?;
+                }
+                pending.insert(next_id, (op, buf));
+                next_id = next_id.wrapping_add(1);
+            }
+
+            match ring.submit_and_wait(1) {
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            let completed: Vec<(u64, i32)> = ring
+                .completion()
+                .map(|cqe| (cqe.user_data(), cqe.result()))
+                .collect();
+            for (id, result) in completed {
+                let Some((op, mut buf)) = pending.remove(&id) else {
+                    continue;
+                };
+                let result = if result < 0 {
+                    Err(io::Error::from_raw_os_error(-result))
+                } else {
+                    buf.truncate(result as usize);
+                    Ok(Bytes::from(buf))
+                };
+                let _ = op.reply.send(result);
+            }
+        }
+    }
+}
+
+// 包在 ServeDir 外面：可直接发送的文件通过 io_uring 按块读取；未启用时直接透传
+#[derive(Clone)]
+pub struct UringService<S> {
+    inner: S,
+    reader: Option<Arc<UringReader>>,
+    dir: PathBuf,
+    precompressed: bool,
+}
+
+impl<S> UringService<S> {
+    pub fn new(inner: S, reader: Option<Arc<UringReader>>, dir: &str, precompressed: bool) -> Self {
+        UringService {
+            inner,
+            reader,
+            dir: PathBuf::from(dir),
+            precompressed,
+        }
+    }
+}
+
+impl<S> Service<Request<Body>> for UringService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self::Response, Self::Error>> + Send>,
+    >;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let Some(reader) = self.reader.clone() else {
+            return Box::pin(inner.call(req));
+        };
+        let path = direct_path(&self.dir, &req);
+        // 启用旁路文件时记下 Accept-Encoding，存在匹配的预压缩版本则交给 ServeDir
+        let accept_encoding = req
+            .headers()
+            .get(header::ACCEPT_ENCODING)
+            .filter(|_| self.precompressed)
+            .cloned();
+
+        Box::pin(async move {
+            let Some(path) = path else {
+                return inner.call(req).await;
+            };
+            if has_sidecar(&path, accept_encoding.as_ref()).await {
+                return inner.call(req).await;
+            }
+            let file = match tokio::fs::File::open(&path).await {
+                Ok(file) => file.into_std().await,
+                Err(_) => return inner.call(req).await,
+            };
+            let meta = match file.metadata() {
+                Ok(meta) if meta.is_file() => meta,
+                _ => return inner.call(req).await,
+            };
+            let range = match req.headers().get(header::RANGE) {
+                Some(value) => match parse_range(value, meta.len()) {
+                    Some(range) => Some(range),
+                    None => return inner.call(req).await,
+                },
+                None => None,
+            };
+
+            let (start, end) = match &range {
+                Some(range) => (range.start, range.end),
+                None => (0, meta.len()),
+            };
+            let mut response = file_response(&path, meta.len(), meta.modified().ok(), range);
+            if req.method() != Method::HEAD {
+                let file = Arc::new(file);
+                let chunks = futures_util::stream::unfold(start, move |offset| {
+                    let reader = reader.clone();
+                    let file = file.clone();
+                    async move {
+                        if offset >= end {
+                            return None;
+                        }
+                        let len = CHUNK_SIZE.min((end - offset) as usize);
+                        match reader.read_at(file, offset, len).await {
+                            Ok(chunk) if chunk.is_empty() => {
+                                Some((Err(io::Error::from(io::ErrorKind::UnexpectedEof)), end))
+                            }
+                            Ok(chunk) => {
+                                let next = offset + chunk.len() as u64;
+                                Some((Ok(chunk), next))
+                            }
+                            Err(e) => Some((Err(e), end)),
+                        }
+                    }
+                });
+                *response.body_mut() = Body::from_stream(chunks);
+            }
+            Ok(response)
+        })
+    }
+}
@@ -0,0 +1,139 @@
+// User-Agent 过滤（[[user_agent]]）：按正则匹配请求的 User-Agent，拒绝滥用的爬虫
+// 或为已知机器人返回固定内容；在路由与文件系统访问之前执行
+use crate::metrics::METRICS;
+use crate::vary;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::Response;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::debug;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct UserAgentRule {
+    // 正则表达式，如 "(?i)scrapy|python-requests"；没有 User-Agent 头时按空字符串匹配
+    pub pattern: String,
+    #[serde(default)]
+    pub action: Action,
+    // 响应状态码，默认 deny 为 403、respond 为 200
+    #[serde(default)]
+    pub status: Option<u16>,
+    // 响应正文，与 file 二选一
+    #[serde(default)]
+    pub body: Option<String>,
+    // 从文件读取响应正文（相对当前目录），Content-Type 按扩展名推断
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    // 放行，不再匹配后面的规则
+    Allow,
+    // 拒绝并计入 ua_blocked
+    #[default]
+    Deny,
+    // 返回固定内容
+    Respond,
+}
+
+struct Rule {
+    pattern: Regex,
+    action: Action,
+    status: StatusCode,
+    content_type: HeaderValue,
+    body: Vec<u8>,
+}
+
+pub struct Filter {
+    rules: Vec<Rule>,
+}
+
+impl Filter {
+    pub fn new(rules: &[UserAgentRule]) -> Result<Self, String> {
+        let mut compiled = Vec::new();
+        for rule in rules {
+            let pattern = Regex::new(&rule.pattern)
+                .map_err(|e| format!("invalid user_agent pattern `{}`: {}", rule.pattern, e))?;
+            let default_status = match rule.action {
+                Action::Respond => 200,
+                _ => 403,
+            };
+            let status = StatusCode::from_u16(rule.status.unwrap_or(default_status))
+                .map_err(|e| format!("invalid status for `{}`: {}", rule.pattern, e))?;
+            let (content_type, body) = match (&rule.body, &rule.file) {
+                (Some(_), Some(_)) => {
+                    return Err(format!(
+                        "user_agent rule `{}` sets both `body` and `file`",
+                        rule.pattern
+                    ))
+                }
+                (_, Some(file)) => {
+                    let body = std::fs::read(file)
+                        .map_err(|e| format!("failed to read {}: {}", file.display(), e))?;
+                    let mime = mime_guess::from_path(file).first_or_octet_stream();
+                    let mime = if mime.type_() == mime_guess::mime::TEXT {
+                        format!("{}; charset=utf-8", mime)
+                    } else {
+                        mime.to_string()
+                    };
+                    let content_type = HeaderValue::from_str(&mime)
+                        .map_err(|e| format!("invalid content type {}: {}", mime, e))?;
+                    (content_type, body)
+                }
+                (body, None) => (
+                    HeaderValue::from_static("text/plain; charset=utf-8"),
+                    body.clone()
+                        .unwrap_or_else(|| {
+                            status.canonical_reason().unwrap_or_default().to_string()
+                        })
+                        .into_bytes(),
+                ),
+            };
+            compiled.push(Rule {
+                pattern,
+                action: rule.action,
+                status,
+                content_type,
+                body,
+            });
+        }
+        Ok(Filter { rules: compiled })
+    }
+}
+
+pub async fn check(State(filter): State<Arc<Filter>>, req: Request, next: Next) -> Response {
+    let agent = req
+        .headers()
+        .get(header::USER_AGENT)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .unwrap_or_default();
+    let Some(rule) = filter
+        .rules
+        .iter()
+        .find(|rule| rule.pattern.is_match(&agent))
+    else {
+        return next.run(req).await;
+    };
+    if rule.action == Action::Allow {
+        return next.run(req).await;
+    }
+    if rule.action == Action::Deny {
+        debug!("Blocked user agent {:?} for {}", agent, req.uri());
+        METRICS.record_ua_blocked();
+    }
+    let mut response = Response::new(Body::from(rule.body.clone()));
+    *response.status_mut() = rule.status;
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, rule.content_type.clone());
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    vary::add(headers, "User-Agent");
+    response
+}
@@ -0,0 +1,67 @@
+// Vary 响应头：各处按请求头协商内容时用 add 记下影响响应的请求头（预压缩版本按 Accept-Encoding、
+// 多语言按 Accept-Language、错误页与图片格式按 Accept、CORS 按 Origin 等），
+// 最外层的 merge 把多个 Vary 合并为一个，去重并统一写法；出现 "*" 时只保留 "*"
+use axum::extract::Request;
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+
+// value 可以是逗号分隔的多个请求头
+pub fn add(headers: &mut HeaderMap, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.append(header::VARY, value);
+    }
+}
+
+// "accept-encoding" -> "Accept-Encoding"
+fn canonical(name: &str) -> String {
+    name.split('-')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => {
+                    first.to_ascii_uppercase().to_string() + &chars.as_str().to_ascii_lowercase()
+                }
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn combine(headers: &mut HeaderMap) {
+    if !headers.contains_key(header::VARY) {
+        return;
+    }
+    let mut names: Vec<String> = Vec::new();
+    for value in headers.get_all(header::VARY) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        for name in value.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let name = if name == "*" {
+                name.to_string()
+            } else {
+                canonical(name)
+            };
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    let combined = if names.iter().any(|n| n == "*") {
+        "*".to_string()
+    } else {
+        names.join(", ")
+    };
+    headers.remove(header::VARY);
+    if let Ok(value) = HeaderValue::from_str(&combined) {
+        headers.insert(header::VARY, value);
+    }
+}
+
+pub async fn merge(req: Request, next: Next) -> Response {
+    let mut response = next.run(req).await;
+    combine(response.headers_mut());
+    response
+}
@@ -0,0 +1,30 @@
+// 版本信息（version_endpoint = true）：/__version 以 JSON 返回版本号、git 提交、构建时间与启用的 feature，
+// 便于批量确认每个实例运行的构建；构建信息由 build.rs 在编译时写入
+use crate::error_pages;
+use axum::http::header::{self, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+use std::time::{Duration, UNIX_EPOCH};
+
+pub const VERSION_PATH: &str = "/__version";
+
+pub async fn handler() -> Response {
+    let epoch = env!("SONICWAVE_BUILD_EPOCH").parse().unwrap_or(0);
+    let features: Vec<&str> = env!("SONICWAVE_FEATURES")
+        .split(',')
+        .filter(|feature| !feature.is_empty())
+        .collect();
+    let mut response = Json(json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "git_hash": env!("SONICWAVE_GIT_HASH"),
+        "build_time": error_pages::rfc3339(UNIX_EPOCH + Duration::from_secs(epoch)),
+        "features": features,
+    }))
+    .into_response();
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
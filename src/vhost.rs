@@ -0,0 +1,119 @@
+// 基于名称的虚拟主机：按 Host 头把请求分发到不同的站点目录
+use crate::site::{self, SiteOptions, SiteOverrides};
+use axum::extract::Request;
+use axum::http::header;
+use axum::Router;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct VhostConfig {
+    // 主机名，支持 "*.example.com" 通配子域名
+    pub host: String,
+    // 同一站点的其他主机名
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    // 站点目录
+    pub dir: String,
+    // HTTPS 证书链与私钥（PEM），按 SNI 选择；未设置时在 [tls] cert_dir 中查找
+    #[serde(default)]
+    pub tls_cert: Option<String>,
+    #[serde(default)]
+    pub tls_key: Option<String>,
+    #[serde(flatten)]
+    pub site: SiteOverrides,
+    // 作为未匹配主机的默认站点（替代顶层 static_dir）
+    #[serde(default)]
+    pub default: bool,
+}
+
+struct Vhosts {
+    exact: HashMap<String, Router>,
+    // (".example.com", 站点)，按后缀长度降序，最具体的优先
+    wildcard: Vec<(String, Router)>,
+    default: Router,
+}
+
+impl Vhosts {
+    fn select(&self, host: Option<&str>) -> Router {
+        let Some(host) = host else {
+            return self.default.clone();
+        };
+        if let Some(router) = self.exact.get(host) {
+            return router.clone();
+        }
+        self.wildcard
+            .iter()
+            .find(|(suffix, _)| host.ends_with(suffix.as_str()))
+            .map(|(_, router)| router.clone())
+            .unwrap_or_else(|| self.default.clone())
+    }
+}
+
+pub fn router(
+    vhosts: &[VhostConfig],
+    defaults: &SiteOptions,
+    root: Router,
+) -> Result<Router, String> {
+    let mut exact = HashMap::new();
+    let mut wildcard = Vec::new();
+    let mut default = None;
+
+    for vhost in vhosts {
+        let options = defaults
+            .with_overrides(&vhost.site)
+            .map_err(|e| format!("Invalid vhost {}: {}", vhost.host, e))?;
+        let site = site::router(&vhost.dir, options)
+            .map_err(|e| format!("Invalid vhost {}: {}", vhost.host, e))?;
+
+        for name in std::iter::once(&vhost.host).chain(&vhost.aliases) {
+            let name = name.trim().to_ascii_lowercase();
+            if let Some(suffix) = name.strip_prefix('*') {
+                if !suffix.starts_with('.') {
+                    return Err(format!("invalid wildcard vhost `{}`", name));
+                }
+                wildcard.push((suffix.to_string(), site.clone()));
+            } else if exact.insert(name.clone(), site.clone()).is_some() {
+                return Err(format!("duplicate vhost `{}`", name));
+            }
+        }
+        if vhost.default {
+            if default.is_some() {
+                return Err("more than one vhost is marked as default".to_string());
+            }
+            default = Some(site);
+        }
+        tracing::info!("Vhost: {} -> {}", vhost.host, vhost.dir);
+    }
+    wildcard.sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+
+    let vhosts = Arc::new(Vhosts {
+        exact,
+        wildcard,
+        default: default.unwrap_or(root),
+    });
+    Ok(
+        Router::new().fallback_service(tower::service_fn(move |req: Request| {
+            let router = vhosts.select(request_host(&req).as_deref());
+            router.oneshot(req)
+        })),
+    )
+}
+
+// Host 头（HTTP/2 时为 URI authority），去掉端口并转为小写
+pub fn request_host(req: &Request) -> Option<String> {
+    let host = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| req.uri().authority().map(|a| a.as_str()))?;
+    let host = match host.strip_prefix('[') {
+        // IPv6 字面量 "[::1]:8089"
+        Some(rest) => rest.split(']').next().unwrap_or(rest),
+        None => host.split(':').next().unwrap_or(host),
+    };
+    Some(host.trim_end_matches('.').to_ascii_lowercase())
+}
@@ -0,0 +1,360 @@
+// 波形峰值（[waveform]）：GET /music/a.mp3?waveform=json（或 dat）首次请求时解码音频，
+// 生成 audiowaveform 格式的峰值数据（8 位，单声道）并缓存在磁盘上，播放器无需在客户端解码
+use crate::index::etag_matches;
+use crate::site::Resolver;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::http::header::{self, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as DecodeError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct WaveformConfig {
+    // 峰值数据的缓存目录
+    #[serde(default = "default_cache_dir")]
+    pub cache_dir: PathBuf,
+    // 未指定 points 时的峰值点数
+    #[serde(default = "default_points")]
+    pub points: usize,
+    // points 的上限，超出返回 400
+    #[serde(default = "default_max_points")]
+    pub max_points: usize,
+}
+
+fn default_cache_dir() -> PathBuf {
+    std::env::temp_dir().join("sonic-wave-waveforms")
+}
+
+fn default_points() -> usize {
+    1000
+}
+
+fn default_max_points() -> usize {
+    20_000
+}
+
+// 先按固定的小块求峰值，再合并为请求的点数
+const BLOCK_FRAMES: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Dat,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Dat => "dat",
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Dat => "application/octet-stream",
+        }
+    }
+}
+
+pub struct Waveform {
+    config: WaveformConfig,
+}
+
+struct Peaks {
+    sample_rate: u32,
+    samples_per_pixel: usize,
+    // 每个点的最小值与最大值，范围 -128..=127
+    data: Vec<(i8, i8)>,
+}
+
+impl Waveform {
+    pub fn new(config: &WaveformConfig) -> Result<Self, String> {
+        std::fs::create_dir_all(&config.cache_dir).map_err(|e| {
+            format!(
+                "failed to create waveform cache {}: {}",
+                config.cache_dir.display(),
+                e
+            )
+        })?;
+        if config.points == 0 || config.points > config.max_points {
+            return Err("waveform points must be between 1 and max_points".to_string());
+        }
+        Ok(Waveform {
+            config: config.clone(),
+        })
+    }
+
+    // ?waveform=json&points=800；没有 waveform 参数时返回 Ok(None)
+    fn parse(&self, query: Option<&str>) -> Result<Option<(Format, usize)>, &'static str> {
+        let mut format = None;
+        let mut points = self.config.points;
+        for pair in query.unwrap_or_default().split('&') {
+            match pair.split_once('=') {
+                Some(("waveform", "json")) => format = Some(Format::Json),
+                Some(("waveform", "dat")) => format = Some(Format::Dat),
+                Some(("waveform", _)) => return Err("waveform must be json or dat"),
+                Some(("points", value)) => points = value.parse().map_err(|_| "invalid points")?,
+                _ => {}
+            }
+        }
+        let Some(format) = format else {
+            return Ok(None);
+        };
+        if points == 0 || points > self.config.max_points {
+            return Err("points is out of range");
+        }
+        Ok(Some((format, points)))
+    }
+}
+
+fn to_i8(sample: f32) -> i8 {
+    (sample.clamp(-1.0, 1.0) * 127.0).round() as i8
+}
+
+// 解码整个文件，多声道取各声道的最小 / 最大值
+fn decode(path: &Path, points: usize) -> Result<Peaks, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| e.to_string())?;
+    let mut reader = probed.format;
+    let track = reader
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("no audio track")?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(0);
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| e.to_string())?;
+
+    let mut blocks: Vec<(f32, f32)> = Vec::new();
+    let mut current = (0.0f32, 0.0f32);
+    let mut frames = 0;
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(DecodeError::ResetRequired) => break,
+            Err(e) => return Err(e.to_string()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // 损坏的帧跳过
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        let spec = *decoded.spec();
+        sample_rate = spec.rate;
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        for frame in buffer.samples().chunks(channels) {
+            for sample in frame {
+                current.0 = current.0.min(*sample);
+                current.1 = current.1.max(*sample);
+            }
+            frames += 1;
+            if frames == BLOCK_FRAMES {
+                blocks.push(current);
+                current = (0.0, 0.0);
+                frames = 0;
+            }
+        }
+    }
+    if frames > 0 {
+        blocks.push(current);
+    }
+    let group = blocks.len().div_ceil(points).max(1);
+    let data = blocks
+        .chunks(group)
+        .map(|chunk| {
+            let min = chunk.iter().map(|b| b.0).fold(0.0, f32::min);
+            let max = chunk.iter().map(|b| b.1).fold(0.0, f32::max);
+            (to_i8(min), to_i8(max))
+        })
+        .collect();
+    Ok(Peaks {
+        sample_rate,
+        samples_per_pixel: group * BLOCK_FRAMES,
+        data,
+    })
+}
+
+// audiowaveform 的 JSON（version 2）与二进制 .dat 格式
+fn encode(peaks: &Peaks, format: Format) -> Vec<u8> {
+    match format {
+        Format::Json => {
+            let data: Vec<i8> = peaks
+                .data
+                .iter()
+                .flat_map(|(min, max)| [*min, *max])
+                .collect();
+            serde_json::json!({
+                "version": 2,
+                "channels": 1,
+                "sample_rate": peaks.sample_rate,
+                "samples_per_pixel": peaks.samples_per_pixel,
+                "bits": 8,
+                "length": peaks.data.len(),
+                "data": data,
+            })
+            .to_string()
+            .into_bytes()
+        }
+        Format::Dat => {
+            let mut output = Vec::with_capacity(24 + peaks.data.len() * 2);
+            output.extend_from_slice(&2i32.to_le_bytes());
+            // flags：bit 0 为 1 表示 8 位数据
+            output.extend_from_slice(&1u32.to_le_bytes());
+            output.extend_from_slice(&(peaks.sample_rate as i32).to_le_bytes());
+            output.extend_from_slice(&(peaks.samples_per_pixel as i32).to_le_bytes());
+            output.extend_from_slice(&(peaks.data.len() as u32).to_le_bytes());
+            output.extend_from_slice(&1i32.to_le_bytes());
+            for (min, max) in &peaks.data {
+                output.push(*min as u8);
+                output.push(*max as u8);
+            }
+            output
+        }
+    }
+}
+
+// 在 resolve 之后执行；只处理磁盘上的音频文件，缓存键包含文件的大小与修改时间，文件变化后重新生成
+pub async fn respond(
+    waveform: &Waveform,
+    resolver: &Resolver,
+    cache_control: &str,
+    req: Request,
+    next: Next,
+) -> Response {
+    let is_head = req.method() == Method::HEAD;
+    if !(is_head || req.method() == Method::GET) {
+        return next.run(req).await;
+    }
+    let path = req.uri().path().to_string();
+    if crate::audio::content_type(&path).is_none() {
+        return next.run(req).await;
+    }
+    let (format, points) = match waveform.parse(req.uri().query()) {
+        Ok(Some(options)) => options,
+        Ok(None) => return next.run(req).await,
+        Err(message) => return (StatusCode::BAD_REQUEST, message).into_response(),
+    };
+    let Some(fs_path) = resolver.include_path(&path).await else {
+        return next.run(req).await;
+    };
+    let Ok(meta) = tokio::fs::metadata(&fs_path).await else {
+        return next.run(req).await;
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(fs_path.to_string_lossy().as_bytes());
+    hasher.update(meta.len().to_le_bytes());
+    hasher.update(format!("{:?}", meta.modified().ok()).as_bytes());
+    hasher.update(points.to_le_bytes());
+    hasher.update(format.extension().as_bytes());
+    let key: String = hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    let etag = HeaderValue::from_str(&format!("W/\"wave-{}\"", &key[..16])).unwrap();
+    let not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|tags| tags.to_str().ok())
+        .is_some_and(|tags| etag_matches(tags, &etag));
+
+    let cached = waveform
+        .config
+        .cache_dir
+        .join(format!("{}.{}", key, format.extension()));
+    let output = match tokio::fs::read(&cached).await {
+        Ok(output) => output,
+        Err(_) if not_modified => Vec::new(),
+        Err(_) => {
+            let source = fs_path.clone();
+            let decoded = tokio::task::spawn_blocking(move || {
+                decode(&source, points).map(|peaks| encode(&peaks, format))
+            })
+            .await;
+            let output = match decoded {
+                Ok(Ok(output)) => output,
+                Ok(Err(e)) => {
+                    warn!("Failed to generate waveform of {}: {}", path, e);
+                    return (StatusCode::UNPROCESSABLE_ENTITY, "unsupported audio file")
+                        .into_response();
+                }
+                Err(e) => {
+                    warn!("Failed to generate waveform of {}: {}", path, e);
+                    return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                }
+            };
+            // 先写临时文件再改名，并发请求不会读到不完整的缓存
+            let temp =
+                cached.with_extension(format!("{}.tmp{}", format.extension(), std::process::id()));
+            let written = tokio::fs::write(&temp, &output).await;
+            if let Err(e) = match written {
+                Ok(()) => tokio::fs::rename(&temp, &cached).await,
+                Err(e) => Err(e),
+            } {
+                warn!("Failed to cache waveform of {}: {}", path, e);
+                let _ = tokio::fs::remove_file(&temp).await;
+            }
+            output
+        }
+    };
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let len = output.len();
+        let mut response = Response::new(if is_head {
+            Body::empty()
+        } else {
+            Body::from(output)
+        });
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(format.content_type()),
+        );
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+        response
+    };
+    let headers = response.headers_mut();
+    headers.insert(header::ETAG, etag);
+    if let Ok(value) = HeaderValue::from_str(cache_control) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+    response
+}
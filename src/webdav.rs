@@ -0,0 +1,152 @@
+// 只读 WebDAV（webdav = true）：响应 OPTIONS 与 PROPFIND（Depth 0 / 1），
+// macOS Finder、Windows 资源管理器等可以将站点目录挂载为只读网络驱动器；GET 仍由 ServeDir 处理
+use axum::body::Body;
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::{Response, StatusCode};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use std::time::SystemTime;
+
+pub const ALLOW: &str = "OPTIONS, GET, HEAD, PROPFIND";
+
+// href 中路径段需要编码的字符
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'\\')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+pub fn encode_segment(name: &str) -> String {
+    utf8_percent_encode(name, SEGMENT).to_string()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    Zero,
+    One,
+}
+
+// 不支持 Depth: infinity（RFC 4918 9.1 允许拒绝）；未提供时按 1 处理，常见客户端都会显式发送
+pub fn depth(headers: &HeaderMap) -> Option<Depth> {
+    match headers
+        .get("depth")
+        .map(|v| v.to_str().unwrap_or("").trim())
+    {
+        Some("0") => Some(Depth::Zero),
+        Some("1") | None => Some(Depth::One),
+        _ => None,
+    }
+}
+
+pub fn infinite_depth() -> Response<Body> {
+    xml_response(
+        StatusCode::FORBIDDEN,
+        "<D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>".to_string(),
+    )
+}
+
+pub struct DavEntry {
+    // 已编码的完整 URL 路径，目录以 "/" 结尾
+    pub href: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl DavEntry {
+    // 目录 href 下的子项
+    pub fn child(parent: &str, name: &str, is_dir: bool, meta: &std::fs::Metadata) -> Self {
+        let mut href = parent.to_string();
+        if !href.ends_with('/') {
+            href.push('/');
+        }
+        href.push_str(&encode_segment(name));
+        if is_dir {
+            href.push('/');
+        }
+        DavEntry {
+            href,
+            name: name.to_string(),
+            is_dir,
+            len: meta.len(),
+            modified: meta.modified().ok(),
+        }
+    }
+}
+
+pub fn options() -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    let headers = response.headers_mut();
+    headers.insert(header::ALLOW, HeaderValue::from_static(ALLOW));
+    headers.insert("dav", HeaderValue::from_static("1"));
+    // Windows 的 WebDAV 客户端依据该头识别服务器
+    headers.insert("ms-author-via", HeaderValue::from_static("DAV"));
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("0"));
+    response
+}
+
+// 207 Multi-Status，每个条目返回全部支持的属性（忽略请求体中的 prop 列表）
+pub fn multistatus(entries: &[DavEntry]) -> Response<Body> {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    for entry in entries {
+        xml.push_str("<D:response><D:href>");
+        xml.push_str(&xml_escape(&entry.href));
+        xml.push_str("</D:href><D:propstat><D:prop>");
+        xml.push_str(&format!(
+            "<D:displayname>{}</D:displayname>",
+            xml_escape(&entry.name)
+        ));
+        if entry.is_dir {
+            xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            let mime = mime_guess::from_path(&entry.name).first_or_octet_stream();
+            xml.push_str(&format!(
+                "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength>\
+                 <D:getcontenttype>{}</D:getcontenttype>",
+                entry.len,
+                xml_escape(mime.as_ref())
+            ));
+        }
+        if let Some(modified) = entry.modified {
+            xml.push_str(&format!(
+                "<D:getlastmodified>{}</D:getlastmodified><D:creationdate>{}</D:creationdate>",
+                httpdate::fmt_http_date(modified),
+                crate::error_pages::rfc3339(modified)
+            ));
+        }
+        xml.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+    }
+    xml.push_str("</D:multistatus>\n");
+    xml_response(StatusCode::MULTI_STATUS, xml)
+}
+
+fn xml_response(status: StatusCode, xml: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(xml));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/xml; charset=utf-8"),
+    );
+    response
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
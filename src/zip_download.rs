@@ -0,0 +1,334 @@
+// 目录打包下载（[zip_download]）：GET /dir/?download=zip 边读文件边生成 zip 流，不在磁盘上暂存归档；
+// 条目使用数据描述符（大小与 CRC 写在数据之后），超过 4 GiB 的条目与归档自动使用 ZIP64
+use axum::body::{Body, Bytes};
+use flate2::write::DeflateEncoder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compression {
+    // 不压缩，适合图片、音视频等已压缩的资源
+    Store,
+    #[default]
+    Deflate,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+pub struct ZipDownloadConfig {
+    #[serde(default)]
+    pub compression: Compression,
+    // 单个归档的文件数上限
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+    // 单个归档的未压缩总大小上限（字节），未设置时不限制
+    #[serde(default)]
+    pub max_size: Option<u64>,
+}
+
+fn default_max_files() -> usize {
+    10_000
+}
+
+// 查询参数中是否有 download=zip
+pub fn requested(query: Option<&str>) -> bool {
+    query.is_some_and(|query| query.split('&').any(|pair| pair == "download=zip"))
+}
+
+pub struct ZipEntry {
+    // 归档内的相对路径，"/" 分隔
+    pub name: String,
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+}
+
+const CHUNK: usize = 64 * 1024;
+const U32_MAX: u64 = 0xFFFF_FFFF;
+
+// 在阻塞线程中写入 zip，按块发送给响应体；客户端断开后写入失败，打包随之中止
+struct ChannelWriter {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    buf: Vec<u8>,
+    written: u64,
+}
+
+impl ChannelWriter {
+    fn send(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK)));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        self.written += data.len() as u64;
+        if self.buf.len() >= CHUNK {
+            self.send()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send()
+    }
+}
+
+// 统计压缩后写出的字节数
+struct Counter<'a> {
+    inner: &'a mut ChannelWriter,
+    count: u64,
+}
+
+impl Write for Counter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(data)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct CentralEntry {
+    name: String,
+    method: u16,
+    dos_time: u16,
+    dos_date: u16,
+    crc: u32,
+    compressed: u64,
+    size: u64,
+    offset: u64,
+}
+
+pub fn stream(entries: Vec<ZipEntry>, compression: Compression) -> Body {
+    let (tx, mut rx) = mpsc::channel::<io::Result<Bytes>>(8);
+    tokio::task::spawn_blocking(move || {
+        let mut out = ChannelWriter {
+            tx: tx.clone(),
+            buf: Vec::with_capacity(CHUNK),
+            written: 0,
+        };
+        if let Err(e) = write_zip(&mut out, &entries, compression) {
+            if e.kind() != io::ErrorKind::BrokenPipe {
+                warn!("ZIP download aborted: {}", e);
+                let _ = tx.blocking_send(Err(e));
+            }
+        }
+    });
+    Body::from_stream(futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx)))
+}
+
+fn write_zip(
+    out: &mut ChannelWriter,
+    entries: &[ZipEntry],
+    compression: Compression,
+) -> io::Result<()> {
+    let mut central = Vec::with_capacity(entries.len());
+    for entry in entries {
+        // 打包期间被删除的文件跳过
+        let mut file = match std::fs::File::open(&entry.path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Skipping {} in ZIP download: {}", entry.path.display(), e);
+                continue;
+            }
+        };
+        let size_hint = file.metadata().map(|m| m.len()).unwrap_or(0);
+        let zip64 = size_hint >= U32_MAX;
+        let (dos_time, dos_date) = dos_datetime(entry.modified);
+        let method: u16 = match compression {
+            Compression::Store => 0,
+            Compression::Deflate => 8,
+        };
+        let offset = out.written;
+
+        // 本地文件头，CRC 与大小为 0，由数据描述符给出
+        let mut header = Vec::with_capacity(30 + entry.name.len() + 20);
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&(if zip64 { 45u16 } else { 20u16 }).to_le_bytes());
+        // bit 3：数据描述符；bit 11：文件名为 UTF-8
+        header.extend_from_slice(&(0x0008u16 | 0x0800).to_le_bytes());
+        header.extend_from_slice(&method.to_le_bytes());
+        header.extend_from_slice(&dos_time.to_le_bytes());
+        header.extend_from_slice(&dos_date.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        let placeholder = if zip64 { 0xFFFF_FFFFu32 } else { 0 };
+        header.extend_from_slice(&placeholder.to_le_bytes());
+        header.extend_from_slice(&placeholder.to_le_bytes());
+        header.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&(if zip64 { 20u16 } else { 0 }).to_le_bytes());
+        header.extend_from_slice(entry.name.as_bytes());
+        if zip64 {
+            header.extend_from_slice(&0x0001u16.to_le_bytes());
+            header.extend_from_slice(&16u16.to_le_bytes());
+            header.extend_from_slice(&[0; 16]);
+        }
+        out.write_all(&header)?;
+
+        let mut hasher = crc32fast::Hasher::new();
+        let mut size = 0u64;
+        let mut buf = vec![0; CHUNK];
+        let compressed = match compression {
+            Compression::Store => {
+                loop {
+                    let n = file.read(&mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    hasher.update(&buf[..n]);
+                    size += n as u64;
+                    out.write_all(&buf[..n])?;
+                }
+                size
+            }
+            Compression::Deflate => {
+                let mut encoder = DeflateEncoder::new(
+                    Counter {
+                        inner: out,
+                        count: 0,
+                    },
+                    flate2::Compression::default(),
+                );
+                loop {
+                    let n = file.read(&mut buf)?;
+                    if n == 0 {
+                        break;
+                    }
+                    hasher.update(&buf[..n]);
+                    size += n as u64;
+                    encoder.write_all(&buf[..n])?;
+                }
+                encoder.finish()?.count
+            }
+        };
+        let crc = hasher.finalize();
+
+        // 数据描述符；本地头带 ZIP64 扩展字段时大小为 8 字节
+        let mut descriptor = Vec::with_capacity(24);
+        descriptor.extend_from_slice(&0x08074b50u32.to_le_bytes());
+        descriptor.extend_from_slice(&crc.to_le_bytes());
+        if zip64 {
+            descriptor.extend_from_slice(&compressed.to_le_bytes());
+            descriptor.extend_from_slice(&size.to_le_bytes());
+        } else {
+            descriptor.extend_from_slice(&(compressed as u32).to_le_bytes());
+            descriptor.extend_from_slice(&(size as u32).to_le_bytes());
+        }
+        out.write_all(&descriptor)?;
+
+        central.push(CentralEntry {
+            name: entry.name.clone(),
+            method,
+            dos_time,
+            dos_date,
+            crc,
+            compressed,
+            size,
+            offset,
+        });
+    }
+
+    // 中央目录
+    let cd_offset = out.written;
+    for entry in &central {
+        // ZIP64 扩展字段只包含溢出的字段，顺序为原始大小、压缩后大小、偏移
+        let mut extra = Vec::new();
+        for value in [entry.size, entry.compressed, entry.offset] {
+            if value >= U32_MAX {
+                extra.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let clamp = |value: u64| value.min(U32_MAX) as u32;
+        let mut record = Vec::with_capacity(46 + entry.name.len() + 4 + extra.len());
+        record.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        // 创建者：Unix，版本 4.5
+        record.extend_from_slice(&(0x0300u16 | 45).to_le_bytes());
+        record.extend_from_slice(&(if extra.is_empty() { 20u16 } else { 45 }).to_le_bytes());
+        record.extend_from_slice(&(0x0008u16 | 0x0800).to_le_bytes());
+        record.extend_from_slice(&entry.method.to_le_bytes());
+        record.extend_from_slice(&entry.dos_time.to_le_bytes());
+        record.extend_from_slice(&entry.dos_date.to_le_bytes());
+        record.extend_from_slice(&entry.crc.to_le_bytes());
+        record.extend_from_slice(&clamp(entry.compressed).to_le_bytes());
+        record.extend_from_slice(&clamp(entry.size).to_le_bytes());
+        record.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+        let extra_len = if extra.is_empty() { 0 } else { 4 + extra.len() };
+        record.extend_from_slice(&(extra_len as u16).to_le_bytes());
+        record.extend_from_slice(&0u16.to_le_bytes()); // 注释长度
+        record.extend_from_slice(&0u16.to_le_bytes()); // 磁盘号
+        record.extend_from_slice(&0u16.to_le_bytes()); // 内部属性
+        record.extend_from_slice(&(0o100644u32 << 16).to_le_bytes()); // 外部属性：普通文件 0644
+        record.extend_from_slice(&clamp(entry.offset).to_le_bytes());
+        record.extend_from_slice(entry.name.as_bytes());
+        if !extra.is_empty() {
+            record.extend_from_slice(&0x0001u16.to_le_bytes());
+            record.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+            record.extend_from_slice(&extra);
+        }
+        out.write_all(&record)?;
+    }
+    let cd_size = out.written - cd_offset;
+    let count = central.len() as u64;
+
+    let mut end = Vec::with_capacity(98);
+    if count >= 0xFFFF || cd_size >= U32_MAX || cd_offset >= U32_MAX {
+        // ZIP64 中央目录结束记录与定位符
+        let zip64_end = out.written;
+        end.extend_from_slice(&0x06064b50u32.to_le_bytes());
+        end.extend_from_slice(&44u64.to_le_bytes());
+        end.extend_from_slice(&(0x0300u16 | 45).to_le_bytes());
+        end.extend_from_slice(&45u16.to_le_bytes());
+        end.extend_from_slice(&0u32.to_le_bytes());
+        end.extend_from_slice(&0u32.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&count.to_le_bytes());
+        end.extend_from_slice(&cd_size.to_le_bytes());
+        end.extend_from_slice(&cd_offset.to_le_bytes());
+        end.extend_from_slice(&0x07064b50u32.to_le_bytes());
+        end.extend_from_slice(&0u32.to_le_bytes());
+        end.extend_from_slice(&zip64_end.to_le_bytes());
+        end.extend_from_slice(&1u32.to_le_bytes());
+    }
+    end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes());
+    end.extend_from_slice(&(count.min(0xFFFF) as u16).to_le_bytes());
+    end.extend_from_slice(&(count.min(0xFFFF) as u16).to_le_bytes());
+    end.extend_from_slice(&(cd_size.min(U32_MAX) as u32).to_le_bytes());
+    end.extend_from_slice(&(cd_offset.min(U32_MAX) as u32).to_le_bytes());
+    end.extend_from_slice(&0u16.to_le_bytes());
+    out.write_all(&end)?;
+    out.flush()
+}
+
+// MS-DOS 时间与日期（UTC），早于 1980 年时取 1980-01-01
+fn dos_datetime(modified: Option<SystemTime>) -> (u16, u16) {
+    let Some(modified) = modified else {
+        return (0, (1 << 5) | 1);
+    };
+    // "2026-10-14T07:00:32Z"
+    let text = crate::error_pages::rfc3339(modified);
+    let field = |range: std::ops::Range<usize>| -> u16 {
+        text.get(range).and_then(|v| v.parse().ok()).unwrap_or(0)
+    };
+    let year = field(0..4);
+    if year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = (field(11..13) << 11) | (field(14..16) << 5) | (field(17..19) / 2);
+    let date = ((year.min(2107) - 1980) << 9) | (field(5..7) << 5) | field(8..10);
+    (time, date)
+}